@@ -0,0 +1,80 @@
+// Times the interpolation of the recovery of a secret for large
+// thresholds: the Lagrange interpolation with an increasing number
+// of threads, and eval, which uses product and remainder trees for
+// 512 or more evaluations.
+//
+// cargo bench --features parallel
+
+use std::time::{Duration, Instant};
+
+use shared_secrets::crypto::shamir::default_prime;
+use shared_secrets::math::random::Rng;
+use shared_secrets::math::{InterpolationPolynomial, ModInteger};
+
+// Thresholds measured with both algorithms
+const THRESHOLDS: &[usize] = &[128, 256, 512, 1024];
+
+// Thresholds measured only with eval, too large for Lagrange
+const LARGE_THRESHOLDS: &[usize] = &[2048, 4096];
+
+// Numbers of threads measured
+const THREADS: &[usize] = &[1, 2, 4, 8];
+
+fn main() {
+    let prime = default_prime();
+    let mut rng = Rng::new().unwrap();
+    let mut polynomial = |k: usize| {
+        let evaluations = (1..=k as u64)
+            .map(|x| {
+                (
+                    ModInteger::zero(prime) + x,
+                    ModInteger::random(prime, &mut rng),
+                )
+            })
+            .collect();
+        InterpolationPolynomial::new(evaluations).unwrap()
+    };
+    for &k in THRESHOLDS {
+        let polynomial = polynomial(k);
+        let mut single = None;
+        for &threads in THREADS {
+            let elapsed = time(|| {
+                polynomial.eval_with_threads(ModInteger::zero(prime), threads);
+            });
+            let baseline = *single.get_or_insert(elapsed);
+            println!(
+                "k = {:5}, lagrange, threads = {}: {:9.2} ms ({:.2}x)",
+                k,
+                threads,
+                elapsed.as_secs_f64() * 1000.0,
+                baseline.as_secs_f64() / elapsed.as_secs_f64()
+            );
+        }
+        let elapsed = time(|| {
+            polynomial.eval(ModInteger::zero(prime));
+        });
+        println!(
+            "k = {:5}, eval:                {:9.2} ms",
+            k,
+            elapsed.as_secs_f64() * 1000.0
+        );
+    }
+    for &k in LARGE_THRESHOLDS {
+        let polynomial = polynomial(k);
+        let elapsed = time(|| {
+            polynomial.eval(ModInteger::zero(prime));
+        });
+        println!(
+            "k = {:5}, eval:                {:9.2} ms",
+            k,
+            elapsed.as_secs_f64() * 1000.0
+        );
+    }
+}
+
+// returns how long the function takes to run
+fn time(f: impl FnOnce()) -> Duration {
+    let start = Instant::now();
+    f();
+    start.elapsed()
+}
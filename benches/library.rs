@@ -0,0 +1,153 @@
+// Times the whole life of an encrypted file through the public run
+// path of the library, as the command line does it: encrypting a
+// file and splitting its key into n shares with threshold k, which
+// writes the encrypted file and the shares file, and recovering the
+// key from the shares and decrypting the file. The files are written
+// in a temporary directory, removed at the end, for inputs from 1 KiB
+// to 1 GiB and schemes from 2 of 3 to 128 of 255 shares.
+//
+// cargo bench --bench library
+// cargo bench --bench library -- 64M     (inputs up to 64 MiB)
+
+use std::env;
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::process;
+use std::time::{Duration, Instant};
+
+use shared_secrets::{Config, DecryptConfig, EncryptConfig};
+
+// Sizes of the inputs measured
+const SIZES: &[u64] = &[1 << 10, 1 << 20, 16 << 20, 256 << 20, 1 << 30];
+
+// Schemes measured, as (k, n)
+const SCHEMES: &[(usize, usize)] = &[(2, 3), (3, 5), (8, 15), (32, 63), (128, 255)];
+
+// Password of the files
+const PASSWORD: &str = "benchmark password";
+
+fn main() {
+    let max_size = match env::args().skip(1).find(|arg| !arg.starts_with('-')) {
+        Some(arg) => parse_size(&arg).unwrap_or_else(|| {
+            eprintln!("invalid size '{}', use bytes or a K, M or G suffix", arg);
+            process::exit(1);
+        }),
+        None => u64::MAX,
+    };
+    let dir = env::temp_dir().join(format!("shared_secrets-bench-{}", process::id()));
+    let result = run(&dir, max_size);
+    let _ = fs::remove_dir_all(&dir);
+    if let Err(e) = result {
+        eprintln!("Application error: {}", e);
+        process::exit(1);
+    }
+}
+
+// measures every input up to the maximum size with every scheme; the
+// files are decrypted into the current directory, so it is changed
+// to a directory of their own while measuring
+fn run(dir: &Path, max_size: u64) -> Result<(), Box<dyn Error>> {
+    fs::create_dir_all(dir.join("encrypted"))?;
+    fs::create_dir_all(dir.join("decrypted"))?;
+    let current_dir = env::current_dir()?;
+    env::set_current_dir(dir.join("decrypted"))?;
+    let result = SIZES
+        .iter()
+        .filter(|&&size| size <= max_size)
+        .try_for_each(|&size| {
+            let input = dir.join("input.bin");
+            write_input(&input, size)?;
+            SCHEMES
+                .iter()
+                .try_for_each(|&(k, n)| bench(dir, &input, size, k, n))
+        });
+    env::set_current_dir(current_dir)?;
+    result
+}
+
+// measures each step for an input and a scheme
+fn bench(dir: &Path, input: &Path, size: u64, k: usize, n: usize) -> Result<(), Box<dyn Error>> {
+    let encrypted_dir = dir.join("encrypted");
+    let encrypted_path = encrypted_dir.join("input.bin.aes");
+    let shares_path = encrypted_dir.join("input.bin.frg");
+    let output_path = dir.join("decrypted").join("input.bin");
+
+    let start = Instant::now();
+    shared_secrets::run(Config::Encrypt(EncryptConfig::new(
+        input,
+        &encrypted_dir.to_string_lossy(),
+        "input.bin",
+        PASSWORD.into(),
+        n,
+        k,
+    )))?;
+    let encrypt = start.elapsed();
+
+    let start = Instant::now();
+    shared_secrets::run(Config::Decrypt(DecryptConfig::new(
+        &encrypted_path.to_string_lossy(),
+        &shares_path.to_string_lossy(),
+    )))?;
+    let decrypt = start.elapsed();
+    assert_eq!(fs::metadata(&output_path)?.len(), size);
+
+    println!(
+        "size = {:>6}, k = {:3}, n = {:3}: split and encrypt {}, recover and decrypt {}",
+        format_size(size),
+        k,
+        n,
+        format_rate(encrypt, size),
+        format_rate(decrypt, size)
+    );
+    for path in [shares_path, encrypted_path, output_path] {
+        fs::remove_file(path)?;
+    }
+    Ok(())
+}
+
+// writes an input of the given size, the bytes 0 to 250 repeated
+fn write_input(path: &Path, size: u64) -> io::Result<()> {
+    let block: Vec<u8> = (0..1 << 16).map(|i: u32| (i % 251) as u8).collect();
+    let mut writer = BufWriter::new(File::create(path)?);
+    let mut written = 0;
+    while written < size {
+        let length = (size - written).min(block.len() as u64) as usize;
+        writer.write_all(&block[..length])?;
+        written += length as u64;
+    }
+    writer.flush()
+}
+
+// the size of a command line argument, in bytes or with a K, M or G suffix
+fn parse_size(arg: &str) -> Option<u64> {
+    let (number, shift) = match arg.chars().last()? {
+        'K' | 'k' => (&arg[..arg.len() - 1], 10),
+        'M' | 'm' => (&arg[..arg.len() - 1], 20),
+        'G' | 'g' => (&arg[..arg.len() - 1], 30),
+        _ => (arg, 0),
+    };
+    number.parse::<u64>().ok()?.checked_mul(1 << shift)
+}
+
+fn format_size(size: u64) -> String {
+    match size {
+        size if size >= 1 << 30 => format!("{}G", size >> 30),
+        size if size >= 1 << 20 => format!("{}M", size >> 20),
+        size => format!("{}K", size >> 10),
+    }
+}
+
+fn format_time(elapsed: Duration) -> String {
+    format!("{:8.2} ms", elapsed.as_secs_f64() * 1e3)
+}
+
+// the time of a step over the input and its throughput
+fn format_rate(elapsed: Duration, size: u64) -> String {
+    format!(
+        "{} ({:7.1} MiB/s)",
+        format_time(elapsed),
+        size as f64 / (1 << 20) as f64 / elapsed.as_secs_f64()
+    )
+}
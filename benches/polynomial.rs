@@ -0,0 +1,47 @@
+// Times the evaluation of polynomials of the 257-bit field, with
+// coefficients as when splitting a secret and from evaluations as
+// when recovering it, for degrees from 2 to 512, at a random point.
+//
+// cargo bench --bench polynomial
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+
+use shared_secrets::crypto::shamir::default_prime;
+use shared_secrets::math::random::Rng;
+use shared_secrets::math::{CoeffPolynomial, InterpolationPolynomial, ModInteger};
+
+// Degrees measured
+const DEGREES: &[usize] = &[2, 4, 8, 16, 32, 64, 128, 256, 512];
+
+fn evaluation(c: &mut Criterion) {
+    let prime = default_prime();
+    let mut rng = Rng::new().unwrap();
+    let mut group = c.benchmark_group("evaluation");
+    for &degree in DEGREES {
+        let mut coefficients: Vec<_> = (0..degree)
+            .map(|_| ModInteger::random(prime, &mut rng))
+            .collect();
+        coefficients.push(ModInteger::random_nonzero(prime, &mut rng));
+        let coefficients = CoeffPolynomial::new(coefficients);
+        let evaluations = (1..=degree as u64 + 1)
+            .map(|x| {
+                (
+                    ModInteger::zero(prime) + x,
+                    ModInteger::random(prime, &mut rng),
+                )
+            })
+            .collect();
+        let interpolation = InterpolationPolynomial::new(evaluations).unwrap();
+        let x = ModInteger::random(prime, &mut rng);
+        group.bench_with_input(BenchmarkId::new("coefficients", degree), &x, |b, x| {
+            b.iter(|| coefficients.eval(x.clone()))
+        });
+        group.bench_with_input(BenchmarkId::new("interpolation", degree), &x, |b, x| {
+            b.iter(|| interpolation.eval(x.clone()))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, evaluation);
+criterion_main!(benches);
@@ -0,0 +1,454 @@
+use std::error::Error;
+use std::fs;
+use std::io::{BufRead, Write};
+use std::path::Path;
+
+use hkdf::Hkdf;
+use sha2::Sha256;
+
+use crate::bech32;
+use crate::crypto::{Cipher, CipherSuite, SecretKey};
+use crate::encoding::{from_base64, to_base64};
+use crate::error::{ArgumentError, CorruptFileError};
+use crate::fragments;
+use crate::math::curve25519::{self, BASEPOINT};
+
+/// Name of the plugin, age runs it as the program age-plugin-NAME.
+pub const PLUGIN_NAME: &str = "sharedsecrets";
+
+// Human readable parts of the recipients and identities of the plugin
+const RECIPIENT_HRP: &str = "age1sharedsecrets";
+const IDENTITY_HRP: &str = "age-plugin-sharedsecrets-";
+
+// Type of the stanzas with the file keys wrapped by the plugin
+const STANZA_TYPE: &str = "sharedsecrets";
+
+// Contexts of the keys derived with HKDF
+const IDENTITY_INFO: &[u8] = b"age-plugin-sharedsecrets identity";
+const WRAP_INFO: &[u8] = b"age-plugin-sharedsecrets wrap";
+
+// Size in bytes of the file keys of age
+const FILE_KEY_SIZE: usize = 16;
+
+// Number of base64 characters in each line of the body of a stanza
+const BODY_COLUMNS: usize = 64;
+
+/// Returns the age recipient of a key split into shares, which
+/// anyone can encrypt files to.
+///
+/// # Parameters
+///
+/// - key: The key recovered from the shares.
+pub fn recipient(key: &SecretKey) -> String {
+    bech32::encode(RECIPIENT_HRP, &public_key(&identity_scalar(key)))
+}
+
+/// Returns the age identity of a shares file, which names the file
+/// the key is recovered from when age decrypts.
+///
+/// # Parameters
+///
+/// - shares_file: The file with the shares of the key.
+///
+/// # Errors
+///
+/// An io::Error if the absolute path of the file cannot be found.
+pub fn identity(shares_file: &str) -> Result<String, Box<dyn Error>> {
+    let path = fs::canonicalize(shares_file)?;
+    let path = path
+        .to_str()
+        .ok_or_else(|| ArgumentError("the path of the shares file is not UTF-8".into()))?;
+    Ok(bech32::encode(IDENTITY_HRP, path.as_bytes()).to_uppercase())
+}
+
+/// Runs one of the state machines of the age plugin protocol,
+/// exchanging stanzas with age through the input and the output.
+///
+/// In recipient-v1 the file keys are wrapped to the recipients
+/// given, and in identity-v1 they are unwrapped with the key
+/// recovered from the shares file named by each identity.
+///
+/// # Parameters
+///
+/// - state_machine: The value of the --age-plugin option.
+/// - input: The messages of age, usually the standard input.
+/// - output: The messages to age, usually the standard output.
+///
+/// # Errors
+///
+/// An ArgumentError if the state machine is unknown, a
+/// CorruptFileError if age sends a malformed stanza or an io::Error
+/// if the exchange fails.
+pub fn run_plugin(
+    state_machine: &str,
+    mut input: impl BufRead,
+    mut output: impl Write,
+) -> Result<(), Box<dyn Error>> {
+    match state_machine {
+        "recipient-v1" => wrap_file_keys(&mut input, &mut output),
+        "identity-v1" => unwrap_file_keys(&mut input, &mut output),
+        _ => Err(Box::new(ArgumentError(format!(
+            "unknown state machine '{}'",
+            state_machine
+        )))),
+    }
+}
+
+// A message of the protocol: a line with a tag and its arguments,
+// followed by a body in base64
+struct Stanza {
+    tag: String,
+    args: Vec<String>,
+    body: Vec<u8>,
+}
+
+impl Stanza {
+    fn new(tag: &str, args: &[&str], body: &[u8]) -> Self {
+        Stanza {
+            tag: tag.into(),
+            args: args.iter().map(|&arg| arg.into()).collect(),
+            body: body.to_vec(),
+        }
+    }
+
+    // reads the next stanza, None once the input ends
+    fn read(input: &mut impl BufRead) -> Result<Option<Self>, Box<dyn Error>> {
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let mut words = match line.trim_end_matches('\n').strip_prefix("-> ") {
+            Some(header) => header.split(' ').map(String::from),
+            None => {
+                return Err(Box::new(CorruptFileError(format!(
+                    "'{}' is not a stanza",
+                    line.trim_end()
+                ))))
+            }
+        };
+        let tag = words.next().unwrap_or_default();
+        let args = words.collect();
+        // the body ends with its first line shorter than a full line
+        let mut encoded = String::new();
+        loop {
+            line.clear();
+            if input.read_line(&mut line)? == 0 {
+                return Err(Box::new(CorruptFileError(format!(
+                    "the stanza {} has no body",
+                    tag
+                ))));
+            }
+            let body_line = line.trim_end_matches('\n');
+            encoded.push_str(body_line);
+            if body_line.len() < BODY_COLUMNS {
+                break;
+            }
+        }
+        let body = from_base64(&encoded).ok_or_else(|| {
+            CorruptFileError(format!("the body of the stanza {} is not base64", tag))
+        })?;
+        Ok(Some(Stanza { tag, args, body }))
+    }
+
+    // writes the stanza with its body in unpadded base64
+    fn write(&self, output: &mut impl Write) -> Result<(), Box<dyn Error>> {
+        let mut header = vec![self.tag.as_str()];
+        header.extend(self.args.iter().map(String::as_str));
+        writeln!(output, "-> {}", header.join(" "))?;
+        let encoded = to_base64(&self.body);
+        let encoded = encoded.trim_end_matches('=');
+        for line in encoded.as_bytes().chunks(BODY_COLUMNS) {
+            output.write_all(line)?;
+            output.write_all(b"\n")?;
+        }
+        if encoded.len() % BODY_COLUMNS == 0 {
+            output.write_all(b"\n")?;
+        }
+        Ok(())
+    }
+
+    // sends the stanza as a command and reads the response of age
+    fn send(
+        &self,
+        input: &mut impl BufRead,
+        output: &mut impl Write,
+    ) -> Result<Stanza, Box<dyn Error>> {
+        self.write(output)?;
+        output.flush()?;
+        Stanza::read(input)?
+            .ok_or_else(|| Box::new(CorruptFileError("age did not respond".into())) as _)
+    }
+}
+
+// the recipient-v1 state machine
+fn wrap_file_keys(input: &mut impl BufRead, output: &mut impl Write) -> Result<(), Box<dyn Error>> {
+    let mut recipients = Vec::new();
+    let mut file_keys = Vec::new();
+    let mut errors = Vec::new();
+    // recipients and identities are numbered separately
+    let (mut added_recipients, mut identities) = (0, 0);
+    while let Some(stanza) = Stanza::read(input)? {
+        match stanza.tag.as_str() {
+            "add-recipient" => {
+                match stanza.args.first().map(|r| parse_recipient(r)) {
+                    Some(Ok(recipient)) => recipients.push(recipient),
+                    _ => errors.push(error_stanza(
+                        "recipient",
+                        added_recipients,
+                        "invalid recipient",
+                    )),
+                }
+                added_recipients += 1;
+            }
+            "add-identity" => {
+                match stanza.args.first().map(|i| identity_key(i)) {
+                    Some(Ok(key)) => recipients.push(public_key(&identity_scalar(&key))),
+                    Some(Err(e)) => {
+                        errors.push(error_stanza("identity", identities, &e.to_string()))
+                    }
+                    None => errors.push(error_stanza("identity", identities, "invalid identity")),
+                }
+                identities += 1;
+            }
+            "wrap-file-key" => file_keys.push(stanza.body),
+            "done" => break,
+            _ => {}
+        }
+    }
+    for error in &errors {
+        error.send(input, output)?;
+    }
+    if errors.is_empty() {
+        for (i, file_key) in file_keys.iter().enumerate() {
+            for recipient in &recipients {
+                let (ephemeral, body) = wrap(file_key, recipient)?;
+                let ephemeral = to_base64(&ephemeral);
+                let stanza = Stanza::new(
+                    "recipient-stanza",
+                    &[&i.to_string(), STANZA_TYPE, ephemeral.trim_end_matches('=')],
+                    &body,
+                );
+                stanza.send(input, output)?;
+            }
+        }
+    }
+    Stanza::new("done", &[], b"").write(output)?;
+    Ok(output.flush()?)
+}
+
+// the identity-v1 state machine
+fn unwrap_file_keys(
+    input: &mut impl BufRead,
+    output: &mut impl Write,
+) -> Result<(), Box<dyn Error>> {
+    let mut identities = Vec::new();
+    let mut stanzas: Vec<(usize, Stanza)> = Vec::new();
+    while let Some(stanza) = Stanza::read(input)? {
+        match stanza.tag.as_str() {
+            "add-identity" => identities.extend(stanza.args.first().cloned()),
+            "recipient-stanza" => {
+                let file = stanza
+                    .args
+                    .first()
+                    .and_then(|file| file.parse().ok())
+                    .ok_or_else(|| CorruptFileError("a stanza has no file index".into()))?;
+                stanzas.push((file, stanza));
+            }
+            "done" => break,
+            _ => {}
+        }
+    }
+    // the key of each identity is recovered once for every file
+    let mut scalars = Vec::new();
+    for (i, identity) in identities.iter().enumerate() {
+        match identity_key(identity) {
+            Ok(key) => scalars.push(identity_scalar(&key)),
+            Err(e) => {
+                error_stanza("identity", i, &e.to_string()).send(input, output)?;
+            }
+        }
+    }
+    let mut files: Vec<usize> = stanzas.iter().map(|(file, _)| *file).collect();
+    files.dedup();
+    for file in files {
+        let file_key = stanzas
+            .iter()
+            .filter(|(other, stanza)| {
+                *other == file && stanza.args.get(1).map(String::as_str) == Some(STANZA_TYPE)
+            })
+            .find_map(|(_, stanza)| {
+                scalars
+                    .iter()
+                    .find_map(|scalar| unwrap(&stanza.args[2..], &stanza.body, scalar))
+            });
+        if let Some(file_key) = file_key {
+            Stanza::new("file-key", &[&file.to_string()], &file_key).send(input, output)?;
+        }
+    }
+    Stanza::new("done", &[], b"").write(output)?;
+    Ok(output.flush()?)
+}
+
+// a stanza that reports an error with a recipient or an identity
+fn error_stanza(kind: &str, index: usize, message: &str) -> Stanza {
+    Stanza::new("error", &[kind, &index.to_string()], message.as_bytes())
+}
+
+// recovers the key of the shares file named by an identity
+fn identity_key(identity: &str) -> Result<SecretKey, Box<dyn Error>> {
+    let (_, path) = bech32::decode(identity)
+        .filter(|(hrp, _)| hrp == IDENTITY_HRP)
+        .ok_or_else(|| ArgumentError(format!("'{}' is not an identity of the plugin", identity)))?;
+    let path = String::from_utf8(path)?;
+    if !Path::new(&path).is_absolute() {
+        return Err(Box::new(ArgumentError(
+            "the identity does not name an absolute path".into(),
+        )));
+    }
+    crate::fragments_key(fragments::read_fragments(&path)?)
+}
+
+// reads the public key of a recipient of the plugin
+fn parse_recipient(recipient: &str) -> Result<[u8; 32], ArgumentError> {
+    let invalid = || ArgumentError(format!("'{}' is not a recipient of the plugin", recipient));
+    match bech32::decode(recipient) {
+        Some((hrp, key)) if hrp == RECIPIENT_HRP && key.len() == 32 => {
+            let mut public = [0u8; 32];
+            public.copy_from_slice(&key);
+            Ok(public)
+        }
+        _ => Err(invalid()),
+    }
+}
+
+// the X25519 scalar of the identity, derived from the key so the
+// key itself is never used as a curve scalar
+fn identity_scalar(key: &SecretKey) -> [u8; 32] {
+    let mut scalar = [0u8; 32];
+    Hkdf::<Sha256>::new(None, key.expose())
+        .expand(IDENTITY_INFO, &mut scalar)
+        .expect("32 bytes is a valid length for HKDF-SHA256");
+    scalar
+}
+
+fn public_key(scalar: &[u8; 32]) -> [u8; 32] {
+    curve25519::x25519(scalar, &BASEPOINT)
+}
+
+// encrypts a file key to a recipient with an ephemeral key, returning
+// the ephemeral public key and the wrapped file key
+fn wrap(file_key: &[u8], recipient: &[u8; 32]) -> Result<([u8; 32], Vec<u8>), Box<dyn Error>> {
+    let ephemeral_scalar = SecretKey::generate(32)?;
+    let mut scalar = [0u8; 32];
+    scalar.copy_from_slice(ephemeral_scalar.expose());
+    let ephemeral = public_key(&scalar);
+    let shared = curve25519::x25519(&scalar, recipient);
+    let mut body = file_key.to_vec();
+    wrap_cipher(&shared, &ephemeral, recipient)?.encrypt(&mut body)?;
+    Ok((ephemeral, body))
+}
+
+// decrypts a file key wrapped to the public key of the scalar, None
+// if it was wrapped to another recipient
+fn unwrap(args: &[String], body: &[u8], scalar: &[u8; 32]) -> Option<Vec<u8>> {
+    let ephemeral = from_base64(args.first()?).filter(|e| e.len() == 32)?;
+    let mut point = [0u8; 32];
+    point.copy_from_slice(&ephemeral);
+    let shared = curve25519::x25519(scalar, &point);
+    let mut file_key = body.to_vec();
+    wrap_cipher(&shared, &point, &public_key(scalar))
+        .ok()?
+        .decrypt(&mut file_key)
+        .ok()?;
+    Some(file_key).filter(|key| key.len() == FILE_KEY_SIZE)
+}
+
+// the cipher of a wrapped file key, derived from the shared secret
+// and bound to both public keys
+fn wrap_cipher(
+    shared: &[u8; 32],
+    ephemeral: &[u8; 32],
+    recipient: &[u8; 32],
+) -> Result<Cipher, Box<dyn Error>> {
+    if shared.iter().all(|&b| b == 0) {
+        return Err(Box::new(ArgumentError(
+            "the recipient is a point of small order".into(),
+        )));
+    }
+    let mut salt = ephemeral.to_vec();
+    salt.extend_from_slice(recipient);
+    let mut key = vec![0u8; CipherSuite::Aes256Gcm.key_size()];
+    Hkdf::<Sha256>::new(Some(&salt), shared)
+        .expand(WRAP_INFO, &mut key)
+        .expect("32 bytes is a valid length for HKDF-SHA256");
+    // every key wraps a single file key, so the fixed nonce is not reused
+    Ok(SecretKey::new(key).into_cipher(CipherSuite::Aes256Gcm)?)
+}
+
+/////////////////////////////////
+// Unit testing of the module. //
+/////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recipients() {
+        let encoded = bech32::encode(RECIPIENT_HRP, &[7u8; 32]);
+        assert!(encoded.starts_with("age1sharedsecrets1"));
+        assert_eq!(parse_recipient(&encoded).unwrap(), [7u8; 32]);
+        assert!(parse_recipient(&bech32::encode(RECIPIENT_HRP, &[7u8; 31])).is_err());
+        assert!(parse_recipient(&bech32::encode("age", &[7u8; 32])).is_err());
+    }
+
+    #[test]
+    fn wrap_and_unwrap() {
+        let key = SecretKey::generate(32).unwrap();
+        let scalar = identity_scalar(&key);
+        let recipient = parse_recipient(&super::recipient(&key)).unwrap();
+        let file_key = [5u8; FILE_KEY_SIZE];
+        let (ephemeral, body) = wrap(&file_key, &recipient).unwrap();
+        let args = vec![to_base64(&ephemeral)];
+        assert_eq!(unwrap(&args, &body, &scalar).unwrap(), file_key);
+        let other = identity_scalar(&SecretKey::generate(32).unwrap());
+        assert!(unwrap(&args, &body, &other).is_none());
+    }
+
+    #[test]
+    fn stanzas() {
+        let stanza = Stanza::new("wrap-file-key", &[], &[1u8; 48]);
+        let mut written = Vec::new();
+        stanza.write(&mut written).unwrap();
+        // 48 bytes are a full line of base64, followed by an empty one
+        assert_eq!(written.iter().filter(|&&c| c == b'\n').count(), 3);
+        let read = Stanza::read(&mut &written[..]).unwrap().unwrap();
+        assert_eq!(read.tag, "wrap-file-key");
+        assert_eq!(read.body, vec![1u8; 48]);
+        assert!(Stanza::read(&mut &b"no stanza\n"[..]).is_err());
+    }
+
+    #[test]
+    fn recipient_state_machine() {
+        let key = SecretKey::generate(32).unwrap();
+        let mut input = Vec::new();
+        Stanza::new("add-recipient", &[&recipient(&key)], b"")
+            .write(&mut input)
+            .unwrap();
+        Stanza::new("wrap-file-key", &[], &[9u8; FILE_KEY_SIZE])
+            .write(&mut input)
+            .unwrap();
+        Stanza::new("done", &[], b"").write(&mut input).unwrap();
+        Stanza::new("ok", &[], b"").write(&mut input).unwrap();
+        let mut output = Vec::new();
+        run_plugin("recipient-v1", &input[..], &mut output).unwrap();
+        let mut output = &output[..];
+        let stanza = Stanza::read(&mut output).unwrap().unwrap();
+        assert_eq!(stanza.tag, "recipient-stanza");
+        assert_eq!(stanza.args[..2], ["0", STANZA_TYPE]);
+        let file_key = unwrap(&stanza.args[2..], &stanza.body, &identity_scalar(&key));
+        assert_eq!(file_key.unwrap(), [9u8; FILE_KEY_SIZE]);
+        assert_eq!(Stanza::read(&mut output).unwrap().unwrap().tag, "done");
+        assert!(run_plugin("recipient-v2", &b""[..], Vec::new()).is_err());
+    }
+}
@@ -0,0 +1,305 @@
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::os::unix::io::AsRawFd;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::time::Duration;
+
+use zeroize::Zeroize;
+
+use crate::container::Header;
+use crate::crypto::Cipher;
+use crate::error::ArgumentError;
+use crate::report;
+use crate::webhook::Webhooks;
+
+// Longest wait for the request of a client, so one that connects
+// and sends nothing does not stop the agent from serving the others
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Largest frame of the content sent to a client, so a client never
+// allocates more than this for a frame
+const MAX_FRAME: usize = 1 << 16;
+
+// A request of a client to the agent
+enum Request {
+    // the content of the encrypted file at the path
+    Decrypt(String),
+    // ends the agent
+    Stop,
+}
+
+/// Decrypts the files the key opens for the clients of a Unix
+/// socket, until a client asks the agent to stop.
+///
+/// The agent reads each file itself and sends only its content, so
+/// neither the key of the shares nor the data keys ever leave it. Only
+/// processes of the same user that runs the agent are served,
+/// checked with the credentials of the peer of each connection, and
+/// the socket can only be opened by that user. The webhooks are
+/// told of each file decrypted, with its path. A connection that
+/// fails or does not send its request in 5 seconds is reported and
+/// the agent goes on serving.
+///
+/// # Parameters
+///
+/// - socket: The path of the socket, which must not exist.
+/// - key: The cipher of the shares, which unwraps the data keys.
+/// - webhooks: The webhooks told of the events.
+///
+/// # Returns
+///
+/// The number of files decrypted.
+///
+/// # Errors
+///
+/// An io::Error if the socket cannot be created or removed.
+pub fn serve(socket: &str, key: &Cipher, webhooks: &Webhooks) -> Result<usize, Box<dyn Error>> {
+    // the socket is created readable only by the user
+    let mask = unsafe { libc::umask(0o177) };
+    let listener = UnixListener::bind(socket);
+    unsafe { libc::umask(mask) };
+    let listener = listener?;
+    let uid = unsafe { libc::getuid() };
+    let mut decrypted = 0;
+    for stream in listener.incoming() {
+        let mut stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                report::warn(format!("the agent could not accept a connection: {}", e));
+                continue;
+            }
+        };
+        match peer_uid(&stream) {
+            Ok(peer) if peer == uid => (),
+            Ok(_) => {
+                let _ = finish(&mut stream, "error the agent only serves its own user");
+                continue;
+            }
+            Err(e) => {
+                report::warn(format!("the agent could not check a client: {}", e));
+                continue;
+            }
+        }
+        if let Err(e) = stream.set_read_timeout(Some(REQUEST_TIMEOUT)) {
+            report::warn(format!("the agent could not serve a client: {}", e));
+            continue;
+        }
+        match answer(&mut stream, key) {
+            Ok(request) => {
+                let _ = finish(&mut stream, "ok");
+                match request {
+                    Request::Stop => break,
+                    Request::Decrypt(path) => {
+                        decrypted += 1;
+                        webhooks.send(
+                            "decryption_performed",
+                            vec![("operation", "agent".into()), ("file", path.into())],
+                        );
+                    }
+                }
+            }
+            Err(e) => {
+                let _ = finish(&mut stream, &format!("error {}", e));
+            }
+        }
+    }
+    fs::remove_file(socket)?;
+    Ok(decrypted)
+}
+
+/// Asks an agent to decrypt a file and writes its content.
+///
+/// The content is written as the agent sends it, before the agent
+/// has checked the digest of the whole file, so on an error the
+/// output must be discarded.
+///
+/// # Parameters
+///
+/// - socket: The path of the socket of the agent.
+/// - encrypted_file: The file to decrypt, which the agent reads.
+/// - output: Where the content is written.
+///
+/// # Errors
+///
+/// An io::Error if the agent is not running, or an ArgumentError
+/// if the key of the agent does not open the file.
+pub fn decrypt(
+    socket: &str,
+    encrypted_file: &str,
+    output: &mut impl Write,
+) -> Result<(), Box<dyn Error>> {
+    let path = fs::canonicalize(encrypted_file)?;
+    let path = path
+        .to_str()
+        .filter(|path| !path.contains('\n'))
+        .ok_or_else(|| ArgumentError("the agent cannot be given this path".into()))?;
+    exchange(socket, &format!("decrypt {}", path), output)
+}
+
+/// Asks an agent to stop, which removes its socket.
+///
+/// # Parameters
+///
+/// - socket: The path of the socket of the agent.
+///
+/// # Errors
+///
+/// An io::Error if the agent is not running.
+pub fn stop(socket: &str) -> Result<(), Box<dyn Error>> {
+    exchange(socket, "stop", &mut io::sink())
+}
+
+// sends a request, writes the content the agent sends in frames
+// of a length of 4 bytes followed by its bytes, up to an empty one,
+// and reads the status line after it, "ok" or the error of the agent
+fn exchange(socket: &str, request: &str, output: &mut impl Write) -> Result<(), Box<dyn Error>> {
+    let mut stream = UnixStream::connect(socket)?;
+    writeln!(stream, "{}", request)?;
+    let mut reader = BufReader::new(stream);
+    let mut frame = Vec::new();
+    loop {
+        let mut length = [0; 4];
+        reader.read_exact(&mut length)?;
+        let length = u32::from_be_bytes(length) as usize;
+        if length == 0 {
+            break;
+        }
+        if length > MAX_FRAME {
+            return Err(Box::new(ArgumentError(
+                "the agent sent an invalid response".into(),
+            )));
+        }
+        frame.resize(length, 0);
+        let written = reader
+            .read_exact(&mut frame)
+            .and_then(|_| output.write_all(&frame));
+        frame.zeroize();
+        written?;
+    }
+    let mut status = String::new();
+    reader.read_line(&mut status)?;
+    match status.trim_end() {
+        "ok" => Ok(output.flush()?),
+        status => match status.strip_prefix("error ") {
+            Some(message) => Err(Box::new(ArgumentError(format!("the agent: {}", message)))),
+            None => Err(Box::new(ArgumentError(
+                "the agent sent an invalid response".into(),
+            ))),
+        },
+    }
+}
+
+// reads a request of a client and answers it, sending the content
+// of a file in frames
+fn answer(stream: &mut UnixStream, key: &Cipher) -> Result<Request, Box<dyn Error>> {
+    let mut line = String::new();
+    BufReader::new(&*stream).read_line(&mut line)?;
+    let request = match line.trim_end() {
+        "stop" => Request::Stop,
+        line => match line.strip_prefix("decrypt ") {
+            Some(path) => Request::Decrypt(path.into()),
+            None => return Err(Box::new(ArgumentError("unknown request".into()))),
+        },
+    };
+    if let Request::Decrypt(path) = &request {
+        let mut reader = BufReader::new(File::open(path)?);
+        let header = Header::read_from(&mut reader)?;
+        let data_key = crate::unwrap_data_key(&header, key)?;
+        crate::decrypt_into(&header, &data_key, &mut reader, &mut Frames(stream))?;
+    }
+    Ok(request)
+}
+
+// ends the content of a response with an empty frame and sends its
+// status line
+fn finish(stream: &mut UnixStream, status: &str) -> io::Result<()> {
+    stream.write_all(&[0; 4])?;
+    writeln!(stream, "{}", status)
+}
+
+// Writes the content of a response in frames of at most MAX_FRAME
+// bytes, each after its length
+struct Frames<'a>(&'a mut UnixStream);
+
+impl Write for Frames<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let length = buf.len().min(MAX_FRAME);
+        if length > 0 {
+            self.0.write_all(&(length as u32).to_be_bytes())?;
+            self.0.write_all(&buf[..length])?;
+        }
+        Ok(length)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+// the user of the process at the other end of the connection
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn peer_uid(stream: &UnixStream) -> io::Result<u32> {
+    let mut credentials = libc::ucred {
+        pid: 0,
+        uid: 0,
+        gid: 0,
+    };
+    let mut length = std::mem::size_of::<libc::ucred>() as libc::socklen_t;
+    let result = unsafe {
+        libc::getsockopt(
+            stream.as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_PEERCRED,
+            &mut credentials as *mut libc::ucred as *mut libc::c_void,
+            &mut length,
+        )
+    };
+    if result != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(credentials.uid)
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn peer_uid(stream: &UnixStream) -> io::Result<u32> {
+    let (mut uid, mut gid) = (0, 0);
+    if unsafe { libc::getpeereid(stream.as_raw_fd(), &mut uid, &mut gid) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(uid)
+}
+
+/////////////////////////////////
+// Unit testing of the module. //
+/////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn peer_credentials() {
+        let (client, _server) = UnixStream::pair().unwrap();
+        assert_eq!(peer_uid(&client).unwrap(), unsafe { libc::getuid() });
+    }
+
+    #[test]
+    fn silent_client() {
+        let socket = std::env::temp_dir().join(format!("agent-{}.sock", std::process::id()));
+        let socket = socket.to_str().unwrap().to_string();
+        let key = Cipher::random(crate::crypto::CipherSuite::Aes256Gcm).unwrap();
+        let agent = {
+            let socket = socket.clone();
+            std::thread::spawn(move || serve(&socket, &key, &Webhooks::default()).unwrap())
+        };
+        while !std::path::Path::new(&socket).exists() {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        // a client that sends nothing does not keep the others waiting
+        let _silent = UnixStream::connect(&socket).unwrap();
+        stop(&socket).unwrap();
+        assert_eq!(agent.join().unwrap(), 0);
+        assert!(!std::path::Path::new(&socket).exists());
+    }
+}
@@ -0,0 +1,222 @@
+use std::error::Error;
+
+use crate::error::{ArgumentError, CorruptFileError};
+
+/// Size in bytes of the blocks of a tar archive.
+pub const BLOCK_SIZE: u64 = 512;
+
+/// An entry of a tar archive: a regular file in it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    /// The path of the file in the archive, without a leading "./".
+    pub name: String,
+    /// The position of the content of the file in the archive.
+    pub offset: u64,
+    /// The size of the content of the file.
+    pub size: u64,
+}
+
+/// Finds a file in a tar archive, reading only the headers of the
+/// entries before it, so its content can be read without reading
+/// the rest of the archive.
+///
+/// The archive can be in the ustar, GNU or pax format; the long names
+/// of GNU and pax are read from the entries that hold them.
+///
+/// # Parameters
+///
+/// - read: Reads the part of the archive of the given offset and
+///   length.
+/// - length: The length of the archive.
+/// - name: The path of the file in the archive.
+///
+/// # Errors
+///
+/// A CorruptFileError if the archive is not a tar archive, an
+/// ArgumentError if it has no file with that path, or the error of
+/// read.
+pub fn find(
+    mut read: impl FnMut(u64, u64) -> Result<Vec<u8>, Box<dyn Error>>,
+    length: u64,
+    name: &str,
+) -> Result<Entry, Box<dyn Error>> {
+    let name = name.trim_start_matches("./");
+    let mut offset = 0;
+    let mut long_name = None;
+    while offset + BLOCK_SIZE <= length {
+        let block = read(offset, BLOCK_SIZE)?;
+        // the archive ends with blocks of zeros
+        if block.iter().all(|&byte| byte == 0) {
+            break;
+        }
+        let (entry_name, size, kind) = parse_header(&block)?;
+        let data = offset + BLOCK_SIZE;
+        if data + size > length {
+            return Err(Box::new(corrupt()));
+        }
+        match kind {
+            b'L' => long_name = Some(text(&read(data, size)?)),
+            b'x' => {
+                let records = read(data, size)?;
+                long_name = pax_path(&records).or(long_name);
+            }
+            b'0' | b'\0' | b'7' => {
+                let entry_name = long_name.take().unwrap_or(entry_name);
+                if entry_name.trim_start_matches("./") == name {
+                    return Ok(Entry {
+                        name: name.to_string(),
+                        offset: data,
+                        size,
+                    });
+                }
+            }
+            _ => long_name = None,
+        }
+        offset = data + size.div_ceil(BLOCK_SIZE) * BLOCK_SIZE;
+    }
+    Err(Box::new(ArgumentError(format!(
+        "there is no file {} in the archive",
+        name
+    ))))
+}
+
+// the name, the size and the type of the entry of a header block
+fn parse_header(block: &[u8]) -> Result<(String, u64, u8), CorruptFileError> {
+    let expected = octal(&block[148..156]).ok_or_else(corrupt)?;
+    let checksum: u64 = block
+        .iter()
+        .enumerate()
+        .map(|(i, &byte)| if (148..156).contains(&i) { b' ' } else { byte } as u64)
+        .sum();
+    if checksum != expected {
+        return Err(corrupt());
+    }
+    let size = if block[124] & 0x80 != 0 {
+        // GNU tar writes large sizes in base 256
+        block[125..136]
+            .iter()
+            .fold(0u64, |size, &byte| (size << 8) | byte as u64)
+    } else {
+        octal(&block[124..136]).ok_or_else(corrupt)?
+    };
+    let mut name = text(&block[..100]);
+    if &block[257..262] == b"ustar" {
+        let prefix = text(&block[345..500]);
+        if !prefix.is_empty() {
+            name = format!("{}/{}", prefix, name);
+        }
+    }
+    Ok((name, size, block[156]))
+}
+
+// the path of the records of a pax extended header, if they have one
+fn pax_path(records: &[u8]) -> Option<String> {
+    let mut rest = records;
+    while let Some(space) = rest.iter().position(|&byte| byte == b' ') {
+        let length: usize = std::str::from_utf8(&rest[..space]).ok()?.parse().ok()?;
+        if length <= space || length > rest.len() {
+            return None;
+        }
+        let record = &rest[space + 1..length];
+        if let Some(path) = record.strip_prefix(b"path=") {
+            return Some(text(path.strip_suffix(b"\n").unwrap_or(path)));
+        }
+        rest = &rest[length..];
+    }
+    None
+}
+
+// the number of an octal field, which ends with a NUL or a space
+fn octal(field: &[u8]) -> Option<u64> {
+    let digits = std::str::from_utf8(field).ok()?;
+    let digits = digits.trim_matches(|c| c == '\0' || c == ' ');
+    if digits.is_empty() {
+        return Some(0);
+    }
+    u64::from_str_radix(digits, 8).ok()
+}
+
+// the text of a field up to its first NUL
+fn text(field: &[u8]) -> String {
+    let end = field
+        .iter()
+        .position(|&byte| byte == 0)
+        .unwrap_or(field.len());
+    String::from_utf8_lossy(&field[..end]).into_owned()
+}
+
+fn corrupt() -> CorruptFileError {
+    CorruptFileError("not a valid tar archive".into())
+}
+
+/////////////////////////////////
+// Unit testing of the module. //
+/////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a header block of a ustar archive
+    fn header(name: &str, size: usize, kind: u8) -> Vec<u8> {
+        let mut block = vec![0u8; BLOCK_SIZE as usize];
+        block[..name.len()].copy_from_slice(name.as_bytes());
+        block[124..135].copy_from_slice(format!("{:011o}", size).as_bytes());
+        block[156] = kind;
+        block[257..263].copy_from_slice(b"ustar\0");
+        block[263..265].copy_from_slice(b"00");
+        block[148..156].copy_from_slice(b"        ");
+        let checksum: u32 = block.iter().map(|&byte| byte as u32).sum();
+        block[148..155].copy_from_slice(format!("{:06o}\0", checksum).as_bytes());
+        block
+    }
+
+    fn push(archive: &mut Vec<u8>, name: &str, kind: u8, content: &[u8]) {
+        archive.extend(header(name, content.len(), kind));
+        archive.extend(content);
+        let padding =
+            (BLOCK_SIZE as usize - content.len() % BLOCK_SIZE as usize) % BLOCK_SIZE as usize;
+        archive.extend(vec![0u8; padding]);
+    }
+
+    #[test]
+    fn find_entries() {
+        let long = format!("{}/key", "d".repeat(120));
+        let pax = format!("{}/key", "p".repeat(120));
+        let record = format!(" path={}\n", pax);
+        let record = format!("{}{}", record.len() + 3, record);
+        let mut archive = Vec::new();
+        push(&mut archive, "./config/", b'5', b"");
+        push(&mut archive, "./config/id_rsa", b'0', b"private key");
+        push(&mut archive, "././@LongLink", b'L', long.as_bytes());
+        push(&mut archive, "ignored", b'0', &[7u8; 600]);
+        push(&mut archive, "PaxHeader", b'x', record.as_bytes());
+        push(&mut archive, "ignored", b'0', b"pax");
+        push(&mut archive, "notes.txt", b'0', b"");
+        archive.extend(vec![0u8; 2 * BLOCK_SIZE as usize]);
+        let length = archive.len() as u64;
+        let read = |offset: u64, length: u64| -> Result<Vec<u8>, Box<dyn Error>> {
+            Ok(archive[offset as usize..(offset + length) as usize].to_vec())
+        };
+
+        let entry = find(read, length, "config/id_rsa").unwrap();
+        assert_eq!(entry.offset, 2 * BLOCK_SIZE);
+        assert_eq!(entry.size, 11);
+        let entry = find(read, length, &long).unwrap();
+        assert_eq!(entry.size, 600);
+        let entry = find(read, length, &pax).unwrap();
+        let content = &archive[entry.offset as usize..(entry.offset + entry.size) as usize];
+        assert_eq!(content, b"pax");
+        assert_eq!(find(read, length, "./notes.txt").unwrap().size, 0);
+        assert!(find(read, length, "config").is_err());
+        assert!(find(read, length, "ignored").is_err());
+        assert!(find(read, length, "missing").is_err());
+
+        let mut corrupt = archive.clone();
+        corrupt[0] = b'x';
+        let read = |offset: u64, length: u64| -> Result<Vec<u8>, Box<dyn Error>> {
+            Ok(corrupt[offset as usize..(offset + length) as usize].to_vec())
+        };
+        assert!(find(read, length, "config/id_rsa").is_err());
+    }
+}
@@ -0,0 +1,176 @@
+/// Characters of the bech32 alphabet, see BIP 173, by their value
+/// in GF(32).
+pub const CHARSET: &[u8; 32] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+
+// Generator of the BCH code of the checksum of bech32
+const GENERATOR: [u128; 5] = [0x3b6a57b2, 0x26508e6d, 0x1ea119fa, 0x3d4233dd, 0x2a1462b3];
+
+// Number of characters of the checksum of bech32
+const CHECKSUM_LEN: usize = 6;
+
+/// Encodes bytes in bech32 without the length limit of BIP 173,
+/// as age does.
+///
+/// # Parameters
+///
+/// - hrp: The human readable part, in lowercase.
+/// - data: The bytes to encode.
+pub fn encode(hrp: &str, data: &[u8]) -> String {
+    let mut values = to_values(data);
+    let residue = polymod(
+        &[hrp_values(hrp), values.clone(), vec![0; CHECKSUM_LEN]].concat(),
+        1,
+        &GENERATOR,
+        CHECKSUM_LEN,
+    ) ^ 1;
+    values
+        .extend((0..CHECKSUM_LEN).map(|i| ((residue >> (5 * (CHECKSUM_LEN - 1 - i))) & 31) as u8));
+    let mut encoded = format!("{}1", hrp);
+    encoded.extend(values.iter().map(|&v| CHARSET[v as usize] as char));
+    encoded
+}
+
+/// Decodes a bech32 string, as written by encode.
+///
+/// # Parameters
+///
+/// - text: The string, all in lowercase or all in uppercase.
+///
+/// # Returns
+///
+/// The human readable part in lowercase and the bytes, or None if
+/// the string mixes cases, has a character outside the alphabet,
+/// its checksum does not match or its padding is not zeros.
+pub fn decode(text: &str) -> Option<(String, Vec<u8>)> {
+    if text.to_lowercase() != text && text.to_uppercase() != text {
+        return None;
+    }
+    let text = text.to_lowercase();
+    let separator = text.rfind('1')?;
+    let (hrp, data) = (&text[..separator], &text[separator + 1..]);
+    if hrp.is_empty() || data.len() < CHECKSUM_LEN {
+        return None;
+    }
+    let values = data.bytes().map(value_of).collect::<Option<Vec<u8>>>()?;
+    if polymod(
+        &[hrp_values(hrp), values.clone()].concat(),
+        1,
+        &GENERATOR,
+        CHECKSUM_LEN,
+    ) != 1
+    {
+        return None;
+    }
+    let values = &values[..values.len() - CHECKSUM_LEN];
+    // at most 4 bits of padding, all of them zero
+    let padding = values.len() * 5 % 8;
+    if padding > 4 || values.last().is_some_and(|v| v & ((1 << padding) - 1) != 0) {
+        return None;
+    }
+    Some((hrp.to_string(), to_bytes(values)))
+}
+
+/// Returns the value of a character of the bech32 alphabet.
+///
+/// # Parameters
+///
+/// - c: The character, in lowercase.
+pub fn value_of(c: u8) -> Option<u8> {
+    CHARSET.iter().position(|&x| x == c).map(|v| v as u8)
+}
+
+/// Splits bytes in values of 5 bits, padding the last one with zeros.
+///
+/// # Parameters
+///
+/// - bytes: The bytes to split.
+pub fn to_values(bytes: &[u8]) -> Vec<u8> {
+    let bits = bytes.len() * 8;
+    (0..bits.div_ceil(5))
+        .map(|i| {
+            (0..5).fold(0, |value, j| {
+                let bit = 5 * i + j;
+                let set = bit < bits && (bytes[bit / 8] >> (7 - bit % 8)) & 1 == 1;
+                (value << 1) | set as u8
+            })
+        })
+        .collect()
+}
+
+/// Joins values of 5 bits in bytes, discarding the padding.
+///
+/// # Parameters
+///
+/// - values: The values, as returned by to_values.
+pub fn to_bytes(values: &[u8]) -> Vec<u8> {
+    let mut bytes = vec![0u8; values.len() * 5 / 8];
+    for bit in 0..bytes.len() * 8 {
+        if (values[bit / 5] >> (4 - bit % 5)) & 1 == 1 {
+            bytes[bit / 8] |= 1 << (7 - bit % 8);
+        }
+    }
+    bytes
+}
+
+/// Computes the residue of a BCH code over GF(32) of the bech32
+/// family, the one of bech32 or the one of codex32 (BIP 93).
+///
+/// # Parameters
+///
+/// - values: The values of the characters checked.
+/// - start: The initial residue of the code.
+/// - generator: The generator of the code.
+/// - checksum_len: The number of characters of the checksum.
+pub fn polymod(values: &[u8], start: u128, generator: &[u128; 5], checksum_len: usize) -> u128 {
+    let shift = 5 * (checksum_len - 1);
+    let mut residue = start;
+    for &value in values {
+        let top = residue >> shift;
+        residue = ((residue & ((1 << shift) - 1)) << 5) ^ value as u128;
+        for (i, generator) in generator.iter().enumerate() {
+            if (top >> i) & 1 == 1 {
+                residue ^= generator;
+            }
+        }
+    }
+    residue
+}
+
+// expands the human readable part for the checksum
+fn hrp_values(hrp: &str) -> Vec<u8> {
+    let mut values: Vec<u8> = hrp.bytes().map(|c| c >> 5).collect();
+    values.push(0);
+    values.extend(hrp.bytes().map(|c| c & 31));
+    values
+}
+
+/////////////////////////////////
+// Unit testing of the module. //
+/////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bech32() {
+        // test vectors of BIP 173
+        assert_eq!(encode("a", b""), "a12uel5l");
+        assert_eq!(decode("A12UEL5L").unwrap(), ("a".into(), Vec::new()));
+        assert!(decode("a12uel5m").is_none());
+        assert!(decode("A12uel5l").is_none());
+        let encoded = encode("hrp", &[7u8; 32]);
+        assert_eq!(decode(&encoded).unwrap(), ("hrp".into(), vec![7u8; 32]));
+    }
+
+    #[test]
+    fn values() {
+        assert_eq!(
+            to_bytes(&to_values(&[0xa5, 0x0f, 0xff])),
+            [0xa5, 0x0f, 0xff]
+        );
+        assert_eq!(value_of(b'q'), Some(0));
+        assert_eq!(value_of(b'l'), Some(31));
+        assert_eq!(value_of(b'b'), None);
+    }
+}
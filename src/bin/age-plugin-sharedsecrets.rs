@@ -0,0 +1,25 @@
+use std::env;
+use std::io;
+use std::process;
+
+use shared_secrets::age;
+
+// age runs the plugin with --age-plugin=<state machine> and talks to
+// it through the standard input and output
+fn main() {
+    let state_machine = env::args()
+        .skip(1)
+        .find_map(|arg| arg.strip_prefix("--age-plugin=").map(String::from))
+        .unwrap_or_else(|| {
+            eprintln!(
+                "age-plugin-{} is run by age, use it with age -r or age -i",
+                age::PLUGIN_NAME
+            );
+            process::exit(1);
+        });
+    let stdin = io::stdin();
+    if let Err(e) = age::run_plugin(&state_machine, stdin.lock(), io::stdout()) {
+        eprintln!("Application error: {}", e);
+        process::exit(1);
+    }
+}
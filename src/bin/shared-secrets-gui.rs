@@ -0,0 +1,278 @@
+use std::path::{Path, PathBuf};
+use std::process;
+
+use eframe::egui;
+use zeroize::Zeroize;
+
+use shared_secrets::crypto::SecretKey;
+use shared_secrets::fragments::Fragment;
+use shared_secrets::gui;
+
+// The operation shown in the window
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Tab {
+    Split,
+    Recover,
+}
+
+// The result of the last action, shown under its button
+enum Status {
+    None,
+    Ok(String),
+    Error(String),
+}
+
+impl Status {
+    fn show(&self, ui: &mut egui::Ui) {
+        match self {
+            Status::None => (),
+            Status::Ok(message) => {
+                ui.colored_label(egui::Color32::DARK_GREEN, message);
+            }
+            Status::Error(message) => {
+                ui.colored_label(egui::Color32::DARK_RED, message);
+            }
+        }
+    }
+}
+
+// The state of the window: the secret being split and the shares
+// written, and the shares dropped to recover a secret
+struct App {
+    tab: Tab,
+    secret: String,
+    total_evals: usize,
+    min_required_evals: usize,
+    output_dir: String,
+    written: Vec<PathBuf>,
+    split_status: Status,
+    dropped: Vec<(Fragment, String)>,
+    share_path: String,
+    recovered: Option<SecretKey>,
+    secret_path: String,
+    recover_status: Status,
+}
+
+impl Default for App {
+    fn default() -> Self {
+        App {
+            tab: Tab::Split,
+            secret: String::new(),
+            total_evals: 5,
+            min_required_evals: 3,
+            output_dir: ".".into(),
+            written: Vec::new(),
+            split_status: Status::None,
+            dropped: Vec::new(),
+            share_path: String::new(),
+            recovered: None,
+            secret_path: "secret.bin".into(),
+            recover_status: Status::None,
+        }
+    }
+}
+
+impl App {
+    fn split(&mut self) {
+        // the typed secret is erased whether it is split or not
+        let fragments = gui::split(
+            self.secret.as_bytes(),
+            self.total_evals,
+            self.min_required_evals,
+        );
+        self.secret.zeroize();
+        self.written.clear();
+        let written = fragments.and_then(|fragments| {
+            fragments
+                .iter()
+                .map(|fragment| gui::write_share(Path::new(&self.output_dir), fragment))
+                .collect::<Result<Vec<_>, _>>()
+        });
+        self.split_status = match written {
+            Ok(written) => {
+                self.written = written;
+                Status::Ok(
+                    "Give each share to a different person and do not keep the secret.".into(),
+                )
+            }
+            Err(e) => Status::Error(e.to_string()),
+        };
+    }
+
+    // adds the shares of a file, skipping the ones already dropped
+    fn add(&mut self, path: &Path) {
+        let source = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        match gui::read_shares(path) {
+            Ok(fragments) => {
+                for fragment in fragments {
+                    if !self.dropped.iter().any(|(other, _)| *other == fragment) {
+                        self.dropped.push((fragment, source.clone()));
+                    }
+                }
+                self.recover_status = Status::Ok(format!("{} distinct shares", self.dropped.len()));
+            }
+            Err(e) => self.recover_status = Status::Error(format!("{}: {}", source, e)),
+        }
+    }
+
+    fn recover(&mut self) {
+        let fragments = self.dropped.iter().map(|(fragment, _)| fragment.clone());
+        match gui::recover(fragments.collect()) {
+            Ok(secret) => {
+                self.recovered = Some(secret);
+                self.recover_status = Status::Ok("The secret was recovered.".into());
+            }
+            Err(e) => self.recover_status = Status::Error(e.to_string()),
+        }
+    }
+
+    fn save(&mut self) {
+        let secret = match &self.recovered {
+            Some(secret) => secret,
+            None => return,
+        };
+        self.recover_status = match gui::write_secret(Path::new(&self.secret_path), secret) {
+            Ok(()) => Status::Ok(format!("The secret was written to {}.", self.secret_path)),
+            Err(e) => Status::Error(e.to_string()),
+        };
+    }
+
+    fn split_tab(&mut self, ui: &mut egui::Ui) {
+        ui.label("Secret");
+        ui.add(
+            egui::TextEdit::multiline(&mut self.secret)
+                .password(true)
+                .desired_width(f32::INFINITY),
+        );
+        ui.horizontal(|ui| {
+            ui.label("Shares");
+            ui.add(egui::DragValue::new(&mut self.total_evals).range(2..=255));
+            ui.label("needed to recover it");
+            ui.add(egui::DragValue::new(&mut self.min_required_evals).range(2..=255));
+        });
+        ui.horizontal(|ui| {
+            ui.label("Directory of the share files");
+            ui.text_edit_singleline(&mut self.output_dir);
+        });
+        if ui.button("Split").clicked() {
+            self.split();
+        }
+        self.split_status.show(ui);
+        for path in &self.written {
+            ui.label(path.display().to_string());
+        }
+    }
+
+    fn recover_tab(&mut self, ui: &mut egui::Ui) {
+        let hovering = ui.ctx().input(|input| !input.raw.hovered_files.is_empty());
+        let stroke = if hovering {
+            egui::Stroke::new(3.0, egui::Color32::DARK_GREEN)
+        } else {
+            egui::Stroke::new(3.0, egui::Color32::GRAY)
+        };
+        egui::Frame::new()
+            .stroke(stroke)
+            .corner_radius(12.0)
+            .inner_margin(24.0)
+            .show(ui, |ui| {
+                ui.set_width(ui.available_width());
+                ui.label("Drop here the share files or the photos of their QR codes");
+                ui.horizontal(|ui| {
+                    ui.text_edit_singleline(&mut self.share_path);
+                    if ui.button("Add").clicked() && !self.share_path.is_empty() {
+                        let path = PathBuf::from(std::mem::take(&mut self.share_path));
+                        self.add(&path);
+                    }
+                });
+            });
+        for (fragment, source) in &self.dropped {
+            let holder = match &fragment.holder {
+                Some(holder) => format!(" of {}", holder),
+                None => String::new(),
+            };
+            ui.label(format!("Share {}{} ({})", fragment.share.0, holder, source));
+        }
+        ui.horizontal(|ui| {
+            if ui
+                .add_enabled(!self.dropped.is_empty(), egui::Button::new("Recover"))
+                .clicked()
+            {
+                self.recover();
+            }
+            if ui.button("Clear").clicked() {
+                self.dropped.clear();
+                self.recovered = None;
+                self.recover_status = Status::None;
+            }
+        });
+        self.recover_status.show(ui);
+        if let Some(secret) = &self.recovered {
+            let mut shown = gui::display(secret);
+            ui.add(
+                egui::TextEdit::multiline(&mut shown.as_str())
+                    .font(egui::TextStyle::Monospace)
+                    .desired_width(f32::INFINITY),
+            );
+            shown.zeroize();
+            ui.horizontal(|ui| {
+                ui.text_edit_singleline(&mut self.secret_path);
+                if ui.button("Save the secret").clicked() {
+                    self.save();
+                }
+            });
+        }
+    }
+}
+
+impl eframe::App for App {
+    fn update(&mut self, ctx: &egui::Context, _: &mut eframe::Frame) {
+        let dropped: Vec<PathBuf> = ctx.input(|input| {
+            input
+                .raw
+                .dropped_files
+                .iter()
+                .filter_map(|file| file.path.clone())
+                .collect()
+        });
+        if !dropped.is_empty() {
+            self.tab = Tab::Recover;
+            for path in &dropped {
+                self.add(path);
+            }
+        }
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.selectable_value(&mut self.tab, Tab::Split, "Split a secret");
+                ui.selectable_value(&mut self.tab, Tab::Recover, "Recover a secret");
+            });
+            ui.separator();
+            match self.tab {
+                Tab::Split => self.split_tab(ui),
+                Tab::Recover => self.recover_tab(ui),
+            }
+        });
+    }
+}
+
+// opens the window of the split and recovery ceremonies
+fn main() {
+    let options = eframe::NativeOptions {
+        viewport: egui::ViewportBuilder::default()
+            .with_title("shared_secrets")
+            .with_inner_size([640.0, 520.0])
+            .with_drag_and_drop(true),
+        ..Default::default()
+    };
+    let result = eframe::run_native(
+        "shared_secrets",
+        options,
+        Box::new(|_| Ok(Box::<App>::default())),
+    );
+    if let Err(e) = result {
+        eprintln!("Application error: {}", e);
+        process::exit(1);
+    }
+}
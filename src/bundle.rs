@@ -0,0 +1,214 @@
+use std::io::{Read, Write};
+
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use flate2::Compression;
+
+use crate::encoding::{from_base64, to_base64};
+use crate::error::CorruptFileError;
+use crate::fragments::{self, Format, Fragment};
+
+/// First line of a bundle of shares.
+pub const BUNDLE_BEGIN: &str = "-----BEGIN SHARED SECRETS BUNDLE-----";
+
+/// Last line of a bundle of shares.
+pub const BUNDLE_END: &str = "-----END SHARED SECRETS BUNDLE-----";
+
+/// Suffix of the bundle of the shares of an encryption.
+pub const EXTENSION: &str = ".bundle";
+
+// Magic bytes and version at the start of the content of a bundle
+const MAGIC: &[u8; 4] = b"SSB1";
+
+// Number of bytes of the BLAKE3 hash of the shares kept in a bundle
+const HASH_SIZE: usize = 16;
+
+// Maximum length of the lines of base64 of a bundle
+const LINE_LEN: usize = 64;
+
+// Maximum length of the shares file of a bundle, so a corrupt
+// bundle cannot claim an arbitrarily large one
+const MAX_LENGTH: usize = 64 << 20;
+
+/// What a bundle holds, as inspect shows it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bundle {
+    /// The shares of the bundle.
+    pub fragments: Vec<Fragment>,
+    /// Length in bytes of the shares file in the bundle.
+    pub length: usize,
+    /// Length in bytes of the shares file compressed.
+    pub compressed: usize,
+}
+
+/// Writes every share as a single bundle: the shares file in the
+/// lines format, compressed and armored in base64 between the
+/// BUNDLE_BEGIN and BUNDLE_END lines, for archiving all of them
+/// together, for example in a vault.
+///
+/// The content of the bundle is MAGIC, the length of the shares file
+/// as a big-endian u32, the first HASH_SIZE bytes of its BLAKE3 hash
+/// and the file compressed with DEFLATE.
+///
+/// # Parameters
+///
+/// - fragments: The fragments to write.
+/// - writer: The writer where the bundle is written.
+///
+/// # Errors
+///
+/// This method returns an error if an error occurs while writing.
+pub fn write_bundle(fragments: &[Fragment], writer: &mut impl Write) -> std::io::Result<()> {
+    let mut text = Vec::new();
+    fragments::write_fragments(fragments, Format::Lines, &mut text)?;
+    let mut content = MAGIC.to_vec();
+    content.extend((text.len() as u32).to_be_bytes());
+    content.extend(&blake3::hash(&text).as_bytes()[..HASH_SIZE]);
+    content.extend(compress(&text)?);
+    writeln!(writer, "{}", BUNDLE_BEGIN)?;
+    for line in to_base64(&content).as_bytes().chunks(LINE_LEN) {
+        writer.write_all(line)?;
+        writeln!(writer)?;
+    }
+    writeln!(writer, "{}", BUNDLE_END)
+}
+
+/// Returns whether the text of a shares file is a bundle.
+pub fn is_bundle(text: &str) -> bool {
+    text.trim_start().starts_with(BUNDLE_BEGIN)
+}
+
+/// Reads a bundle written by write_bundle.
+///
+/// # Parameters
+///
+/// - text: The armored bundle.
+///
+/// # Returns
+///
+/// The shares of the bundle and its lengths.
+///
+/// # Errors
+///
+/// A CorruptFileError if the text is not a bundle, it was altered
+/// or the shares file in it is corrupt.
+pub fn read_bundle(text: &str) -> Result<Bundle, CorruptFileError> {
+    let corrupt = |reason: &str| CorruptFileError(format!("invalid bundle: {}", reason));
+    let mut lines = text.lines().map(str::trim).skip_while(|l| l.is_empty());
+    if lines.next() != Some(BUNDLE_BEGIN) {
+        return Err(corrupt("no begin line"));
+    }
+    let mut base64 = String::new();
+    let mut ended = false;
+    for line in &mut lines {
+        if line == BUNDLE_END {
+            ended = true;
+            break;
+        }
+        base64.push_str(line);
+    }
+    if !ended {
+        return Err(corrupt("no end line"));
+    }
+    let content = from_base64(&base64).ok_or_else(|| corrupt("not base64"))?;
+    let header = MAGIC.len() + 4 + HASH_SIZE;
+    if content.len() < header || &content[..MAGIC.len()] != MAGIC {
+        return Err(corrupt("unknown format"));
+    }
+    let mut length = [0u8; 4];
+    length.copy_from_slice(&content[MAGIC.len()..MAGIC.len() + 4]);
+    let length = u32::from_be_bytes(length) as usize;
+    let compressed = &content[header..];
+    let text = decompress(compressed, length).ok_or_else(|| corrupt("damaged content"))?;
+    if blake3::hash(&text).as_bytes()[..HASH_SIZE] != content[header - HASH_SIZE..header] {
+        return Err(corrupt("damaged content"));
+    }
+    let text = String::from_utf8(text).map_err(|_| corrupt("damaged content"))?;
+    Ok(Bundle {
+        fragments: fragments::parse_fragments(&text)?,
+        length,
+        compressed: compressed.len(),
+    })
+}
+
+// compresses with raw DEFLATE, without a zlib or gzip header, since
+// the bundle has its own length and hash
+fn compress(data: &[u8]) -> std::io::Result<Vec<u8>> {
+    let mut encoder = DeflateEncoder::new(Vec::new(), Compression::best());
+    encoder.write_all(data)?;
+    encoder.finish()
+}
+
+// decompresses data compressed by compress, or None if it does not
+// decompress to exactly length bytes
+fn decompress(data: &[u8], length: usize) -> Option<Vec<u8>> {
+    if length > MAX_LENGTH {
+        return None;
+    }
+    let mut output = Vec::with_capacity(length);
+    // one byte more than the length is read, to tell a longer output
+    DeflateDecoder::new(data)
+        .take(length as u64 + 1)
+        .read_to_end(&mut output)
+        .ok()?;
+    Some(output).filter(|output| output.len() == length)
+}
+
+/////////////////////////////////
+// Unit testing of the module. //
+/////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compression() {
+        for data in [
+            &b""[..],
+            b"a",
+            b"abcabcabcabcabcabcabcabcabcabcabc",
+            b"aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa",
+            b"field=208351617316091241234326746312124448251235562226470491514186331217050270460481",
+        ] {
+            let compressed = compress(data).unwrap();
+            assert_eq!(decompress(&compressed, data.len()).unwrap(), data);
+        }
+        let repeated = b"1:2b.3c:holder=alice:field=65537:len=32\n".repeat(50);
+        let compressed = compress(&repeated).unwrap();
+        assert!(compressed.len() < repeated.len() / 5);
+        assert_eq!(decompress(&compressed, repeated.len()).unwrap(), repeated);
+        assert!(decompress(&compressed, repeated.len() - 1).is_none());
+        assert!(decompress(&compressed, repeated.len() + 1).is_none());
+        // not DEFLATE
+        assert!(decompress(&[0xff, 0, 0], 3).is_none());
+    }
+
+    #[test]
+    fn bundle() {
+        let fragments = fragments::parse_fragments(
+            "1:2b:holder=alice:field=65537:len=32\n2:3c:holder=bob:field=65537:len=32\n",
+        )
+        .unwrap();
+        let mut text = Vec::new();
+        write_bundle(&fragments, &mut text).unwrap();
+        let text = String::from_utf8(text).unwrap();
+        assert!(is_bundle(&text));
+        assert!(text
+            .lines()
+            .all(|line| line.len() <= LINE_LEN.max(BUNDLE_END.len())));
+        let bundle = read_bundle(&text).unwrap();
+        assert_eq!(bundle.fragments, fragments);
+        let mut written = Vec::new();
+        fragments::write_fragments(&fragments, Format::Lines, &mut written).unwrap();
+        assert_eq!(bundle.length, written.len());
+        assert_eq!(fragments::parse_fragments(&text).unwrap(), fragments);
+
+        assert!(read_bundle(&text.replace(BUNDLE_END, "")).is_err());
+        let lines: Vec<&str> = text.lines().collect();
+        let mut altered = lines[1].to_string();
+        let last = if altered.ends_with('A') { "B" } else { "A" };
+        altered.replace_range(altered.len() - 1.., last);
+        assert!(read_bundle(&text.replace(lines[1], &altered)).is_err());
+    }
+}
@@ -0,0 +1,187 @@
+use std::io::{self, Write};
+
+use crate::container::Header;
+use crate::crypto::Share;
+use crate::error::ArgumentError;
+use crate::fragments::Fragment;
+
+// Escape sequence that clears the terminal and moves the cursor home
+const CLEAR_SCREEN: &str = "\x1b[2J\x1b[H";
+
+/// The state of a recovery ceremony, where the holders enter
+/// their shares one by one in the same terminal.
+pub struct Ceremony<'a> {
+    header: &'a Header,
+    fragments: Vec<Fragment>,
+}
+
+impl<'a> Ceremony<'a> {
+    /// Creates a ceremony to recover the key of a file.
+    ///
+    /// # Parameters
+    ///
+    /// - header: The header of the encrypted file.
+    pub fn new(header: &'a Header) -> Self {
+        Ceremony {
+            header,
+            fragments: Vec::new(),
+        }
+    }
+
+    /// Returns the shares accepted so far.
+    pub fn fragments(&self) -> &[Fragment] {
+        &self.fragments
+    }
+
+    /// Returns whether enough shares were accepted to try to
+    /// recover the key, always true if the file does not store
+    /// how many are needed.
+    pub fn ready(&self) -> bool {
+        match self.header.scheme {
+            Some((threshold, _)) => self.fragments.len() >= threshold,
+            None => !self.fragments.is_empty(),
+        }
+    }
+
+    /// Checks a share entered by a holder and accepts it if valid.
+    ///
+    /// # Parameters
+    ///
+    /// - line: The line of the share, as in a shares file.
+    ///
+    /// # Returns
+    ///
+    /// The accepted fragment.
+    ///
+    /// # Errors
+    ///
+    /// An ArgumentError if the line is not a share, if its holder is
+    /// not a holder of the file or if the share was already entered.
+    pub fn enter(&mut self, line: &str) -> Result<&Fragment, ArgumentError> {
+        let fragment =
+            Fragment::parse(line.trim()).map_err(|_| ArgumentError("not a share".into()))?;
+        let Share(x, y) = &fragment.share;
+        let is_number = |s: &str| s.chars().all(|c| c.is_ascii_alphanumeric());
+        if !x.split('/').all(|x| !x.is_empty() && is_number(x)) || !is_number(y) {
+            return Err(ArgumentError("not a share".into()));
+        }
+        if let Some(holder) = &fragment.holder {
+            if !self.header.holders.is_empty() && !self.header.holders.contains(holder) {
+                return Err(ArgumentError(format!(
+                    "'{}' is not a holder of this file",
+                    holder
+                )));
+            }
+        } else if self.header.policy.is_some() {
+            return Err(ArgumentError(
+                "the share does not name its holder, required by the policy".into(),
+            ));
+        }
+        for entered in &self.fragments {
+            if entered.share.0 == fragment.share.0
+                || (entered.holder.is_some() && entered.holder == fragment.holder)
+            {
+                return Err(ArgumentError("the share was already entered".into()));
+            }
+        }
+        self.fragments.push(fragment);
+        Ok(self.fragments.last().unwrap())
+    }
+
+    /// Draws the state of the ceremony, clearing the terminal first.
+    ///
+    /// # Parameters
+    ///
+    /// - writer: The terminal where the state is drawn.
+    /// - message: The result of the last action, shown last.
+    ///
+    /// # Errors
+    ///
+    /// This method returns an error if an error occurs while writing.
+    pub fn render(&self, writer: &mut impl Write, message: &str) -> io::Result<()> {
+        write!(writer, "{}", CLEAR_SCREEN)?;
+        writeln!(
+            writer,
+            "Recovery of {}",
+            String::from_utf8_lossy(&self.header.file_name)
+        )?;
+        if let Some(policy) = &self.header.policy {
+            writeln!(writer, "policy: {}", policy)?;
+        }
+        writeln!(writer)?;
+        for holder in &self.header.holders {
+            let entered = self
+                .fragments
+                .iter()
+                .any(|f| f.holder.as_deref() == Some(holder));
+            writeln!(writer, "  [{}] {}", if entered { "x" } else { " " }, holder)?;
+        }
+        match self.header.scheme {
+            Some((threshold, total)) => writeln!(
+                writer,
+                "\nshares entered: {} ({} of {} needed)",
+                self.fragments.len(),
+                threshold,
+                total
+            )?,
+            None => writeln!(writer, "\nshares entered: {}", self.fragments.len())?,
+        }
+        writeln!(writer, "{}", message)?;
+        writer.flush()
+    }
+}
+
+/////////////////////////////////
+// Unit testing of the module. //
+/////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::CipherSuite;
+
+    fn header() -> Header {
+        Header::new(
+            b"msg.txt".to_vec(),
+            vec!["alice".into(), "bob".into(), "carol".into()],
+            2,
+            3,
+            CipherSuite::Aes256Gcm,
+        )
+    }
+
+    #[test]
+    fn enter_shares() {
+        let header = header();
+        let mut ceremony = Ceremony::new(&header);
+        assert!(ceremony.enter("1a:2b").is_ok());
+        assert!(!ceremony.ready());
+        assert!(ceremony.enter("garbage").is_err());
+        assert!(ceremony.enter("1a:3c:holder=bob").is_err());
+        assert!(ceremony.enter("4d:3c:holder=mallory").is_err());
+        assert!(ceremony.enter("4d:3-c:holder=bob").is_err());
+        assert_eq!(
+            ceremony.enter(" 4d:3c:holder=bob\n").unwrap().holder,
+            Some("bob".into())
+        );
+        assert!(ceremony.enter("5e:3c:holder=bob").is_err());
+        assert!(ceremony.ready());
+        assert_eq!(ceremony.fragments().len(), 2);
+    }
+
+    #[test]
+    fn render_state() {
+        let header = header();
+        let mut ceremony = Ceremony::new(&header);
+        ceremony.enter("4d:3c:holder=bob").unwrap();
+        let mut screen = Vec::new();
+        ceremony
+            .render(&mut screen, "share of bob accepted")
+            .unwrap();
+        let screen = String::from_utf8(screen).unwrap();
+        assert!(screen.starts_with(CLEAR_SCREEN));
+        assert!(screen.contains("  [ ] alice\n  [x] bob\n  [ ] carol\n"));
+        assert!(screen.contains("shares entered: 1 (2 of 3 needed)"));
+        assert!(screen.ends_with("share of bob accepted\n"));
+    }
+}
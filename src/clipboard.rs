@@ -0,0 +1,225 @@
+use std::env;
+use std::io::{self, Read, Write};
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::Duration;
+
+// Programs that write their input to the system clipboard, with
+// their arguments, in the order they are tried
+const CLIPBOARD_TOOLS: &[(&str, &[&str])] = &[
+    ("wl-copy", &[]),
+    ("xclip", &["-selection", "clipboard"]),
+    ("xsel", &["--clipboard", "--input"]),
+    ("pbcopy", &[]),
+    ("clip", &[]),
+];
+
+// Programs that print the content of the system clipboard, in the
+// same order as the ones that write it
+const PASTE_TOOLS: &[(&str, &[&str])] = &[
+    ("wl-paste", &["--no-newline"]),
+    ("xclip", &["-selection", "clipboard", "-out"]),
+    ("xsel", &["--clipboard", "--output"]),
+    ("pbpaste", &[]),
+    (
+        "powershell",
+        &["-NoProfile", "-Command", "Get-Clipboard -Raw"],
+    ),
+];
+
+/// Places text in the system clipboard, using the first
+/// clipboard program installed.
+///
+/// # Parameters
+///
+/// - text: The text to copy.
+///
+/// # Errors
+///
+/// An error of kind NotFound if no clipboard program is installed,
+/// or an error if the program fails.
+pub fn copy(text: &str) -> io::Result<()> {
+    copy_with(CLIPBOARD_TOOLS, text)
+}
+
+/// Places text in the system clipboard and returns at once, leaving
+/// another process of this program to empty the clipboard once the
+/// timeout passes, if it still holds the text.
+///
+/// The process is detached, so the clipboard is emptied even if this
+/// one is interrupted, and it is only given the BLAKE3 hash of the
+/// text, through its standard input, so it can tell whether the user
+/// copied something else meanwhile, which is left in place.
+///
+/// # Parameters
+///
+/// - text: The text to copy.
+/// - timeout: How long the text stays in the clipboard.
+///
+/// # Errors
+///
+/// This method returns an error if the text cannot be copied or the
+/// process that empties the clipboard cannot be started, in which
+/// case the clipboard is emptied at once.
+pub fn copy_for(text: &str, timeout: Duration) -> io::Result<()> {
+    copy(text)?;
+    if let Err(e) = spawn_clear(text, timeout) {
+        copy("")?;
+        return Err(e);
+    }
+    Ok(())
+}
+
+/// Empties the system clipboard once the timeout passes, if it still
+/// holds the text whose hash is given, or if it cannot be read.
+///
+/// # Parameters
+///
+/// - hash: The BLAKE3 hash of the copied text, in hexadecimal.
+/// - timeout: How long to wait before emptying the clipboard.
+///
+/// # Errors
+///
+/// An error if the clipboard cannot be emptied.
+pub fn clear_after(hash: &str, timeout: Duration) -> io::Result<()> {
+    thread::sleep(timeout);
+    match paste_with(PASTE_TOOLS) {
+        Ok(text) if hash_of(&text) != hash => Ok(()),
+        // a clipboard that cannot be read may still hold the text
+        _ => copy(""),
+    }
+}
+
+// starts a detached process of this program that empties the
+// clipboard after the timeout, given the hash of the text
+fn spawn_clear(text: &str, timeout: Duration) -> io::Result<()> {
+    let mut command = Command::new(env::current_exe()?);
+    command
+        .arg("clear-clipboard")
+        .arg(timeout.as_secs().to_string())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+    // out of the process group of the terminal, so an interrupt
+    // does not reach it
+    #[cfg(unix)]
+    std::os::unix::process::CommandExt::process_group(&mut command, 0);
+    #[cfg(windows)]
+    std::os::windows::process::CommandExt::creation_flags(
+        &mut command,
+        DETACHED_PROCESS | CREATE_NEW_PROCESS_GROUP,
+    );
+    let mut child = command.spawn()?;
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(hash_of(text).as_bytes())
+}
+
+#[cfg(windows)]
+const DETACHED_PROCESS: u32 = 0x0000_0008;
+#[cfg(windows)]
+const CREATE_NEW_PROCESS_GROUP: u32 = 0x0000_0200;
+
+// the hash of a text as copied, without the line breaks some
+// programs add at its end when printing the clipboard
+fn hash_of(text: &str) -> String {
+    blake3::hash(text.trim_end_matches(['\r', '\n']).as_bytes())
+        .to_hex()
+        .to_string()
+}
+
+// reads the output of the first program that exists
+fn paste_with(tools: &[(&str, &[&str])]) -> io::Result<String> {
+    for (program, args) in tools {
+        let mut child = match Command::new(program)
+            .args(*args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+        {
+            Ok(child) => child,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e),
+        };
+        let mut bytes = Vec::new();
+        child.stdout.take().unwrap().read_to_end(&mut bytes)?;
+        if !child.wait()?.success() {
+            return Err(io::Error::other(format!(
+                "{} could not read the clipboard",
+                program
+            )));
+        }
+        return Ok(String::from_utf8_lossy(&bytes).into_owned());
+    }
+    Err(io::Error::new(
+        io::ErrorKind::NotFound,
+        "no program to read the clipboard was found",
+    ))
+}
+
+// writes the text to the input of the first program that exists
+fn copy_with(tools: &[(&str, &[&str])], text: &str) -> io::Result<()> {
+    for (program, args) in tools {
+        let mut child = match Command::new(program)
+            .args(*args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+        {
+            Ok(child) => child,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e),
+        };
+        child.stdin.take().unwrap().write_all(text.as_bytes())?;
+        if !child.wait()?.success() {
+            return Err(io::Error::other(format!(
+                "{} could not copy to the clipboard",
+                program
+            )));
+        }
+        return Ok(());
+    }
+    Err(io::Error::new(
+        io::ErrorKind::NotFound,
+        "no clipboard program was found (wl-copy, xclip, xsel, pbcopy or clip)",
+    ))
+}
+
+/////////////////////////////////
+// Unit testing of the module. //
+/////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn first_program_found() {
+        let tools: &[(&str, &[&str])] = &[("shared-secrets-no-such-tool", &[]), ("cat", &[])];
+        assert!(copy_with(tools, "share").is_ok());
+        let tools: &[(&str, &[&str])] = &[("false", &[])];
+        assert!(copy_with(tools, "share").is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn paste_first_program_found() {
+        let tools: &[(&str, &[&str])] =
+            &[("shared-secrets-no-such-tool", &[]), ("echo", &["share"])];
+        let text = paste_with(tools).unwrap();
+        assert_eq!(hash_of(&text), hash_of("share"));
+        assert_ne!(hash_of(&text), hash_of("other"));
+    }
+
+    #[test]
+    fn no_program_found() {
+        let tools: &[(&str, &[&str])] = &[("shared-secrets-no-such-tool", &[])];
+        let error = copy_with(tools, "share").unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::NotFound);
+    }
+}
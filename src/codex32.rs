@@ -0,0 +1,338 @@
+use std::error::Error;
+
+use crate::bech32::{self, to_bytes, to_values, value_of, CHARSET};
+use crate::error::{ArgumentError, CorruptFileError};
+
+// Human readable part and separator of every codex32 string
+const PREFIX: &str = "ms1";
+
+// Value of the share index of the unshared secret, the character s
+const SECRET_INDEX: u8 = 16;
+
+// Share indices given to the shares, in order
+const SHARE_INDICES: &[u8] = b"acdefghjklmnpqrtuvwxyz023456789";
+
+// Length of the threshold, identifier and share index
+const HEADER_LEN: usize = 6;
+
+// Length of the checksum of a short codex32 string
+const CHECKSUM_LEN: usize = 13;
+
+// Generator of the BCH code of the checksum
+const GENERATOR: [u128; 5] = [
+    0x19dc500ce73fde210,
+    0x1bfae00def77fe529,
+    0x1fbd920fffe7bee52,
+    0x1739640bdeee3fdad,
+    0x07729a039cfc75f5a,
+];
+
+// Residue of a valid checksum
+const TARGET: u128 = 0x10ce0795c2fd1e62a;
+
+/// Minimum size in bytes of a master seed.
+pub const MIN_SEED_SIZE: usize = 16;
+
+/// Maximum size in bytes of a master seed, the largest that
+/// fits in a short codex32 string.
+pub const MAX_SEED_SIZE: usize = 32;
+
+/// Maximum number of shares of a master seed.
+pub const MAX_SHARES: usize = 31;
+
+/// Splits a BIP-32 master seed into codex32 strings, as
+/// specified in BIP-93.
+///
+/// The secret is shared character by character in GF(32), so the
+/// shares can be checked and combined by hand with the codex32
+/// volvelles or by any wallet that supports BIP-93.
+///
+/// # Parameters
+///
+/// - seed: The master seed.
+/// - n: The total number of shares to return.
+/// - k: The minimum number of shares to recover the seed (2 <= k <= 9).
+///
+/// # Returns
+///
+/// The n shares, with a random identifier common to all of them.
+///
+/// # Errors
+///
+/// An ArgumentError if the seed does not have between MIN_SEED_SIZE
+/// and MAX_SEED_SIZE bytes, if k is not in the range 2 <= k <= 9,
+/// if n is lower than k or greater than MAX_SHARES, or if no random
+/// numbers can be generated.
+pub fn split(seed: &[u8], n: usize, k: usize) -> Result<Vec<String>, ArgumentError> {
+    if seed.len() < MIN_SEED_SIZE || seed.len() > MAX_SEED_SIZE {
+        return Err(ArgumentError(format!(
+            "a codex32 seed must have between {} and {} bytes",
+            MIN_SEED_SIZE, MAX_SEED_SIZE
+        )));
+    }
+    if !(2..=9).contains(&k) || k > n || n > MAX_SHARES {
+        return Err(ArgumentError(format!(
+            "codex32 requires 2 <= K <= 9 and K <= N <= {}",
+            MAX_SHARES
+        )));
+    }
+    let payload = to_values(seed);
+    let mut random = vec![0u8; 4 + (k - 1) * payload.len()];
+    getrandom::getrandom(&mut random)
+        .map_err(|_| ArgumentError("Error while generating random numbers".into()))?;
+    let mut random = random.into_iter().map(|b| b & 31);
+    let mut header = vec![value_of(b'0' + k as u8).unwrap()];
+    header.extend(random.by_ref().take(4));
+    // the secret and k - 1 random shares determine the rest
+    let mut known = vec![with_checksum(&header, SECRET_INDEX, &payload)];
+    for &index in &SHARE_INDICES[..k - 1] {
+        let payload: Vec<u8> = random.by_ref().take(payload.len()).collect();
+        known.push(with_checksum(&header, value_of(index).unwrap(), &payload));
+    }
+    Ok(SHARE_INDICES[..n]
+        .iter()
+        .map(|&index| {
+            let index = value_of(index).unwrap();
+            match known.iter().find(|data| data[HEADER_LEN - 1] == index) {
+                Some(data) => to_string(data),
+                None => to_string(&interpolate(&known, index)),
+            }
+        })
+        .collect())
+}
+
+/// Recovers a master seed from codex32 strings.
+///
+/// The strings are read in either case, and the shares must
+/// have the same identifier.
+///
+/// # Parameters
+///
+/// - shares: At least k shares of the seed, or its unshared secret.
+///
+/// # Returns
+///
+/// The master seed.
+///
+/// # Errors
+///
+/// A CorruptFileError if a string is not a valid codex32 string or
+/// its checksum does not match, or an ArgumentError if the shares
+/// are not of the same seed or are not enough to recover it.
+pub fn combine(shares: &[&str]) -> Result<Vec<u8>, Box<dyn Error>> {
+    let shares = shares
+        .iter()
+        .map(|share| parse(share))
+        .collect::<Result<Vec<_>, _>>()?;
+    let first = match shares.first() {
+        Some(first) => first,
+        None => return Err(Box::new(ArgumentError("no shares were given".into()))),
+    };
+    let secret = match shares
+        .iter()
+        .find(|data| data[HEADER_LEN - 1] == SECRET_INDEX)
+    {
+        Some(secret) => secret.clone(),
+        None => {
+            let k = (CHARSET[first[0] as usize] - b'0') as usize;
+            for (i, data) in shares.iter().enumerate() {
+                if data.len() != first.len() || data[..HEADER_LEN - 1] != first[..HEADER_LEN - 1] {
+                    return Err(Box::new(ArgumentError(format!(
+                        "share {} is not of the same seed",
+                        to_string(data)
+                    ))));
+                }
+                if shares[..i]
+                    .iter()
+                    .any(|other| other[HEADER_LEN - 1] == data[HEADER_LEN - 1])
+                {
+                    return Err(Box::new(ArgumentError(format!(
+                        "share {} was given twice",
+                        to_string(data)
+                    ))));
+                }
+            }
+            if k == 0 || shares.len() < k {
+                return Err(Box::new(ArgumentError(format!(
+                    "{} shares are required but {} were given",
+                    k,
+                    shares.len()
+                ))));
+            }
+            interpolate(&shares[..k], SECRET_INDEX)
+        }
+    };
+    Ok(to_bytes(&secret[HEADER_LEN..secret.len() - CHECKSUM_LEN]))
+}
+
+// reads the values of the data part of a codex32 string,
+// checking its checksum
+fn parse(share: &str) -> Result<Vec<u8>, CorruptFileError> {
+    let share = share.trim();
+    let corrupt = || CorruptFileError(format!("'{}' is not a codex32 string", share));
+    if share != share.to_lowercase() && share != share.to_uppercase() {
+        return Err(corrupt());
+    }
+    let lowercase = share.to_lowercase();
+    let data = lowercase.strip_prefix(PREFIX).ok_or_else(corrupt)?;
+    let data = data
+        .bytes()
+        .map(value_of)
+        .collect::<Option<Vec<u8>>>()
+        .ok_or_else(corrupt)?;
+    let payload_len = data.len().saturating_sub(HEADER_LEN + CHECKSUM_LEN);
+    let threshold = CHARSET[*data.first().ok_or_else(corrupt)? as usize];
+    if payload_len * 5 / 8 < MIN_SEED_SIZE
+        || payload_len * 5 / 8 > MAX_SEED_SIZE
+        || !(threshold == b'0' || (b'2'..=b'9').contains(&threshold))
+    {
+        return Err(corrupt());
+    }
+    if threshold == b'0' && data[HEADER_LEN - 1] != SECRET_INDEX {
+        return Err(corrupt());
+    }
+    if polymod(&data) != TARGET {
+        return Err(CorruptFileError(format!(
+            "the checksum of '{}' does not match, check every character",
+            share
+        )));
+    }
+    Ok(data)
+}
+
+// appends the checksum to the header, index and payload
+fn with_checksum(header: &[u8], index: u8, payload: &[u8]) -> Vec<u8> {
+    let mut data = header.to_vec();
+    data.push(index);
+    data.extend_from_slice(payload);
+    let residue = polymod(&[&data[..], &[0; CHECKSUM_LEN]].concat()) ^ TARGET;
+    data.extend((0..CHECKSUM_LEN).map(|i| ((residue >> (5 * (CHECKSUM_LEN - 1 - i))) & 31) as u8));
+    data
+}
+
+// computes the residue of the BCH code of the checksum
+fn polymod(values: &[u8]) -> u128 {
+    bech32::polymod(values, 0x23181b3, &GENERATOR, CHECKSUM_LEN)
+}
+
+// evaluates at x the polynomial through the shares, character by
+// character, using the share index of each one as its point
+fn interpolate(shares: &[Vec<u8>], x: u8) -> Vec<u8> {
+    let indices: Vec<u8> = shares.iter().map(|data| data[HEADER_LEN - 1]).collect();
+    let weights: Vec<u8> = indices
+        .iter()
+        .map(|&i| {
+            let mut numerator = 1;
+            let mut denominator = 1;
+            for &j in indices.iter().filter(|&&j| j != i) {
+                numerator = multiply(numerator, x ^ j);
+                denominator = multiply(denominator, i ^ j);
+            }
+            multiply(numerator, inverse(denominator))
+        })
+        .collect();
+    (0..shares[0].len())
+        .map(|position| {
+            shares.iter().zip(&weights).fold(0, |sum, (data, &weight)| {
+                sum ^ multiply(weight, data[position])
+            })
+        })
+        .collect()
+}
+
+// multiplies two elements of GF(32), modulo x^5 + x^3 + 1
+fn multiply(mut a: u8, b: u8) -> u8 {
+    let mut product = 0;
+    for i in 0..5 {
+        if (b >> i) & 1 == 1 {
+            product ^= a;
+        }
+        a <<= 1;
+        if a & 32 != 0 {
+            a ^= 41;
+        }
+    }
+    product
+}
+
+// returns the multiplicative inverse of a non zero element of GF(32)
+fn inverse(a: u8) -> u8 {
+    (1..32).find(|&b| multiply(a, b) == 1).unwrap()
+}
+
+// writes the data part of a codex32 string after its prefix
+fn to_string(data: &[u8]) -> String {
+    let data: String = data.iter().map(|&v| CHARSET[v as usize] as char).collect();
+    format!("{}{}", PREFIX, data)
+}
+
+/////////////////////////////////
+// Unit testing of the module. //
+/////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn secret_vector() {
+        // test vector 1 of BIP-93
+        let seed = combine(&["ms10testsxxxxxxxxxxxxxxxxxxxxxxxxxx4nzvca9cmczlw"]).unwrap();
+        let mut expected = [0x31, 0x8c, 0x63, 0x18, 0xc6].repeat(4);
+        expected.truncate(16);
+        assert_eq!(seed, expected);
+    }
+
+    #[test]
+    fn shares_vector() {
+        // test vector 2 of BIP-93
+        let shares = [
+            "MS12NAMEA320ZYXWVUTSRQPNMLKJHGFEDCAXRPP870HKKQRM",
+            "MS12NAMECACDEFGHJKLMNPQRSTUVWXYZ023FTR2GDZMPY6PN",
+        ];
+        let seed = [
+            0xd1, 0x80, 0x8e, 0x09, 0x6b, 0x35, 0xb2, 0x09, 0xca, 0x12, 0x13, 0x2b, 0x26, 0x46,
+            0x62, 0xa5,
+        ];
+        assert_eq!(combine(&shares).unwrap(), seed);
+        let data: Vec<Vec<u8>> = shares.iter().map(|s| parse(s).unwrap()).collect();
+        assert_eq!(
+            to_string(&interpolate(&data, SECRET_INDEX)),
+            "ms12names6xqguzttxkeqnjsjzv4jv3nz5k3kwgsphuh6evw"
+        );
+    }
+
+    #[test]
+    fn split_and_combine() {
+        let seed = [0x5au8; 32];
+        let shares = split(&seed, 5, 3).unwrap();
+        assert_eq!(shares.len(), 5);
+        assert!(shares.iter().all(|share| share[..4] == *"ms13"));
+        assert!(shares.iter().all(|share| share[4..8] == shares[0][4..8]));
+        let uppercase = shares[3].to_uppercase();
+        let some: Vec<&str> = vec![&shares[4], &uppercase, &shares[1]];
+        assert_eq!(combine(&some).unwrap(), seed);
+        assert!(combine(&[&shares[0], &shares[1]]).is_err());
+        assert!(combine(&[&shares[0], &shares[0], &shares[1]]).is_err());
+    }
+
+    #[test]
+    fn invalid_shares() {
+        let valid = "ms10testsxxxxxxxxxxxxxxxxxxxxxxxxxx4nzvca9cmczlw";
+        assert!(combine(&[&valid.replace("4nz", "4ny")]).is_err());
+        assert!(combine(&[&valid.replace("ms10test", "ms10TEST")]).is_err());
+        assert!(combine(&["ms10testsxxxx"]).is_err());
+        assert!(combine(&[]).is_err());
+        assert!(split(&[0u8; 15], 3, 2).is_err());
+        assert!(split(&[0u8; 16], 3, 1).is_err());
+        assert!(split(&[0u8; 16], 32, 2).is_err());
+    }
+
+    #[test]
+    fn field_arithmetic() {
+        for a in 1..32 {
+            assert_eq!(multiply(a, inverse(a)), 1);
+            assert_eq!(multiply(a, 1), a);
+        }
+    }
+}
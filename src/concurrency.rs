@@ -0,0 +1,110 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+use crate::error::ArgumentError;
+
+// Number of threads set by the program, see Concurrency::set_global;
+// zero until it is set
+static THREADS: AtomicUsize = AtomicUsize::new(0);
+
+/// How many threads the work that can be split, the generation of
+/// many shares, the interpolation of many shares and the encryption
+/// of the chunks of a file, is split into.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Concurrency {
+    threads: usize,
+}
+
+impl Concurrency {
+    /// Returns a concurrency of the given number of threads.
+    ///
+    /// # Parameters
+    ///
+    /// - threads: The number of threads, at least 1.
+    ///
+    /// # Errors
+    ///
+    /// An ArgumentError if the number of threads is 0.
+    pub fn new(threads: usize) -> Result<Self, ArgumentError> {
+        if threads == 0 {
+            return Err(ArgumentError(
+                "the number of threads must be greater than 0".into(),
+            ));
+        }
+        Ok(Concurrency { threads })
+    }
+
+    /// Returns a concurrency of one thread for each processor, or of
+    /// a single thread if their number is unknown.
+    pub fn available() -> Self {
+        Concurrency {
+            threads: thread::available_parallelism().map_or(1, |n| n.get()),
+        }
+    }
+
+    /// Parses the number of threads of a command line argument.
+    ///
+    /// # Parameters
+    ///
+    /// - threads: The value of the argument, None for the available
+    ///   concurrency.
+    ///
+    /// # Errors
+    ///
+    /// An ArgumentError if the value is not a number greater than 0.
+    pub fn parse(threads: Option<&str>) -> Result<Self, ArgumentError> {
+        match threads {
+            Some(threads) => {
+                Concurrency::new(threads.parse().map_err(|_| {
+                    ArgumentError(format!("invalid number of threads '{}'", threads))
+                })?)
+            }
+            None => Ok(Concurrency::available()),
+        }
+    }
+
+    /// Returns the number of threads.
+    pub fn threads(self) -> usize {
+        self.threads
+    }
+
+    /// Returns the concurrency of the program, the available one
+    /// until set_global is called.
+    pub fn global() -> Self {
+        match THREADS.load(Ordering::Relaxed) {
+            0 => Concurrency::available(),
+            threads => Concurrency { threads },
+        }
+    }
+
+    /// Sets this concurrency as the one of the program, used by
+    /// the operations that are not given one.
+    pub fn set_global(self) {
+        THREADS.store(self.threads, Ordering::Relaxed);
+    }
+}
+
+impl Default for Concurrency {
+    fn default() -> Self {
+        Concurrency::available()
+    }
+}
+
+/////////////////////////////////
+// Unit testing of the module. //
+/////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_threads() {
+        assert_eq!(Concurrency::parse(Some("3")).unwrap().threads(), 3);
+        assert_eq!(Concurrency::parse(None).unwrap(), Concurrency::available());
+        assert!(Concurrency::available().threads() >= 1);
+        assert!(Concurrency::parse(Some("0")).is_err());
+        assert!(Concurrency::parse(Some("many")).is_err());
+        assert!(Concurrency::new(0).is_err());
+    }
+}
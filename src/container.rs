@@ -0,0 +1,887 @@
+use std::ffi::{OsStr, OsString};
+use std::io::{self, BufRead, Read, Write};
+#[cfg(unix)]
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+
+use crate::crypto::error::CipherError;
+use crate::crypto::key::MAX_PASSWORD_LOG_N;
+use crate::crypto::{self, Cipher, CipherSuite};
+use crate::error::CorruptFileError;
+
+/// Bytes every encrypted file produced by this program starts with.
+pub const MAGIC: &[u8; 8] = b"SHSECRET";
+
+/// Version of the encrypted file format written by this program.
+///
+/// Files of version 0, written before the header was introduced,
+/// start with the name of the encrypted file in a line followed by
+/// the ciphertext as a single AES-GCM block, and are only read to
+/// migrate them. Version 1 files start with the header: the name of
+/// the file, the holders, the scheme, the cipher suite, the digest
+/// and the length of the plaintext if they were stored, the policy
+/// and the version of the program, all authenticated with the
+/// ciphertext; then KEY_SLOTS key slots, each with the data key
+/// wrapped for an unlock method and the cost and the salt of scrypt
+/// when the key of the slot is derived from a password, and the data
+/// key wrapped for each recovery agent. The content is encrypted
+/// with the random data key in the chunked format, with a commitment
+/// to the key.
+///
+/// Every length and count is a little-endian integer of a fixed
+/// size: u32 for the strings and the scheme, u64 for the length of
+/// the plaintext and the indexes of the chunks, so files larger
+/// than 4 GiB are read the same on 32-bit and 64-bit platforms.
+pub const FORMAT_VERSION: u8 = 1;
+
+/// Number of key slots of a file.
+pub const KEY_SLOTS: usize = 4;
+
+/// Largest number of recovery agents of a file.
+pub const MAX_RECOVERY_SLOTS: usize = 16;
+
+/// Size in bytes of an X25519 public key.
+pub const PUBLIC_KEY_SIZE: usize = 32;
+
+// Longest first line of a file without header that is read as the
+// name of the encrypted file, the longest name most systems allow
+const MAX_LEGACY_NAME: usize = 255;
+
+/// Size in bytes of the salt of the key of a password.
+pub const PASSWORD_SALT_SIZE: usize = 16;
+
+/// Size in bytes of the BLAKE3 digest of the plaintext.
+pub const DIGEST_SIZE: usize = 32;
+
+/// Metadata stored at the beginning of an encrypted file,
+/// before the ciphertext.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Header {
+    /// Version of the format of the file, 0 for files
+    /// written before the header was introduced.
+    pub version: u8,
+    /// Name of the file that was encrypted, as stored in the
+    /// file, which is not necessarily valid UTF-8.
+    pub file_name: Vec<u8>,
+    /// Names of the people the shares were generated for, empty
+    /// if no holders were given when encrypting.
+    pub holders: Vec<String>,
+    /// Minimum number of shares needed to decrypt the file and
+    /// total number of shares, None for files of version 0.
+    pub scheme: Option<(usize, usize)>,
+    /// The AEAD algorithm the file was encrypted with.
+    pub suite: CipherSuite,
+    /// BLAKE3 digest of the plaintext, if it was stored.
+    pub digest: Option<[u8; DIGEST_SIZE]>,
+    /// The policy the key recovered by the shares was split with,
+    /// see Policy, or None if it was split in k of n shares.
+    pub policy: Option<String>,
+    /// Length in bytes of the plaintext, checked when decrypting,
+    /// or None if it was not known before encrypting, as when the
+    /// input is a FIFO.
+    pub length: Option<u64>,
+    /// Version of the program that wrote the file, None for files
+    /// of version 0.
+    pub program_version: Option<String>,
+    /// The data key of the file wrapped for each unlock method,
+    /// empty for files of version 0, whose key the shares recover.
+    ///
+    /// The slots are not part of the associated data, so they can
+    /// be replaced without encrypting the file again.
+    pub key_slots: Vec<KeySlot>,
+    /// The data key wrapped for each recovery agent. Like the key
+    /// slots, they are not part of the associated data.
+    pub recovery_slots: Vec<RecoverySlot>,
+}
+
+/// The secrets that can unlock an encrypted file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnlockMethod {
+    /// The key recovered from the shares, which is also the key
+    /// of the password given when encrypting.
+    Shares,
+    /// A password.
+    Password,
+    /// The content of a key file.
+    Keyfile,
+}
+
+// Every method, in the order of their identifiers
+const UNLOCK_METHODS: [UnlockMethod; 3] = [
+    UnlockMethod::Shares,
+    UnlockMethod::Password,
+    UnlockMethod::Keyfile,
+];
+
+impl UnlockMethod {
+    /// Returns the identifier of this method stored in a key slot.
+    pub fn id(self) -> u8 {
+        match self {
+            UnlockMethod::Shares => 1,
+            UnlockMethod::Password => 2,
+            UnlockMethod::Keyfile => 3,
+        }
+    }
+
+    /// Returns the method with the given identifier.
+    ///
+    /// # Parameters
+    ///
+    /// - id: The identifier stored in a key slot.
+    ///
+    /// # Errors
+    ///
+    /// A CorruptFileError if there is no method with the identifier.
+    pub fn from_id(id: u8) -> Result<Self, CorruptFileError> {
+        UNLOCK_METHODS
+            .iter()
+            .copied()
+            .find(|method| method.id() == id)
+            .ok_or_else(|| CorruptFileError(format!("unknown unlock method {}", id)))
+    }
+
+    /// Returns the name of this method as given in the command line.
+    pub fn name(self) -> &'static str {
+        match self {
+            UnlockMethod::Shares => "shares",
+            UnlockMethod::Password => "password",
+            UnlockMethod::Keyfile => "keyfile",
+        }
+    }
+
+    /// Returns the method with the given name.
+    ///
+    /// # Parameters
+    ///
+    /// - name: The name of the method.
+    pub fn from_name(name: &str) -> Option<Self> {
+        UNLOCK_METHODS
+            .iter()
+            .copied()
+            .find(|method| method.name() == name)
+    }
+}
+
+/// The data key of a file wrapped for an unlock method.
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeySlot {
+    /// The method whose key wraps the data key.
+    pub method: UnlockMethod,
+    /// How the key was derived from a password, None if it is the
+    /// SHA-256 of the password, as in the files migrated from version
+    /// 0, or the key of a key file.
+    pub kdf: Option<PasswordKdf>,
+    /// The wrapped data key, see Cipher::wrap_key.
+    pub wrapped_key: Vec<u8>,
+}
+
+/// How the key of a key slot is derived from a password: scrypt with
+/// a cost and a random salt of its own.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PasswordKdf {
+    /// Base 2 logarithm of the cost N of scrypt.
+    pub log_n: u8,
+    /// The salt of the key.
+    pub salt: [u8; PASSWORD_SALT_SIZE],
+}
+
+impl PasswordKdf {
+    /// Creates the derivation of the key of a new password, with the
+    /// cost crypto::key::PASSWORD_LOG_N and a random salt.
+    ///
+    /// # Errors
+    ///
+    /// A CipherError if the salt cannot be generated.
+    pub fn generate() -> Result<Self, CipherError> {
+        let mut salt = [0u8; PASSWORD_SALT_SIZE];
+        getrandom::getrandom(&mut salt)
+            .map_err(|_| CipherError("Error while generating the salt of a password".into()))?;
+        Ok(PasswordKdf {
+            log_n: crypto::key::PASSWORD_LOG_N,
+            salt,
+        })
+    }
+
+    /// Returns the cipher of the key of a password.
+    ///
+    /// # Parameters
+    ///
+    /// - password: The password.
+    /// - suite: The AEAD algorithm of the file.
+    pub fn cipher(&self, password: &str, suite: CipherSuite) -> Cipher {
+        Cipher::from_password(password, &self.salt, self.log_n, suite)
+    }
+}
+
+/// The data key of a file wrapped for a recovery agent, with a key
+/// agreed by X25519 between an ephemeral key and the key of the
+/// agent, see escrow::wrap.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecoverySlot {
+    /// The X25519 public key of the recovery agent.
+    pub public_key: [u8; PUBLIC_KEY_SIZE],
+    /// The X25519 public key of the ephemeral key of the slot.
+    pub ephemeral_key: [u8; PUBLIC_KEY_SIZE],
+    /// The wrapped data key, see Cipher::wrap_key.
+    pub wrapped_key: Vec<u8>,
+}
+
+impl Header {
+    /// Creates a new header.
+    ///
+    /// # Parameters
+    ///
+    /// - file_name: Name of the file that was encrypted.
+    /// - holders: Names of the holders of the shares.
+    /// - threshold: Minimum number of shares needed to decrypt the file.
+    /// - total: Total number of shares.
+    /// - suite: The AEAD algorithm used to encrypt the file.
+    ///
+    /// # Returns
+    ///
+    /// A header with the given metadata.
+    pub fn new(
+        file_name: Vec<u8>,
+        holders: Vec<String>,
+        threshold: usize,
+        total: usize,
+        suite: CipherSuite,
+    ) -> Self {
+        Header {
+            version: FORMAT_VERSION,
+            file_name,
+            holders,
+            scheme: Some((threshold, total)),
+            suite,
+            digest: None,
+            policy: None,
+            length: None,
+            program_version: Some(crate::VERSION.into()),
+            key_slots: Vec::new(),
+            recovery_slots: Vec::new(),
+        }
+    }
+
+    /// Writes this header.
+    ///
+    /// The key slots and the recovery slots are written last, after
+    /// the associated data.
+    ///
+    /// # Parameters
+    ///
+    /// - writer: The writer where the header is written.
+    ///
+    /// # Errors
+    ///
+    /// This method returns an error if an error occurs while writing.
+    pub fn write_to(&self, writer: &mut impl Write) -> io::Result<()> {
+        self.write_authenticated(writer)?;
+        self.write_key_slots(writer)?;
+        writer.write_all(&(self.recovery_slots.len() as u32).to_le_bytes())?;
+        for slot in &self.recovery_slots {
+            writer.write_all(&slot.public_key)?;
+            writer.write_all(&slot.ephemeral_key)?;
+            writer.write_all(&slot.wrapped_key)?;
+        }
+        Ok(())
+    }
+
+    /// Writes the key slots of this header, which can be written
+    /// again at key_slots_offset to change the unlock methods.
+    ///
+    /// There are always KEY_SLOTS slots, the ones not in use filled
+    /// with zeros. Each slot stores the cost and the salt of scrypt
+    /// after its method, zeros when the key was not derived with
+    /// scrypt.
+    ///
+    /// # Parameters
+    ///
+    /// - writer: The writer where the slots are written.
+    ///
+    /// # Errors
+    ///
+    /// This method returns an error if an error occurs while writing.
+    pub fn write_key_slots(&self, writer: &mut impl Write) -> io::Result<()> {
+        // the method, the cost and the salt, and the wrapped key
+        let size = 1 + 1 + PASSWORD_SALT_SIZE + crypto::wrapped_key_size(self.suite);
+        for index in 0..KEY_SLOTS {
+            match self.key_slots.get(index) {
+                Some(slot) => {
+                    let kdf = slot.kdf.unwrap_or(PasswordKdf {
+                        log_n: 0,
+                        salt: [0; PASSWORD_SALT_SIZE],
+                    });
+                    writer.write_all(&[slot.method.id(), kdf.log_n])?;
+                    writer.write_all(&kdf.salt)?;
+                    writer.write_all(&slot.wrapped_key)?;
+                }
+                None => writer.write_all(&vec![0u8; size])?,
+            }
+        }
+        Ok(())
+    }
+
+    // writes every field but the key slots
+    fn write_authenticated(&self, writer: &mut impl Write) -> io::Result<()> {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&[self.version])?;
+        write_bytes(writer, &self.file_name)?;
+        writer.write_all(&(self.holders.len() as u32).to_le_bytes())?;
+        for holder in &self.holders {
+            write_bytes(writer, holder.as_bytes())?;
+        }
+        let (threshold, total) = self.scheme.unwrap_or((0, 0));
+        writer.write_all(&(threshold as u32).to_le_bytes())?;
+        writer.write_all(&(total as u32).to_le_bytes())?;
+        writer.write_all(&[self.suite.id()])?;
+        match &self.digest {
+            Some(digest) => {
+                writer.write_all(&[1])?;
+                writer.write_all(digest)?;
+            }
+            None => writer.write_all(&[0])?,
+        }
+        write_bytes(writer, self.policy.as_deref().unwrap_or("").as_bytes())?;
+        match self.length {
+            Some(length) => {
+                writer.write_all(&[1])?;
+                writer.write_all(&length.to_le_bytes())?;
+            }
+            None => writer.write_all(&[0])?,
+        }
+        let program_version = self.program_version.as_deref().unwrap_or("");
+        write_bytes(writer, program_version.as_bytes())
+    }
+
+    /// Returns the name under which the decrypted file is saved.
+    ///
+    /// The name stored in the header comes from the encrypted file,
+    /// so any directory in it is removed and the file is always
+    /// saved in the current directory.
+    ///
+    /// # Errors
+    ///
+    /// A CorruptFileError if the name has control characters or
+    /// does not name a file once the directories are removed.
+    pub fn output_name(&self) -> Result<OsString, CorruptFileError> {
+        let lossy = String::from_utf8_lossy(&self.file_name);
+        let invalid = || CorruptFileError(format!("invalid file name in header: {:?}", lossy));
+        if lossy.chars().any(char::is_control) {
+            return Err(invalid());
+        }
+        // both separators are removed, the file may come from another system
+        let name = self
+            .file_name
+            .rsplit(|&b| b == b'/' || b == b'\\')
+            .next()
+            .unwrap();
+        if name.is_empty() || name == b"." || name == b".." {
+            return Err(invalid());
+        }
+        Ok(bytes_to_name(name.to_vec()))
+    }
+
+    /// Returns the associated data that is authenticated
+    /// together with the ciphertext.
+    ///
+    /// This is the whole header but the key slots and the recovery
+    /// slots, so the header cannot be modified or swapped with the
+    /// header of another file without failing the decryption.
+    pub fn associated_data(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        self.write_authenticated(&mut bytes).unwrap();
+        bytes
+    }
+
+    /// Returns the position in the file of the key slots.
+    pub fn key_slots_offset(&self) -> u64 {
+        // the slots follow the associated data
+        self.associated_data().len() as u64
+    }
+
+    /// Reads a header, leaving the reader positioned at the
+    /// start of the ciphertext.
+    ///
+    /// Files written before the header was introduced, of format
+    /// version 0, are rejected, as any text whose first line is short
+    /// could be read as one; read_legacy_from reads them to migrate
+    /// them.
+    ///
+    /// # Parameters
+    ///
+    /// - reader: The reader from which the header is read.
+    ///
+    /// # Returns
+    ///
+    /// The header read.
+    ///
+    /// # Errors
+    ///
+    /// This method returns an error if an error occurs while reading
+    /// or if the header is corrupt.
+    pub fn read_from(reader: &mut impl BufRead) -> Result<Self, Box<dyn std::error::Error>> {
+        if !reader.fill_buf()?.starts_with(MAGIC) {
+            return Err(Box::new(not_encrypted()));
+        }
+        reader.consume(MAGIC.len());
+        let version = read_array::<1>(reader)?[0];
+        if version > FORMAT_VERSION {
+            return Err(Box::new(CorruptFileError(format!(
+                "the encrypted file has format version {}, this program reads version {}; \
+                 use a newer version of shared_secrets",
+                version, FORMAT_VERSION
+            ))));
+        }
+        if version != FORMAT_VERSION {
+            return Err(Box::new(CorruptFileError(
+                "encrypted file header is corrupt".into(),
+            )));
+        }
+        let file_name = read_bytes(reader)?;
+        let holders_len = u32::from_le_bytes(read_array(reader)?);
+        let holders = (0..holders_len)
+            .map(|_| read_string(reader))
+            .collect::<Result<_, _>>()?;
+        let threshold = u32::from_le_bytes(read_array(reader)?);
+        let total = u32::from_le_bytes(read_array(reader)?);
+        let scheme = Some((threshold as usize, total as usize));
+        let suite = CipherSuite::from_id(read_array::<1>(reader)?[0])?;
+        let digest = match read_array::<1>(reader)?[0] {
+            0 => None,
+            1 => Some(read_array(reader)?),
+            _ => {
+                return Err(Box::new(CorruptFileError(
+                    "encrypted file header is corrupt".into(),
+                )))
+            }
+        };
+        let policy = Some(read_string(reader)?).filter(|policy| !policy.is_empty());
+        let length = match read_array::<1>(reader)?[0] {
+            0 => None,
+            1 => Some(u64::from_le_bytes(read_array(reader)?)),
+            _ => {
+                return Err(Box::new(CorruptFileError(
+                    "encrypted file header is corrupt".into(),
+                )))
+            }
+        };
+        let program_version =
+            Some(read_string(reader)?).filter(|program_version| !program_version.is_empty());
+        let key_slots = read_key_slots(reader, suite)?;
+        let recovery_slots = read_recovery_slots(reader, suite)?;
+        Ok(Header {
+            version,
+            file_name,
+            holders,
+            scheme,
+            suite,
+            digest,
+            policy,
+            length,
+            program_version,
+            key_slots,
+            recovery_slots,
+        })
+    }
+
+    /// Reads a header like read_from, or else the first line of a
+    /// file of format version 0, which is its original file name.
+    ///
+    /// Only the migration of these files should read them: any text
+    /// whose first line is short and printable is read as one. The
+    /// line is read up to the longest name most systems allow, so
+    /// any other file is not read to the end looking for a newline.
+    ///
+    /// # Parameters
+    ///
+    /// - reader: The reader from which the header is read.
+    ///
+    /// # Returns
+    ///
+    /// The header read, of version 0 if the file has no header.
+    ///
+    /// # Errors
+    ///
+    /// This method returns an error if an error occurs while reading
+    /// or if the file has neither a header nor a file name line.
+    pub fn read_legacy_from(reader: &mut impl BufRead) -> Result<Self, Box<dyn std::error::Error>> {
+        if reader.fill_buf()?.starts_with(MAGIC) {
+            return Self::read_from(reader);
+        }
+        let mut file_name = Vec::new();
+        reader
+            .by_ref()
+            .take(MAX_LEGACY_NAME as u64 + 1)
+            .read_until(b'\n', &mut file_name)?;
+        // remove the newline char
+        if file_name.pop() != Some(b'\n')
+            || file_name.is_empty()
+            || file_name.iter().any(|&b| b < 0x20 || b == 0x7f)
+        {
+            return Err(Box::new(not_encrypted()));
+        }
+        Ok(Header {
+            version: 0,
+            file_name,
+            holders: Vec::new(),
+            scheme: None,
+            suite: CipherSuite::Aes256Gcm,
+            digest: None,
+            policy: None,
+            length: None,
+            program_version: None,
+            key_slots: Vec::new(),
+            recovery_slots: Vec::new(),
+        })
+    }
+}
+
+// the error of a file without the header of an encrypted file
+fn not_encrypted() -> CorruptFileError {
+    CorruptFileError(format!(
+        "not a shared_secrets file (accepted version {}); a file of format version 0, \
+         without header, must first be upgraded with shared_secrets migrate",
+        FORMAT_VERSION
+    ))
+}
+
+// reads the key slots after the associated data, leaving out the
+// ones not in use
+fn read_key_slots(
+    reader: &mut impl BufRead,
+    suite: CipherSuite,
+) -> Result<Vec<KeySlot>, Box<dyn std::error::Error>> {
+    let mut key_slots = Vec::new();
+    for _ in 0..KEY_SLOTS {
+        let [id, log_n] = read_array(reader)?;
+        let salt = read_array(reader)?;
+        if log_n > MAX_PASSWORD_LOG_N {
+            return Err(Box::new(CorruptFileError(format!(
+                "the cost of a key slot, 2^{}, is higher than 2^{}",
+                log_n, MAX_PASSWORD_LOG_N
+            ))));
+        }
+        let kdf = Some(PasswordKdf { log_n, salt }).filter(|kdf| kdf.log_n != 0);
+        let mut wrapped_key = vec![0u8; crypto::wrapped_key_size(suite)];
+        reader
+            .read_exact(&mut wrapped_key)
+            .map_err(|_| CorruptFileError("encrypted file header is truncated".into()))?;
+        if id != 0 {
+            key_slots.push(KeySlot {
+                method: UnlockMethod::from_id(id)?,
+                kdf,
+                wrapped_key,
+            });
+        }
+    }
+    Ok(key_slots)
+}
+
+// reads the recovery slots after the key slots, at most
+// MAX_RECOVERY_SLOTS so a corrupt count is not allocated
+fn read_recovery_slots(
+    reader: &mut impl BufRead,
+    suite: CipherSuite,
+) -> Result<Vec<RecoverySlot>, Box<dyn std::error::Error>> {
+    let count = u32::from_le_bytes(read_array(reader)?) as usize;
+    if count > MAX_RECOVERY_SLOTS {
+        return Err(Box::new(CorruptFileError(
+            "encrypted file header is corrupt".into(),
+        )));
+    }
+    let mut slots = Vec::with_capacity(count);
+    for _ in 0..count {
+        let public_key = read_array(reader)?;
+        let ephemeral_key = read_array(reader)?;
+        let mut wrapped_key = vec![0u8; crypto::wrapped_key_size(suite)];
+        reader
+            .read_exact(&mut wrapped_key)
+            .map_err(|_| CorruptFileError("encrypted file header is truncated".into()))?;
+        slots.push(RecoverySlot {
+            public_key,
+            ephemeral_key,
+            wrapped_key,
+        });
+    }
+    Ok(slots)
+}
+
+/// Returns the bytes stored in a header for a file name.
+///
+/// On Unix the bytes of the name are kept as they are, even if
+/// they are not valid UTF-8; elsewhere invalid sequences are
+/// replaced.
+///
+/// # Parameters
+///
+/// - name: The name of the file.
+pub fn name_to_bytes(name: &OsStr) -> Vec<u8> {
+    #[cfg(unix)]
+    return name.as_bytes().to_vec();
+    #[cfg(not(unix))]
+    return name.to_string_lossy().into_owned().into_bytes();
+}
+
+// the inverse of name_to_bytes
+fn bytes_to_name(bytes: Vec<u8>) -> OsString {
+    #[cfg(unix)]
+    return OsString::from_vec(bytes);
+    #[cfg(not(unix))]
+    return String::from_utf8_lossy(&bytes).into_owned().into();
+}
+
+// writes a slice of bytes prefixed by its length
+fn write_bytes(writer: &mut impl Write, bytes: &[u8]) -> io::Result<()> {
+    writer.write_all(&(bytes.len() as u32).to_le_bytes())?;
+    writer.write_all(bytes)
+}
+
+// reads a fixed number of bytes, failing if the file ends before
+fn read_array<const N: usize>(reader: &mut impl BufRead) -> Result<[u8; N], CorruptFileError> {
+    let mut array = [0u8; N];
+    reader
+        .read_exact(&mut array)
+        .map_err(|_| CorruptFileError("encrypted file header is truncated".into()))?;
+    Ok(array)
+}
+
+// reads a slice of bytes prefixed by its length
+fn read_bytes(reader: &mut impl BufRead) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let len = u32::from_le_bytes(read_array(reader)?);
+    let mut bytes = Vec::new();
+    reader.by_ref().take(len.into()).read_to_end(&mut bytes)?;
+    if bytes.len() as u64 != u64::from(len) {
+        return Err(Box::new(CorruptFileError(
+            "encrypted file header is truncated".into(),
+        )));
+    }
+    Ok(bytes)
+}
+
+// reads a string prefixed by its length
+fn read_string(reader: &mut impl BufRead) -> Result<String, Box<dyn std::error::Error>> {
+    Ok(String::from_utf8(read_bytes(reader)?)?)
+}
+
+/////////////////////////////////
+// Unit testing of the module. //
+/////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn header_round_trip() {
+        let mut header = Header::new(
+            b"msg.txt".to_vec(),
+            vec!["alice".into(), "bob".into()],
+            2,
+            3,
+            CipherSuite::Aes256GcmSiv,
+        );
+        header.digest = Some([0x42u8; DIGEST_SIZE]);
+        header.policy = Some("alice and bob".into());
+        // larger than 4 GiB, the length does not fit in 32 bits
+        header.length = Some(5 << 30);
+        header.key_slots = vec![
+            KeySlot {
+                method: UnlockMethod::Shares,
+                kdf: Some(PasswordKdf {
+                    log_n: 16,
+                    salt: [0x07u8; PASSWORD_SALT_SIZE],
+                }),
+                wrapped_key: vec![0x24u8; 60],
+            },
+            KeySlot {
+                method: UnlockMethod::Keyfile,
+                kdf: None,
+                wrapped_key: vec![0x42u8; 60],
+            },
+        ];
+        header.recovery_slots = vec![RecoverySlot {
+            public_key: [0x11u8; PUBLIC_KEY_SIZE],
+            ephemeral_key: [0x22u8; PUBLIC_KEY_SIZE],
+            wrapped_key: vec![0x33u8; 60],
+        }];
+        let mut bytes = Vec::new();
+        header.write_to(&mut bytes).unwrap();
+        bytes.extend_from_slice(b"ciphertext");
+        let mut reader = &bytes[..];
+        let read = Header::read_from(&mut reader).unwrap();
+        assert_eq!(read, header);
+        assert_eq!(read.program_version.as_deref(), Some(crate::VERSION));
+        assert_eq!(reader, b"ciphertext");
+
+        // a cost that would exhaust the memory is not trusted
+        let offset = header.key_slots_offset() as usize;
+        bytes[offset + 1] = MAX_PASSWORD_LOG_N + 1;
+        assert!(Header::read_from(&mut &bytes[..]).is_err());
+    }
+
+    #[test]
+    fn key_slots_outside_associated_data() {
+        let mut header = Header::new(
+            b"msg.txt".to_vec(),
+            Vec::new(),
+            2,
+            3,
+            CipherSuite::Aes128Gcm,
+        );
+        header.key_slots = vec![KeySlot {
+            method: UnlockMethod::Password,
+            kdf: None,
+            wrapped_key: vec![0x24u8; 44],
+        }];
+        let associated_data = header.associated_data();
+        let mut bytes = Vec::new();
+        header.write_to(&mut bytes).unwrap();
+        let offset = header.key_slots_offset() as usize;
+        assert!(bytes.starts_with(&associated_data));
+        // the slots, followed by the count of recovery slots
+        assert_eq!(
+            bytes.len(),
+            offset + KEY_SLOTS * (45 + PASSWORD_SALT_SIZE + 1) + 4
+        );
+        assert_eq!(bytes[offset], UnlockMethod::Password.id());
+        header.key_slots[0].wrapped_key = vec![0x42u8; 44];
+        assert_eq!(header.associated_data(), associated_data);
+
+        // a corrupt count of recovery slots is not trusted
+        let count = bytes.len() - 4;
+        bytes[count..].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(Header::read_from(&mut &bytes[..]).is_err());
+    }
+
+    #[test]
+    fn header_legacy() {
+        let mut reader = &b"msg.txt\nciphertext"[..];
+        let header = Header::read_legacy_from(&mut reader).unwrap();
+        assert_eq!(header.version, 0);
+        assert_eq!(header.file_name, b"msg.txt");
+        assert!(header.holders.is_empty());
+        assert_eq!(reader, b"ciphertext");
+        // only the migration reads files without header
+        assert!(Header::read_from(&mut &b"msg.txt\nciphertext"[..]).is_err());
+        let header = Header::new(
+            b"msg.txt".to_vec(),
+            Vec::new(),
+            2,
+            3,
+            CipherSuite::Aes256Gcm,
+        );
+        let mut bytes = Vec::new();
+        header.write_to(&mut bytes).unwrap();
+        assert_eq!(Header::read_legacy_from(&mut &bytes[..]).unwrap(), header);
+    }
+
+    #[test]
+    fn not_encrypted_files() {
+        let error = |bytes: &[u8]| Header::read_from(&mut &bytes[..]).unwrap_err().to_string();
+        let legacy_error = |bytes: &[u8]| {
+            Header::read_legacy_from(&mut &bytes[..])
+                .unwrap_err()
+                .to_string()
+        };
+        let expected = format!(
+            "not a shared_secrets file (accepted version {}); a file of format version 0, \
+             without header, must first be upgraded with shared_secrets migrate",
+            FORMAT_VERSION
+        );
+        for bytes in [
+            &b""[..],
+            b"\n",
+            b"\x89PNG\r\n\x1a\n",
+            b"no newline",
+            b"a text\n",
+        ]
+        .iter()
+        {
+            assert_eq!(error(bytes), expected);
+        }
+        assert_eq!(legacy_error(b""), expected);
+        assert_eq!(legacy_error(b"\n"), expected);
+        assert_eq!(legacy_error(b"\x89PNG\r\n\x1a\n"), expected);
+        assert_eq!(legacy_error(b"no newline"), expected);
+        assert_eq!(legacy_error(&vec![b'a'; 1 << 20]), expected);
+        let mut long_line = vec![b'a'; MAX_LEGACY_NAME + 1];
+        long_line.push(b'\n');
+        assert_eq!(legacy_error(&long_line), expected);
+        let mut name = vec![b'a'; MAX_LEGACY_NAME];
+        name.extend_from_slice(b"\nciphertext");
+        assert_eq!(Header::read_legacy_from(&mut &name[..]).unwrap().version, 0);
+        // a truncated header is told apart from another file
+        let mut bytes = MAGIC.to_vec();
+        bytes.push(FORMAT_VERSION);
+        assert_eq!(error(&bytes), "encrypted file header is truncated");
+        bytes[MAGIC.len()] = FORMAT_VERSION + 1;
+        assert_eq!(
+            error(&bytes),
+            format!(
+                "the encrypted file has format version {}, this program reads version {}; \
+                 use a newer version of shared_secrets",
+                FORMAT_VERSION + 1,
+                FORMAT_VERSION
+            )
+        );
+        bytes[MAGIC.len()] = 0;
+        assert_eq!(error(&bytes), "encrypted file header is corrupt");
+    }
+
+    #[test]
+    fn hostile_names() {
+        let output_name = |name: &str| {
+            let header = Header::new(name.into(), Vec::new(), 2, 3, CipherSuite::Aes256Gcm);
+            header.output_name().map(|name| name.into_string().unwrap())
+        };
+        assert_eq!(output_name("msg.txt").unwrap(), "msg.txt");
+        assert_eq!(output_name("../../.bashrc").unwrap(), ".bashrc");
+        assert_eq!(output_name("/etc/passwd").unwrap(), "passwd");
+        assert_eq!(output_name("..\\..\\boot.ini").unwrap(), "boot.ini");
+        assert!(output_name("").is_err());
+        assert!(output_name("..").is_err());
+        assert!(output_name("dir/").is_err());
+        assert!(output_name("msg\n.txt").is_err());
+        assert!(output_name("msg\u{1b}[2J.txt").is_err());
+    }
+
+    #[test]
+    fn binary_names() {
+        let name = b"caf\xe9\nmen\xfa.txt".to_vec();
+        let header = Header::new(name.clone(), Vec::new(), 2, 3, CipherSuite::Aes256Gcm);
+        let mut bytes = Vec::new();
+        header.write_to(&mut bytes).unwrap();
+        let read = Header::read_from(&mut &bytes[..]).unwrap();
+        assert_eq!(read.file_name, name);
+        // the newline is stored, but not used to name the output
+        assert!(read.output_name().is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn non_utf8_output_name() {
+        let name = OsStr::from_bytes(b"caf\xe9.txt");
+        let header = Header::new(
+            name_to_bytes(name),
+            Vec::new(),
+            2,
+            3,
+            CipherSuite::Aes256Gcm,
+        );
+        assert_eq!(header.output_name().unwrap(), name);
+    }
+
+    #[test]
+    fn header_truncated() {
+        let header = Header::new(
+            b"msg.txt".to_vec(),
+            vec!["alice".into()],
+            1,
+            3,
+            CipherSuite::Aes256Gcm,
+        );
+        let mut bytes = Vec::new();
+        header.write_to(&mut bytes).unwrap();
+        bytes.truncate(bytes.len() - 2);
+        assert!(Header::read_from(&mut &bytes[..]).is_err());
+    }
+}
@@ -1,49 +1,106 @@
 use std::error::Error;
+use std::io::{self, Read, Seek, Write};
 
-use aes_gcm::aead::{generic_array::GenericArray, AeadInPlace, NewAead};
-use aes_gcm::Aes256Gcm;
-use sha2::{Digest, Sha256};
+use subtle::ConstantTimeEq;
 
+use crate::encoding::to_hex;
+use crate::math::Prime;
+use crate::policy::Policy;
 use error::CipherError;
+use suite::Aead;
 
+pub use crate::crypto::chunked::{DecryptReader, EncryptWriter, RangeReader};
+pub use crate::crypto::key::SecretKey;
+pub use crate::crypto::scheme::SecretSharingScheme;
 pub use crate::crypto::shamir::{Share, ShareIter};
+pub use crate::crypto::suite::CipherSuite;
 
+/// Size in bytes of the nonce stored with a wrapped key.
+pub const WRAP_NONCE_SIZE: usize = 12;
+
+// Size in bytes of the tag of a wrapped key
+const WRAP_TAG_SIZE: usize = 16;
+
+// Context of the key derived from a secret to authenticate its shares
+const SHARE_MAC_CONTEXT: &str = "shared_secrets share authentication v1";
+
+/// Size in bytes of the MAC of a share, see Cipher::share_mac.
+pub const SHARE_MAC_SIZE: usize = 16;
+
+// Maximum number of subsets of shares tried when looking
+// for the shares that were forged
+const MAX_SUBSETS: usize = 1000;
+
+/// Returns the size in bytes of a key of the suite
+/// wrapped with Cipher::wrap_key.
+pub fn wrapped_key_size(suite: CipherSuite) -> usize {
+    WRAP_NONCE_SIZE + suite.key_size() + WRAP_TAG_SIZE
+}
+
+pub mod chunked;
+pub mod crt;
 pub mod error;
+pub mod key;
+pub mod scheme;
 pub mod shamir;
+pub mod suite;
 
-/// A AES-256 cipher which supports splitting keys
-/// into shares using shamir secret sharing scheme
+/// An AES cipher whose key can be split into shares
+/// using shamir secret sharing scheme, see SecretKey.
 pub struct Cipher {
-    aes: Aes256Gcm,
-    key: Vec<u8>,
+    suite: CipherSuite,
+    aead: Aead,
+    key: SecretKey,
 }
 
 impl Cipher {
-    /// Creates a new cipher using the given string
-    /// to generate the key.
+    /// Creates a new cipher whose key is derived from a password
+    /// with scrypt, see SecretKey::from_password.
+    ///
+    /// # Parameters
+    ///
+    /// - password: The password given by the user.
+    /// - salt: The random salt of the key.
+    /// - log_n: Base 2 logarithm of the cost N of scrypt.
+    /// - suite: The AEAD algorithm of the cipher.
+    ///
+    /// # Returns
+    ///
+    /// A cipher with the derived key.
+    pub fn from_password(password: &str, salt: &[u8], log_n: u8, suite: CipherSuite) -> Self {
+        Cipher::with_key(
+            SecretKey::from_password(password, salt, log_n, suite),
+            suite,
+        )
+    }
+
+    /// Creates a new cipher whose key is generated from
+    /// the content of a key file.
     ///
     /// # Parameters
     ///
-    /// - key: A string given by the user, used for
-    /// generating a key.
+    /// - content: The bytes of the key file.
+    /// - suite: The AEAD algorithm of the cipher.
     ///
     /// # Returns
     ///
     /// A cipher with a given generated key.
-    pub fn new(key: &str) -> Self {
-        let key = Sha256::digest(key.as_bytes());
-        Cipher {
-            aes: Aes256Gcm::new(&key),
-            key: key.iter().cloned().collect(),
-        }
+    pub fn from_keyfile(content: &[u8], suite: CipherSuite) -> Self {
+        Cipher::with_key(SecretKey::from_keyfile(content, suite), suite)
     }
 
     /// Creates an cipher from an iterator of shares.
     ///
+    /// The shares do not keep the leading zero bytes of the key, which
+    /// are restored when the length of the key is given.
+    ///
     /// # Parameters
     ///
     /// - shares: An Iterator of shares; it's required for each
-    /// share to have a unique first element.
+    ///   share to have a unique first element.
+    /// - suite: The AEAD algorithm of the cipher.
+    /// - prime: The prime of the field of the shares, None for the default.
+    /// - length: The length of the key recorded with the shares, or None.
     ///
     /// # Returns
     ///
@@ -52,18 +109,211 @@ impl Cipher {
     /// # Errors
     ///
     /// This method returns an error if there are two shares with the same
-    /// first element or if it failed to parse the shares.
-    pub fn from_shares(shares: impl Iterator<Item = Share>) -> Result<Self, Box<dyn Error>> {
-        let key = shamir::recover_secret(shares)?;
-        if key.len() != 32 {
-            return Err(Box::new(CipherError(
-                "Error while recovering key from shares".into(),
-            )));
+    /// first element, if it failed to parse the shares or if the recovered
+    /// key does not have the key size of the suite.
+    pub fn from_shares(
+        shares: impl Iterator<Item = Share>,
+        suite: CipherSuite,
+        prime: Option<&Prime>,
+        length: Option<usize>,
+    ) -> Result<Self, Box<dyn Error>> {
+        Ok(SecretKey::recover(shares, prime, length)?.into_cipher(suite)?)
+    }
+
+    /// Creates a cipher from shares authenticated with share_mac,
+    /// leaving out the shares whose MAC does not verify.
+    ///
+    /// Subsets of k shares are tried until one recovers a key under
+    /// which all its MACs verify, so up to n - k forged or corrupt
+    /// shares are detected instead of recovering a wrong key. The
+    /// first subset tried is the one with the lowest indices.
+    ///
+    /// # Parameters
+    ///
+    /// - shares: The shares given, together with their MAC.
+    /// - k: The minimum number of shares to recover the key.
+    /// - suite: The AEAD algorithm of the cipher.
+    /// - prime: The prime of the field of the shares, None for the default.
+    /// - length: The length of the key, see from_shares.
+    ///
+    /// # Returns
+    ///
+    /// A cipher that uses the recovered key, and the positions in
+    /// shares of the shares whose MAC does not verify under it.
+    ///
+    /// # Errors
+    ///
+    /// This method returns an error if there are less than k shares,
+    /// or if no subset of k shares recovers a key under which all
+    /// of its MACs verify.
+    pub fn from_authenticated_shares(
+        shares: &[(Share, String)],
+        k: usize,
+        suite: CipherSuite,
+        prime: Option<&Prime>,
+        length: Option<usize>,
+    ) -> Result<(Self, Vec<usize>), Box<dyn Error>> {
+        if k == 0 || shares.len() < k {
+            return Err(Box::new(CipherError(format!(
+                "found {} shares, need at least {}",
+                shares.len(),
+                k
+            ))));
+        }
+        let order = by_index(shares.iter().map(|(share, _)| share));
+        let mut subset: Vec<usize> = (0..k).collect();
+        for _ in 0..MAX_SUBSETS {
+            let chosen = subset.iter().map(|&i| shares[order[i]].0.clone());
+            if let Ok(cipher) = Cipher::from_shares(chosen, suite, prime, length) {
+                if subset.iter().all(|&i| {
+                    let (share, mac) = &shares[order[i]];
+                    cipher.verify_share(share, mac)
+                }) {
+                    let forged = (0..shares.len())
+                        .filter(|&i| !cipher.verify_share(&shares[i].0, &shares[i].1))
+                        .collect();
+                    return Ok((cipher, forged));
+                }
+            }
+            if !next_subset(&mut subset, shares.len()) {
+                break;
+            }
+        }
+        Err(Box::new(CipherError(
+            "the shares do not recover the key, some of them are forged or corrupt".into(),
+        )))
+    }
+
+    /// Creates a cipher from the k shares with the lowest indices, so
+    /// the same shares are used whatever the order they are given in.
+    ///
+    /// With cross_check, when more than k shares are given the key is
+    /// also recovered from the k shares with the highest indices, and
+    /// both keys must be the same, which detects shares that are not
+    /// consistent with the others.
+    ///
+    /// # Parameters
+    ///
+    /// - shares: The shares given.
+    /// - k: The minimum number of shares to recover the key.
+    /// - suite: The AEAD algorithm of the cipher.
+    /// - prime: The prime of the field of the shares, None for the default.
+    /// - length: The length of the key, see from_shares.
+    /// - cross_check: Whether to recover the key from a second subset.
+    ///
+    /// # Returns
+    ///
+    /// A cipher that uses the key recovered from the shares.
+    ///
+    /// # Errors
+    ///
+    /// This method returns an error if there are less than k shares,
+    /// if the key cannot be recovered, see from_shares, or if the
+    /// second subset recovers a different key.
+    pub fn from_lowest_shares(
+        shares: &[Share],
+        k: usize,
+        suite: CipherSuite,
+        prime: Option<&Prime>,
+        length: Option<usize>,
+        cross_check: bool,
+    ) -> Result<Self, Box<dyn Error>> {
+        if k == 0 || shares.len() < k {
+            return Err(Box::new(CipherError(format!(
+                "found {} shares, need at least {}",
+                shares.len(),
+                k
+            ))));
+        }
+        let order = by_index(shares.iter());
+        let lowest = order[..k].iter().map(|&i| shares[i].clone());
+        let cipher = Cipher::from_shares(lowest, suite, prime, length)?;
+        if cross_check && shares.len() > k {
+            let highest = order[order.len() - k..].iter().map(|&i| shares[i].clone());
+            let other = Cipher::from_shares(highest, suite, prime, length);
+            if other.map_or(true, |other| other.key != cipher.key) {
+                return Err(Box::new(CipherError(
+                    "the shares are not consistent, some of them are corrupt".into(),
+                )));
+            }
         }
-        Ok(Cipher {
-            aes: Aes256Gcm::new(GenericArray::from_slice(&key)),
+        Ok(cipher)
+    }
+
+    /// Creates a cipher from the shares of a key split with a policy.
+    ///
+    /// # Parameters
+    ///
+    /// - policy: The policy the key was split with.
+    /// - shares: The shares given, together with the name of their holder.
+    /// - suite: The AEAD algorithm of the cipher.
+    /// - length: The length of the key, see from_shares.
+    ///
+    /// # Returns
+    ///
+    /// A cipher that uses the key recovered from the shares.
+    ///
+    /// # Errors
+    ///
+    /// This method returns an error if the holders of the shares do
+    /// not satisfy the policy, if it failed to parse the shares or if
+    /// the recovered key does not have the key size of the suite.
+    pub fn from_policy(
+        policy: &Policy,
+        shares: &[(String, Share)],
+        suite: CipherSuite,
+        length: Option<usize>,
+    ) -> Result<Self, Box<dyn Error>> {
+        Ok(SecretKey::recover_by(policy, shares, length)?.into_cipher(suite)?)
+    }
+
+    /// Creates a cipher with a random key, used as the data key
+    /// of a file, see wrap_key.
+    ///
+    /// # Parameters
+    ///
+    /// - suite: The AEAD algorithm of the cipher.
+    ///
+    /// # Errors
+    ///
+    /// A CipherError if the key cannot be generated.
+    pub fn random(suite: CipherSuite) -> Result<Self, CipherError> {
+        Ok(Cipher::with_key(SecretKey::random(suite)?, suite))
+    }
+
+    /// Creates a cipher that uses the given key as is, for
+    /// known-answer tests.
+    ///
+    /// # Parameters
+    ///
+    /// - key: The key of the cipher.
+    /// - suite: The AEAD algorithm of the cipher.
+    ///
+    /// # Errors
+    ///
+    /// A CipherError if the key does not have the key size of the suite.
+    #[cfg(any(test, feature = "deterministic"))]
+    pub fn from_key(key: &[u8], suite: CipherSuite) -> Result<Self, CipherError> {
+        SecretKey::new(key.to_vec()).into_cipher(suite)
+    }
+
+    // creates a cipher from a key of the size of the suite
+    pub(crate) fn with_key(key: SecretKey, suite: CipherSuite) -> Self {
+        Cipher {
+            suite,
+            aead: Aead::new(suite, key.expose()),
             key,
-        })
+        }
+    }
+
+    /// Returns the key of this cipher, to split it into shares.
+    pub fn key(&self) -> &SecretKey {
+        &self.key
+    }
+
+    /// Returns the AEAD algorithm of this cipher.
+    pub fn suite(&self) -> CipherSuite {
+        self.suite
     }
 
     /// Encrypts the given block in place.
@@ -71,7 +321,7 @@ impl Cipher {
     /// # Parameters
     ///
     /// - plaintext: An array of bytes that represent
-    /// a given decrypted block.
+    ///   a given decrypted block.
     ///
     /// # Returns
     ///
@@ -81,13 +331,253 @@ impl Cipher {
     ///
     /// This method returns an error if an error occurs while encrypting
     pub fn encrypt(&self, plaintext: &mut Vec<u8>) -> Result<(), CipherError> {
-        match self
-            .aes
-            .encrypt_in_place(GenericArray::from_slice(&[0x44u8; 12]), b"", plaintext)
-        {
-            Ok(_) => Ok(()),
-            Err(_) => Err(CipherError("Error while encrypting".into())),
+        self.aead.encrypt_in_place(&[0x44u8; 12], b"", plaintext)
+    }
+
+    /// Encrypts everything read from the reader using the chunked
+    /// format and writes the result to the writer.
+    ///
+    /// The nonce of each chunk, and optionally its key, are derived
+    /// from the key of this cipher, see chunked::ChunkKdf.
+    ///
+    /// # Parameters
+    ///
+    /// - reader: The reader of the plaintext.
+    /// - writer: The writer where the ciphertext is written.
+    /// - subkeys: Whether each chunk is encrypted with its own subkey.
+    /// - aad: Associated data authenticated with the ciphertext.
+    ///
+    /// # Errors
+    ///
+    /// This method returns an error if an error occurs while
+    /// reading, encrypting or writing.
+    pub fn encrypt_stream(
+        &self,
+        reader: &mut impl Read,
+        writer: &mut impl Write,
+        subkeys: bool,
+        aad: &[u8],
+    ) -> Result<(), Box<dyn Error>> {
+        chunked::encrypt(self.key.expose(), reader, writer, self.suite, subkeys, aad)
+    }
+
+    /// Returns a writer that encrypts everything written to it
+    /// using the chunked format and writes the result to the writer.
+    ///
+    /// # Parameters
+    ///
+    /// - writer: The writer where the ciphertext is written.
+    /// - subkeys: Whether each chunk is encrypted with its own subkey.
+    /// - aad: Associated data authenticated with the ciphertext.
+    ///
+    /// # Errors
+    ///
+    /// This method returns an error if the salt cannot be generated
+    /// or if the start of the ciphertext cannot be written.
+    pub fn encrypt_writer<W: Write>(
+        &self,
+        writer: W,
+        subkeys: bool,
+        aad: &[u8],
+    ) -> Result<EncryptWriter<W>, Box<dyn Error>> {
+        EncryptWriter::new(self.key.expose(), writer, self.suite, subkeys, aad)
+    }
+
+    /// Returns an encryptor of the chunked format that uses the
+    /// key of this cipher, to encrypt a stream in several runs.
+    ///
+    /// # Parameters
+    ///
+    /// - subkeys: Whether each chunk is encrypted with its own subkey.
+    /// - salt: The salt of the stream to resume, None for a new stream.
+    ///
+    /// # Errors
+    ///
+    /// A CipherError if the salt cannot be generated.
+    pub fn encryptor(
+        &self,
+        subkeys: bool,
+        salt: Option<[u8; chunked::SALT_SIZE]>,
+    ) -> Result<chunked::Encryptor, CipherError> {
+        match salt {
+            Some(salt) => Ok(chunked::Encryptor::with_salt(
+                self.key.expose(),
+                self.suite,
+                subkeys,
+                salt,
+            )),
+            None => chunked::Encryptor::new(self.key.expose(), self.suite, subkeys),
+        }
+    }
+
+    /// Decrypts a ciphertext in the chunked format read from the
+    /// reader and writes the plaintext to the writer.
+    ///
+    /// # Parameters
+    ///
+    /// - reader: The reader of the ciphertext.
+    /// - writer: The writer where the plaintext is written.
+    /// - aad: Associated data the ciphertext was encrypted with.
+    ///
+    /// # Errors
+    ///
+    /// This method returns an error if an error occurs while
+    /// reading or writing, if the key of this cipher does not match
+    /// the key commitment or if the ciphertext fails authentication.
+    pub fn decrypt_stream(
+        &self,
+        reader: &mut impl Read,
+        writer: &mut impl Write,
+        aad: &[u8],
+    ) -> Result<(), Box<dyn Error>> {
+        chunked::decrypt(self.key.expose(), reader, writer, self.suite, aad)
+    }
+
+    /// Returns a reader of the plaintext of a ciphertext in the
+    /// chunked format read from the reader.
+    ///
+    /// # Parameters
+    ///
+    /// - reader: The reader of the ciphertext.
+    /// - aad: Associated data the ciphertext was encrypted with.
+    ///
+    /// # Errors
+    ///
+    /// This method returns an error if the start of the ciphertext
+    /// cannot be read or if the key of this cipher does not match
+    /// the key commitment.
+    pub fn decrypt_reader<R: Read>(
+        &self,
+        reader: R,
+        aad: &[u8],
+    ) -> Result<DecryptReader<R>, Box<dyn Error>> {
+        DecryptReader::new(self.key.expose(), reader, self.suite, aad)
+    }
+
+    /// Returns a reader of any part of the plaintext of a ciphertext
+    /// in the chunked format, which decrypts only the chunks of the
+    /// parts that are read.
+    ///
+    /// # Parameters
+    ///
+    /// - reader: The reader of the ciphertext, which holds it up to
+    ///   its end.
+    /// - aad: Associated data the ciphertext was encrypted with.
+    ///
+    /// # Errors
+    ///
+    /// This method returns an error if the start of the ciphertext
+    /// cannot be read or if the key of this cipher does not match
+    /// the key commitment.
+    pub fn range_reader<R: Read + Seek>(
+        &self,
+        reader: R,
+        aad: &[u8],
+    ) -> Result<RangeReader<R>, Box<dyn Error>> {
+        RangeReader::new(self.key.expose(), reader, self.suite, aad)
+    }
+
+    /// Checks that a ciphertext in the chunked format read from
+    /// the reader is authentic, without keeping the plaintext.
+    ///
+    /// # Parameters
+    ///
+    /// - ciphertext: The reader of the ciphertext.
+    /// - aad: Associated data the ciphertext was encrypted with.
+    ///
+    /// # Errors
+    ///
+    /// This method returns an error if an error occurs while reading,
+    /// if the key of this cipher does not match the key commitment or
+    /// if the ciphertext fails authentication.
+    pub fn verify(&self, ciphertext: &mut impl Read, aad: &[u8]) -> Result<(), Box<dyn Error>> {
+        self.decrypt_stream(ciphertext, &mut io::sink(), aad)
+    }
+
+    /// Encrypts the key of another cipher with this one, so that
+    /// the key can be stored next to the data it encrypts.
+    ///
+    /// The result is a random nonce followed by the encrypted key
+    /// and its tag.
+    ///
+    /// # Parameters
+    ///
+    /// - key: The cipher whose key is wrapped.
+    /// - aad: Associated data authenticated with the key.
+    ///
+    /// # Errors
+    ///
+    /// A CipherError if the nonce cannot be generated or if an
+    /// error occurs while encrypting.
+    pub fn wrap_key(&self, key: &Cipher, aad: &[u8]) -> Result<Vec<u8>, CipherError> {
+        let mut nonce = [0u8; WRAP_NONCE_SIZE];
+        getrandom::getrandom(&mut nonce)
+            .map_err(|_| CipherError("Error while generating a nonce".into()))?;
+        self.seal_key(key, aad, nonce)
+    }
+
+    /// Wraps a key like wrap_key, using the given nonce instead
+    /// of a random one, for known-answer tests.
+    ///
+    /// # Parameters
+    ///
+    /// - key: The cipher whose key is wrapped.
+    /// - aad: Associated data authenticated with the key.
+    /// - nonce: The nonce to encrypt the key with.
+    ///
+    /// # Errors
+    ///
+    /// A CipherError if an error occurs while encrypting.
+    #[cfg(any(test, feature = "deterministic"))]
+    pub fn wrap_key_with_nonce(
+        &self,
+        key: &Cipher,
+        aad: &[u8],
+        nonce: [u8; WRAP_NONCE_SIZE],
+    ) -> Result<Vec<u8>, CipherError> {
+        self.seal_key(key, aad, nonce)
+    }
+
+    // encrypts the key of the cipher, prefixed by the nonce
+    fn seal_key(
+        &self,
+        key: &Cipher,
+        aad: &[u8],
+        nonce: [u8; WRAP_NONCE_SIZE],
+    ) -> Result<Vec<u8>, CipherError> {
+        let mut sealed = key.key.expose().to_vec();
+        self.aead.encrypt_in_place(&nonce, aad, &mut sealed)?;
+        let mut wrapped = nonce.to_vec();
+        wrapped.extend_from_slice(&sealed);
+        Ok(wrapped)
+    }
+
+    /// Decrypts a key wrapped with wrap_key.
+    ///
+    /// # Parameters
+    ///
+    /// - wrapped: The wrapped key.
+    /// - aad: Associated data the key was wrapped with.
+    ///
+    /// # Returns
+    ///
+    /// A cipher of the same suite that uses the unwrapped key.
+    ///
+    /// # Errors
+    ///
+    /// A CipherError if the key was not wrapped with the key of this
+    /// cipher and the given associated data, or if it was modified.
+    pub fn unwrap_key(&self, wrapped: &[u8], aad: &[u8]) -> Result<Cipher, CipherError> {
+        let invalid = || CipherError("Error while unwrapping the key".into());
+        if wrapped.len() != wrapped_key_size(self.suite) {
+            return Err(invalid());
         }
+        let (nonce, sealed) = wrapped.split_at(WRAP_NONCE_SIZE);
+        let mut key = sealed.to_vec();
+        self.aead
+            .decrypt_in_place(nonce, aad, &mut key)
+            .map_err(|_| invalid())?;
+        Ok(Cipher::with_key(SecretKey::new(key), self.suite))
     }
 
     /// Decrypts the given block in place.
@@ -95,7 +585,7 @@ impl Cipher {
     /// # Parameters
     ///
     /// - ciphertext: An array of bytes that represent
-    /// a given encrypted block.
+    ///   a given encrypted block.
     ///
     /// # Returns
     ///
@@ -105,35 +595,67 @@ impl Cipher {
     ///
     /// This method returns an error if an error occurs while decrypting
     pub fn decrypt(&self, ciphertext: &mut Vec<u8>) -> Result<(), CipherError> {
-        match self
-            .aes
-            .decrypt_in_place(GenericArray::from_slice(&[0x44u8; 12]), b"", ciphertext)
-        {
-            Ok(_) => Ok(()),
-            Err(_) => Err(CipherError("Error while decrypting".into())),
-        }
+        self.aead.decrypt_in_place(&[0x44u8; 12], b"", ciphertext)
     }
 
-    /// Splits the key of this cipher into n shares with
-    /// only needing k to recover the original key.
+    /// Returns the MAC of a share of the key of this cipher, which
+    /// holders cannot forge without knowing the key.
     ///
-    /// # Parameter
+    /// # Parameters
     ///
-    /// - n: The total number of shares to return (n > 2)
-    /// - k: The minimum number of shares to recover the secret (0 < k <= n)
+    /// - share: The share to authenticate.
     ///
     /// # Returns
     ///
-    /// A ShareIter with n Shares.
+    /// The MAC in hexadecimal.
+    pub fn share_mac(&self, share: &Share) -> String {
+        let mac_key = blake3::derive_key(SHARE_MAC_CONTEXT, self.key.expose());
+        let Share(x, y) = share;
+        let mac = blake3::keyed_hash(&mac_key, format!("{}:{}", x, y).as_bytes());
+        to_hex(&mac.as_bytes()[..SHARE_MAC_SIZE])
+    }
+
+    /// Returns whether the MAC of a share verifies under the key of
+    /// this cipher, see share_mac.
     ///
-    /// # Panics
+    /// # Parameters
     ///
-    /// This method panics if the parameters constraints are not met.
-    pub fn split_key(&self, n: usize, k: usize) -> ShareIter {
-        shamir::split_secret(&self.key, n, k)
+    /// - share: The share given.
+    /// - mac: The MAC given with the share, in hexadecimal.
+    pub fn verify_share(&self, share: &Share, mac: &str) -> bool {
+        let expected = self.share_mac(share);
+        expected
+            .as_bytes()
+            .ct_eq(mac.to_ascii_lowercase().as_bytes())
+            .into()
     }
 }
 
+// advances a subset of indices in 0..n to the next one in
+// lexicographic order, returning false after the last one
+fn next_subset(subset: &mut [usize], n: usize) -> bool {
+    let k = subset.len();
+    for i in (0..k).rev() {
+        if subset[i] < n - k + i {
+            subset[i] += 1;
+            for j in i + 1..k {
+                subset[j] = subset[j - 1] + 1;
+            }
+            return true;
+        }
+    }
+    false
+}
+
+// returns the positions of the shares ordered by their index
+fn by_index<'a>(shares: impl Iterator<Item = &'a Share>) -> Vec<usize> {
+    let shares: Vec<&Share> = shares.collect();
+    let mut order: Vec<usize> = (0..shares.len()).collect();
+    // the indices are in base 36 without leading zeros
+    order.sort_by_key(|&i| (shares[i].0.len(), &shares[i].0));
+    order
+}
+
 /////////////////////////////////
 // Unit testing of the module. //
 /////////////////////////////////
@@ -144,11 +666,7 @@ mod tests {
 
     #[test]
     fn integrity() {
-        let key = [0x10u8; 32];
-        let cipher = Cipher {
-            aes: Aes256Gcm::new(GenericArray::from_slice(&key)),
-            key: key.to_vec(),
-        };
+        let cipher = Cipher::with_key(SecretKey::new(vec![0x10u8; 32]), CipherSuite::Aes256Gcm);
         let mut message = b"This is a message".to_vec();
         cipher.encrypt(&mut message).unwrap();
         cipher.decrypt(&mut message).unwrap();
@@ -157,7 +675,7 @@ mod tests {
 
     #[test]
     fn integrity_new() {
-        let cipher = Cipher::new("This is a secure key");
+        let cipher = Cipher::from_keyfile(b"This is a secure key", CipherSuite::Aes256Gcm);
         let mut message = b"This is a message".to_vec();
         cipher.encrypt(&mut message).unwrap();
         cipher.decrypt(&mut message).unwrap();
@@ -166,16 +684,179 @@ mod tests {
 
     #[test]
     fn integrity_from_shares() {
-        let key = [0x12u8; 32];
-        let cipher = Cipher {
-            aes: Aes256Gcm::new(GenericArray::from_slice(&key)),
-            key: key.to_vec(),
-        };
+        let cipher = Cipher::with_key(SecretKey::new(vec![0x12u8; 32]), CipherSuite::Aes256Gcm);
         let mut message = b"message".to_vec();
         cipher.encrypt(&mut message).unwrap();
-        let shares = cipher.split_key(4, 3);
-        let decipher = Cipher::from_shares(shares).unwrap();
+        let shares = cipher.key().split(4, 3, None).unwrap();
+        let decipher = Cipher::from_shares(shares, CipherSuite::Aes256Gcm, None, None).unwrap();
         decipher.decrypt(&mut message).unwrap();
         assert_eq!(&message, b"message");
+        let prime = shamir::parse_field("127").unwrap();
+        let shares = cipher.key().split(4, 3, Some(&prime)).unwrap();
+        let decipher =
+            Cipher::from_shares(shares, CipherSuite::Aes256Gcm, Some(&prime), None).unwrap();
+        assert_eq!(decipher.key, cipher.key);
+    }
+
+    #[test]
+    fn leading_zero_key() {
+        let mut key = vec![0u8; 2];
+        key.extend(vec![0x42u8; 30]);
+        let cipher = Cipher::with_key(SecretKey::new(key.clone()), CipherSuite::Aes256Gcm);
+        let shares: Vec<Share> = cipher.key().split(3, 2, None).unwrap().collect();
+        let error = Cipher::from_shares(
+            shares.clone().into_iter(),
+            CipherSuite::Aes256Gcm,
+            None,
+            None,
+        )
+        .err()
+        .unwrap();
+        assert!(error.to_string().contains("30 bytes"));
+        let decipher =
+            Cipher::from_shares(shares.into_iter(), CipherSuite::Aes256Gcm, None, Some(32))
+                .unwrap();
+        assert_eq!(decipher.key.expose(), &key[..]);
+    }
+
+    #[test]
+    fn integrity_from_policy() {
+        let cipher = Cipher::with_key(SecretKey::new(vec![0x12u8; 32]), CipherSuite::Aes256Gcm);
+        let policy = Policy::parse("alice or (bob and carol)").unwrap();
+        let shares = cipher.key().split_by(&policy);
+        let decipher =
+            Cipher::from_policy(&policy, &shares[1..], CipherSuite::Aes256Gcm, None).unwrap();
+        assert_eq!(decipher.key, cipher.key);
+        assert!(Cipher::from_policy(&policy, &shares[2..], CipherSuite::Aes256Gcm, None).is_err());
+    }
+
+    #[test]
+    fn forged_shares() {
+        let cipher = Cipher::with_key(SecretKey::new(vec![0x13u8; 32]), CipherSuite::Aes256Gcm);
+        let mut shares: Vec<(Share, String)> = cipher
+            .key()
+            .split(5, 3, None)
+            .unwrap()
+            .map(|share| {
+                let mac = cipher.share_mac(&share);
+                (share, mac)
+            })
+            .collect();
+        assert!(cipher.verify_share(&shares[0].0, &shares[0].1.to_uppercase()));
+        let Share(x, y) = shares[1].0.clone();
+        shares[1].0 = Share(x, y.replace(|c: char| c != '1', "1"));
+        let (decipher, forged) =
+            Cipher::from_authenticated_shares(&shares, 3, CipherSuite::Aes256Gcm, None, None)
+                .unwrap();
+        assert_eq!(decipher.key, cipher.key);
+        assert_eq!(forged, vec![1]);
+        assert!(Cipher::from_authenticated_shares(
+            &shares[..3],
+            3,
+            CipherSuite::Aes256Gcm,
+            None,
+            None
+        )
+        .is_err());
+        assert!(Cipher::from_authenticated_shares(
+            &shares[..2],
+            3,
+            CipherSuite::Aes256Gcm,
+            None,
+            None
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn lowest_shares() {
+        let cipher = Cipher::with_key(SecretKey::new(vec![0x14u8; 32]), CipherSuite::Aes256Gcm);
+        let mut shares: Vec<Share> = cipher.key().split(40, 3, None).unwrap().collect();
+        shares.reverse();
+        assert_eq!(by_index(shares.iter())[..3], [39, 38, 37]);
+        let suite = CipherSuite::Aes256Gcm;
+        let decipher = Cipher::from_lowest_shares(&shares, 3, suite, None, None, true).unwrap();
+        assert_eq!(decipher.key, cipher.key);
+        // a corrupt share among the highest indices is only seen cross checking
+        shares[0].1 = shares[1].1.clone();
+        assert!(Cipher::from_lowest_shares(&shares, 3, suite, None, None, false).is_ok());
+        assert!(Cipher::from_lowest_shares(&shares, 3, suite, None, None, true).is_err());
+        assert!(Cipher::from_lowest_shares(&shares[..2], 3, suite, None, None, false).is_err());
+    }
+
+    #[test]
+    fn subsets() {
+        let mut subset = vec![0, 1];
+        let mut all = vec![subset.clone()];
+        while next_subset(&mut subset, 4) {
+            all.push(subset.clone());
+        }
+        assert_eq!(
+            all,
+            vec![
+                vec![0, 1],
+                vec![0, 2],
+                vec![0, 3],
+                vec![1, 2],
+                vec![1, 3],
+                vec![2, 3]
+            ]
+        );
+    }
+
+    #[test]
+    fn key_size() {
+        let cipher = Cipher::from_keyfile(b"This is a secure key", CipherSuite::Aes128Gcm);
+        assert_eq!(cipher.key.len(), 16);
+        let shares = cipher.key().split(3, 2, None).unwrap();
+        assert!(Cipher::from_shares(shares, CipherSuite::Aes256Gcm, None, None).is_err());
+    }
+
+    #[test]
+    fn integrity_stream() {
+        for &suite in &[
+            CipherSuite::Aes256Gcm,
+            CipherSuite::Aes256GcmSiv,
+            CipherSuite::Aes128Gcm,
+        ] {
+            let cipher = Cipher::from_keyfile(b"This is a secure key", suite);
+            let mut ciphertext = Vec::new();
+            cipher
+                .encrypt_stream(&mut &b"This is a message"[..], &mut ciphertext, true, b"")
+                .unwrap();
+            let mut message = Vec::new();
+            cipher
+                .decrypt_stream(&mut &ciphertext[..], &mut message, b"")
+                .unwrap();
+            assert_eq!(&message, b"This is a message");
+        }
+    }
+
+    #[test]
+    fn wrapped_key() {
+        let kek = Cipher::from_keyfile(b"This is a secure key", CipherSuite::Aes128Gcm);
+        let dek = Cipher::random(CipherSuite::Aes128Gcm).unwrap();
+        let mut wrapped = kek.wrap_key(&dek, b"header").unwrap();
+        assert_eq!(wrapped.len(), wrapped_key_size(CipherSuite::Aes128Gcm));
+        assert_eq!(wrapped.len(), WRAP_NONCE_SIZE + 16 + 16);
+        assert_eq!(kek.unwrap_key(&wrapped, b"header").unwrap().key, dek.key);
+        assert!(kek.unwrap_key(&wrapped, b"other header").is_err());
+        let other = Cipher::from_keyfile(b"This is another key", CipherSuite::Aes128Gcm);
+        assert!(other.unwrap_key(&wrapped, b"header").is_err());
+        wrapped[WRAP_NONCE_SIZE] ^= 0x01;
+        assert!(kek.unwrap_key(&wrapped, b"header").is_err());
+    }
+
+    #[test]
+    fn verify_stream() {
+        let cipher = Cipher::from_keyfile(b"This is a secure key", CipherSuite::Aes256Gcm);
+        let mut ciphertext = Vec::new();
+        cipher
+            .encrypt_stream(&mut &b"This is a message"[..], &mut ciphertext, false, b"")
+            .unwrap();
+        assert!(cipher.verify(&mut &ciphertext[..], b"").is_ok());
+        let last = ciphertext.len() - 1;
+        ciphertext[last] ^= 0x01;
+        assert!(cipher.verify(&mut &ciphertext[..], b"").is_err());
     }
 }
@@ -0,0 +1,1010 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::mem;
+use std::sync::{mpsc, Mutex};
+use std::thread;
+
+use hkdf::Hkdf;
+use sha2::Sha256;
+use subtle::ConstantTimeEq;
+
+use crate::crypto::error::CipherError;
+use crate::crypto::suite::{Aead, CipherSuite};
+
+/// Size in bytes of the plaintext of each chunk,
+/// only the last chunk of a stream may be shorter.
+pub const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Size in bytes of the random salt written at the
+/// start of every chunked stream.
+pub const SALT_SIZE: usize = 32;
+
+/// Size in bytes of the key commitment written after
+/// the salt and the flags of a chunked stream.
+pub const COMMITMENT_SIZE: usize = 32;
+
+/// Size in bytes of the start of a stream, before the chunks.
+pub const HEADER_SIZE: usize = SALT_SIZE + 1 + COMMITMENT_SIZE;
+
+/// Size in bytes of each encrypted chunk, only the
+/// last chunk of a stream may be shorter.
+pub const SEALED_CHUNK_SIZE: usize = CHUNK_SIZE + TAG_SIZE;
+
+/// Number of chunks between the checkpoints of an Encryptor.
+pub const CHECKPOINT_CHUNKS: u64 = 256;
+
+// Size in bytes of the authentication tag of each chunk
+const TAG_SIZE: usize = 16;
+
+// Flag set in the stream header when each chunk has its own subkey
+const FLAG_SUBKEYS: u8 = 0x01;
+
+// Labels used as HKDF info to separate the derived values
+const NONCE_LABEL: &[u8] = b"shared_secrets chunk nonce";
+const SUBKEY_LABEL: &[u8] = b"shared_secrets chunk subkey";
+const COMMITMENT_LABEL: &[u8] = b"shared_secrets key commitment";
+
+/// Derives the nonce and the subkey of every chunk of a
+/// stream from the master key using HKDF-SHA256.
+///
+/// The salt is random for each stream and the chunk counter
+/// and a last chunk marker are part of the derivation, so no
+/// nonce is reused across chunks or files and a stream cannot
+/// be truncated or reordered without failing authentication.
+pub struct ChunkKdf {
+    hkdf: Hkdf<Sha256>,
+}
+
+impl ChunkKdf {
+    /// Creates the derivation for a stream.
+    ///
+    /// # Parameters
+    ///
+    /// - key: The master key.
+    /// - salt: The salt of the stream.
+    ///
+    /// # Returns
+    ///
+    /// A ChunkKdf for the stream.
+    pub fn new(key: &[u8], salt: &[u8]) -> Self {
+        ChunkKdf {
+            hkdf: Hkdf::new(Some(salt), key),
+        }
+    }
+
+    /// Returns the nonce of a chunk.
+    ///
+    /// # Parameters
+    ///
+    /// - index: The position of the chunk in the stream.
+    /// - last: Whether it is the last chunk of the stream.
+    pub fn nonce(&self, index: u64, last: bool) -> [u8; 12] {
+        let mut nonce = [0u8; 12];
+        self.expand(NONCE_LABEL, index, last, &mut nonce);
+        nonce
+    }
+
+    /// Returns the subkey of a chunk, suites with
+    /// shorter keys use its first bytes.
+    ///
+    /// # Parameters
+    ///
+    /// - index: The position of the chunk in the stream.
+    /// - last: Whether it is the last chunk of the stream.
+    pub fn subkey(&self, index: u64, last: bool) -> [u8; 32] {
+        let mut subkey = [0u8; 32];
+        self.expand(SUBKEY_LABEL, index, last, &mut subkey);
+        subkey
+    }
+
+    /// Returns the commitment to the master key of the stream.
+    ///
+    /// AES-GCM alone is not key-committing, a ciphertext can be
+    /// crafted to decrypt under two different keys. Storing this
+    /// value and checking it before decrypting ties the stream
+    /// to a single key.
+    pub fn commitment(&self) -> [u8; COMMITMENT_SIZE] {
+        let mut commitment = [0u8; COMMITMENT_SIZE];
+        self.hkdf.expand(COMMITMENT_LABEL, &mut commitment).unwrap();
+        commitment
+    }
+
+    // fills okm with the value derived for the label and chunk
+    fn expand(&self, label: &[u8], index: u64, last: bool, okm: &mut [u8]) {
+        let mut info = label.to_vec();
+        info.extend_from_slice(&index.to_be_bytes());
+        info.push(last as u8);
+        // the outputs are far below the HKDF-SHA256 limit
+        self.hkdf.expand(&info, okm).unwrap();
+    }
+}
+
+// Encrypts and decrypts the chunks of a single stream
+struct ChunkCipher {
+    kdf: ChunkKdf,
+    suite: CipherSuite,
+    aead: Aead,
+    subkeys: bool,
+}
+
+impl ChunkCipher {
+    fn new(key: &[u8], salt: &[u8], suite: CipherSuite, subkeys: bool) -> Self {
+        ChunkCipher {
+            kdf: ChunkKdf::new(key, salt),
+            suite,
+            aead: Aead::new(suite, key),
+            subkeys,
+        }
+    }
+
+    // returns the AEAD instance for a chunk if it has its own subkey
+    fn subkey_aead(&self, index: u64, last: bool) -> Option<Aead> {
+        if self.subkeys {
+            let subkey = self.kdf.subkey(index, last);
+            Some(Aead::new(self.suite, &subkey[..self.suite.key_size()]))
+        } else {
+            None
+        }
+    }
+
+    fn seal(
+        &self,
+        index: u64,
+        last: bool,
+        aad: &[u8],
+        chunk: &mut Vec<u8>,
+    ) -> Result<(), CipherError> {
+        let nonce = self.kdf.nonce(index, last);
+        let subkey_aead = self.subkey_aead(index, last);
+        subkey_aead
+            .as_ref()
+            .unwrap_or(&self.aead)
+            .encrypt_in_place(&nonce, aad, chunk)
+    }
+
+    fn open(
+        &self,
+        index: u64,
+        last: bool,
+        aad: &[u8],
+        chunk: &mut Vec<u8>,
+    ) -> Result<(), CipherError> {
+        let nonce = self.kdf.nonce(index, last);
+        let subkey_aead = self.subkey_aead(index, last);
+        subkey_aead
+            .as_ref()
+            .unwrap_or(&self.aead)
+            .decrypt_in_place(&nonce, aad, chunk)
+    }
+}
+
+/// Encrypts the chunks of a stream, possibly resuming
+/// an encryption that was interrupted.
+pub struct Encryptor {
+    cipher: ChunkCipher,
+    salt: [u8; SALT_SIZE],
+    threads: usize,
+}
+
+impl Encryptor {
+    /// Creates an encryptor for a new stream with a random salt.
+    ///
+    /// # Parameters
+    ///
+    /// - key: The master key, of the key size of the suite.
+    /// - suite: The AEAD algorithm used to encrypt the chunks.
+    /// - subkeys: Whether each chunk is encrypted with its own subkey.
+    ///
+    /// # Errors
+    ///
+    /// A CipherError if the salt cannot be generated.
+    pub fn new(key: &[u8], suite: CipherSuite, subkeys: bool) -> Result<Self, CipherError> {
+        let mut salt = [0u8; SALT_SIZE];
+        getrandom::getrandom(&mut salt)
+            .map_err(|_| CipherError("Error while generating the salt".into()))?;
+        Ok(Encryptor::with_salt(key, suite, subkeys, salt))
+    }
+
+    /// Creates an encryptor for a stream with the given salt,
+    /// used to resume an interrupted encryption.
+    ///
+    /// # Parameters
+    ///
+    /// - key: The master key, of the key size of the suite.
+    /// - suite: The AEAD algorithm used to encrypt the chunks.
+    /// - subkeys: Whether each chunk is encrypted with its own subkey.
+    /// - salt: The salt of the stream.
+    pub fn with_salt(key: &[u8], suite: CipherSuite, subkeys: bool, salt: [u8; SALT_SIZE]) -> Self {
+        Encryptor {
+            cipher: ChunkCipher::new(key, &salt, suite, subkeys),
+            salt,
+            threads: 1,
+        }
+    }
+
+    /// Sets the number of threads that encrypt chunks at the
+    /// same time, by default chunks are encrypted one by one.
+    ///
+    /// # Parameters
+    ///
+    /// - threads: The number of worker threads, at least 1.
+    ///
+    /// # Returns
+    ///
+    /// This encryptor using the given number of threads.
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    /// Returns the salt of the stream.
+    pub fn salt(&self) -> [u8; SALT_SIZE] {
+        self.salt
+    }
+
+    /// Returns the bytes written at the start of the stream,
+    /// before the chunks: the salt, the flags and the key commitment.
+    pub fn header(&self) -> Vec<u8> {
+        let mut header = self.salt.to_vec();
+        header.push(if self.cipher.subkeys { FLAG_SUBKEYS } else { 0 });
+        header.extend_from_slice(&self.cipher.kdf.commitment());
+        header
+    }
+
+    /// Encrypts everything read from the reader and writes the
+    /// chunks to the writer, starting from the given chunk.
+    ///
+    /// The reader must be positioned at the start of the first chunk
+    /// to encrypt. Every CHECKPOINT_CHUNKS chunks, checkpoint is called
+    /// with the writer and the number of chunks written so far.
+    ///
+    /// # Parameters
+    ///
+    /// - reader: The reader of the plaintext.
+    /// - writer: The writer where the chunks are written.
+    /// - aad: Associated data authenticated with every chunk.
+    /// - first: The index of the first chunk to encrypt.
+    /// - checkpoint: Saves the progress of the encryption.
+    ///
+    /// # Errors
+    ///
+    /// This method returns an error if an error occurs while
+    /// reading, encrypting or writing, or if checkpoint fails.
+    pub fn encrypt_from<W: Write>(
+        &self,
+        reader: &mut impl Read,
+        writer: &mut W,
+        aad: &[u8],
+        first: u64,
+        checkpoint: impl FnMut(&mut W, u64) -> Result<(), Box<dyn Error>>,
+    ) -> Result<(), Box<dyn Error>> {
+        let mut output = Reassembly {
+            writer,
+            checkpoint,
+            pending: BTreeMap::new(),
+            next: first,
+        };
+        if self.threads == 1 {
+            return read_chunks(reader, first, |index, last, mut chunk| {
+                self.cipher.seal(index, last, aad, &mut chunk)?;
+                output.push(index, chunk)
+            });
+        }
+        // the queue is bounded so only a few chunks are in memory
+        let (job_tx, job_rx) = mpsc::sync_channel::<(u64, bool, Vec<u8>)>(self.threads);
+        let job_rx = Mutex::new(job_rx);
+        let (done_tx, done_rx) = mpsc::channel();
+        thread::scope(|scope| {
+            for _ in 0..self.threads {
+                let (job_rx, done_tx) = (&job_rx, done_tx.clone());
+                scope.spawn(move || loop {
+                    let job = job_rx.lock().unwrap().recv();
+                    let (index, last, mut chunk) = match job {
+                        Ok(job) => job,
+                        Err(_) => break,
+                    };
+                    let sealed = self.cipher.seal(index, last, aad, &mut chunk);
+                    if done_tx.send((index, sealed.map(|_| chunk))).is_err() {
+                        break;
+                    }
+                });
+            }
+            drop(done_tx);
+            let read = read_chunks(reader, first, |index, last, chunk| {
+                job_tx
+                    .send((index, last, chunk))
+                    .map_err(|_| CipherError("Error while encrypting".into()))?;
+                while let Ok((index, sealed)) = done_rx.try_recv() {
+                    output.push(index, sealed?)?;
+                }
+                Ok(())
+            });
+            // no more jobs, the workers stop once the queue is empty
+            drop(job_tx);
+            read?;
+            for (index, sealed) in done_rx {
+                output.push(index, sealed?)?;
+            }
+            Ok(())
+        })
+    }
+}
+
+// Writes the encrypted chunks in order as they are completed
+struct Reassembly<'a, W, F> {
+    writer: &'a mut W,
+    checkpoint: F,
+    pending: BTreeMap<u64, Vec<u8>>,
+    next: u64,
+}
+
+impl<W, F> Reassembly<'_, W, F>
+where
+    W: Write,
+    F: FnMut(&mut W, u64) -> Result<(), Box<dyn Error>>,
+{
+    fn push(&mut self, index: u64, chunk: Vec<u8>) -> Result<(), Box<dyn Error>> {
+        self.pending.insert(index, chunk);
+        while let Some(chunk) = self.pending.remove(&self.next) {
+            self.writer.write_all(&chunk)?;
+            self.next += 1;
+            if self.next % CHECKPOINT_CHUNKS == 0 {
+                (self.checkpoint)(self.writer, self.next)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// calls f with the index, the last chunk marker and the
+// plaintext of every chunk read, starting from first
+fn read_chunks(
+    reader: &mut impl Read,
+    first: u64,
+    mut f: impl FnMut(u64, bool, Vec<u8>) -> Result<(), Box<dyn Error>>,
+) -> Result<(), Box<dyn Error>> {
+    let mut chunk = read_chunk(reader, CHUNK_SIZE)?;
+    for index in first.. {
+        let next = read_chunk(reader, CHUNK_SIZE)?;
+        let last = next.is_empty();
+        f(index, last, chunk)?;
+        if last {
+            break;
+        }
+        chunk = next;
+    }
+    Ok(())
+}
+
+/// Encrypts everything read from the reader as a chunked
+/// stream and writes it to the writer.
+///
+/// # Parameters
+///
+/// - key: The master key, of the key size of the suite.
+/// - reader: The reader of the plaintext.
+/// - writer: The writer where the stream is written.
+/// - suite: The AEAD algorithm used to encrypt the chunks.
+/// - subkeys: Whether each chunk is encrypted with its own subkey.
+/// - aad: Associated data authenticated with every chunk.
+///
+/// # Errors
+///
+/// This method returns an error if an error occurs while
+/// reading, encrypting or writing.
+pub fn encrypt(
+    key: &[u8],
+    reader: &mut impl Read,
+    writer: &mut impl Write,
+    suite: CipherSuite,
+    subkeys: bool,
+    aad: &[u8],
+) -> Result<(), Box<dyn Error>> {
+    let encryptor = Encryptor::new(key, suite, subkeys)?;
+    writer.write_all(&encryptor.header())?;
+    encryptor.encrypt_from(reader, writer, aad, 0, |_, _| Ok(()))
+}
+
+/// Encrypts everything written to it as a chunked stream and
+/// writes the stream to an inner writer, a chunk at a time.
+///
+/// The last chunk is only known when no more plaintext is written,
+/// so the stream must be completed with finish. A stream dropped
+/// without calling finish is truncated and fails to decrypt.
+pub struct EncryptWriter<W: Write> {
+    cipher: ChunkCipher,
+    aad: Vec<u8>,
+    writer: W,
+    buffer: Vec<u8>,
+    index: u64,
+}
+
+impl<W: Write> EncryptWriter<W> {
+    /// Creates a writer for a new stream with a random salt
+    /// and writes the start of the stream to the inner writer.
+    ///
+    /// # Parameters
+    ///
+    /// - key: The master key, of the key size of the suite.
+    /// - writer: The writer where the stream is written.
+    /// - suite: The AEAD algorithm used to encrypt the chunks.
+    /// - subkeys: Whether each chunk is encrypted with its own subkey.
+    /// - aad: Associated data authenticated with every chunk.
+    ///
+    /// # Errors
+    ///
+    /// This method returns an error if the salt cannot be generated
+    /// or if the start of the stream cannot be written.
+    pub fn new(
+        key: &[u8],
+        mut writer: W,
+        suite: CipherSuite,
+        subkeys: bool,
+        aad: &[u8],
+    ) -> Result<Self, Box<dyn Error>> {
+        let encryptor = Encryptor::new(key, suite, subkeys)?;
+        writer.write_all(&encryptor.header())?;
+        Ok(EncryptWriter {
+            cipher: encryptor.cipher,
+            aad: aad.to_vec(),
+            writer,
+            buffer: Vec::with_capacity(CHUNK_SIZE + 1 + TAG_SIZE),
+            index: 0,
+        })
+    }
+
+    /// Encrypts the plaintext still buffered as the last chunk
+    /// of the stream and flushes the inner writer.
+    ///
+    /// # Returns
+    ///
+    /// The inner writer.
+    ///
+    /// # Errors
+    ///
+    /// This method returns an error if an error occurs while
+    /// encrypting or writing.
+    pub fn finish(mut self) -> io::Result<W> {
+        let mut chunk = mem::take(&mut self.buffer);
+        self.write_chunk(true, &mut chunk)?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+
+    // encrypts a chunk and writes it to the inner writer
+    fn write_chunk(&mut self, last: bool, chunk: &mut Vec<u8>) -> io::Result<()> {
+        self.cipher
+            .seal(self.index, last, &self.aad, chunk)
+            .map_err(io::Error::other)?;
+        self.writer.write_all(chunk)?;
+        self.index += 1;
+        Ok(())
+    }
+}
+
+impl<W: Write> Write for EncryptWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // a full chunk is only encrypted once a byte after it is
+        // written, since the last chunk is encrypted differently
+        let len = buf.len().min(CHUNK_SIZE + 1 - self.buffer.len());
+        self.buffer.extend_from_slice(&buf[..len]);
+        if self.buffer.len() > CHUNK_SIZE {
+            let rest = self.buffer.split_off(CHUNK_SIZE);
+            let mut chunk = mem::replace(&mut self.buffer, rest);
+            self.write_chunk(false, &mut chunk)?;
+        }
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// Decrypts a chunked stream read from an inner reader,
+/// a chunk at a time, as the plaintext is read from it.
+///
+/// Each chunk is authenticated before any of its plaintext is
+/// returned. Errors while decrypting are returned as io errors
+/// of kind InvalidData.
+pub struct DecryptReader<R: Read> {
+    cipher: ChunkCipher,
+    aad: Vec<u8>,
+    reader: R,
+    next: Vec<u8>,
+    plaintext: Vec<u8>,
+    position: usize,
+    index: u64,
+    done: bool,
+}
+
+impl<R: Read> DecryptReader<R> {
+    /// Creates a reader of the plaintext of a stream, reading
+    /// the start of the stream from the inner reader.
+    ///
+    /// # Parameters
+    ///
+    /// - key: The master key, of the key size of the suite.
+    /// - reader: The reader of the stream.
+    /// - suite: The AEAD algorithm the chunks were encrypted with.
+    /// - aad: Associated data the stream was encrypted with.
+    ///
+    /// # Errors
+    ///
+    /// This method returns an error if the start of the stream
+    /// cannot be read or if the key does not match the key commitment.
+    pub fn new(
+        key: &[u8],
+        mut reader: R,
+        suite: CipherSuite,
+        aad: &[u8],
+    ) -> Result<Self, Box<dyn Error>> {
+        let cipher = read_start(key, &mut reader, suite)?;
+        let next = read_chunk(&mut reader, SEALED_CHUNK_SIZE)?;
+        Ok(DecryptReader {
+            cipher,
+            aad: aad.to_vec(),
+            reader,
+            next,
+            plaintext: Vec::new(),
+            position: 0,
+            index: 0,
+            done: false,
+        })
+    }
+
+    /// Returns the inner reader.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    // decrypts the next chunk of the stream into the plaintext
+    fn open_next(&mut self) -> io::Result<()> {
+        let mut chunk = mem::take(&mut self.next);
+        if chunk.len() < TAG_SIZE {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                CipherError("encrypted stream is truncated".into()),
+            ));
+        }
+        self.next = read_chunk(&mut self.reader, SEALED_CHUNK_SIZE)?;
+        let last = self.next.is_empty();
+        self.cipher
+            .open(self.index, last, &self.aad, &mut chunk)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        self.plaintext = chunk;
+        self.position = 0;
+        self.index += 1;
+        self.done = last;
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for DecryptReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.position == self.plaintext.len() {
+            if self.done {
+                return Ok(0);
+            }
+            self.open_next()?;
+        }
+        let len = buf.len().min(self.plaintext.len() - self.position);
+        buf[..len].copy_from_slice(&self.plaintext[self.position..self.position + len]);
+        self.position += len;
+        Ok(len)
+    }
+}
+
+/// Reads any part of the plaintext of a chunked stream that can be
+/// seeked, decrypting only the chunks that hold it, so a part of a
+/// large file is read without decrypting the rest of it.
+///
+/// Each chunk is authenticated, with its index and whether it is
+/// the last one, before any of its plaintext is returned, but the
+/// chunks that are not read are not checked.
+pub struct RangeReader<R: Read + Seek> {
+    cipher: ChunkCipher,
+    aad: Vec<u8>,
+    reader: R,
+    // the positions of the first chunk and of the end of the stream
+    start: u64,
+    end: u64,
+}
+
+impl<R: Read + Seek> RangeReader<R> {
+    /// Creates a reader of the parts of the plaintext of a stream,
+    /// reading the start of the stream from the inner reader, which
+    /// holds the stream up to its end.
+    ///
+    /// # Parameters
+    ///
+    /// - key: The master key, of the key size of the suite.
+    /// - reader: The reader of the stream.
+    /// - suite: The AEAD algorithm the chunks were encrypted with.
+    /// - aad: Associated data the stream was encrypted with.
+    ///
+    /// # Errors
+    ///
+    /// This method returns an error if the start of the stream
+    /// cannot be read or if the key does not match the key commitment.
+    pub fn new(
+        key: &[u8],
+        mut reader: R,
+        suite: CipherSuite,
+        aad: &[u8],
+    ) -> Result<Self, Box<dyn Error>> {
+        let cipher = read_start(key, &mut reader, suite)?;
+        let start = reader.stream_position()?;
+        let end = reader.seek(SeekFrom::End(0))?;
+        if end - start < TAG_SIZE as u64 {
+            return Err(Box::new(CipherError(
+                "encrypted stream is truncated".into(),
+            )));
+        }
+        Ok(RangeReader {
+            cipher,
+            aad: aad.to_vec(),
+            reader,
+            start,
+            end,
+        })
+    }
+
+    /// Returns the length of the plaintext.
+    pub fn len(&self) -> u64 {
+        let sealed = self.end - self.start;
+        let chunks = sealed.div_ceil(SEALED_CHUNK_SIZE as u64).max(1);
+        sealed - chunks * TAG_SIZE as u64
+    }
+
+    /// Returns whether the plaintext is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Decrypts a part of the plaintext and writes it to the writer.
+    ///
+    /// # Parameters
+    ///
+    /// - offset: The position of the part in the plaintext.
+    /// - length: The length of the part.
+    /// - writer: The writer where the part is written.
+    ///
+    /// # Errors
+    ///
+    /// A CipherError if the part goes beyond the end of the
+    /// plaintext or a chunk that holds it fails authentication, or
+    /// an io::Error if an error occurs while reading or writing.
+    pub fn read_range(
+        &mut self,
+        offset: u64,
+        length: u64,
+        writer: &mut impl Write,
+    ) -> Result<(), Box<dyn Error>> {
+        let end = offset
+            .checked_add(length)
+            .filter(|&end| end <= self.len())
+            .ok_or_else(|| {
+                CipherError(format!(
+                    "the part goes beyond the end of the plaintext, of {} bytes",
+                    self.len()
+                ))
+            })?;
+        let mut position = offset;
+        while position < end {
+            let index = position / CHUNK_SIZE as u64;
+            let chunk_start = index * CHUNK_SIZE as u64;
+            let chunk = self.open_chunk(index)?;
+            let to = ((end - chunk_start) as usize).min(chunk.len());
+            writer.write_all(&chunk[(position - chunk_start) as usize..to])?;
+            position = chunk_start + to as u64;
+        }
+        Ok(())
+    }
+
+    // reads and decrypts the chunk with the given index
+    fn open_chunk(&mut self, index: u64) -> Result<Vec<u8>, Box<dyn Error>> {
+        let position = self.start + index * SEALED_CHUNK_SIZE as u64;
+        self.reader.seek(SeekFrom::Start(position))?;
+        let mut chunk = read_chunk(&mut self.reader, SEALED_CHUNK_SIZE)?;
+        let last = position + chunk.len() as u64 >= self.end;
+        self.cipher.open(index, last, &self.aad, &mut chunk)?;
+        Ok(chunk)
+    }
+}
+
+/// Decrypts a chunked stream read from the reader and writes
+/// the plaintext to the writer.
+///
+/// # Parameters
+///
+/// - key: The master key, of the key size of the suite.
+/// - reader: The reader of the stream.
+/// - writer: The writer where the plaintext is written.
+/// - suite: The AEAD algorithm the chunks were encrypted with.
+/// - aad: Associated data the stream was encrypted with.
+///
+/// # Errors
+///
+/// This method returns an error if an error occurs while
+/// reading or writing, if the key does not match the key
+/// commitment or if the stream fails authentication.
+pub fn decrypt(
+    key: &[u8],
+    reader: &mut impl Read,
+    writer: &mut impl Write,
+    suite: CipherSuite,
+    aad: &[u8],
+) -> Result<(), Box<dyn Error>> {
+    let mut plaintext = DecryptReader::new(key, reader, suite, aad)?;
+    io::copy(&mut plaintext, writer)?;
+    Ok(())
+}
+
+// reads the salt, the flags and the key commitment at the start of
+// a stream, returning the cipher of its chunks
+fn read_start(
+    key: &[u8],
+    reader: &mut impl Read,
+    suite: CipherSuite,
+) -> Result<ChunkCipher, Box<dyn Error>> {
+    let truncated = || CipherError("encrypted stream is truncated".into());
+    let mut salt = [0u8; SALT_SIZE];
+    reader.read_exact(&mut salt).map_err(|_| truncated())?;
+    let mut flags = [0u8; 1];
+    reader.read_exact(&mut flags).map_err(|_| truncated())?;
+    if flags[0] & !FLAG_SUBKEYS != 0 {
+        return Err(Box::new(CipherError("unknown stream flags".into())));
+    }
+    let cipher = ChunkCipher::new(key, &salt, suite, flags[0] & FLAG_SUBKEYS != 0);
+    let mut commitment = [0u8; COMMITMENT_SIZE];
+    reader
+        .read_exact(&mut commitment)
+        .map_err(|_| truncated())?;
+    // compares every byte, so the time does not depend on the key
+    let matches: bool = commitment.ct_eq(&cipher.kdf.commitment()).into();
+    if !matches {
+        return Err(Box::new(CipherError(
+            "the key does not match the encrypted file".into(),
+        )));
+    }
+    Ok(cipher)
+}
+
+// reads the next chunk of at most size bytes
+fn read_chunk(reader: &mut impl Read, size: usize) -> io::Result<Vec<u8>> {
+    let mut chunk = Vec::with_capacity(size + TAG_SIZE);
+    reader.by_ref().take(size as u64).read_to_end(&mut chunk)?;
+    Ok(chunk)
+}
+
+/////////////////////////////////
+// Unit testing of the module. //
+/////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GCM: CipherSuite = CipherSuite::Aes256Gcm;
+
+    fn round_trip(len: usize, suite: CipherSuite, subkeys: bool) {
+        let key = [0x33u8; 32];
+        let message: Vec<u8> = (0..len).map(|i| i as u8).collect();
+        let mut stream = Vec::new();
+        encrypt(&key, &mut &message[..], &mut stream, suite, subkeys, b"aad").unwrap();
+        let mut plaintext = Vec::new();
+        decrypt(&key, &mut &stream[..], &mut plaintext, suite, b"aad").unwrap();
+        assert_eq!(plaintext, message);
+    }
+
+    #[test]
+    fn chunked_round_trip() {
+        for &len in &[0, 1, CHUNK_SIZE, CHUNK_SIZE + 1, 2 * CHUNK_SIZE + 5] {
+            round_trip(len, GCM, false);
+            round_trip(len, GCM, true);
+        }
+    }
+
+    #[test]
+    fn chunked_ranges() {
+        let key = [0x33u8; 32];
+        let message: Vec<u8> = (0..2 * CHUNK_SIZE + 5).map(|i| i as u8).collect();
+        let mut stream = Vec::new();
+        encrypt(&key, &mut &message[..], &mut stream, GCM, true, b"aad").unwrap();
+        let mut reader = RangeReader::new(&key, io::Cursor::new(&stream), GCM, b"aad").unwrap();
+        assert_eq!(reader.len(), message.len() as u64);
+        for &(offset, length) in &[
+            (0, 0),
+            (0, 10),
+            (CHUNK_SIZE - 3, 6),
+            (5, 2 * CHUNK_SIZE),
+            (2 * CHUNK_SIZE, 5),
+        ] {
+            let mut part = Vec::new();
+            reader
+                .read_range(offset as u64, length as u64, &mut part)
+                .unwrap();
+            assert_eq!(part, &message[offset..offset + length]);
+        }
+        assert!(reader
+            .read_range(2 * CHUNK_SIZE as u64, 6, &mut Vec::new())
+            .is_err());
+
+        // a chunk that is not read may be corrupt, one that is read not
+        let mut corrupt = stream.clone();
+        corrupt[HEADER_SIZE + 1] ^= 1;
+        let mut reader = RangeReader::new(&key, io::Cursor::new(&corrupt), GCM, b"aad").unwrap();
+        assert!(reader.read_range(0, 1, &mut Vec::new()).is_err());
+        assert!(reader
+            .read_range(CHUNK_SIZE as u64, 1, &mut Vec::new())
+            .is_ok());
+        // a truncated stream, whose last chunk is not the last one
+        let truncated = &stream[..stream.len() - 5 - TAG_SIZE];
+        let mut reader = RangeReader::new(&key, io::Cursor::new(truncated), GCM, b"aad").unwrap();
+        assert!(reader
+            .read_range(CHUNK_SIZE as u64, 1, &mut Vec::new())
+            .is_err());
+
+        let mut empty = Vec::new();
+        encrypt(&key, &mut &[][..], &mut empty, GCM, false, b"aad").unwrap();
+        let reader = RangeReader::new(&key, io::Cursor::new(&empty), GCM, b"aad").unwrap();
+        assert!(reader.is_empty());
+    }
+
+    #[test]
+    fn chunked_round_trip_siv() {
+        round_trip(CHUNK_SIZE + 1, CipherSuite::Aes256GcmSiv, false);
+        round_trip(CHUNK_SIZE + 1, CipherSuite::Aes256GcmSiv, true);
+    }
+
+    #[test]
+    fn chunked_derivation_unique() {
+        let kdf = ChunkKdf::new(&[0x33u8; 32], &[0x01u8; SALT_SIZE]);
+        let other = ChunkKdf::new(&[0x33u8; 32], &[0x02u8; SALT_SIZE]);
+        assert_ne!(kdf.nonce(0, false), kdf.nonce(1, false));
+        assert_ne!(kdf.nonce(0, false), kdf.nonce(0, true));
+        assert_ne!(kdf.nonce(0, false), other.nonce(0, false));
+        assert_ne!(kdf.subkey(0, false), kdf.subkey(1, false));
+    }
+
+    #[test]
+    fn chunked_random_salt() {
+        let key = [0x33u8; 32];
+        let mut first = Vec::new();
+        let mut second = Vec::new();
+        encrypt(&key, &mut &b"message"[..], &mut first, GCM, false, b"").unwrap();
+        encrypt(&key, &mut &b"message"[..], &mut second, GCM, false, b"").unwrap();
+        assert_ne!(first, second);
+    }
+
+    #[test]
+    fn chunked_truncated() {
+        let key = [0x33u8; 32];
+        let message = vec![0x55u8; 2 * CHUNK_SIZE + 5];
+        let mut stream = Vec::new();
+        encrypt(&key, &mut &message[..], &mut stream, GCM, false, b"").unwrap();
+        // drop the last chunk
+        stream.truncate(HEADER_SIZE + 2 * SEALED_CHUNK_SIZE);
+        assert!(decrypt(&key, &mut &stream[..], &mut Vec::new(), GCM, b"").is_err());
+    }
+
+    #[test]
+    fn chunked_key_commitment() {
+        let key = [0x33u8; 32];
+        let kdf = ChunkKdf::new(&key, &[0x01u8; SALT_SIZE]);
+        let other = ChunkKdf::new(&[0x34u8; 32], &[0x01u8; SALT_SIZE]);
+        assert_ne!(kdf.commitment(), other.commitment());
+        let mut stream = Vec::new();
+        encrypt(&key, &mut &b"message"[..], &mut stream, GCM, false, b"").unwrap();
+        let err = decrypt(&[0x34u8; 32], &mut &stream[..], &mut Vec::new(), GCM, b"");
+        assert_eq!(
+            err.unwrap_err().to_string(),
+            "the key does not match the encrypted file"
+        );
+    }
+
+    #[test]
+    fn chunked_resume() {
+        let key = [0x33u8; 32];
+        let message: Vec<u8> = (0..(CHECKPOINT_CHUNKS as usize + 3) * CHUNK_SIZE)
+            .map(|i| (i / 7) as u8)
+            .collect();
+        let encryptor = Encryptor::new(&key, GCM, true).unwrap();
+        let mut stream = encryptor.header();
+        let mut saved = None;
+        // interrupt the encryption at the first checkpoint
+        let interrupted = encryptor.encrypt_from(&mut &message[..], &mut stream, b"", 0, |w, n| {
+            saved = Some((w.len(), n));
+            Err(Box::new(CipherError("interrupted".into())))
+        });
+        assert!(interrupted.is_err());
+        let (len, chunks) = saved.unwrap();
+        assert_eq!(len, HEADER_SIZE + chunks as usize * SEALED_CHUNK_SIZE);
+        stream.truncate(len);
+        let resumed = Encryptor::with_salt(&key, GCM, true, encryptor.salt());
+        let rest = &message[chunks as usize * CHUNK_SIZE..];
+        resumed
+            .encrypt_from(&mut &rest[..], &mut stream, b"", chunks, |_, _| Ok(()))
+            .unwrap();
+        let mut plaintext = Vec::new();
+        decrypt(&key, &mut &stream[..], &mut plaintext, GCM, b"").unwrap();
+        assert!(plaintext == message);
+    }
+
+    #[test]
+    fn chunked_threads() {
+        let key = [0x33u8; 32];
+        let message: Vec<u8> = (0..9 * CHUNK_SIZE + 3).map(|i| (i / 5) as u8).collect();
+        let encryptor = Encryptor::new(&key, GCM, false).unwrap().with_threads(4);
+        let mut stream = encryptor.header();
+        encryptor
+            .encrypt_from(&mut &message[..], &mut stream, b"", 0, |_, _| Ok(()))
+            .unwrap();
+        let serial = Encryptor::with_salt(&key, GCM, false, encryptor.salt());
+        let mut expected = serial.header();
+        serial
+            .encrypt_from(&mut &message[..], &mut expected, b"", 0, |_, _| Ok(()))
+            .unwrap();
+        assert!(stream == expected);
+    }
+
+    #[test]
+    fn writer_and_reader() {
+        let key = [0x33u8; 32];
+        for &len in &[0, 1, CHUNK_SIZE, CHUNK_SIZE + 1, 2 * CHUNK_SIZE + 5] {
+            let message: Vec<u8> = (0..len).map(|i| (i / 3) as u8).collect();
+            let mut writer = EncryptWriter::new(&key, Vec::new(), GCM, true, b"aad").unwrap();
+            for piece in message.chunks(1000) {
+                writer.write_all(piece).unwrap();
+            }
+            let stream = writer.finish().unwrap();
+            let chunks = len.div_ceil(CHUNK_SIZE).max(1);
+            assert_eq!(stream.len(), HEADER_SIZE + len + chunks * TAG_SIZE);
+            let mut plaintext = Vec::new();
+            decrypt(&key, &mut &stream[..], &mut plaintext, GCM, b"aad").unwrap();
+            assert!(plaintext == message);
+            let mut reader = DecryptReader::new(&key, &stream[..], GCM, b"aad").unwrap();
+            let mut plaintext = Vec::new();
+            let mut buf = [0u8; 777];
+            loop {
+                let n = reader.read(&mut buf).unwrap();
+                if n == 0 {
+                    break;
+                }
+                plaintext.extend_from_slice(&buf[..n]);
+            }
+            assert!(plaintext == message);
+        }
+    }
+
+    #[test]
+    fn unfinished_writer() {
+        let key = [0x33u8; 32];
+        let mut stream = Vec::new();
+        let mut writer = EncryptWriter::new(&key, &mut stream, GCM, false, b"").unwrap();
+        writer.write_all(&vec![0x55u8; CHUNK_SIZE + 5]).unwrap();
+        drop(writer);
+        let mut reader = DecryptReader::new(&key, &stream[..], GCM, b"").unwrap();
+        let error = reader.read_to_end(&mut Vec::new()).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn chunked_associated_data() {
+        let key = [0x33u8; 32];
+        let mut stream = Vec::new();
+        encrypt(
+            &key,
+            &mut &b"message"[..],
+            &mut stream,
+            GCM,
+            false,
+            b"header",
+        )
+        .unwrap();
+        assert!(decrypt(&key, &mut &stream[..], &mut Vec::new(), GCM, b"header",).is_ok());
+        assert!(decrypt(&key, &mut &stream[..], &mut Vec::new(), GCM, b"other").is_err());
+    }
+}
@@ -0,0 +1,321 @@
+use std::collections::HashSet;
+
+use rug::integer::Order;
+use rug::Integer;
+
+use crate::crypto::error::ShamirError;
+use crate::crypto::shamir::Share;
+use crate::math::crt::{chinese_remainder, coprime_moduli};
+use crate::math::random::Rng;
+
+/// Maximum number of shares of a secret, since the modulus
+/// of each share is found searching for primes.
+pub const MAX_SHARES: usize = 255;
+
+// Size in bytes of the blocks of a secret shared separately
+const BLOCK_SIZE: usize = 32;
+
+// Bits between the modulus of the blocks and the moduli of the
+// shares, so the Asmuth-Bloom condition holds for any k and n
+const MARGIN_BITS: u32 = 64;
+
+// Radix of the elements of the shares
+const RADIX: i32 = 36;
+
+// Separator of the values of the blocks of a secret in a share
+const BLOCK_SEPARATOR: &str = ".";
+
+/// Splits a secret with the Asmuth-Bloom threshold scheme.
+///
+/// Each block of the secret is a number s lower than a prime m0,
+/// and the share with index i is y mod mi, where y = s + a * m0
+/// for a random a such that y is lower than the product of the k
+/// smallest moduli. Any k shares determine y with the chinese
+/// remainder theorem, and s = y mod m0.
+///
+/// # Parameters
+///
+/// - secret: The secret to share.
+/// - n: The total number of shares to return (2 <= n <= MAX_SHARES).
+/// - k: The minimum number of shares to recover the secret (0 < k <= n).
+///
+/// # Returns
+///
+/// A vector with n Shares, the index of each share in base 36
+/// and the residues of the blocks joined with dots.
+///
+/// # Errors
+///
+/// A ShamirError::InvalidThreshold if the parameter constraints
+/// are not met.
+pub fn split_secret(secret: &[u8], n: usize, k: usize) -> Result<Vec<Share>, ShamirError> {
+    if !(2..=MAX_SHARES).contains(&n) {
+        return Err(ShamirError::InvalidThreshold(format!(
+            "n must be in the range 2 <= n <= {}",
+            MAX_SHARES
+        )));
+    }
+    if k == 0 || k > n {
+        return Err(ShamirError::InvalidThreshold(
+            "k must be in the range 0 < k <= n".into(),
+        ));
+    }
+    let (m0, moduli) = moduli(n);
+    let smallest = product(&moduli[..k]);
+    if smallest <= &m0 * product(&moduli[n - k + 1..]) {
+        return Err(ShamirError::InvalidThreshold(
+            "the moduli do not satisfy the Asmuth-Bloom condition".into(),
+        ));
+    }
+    // every y = s + a * m0 with a below the bound is lower than smallest
+    let bound = smallest / &m0;
+    let mut rng = Rng::new().map_err(|_| ShamirError::RandomError)?;
+    let mut values = vec![Vec::new(); n];
+    for block in blocks(secret) {
+        let y = Integer::from_digits(block, Order::Msf) + rng.below(&bound) * &m0;
+        for (share_values, modulus) in values.iter_mut().zip(&moduli) {
+            share_values.push(Integer::from(&y % modulus).to_string_radix(RADIX));
+        }
+    }
+    Ok(values
+        .into_iter()
+        .enumerate()
+        .map(|(i, share_values)| {
+            let x = Integer::from(i + 1).to_string_radix(RADIX);
+            Share(x, share_values.join(BLOCK_SEPARATOR))
+        })
+        .collect())
+}
+
+/// Recovers a secret from shares of split_secret.
+///
+/// Every block but the last one has the size of a block, and the
+/// leading zero bytes of the last one are not kept, see
+/// shamir::restore_length.
+///
+/// # Parameters
+///
+/// - shares: At least k of the shares, each with a unique index.
+///
+/// # Returns
+///
+/// A vector of bytes containing the original secret, or other
+/// bytes if there are less than k shares.
+///
+/// # Errors
+///
+/// A ShamirError::InvalidThreshold if no share is given, a
+/// ShamirError::DuplicateShare if two shares have the same index,
+/// a ShamirError::ParseError if an element is not a number and a
+/// ShamirError::FieldMismatch if a residue is not lower than its
+/// modulus or if the shares do not have the same number of blocks.
+pub fn recover_secret(shares: &[Share]) -> Result<Vec<u8>, ShamirError> {
+    let (m0, numbers) = recover_numbers(shares, shares.len())?;
+    let blocks = numbers.len();
+    let mut secret = Vec::new();
+    for (block, y) in numbers.into_iter().enumerate() {
+        let digits = (y % &m0).to_digits::<u8>(Order::Msf);
+        if block + 1 < blocks {
+            secret.extend(vec![0u8; BLOCK_SIZE.saturating_sub(digits.len())]);
+        }
+        secret.extend(digits);
+    }
+    Ok(secret)
+}
+
+/// Checks that the shares beyond the first k have the residues of
+/// the numbers the first k determine.
+///
+/// # Parameters
+///
+/// - shares: The shares, each with a unique index.
+/// - k: The minimum number of shares to recover the secret.
+///
+/// # Returns
+///
+/// Whether all the shares are shares of the same secret, always
+/// true with k shares or less.
+///
+/// # Errors
+///
+/// The errors of recover_secret if the shares cannot be parsed.
+pub fn verify_shares(shares: &[Share], k: usize) -> Result<bool, ShamirError> {
+    if k == 0 {
+        return Err(ShamirError::InvalidThreshold(
+            "K must be greater than 0".into(),
+        ));
+    }
+    let (indices, residues) = parse_shares(shares)?;
+    if shares.len() <= k {
+        return Ok(true);
+    }
+    let (_, numbers) = recover_numbers(shares, k)?;
+    let (_, moduli) = moduli(*indices.iter().max().unwrap());
+    for (index, share_residues) in indices.iter().zip(&residues).skip(k) {
+        let modulus = &moduli[index - 1];
+        for (y, residue) in numbers.iter().zip(share_residues) {
+            if Integer::from(y % modulus) != *residue {
+                return Ok(false);
+            }
+        }
+    }
+    Ok(true)
+}
+
+// the modulus of the blocks and the moduli of the first n shares
+fn moduli(n: usize) -> (Integer, Vec<Integer>) {
+    let bits = 8 * BLOCK_SIZE as u32;
+    let m0 = coprime_moduli(bits, 1).remove(0);
+    (m0, coprime_moduli(bits + MARGIN_BITS, n))
+}
+
+// the blocks of a secret, at least one
+fn blocks(secret: &[u8]) -> Vec<&[u8]> {
+    if secret.is_empty() {
+        return vec![secret];
+    }
+    secret.chunks(BLOCK_SIZE).collect()
+}
+
+fn product(values: &[Integer]) -> Integer {
+    values
+        .iter()
+        .fold(Integer::from(1), |product, value| product * value)
+}
+
+// recovers the numbers y of every block from the first k shares,
+// returning them together with the modulus of the blocks
+fn recover_numbers(shares: &[Share], k: usize) -> Result<(Integer, Vec<Integer>), ShamirError> {
+    let (indices, residues) = parse_shares(shares)?;
+    let (m0, moduli) = moduli(*indices.iter().max().unwrap());
+    for (index, share_residues) in indices.iter().zip(&residues) {
+        let modulus = &moduli[index - 1];
+        if let Some(residue) = share_residues.iter().find(|residue| *residue >= modulus) {
+            return Err(ShamirError::FieldMismatch(format!(
+                "{} is not lower than the modulus of share {}",
+                residue.to_string_radix(RADIX),
+                index
+            )));
+        }
+    }
+    let numbers = (0..residues[0].len())
+        .map(|block| {
+            let congruences: Vec<(Integer, Integer)> = indices
+                .iter()
+                .zip(&residues)
+                .take(k)
+                .map(|(index, share_residues)| {
+                    (share_residues[block].clone(), moduli[index - 1].clone())
+                })
+                .collect();
+            // the moduli are distinct primes
+            chinese_remainder(&congruences).unwrap()
+        })
+        .collect();
+    Ok((m0, numbers))
+}
+
+// parses the indices and the residues of the blocks of the shares
+fn parse_shares(shares: &[Share]) -> Result<(Vec<usize>, Vec<Vec<Integer>>), ShamirError> {
+    if shares.is_empty() {
+        return Err(ShamirError::InvalidThreshold("no shares were given".into()));
+    }
+    let parse = |s: &str| {
+        Integer::parse_radix(s, RADIX)
+            .map(Integer::from)
+            .map_err(|_| ShamirError::ParseError(s.to_string()))
+    };
+    let mut indices = Vec::with_capacity(shares.len());
+    let mut seen = HashSet::with_capacity(shares.len());
+    let mut residues = Vec::with_capacity(shares.len());
+    for Share(x, y) in shares {
+        let index = parse(x)?
+            .to_usize()
+            .filter(|index| (1..=MAX_SHARES).contains(index))
+            .ok_or_else(|| ShamirError::ParseError(x.clone()))?;
+        if !seen.insert(index) {
+            return Err(ShamirError::DuplicateShare(x.to_lowercase()));
+        }
+        indices.push(index);
+        residues.push(
+            y.split(BLOCK_SEPARATOR)
+                .map(parse)
+                .collect::<Result<Vec<_>, _>>()?,
+        );
+    }
+    if residues
+        .iter()
+        .any(|values| values.len() != residues[0].len())
+    {
+        return Err(ShamirError::FieldMismatch(
+            "the shares do not have the same number of blocks".into(),
+        ));
+    }
+    Ok((indices, residues))
+}
+
+/////////////////////////////////
+// Unit testing of the module. //
+/////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_and_recover() {
+        let secret: Vec<u8> = (1..=70).collect();
+        let shares = split_secret(&secret, 5, 3).unwrap();
+        assert_eq!(shares.len(), 5);
+        assert_eq!(shares[0].1.split(BLOCK_SEPARATOR).count(), 3);
+        assert_eq!(recover_secret(&shares[2..]).unwrap(), secret);
+        assert_eq!(recover_secret(&shares).unwrap(), secret);
+        let some = vec![shares[4].clone(), shares[0].clone(), shares[2].clone()];
+        assert_eq!(recover_secret(&some).unwrap(), secret);
+        assert_ne!(recover_secret(&shares[..2]).unwrap(), secret);
+    }
+
+    #[test]
+    fn leading_zeros() {
+        let secret = vec![0u8, 0, 0x42];
+        let shares = split_secret(&secret, 3, 2).unwrap();
+        assert_eq!(recover_secret(&shares[1..]).unwrap(), vec![0x42]);
+        let shares = split_secret(&[], 3, 3).unwrap();
+        assert_eq!(recover_secret(&shares).unwrap(), Vec::<u8>::new());
+    }
+
+    #[test]
+    fn verify() {
+        let mut shares = split_secret(&[0x5au8; 40], 5, 3).unwrap();
+        assert!(verify_shares(&shares, 3).unwrap());
+        shares[4].1 = shares[3].1.clone();
+        assert!(!verify_shares(&shares, 3).unwrap());
+        assert!(verify_shares(&shares[..4], 3).unwrap());
+    }
+
+    #[test]
+    fn errors() {
+        let shares = split_secret(b"secret", 3, 2).unwrap();
+        assert!(matches!(
+            split_secret(b"secret", MAX_SHARES + 1, 2),
+            Err(ShamirError::InvalidThreshold(_))
+        ));
+        assert!(split_secret(b"secret", 3, 4).is_err());
+        let twice = vec![shares[0].clone(), shares[0].clone()];
+        assert_eq!(
+            recover_secret(&twice),
+            Err(ShamirError::DuplicateShare("1".into()))
+        );
+        let invalid = vec![shares[0].clone(), Share("2".into(), "-".into())];
+        assert_eq!(
+            recover_secret(&invalid),
+            Err(ShamirError::ParseError("-".into()))
+        );
+        let large = vec![shares[0].clone(), Share("2".into(), "z".repeat(80))];
+        assert!(matches!(
+            recover_secret(&large),
+            Err(ShamirError::FieldMismatch(_))
+        ));
+        assert!(recover_secret(&[]).is_err());
+    }
+}
@@ -12,3 +12,44 @@ impl fmt::Display for CipherError {
 }
 
 impl Error for CipherError {}
+
+/// An error of shamir secret sharing, see the shamir module.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ShamirError {
+    /// The number of shares or the threshold is out of its range.
+    InvalidThreshold(String),
+    /// Two shares have the same index, in the radix of the shares.
+    DuplicateShare(String),
+    /// A share or a value is not a number in the radix expected.
+    ParseError(String),
+    /// The shares or the secret do not belong to the same field,
+    /// or do not fit in it.
+    FieldMismatch(String),
+    /// The shares name a secret sharing scheme that is not known.
+    UnknownScheme(String),
+    /// The random bytes of the shares could not be generated.
+    RandomError,
+    /// The secret recovered does not have the structure embedded
+    /// when it was split, so a share was altered.
+    CheatingDetected,
+}
+
+impl fmt::Display for ShamirError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ShamirError::InvalidThreshold(reason) => write!(f, "{}", reason),
+            ShamirError::DuplicateShare(x) => write!(f, "two shares have the index {}", x),
+            ShamirError::ParseError(value) => write!(f, "'{}' is not a valid share value", value),
+            ShamirError::FieldMismatch(reason) => write!(f, "{}", reason),
+            ShamirError::UnknownScheme(name) => {
+                write!(f, "unknown secret sharing scheme '{}'", name)
+            }
+            ShamirError::RandomError => write!(f, "Error while generating random bytes"),
+            ShamirError::CheatingDetected => {
+                write!(f, "the secret recovered is not valid, a share was altered")
+            }
+        }
+    }
+}
+
+impl Error for ShamirError {}
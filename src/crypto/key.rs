@@ -0,0 +1,421 @@
+use std::error::Error;
+use std::fmt;
+
+use sha2::{Digest, Sha256};
+use subtle::{Choice, ConstantTimeEq};
+use zeroize::Zeroize;
+
+use crate::crypto::error::{CipherError, ShamirError};
+use crate::crypto::scheme::SecretSharingScheme;
+use crate::crypto::shamir::{self, Share, ShareIter};
+use crate::crypto::suite::CipherSuite;
+use crate::crypto::Cipher;
+use crate::math::scrypt::{self, Params};
+use crate::math::Prime;
+use crate::policy::Policy;
+use crate::secure::SecureBuffer;
+
+/// Base 2 logarithm of the cost N of scrypt when deriving the key of
+/// a password, 64 MiB of memory. The tests use a low cost, which
+/// the key slots record as they record this one.
+#[cfg(not(test))]
+pub const PASSWORD_LOG_N: u8 = 16;
+#[cfg(test)]
+pub const PASSWORD_LOG_N: u8 = 4;
+
+/// Highest cost of scrypt accepted from a file, 1 GiB of memory, so
+/// a crafted file cannot exhaust the memory of the computer.
+pub const MAX_PASSWORD_LOG_N: u8 = 20;
+
+/// The key of a cipher, which can be split into shares and
+/// recovered from them.
+///
+/// The bytes of the key are kept in locked memory, see SecureBuffer,
+/// overwritten with zeros when it is dropped, and never printed.
+pub struct SecretKey {
+    bytes: SecureBuffer,
+}
+
+impl SecretKey {
+    /// Derives a key from a password given by the user with scrypt,
+    /// so each guess of the password takes the time and memory of
+    /// scrypt.
+    ///
+    /// # Parameters
+    ///
+    /// - password: The password of the user.
+    /// - salt: A random salt, different for each key derived.
+    /// - log_n: Base 2 logarithm of the cost N of scrypt, at most
+    ///   MAX_PASSWORD_LOG_N.
+    /// - suite: The AEAD algorithm the key is used with.
+    pub fn from_password(password: &str, salt: &[u8], log_n: u8, suite: CipherSuite) -> Self {
+        let params = Params { log_n, r: 8, p: 1 };
+        let mut key = SecretKey::new(vec![0u8; suite.key_size()]);
+        scrypt::scrypt(password.as_bytes(), salt, params, key.bytes.as_mut_bytes());
+        key
+    }
+
+    /// Derives a key from the content of a key file.
+    ///
+    /// # Parameters
+    ///
+    /// - content: The bytes of the key file.
+    /// - suite: The AEAD algorithm the key is used with.
+    pub fn from_keyfile(content: &[u8], suite: CipherSuite) -> Self {
+        let mut digest = Sha256::digest(content);
+        let key = SecretKey::new(digest[..suite.key_size()].to_vec());
+        digest.as_mut_slice().zeroize();
+        key
+    }
+
+    /// Generates a random key, used as the data key of a file.
+    ///
+    /// # Parameters
+    ///
+    /// - suite: The AEAD algorithm the key is used with.
+    ///
+    /// # Errors
+    ///
+    /// A CipherError if the key cannot be generated.
+    pub fn random(suite: CipherSuite) -> Result<Self, CipherError> {
+        SecretKey::generate(suite.key_size())
+    }
+
+    /// Generates a random key of any length, which is meant to be
+    /// split without ever being shown.
+    ///
+    /// # Parameters
+    ///
+    /// - length: The length of the key in bytes.
+    ///
+    /// # Errors
+    ///
+    /// A CipherError if the key cannot be generated.
+    pub fn generate(length: usize) -> Result<Self, CipherError> {
+        let mut key = SecretKey::new(vec![0u8; length]);
+        getrandom::getrandom(key.bytes.as_mut_bytes())
+            .map_err(|_| CipherError("Error while generating a key".into()))?;
+        Ok(key)
+    }
+
+    /// Recovers a key from its shares.
+    ///
+    /// The shares do not keep the leading zero bytes of the key, which
+    /// are restored when the length of the key is given.
+    ///
+    /// # Parameters
+    ///
+    /// - shares: The shares, each with a unique first element.
+    /// - prime: The prime of the field of the shares, None for the default.
+    /// - length: The length of the key recorded with the shares, or None.
+    ///
+    /// # Errors
+    ///
+    /// A ShamirError if there are two shares with the same first
+    /// element or if it failed to parse the shares.
+    pub fn recover(
+        shares: impl Iterator<Item = Share>,
+        prime: Option<&Prime>,
+        length: Option<usize>,
+    ) -> Result<Self, ShamirError> {
+        Ok(SecretKey::new(match length {
+            Some(length) => shamir::recover_secret_with_length(shares, prime, length)?,
+            None => shamir::recover_secret(shares, prime)?,
+        }))
+    }
+
+    /// Recovers a key from shares split with a secret sharing scheme.
+    ///
+    /// # Parameters
+    ///
+    /// - scheme: The scheme the key was split with.
+    /// - shares: The shares, each with a unique first element.
+    /// - length: The length of the key recorded with the shares, or None.
+    ///
+    /// # Errors
+    ///
+    /// The errors of SecretSharingScheme::recover.
+    pub fn recover_with(
+        scheme: &dyn SecretSharingScheme,
+        shares: &[Share],
+        length: Option<usize>,
+    ) -> Result<Self, ShamirError> {
+        Ok(SecretKey::new(scheme.recover(shares, length)?))
+    }
+
+    /// Recovers a key from the shares of a key split with a policy.
+    ///
+    /// # Parameters
+    ///
+    /// - policy: The policy the key was split with.
+    /// - shares: The shares given, together with the name of their holder.
+    /// - length: The length of the key, see recover.
+    ///
+    /// # Errors
+    ///
+    /// This method returns an error if the holders of the shares do
+    /// not satisfy the policy or if it failed to parse the shares.
+    pub fn recover_by(
+        policy: &Policy,
+        shares: &[(String, Share)],
+        length: Option<usize>,
+    ) -> Result<Self, Box<dyn Error>> {
+        let key = SecretKey::new(policy.recover(shares)?);
+        // the value of the policy does not keep the leading zero bytes
+        match length.filter(|&length| length > key.len()) {
+            Some(length) => {
+                let mut bytes = Vec::with_capacity(length);
+                bytes.resize(length - key.len(), 0);
+                bytes.extend_from_slice(key.expose());
+                Ok(SecretKey::new(bytes))
+            }
+            None => Ok(key),
+        }
+    }
+
+    /// Splits the key into n shares with only needing k
+    /// to recover it.
+    ///
+    /// # Parameters
+    ///
+    /// - n: The total number of shares to return (2 < n <= MAX_SHARES)
+    /// - k: The minimum number of shares to recover the key (0 < k <= n)
+    /// - prime: The prime of the field of the shares, None for the default.
+    ///
+    /// # Errors
+    ///
+    /// A ShamirError::InvalidThreshold if the parameters constraints
+    /// are not met.
+    pub fn split<'a>(
+        &self,
+        n: usize,
+        k: usize,
+        prime: Option<&'a Prime>,
+    ) -> Result<ShareIter<'a>, ShamirError> {
+        shamir::split_secret(self.expose(), n, k, prime)
+    }
+
+    /// Splits the key like split, but deriving the shares from a
+    /// seed, so the same seed gives the same shares again, see
+    /// shamir::split_secret_from_seed.
+    ///
+    /// # Parameters
+    ///
+    /// - n: The total number of shares to return (2 < n <= MAX_SHARES)
+    /// - k: The minimum number of shares to recover the key (0 < k <= n)
+    /// - seed: The seed, with at least shamir::MIN_SEED_SIZE random bytes.
+    /// - prime: The prime of the field of the shares, None for the default.
+    ///
+    /// # Errors
+    ///
+    /// A ShamirError::InvalidThreshold if the parameters constraints
+    /// are not met or the seed is too short.
+    pub fn split_from_seed<'a>(
+        &self,
+        n: usize,
+        k: usize,
+        seed: &[u8],
+        prime: Option<&'a Prime>,
+    ) -> Result<ShareIter<'a>, ShamirError> {
+        shamir::split_secret_from_seed(self.expose(), n, k, seed, prime)
+    }
+
+    /// Splits the key into n shares with a secret sharing scheme.
+    ///
+    /// # Parameters
+    ///
+    /// - scheme: The scheme to split the key with.
+    /// - n: The total number of shares to return.
+    /// - k: The minimum number of shares to recover the key.
+    ///
+    /// # Errors
+    ///
+    /// A ShamirError::InvalidThreshold if the scheme does not
+    /// support n and k.
+    pub fn split_with(
+        &self,
+        scheme: &dyn SecretSharingScheme,
+        n: usize,
+        k: usize,
+    ) -> Result<Vec<Share>, ShamirError> {
+        scheme.split(self.expose(), n, k)
+    }
+
+    /// Splits the key according to a policy.
+    ///
+    /// # Parameters
+    ///
+    /// - policy: The holders that can recover the key together.
+    ///
+    /// # Returns
+    ///
+    /// The share of each holder of the policy, together with their name.
+    pub fn split_by(&self, policy: &Policy) -> Vec<(String, Share)> {
+        policy.split(self.expose())
+    }
+
+    /// Splits the key like split, always returning the
+    /// same shares for the same seed.
+    ///
+    /// # Parameters
+    ///
+    /// - n: The total number of shares to return (n > 2)
+    /// - k: The minimum number of shares to recover the key (0 < k <= n)
+    /// - seed: The key of the ChaCha20 generator, see Rng::from_seed.
+    ///
+    /// # Returns
+    ///
+    /// A vector with n Shares, in the order they were generated.
+    ///
+    /// # Errors
+    ///
+    /// A ShamirError::InvalidThreshold if the parameters constraints
+    /// are not met.
+    #[cfg(any(test, feature = "deterministic"))]
+    pub fn split_seeded(
+        &self,
+        n: usize,
+        k: usize,
+        seed: [u8; crate::math::random::SEED_SIZE],
+    ) -> Result<Vec<Share>, ShamirError> {
+        shamir::split_secret_seeded(self.expose(), n, k, seed, None)
+    }
+
+    /// Returns a cipher that uses this key.
+    ///
+    /// # Parameters
+    ///
+    /// - suite: The AEAD algorithm of the cipher.
+    ///
+    /// # Errors
+    ///
+    /// A CipherError if the key does not have the key size of the suite.
+    pub fn into_cipher(self, suite: CipherSuite) -> Result<Cipher, CipherError> {
+        if self.len() != suite.key_size() {
+            return Err(CipherError(format!(
+                "the key has {} bytes, but {} requires {}",
+                self.len(),
+                suite,
+                suite.key_size()
+            )));
+        }
+        Ok(Cipher::with_key(self, suite))
+    }
+
+    /// Returns the length of the key in bytes.
+    pub fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    /// Returns whether the key has no bytes.
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    /// Returns whether the key is kept in locked memory.
+    pub fn is_locked(&self) -> bool {
+        self.bytes.is_locked()
+    }
+
+    // takes ownership of the bytes of a key
+    pub(crate) fn new(bytes: Vec<u8>) -> Self {
+        SecretKey {
+            bytes: SecureBuffer::new(bytes),
+        }
+    }
+
+    // the bytes of the key, only given to the primitives that use it
+    pub(crate) fn expose(&self) -> &[u8] {
+        self.bytes.as_bytes()
+    }
+}
+
+impl ConstantTimeEq for SecretKey {
+    fn ct_eq(&self, other: &SecretKey) -> Choice {
+        // only the length, which is not secret, ends the comparison early
+        self.expose().ct_eq(other.expose())
+    }
+}
+
+impl PartialEq for SecretKey {
+    fn eq(&self, other: &SecretKey) -> bool {
+        self.ct_eq(other).into()
+    }
+}
+
+impl Eq for SecretKey {}
+
+impl fmt::Debug for SecretKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "SecretKey({} bytes)", self.len())
+    }
+}
+
+/////////////////////////////////
+// Unit testing of the module. //
+/////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::scheme::Shamir;
+
+    const GCM: CipherSuite = CipherSuite::Aes256Gcm;
+
+    #[test]
+    fn split_and_recover() {
+        let key = SecretKey::from_password("This is a secure key", b"salt", PASSWORD_LOG_N, GCM);
+        let shares: Vec<Share> = key.split(5, 3, None).unwrap().collect();
+        let recovered = SecretKey::recover(shares[2..].iter().cloned(), None, None).unwrap();
+        assert_eq!(recovered, key);
+        let scheme = Shamir::new(None);
+        let shares = key.split_with(&scheme, 4, 2).unwrap();
+        let recovered = SecretKey::recover_with(&scheme, &shares[2..], Some(32)).unwrap();
+        assert_eq!(recovered, key);
+        let policy = Policy::parse("alice or (bob and carol)").unwrap();
+        let shares = key.split_by(&policy);
+        assert_eq!(
+            SecretKey::recover_by(&policy, &shares[1..], None).unwrap(),
+            key
+        );
+    }
+
+    #[test]
+    fn password_keys() {
+        let key = SecretKey::from_password("password", b"salt", PASSWORD_LOG_N, GCM);
+        assert_eq!(key.len(), 32);
+        assert_eq!(
+            key,
+            SecretKey::from_password("password", b"salt", PASSWORD_LOG_N, GCM)
+        );
+        assert_ne!(
+            key,
+            SecretKey::from_password("password", b"other salt", PASSWORD_LOG_N, GCM)
+        );
+        assert_ne!(
+            key,
+            SecretKey::from_password("password", b"salt", PASSWORD_LOG_N + 1, GCM)
+        );
+        assert_ne!(key, SecretKey::from_keyfile(b"password", GCM));
+    }
+
+    #[test]
+    fn constant_time_eq() {
+        let key = SecretKey::new(vec![1, 2, 3]);
+        assert!(bool::from(key.ct_eq(&SecretKey::new(vec![1, 2, 3]))));
+        assert!(!bool::from(key.ct_eq(&SecretKey::new(vec![1, 2, 4]))));
+        assert!(!bool::from(key.ct_eq(&SecretKey::new(vec![1, 2]))));
+        assert_ne!(key, SecretKey::new(vec![1, 2, 3, 0]));
+    }
+
+    #[test]
+    fn key_into_cipher() {
+        let key = SecretKey::from_keyfile(b"This is a secure key", CipherSuite::Aes128Gcm);
+        assert_eq!(key.len(), 16);
+        assert_eq!(format!("{:?}", key), "SecretKey(16 bytes)");
+        let error = key.into_cipher(GCM).err().unwrap();
+        assert_eq!(error.0, "the key has 16 bytes, but aes-gcm requires 32");
+        let cipher = SecretKey::random(GCM).unwrap().into_cipher(GCM).unwrap();
+        assert_eq!(cipher.key().len(), 32);
+    }
+}
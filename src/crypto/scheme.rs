@@ -0,0 +1,622 @@
+use std::collections::HashSet;
+
+use sha2::{Digest, Sha256};
+use subtle::ConstantTimeEq;
+
+use crate::crypto::crt;
+use crate::crypto::error::ShamirError;
+use crate::crypto::shamir::{self, Share, MAX_SHARES};
+use crate::encoding::{from_hex, to_hex};
+use crate::math::Prime;
+
+/// Name of the scheme of the shares that do not record one.
+pub const DEFAULT_SCHEME: &str = "shamir";
+
+/// Name of the n-of-n scheme that xors the shares, see Xor.
+pub const XOR_SCHEME: &str = "xor";
+
+/// Name of the n-of-n scheme that adds the shares, see Additive.
+pub const ADDITIVE_SCHEME: &str = "additive";
+
+/// Name of the threshold scheme based on the chinese remainder
+/// theorem, see AsmuthBloom.
+pub const CRT_SCHEME: &str = "crt";
+
+/// Name of the ramp scheme, recorded followed by the number of
+/// pieces of the secret in each polynomial, see Ramp.
+pub const RAMP_SCHEME: &str = "ramp";
+
+/// Suffix of the name of a scheme whose secrets are padded to
+/// detect cheaters, see Checked.
+pub const CHECKED_SUFFIX: &str = "-checked";
+
+// Size in bytes of the random prefix and of the tag of the
+// secrets padded to detect cheaters
+const CHECK_SIZE: usize = 16;
+
+/// Names of the schemes that can be chosen to split a secret.
+pub const SCHEMES: [&str; 5] = [
+    DEFAULT_SCHEME,
+    XOR_SCHEME,
+    ADDITIVE_SCHEME,
+    CRT_SCHEME,
+    RAMP_SCHEME,
+];
+
+/// A way of splitting a secret into shares, of which a
+/// number of them is enough to recover the secret.
+///
+/// The name of the scheme is recorded with the shares, so
+/// the scheme is chosen again when they are recovered.
+pub trait SecretSharingScheme {
+    /// Returns the name recorded with the shares of this scheme.
+    fn name(&self) -> String;
+
+    /// Splits a secret into n shares, k of which recover it.
+    ///
+    /// # Parameters
+    ///
+    /// - secret: The secret to share.
+    /// - n: The total number of shares to return.
+    /// - k: The minimum number of shares to recover the secret.
+    ///
+    /// # Returns
+    ///
+    /// A vector with n shares, in the order of their indices.
+    ///
+    /// # Errors
+    ///
+    /// A ShamirError::InvalidThreshold if the scheme does not
+    /// support n and k.
+    fn split(&self, secret: &[u8], n: usize, k: usize) -> Result<Vec<Share>, ShamirError>;
+
+    /// Recovers a secret from its shares.
+    ///
+    /// # Parameters
+    ///
+    /// - shares: The shares, each with a unique first element.
+    /// - length: The length in bytes of the secret, or None.
+    ///
+    /// # Errors
+    ///
+    /// A ShamirError if the shares are malformed or are not
+    /// enough to recover the secret.
+    fn recover(&self, shares: &[Share], length: Option<usize>) -> Result<Vec<u8>, ShamirError>;
+
+    /// Checks that shares are shares of the same secret.
+    ///
+    /// # Parameters
+    ///
+    /// - shares: The shares, each with a unique first element.
+    /// - k: The minimum number of shares to recover the secret.
+    ///
+    /// # Returns
+    ///
+    /// Whether the shares are consistent with each other.
+    ///
+    /// # Errors
+    ///
+    /// A ShamirError if the shares are malformed.
+    fn verify(&self, shares: &[Share], k: usize) -> Result<bool, ShamirError>;
+
+    /// Returns whether every share is needed to recover the
+    /// secret, so k must be equal to n.
+    fn needs_all_shares(&self) -> bool {
+        false
+    }
+
+    /// Returns whether the shares are numbers of a prime field
+    /// that can be chosen.
+    fn uses_field(&self) -> bool {
+        false
+    }
+}
+
+/// Shamir secret sharing over a prime field, see the shamir module.
+pub struct Shamir<'a> {
+    prime: Option<&'a Prime>,
+}
+
+impl<'a> Shamir<'a> {
+    /// Creates the scheme over the field of a prime.
+    ///
+    /// # Parameters
+    ///
+    /// - prime: The prime of the field, None for the default.
+    pub fn new(prime: Option<&'a Prime>) -> Self {
+        Shamir { prime }
+    }
+}
+
+impl SecretSharingScheme for Shamir<'_> {
+    fn name(&self) -> String {
+        DEFAULT_SCHEME.into()
+    }
+
+    fn split(&self, secret: &[u8], n: usize, k: usize) -> Result<Vec<Share>, ShamirError> {
+        Ok(shamir::split_secret(secret, n, k, self.prime)?.collect())
+    }
+
+    fn recover(&self, shares: &[Share], length: Option<usize>) -> Result<Vec<u8>, ShamirError> {
+        let shares = shares.iter().cloned();
+        match length {
+            Some(length) => shamir::recover_secret_with_length(shares, self.prime, length),
+            None => shamir::recover_secret(shares, self.prime),
+        }
+    }
+
+    fn verify(&self, shares: &[Share], k: usize) -> Result<bool, ShamirError> {
+        shamir::verify_shares(shares, k, self.prime)
+    }
+
+    fn uses_field(&self) -> bool {
+        true
+    }
+}
+
+/// A (k, L, n) ramp scheme over a prime field, whose shares are
+/// about 1/L the size of the secret, but where more than k - L
+/// shares give some information about it, see shamir::split_ramp.
+pub struct Ramp<'a> {
+    prime: Option<&'a Prime>,
+    pieces: usize,
+}
+
+impl<'a> Ramp<'a> {
+    /// Creates the scheme over the field of a prime.
+    ///
+    /// # Parameters
+    ///
+    /// - prime: The prime of the field, None for the default.
+    /// - pieces: The number L of pieces of the secret in each polynomial.
+    pub fn new(prime: Option<&'a Prime>, pieces: usize) -> Self {
+        Ramp { prime, pieces }
+    }
+}
+
+impl SecretSharingScheme for Ramp<'_> {
+    fn name(&self) -> String {
+        format!("{}{}", RAMP_SCHEME, self.pieces)
+    }
+
+    fn split(&self, secret: &[u8], n: usize, k: usize) -> Result<Vec<Share>, ShamirError> {
+        shamir::split_ramp(secret, n, k, self.pieces, self.prime)
+    }
+
+    fn recover(&self, shares: &[Share], length: Option<usize>) -> Result<Vec<u8>, ShamirError> {
+        let secret = shamir::recover_ramp(shares, self.pieces, self.prime)?;
+        match length {
+            Some(length) => shamir::restore_length(secret, length),
+            None => Ok(secret),
+        }
+    }
+
+    // the number of numbers of the secret is the same in every
+    // share, so it is checked like the values of a polynomial
+    fn verify(&self, shares: &[Share], k: usize) -> Result<bool, ShamirError> {
+        shamir::verify_shares(shares, k, self.prime)
+    }
+
+    fn uses_field(&self) -> bool {
+        true
+    }
+}
+
+/// An n-of-n scheme where the shares are numbers of a prime field
+/// whose sum is the secret, see shamir::split_additive.
+pub struct Additive<'a> {
+    prime: Option<&'a Prime>,
+}
+
+impl<'a> Additive<'a> {
+    /// Creates the scheme over the field of a prime.
+    ///
+    /// # Parameters
+    ///
+    /// - prime: The prime of the field, None for the default.
+    pub fn new(prime: Option<&'a Prime>) -> Self {
+        Additive { prime }
+    }
+}
+
+impl SecretSharingScheme for Additive<'_> {
+    fn name(&self) -> String {
+        ADDITIVE_SCHEME.into()
+    }
+
+    fn split(&self, secret: &[u8], n: usize, k: usize) -> Result<Vec<Share>, ShamirError> {
+        if k != n {
+            return Err(ShamirError::InvalidThreshold(
+                "the additive scheme needs all the shares, K must be equal to N".into(),
+            ));
+        }
+        shamir::split_additive(secret, n, self.prime)
+    }
+
+    fn recover(&self, shares: &[Share], length: Option<usize>) -> Result<Vec<u8>, ShamirError> {
+        let secret = shamir::recover_additive(shares.iter().cloned(), self.prime)?;
+        match length {
+            Some(length) => shamir::restore_length(secret, length),
+            None => Ok(secret),
+        }
+    }
+
+    // any shares add up to some secret, so only
+    // their format can be checked
+    fn verify(&self, shares: &[Share], _k: usize) -> Result<bool, ShamirError> {
+        shamir::recover_additive(shares.iter().cloned(), self.prime).map(|_| true)
+    }
+
+    fn needs_all_shares(&self) -> bool {
+        true
+    }
+
+    fn uses_field(&self) -> bool {
+        true
+    }
+}
+
+/// An n-of-n scheme where every share but one is random and the
+/// last one is the secret xor the others, so all the shares are
+/// needed to recover the secret.
+///
+/// The first element of each share is its index in base 36 and the
+/// second one has the length of the secret in hexadecimal, so the
+/// shares are as long as the secret, leading zeros included.
+pub struct Xor;
+
+impl SecretSharingScheme for Xor {
+    fn name(&self) -> String {
+        XOR_SCHEME.into()
+    }
+
+    fn split(&self, secret: &[u8], n: usize, k: usize) -> Result<Vec<Share>, ShamirError> {
+        if !(2..=MAX_SHARES).contains(&n) {
+            return Err(ShamirError::InvalidThreshold(format!(
+                "N must be in the range 2 <= N <= {}",
+                MAX_SHARES
+            )));
+        }
+        if k != n {
+            return Err(ShamirError::InvalidThreshold(
+                "the xor scheme needs all the shares, K must be equal to N".into(),
+            ));
+        }
+        let mut last = secret.to_vec();
+        let mut shares = Vec::with_capacity(n);
+        for i in 1..n {
+            let mut pad = vec![0u8; secret.len()];
+            getrandom::getrandom(&mut pad).map_err(|_| ShamirError::RandomError)?;
+            xor_into(&mut last, &pad);
+            shares.push(Share(to_radix_36(i), to_hex(&pad)));
+        }
+        shares.push(Share(to_radix_36(n), to_hex(&last)));
+        Ok(shares)
+    }
+
+    fn recover(&self, shares: &[Share], length: Option<usize>) -> Result<Vec<u8>, ShamirError> {
+        let pads = xor_pads(shares)?;
+        let mut secret = vec![0u8; pads[0].len()];
+        for pad in &pads {
+            xor_into(&mut secret, pad);
+        }
+        match length {
+            Some(length) if length != secret.len() => Err(ShamirError::FieldMismatch(format!(
+                "the shares recover {} bytes instead of {}",
+                secret.len(),
+                length
+            ))),
+            _ => Ok(secret),
+        }
+    }
+
+    // any set of pads is the split of some secret, so only
+    // their format can be checked
+    fn verify(&self, shares: &[Share], _k: usize) -> Result<bool, ShamirError> {
+        xor_pads(shares).map(|_| true)
+    }
+
+    fn needs_all_shares(&self) -> bool {
+        true
+    }
+}
+
+/// The Asmuth-Bloom threshold scheme, where each share is the
+/// residue of a number modulo a prime of its own and any k of
+/// them recover the number with the chinese remainder theorem,
+/// see the crt module.
+pub struct AsmuthBloom;
+
+impl SecretSharingScheme for AsmuthBloom {
+    fn name(&self) -> String {
+        CRT_SCHEME.into()
+    }
+
+    fn split(&self, secret: &[u8], n: usize, k: usize) -> Result<Vec<Share>, ShamirError> {
+        crt::split_secret(secret, n, k)
+    }
+
+    fn recover(&self, shares: &[Share], length: Option<usize>) -> Result<Vec<u8>, ShamirError> {
+        let secret = crt::recover_secret(shares)?;
+        match length {
+            Some(length) => shamir::restore_length(secret, length),
+            None => Ok(secret),
+        }
+    }
+
+    fn verify(&self, shares: &[Share], k: usize) -> Result<bool, ShamirError> {
+        crt::verify_shares(shares, k)
+    }
+}
+
+// parses the pads of xor shares, which must have distinct indices
+// and the same length
+fn xor_pads(shares: &[Share]) -> Result<Vec<Vec<u8>>, ShamirError> {
+    if shares.is_empty() {
+        return Err(ShamirError::InvalidThreshold("no shares were given".into()));
+    }
+    let mut indices = HashSet::with_capacity(shares.len());
+    let mut pads = Vec::with_capacity(shares.len());
+    for Share(x, y) in shares {
+        if !indices.insert(x.to_lowercase()) {
+            return Err(ShamirError::DuplicateShare(x.clone()));
+        }
+        let pad = from_hex(y).ok_or_else(|| ShamirError::ParseError(y.clone()))?;
+        pads.push(pad);
+    }
+    if pads.iter().any(|pad| pad.len() != pads[0].len()) {
+        return Err(ShamirError::FieldMismatch(
+            "the shares do not have the same length".into(),
+        ));
+    }
+    Ok(pads)
+}
+
+// xors the bytes of a pad into a buffer of the same length
+fn xor_into(buffer: &mut [u8], pad: &[u8]) {
+    for (b, p) in buffer.iter_mut().zip(pad) {
+        *b ^= p;
+    }
+}
+
+// writes the index of a share in base 36, like the shamir shares
+fn to_radix_36(mut n: usize) -> String {
+    let mut digits = Vec::new();
+    loop {
+        digits.push(std::char::from_digit((n % 36) as u32, 36).unwrap());
+        n /= 36;
+        if n == 0 {
+            break;
+        }
+    }
+    digits.iter().rev().collect()
+}
+
+/// A scheme whose secrets are padded with a random prefix and a tag,
+/// the first bytes of the SHA-256 of the prefix and the secret, in
+/// the style of Tompa and Woll, so a secret that a cheater skewed
+/// with an altered share is detected with high probability.
+///
+/// The length recorded with the shares is needed to tell a skewed
+/// secret from one whose leading zero bytes were not kept.
+pub struct Checked<'a> {
+    inner: Box<dyn SecretSharingScheme + 'a>,
+}
+
+impl<'a> Checked<'a> {
+    /// Creates a scheme that pads the secrets of another one.
+    ///
+    /// # Parameters
+    ///
+    /// - inner: The scheme the padded secrets are split with.
+    pub fn new(inner: Box<dyn SecretSharingScheme + 'a>) -> Self {
+        Checked { inner }
+    }
+}
+
+impl SecretSharingScheme for Checked<'_> {
+    fn name(&self) -> String {
+        format!("{}{}", self.inner.name(), CHECKED_SUFFIX)
+    }
+
+    fn split(&self, secret: &[u8], n: usize, k: usize) -> Result<Vec<Share>, ShamirError> {
+        let mut padded = vec![0u8; CHECK_SIZE];
+        getrandom::getrandom(&mut padded).map_err(|_| ShamirError::RandomError)?;
+        padded.extend(secret);
+        let tag = Sha256::digest(&padded);
+        padded.extend(&tag[..CHECK_SIZE]);
+        self.inner.split(&padded, n, k)
+    }
+
+    fn recover(&self, shares: &[Share], length: Option<usize>) -> Result<Vec<u8>, ShamirError> {
+        let length = length.map(|length| length + 2 * CHECK_SIZE);
+        let padded = self.inner.recover(shares, length)?;
+        if padded.len() < 2 * CHECK_SIZE {
+            return Err(ShamirError::CheatingDetected);
+        }
+        let (message, tag) = padded.split_at(padded.len() - CHECK_SIZE);
+        // compares every byte, so the time does not depend on the tag
+        if !bool::from(Sha256::digest(message)[..CHECK_SIZE].ct_eq(tag)) {
+            return Err(ShamirError::CheatingDetected);
+        }
+        Ok(message[CHECK_SIZE..].to_vec())
+    }
+
+    fn verify(&self, shares: &[Share], k: usize) -> Result<bool, ShamirError> {
+        self.inner.verify(shares, k)
+    }
+
+    fn needs_all_shares(&self) -> bool {
+        self.inner.needs_all_shares()
+    }
+
+    fn uses_field(&self) -> bool {
+        self.inner.uses_field()
+    }
+}
+
+/// Returns the scheme with the given name.
+///
+/// The name of a ramp scheme is followed by its number of pieces,
+/// like ramp2, and the name of a scheme whose secrets are padded to
+/// detect cheaters by CHECKED_SUFFIX, like shamir-checked.
+///
+/// # Parameters
+///
+/// - name: The name recorded with the shares, see SecretSharingScheme::name.
+/// - prime: The prime of the field of the shares, None for the default.
+///
+/// # Errors
+///
+/// A ShamirError::UnknownScheme if there is no scheme with the name.
+pub fn from_name<'a>(
+    name: &str,
+    prime: Option<&'a Prime>,
+) -> Result<Box<dyn SecretSharingScheme + 'a>, ShamirError> {
+    if let Some(inner) = name.strip_suffix(CHECKED_SUFFIX) {
+        return Ok(Box::new(Checked::new(from_name(inner, prime)?)));
+    }
+    match name {
+        DEFAULT_SCHEME => Ok(Box::new(Shamir::new(prime))),
+        XOR_SCHEME => Ok(Box::new(Xor)),
+        ADDITIVE_SCHEME => Ok(Box::new(Additive::new(prime))),
+        CRT_SCHEME => Ok(Box::new(AsmuthBloom)),
+        _ => match name.strip_prefix(RAMP_SCHEME).map(str::parse) {
+            Some(Ok(pieces)) if pieces > 0 => Ok(Box::new(Ramp::new(prime, pieces))),
+            _ => Err(ShamirError::UnknownScheme(name.to_string())),
+        },
+    }
+}
+
+/////////////////////////////////
+// Unit testing of the module. //
+/////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shamir_scheme() {
+        let scheme = from_name("shamir", None).unwrap();
+        assert_eq!(scheme.name(), DEFAULT_SCHEME);
+        let secret = vec![0u8, 0x42, 0x17];
+        let mut shares = scheme.split(&secret, 5, 3).unwrap();
+        assert_eq!(scheme.recover(&shares[1..4], Some(3)).unwrap(), secret);
+        assert!(scheme.verify(&shares, 3).unwrap());
+        shares[0].1 = shares[1].1.clone();
+        assert!(!scheme.verify(&shares, 3).unwrap());
+    }
+
+    #[test]
+    fn xor_scheme() {
+        let scheme = from_name("xor", None).unwrap();
+        let secret = vec![0u8, 0x42, 0x17, 0xff];
+        let shares = scheme.split(&secret, 4, 4).unwrap();
+        assert_eq!(shares.len(), 4);
+        assert_eq!(shares[3].0, "4");
+        assert!(shares.iter().all(|Share(_, y)| y.len() == 2 * secret.len()));
+        let mut reordered = shares.clone();
+        reordered.reverse();
+        assert_eq!(scheme.recover(&reordered, Some(4)).unwrap(), secret);
+        assert_ne!(scheme.recover(&shares[1..], None).unwrap(), secret);
+        assert!(scheme.recover(&shares, Some(5)).is_err());
+        assert!(scheme.verify(&shares, 4).unwrap());
+    }
+
+    #[test]
+    fn xor_errors() {
+        let scheme = Xor;
+        assert!(matches!(
+            scheme.split(b"secret", 3, 2),
+            Err(ShamirError::InvalidThreshold(_))
+        ));
+        assert!(scheme.split(b"secret", 1, 1).is_err());
+        let shares = scheme.split(b"secret", 2, 2).unwrap();
+        let twice = vec![shares[0].clone(), shares[0].clone()];
+        assert_eq!(
+            scheme.recover(&twice, None),
+            Err(ShamirError::DuplicateShare("1".into()))
+        );
+        let odd = vec![shares[0].clone(), Share("2".into(), "abc".into())];
+        assert_eq!(
+            scheme.recover(&odd, None),
+            Err(ShamirError::ParseError("abc".into()))
+        );
+        let short = vec![shares[0].clone(), Share("2".into(), "ab".into())];
+        assert!(matches!(
+            scheme.recover(&short, None),
+            Err(ShamirError::FieldMismatch(_))
+        ));
+        assert!(scheme.recover(&[], None).is_err());
+    }
+
+    #[test]
+    fn additive_scheme() {
+        let prime = shamir::parse_field("127").unwrap();
+        let scheme = from_name("additive", Some(&prime)).unwrap();
+        assert_eq!(scheme.name(), ADDITIVE_SCHEME);
+        let secret = vec![0u8, 0x42, 0x17];
+        let shares = scheme.split(&secret, 3, 3).unwrap();
+        assert_eq!(scheme.recover(&shares, Some(3)).unwrap(), secret);
+        assert_ne!(scheme.recover(&shares[1..], Some(3)).ok(), Some(secret));
+        assert!(scheme.verify(&shares, 3).unwrap());
+        assert!(scheme.split(b"secret", 3, 2).is_err());
+    }
+
+    #[test]
+    fn crt_scheme() {
+        let scheme = from_name("crt", None).unwrap();
+        assert_eq!(scheme.name(), CRT_SCHEME);
+        assert!(!scheme.needs_all_shares() && !scheme.uses_field());
+        let secret = vec![0u8, 0x42, 0x17];
+        let mut shares = scheme.split(&secret, 5, 3).unwrap();
+        assert_eq!(scheme.recover(&shares[1..4], Some(3)).unwrap(), secret);
+        assert!(scheme.verify(&shares, 3).unwrap());
+        shares[0].1 = shares[1].1.clone();
+        assert!(!scheme.verify(&shares, 3).unwrap());
+    }
+
+    #[test]
+    fn ramp_scheme() {
+        let scheme = from_name("ramp2", None).unwrap();
+        assert_eq!(scheme.name(), "ramp2");
+        let secret = vec![0u8; 32];
+        let mut shares = scheme.split(&secret, 5, 3).unwrap();
+        assert_eq!(scheme.recover(&shares[2..], Some(32)).unwrap(), secret);
+        assert!(scheme.verify(&shares, 3).unwrap());
+        shares[0].1 = shares[1].1.clone();
+        assert!(!scheme.verify(&shares, 3).unwrap());
+        assert!(from_name("ramp", None).is_err());
+        assert!(from_name("ramp0", None).is_err());
+    }
+
+    #[test]
+    fn checked_scheme() {
+        let scheme = from_name("shamir-checked", None).unwrap();
+        assert_eq!(scheme.name(), "shamir-checked");
+        let secret = vec![0u8, 0x42, 0x17];
+        let mut shares = scheme.split(&secret, 5, 3).unwrap();
+        assert_eq!(scheme.recover(&shares[2..], Some(3)).unwrap(), secret);
+        // an altered digit skews the secret recovered
+        let last = if shares[0].1.ends_with('0') { '1' } else { '0' };
+        shares[0].1.pop();
+        shares[0].1.push(last);
+        assert_eq!(
+            scheme.recover(&shares[..3], Some(3)),
+            Err(ShamirError::CheatingDetected)
+        );
+        let xor = from_name("xor-checked", None).unwrap();
+        assert!(xor.needs_all_shares());
+        let shares = xor.split(&secret, 3, 3).unwrap();
+        assert_eq!(xor.recover(&shares, Some(3)).unwrap(), secret);
+        assert!(from_name("blakley-checked", None).is_err());
+    }
+
+    #[test]
+    fn unknown_scheme() {
+        let error = from_name("blakley", None).err().unwrap();
+        assert_eq!(error, ShamirError::UnknownScheme("blakley".into()));
+        assert_eq!(error.to_string(), "unknown secret sharing scheme 'blakley'");
+    }
+}
@@ -1,110 +1,1494 @@
-use std::collections::{hash_set, HashSet};
-use std::error::Error;
+use std::collections::HashSet;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::OnceLock;
+#[cfg(feature = "parallel")]
+use std::thread;
+use std::vec;
 
-use crate::math::{error::ParseIntegerError, random::Rng};
-use crate::math::{Evaluation, ModInteger, Polynomial, Prime};
+use rug::Integer;
+use subtle::{Choice, ConstantTimeEq};
+use zeroize::Zeroize;
 
-const PRIME_257: &str =
-    "208351617316091241234326746312124448251235562226470491514186331217050270460481";
+#[cfg(feature = "parallel")]
+use crate::concurrency::Concurrency;
+use crate::crypto::error::ShamirError;
+use crate::math::{error::ValueError, random::Rng};
+use crate::math::{prime, Evaluation, ModInteger, Polynomial, Prime};
 
 // Radix used when transforming shares to evaluations
 const RADIX: i32 = 36;
 
-/// A share of the secret.
-pub type Share = (String, String);
+// Radix of the second element of the shares of a scalar
+const SCALAR_RADIX: i32 = 16;
 
-/// A consuming Iterator over shares.
-pub type ShareIter = hash_set::IntoIter<Share>;
+// Separator of the values of the blocks of a secret in a share
+const BLOCK_SEPARATOR: &str = ".";
+
+/// Minimum number of shares that ShareIter::evaluate_all evaluates
+/// in several threads, with the parallel feature.
+pub const PARALLEL_SHARES: usize = 64;
+
+/// Minimum number of bytes of the seed of split_secret_from_seed,
+/// 128 bits of entropy.
+pub const MIN_SEED_SIZE: usize = 16;
+
+// Contexts of the keys derived from the seed of
+// split_secret_from_seed, one for the indices of the shares and
+// one for the coefficients of the polynomials
+const INDICES_CONTEXT: &str = "shared_secrets 2024-06-01 indices of shares from a seed";
+const COEFFICIENTS_CONTEXT: &str = "shared_secrets 2024-06-01 coefficients of shares from a seed";
+
+/// Minimum number of bits of the prime of a field, so the
+/// indices of MAX_SHARES shares are distinct values of it.
+pub const MIN_FIELD_BITS: u32 = 17;
+
+/// Size in bytes of a scalar of the curves supported by split_scalar.
+pub const SCALAR_SIZE: usize = 32;
+
+/// Returns the prime of the field the secrets are shared in by
+/// default, parsed on the first call and shared by every later one.
+pub fn default_prime() -> &'static Prime {
+    static PRIME: OnceLock<Prime> = OnceLock::new();
+    PRIME.get_or_init(|| prime::registry()[0].prime())
+}
+
+/// Parses the field a secret is split in.
+///
+/// # Parameters
+///
+/// - field: The identifier of a prime of the registry, see
+///   prime::registry, for example `257` for the default prime, or
+///   `custom:<prime>` with a prime in decimal.
+///
+/// # Returns
+///
+/// The prime of the field.
+///
+/// # Errors
+///
+/// A ValueError if the field is unknown or if the custom prime is
+/// not valid, see field_prime.
+pub fn parse_field(field: &str) -> Result<Prime, ValueError> {
+    match field.strip_prefix("custom:") {
+        Some(prime) => field_prime(prime),
+        None => match prime::named(field) {
+            Some(named) => Ok(named.prime()),
+            None => Err(ValueError(format!("unknown field '{}'", field))),
+        },
+    }
+}
+
+/// Parses the prime of a field, as written in decimal by Display
+/// or by the identifier of a prime of the registry.
+///
+/// # Parameters
+///
+/// - prime: The prime in decimal, or its identifier.
+///
+/// # Returns
+///
+/// The prime.
+///
+/// # Errors
+///
+/// A ValueError if the number cannot be parsed, if it is not a
+/// prime or if it has less than MIN_FIELD_BITS bits.
+pub fn field_prime(prime: &str) -> Result<Prime, ValueError> {
+    if let Some(named) = prime::named(prime) {
+        return Ok(named.prime());
+    }
+    let parsed =
+        Prime::parse(prime).map_err(|_| ValueError(format!("'{}' is not a number", prime)))?;
+    if !parsed.is_probably_prime() {
+        return Err(ValueError(format!("{} is not a prime", prime)));
+    }
+    if parsed.bits() < MIN_FIELD_BITS {
+        return Err(ValueError(format!(
+            "the prime must have at least {} bits",
+            MIN_FIELD_BITS
+        )));
+    }
+    Ok(parsed)
+}
+
+/// An elliptic curve whose private keys are scalars modulo the
+/// order of its group, a prime field that keys can be split in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Curve {
+    Ed25519,
+    Secp256k1,
+}
+
+impl Curve {
+    /// Returns the order of the group of the curve, in decimal.
+    pub fn order(self) -> &'static str {
+        match self {
+            Curve::Ed25519 => prime::named("ed25519-order"),
+            Curve::Secp256k1 => prime::named("secp256k1-order"),
+        }
+        .unwrap()
+        .decimal
+    }
+}
+
+impl FromStr for Curve {
+    type Err = ValueError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ed25519" => Ok(Curve::Ed25519),
+            "secp256k1" => Ok(Curve::Secp256k1),
+            _ => Err(ValueError(format!("unknown curve '{}'", s))),
+        }
+    }
+}
+
+impl fmt::Display for Curve {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Curve::Ed25519 => write!(f, "ed25519"),
+            Curve::Secp256k1 => write!(f, "secp256k1"),
+        }
+    }
+}
+
+/// A share of the secret: its index and its value, in base 36.
+///
+/// Shares are compared in constant time, see ConstantTimeEq, so the
+/// time it takes does not tell how much of a share was guessed right.
+#[derive(Debug, Clone)]
+pub struct Share(pub String, pub String);
+
+impl ConstantTimeEq for Share {
+    /// Only the lengths of the indices and the values can end the
+    /// comparison early.
+    fn ct_eq(&self, other: &Share) -> Choice {
+        self.0.as_bytes().ct_eq(other.0.as_bytes()) & self.1.as_bytes().ct_eq(other.1.as_bytes())
+    }
+}
+
+impl PartialEq for Share {
+    fn eq(&self, other: &Share) -> bool {
+        self.ct_eq(other).into()
+    }
+}
+
+impl Eq for Share {}
+
+/// Maximum number of shares of a secret, so the index of every
+/// share fits in 16 bits.
+pub const MAX_SHARES: usize = u16::MAX as usize;
+
+/// Size in bytes of the index of a share in its compact form.
+pub const INDEX_SIZE: usize = 2;
+
+/// Writes a share in its compact binary form: the index, the
+/// first element, as a big-endian u16, followed by the value of
+/// each block as a big-endian number of the byte_len of the prime,
+/// see ModInteger::to_bytes_be.
+///
+/// A share of the default field takes 35 bytes, against the 50 or
+/// so characters of its text form, which makes it practical for
+/// binary and mnemonic encodings.
+///
+/// # Parameters
+///
+/// - share: A share of split_secret.
+/// - prime: The prime of the field the secret was split in,
+///   default_prime if None.
+///
+/// # Returns
+///
+/// The bytes of the share.
+///
+/// # Errors
+///
+/// A ParseError if the index or a value is not a number, or a
+/// FieldMismatch if the index is not in 1..=MAX_SHARES or a value
+/// is not a number of the field.
+pub fn share_to_bytes(share: &Share, prime: Option<&Prime>) -> Result<Vec<u8>, ShamirError> {
+    let prime = field(prime);
+    let Share(x, y) = share;
+    let index = parse_number(x, prime, RADIX)?
+        .to_string()
+        .parse::<u16>()
+        .ok()
+        .filter(|&index| index > 0)
+        .ok_or_else(|| ShamirError::FieldMismatch(format!("{} is not the index of a share", x)))?;
+    let mut bytes = index.to_be_bytes().to_vec();
+    for value in y.split(BLOCK_SEPARATOR) {
+        let mut number = parse_number(value, prime, RADIX)?;
+        bytes.extend(number.to_bytes_be(prime.byte_len()).unwrap());
+        number.zeroize();
+    }
+    Ok(bytes)
+}
+
+/// Reads a share written by share_to_bytes.
+///
+/// # Parameters
+///
+/// - bytes: The compact form of the share.
+/// - prime: The prime of the field the secret was split in,
+///   default_prime if None.
+///
+/// # Returns
+///
+/// The share, as split_secret returns it.
+///
+/// # Errors
+///
+/// A ParseError if the length of the bytes does not match the
+/// field, or a FieldMismatch if the index is zero or a value is not
+/// a number of the field.
+pub fn share_from_bytes(bytes: &[u8], prime: Option<&Prime>) -> Result<Share, ShamirError> {
+    let prime = field(prime);
+    let width = prime.byte_len();
+    let invalid = || ShamirError::ParseError(format!("{} bytes", bytes.len()));
+    if bytes.len() <= INDEX_SIZE || (bytes.len() - INDEX_SIZE) % width != 0 {
+        return Err(invalid());
+    }
+    let index = u16::from_be_bytes([bytes[0], bytes[1]]);
+    if index == 0 {
+        return Err(ShamirError::FieldMismatch(
+            "0 is not the index of a share".into(),
+        ));
+    }
+    let x = ModInteger::zero(prime) + index as u64;
+    let values = bytes[INDEX_SIZE..]
+        .chunks(width)
+        .map(|block| {
+            let mut number = ModInteger::from_bytes_be(block, prime)
+                .map_err(|e| ShamirError::FieldMismatch(e.to_string()))?;
+            let value = number.to_string_radix(RADIX);
+            number.zeroize();
+            Ok(value)
+        })
+        .collect::<Result<Vec<_>, ShamirError>>()?;
+    Ok(Share(
+        x.to_string_radix(RADIX),
+        values.join(BLOCK_SEPARATOR),
+    ))
+}
+
+/// A consuming Iterator over shares, which evaluates the
+/// polynomials at the index of each share only when it is reached.
+pub struct ShareIter<'a> {
+    // one polynomial for each block of the secret
+    polynomials: Vec<Polynomial<'a>>,
+    prime: &'a Prime,
+    // the indices of the shares not reached yet
+    indices: vec::IntoIter<usize>,
+}
+
+impl<'a> Iterator for ShareIter<'a> {
+    type Item = Share;
+
+    fn next(&mut self) -> Option<Share> {
+        let i = self.indices.next()?;
+        Some(self.share(i))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.indices.size_hint()
+    }
+}
+
+impl<'a> ExactSizeIterator for ShareIter<'a> {}
+
+impl<'a> ShareIter<'a> {
+    /// Evaluates every remaining share. With the parallel feature,
+    /// PARALLEL_SHARES or more shares are split among the threads
+    /// of the global Concurrency.
+    ///
+    /// # Returns
+    ///
+    /// The shares, in the order the iterator returns them.
+    #[cfg(feature = "parallel")]
+    pub fn evaluate_all(self) -> Vec<Share> {
+        let indices = self.indices.as_slice();
+        let threads = Concurrency::global().threads();
+        if indices.len() < PARALLEL_SHARES || threads == 1 {
+            return self.collect();
+        }
+        let chunk = indices.len().div_ceil(threads);
+        let iter = &self;
+        thread::scope(|scope| {
+            let parts: Vec<_> = indices
+                .chunks(chunk)
+                .map(|part| {
+                    scope.spawn(move || part.iter().map(|&i| iter.share(i)).collect::<Vec<_>>())
+                })
+                .collect();
+            parts
+                .into_iter()
+                .flat_map(|part| part.join().unwrap())
+                .collect()
+        })
+    }
+
+    /// Evaluates every remaining share. With the parallel feature,
+    /// PARALLEL_SHARES or more shares are split among the threads
+    /// of the global Concurrency.
+    ///
+    /// # Returns
+    ///
+    /// The shares, in the order the iterator returns them.
+    #[cfg(not(feature = "parallel"))]
+    pub fn evaluate_all(self) -> Vec<Share> {
+        self.collect()
+    }
+
+    // the share of the index i
+    fn share(&self, i: usize) -> Share {
+        let x = ModInteger::zero(self.prime) + i as u64;
+        let mut values: Vec<String> = self
+            .polynomials
+            .iter()
+            .map(|polynomial| {
+                let mut y = polynomial.eval(x.clone()).1;
+                let value = y.to_string_radix(RADIX);
+                y.zeroize();
+                value
+            })
+            .collect();
+        let share = Share(x.to_string_radix(RADIX), values.join(BLOCK_SEPARATOR));
+        values.zeroize();
+        share
+    }
+}
 
 /// Splits a secret using shamir secret sharing scheme.
 ///
+/// The polynomial is evaluated at 1, 2, ..., n, so the first
+/// element of each share is its index, in the radix of the shares,
+/// and no two shares are evaluated at the same point. A secret
+/// too long for the field is split in blocks shared separately,
+/// whose values are joined with dots in the second element.
+///
+/// Each block is shared as the number of its bytes in big-endian
+/// order, see ModInteger::from_digits, and the elements of the
+/// shares are written in base 36, so shares split on one platform
+/// recover the same secret on any other.
+///
+/// # Parameters
+///
+/// - secret: The secret to share.
+/// - n: The total number of shares to return (2 < n <= MAX_SHARES).
+/// - k: The minimum number of shares to decipher the secret (0 < k <= n).
+/// - prime: The prime of the field, default_prime if None.
+///
+/// # Returns
+///
+/// A ShareIter with n Shares, in the order of their indices.
+///
+/// # Errors
+///
+/// A ShamirError::InvalidThreshold if the parameter constraints
+/// are not met.
+pub fn split_secret<'a>(
+    secret: &[u8],
+    n: usize,
+    k: usize,
+    prime: Option<&'a Prime>,
+) -> Result<ShareIter<'a>, ShamirError> {
+    split_with(
+        secret,
+        n,
+        k,
+        field(prime),
+        &mut Rng::new().map_err(|_| ShamirError::RandomError)?,
+    )
+}
+
+/// Splits a secret like split_secret, but taking the random
+/// numbers from a ChaCha20 generator with the given seed, so the
+/// same shares are returned every time, for known-answer tests.
+///
+/// # Parameters
+///
+/// - secret: The secret to share.
+/// - n: The total number of shares to return (2 < n <= MAX_SHARES).
+/// - k: The minimum number of shares to decipher the secret (0 < k <= n).
+/// - seed: The key of the ChaCha20 generator, see Rng::from_seed.
+/// - prime: The prime of the field, default_prime if None.
+///
+/// # Returns
+///
+/// A vector with n Shares, in the order of their indices.
+///
+/// # Errors
+///
+/// A ShamirError::InvalidThreshold if the parameter constraints
+/// are not met.
+#[cfg(any(test, feature = "deterministic"))]
+pub fn split_secret_seeded(
+    secret: &[u8],
+    n: usize,
+    k: usize,
+    seed: [u8; crate::math::random::SEED_SIZE],
+    prime: Option<&Prime>,
+) -> Result<Vec<Share>, ShamirError> {
+    Ok(split_with(secret, n, k, field(prime), &mut Rng::from_seed(seed))?.collect())
+}
+
+/// Splits a secret like split_secret, but deriving the random
+/// numbers from a seed with BLAKE3 in key derivation mode, so the
+/// same seed and secret always give the same shares, and a lost
+/// share can be made again later without storing the shares.
+///
+/// The indices of the shares are n distinct numbers in
+/// 1..=MAX_SHARES drawn from a key derived from the seed alone, so
+/// splitting again with a larger n keeps the first shares. The
+/// coefficients of the polynomials are drawn from a key derived
+/// from the seed, k and the secret, so a seed used for two secrets
+/// does not reveal how they differ. The seed must be kept as
+/// carefully as the secret: with it, any share is made again.
+///
 /// # Parameters
 ///
 /// - secret: The secret to share.
-/// - n: The total number of shares to return (n > 2).
+/// - n: The total number of shares to return (2 < n <= MAX_SHARES).
 /// - k: The minimum number of shares to decipher the secret (0 < k <= n).
+/// - seed: The seed, with at least MIN_SEED_SIZE random bytes.
+/// - prime: The prime of the field, default_prime if None.
 ///
 /// # Returns
 ///
-/// A ShareIter with n Shares.
+/// A ShareIter with n Shares, in the order they are drawn.
 ///
-/// # Panics
+/// # Errors
 ///
-/// This method panics if the parameter constraints are not met.
-pub fn split_secret(secret: &[u8], n: usize, k: usize) -> ShareIter {
+/// A ShamirError::InvalidThreshold if the parameter constraints
+/// are not met or the seed is shorter than MIN_SEED_SIZE.
+pub fn split_secret_from_seed<'a>(
+    secret: &[u8],
+    n: usize,
+    k: usize,
+    seed: &[u8],
+    prime: Option<&'a Prime>,
+) -> Result<ShareIter<'a>, ShamirError> {
+    if seed.len() < MIN_SEED_SIZE {
+        return Err(ShamirError::InvalidThreshold(format!(
+            "the seed must have at least {} bytes",
+            MIN_SEED_SIZE
+        )));
+    }
+    let mut material = Vec::with_capacity(seed.len() + 8 + secret.len());
+    material.extend_from_slice(seed);
+    material.extend_from_slice(&(k as u64).to_le_bytes());
+    material.extend_from_slice(secret);
+    let mut key = blake3::derive_key(COEFFICIENTS_CONTEXT, &material);
+    material.zeroize();
+    let mut rng = Rng::from_seed(key);
+    key.zeroize();
+    let mut shares = split_with(secret, n, k, field(prime), &mut rng)?;
+
+    let mut key = blake3::derive_key(INDICES_CONTEXT, seed);
+    let mut rng = Rng::from_seed(key);
+    key.zeroize();
+    let bound = Integer::from(MAX_SHARES);
+    let mut drawn = HashSet::with_capacity(n);
+    let mut indices = Vec::with_capacity(n);
+    while indices.len() < n {
+        let index = rng.below(&bound).to_usize().unwrap() + 1;
+        if drawn.insert(index) {
+            indices.push(index);
+        }
+    }
+    shares.indices = indices.into_iter();
+    Ok(shares)
+}
+
+// returns the given prime or else the default one
+fn field(prime: Option<&Prime>) -> &Prime {
+    prime.unwrap_or_else(|| default_prime())
+}
+
+// splits the secret in n shares with the indices 1 to n
+fn split_with<'a>(
+    secret: &[u8],
+    n: usize,
+    k: usize,
+    prime: &'a Prime,
+    rng: &mut Rng,
+) -> Result<ShareIter<'a>, ShamirError> {
     if n <= 2 {
-        panic!("n must be greater than 2");
+        return Err(ShamirError::InvalidThreshold(
+            "n must be greater than 2".into(),
+        ));
     }
-    if k == 0 || k > n {
-        panic!("k must be in the range 0 < k <= n");
+    split_numbers(secret_numbers(secret, prime), n, k, prime, rng)
+}
+
+// number of bytes of the blocks a secret is split in, the
+// largest whose numbers are always lower than the prime
+fn block_size(prime: &Prime) -> usize {
+    ((prime.bits() - 1) / 8) as usize
+}
+
+// the numbers of the blocks of a secret, at least one
+fn secret_numbers<'a>(secret: &[u8], prime: &'a Prime) -> Vec<ModInteger<'a>> {
+    if secret.is_empty() {
+        return vec![ModInteger::zero(prime)];
     }
+    secret
+        .chunks(block_size(prime))
+        .map(|block| ModInteger::from_digits(block, prime))
+        .collect()
+}
+
+// splits numbers of the field in n shares with the indices 1 to n,
+// any k of them being the evaluations that determine the polynomials
+fn split_numbers<'a>(
+    secret_numbers: Vec<ModInteger<'a>>,
+    n: usize,
+    k: usize,
+    prime: &'a Prime,
+    rng: &mut Rng,
+) -> Result<ShareIter<'a>, ShamirError> {
+    check_threshold(n, k)?;
+    if n > MAX_SHARES {
+        return Err(ShamirError::InvalidThreshold(format!(
+            "n must not be greater than {}",
+            MAX_SHARES
+        )));
+    }
+    Ok(ShareIter {
+        polynomials: secret_numbers
+            .into_iter()
+            .map(|number| random_polynomial(number, k, prime, rng))
+            .collect(),
+        prime,
+        indices: (1..n + 1).collect::<Vec<_>>().into_iter(),
+    })
+}
 
-    // Initialize values
-    let prime = Prime::parse(PRIME_257).unwrap();
-    let zero = ModInteger::zero(&prime);
-    let mut rng = Rng::new();
-    let secret_number = ModInteger::from_digits(secret, &prime);
+// checks that k is in the range 0 < k <= n
+fn check_threshold(n: usize, k: usize) -> Result<(), ShamirError> {
+    if k == 0 || k > n {
+        return Err(ShamirError::InvalidThreshold(
+            "k must be in the range 0 < k <= n".into(),
+        ));
+    }
+    Ok(())
+}
 
-    // Create the polynomial
+// creates a random polynomial of degree k - 1 whose value at zero is the secret
+fn random_polynomial<'a>(
+    secret_number: ModInteger<'a>,
+    k: usize,
+    prime: &'a Prime,
+    rng: &mut Rng,
+) -> Polynomial<'a> {
     let mut coefficients = Vec::with_capacity(k);
     coefficients.push(secret_number);
     if k > 1 {
-        for _ in 1..k - 2 {
-            coefficients.push(ModInteger::random(&prime, &mut rng));
+        for _ in 1..k - 1 {
+            coefficients.push(ModInteger::random(prime, rng));
         }
         // Ensure last element is not zero
-        coefficients.push(non_zero_random(&prime, &mut rng, &zero));
+        coefficients.push(ModInteger::random_nonzero(prime, rng));
     }
-    let polynomial = Polynomial::from_coefficients(coefficients);
+    Polynomial::from_coefficients(coefficients)
+}
 
-    // Compute n random evaluations of polynomial
-    let mut evaluations = HashSet::with_capacity(n);
-    while evaluations.len() < n {
-        let x = non_zero_random(&prime, &mut rng, &zero);
-        let eval = polynomial.eval(x);
-        evaluations.insert((eval.0.to_string_radix(RADIX), eval.1.to_string_radix(RADIX)));
+/// Recovers a secret given an Iterator of Shares.
+///
+/// # Parameters
+///
+/// - shares: An Iterator of shares, requires that each share has a
+///   unique first element.
+/// - prime: The prime of the field the secret was split in,
+///   default_prime if None.
+///
+/// # Returns
+///
+/// A vector of bytes containing the original secret.
+///
+/// # Errors
+///
+/// A ShamirError::ParseError if it cannot parse the integers, a
+/// ShamirError::DuplicateShare if there are two shares with the same
+/// first element, a ShamirError::FieldMismatch if a value is not in
+/// the field or if the shares do not have the same number of blocks,
+/// and a ShamirError::InvalidThreshold if no share is given.
+pub fn recover_secret(
+    shares: impl Iterator<Item = Share>,
+    prime: Option<&Prime>,
+) -> Result<Vec<u8>, ShamirError> {
+    let prime = field(prime);
+    recover_blocks(shares, prime, |values| {
+        recover_number(values.into_iter(), prime)
+    })
+}
+
+// recovers the number of each block of the shares and
+// joins the bytes of the numbers in the secret
+fn recover_blocks<'a>(
+    shares: impl Iterator<Item = Share>,
+    prime: &'a Prime,
+    recover: impl Fn(Vec<Share>) -> Result<ModInteger<'a>, ShamirError>,
+) -> Result<Vec<u8>, ShamirError> {
+    let shares: Vec<(String, Vec<String>)> = shares
+        .map(|Share(x, y)| (x, y.split(BLOCK_SEPARATOR).map(String::from).collect()))
+        .collect();
+    let blocks = shares.first().map_or(1, |(_, values)| values.len());
+    if shares.iter().any(|(_, values)| values.len() != blocks) {
+        return Err(ShamirError::FieldMismatch(
+            "the shares do not have the same number of blocks".into(),
+        ));
     }
+    let mut secret = Vec::new();
+    for block in 0..blocks {
+        let values = shares
+            .iter()
+            .map(|(x, values)| Share(x.clone(), values[block].clone()))
+            .collect();
+        let digits = recover(values)?.to_digits();
+        // every block but the last one has the block size
+        if block + 1 < blocks {
+            let padding = block_size(prime).saturating_sub(digits.len());
+            secret.extend(vec![0u8; padding]);
+        }
+        secret.extend(digits);
+    }
+    Ok(secret)
+}
 
-    evaluations.into_iter()
+/// Recovers a secret of a known length like recover_secret, but
+/// restoring the leading zero bytes, which the numbers of the field
+/// the secret is shared as do not keep.
+///
+/// # Parameters
+///
+/// - shares: An Iterator of shares, requires that each share has a
+///   unique first element.
+/// - prime: The prime of the field the secret was split in,
+///   default_prime if None.
+/// - length: The length in bytes of the secret.
+///
+/// # Returns
+///
+/// A vector of bytes containing the original secret.
+///
+/// # Errors
+///
+/// This method returns the errors of recover_secret, and a
+/// ShamirError::FieldMismatch if the secret recovered is longer
+/// than length.
+pub fn recover_secret_with_length(
+    shares: impl Iterator<Item = Share>,
+    prime: Option<&Prime>,
+    length: usize,
+) -> Result<Vec<u8>, ShamirError> {
+    restore_length(recover_secret(shares, prime)?, length)
 }
 
-// Computes a random ModInteger that does not equals zero.
-fn non_zero_random<'a>(prime: &'a Prime, rng: &mut Rng, zero: &ModInteger) -> ModInteger<'a> {
-    let mut random = ModInteger::random(&prime, rng);
-    while random == *zero {
-        random = ModInteger::random(&prime, rng);
+/// Restores the leading zero bytes of a secret recovered from
+/// numbers of a field, which do not keep them.
+///
+/// # Parameters
+///
+/// - secret: The secret recovered.
+/// - length: The length in bytes of the secret.
+///
+/// # Errors
+///
+/// A ShamirError::FieldMismatch if the secret is longer than length.
+pub fn restore_length(secret: Vec<u8>, length: usize) -> Result<Vec<u8>, ShamirError> {
+    if secret.len() > length {
+        return Err(ShamirError::FieldMismatch(format!(
+            "the shares recover {} bytes instead of {}",
+            secret.len(),
+            length
+        )));
     }
-    random
+    let mut padded = vec![0u8; length - secret.len()];
+    padded.extend(secret);
+    Ok(padded)
 }
 
-/// Recovers a secret given an Iterator of Shares.
+/// Splits a secret into n additive shares, numbers of the field
+/// whose sum is the secret, so all of them are needed to recover it.
+///
+/// The shares have the form of the shares of split_secret: the
+/// index of the share and the values of the blocks of the secret.
+///
+/// # Parameters
+///
+/// - secret: The secret to share.
+/// - n: The total number of shares to return (2 <= n <= MAX_SHARES).
+/// - prime: The prime of the field, default_prime if None.
+///
+/// # Returns
+///
+/// A vector with n Shares, in the order of their indices.
+///
+/// # Errors
+///
+/// A ShamirError::InvalidThreshold if n is out of its range.
+pub fn split_additive(
+    secret: &[u8],
+    n: usize,
+    prime: Option<&Prime>,
+) -> Result<Vec<Share>, ShamirError> {
+    let prime = field(prime);
+    if !(2..=MAX_SHARES).contains(&n) {
+        return Err(ShamirError::InvalidThreshold(format!(
+            "n must be in the range 2 <= n <= {}",
+            MAX_SHARES
+        )));
+    }
+    let mut rng = Rng::new().map_err(|_| ShamirError::RandomError)?;
+    let mut values = vec![Vec::new(); n];
+    for number in secret_numbers(secret, prime) {
+        let mut last = number;
+        for share_values in &mut values[..n - 1] {
+            let random = ModInteger::random(prime, &mut rng);
+            last -= &random;
+            share_values.push(random.to_string_radix(RADIX));
+        }
+        values[n - 1].push(last.to_string_radix(RADIX));
+    }
+    Ok(values
+        .into_iter()
+        .enumerate()
+        .map(|(i, share_values)| {
+            let x = ModInteger::zero(prime) + (i + 1) as u64;
+            Share(x.to_string_radix(RADIX), share_values.join(BLOCK_SEPARATOR))
+        })
+        .collect())
+}
+
+/// Recovers a secret from all of its additive shares, see
+/// split_additive.
 ///
 /// # Parameters
 ///
 /// - shares: An Iterator of shares, requires that each share has a
-/// unique first element.
+///   unique first element.
+/// - prime: The prime of the field the secret was split in,
+///   default_prime if None.
 ///
 /// # Returns
 ///
-/// A vector of bytes containing the original secret.
+/// A vector of bytes containing the original secret, or other
+/// bytes if a share is missing.
+///
+/// # Errors
+///
+/// The errors of recover_secret.
+pub fn recover_additive(
+    shares: impl Iterator<Item = Share>,
+    prime: Option<&Prime>,
+) -> Result<Vec<u8>, ShamirError> {
+    let prime = field(prime);
+    recover_blocks(shares, prime, |values| {
+        if values.is_empty() {
+            return Err(ShamirError::InvalidThreshold("no shares were given".into()));
+        }
+        let mut indices = HashSet::with_capacity(values.len());
+        let mut sum = ModInteger::zero(prime);
+        for Share(x, y) in &values {
+            let index = parse_number(x, prime, RADIX)?;
+            if !indices.insert(index.to_string_radix(RADIX)) {
+                return Err(ShamirError::DuplicateShare(index.to_string_radix(RADIX)));
+            }
+            sum += parse_number(y, prime, RADIX)?;
+        }
+        Ok(sum)
+    })
+}
+
+/// Splits a secret with a (k, L, n) ramp scheme, where each share is
+/// about 1/L the size of the secret.
+///
+/// The numbers of the secret are taken L at a time as the values of
+/// a polynomial of degree k - 1 at -1, ..., -L, whose values at
+/// -L - 1, ..., -k are random, and the shares are its values at
+/// 1, ..., n. Any k shares recover the secret, while k - L shares or
+/// less give no information about it. The second element of each
+/// share is the number of numbers of the secret followed by the
+/// values of the polynomials, joined with dots.
+///
+/// # Parameters
+///
+/// - secret: The secret to share.
+/// - n: The total number of shares to return (2 <= n <= MAX_SHARES).
+/// - k: The minimum number of shares to recover the secret (0 < k <= n).
+/// - pieces: The number L of numbers of the secret in each polynomial (0 < L < k).
+/// - prime: The prime of the field, default_prime if None.
+///
+/// # Returns
+///
+/// A vector with n Shares, in the order of their indices.
+///
+/// # Errors
+///
+/// A ShamirError::InvalidThreshold if the parameter constraints are
+/// not met or if the field does not have n + k distinct points.
+pub fn split_ramp(
+    secret: &[u8],
+    n: usize,
+    k: usize,
+    pieces: usize,
+    prime: Option<&Prime>,
+) -> Result<Vec<Share>, ShamirError> {
+    let prime = field(prime);
+    check_threshold(n, k)?;
+    if pieces == 0 || pieces >= k {
+        return Err(ShamirError::InvalidThreshold(
+            "L must be in the range 0 < L < k".into(),
+        ));
+    }
+    let mut numbers = secret_numbers(secret, prime);
+    let count = numbers.len();
+    numbers.resize(count.div_ceil(pieces) * pieces, ModInteger::zero(prime));
+    let groups = numbers.chunks(pieces).map(<[_]>::to_vec).collect();
+    pack_shares(&[count], groups, k - pieces, n, prime)
+}
+
+/// Recovers a secret from the shares of split_ramp.
+///
+/// # Parameters
+///
+/// - shares: At least k of the shares, each with a unique first element.
+/// - pieces: The number L of numbers of the secret in each polynomial.
+/// - prime: The prime of the field the secret was split in,
+///   default_prime if None.
+///
+/// # Returns
+///
+/// A vector of bytes containing the original secret, or other
+/// bytes if there are less than k shares.
+///
+/// # Errors
+///
+/// The errors of recover_secret, and a ShamirError::FieldMismatch if
+/// the number of numbers of the secret does not match the values.
+pub fn recover_ramp(
+    shares: &[Share],
+    pieces: usize,
+    prime: Option<&Prime>,
+) -> Result<Vec<u8>, ShamirError> {
+    let prime = field(prime);
+    if pieces == 0 {
+        return Err(ShamirError::InvalidThreshold(
+            "L must be greater than 0".into(),
+        ));
+    }
+    let (xs, values) = unpack_shares(shares, prime)?;
+    let count = prefix_number(&values, 0)?;
+    let groups = values[0].len() - 1;
+    if count == 0 || count > groups * pieces || count + pieces <= groups * pieces {
+        return Err(ShamirError::FieldMismatch(format!(
+            "the shares do not have {} numbers in groups of {}",
+            count, pieces
+        )));
+    }
+    let numbers: Vec<ModInteger> = unpack_groups(&xs, &values, 1, pieces, prime)
+        .into_iter()
+        .flatten()
+        .collect();
+    Ok(join_numbers(&numbers[..count], prime))
+}
+
+/// Splits several secrets with packed secret sharing, so they are
+/// shared with a single set of shares.
+///
+/// The numbers of the secrets at the same position are the values of
+/// a polynomial of degree k + m - 1 at -1, ..., -m, whose values at
+/// -m - 1, ..., -m - k are random, and the shares are its values at
+/// 1, ..., n. Any k + m shares recover every secret, while k shares
+/// or less give no information about them. The second element of
+/// each share is m, the number of numbers of each secret and the
+/// values of the polynomials, joined with dots.
+///
+/// # Parameters
+///
+/// - secrets: The m secrets to share, at least one.
+/// - n: The total number of shares to return (k + m <= n <= MAX_SHARES).
+/// - k: The number of shares that give no information about the secrets (k > 0).
+/// - prime: The prime of the field, default_prime if None.
+///
+/// # Returns
+///
+/// A vector with n Shares, in the order of their indices.
+///
+/// # Errors
+///
+/// A ShamirError::InvalidThreshold if the parameter constraints are
+/// not met or if the field does not have n + k + m distinct points.
+pub fn split_packed(
+    secrets: &[&[u8]],
+    n: usize,
+    k: usize,
+    prime: Option<&Prime>,
+) -> Result<Vec<Share>, ShamirError> {
+    let prime = field(prime);
+    let m = secrets.len();
+    if m == 0 {
+        return Err(ShamirError::InvalidThreshold(
+            "no secrets were given".into(),
+        ));
+    }
+    if k == 0 || k + m > n {
+        return Err(ShamirError::InvalidThreshold(
+            "k must be in the range 0 < k <= n - m".into(),
+        ));
+    }
+    let numbers: Vec<Vec<ModInteger>> = secrets
+        .iter()
+        .map(|secret| secret_numbers(secret, prime))
+        .collect();
+    let mut prefix = vec![m];
+    prefix.extend(numbers.iter().map(Vec::len));
+    let blocks = numbers.iter().map(Vec::len).max().unwrap();
+    let groups = (0..blocks)
+        .map(|block| {
+            numbers
+                .iter()
+                .map(|secret| {
+                    let zero = ModInteger::zero(prime);
+                    secret.get(block).cloned().unwrap_or(zero)
+                })
+                .collect()
+        })
+        .collect();
+    pack_shares(&prefix, groups, k, n, prime)
+}
+
+/// Recovers the secrets from the shares of split_packed.
+///
+/// # Parameters
+///
+/// - shares: At least k + m of the shares, each with a unique first element.
+/// - prime: The prime of the field the secrets were split in,
+///   default_prime if None.
+///
+/// # Returns
+///
+/// The m secrets, in the order they were given, or other bytes if
+/// there are less than k + m shares.
+///
+/// # Errors
+///
+/// The errors of recover_secret, and a ShamirError::FieldMismatch if
+/// the number of secrets or of their numbers does not match the values.
+pub fn recover_packed(
+    shares: &[Share],
+    prime: Option<&Prime>,
+) -> Result<Vec<Vec<u8>>, ShamirError> {
+    let prime = field(prime);
+    let (xs, values) = unpack_shares(shares, prime)?;
+    let m = prefix_number(&values, 0)?;
+    if m == 0 || m + 1 >= values[0].len() {
+        return Err(ShamirError::FieldMismatch(format!(
+            "the shares do not have {} secrets",
+            m
+        )));
+    }
+    let counts = (1..=m)
+        .map(|position| prefix_number(&values, position))
+        .collect::<Result<Vec<_>, _>>()?;
+    let blocks = values[0].len() - m - 1;
+    if counts.iter().any(|&count| count == 0 || count > blocks) {
+        return Err(ShamirError::FieldMismatch(format!(
+            "the shares do not have {} blocks for every secret",
+            blocks
+        )));
+    }
+    let groups = unpack_groups(&xs, &values, m + 1, m, prime);
+    Ok(counts
+        .iter()
+        .enumerate()
+        .map(|(secret, &count)| {
+            let numbers: Vec<ModInteger> = groups[..count]
+                .iter()
+                .map(|group| group[secret].clone())
+                .collect();
+            join_numbers(&numbers, prime)
+        })
+        .collect())
+}
+
+// writes the shares with the indices 1 to n of the polynomials whose
+// values at -1, -2, ... are the numbers of each group followed by
+// randoms random numbers, after the numbers of the prefix
+fn pack_shares(
+    prefix: &[usize],
+    groups: Vec<Vec<ModInteger>>,
+    randoms: usize,
+    n: usize,
+    prime: &Prime,
+) -> Result<Vec<Share>, ShamirError> {
+    if !(2..=MAX_SHARES).contains(&n) {
+        return Err(ShamirError::InvalidThreshold(format!(
+            "n must be in the range 2 <= n <= {}",
+            MAX_SHARES
+        )));
+    }
+    let points = n + groups[0].len() + randoms;
+    // the number of points is lower than the prime when it is not reduced
+    if (ModInteger::zero(prime) + points as u64).to_string_radix(10) != points.to_string() {
+        return Err(ShamirError::InvalidThreshold(format!(
+            "the field is too small for {} distinct points",
+            points
+        )));
+    }
+    let mut rng = Rng::new().map_err(|_| ShamirError::RandomError)?;
+    let polynomials: Vec<Polynomial> = groups
+        .into_iter()
+        .map(|group| {
+            let pieces = group.len();
+            let mut evaluations: Vec<Evaluation> = group
+                .into_iter()
+                .enumerate()
+                .map(|(j, number)| (negative_point(j + 1, prime), number))
+                .collect();
+            for j in pieces + 1..=pieces + randoms {
+                evaluations.push((
+                    negative_point(j, prime),
+                    ModInteger::random(prime, &mut rng),
+                ));
+            }
+            // the negative points are distinct
+            Polynomial::from_evals(evaluations).unwrap()
+        })
+        .collect();
+    Ok((1..=n)
+        .map(|i| {
+            let x = ModInteger::zero(prime) + i as u64;
+            let mut values: Vec<String> = prefix
+                .iter()
+                .map(|&number| (ModInteger::zero(prime) + number as u64).to_string_radix(RADIX))
+                .collect();
+            values.extend(
+                polynomials
+                    .iter()
+                    .map(|polynomial| polynomial.eval(x.clone()).1.to_string_radix(RADIX)),
+            );
+            Share(x.to_string_radix(RADIX), values.join(BLOCK_SEPARATOR))
+        })
+        .collect())
+}
+
+// parses the indices and the values of shares written by pack_shares,
+// which must be distinct and have the same number of values
+fn unpack_shares<'a>(
+    shares: &[Share],
+    prime: &'a Prime,
+) -> Result<(Vec<ModInteger<'a>>, Vec<Vec<ModInteger<'a>>>), ShamirError> {
+    if shares.is_empty() {
+        return Err(ShamirError::InvalidThreshold("no shares were given".into()));
+    }
+    let mut xs = Vec::with_capacity(shares.len());
+    let mut values = Vec::with_capacity(shares.len());
+    for Share(x, y) in shares {
+        let x = parse_number(x, prime, RADIX)?;
+        if xs.contains(&x) {
+            return Err(ShamirError::DuplicateShare(x.to_string_radix(RADIX)));
+        }
+        xs.push(x);
+        values.push(
+            y.split(BLOCK_SEPARATOR)
+                .map(|value| parse_number(value, prime, RADIX))
+                .collect::<Result<Vec<_>, _>>()?,
+        );
+    }
+    if values.iter().any(|other| other.len() != values[0].len()) {
+        return Err(ShamirError::FieldMismatch(
+            "the shares do not have the same number of blocks".into(),
+        ));
+    }
+    Ok((xs, values))
+}
+
+// reads a number of the prefix written by pack_shares, which
+// must be the same in every share
+fn prefix_number(values: &[Vec<ModInteger>], position: usize) -> Result<usize, ShamirError> {
+    let number = match values[0].get(position) {
+        Some(number) if values.iter().all(|other| other[position] == *number) => number,
+        _ => {
+            return Err(ShamirError::FieldMismatch(
+                "the shares do not have the same number of blocks".into(),
+            ))
+        }
+    };
+    Ok(number.to_string_radix(10).parse().unwrap_or(usize::MAX))
+}
+
+// interpolates the values of the shares from offset on, a polynomial
+// for each position, and evaluates them at -1, ..., -pieces
+fn unpack_groups<'a>(
+    xs: &[ModInteger<'a>],
+    values: &[Vec<ModInteger<'a>>],
+    offset: usize,
+    pieces: usize,
+    prime: &'a Prime,
+) -> Vec<Vec<ModInteger<'a>>> {
+    (offset..values[0].len())
+        .map(|position| {
+            let evaluations: Vec<Evaluation> = xs
+                .iter()
+                .cloned()
+                .zip(values.iter().map(|share| share[position].clone()))
+                .collect();
+            // the shares are not empty and their indices distinct
+            let polynomial = Polynomial::from_evals(evaluations).unwrap();
+            (1..=pieces)
+                .map(|j| polynomial.eval(negative_point(j, prime)).1)
+                .collect()
+        })
+        .collect()
+}
+
+// joins the bytes of the numbers of a secret, every number
+// but the last one having the block size
+fn join_numbers(numbers: &[ModInteger], prime: &Prime) -> Vec<u8> {
+    let mut secret = Vec::new();
+    for (i, number) in numbers.iter().enumerate() {
+        let digits = number.to_digits();
+        if i + 1 < numbers.len() {
+            secret.extend(vec![0u8; block_size(prime).saturating_sub(digits.len())]);
+        }
+        secret.extend(digits);
+    }
+    secret
+}
+
+// the point -j of the field, where the numbers of ramp and
+// packed secrets and their random values are placed
+fn negative_point(j: usize, prime: &Prime) -> ModInteger<'_> {
+    ModInteger::zero(prime) - (ModInteger::zero(prime) + j as u64)
+}
+
+/// Checks that shares are consistent, that is, that every share
+/// beyond the first k lies on the polynomials the first k define.
+///
+/// # Parameters
+///
+/// - shares: The shares, each with a unique first element.
+/// - k: The minimum number of shares to recover the secret.
+/// - prime: The prime of the field the secret was split in,
+///   default_prime if None.
+///
+/// # Returns
+///
+/// Whether all the shares are shares of the same secret, always
+/// true with k shares or less.
+///
+/// # Errors
+///
+/// The errors of recover_secret if the shares cannot be parsed.
+pub fn verify_shares(
+    shares: &[Share],
+    k: usize,
+    prime: Option<&Prime>,
+) -> Result<bool, ShamirError> {
+    let prime = field(prime);
+    if k == 0 {
+        return Err(ShamirError::InvalidThreshold(
+            "K must be greater than 0".into(),
+        ));
+    }
+    let blocks: Vec<Vec<&str>> = shares
+        .iter()
+        .map(|Share(_, y)| y.split(BLOCK_SEPARATOR).collect())
+        .collect();
+    if blocks.iter().any(|values| values.len() != blocks[0].len()) {
+        return Err(ShamirError::FieldMismatch(
+            "the shares do not have the same number of blocks".into(),
+        ));
+    }
+    if shares.len() <= k {
+        return Ok(true);
+    }
+    let xs = shares
+        .iter()
+        .map(|Share(x, _)| parse_number(x, prime, RADIX))
+        .collect::<Result<Vec<_>, _>>()?;
+    for block in 0..blocks[0].len() {
+        let ys = blocks
+            .iter()
+            .map(|values| parse_number(values[block], prime, RADIX))
+            .collect::<Result<Vec<_>, _>>()?;
+        let evaluations: Vec<Evaluation> = xs[..k]
+            .iter()
+            .cloned()
+            .zip(ys[..k].iter().cloned())
+            .collect();
+        // checks the first elements are distinct
+        interpolate_at_zero(evaluations.clone(), prime, RADIX)?;
+        let polynomial = Polynomial::from_evals(evaluations).unwrap();
+        for (x, y) in xs[k..].iter().zip(&ys[k..]) {
+            if polynomial.eval(x.clone()).1 != *y {
+                return Ok(false);
+            }
+        }
+    }
+    Ok(true)
+}
+
+// interpolates the shares and evaluates the polynomial at zero
+fn recover_number<'a>(
+    shares: impl Iterator<Item = Share>,
+    prime: &'a Prime,
+) -> Result<ModInteger<'a>, ShamirError> {
+    let evaluations = shares
+        .map(|Share(x, y)| {
+            Ok((
+                parse_number(&x, prime, RADIX)?,
+                parse_number(&y, prime, RADIX)?,
+            ))
+        })
+        .collect::<Result<Vec<Evaluation>, _>>()?;
+    interpolate_at_zero(evaluations, prime, RADIX)
+}
+
+// parses a number of the field, which must be lower than the prime
+// instead of being reduced, so shares of another field are detected
+fn parse_number<'a>(s: &str, prime: &'a Prime, radix: i32) -> Result<ModInteger<'a>, ShamirError> {
+    let number = ModInteger::parse_radix(s, prime, radix)
+        .map_err(|_| ShamirError::ParseError(s.to_string()))?;
+    let digits = match s.trim_start_matches('0') {
+        "" => "0".to_string(),
+        digits => digits.to_lowercase(),
+    };
+    if number.to_string_radix(radix) != digits {
+        return Err(ShamirError::FieldMismatch(format!(
+            "{} is not a value of the field",
+            s
+        )));
+    }
+    Ok(number)
+}
+
+// interpolates the evaluations, each with a distinct first element,
+// and evaluates the polynomial at zero
+fn interpolate_at_zero<'a>(
+    evaluations: Vec<Evaluation<'a>>,
+    prime: &'a Prime,
+    radix: i32,
+) -> Result<ModInteger<'a>, ShamirError> {
+    if evaluations.is_empty() {
+        return Err(ShamirError::InvalidThreshold("no shares were given".into()));
+    }
+    let mut indices = HashSet::with_capacity(evaluations.len());
+    for (x, _) in &evaluations {
+        if !indices.insert(x) {
+            return Err(ShamirError::DuplicateShare(x.to_string_radix(radix)));
+        }
+    }
+    // the evaluations are not empty and their first elements distinct
+    let polynomial = Polynomial::from_evals(evaluations).unwrap();
+    let (_, number) = polynomial.eval(ModInteger::zero(prime));
+    Ok(number)
+}
+
+/// Splits a secret like split_secret, but also for n = 1 or 2, so
+/// the first element of each share is its index and does not need
+/// to be kept.
+///
+/// # Parameters
+///
+/// - secret: The secret to share.
+/// - n: The total number of shares to return (0 < n <= MAX_SHARES).
+/// - k: The minimum number of shares to decipher the secret (0 < k <= n).
+///
+/// # Returns
+///
+/// A vector with n Shares, the share with index i at position i - 1
+/// and with i in the radix of the shares as first element.
+///
+/// # Errors
+///
+/// A ShamirError::InvalidThreshold if the parameter constraints
+/// are not met.
+pub fn split_secret_indexed(secret: &[u8], n: usize, k: usize) -> Result<Vec<Share>, ShamirError> {
+    let prime = default_prime();
+    Ok(split_numbers(
+        secret_numbers(secret, prime),
+        n,
+        k,
+        prime,
+        &mut Rng::new().map_err(|_| ShamirError::RandomError)?,
+    )?
+    .collect())
+}
+
+/// Splits the second element of a share again, so that the share
+/// can be held by a group instead of a single person.
+///
+/// # Parameters
+///
+/// - value: The second element of a share, or a secret as
+///   returned by secret_to_value.
+/// - n: The total number of shares to return (0 < n <= MAX_SHARES).
+/// - k: The minimum number of shares to recover the value (0 < k <= n).
+///
+/// # Returns
+///
+/// A vector with n Shares, the share with index i at position i - 1.
+///
+/// # Errors
+///
+/// A ShamirError::ParseError or FieldMismatch if the value is not a
+/// number of the field, and a ShamirError::InvalidThreshold if the
+/// parameter constraints are not met.
+pub fn split_value(value: &str, n: usize, k: usize) -> Result<Vec<Share>, ShamirError> {
+    let prime = default_prime();
+    let number = parse_number(value, prime, RADIX)?;
+    Ok(split_numbers(
+        vec![number],
+        n,
+        k,
+        prime,
+        &mut Rng::new().map_err(|_| ShamirError::RandomError)?,
+    )?
+    .collect())
+}
+
+/// Recovers a value split with split_value.
+///
+/// # Parameters
+///
+/// - shares: An Iterator of shares, requires that each share has a
+///   unique first element.
+///
+/// # Returns
+///
+/// The value, in the same representation as the second
+/// element of a share.
+///
+/// # Errors
+///
+/// The errors of recover_secret, except for the blocks.
+pub fn recover_value(shares: impl Iterator<Item = Share>) -> Result<String, ShamirError> {
+    Ok(recover_number(shares, default_prime())?.to_string_radix(RADIX))
+}
+
+/// Returns the number a secret is shared as, in the same
+/// representation as the second element of a share.
+///
+/// # Parameters
+///
+/// - secret: The secret to share.
+pub fn secret_to_value(secret: &[u8]) -> String {
+    ModInteger::from_digits(secret, default_prime()).to_string_radix(RADIX)
+}
+
+/// The inverse of secret_to_value.
+///
+/// # Parameters
+///
+/// - value: The number the secret was shared as.
 ///
 /// # Errors
 ///
-/// This method returns an error if it cannot parse the integers
-/// or if there are two shares with the same first element.
-pub fn recover_secret(shares: impl Iterator<Item = Share>) -> Result<Vec<u8>, Box<dyn Error>> {
-    let prime = Prime::parse(PRIME_257).unwrap();
+/// A ShamirError::ParseError or FieldMismatch if the value is not a
+/// number of the field.
+pub fn value_to_secret(value: &str) -> Result<Vec<u8>, ShamirError> {
+    Ok(parse_number(value, default_prime(), RADIX)?.to_digits())
+}
+
+/// Splits a private key of an elliptic curve in the field of the
+/// scalars of the curve, so every share is a scalar of the curve too.
+///
+/// The polynomial is evaluated at 1, 2, ..., n as in
+/// split_secret_indexed, and the second element of each share is
+/// its scalar in hexadecimal with 64 digits.
+///
+/// # Parameters
+///
+/// - scalar: The key, as a big endian number of SCALAR_SIZE bytes.
+/// - curve: The curve of the key.
+/// - n: The total number of shares to return (n > 0).
+/// - k: The minimum number of shares to recover the key (0 < k <= n).
+///
+/// # Returns
+///
+/// A vector with n Shares, the share with index i at position i - 1.
+///
+/// # Errors
+///
+/// A ShamirError::FieldMismatch if the key does not have SCALAR_SIZE
+/// bytes, if it is zero or if it is not lower than the order of the
+/// curve, and a ShamirError::InvalidThreshold if the parameter
+/// constraints are not met.
+pub fn split_scalar(
+    scalar: &[u8],
+    curve: Curve,
+    n: usize,
+    k: usize,
+) -> Result<Vec<Share>, ShamirError> {
+    check_threshold(n, k)?;
+    if scalar.len() != SCALAR_SIZE {
+        return Err(ShamirError::FieldMismatch(format!(
+            "a key of {} must have {} bytes",
+            curve, SCALAR_SIZE
+        )));
+    }
+    let order = Prime::parse(curve.order()).unwrap();
+    let number = ModInteger::from_digits(scalar, &order);
+    // the number is reduced, so a key out of the field changes
+    let significant: Vec<u8> = scalar.iter().copied().skip_while(|&b| b == 0).collect();
+    if significant.is_empty() || number.to_digits() != significant {
+        return Err(ShamirError::FieldMismatch(format!(
+            "the key is not a valid scalar of {}",
+            curve
+        )));
+    }
+    let polynomial = random_polynomial(
+        number,
+        k,
+        &order,
+        &mut Rng::new().map_err(|_| ShamirError::RandomError)?,
+    );
+    Ok((1..=n)
+        .map(|i| {
+            let x = ModInteger::zero(&order) + i as u64;
+            let (x, y) = polynomial.eval(x);
+            Share(x.to_string(), y.to_hex())
+        })
+        .collect())
+}
+
+/// Recovers a private key split with split_scalar.
+///
+/// # Parameters
+///
+/// - shares: An Iterator of shares, requires that each share has a
+///   unique first element.
+/// - curve: The curve of the key.
+///
+/// # Returns
+///
+/// The key, as a big endian number of SCALAR_SIZE bytes.
+///
+/// # Errors
+///
+/// The errors of recover_secret, except for the blocks.
+pub fn recover_scalar(
+    shares: impl Iterator<Item = Share>,
+    curve: Curve,
+) -> Result<Vec<u8>, ShamirError> {
+    let order = Prime::parse(curve.order()).unwrap();
     let evaluations = shares
-        .map::<Result<Evaluation, ParseIntegerError>, _>(|(x, y)| {
+        .map(|Share(x, y)| {
             Ok((
-                ModInteger::parse_radix(&x, &prime, RADIX)?,
-                ModInteger::parse_radix(&y, &prime, RADIX)?,
+                parse_number(&x, &order, 10)?,
+                parse_number(&y, &order, SCALAR_RADIX)?,
             ))
         })
         .collect::<Result<Vec<Evaluation>, _>>()?;
-    let polynomial = Polynomial::from_evals(evaluations)?;
-    let (_, secret_number) = polynomial.eval(ModInteger::zero(&prime));
-    Ok(secret_number.to_digits())
+    let number = interpolate_at_zero(evaluations, &order, 10)?;
+    let digits = number.to_digits();
+    let mut scalar = vec![0u8; SCALAR_SIZE - digits.len()];
+    scalar.extend(digits);
+    Ok(scalar)
 }
 
 /////////////////////////////////
@@ -114,17 +1498,122 @@ pub fn recover_secret(shares: impl Iterator<Item = Share>) -> Result<Vec<u8>, Bo
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::math::random::SEED_SIZE;
 
     macro_rules! split_secret_test {
         ($secret:expr, $n:expr, $k:expr, $take:expr) => {
             let secret = $secret;
-            let result = split_secret(&secret, $n, $k);
+            let result = split_secret(&secret, $n, $k, None).unwrap();
             assert_eq!(result.len(), $n);
-            let returned_secret = recover_secret(result.take($take)).unwrap();
+            let returned_secret = recover_secret(result.take($take), None).unwrap();
             assert_eq!(secret, returned_secret);
         };
     }
 
+    #[test]
+    fn evaluate_all_shares() {
+        let secret = vec![9u8; 40];
+        let mut iter = split_secret(&secret, 2 * PARALLEL_SHARES + 3, 5, None).unwrap();
+        let first = iter.next().unwrap();
+        let shares = iter.evaluate_all();
+        assert_eq!(shares.len(), 2 * PARALLEL_SHARES + 2);
+        let indices: Vec<String> = shares.iter().map(|Share(x, _)| x.clone()).collect();
+        let expected: Vec<String> = (2..=2 * PARALLEL_SHARES + 3)
+            .map(|i| (ModInteger::zero(default_prime()) + i as u64).to_string_radix(RADIX))
+            .collect();
+        assert_eq!(indices, expected);
+        let last = shares.iter().rev().take(4).cloned();
+        assert_eq!(
+            recover_secret(std::iter::once(first).chain(last), None).unwrap(),
+            secret
+        );
+    }
+
+    #[test]
+    fn shares_from_seed() {
+        let secret = vec![0x42u8; 40];
+        let seed = [7u8; MIN_SEED_SIZE];
+        let shares: Vec<Share> = split_secret_from_seed(&secret, 5, 3, &seed, None)
+            .unwrap()
+            .collect();
+        let again: Vec<Share> = split_secret_from_seed(&secret, 8, 3, &seed, None)
+            .unwrap()
+            .collect();
+        // the same seed gives the same shares, also with more of them
+        assert_eq!(shares[..], again[..5]);
+        assert_eq!(
+            recover_secret(again[4..7].iter().cloned(), None).unwrap(),
+            secret
+        );
+        let indices: HashSet<&String> = again.iter().map(|Share(x, _)| x).collect();
+        assert_eq!(indices.len(), 8);
+        // another secret gets other values at the same indices
+        let other: Vec<Share> = split_secret_from_seed(&[0x43u8; 40], 5, 3, &seed, None)
+            .unwrap()
+            .collect();
+        for (share, other) in shares.iter().zip(&other) {
+            assert_eq!(share.0, other.0);
+            assert_ne!(share.1, other.1);
+        }
+        let other: Vec<Share> = split_secret_from_seed(&secret, 5, 3, &[8u8; 32], None)
+            .unwrap()
+            .collect();
+        assert_ne!(shares, other);
+        assert!(split_secret_from_seed(&secret, 5, 3, &seed[1..], None).is_err());
+    }
+
+    #[test]
+    fn compact_shares() {
+        let secret = vec![7u8; 70];
+        let shares: Vec<Share> = split_secret(&secret, 5, 3, None).unwrap().collect();
+        let bytes: Vec<Vec<u8>> = shares
+            .iter()
+            .map(|share| share_to_bytes(share, None).unwrap())
+            .collect();
+        // three blocks of 32 bytes in the default field
+        assert!(bytes.iter().all(|share| share.len() == INDEX_SIZE + 3 * 33));
+        assert_eq!(bytes[4][..INDEX_SIZE], [0, 5]);
+        let decoded: Vec<Share> = bytes
+            .iter()
+            .map(|share| share_from_bytes(share, None).unwrap())
+            .collect();
+        assert_eq!(decoded, shares);
+        assert_eq!(
+            recover_secret(decoded.into_iter().skip(2), None).unwrap(),
+            secret
+        );
+
+        let prime = parse_field("127").unwrap();
+        let share = split_secret(&[1, 2, 3], 3, 2, Some(&prime))
+            .unwrap()
+            .next()
+            .unwrap();
+        let bytes = share_to_bytes(&share, Some(&prime)).unwrap();
+        assert_eq!(bytes.len(), INDEX_SIZE + 16);
+        assert_eq!(share_from_bytes(&bytes, Some(&prime)).unwrap(), share);
+        assert!(share_from_bytes(&bytes, None).is_err());
+        assert!(share_from_bytes(&bytes[..INDEX_SIZE], Some(&prime)).is_err());
+        let mut zero = bytes.clone();
+        zero[..INDEX_SIZE].copy_from_slice(&[0, 0]);
+        assert!(share_from_bytes(&zero, Some(&prime)).is_err());
+        let mut too_large = bytes;
+        too_large[INDEX_SIZE..].copy_from_slice(&[0xff; 16]);
+        assert!(share_from_bytes(&too_large, Some(&prime)).is_err());
+        assert!(share_to_bytes(&Share("0".into(), "1".into()), None).is_err());
+        assert!(share_to_bytes(&Share("1ekg".into(), "1".into()), None).is_err());
+    }
+
+    #[test]
+    fn shares_constant_time_eq() {
+        let share = Share("1".into(), "abc".into());
+        assert!(bool::from(share.ct_eq(&share.clone())));
+        assert!(!bool::from(share.ct_eq(&Share("2".into(), "abc".into()))));
+        assert!(!bool::from(share.ct_eq(&Share("1".into(), "abd".into()))));
+        assert!(!bool::from(share.ct_eq(&Share("1".into(), "ab".into()))));
+        assert_eq!(share, share.clone());
+        assert_ne!(share, Share("1".into(), "ab".into()));
+    }
+
     #[test]
     fn secret_empty_vec() {
         split_secret_test!(vec![], 5, 3, 3);
@@ -159,4 +1648,311 @@ mod tests {
     fn secret_more_than_k_evals() {
         split_secret_test!(vec![0xafu8, 0xbbu8, 0x13u8, 0x01u8], 10, 3, 9);
     }
+
+    #[test]
+    fn seeded_split() {
+        let secret = vec![0x5au8; 32];
+        let shares = split_secret_seeded(&secret, 5, 3, [7; SEED_SIZE], None).unwrap();
+        assert_eq!(
+            shares,
+            split_secret_seeded(&secret, 5, 3, [7; SEED_SIZE], None).unwrap()
+        );
+        assert_ne!(
+            shares,
+            split_secret_seeded(&secret, 5, 3, [8; SEED_SIZE], None).unwrap()
+        );
+        let returned_secret = recover_secret(shares.into_iter().skip(2), None).unwrap();
+        assert_eq!(secret, returned_secret);
+    }
+
+    #[test]
+    fn verify() {
+        let secret = vec![0x5au8; 40];
+        let mut shares = split_secret_seeded(&secret, 5, 3, [7; SEED_SIZE], None).unwrap();
+        assert!(verify_shares(&shares, 3, None).unwrap());
+        assert!(verify_shares(&shares[..3], 3, None).unwrap());
+        shares[4].1 = shares[3].1.clone();
+        assert!(!verify_shares(&shares, 3, None).unwrap());
+        assert!(verify_shares(&shares[..4], 3, None).unwrap());
+        assert!(matches!(
+            verify_shares(&shares, 0, None),
+            Err(ShamirError::InvalidThreshold(_))
+        ));
+    }
+
+    #[test]
+    fn additive() {
+        let secret = vec![0x5au8; 40];
+        let shares = split_additive(&secret, 4, None).unwrap();
+        assert_eq!(shares[3].0, "4");
+        assert_eq!(shares[0].1.split(BLOCK_SEPARATOR).count(), 2);
+        let reversed = shares.iter().rev().cloned();
+        assert_eq!(recover_additive(reversed, None).unwrap(), secret);
+        let some = shares[1..].iter().cloned();
+        assert_ne!(recover_additive(some, None).unwrap(), secret);
+        let twice = vec![shares[0].clone(), shares[0].clone()];
+        assert_eq!(
+            recover_additive(twice.into_iter(), None),
+            Err(ShamirError::DuplicateShare("1".into()))
+        );
+        assert!(split_additive(&secret, 1, None).is_err());
+        let zeros = split_additive(&[0, 0, 7], 2, None).unwrap();
+        let recovered = recover_additive(zeros.into_iter(), None).unwrap();
+        assert_eq!(restore_length(recovered, 3).unwrap(), vec![0, 0, 7]);
+    }
+
+    #[test]
+    fn ramp() {
+        let secret: Vec<u8> = (1..=100).collect();
+        let shares = split_ramp(&secret, 5, 4, 2, None).unwrap();
+        assert_eq!(shares[4].0, "5");
+        // 4 numbers of the secret in 2 polynomials, after the count
+        assert_eq!(shares[0].1.split(BLOCK_SEPARATOR).count(), 3);
+        assert_eq!(recover_ramp(&shares[1..], 2, None).unwrap(), secret);
+        let some = vec![
+            shares[4].clone(),
+            shares[0].clone(),
+            shares[2].clone(),
+            shares[1].clone(),
+        ];
+        assert_eq!(recover_ramp(&some, 2, None).unwrap(), secret);
+        assert_ne!(recover_ramp(&shares[..3], 2, None).unwrap(), secret);
+        assert!(verify_shares(&shares, 4, None).unwrap());
+        let odd = split_ramp(&secret[..70], 4, 3, 2, None).unwrap();
+        assert_eq!(recover_ramp(&odd[1..], 2, None).unwrap(), &secret[..70]);
+        assert!(recover_ramp(&odd[1..], 1, None).is_err());
+        assert!(split_ramp(&secret, 5, 3, 3, None).is_err());
+        assert!(split_ramp(&secret, 5, 3, 0, None).is_err());
+        let small = field_prime("65537").unwrap();
+        assert!(split_ramp(&secret, 65535, 3, 2, Some(&small)).is_err());
+    }
+
+    #[test]
+    fn packed() {
+        let first: Vec<u8> = (1..=40).collect();
+        let secrets: Vec<&[u8]> = vec![&first, b"second", &[0, 0, 7]];
+        let shares = split_packed(&secrets, 7, 2, None).unwrap();
+        assert_eq!(shares.len(), 7);
+        // m, the numbers of each secret and 2 polynomials
+        assert_eq!(shares[0].1.split(BLOCK_SEPARATOR).count(), 1 + 3 + 2);
+        let recovered = recover_packed(&shares[2..], None).unwrap();
+        assert_eq!(recovered, vec![first.clone(), b"second".to_vec(), vec![7]]);
+        let some = vec![
+            shares[6].clone(),
+            shares[0].clone(),
+            shares[3].clone(),
+            shares[1].clone(),
+            shares[4].clone(),
+        ];
+        assert_eq!(recover_packed(&some, None).unwrap()[1], b"second");
+        assert_ne!(recover_packed(&shares[..4], None).unwrap()[0], first);
+        assert!(verify_shares(&shares, 5, None).unwrap());
+        assert!(split_packed(&secrets, 4, 2, None).is_err());
+        assert!(split_packed(&[], 4, 2, None).is_err());
+        let twice = vec![shares[0].clone(), shares[0].clone()];
+        assert_eq!(
+            recover_packed(&twice, None),
+            Err(ShamirError::DuplicateShare("1".into()))
+        );
+        let ramp = split_ramp(&first, 4, 3, 2, None).unwrap();
+        assert!(recover_packed(&ramp, None).is_err());
+    }
+
+    #[test]
+    fn fewer_than_k_shares() {
+        let secret = vec![0x5au8; 32];
+        let shares = split_secret_seeded(&secret, 5, 4, [3; SEED_SIZE], None).unwrap();
+        let returned_secret = recover_secret(shares.into_iter().take(3), None).unwrap();
+        assert_ne!(secret, returned_secret);
+    }
+
+    #[test]
+    fn given_prime() {
+        let prime = Prime::parse("340282366920938463463374607431768211507").unwrap();
+        let secret = vec![0x7fu8; 15];
+        let shares = split_secret(&secret, 4, 2, Some(&prime)).unwrap();
+        let returned_secret = recover_secret(shares.take(2), Some(&prime)).unwrap();
+        assert_eq!(secret, returned_secret);
+        assert!(std::ptr::eq(default_prime(), default_prime()));
+        assert_eq!(field(None), &prime::named("257").unwrap().prime());
+    }
+
+    #[test]
+    fn nested_values() {
+        let secret = vec![0x17u8, 0x2a, 0x00, 0xff];
+        let value = secret_to_value(&secret);
+        let shares = split_value(&value, 2, 1).unwrap();
+        let inner = split_value(&shares[1].1, 3, 2).unwrap();
+        let recovered = recover_value(inner.into_iter().skip(1)).unwrap();
+        assert_eq!(recovered, shares[1].1);
+        let shares = vec![Share(shares[1].0.clone(), recovered)];
+        let value = recover_value(shares.into_iter()).unwrap();
+        assert_eq!(value_to_secret(&value).unwrap(), secret);
+    }
+
+    #[test]
+    fn indexed_split() {
+        let secret = vec![0x61u8; 32];
+        let shares = split_secret_indexed(&secret, 5, 3).unwrap();
+        let xs: Vec<&str> = shares.iter().map(|Share(x, _)| x.as_str()).collect();
+        assert_eq!(xs, ["1", "2", "3", "4", "5"]);
+        let returned_secret = recover_secret(shares.into_iter().skip(2), None).unwrap();
+        assert_eq!(secret, returned_secret);
+    }
+
+    #[test]
+    fn max_shares() {
+        let secret = vec![0x3cu8; 32];
+        let mut shares = split_secret(&secret, MAX_SHARES, 2, None).unwrap();
+        assert_eq!(shares.len(), MAX_SHARES);
+        assert_eq!(shares.next().unwrap().0, "1");
+        let last = shares.skip(MAX_SHARES - 3).collect::<Vec<_>>();
+        assert_eq!(last.len(), 2);
+        assert_eq!(last[1].0, "1ekf");
+        let returned_secret = recover_secret(last.into_iter(), None).unwrap();
+        assert_eq!(secret, returned_secret);
+        assert!(matches!(
+            split_secret(&secret, MAX_SHARES + 1, 2, None),
+            Err(ShamirError::InvalidThreshold(_))
+        ));
+    }
+
+    #[test]
+    fn leading_zeros() {
+        let prime = parse_field("127").unwrap();
+        for secret in [vec![0u8; 32], vec![0x00, 0x01, 0x02], {
+            let mut secret = vec![0u8; 31];
+            secret.push(0x7f);
+            secret
+        }] {
+            for prime in [None, Some(&prime)] {
+                let shares = split_secret(&secret, 3, 2, prime)
+                    .unwrap()
+                    .collect::<Vec<_>>();
+                let returned_secret =
+                    recover_secret_with_length(shares.clone().into_iter(), prime, secret.len())
+                        .unwrap();
+                assert_eq!(secret, returned_secret);
+                assert!(recover_secret(shares.into_iter(), prime).unwrap().len() < secret.len());
+            }
+        }
+        let shares = split_secret(&[0x01, 0x02], 3, 2, None).unwrap();
+        assert!(recover_secret_with_length(shares, None, 1).is_err());
+    }
+
+    #[test]
+    fn fields() {
+        assert_eq!(parse_field("257").unwrap(), *default_prime());
+        assert_eq!(parse_field("127").unwrap().bits(), 127);
+        assert_eq!(parse_field("secp256k1").unwrap().bits(), 256);
+        assert_eq!(parse_field("129").unwrap().bits(), 129);
+        assert_eq!(
+            parse_field("ed25519-order").unwrap().to_string(),
+            Curve::Ed25519.order()
+        );
+        assert_eq!(field_prime("secp256k1-order").unwrap().bits(), 256);
+        let prime = parse_field("custom:340282366920938463463374607431768211507").unwrap();
+        assert_eq!(field_prime(&prime.to_string()).unwrap(), prime);
+        assert!(parse_field("custom:340282366920938463463374607431768211509").is_err());
+        assert!(parse_field("custom:65521").is_err());
+        assert!(parse_field("custom:x").is_err());
+        assert!(parse_field("128").is_err());
+    }
+
+    #[test]
+    fn blocks() {
+        let prime = parse_field("127").unwrap();
+        let mut secret = vec![0u8; 16];
+        secret.extend(vec![0xa5u8; 16]);
+        let shares = split_secret(&secret, 4, 3, Some(&prime))
+            .unwrap()
+            .collect::<Vec<_>>();
+        assert_eq!(shares[0].1.split(BLOCK_SEPARATOR).count(), 3);
+        let returned_secret = recover_secret(shares.into_iter().skip(1), Some(&prime)).unwrap();
+        assert_eq!(secret, returned_secret);
+        let mut shares = split_secret(&secret, 3, 2, Some(&prime))
+            .unwrap()
+            .collect::<Vec<_>>();
+        shares[0].1 = shares[0].1.split(BLOCK_SEPARATOR).next().unwrap().into();
+        assert!(matches!(
+            recover_secret(shares.into_iter(), Some(&prime)),
+            Err(ShamirError::FieldMismatch(_))
+        ));
+    }
+
+    #[test]
+    fn errors() {
+        let secret = vec![0x5au8; 32];
+        for (n, k) in [(2, 1), (3, 0), (3, 4)] {
+            assert!(matches!(
+                split_secret(&secret, n, k, None),
+                Err(ShamirError::InvalidThreshold(_))
+            ));
+        }
+        assert!(split_secret_indexed(&secret, 2, 1).is_ok());
+        assert!(split_secret_indexed(&secret, 2, 3).is_err());
+        let shares = split_secret(&secret, 3, 2, None)
+            .unwrap()
+            .collect::<Vec<_>>();
+        let duplicate = vec![shares[1].clone(), shares[0].clone(), shares[1].clone()];
+        assert_eq!(
+            recover_secret(duplicate.into_iter(), None),
+            Err(ShamirError::DuplicateShare("2".into()))
+        );
+        let padded = vec![
+            Share("02".to_string(), shares[1].1.clone()),
+            shares[1].clone(),
+        ];
+        assert_eq!(
+            recover_secret(padded.into_iter(), None),
+            Err(ShamirError::DuplicateShare("2".into()))
+        );
+        let typo = vec![shares[0].clone(), Share("2".into(), "1-x".into())];
+        assert_eq!(
+            recover_secret(typo.into_iter(), None),
+            Err(ShamirError::ParseError("1-x".into()))
+        );
+        assert_eq!(
+            recover_secret(std::iter::empty(), None),
+            Err(ShamirError::InvalidThreshold("no shares were given".into()))
+        );
+        // the values of the default field do not fit in a smaller one
+        let prime = parse_field("127").unwrap();
+        assert!(matches!(
+            recover_secret(shares.into_iter(), Some(&prime)),
+            Err(ShamirError::FieldMismatch(_))
+        ));
+    }
+
+    #[test]
+    fn curve_orders() {
+        for &curve in &[Curve::Ed25519, Curve::Secp256k1] {
+            assert_eq!(curve.to_string().parse::<Curve>().unwrap(), curve);
+            assert!(Prime::parse(curve.order()).is_ok());
+        }
+        assert!("p256".parse::<Curve>().is_err());
+    }
+
+    #[test]
+    fn scalar_split() {
+        let mut scalar = vec![0xffu8; SCALAR_SIZE];
+        scalar[0] = 0x0f;
+        scalar[1] = 0x00;
+        for &curve in &[Curve::Ed25519, Curve::Secp256k1] {
+            let shares = split_scalar(&scalar, curve, 5, 3).unwrap();
+            assert_eq!(shares[2].0, "3");
+            assert!(shares.iter().all(|Share(_, y)| y.len() == 2 * SCALAR_SIZE));
+            let recovered = recover_scalar(shares.into_iter().skip(2), curve).unwrap();
+            assert_eq!(recovered, scalar);
+        }
+    }
+
+    #[test]
+    fn invalid_scalars() {
+        let order = vec![0xffu8; SCALAR_SIZE];
+        assert!(split_scalar(&order, Curve::Ed25519, 3, 2).is_err());
+        assert!(split_scalar(&order, Curve::Secp256k1, 3, 2).is_err());
+        assert!(split_scalar(&[0u8; SCALAR_SIZE], Curve::Secp256k1, 3, 2).is_err());
+        assert!(split_scalar(&[1u8; 16], Curve::Secp256k1, 3, 2).is_err());
+    }
 }
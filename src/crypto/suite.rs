@@ -0,0 +1,175 @@
+use std::fmt;
+use std::str::FromStr;
+
+use aes_gcm::aead::{generic_array::GenericArray, AeadInPlace, NewAead};
+use aes_gcm::{Aes128Gcm, Aes256Gcm};
+use aes_gcm_siv::Aes256GcmSiv;
+
+use crate::crypto::error::CipherError;
+
+/// The AEAD algorithms that can be used to encrypt a file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CipherSuite {
+    /// AES-256 in Galois/Counter Mode, the default.
+    Aes256Gcm,
+    /// AES-256 in GCM-SIV mode, which does not fail catastrophically
+    /// if a nonce is ever reused.
+    Aes256GcmSiv,
+    /// AES-128 in Galois/Counter Mode, for interoperability
+    /// with systems that do not support 256 bit keys.
+    Aes128Gcm,
+}
+
+// Every suite, in the order of their identifiers
+const SUITES: [CipherSuite; 3] = [
+    CipherSuite::Aes256Gcm,
+    CipherSuite::Aes256GcmSiv,
+    CipherSuite::Aes128Gcm,
+];
+
+impl CipherSuite {
+    /// Returns the identifier of this suite stored in the
+    /// header of encrypted files.
+    pub fn id(self) -> u8 {
+        match self {
+            CipherSuite::Aes256Gcm => 1,
+            CipherSuite::Aes256GcmSiv => 2,
+            CipherSuite::Aes128Gcm => 3,
+        }
+    }
+
+    /// Returns the suite with the given identifier.
+    ///
+    /// # Parameters
+    ///
+    /// - id: The identifier stored in the header of a file.
+    ///
+    /// # Errors
+    ///
+    /// A CipherError if there is no suite with the identifier.
+    pub fn from_id(id: u8) -> Result<Self, CipherError> {
+        SUITES
+            .iter()
+            .copied()
+            .find(|suite| suite.id() == id)
+            .ok_or_else(|| CipherError(format!("unknown cipher suite {}", id)))
+    }
+
+    /// Returns the name of this suite as given in the command line.
+    pub fn name(self) -> &'static str {
+        match self {
+            CipherSuite::Aes256Gcm => "aes-gcm",
+            CipherSuite::Aes256GcmSiv => "aes-gcm-siv",
+            CipherSuite::Aes128Gcm => "aes-128-gcm",
+        }
+    }
+
+    /// Returns the size in bytes of the keys of this suite.
+    pub fn key_size(self) -> usize {
+        match self {
+            CipherSuite::Aes128Gcm => 16,
+            _ => 32,
+        }
+    }
+}
+
+impl fmt::Display for CipherSuite {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for CipherSuite {
+    type Err = CipherError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        SUITES
+            .iter()
+            .copied()
+            .find(|suite| suite.name() == s)
+            .ok_or_else(|| CipherError(format!("unknown cipher '{}'", s)))
+    }
+}
+
+// An instance of the AEAD algorithm of a suite
+pub(crate) enum Aead {
+    Aes256Gcm(Aes256Gcm),
+    Aes256GcmSiv(Aes256GcmSiv),
+    Aes128Gcm(Aes128Gcm),
+}
+
+impl Aead {
+    // panics if the key does not have the key size of the suite
+    pub(crate) fn new(suite: CipherSuite, key: &[u8]) -> Self {
+        match suite {
+            CipherSuite::Aes256Gcm => Aead::Aes256Gcm(Aes256Gcm::new_varkey(key).unwrap()),
+            CipherSuite::Aes256GcmSiv => Aead::Aes256GcmSiv(Aes256GcmSiv::new_varkey(key).unwrap()),
+            CipherSuite::Aes128Gcm => Aead::Aes128Gcm(Aes128Gcm::new_varkey(key).unwrap()),
+        }
+    }
+
+    pub(crate) fn encrypt_in_place(
+        &self,
+        nonce: &[u8],
+        aad: &[u8],
+        buffer: &mut Vec<u8>,
+    ) -> Result<(), CipherError> {
+        let nonce = GenericArray::from_slice(nonce);
+        match self {
+            Aead::Aes256Gcm(aead) => aead.encrypt_in_place(nonce, aad, buffer),
+            Aead::Aes256GcmSiv(aead) => aead.encrypt_in_place(nonce, aad, buffer),
+            Aead::Aes128Gcm(aead) => aead.encrypt_in_place(nonce, aad, buffer),
+        }
+        .map_err(|_| CipherError("Error while encrypting".into()))
+    }
+
+    pub(crate) fn decrypt_in_place(
+        &self,
+        nonce: &[u8],
+        aad: &[u8],
+        buffer: &mut Vec<u8>,
+    ) -> Result<(), CipherError> {
+        let nonce = GenericArray::from_slice(nonce);
+        match self {
+            Aead::Aes256Gcm(aead) => aead.decrypt_in_place(nonce, aad, buffer),
+            Aead::Aes256GcmSiv(aead) => aead.decrypt_in_place(nonce, aad, buffer),
+            Aead::Aes128Gcm(aead) => aead.decrypt_in_place(nonce, aad, buffer),
+        }
+        .map_err(|_| CipherError("Error while decrypting".into()))
+    }
+}
+
+/////////////////////////////////
+// Unit testing of the module. //
+/////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn suite_ids() {
+        for &suite in &SUITES {
+            assert_eq!(CipherSuite::from_id(suite.id()).unwrap(), suite);
+            assert_eq!(suite.name().parse::<CipherSuite>().unwrap(), suite);
+        }
+        assert!(CipherSuite::from_id(0).is_err());
+        assert!("aes-cbc".parse::<CipherSuite>().is_err());
+    }
+
+    #[test]
+    fn suites_differ() {
+        let key = [0x21u8; 32];
+        let nonce = [0x01u8; 12];
+        let mut gcm = b"message".to_vec();
+        let mut siv = gcm.clone();
+        Aead::new(CipherSuite::Aes256Gcm, &key)
+            .encrypt_in_place(&nonce, b"", &mut gcm)
+            .unwrap();
+        let aead = Aead::new(CipherSuite::Aes256GcmSiv, &key);
+        aead.encrypt_in_place(&nonce, b"", &mut siv).unwrap();
+        assert_ne!(gcm, siv);
+        aead.decrypt_in_place(&nonce, b"", &mut siv).unwrap();
+        assert_eq!(&siv, b"message");
+    }
+}
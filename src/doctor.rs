@@ -0,0 +1,483 @@
+use std::collections::BTreeSet;
+use std::fs::{self, File};
+use std::io::{BufReader, Read, Seek};
+
+use crate::container::{Header, UnlockMethod, FORMAT_VERSION, MAGIC};
+use crate::crypto::chunked::{self, CHUNK_SIZE, SEALED_CHUNK_SIZE};
+use crate::crypto::{scheme, shamir};
+use crate::fragments::{self, Fragment};
+
+/// How serious a finding of the doctor is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// A check that passed.
+    Ok,
+    /// Something unusual that does not stop the recovery.
+    Warning,
+    /// Something that stops the recovery.
+    Problem,
+}
+
+impl Severity {
+    /// Returns the name of the severity, as shown in the reports.
+    pub fn name(self) -> &'static str {
+        match self {
+            Severity::Ok => "ok",
+            Severity::Warning => "warning",
+            Severity::Problem => "problem",
+        }
+    }
+}
+
+/// The result of a check of the doctor, with what to do about it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Finding {
+    pub severity: Severity,
+    pub message: String,
+    /// What the user can do to fix the problem, None for the
+    /// checks that passed.
+    pub suggestion: Option<String>,
+}
+
+impl Finding {
+    fn ok(message: String) -> Self {
+        Finding {
+            severity: Severity::Ok,
+            message,
+            suggestion: None,
+        }
+    }
+
+    fn warning(message: String, suggestion: &str) -> Self {
+        Finding {
+            severity: Severity::Warning,
+            message,
+            suggestion: Some(suggestion.into()),
+        }
+    }
+
+    fn problem(message: String, suggestion: &str) -> Self {
+        Finding {
+            severity: Severity::Problem,
+            message,
+            suggestion: Some(suggestion.into()),
+        }
+    }
+}
+
+/// Looks for the reasons an encrypted file cannot be recovered: a
+/// header that is damaged or of a newer version, a truncated file,
+/// and shares that are corrupt, repeated, of another field or of
+/// another file, or too few.
+///
+/// When no problem is found, the key is recovered from the shares
+/// to check that they open the file; the shares protected with a
+/// PIN are not checked.
+///
+/// # Parameters
+///
+/// - encrypted_file: The path of the encrypted file.
+/// - shares_file: The path of the shares file, or None to check
+///   only the encrypted file.
+///
+/// # Returns
+///
+/// The findings, in the order they were checked.
+pub fn examine(encrypted_file: &str, shares_file: Option<&str>) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    let header = match examine_encrypted_file(encrypted_file, &mut findings) {
+        Some(header) => header,
+        None => return findings,
+    };
+    if let Some(shares_file) = shares_file {
+        examine_shares(&header, shares_file, &mut findings);
+    }
+    findings
+}
+
+// checks the header and the size of the encrypted file, returning
+// the header if it can be read
+fn examine_encrypted_file(path: &str, findings: &mut Vec<Finding>) -> Option<Header> {
+    let mut reader = match File::open(path) {
+        Ok(file) => BufReader::new(file),
+        Err(e) => {
+            findings.push(Finding::problem(
+                format!("cannot read {}: {}", path, e),
+                "check the path and the permissions of the encrypted file",
+            ));
+            return None;
+        }
+    };
+    let mut start = [0u8; 9];
+    let read = reader.read(&mut start).unwrap_or(0);
+    let version = Some(start[8]).filter(|_| read == start.len() && start.starts_with(MAGIC));
+    if reader.rewind().is_err() {
+        findings.push(Finding::problem(
+            format!("cannot read {}", path),
+            "copy the encrypted file to a regular file and try again",
+        ));
+        return None;
+    }
+    if let Some(version) = version.filter(|&version| version > FORMAT_VERSION) {
+        findings.push(Finding::problem(
+            format!(
+                "{} has format version {}, this program reads version {}",
+                path, version, FORMAT_VERSION
+            ),
+            "decrypt it with a newer version of shared_secrets",
+        ));
+        return None;
+    }
+    // without header nothing else of the file can be checked
+    if version.is_none() {
+        findings.push(Finding::warning(
+            format!("{} has no header", path),
+            "files written before the header was introduced have none, run \
+             shared_secrets migrate to upgrade it; if this one was not, check \
+             that it is the encrypted file",
+        ));
+        return None;
+    }
+    let header = match Header::read_from(&mut reader) {
+        Ok(header) => header,
+        Err(e) => {
+            findings.push(Finding::problem(
+                format!("the header of {} cannot be read: {}", path, e),
+                "the file is truncated or damaged, copy it again from a backup",
+            ));
+            return None;
+        }
+    };
+    let mut summary = format!("{}: format version {}", path, header.version);
+    if let Some(program_version) = &header.program_version {
+        summary.push_str(&format!(", written by shared_secrets {}", program_version));
+    }
+    summary.push_str(&format!(", cipher {}", header.suite));
+    match (&header.policy, header.scheme) {
+        (Some(policy), _) => summary.push_str(&format!(", policy {}", policy)),
+        (None, Some((threshold, total))) => {
+            summary.push_str(&format!(", {} of {} shares", threshold, total))
+        }
+        (None, None) => (),
+    }
+    findings.push(Finding::ok(summary));
+    let content = match (reader.stream_position(), fs::metadata(path)) {
+        (Ok(position), Ok(metadata)) => metadata.len().saturating_sub(position),
+        _ => return Some(header),
+    };
+    match header.length.map(sealed_size) {
+        Some(expected) if content < expected => findings.push(Finding::problem(
+            format!(
+                "{} is truncated, it has {} bytes of content and should have {}",
+                path, content, expected
+            ),
+            "copy it again from a backup; an interrupted copy or download, or a full disk, \
+             leaves files truncated",
+        )),
+        Some(expected) if content > expected => findings.push(Finding::problem(
+            format!(
+                "{} has {} bytes more than it should",
+                path,
+                content - expected
+            ),
+            "something was appended to the file, copy it again from a backup",
+        )),
+        Some(_) => findings.push(Finding::ok(format!("{} has the expected size", path))),
+        None if content == 0 => findings.push(Finding::problem(
+            format!("{} has no content after the header", path),
+            "the file is truncated, copy it again from a backup",
+        )),
+        None => (),
+    }
+    let shares_slot = header
+        .key_slots
+        .iter()
+        .any(|slot| slot.method == UnlockMethod::Shares);
+    if !shares_slot {
+        findings.push(Finding::warning(
+            format!("the shares no longer unlock {}", path),
+            "decrypt it with the password or the key file, see the unlock methods in inspect",
+        ));
+    }
+    Some(header)
+}
+
+// the size of the content of a chunked file with a plaintext of the
+// given length, which has at least one chunk, even if empty
+fn sealed_size(length: u64) -> u64 {
+    let tag_size = (SEALED_CHUNK_SIZE - CHUNK_SIZE) as u64;
+    let chunks = length.div_ceil(CHUNK_SIZE as u64).max(1);
+    chunked::HEADER_SIZE as u64 + length + chunks * tag_size
+}
+
+// checks the shares: that they can be read, are distinct, of one
+// field, of the holders of the file and enough, and then that they
+// open the file
+fn examine_shares(header: &Header, path: &str, findings: &mut Vec<Finding>) {
+    let fragments = match fs::read_to_string(path) {
+        Ok(text) => match fragments::parse_fragments(&text) {
+            Ok(fragments) => fragments,
+            Err(e) => {
+                findings.push(Finding::problem(
+                    format!("{}: {}", path, e),
+                    "fix or remove that line; compare it with the paper backup or \
+                     ask its holder for it again",
+                ));
+                return;
+            }
+        },
+        Err(e) => {
+            findings.push(Finding::problem(
+                format!("cannot read {}: {}", path, e),
+                "check the path and the permissions of the shares file",
+            ));
+            return;
+        }
+    };
+    let problems = findings.len();
+    let fragments = distinct(fragments, findings);
+    findings.push(Finding::ok(format!(
+        "{} has {} distinct shares",
+        path,
+        fragments.len()
+    )));
+    if let Some(field) = examine_attributes(&fragments, findings) {
+        for fragment in &fragments {
+            examine_value(fragment, field.as_deref(), findings);
+        }
+    }
+    let unknown: Vec<&str> = fragments
+        .iter()
+        .filter_map(|fragment| fragment.holder.as_deref())
+        .filter(|holder| !header.holders.iter().any(|known| known == holder))
+        .collect();
+    if !header.holders.is_empty() && !unknown.is_empty() {
+        findings.push(Finding::problem(
+            format!(
+                "the shares of {} are not of this file, whose holders are {}",
+                unknown.join(", "),
+                header.holders.join(", ")
+            ),
+            "they belong to another encryption, use the shares file written with this file",
+        ));
+    }
+    if let (None, Some((threshold, _))) = (&header.policy, header.scheme) {
+        if fragments.len() < threshold {
+            let (_, missing) = crate::holders_report(&header.holders, &fragments);
+            let mut message = format!(
+                "found {} shares, at least {} are needed",
+                fragments.len(),
+                threshold
+            );
+            if !missing.is_empty() {
+                message.push_str(&format!(", missing the shares of {}", missing.join(", ")));
+            }
+            findings.push(Finding::problem(
+                message,
+                "gather the shares of more holders in the shares file",
+            ));
+        }
+    }
+    let protected: Vec<String> = fragments
+        .iter()
+        .filter(|fragment| fragment.pin.is_some())
+        .map(name)
+        .collect();
+    if !protected.is_empty() {
+        findings.push(Finding::warning(
+            format!("{} protected with a PIN", protected.join(", ")),
+            "d asks for the PIN of each of them, which doctor does not check",
+        ));
+    }
+    let found_problems = findings[problems..]
+        .iter()
+        .any(|finding| finding.severity == Severity::Problem);
+    if found_problems || !protected.is_empty() {
+        return;
+    }
+    match crate::shares_cipher(fragments, header, false)
+        .and_then(|cipher| crate::unwrap_data_key(header, &cipher))
+    {
+        Ok(_) => findings.push(Finding::ok("the shares open the file".into())),
+        Err(e) => findings.push(Finding::problem(
+            format!("the shares do not open the file: {}", e),
+            "some shares are mistyped or of another file; compare their fingerprints in \
+             inspect with the paper backups, or try d --cross-check",
+        )),
+    }
+}
+
+// removes the copies of a share and reports them, and the indices
+// shared by different shares
+fn distinct(fragments: Vec<Fragment>, findings: &mut Vec<Finding>) -> Vec<Fragment> {
+    let mut distinct: Vec<Fragment> = Vec::with_capacity(fragments.len());
+    let mut conflicts = BTreeSet::new();
+    for fragment in fragments {
+        match distinct
+            .iter()
+            .find(|other| other.share.0 == fragment.share.0)
+        {
+            Some(other) if other.share == fragment.share => findings.push(Finding::warning(
+                format!("{} is given more than once", name(&fragment)),
+                "remove the copies, a share only counts once",
+            )),
+            Some(_) => {
+                conflicts.insert(fragment.share.0.clone());
+            }
+            None => distinct.push(fragment),
+        }
+    }
+    for x in conflicts {
+        findings.push(Finding::problem(
+            format!("two different shares have the index {}", x),
+            "one of them is mistyped or of another file; keep only the one of this file",
+        ));
+    }
+    distinct
+}
+
+// checks that the shares have the same scheme, field and length, and
+// that this program knows the scheme, returning the field if so
+fn examine_attributes(
+    fragments: &[Fragment],
+    findings: &mut Vec<Finding>,
+) -> Option<Option<String>> {
+    let values = |attribute: fn(&Fragment) -> Option<String>| -> BTreeSet<_> {
+        fragments.iter().map(attribute).collect()
+    };
+    let attributes = [
+        ("schemes", values(|fragment| fragment.scheme.clone())),
+        ("fields", values(|fragment| fragment.field.clone())),
+        (
+            "key lengths",
+            values(|fragment| fragment.length.map(|length| length.to_string())),
+        ),
+    ];
+    let mut mixed = false;
+    for (name, values) in attributes {
+        if values.len() > 1 {
+            let values: Vec<_> = values
+                .into_iter()
+                .map(|value| value.unwrap_or_else(|| "default".into()))
+                .collect();
+            findings.push(Finding::problem(
+                format!("the shares have different {}: {}", name, values.join(", ")),
+                "the shares come from different encryptions; use only those of this file",
+            ));
+            mixed = true;
+        }
+    }
+    let first = fragments.first()?;
+    if mixed {
+        return None;
+    }
+    let prime = match first.field.as_deref().map(shamir::field_prime).transpose() {
+        Ok(prime) => prime,
+        Err(e) => {
+            findings.push(Finding::problem(
+                format!("the field of the shares is invalid: {}", e),
+                "the field of a share was mistyped, copy it from another share",
+            ));
+            return None;
+        }
+    };
+    if let Some(name) = &first.scheme {
+        if let Err(e) = scheme::from_name(name, prime.as_ref()) {
+            findings.push(Finding::problem(
+                format!("the shares use an unknown scheme: {}", e),
+                "they were written by a newer version of shared_secrets, decrypt with it",
+            ));
+            return None;
+        }
+        // the values of other schemes are checked when recovering
+        if name != scheme::DEFAULT_SCHEME {
+            return None;
+        }
+    }
+    Some(first.field.clone())
+}
+
+// checks that the value of a share of the default scheme is in its field
+fn examine_value(fragment: &Fragment, field: Option<&str>, findings: &mut Vec<Finding>) {
+    if fragment.pin.is_some() {
+        return;
+    }
+    let prime = field.map(|field| shamir::field_prime(field).unwrap());
+    if let Err(e) = shamir::share_to_bytes(&fragment.share, prime.as_ref()) {
+        findings.push(Finding::problem(
+            format!("{} is not a valid share: {}", name(fragment), e),
+            "it was mistyped or belongs to another field; compare it with its paper backup",
+        ));
+    }
+}
+
+// the name of a share in the findings
+fn name(fragment: &Fragment) -> String {
+    match &fragment.holder {
+        Some(holder) => format!("the share of {}", holder),
+        None => format!("share {}", fragment.share.0),
+    }
+}
+
+/////////////////////////////////
+// Unit testing of the module. //
+/////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sealed_sizes() {
+        let header = chunked::HEADER_SIZE as u64;
+        assert_eq!(sealed_size(0), header + 16);
+        assert_eq!(sealed_size(10), header + 10 + 16);
+        assert_eq!(
+            sealed_size(CHUNK_SIZE as u64),
+            header + CHUNK_SIZE as u64 + 16
+        );
+        assert_eq!(
+            sealed_size(CHUNK_SIZE as u64 + 1),
+            header + CHUNK_SIZE as u64 + 1 + 32
+        );
+    }
+
+    #[test]
+    fn distinct_shares() {
+        let mut findings = Vec::new();
+        let fragments =
+            fragments::parse_fragments("1:2a:holder=alice\n1:2a:holder=alice\n2:3b\n2:3c\n")
+                .unwrap();
+        let fragments = distinct(fragments, &mut findings);
+        assert_eq!(fragments.len(), 2);
+        assert_eq!(findings.len(), 2);
+        assert_eq!(findings[0].severity, Severity::Warning);
+        assert_eq!(
+            findings[0].message,
+            "the share of alice is given more than once"
+        );
+        assert_eq!(findings[1].severity, Severity::Problem);
+        assert_eq!(findings[1].message, "two different shares have the index 2");
+    }
+
+    #[test]
+    fn mixed_fields() {
+        let mut findings = Vec::new();
+        let fragments = fragments::parse_fragments("1:2a:field=127\n2:3b:field=257\n").unwrap();
+        assert_eq!(examine_attributes(&fragments, &mut findings), None);
+        assert_eq!(
+            findings[0].message,
+            "the shares have different fields: 127, 257"
+        );
+        findings.clear();
+        let fragments = fragments::parse_fragments("1:2a:field=257\n2:3b:field=257\n").unwrap();
+        assert_eq!(
+            examine_attributes(&fragments, &mut findings),
+            Some(Some("257".into()))
+        );
+        assert!(findings.is_empty());
+        let fragment = Fragment::parse("1:ffffffffffffffffffffffffffffffffffff:field=127").unwrap();
+        examine_value(&fragment, Some("127"), &mut findings);
+        assert_eq!(findings[0].severity, Severity::Problem);
+    }
+}
@@ -0,0 +1,166 @@
+// Characters of the standard base64 alphabet, see RFC 4648
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Encodes bytes in lowercase hexadecimal, two digits for each byte.
+///
+/// # Parameters
+///
+/// - bytes: The bytes to encode.
+pub fn to_hex(bytes: &[u8]) -> String {
+    const DIGITS: &[u8; 16] = b"0123456789abcdef";
+    let mut hex = String::with_capacity(2 * bytes.len());
+    for byte in bytes {
+        hex.push(DIGITS[(byte >> 4) as usize] as char);
+        hex.push(DIGITS[(byte & 0xf) as usize] as char);
+    }
+    hex
+}
+
+/// Decodes hexadecimal, as written by to_hex.
+///
+/// # Parameters
+///
+/// - hex: Two digits for each byte, in upper or lower case and
+///   without any sign, prefix or whitespace.
+///
+/// # Returns
+///
+/// The bytes, or None if the text has an odd number of characters
+/// or a character that is not a hexadecimal digit.
+pub fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    let hex = hex.as_bytes();
+    if hex.len() % 2 != 0 {
+        return None;
+    }
+    hex.chunks(2)
+        .map(|pair| Some(hex_digit(pair[0])? << 4 | hex_digit(pair[1])?))
+        .collect()
+}
+
+// the value of a hexadecimal digit, unlike from_str_radix it does
+// not accept a sign
+fn hex_digit(c: u8) -> Option<u8> {
+    match c {
+        b'0'..=b'9' => Some(c - b'0'),
+        b'a'..=b'f' => Some(c - b'a' + 10),
+        b'A'..=b'F' => Some(c - b'A' + 10),
+        _ => None,
+    }
+}
+
+/// Encodes bytes in standard base64 with padding.
+///
+/// # Parameters
+///
+/// - bytes: The bytes to encode.
+pub fn to_base64(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let mut group = [0u8; 3];
+        group[..chunk.len()].copy_from_slice(chunk);
+        let n = (group[0] as u32) << 16 | (group[1] as u32) << 8 | group[2] as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(BASE64_ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+/// Decodes standard base64, as written by to_base64 or without its
+/// padding.
+///
+/// # Parameters
+///
+/// - text: The encoded bytes, without whitespace.
+///
+/// # Returns
+///
+/// The bytes, or None if the text has a character out of the
+/// alphabet, the wrong padding, or bits after the last byte that
+/// are not zero, so every sequence of bytes has a single encoding.
+pub fn from_base64(text: &str) -> Option<Vec<u8>> {
+    let text = text.as_bytes();
+    let data = match text.iter().position(|&c| c == b'=') {
+        // the padding completes the last group of four characters
+        Some(padding)
+            if text[padding..].iter().all(|&c| c == b'=')
+                && text.len() % 4 == 0
+                && text.len() - padding <= 2 =>
+        {
+            &text[..padding]
+        }
+        Some(_) => return None,
+        None => text,
+    };
+    if data.len() % 4 == 1 {
+        return None;
+    }
+    let mut decoded = Vec::with_capacity(data.len() * 3 / 4);
+    for chunk in data.chunks(4) {
+        let mut n = 0u32;
+        for (i, c) in chunk.iter().enumerate() {
+            let value = BASE64_ALPHABET.iter().position(|a| a == c)? as u32;
+            n |= value << (18 - 6 * i);
+        }
+        let bytes = n.to_be_bytes();
+        if bytes[chunk.len()..].iter().any(|&b| b != 0) {
+            return None;
+        }
+        decoded.extend_from_slice(&bytes[1..chunk.len()]);
+    }
+    Some(decoded)
+}
+
+/////////////////////////////////
+// Unit testing of the module. //
+/////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hex() {
+        assert_eq!(to_hex(&[0x00, 0xab, 0x10]), "00ab10");
+        assert_eq!(from_hex("00ab10").unwrap(), vec![0x00, 0xab, 0x10]);
+        assert_eq!(from_hex("00AB10").unwrap(), vec![0x00, 0xab, 0x10]);
+        assert_eq!(from_hex("").unwrap(), Vec::<u8>::new());
+        assert!(from_hex("0").is_none());
+        assert!(from_hex("zz").is_none());
+        assert!(from_hex("+f").is_none());
+        assert!(from_hex("-1").is_none());
+        assert!(from_hex(" f").is_none());
+        assert!(from_hex("é0").is_none());
+    }
+
+    #[test]
+    fn base64() {
+        // test vectors of RFC 4648
+        let vectors = [
+            ("", ""),
+            ("f", "Zg=="),
+            ("fo", "Zm8="),
+            ("foo", "Zm9v"),
+            ("foob", "Zm9vYg=="),
+            ("fooba", "Zm9vYmE="),
+            ("foobar", "Zm9vYmFy"),
+        ];
+        for (input, output) in vectors.iter() {
+            assert_eq!(to_base64(input.as_bytes()), *output);
+            assert_eq!(from_base64(output).unwrap(), input.as_bytes());
+            let unpadded = output.trim_end_matches('=');
+            assert_eq!(from_base64(unpadded).unwrap(), input.as_bytes());
+        }
+        assert!(from_base64("Zm9=v").is_none());
+        assert!(from_base64("Z").is_none());
+        assert!(from_base64("Zg=").is_none());
+        assert!(from_base64("Zm9v====").is_none());
+        assert!(from_base64("Zh==").is_none());
+        assert!(from_base64("Zm9v\n").is_none());
+    }
+}
@@ -0,0 +1,188 @@
+use std::error::Error;
+
+use zeroize::Zeroize;
+
+use crate::error::{ArgumentError, CorruptFileError};
+use crate::report::Json;
+
+/// Parses a file of named secrets: a JSON object, whose values are
+/// kept as text, or a .env file, with a NAME=value line for each
+/// secret.
+///
+/// In a .env file, blank lines and lines starting with '#' are
+/// skipped, an export before the name is ignored, a value between
+/// double quotes can have the escapes \n, \" and \\, a value between
+/// single quotes is taken as is, and an unquoted value ends before
+/// a " #" comment.
+///
+/// # Parameters
+///
+/// - text: The content of the file.
+///
+/// # Returns
+///
+/// The name and the value of each secret, in the order of the file.
+///
+/// # Errors
+///
+/// A CorruptFileError if the text is not a JSON object or a .env
+/// file, or if a name is repeated.
+pub fn parse(text: &str) -> Result<Vec<(String, String)>, CorruptFileError> {
+    let text = text.trim_start_matches('\u{feff}');
+    let entries = if text.trim_start().starts_with('{') {
+        parse_json(text)?
+    } else {
+        parse_env(text)?
+    };
+    for (i, (name, _)) in entries.iter().enumerate() {
+        if entries[..i].iter().any(|(other, _)| other == name) {
+            return Err(CorruptFileError(format!("the secret {} is repeated", name)));
+        }
+    }
+    Ok(entries)
+}
+
+/// Returns the value of a secret of a file of named secrets, see
+/// parse. The values of the other secrets are erased from memory.
+///
+/// # Parameters
+///
+/// - text: The content of the file.
+/// - name: The name of the secret.
+///
+/// # Errors
+///
+/// A CorruptFileError if the text is not a file of named secrets,
+/// or an ArgumentError if it has no secret with that name, which
+/// lists the names it has.
+pub fn get(text: &str, name: &str) -> Result<String, Box<dyn Error>> {
+    let mut entries = parse(text)?;
+    let value = entries
+        .iter()
+        .position(|(other, _)| other == name)
+        .map(|i| entries.swap_remove(i).1);
+    let names: Vec<String> = entries.iter().map(|(name, _)| name.clone()).collect();
+    for (_, value) in &mut entries {
+        value.zeroize();
+    }
+    value.ok_or_else(|| {
+        Box::new(ArgumentError(format!(
+            "there is no secret {}, the secrets are: {}",
+            name,
+            names.join(", ")
+        ))) as _
+    })
+}
+
+// the fields of a JSON object, with the text of the values that are
+// not strings
+fn parse_json(text: &str) -> Result<Vec<(String, String)>, CorruptFileError> {
+    match Json::parse(text)? {
+        Json::Object(fields) => Ok(fields
+            .into_iter()
+            .map(|(name, value)| {
+                let value = match value {
+                    Json::String(value) => value,
+                    Json::Null => String::new(),
+                    value => value.to_string(),
+                };
+                (name, value)
+            })
+            .collect()),
+        _ => Err(CorruptFileError(
+            "a JSON file of secrets must be an object".into(),
+        )),
+    }
+}
+
+fn parse_env(text: &str) -> Result<Vec<(String, String)>, CorruptFileError> {
+    let mut entries = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let invalid = || CorruptFileError(format!("line {} is not NAME=value", number + 1));
+        let line = line.strip_prefix("export ").unwrap_or(line);
+        let (name, value) = line.split_once('=').ok_or_else(invalid)?;
+        let name = name.trim();
+        if name.is_empty() || name.contains(char::is_whitespace) {
+            return Err(invalid());
+        }
+        entries.push((
+            name.to_string(),
+            env_value(value.trim()).ok_or_else(invalid)?,
+        ));
+    }
+    Ok(entries)
+}
+
+// the value of a .env line, None if a quote is not closed
+fn env_value(value: &str) -> Option<String> {
+    if let Some(quoted) = value.strip_prefix('\'') {
+        let end = quoted.find('\'')?;
+        return Some(quoted[..end].to_string());
+    }
+    if let Some(quoted) = value.strip_prefix('"') {
+        let mut unescaped = String::new();
+        let mut chars = quoted.chars();
+        loop {
+            match chars.next()? {
+                '"' => return Some(unescaped),
+                '\\' => match chars.next()? {
+                    'n' => unescaped.push('\n'),
+                    c => unescaped.push(c),
+                },
+                c => unescaped.push(c),
+            }
+        }
+    }
+    let value = match value.find(" #") {
+        Some(comment) => &value[..comment],
+        None => value,
+    };
+    Some(value.trim_end().to_string())
+}
+
+/////////////////////////////////
+// Unit testing of the module. //
+/////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn env_files() {
+        let text = "# database\nexport DB_USER=admin\nDB_PASSWORD=\"p@ss \\\"word\\\"\\n\"\n\
+                    API_KEY='a#b\\n' \nTOKEN=abc # the token\nEMPTY=\n";
+        let entries = parse(text).unwrap();
+        let names: Vec<&str> = entries.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(
+            names,
+            ["DB_USER", "DB_PASSWORD", "API_KEY", "TOKEN", "EMPTY"]
+        );
+        assert_eq!(entries[1].1, "p@ss \"word\"\n");
+        assert_eq!(entries[2].1, "a#b\\n");
+        assert_eq!(entries[3].1, "abc");
+        assert_eq!(entries[4].1, "");
+        assert_eq!(get(text, "DB_USER").unwrap(), "admin");
+        let missing = get(text, "OTHER").unwrap_err().to_string();
+        assert!(missing.contains("the secrets are: DB_USER"));
+        assert!(!missing.contains("admin"));
+        assert!(parse("NAME\n").is_err());
+        assert!(parse("NAME=\"open\n").is_err());
+        assert!(parse("A=1\nA=2\n").is_err());
+    }
+
+    #[test]
+    fn json_files() {
+        let text = "{\"password\": \"secret\", \"port\": 5432, \"tls\": true, \"none\": null}";
+        assert_eq!(get(text, "password").unwrap(), "secret");
+        assert_eq!(get(text, "port").unwrap(), "5432");
+        assert_eq!(get(text, "tls").unwrap(), "true");
+        assert_eq!(get(text, "none").unwrap(), "");
+        assert!(parse("[1, 2]").is_err());
+        assert!(parse("{\"a\": ").is_err());
+    }
+}
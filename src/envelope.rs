@@ -0,0 +1,177 @@
+use std::io::{self, Write};
+
+use crate::fragments::{self, Format, Fragment};
+
+/// Name of the file of an envelope with the share of its holder.
+pub const SHARE_FILE: &str = "share.txt";
+
+/// Name of the file of an envelope with its cover sheet.
+pub const COVER_FILE: &str = "cover.txt";
+
+/// The contents of the envelope of a holder: their share and a
+/// cover sheet that tells what it is and how to use it.
+pub struct Envelope<'a> {
+    /// The fragment of the holder.
+    pub fragment: &'a Fragment,
+    /// Name of the encrypted file the share unlocks.
+    pub file_name: &'a str,
+    /// Minimum number of shares needed to decrypt the file.
+    pub threshold: usize,
+    /// Total number of shares generated.
+    pub total: usize,
+    /// Fingerprint of the whole set of shares, see
+    /// fragments::set_fingerprint.
+    pub set_fingerprint: &'a str,
+    /// Who to contact, and when, if the share is lost or needed.
+    pub contact: Option<&'a str>,
+}
+
+impl Envelope<'_> {
+    /// Returns the name of the directory of this envelope, the index
+    /// of its share followed by its holder, if it has one, with every
+    /// character that is not a letter, a digit, '-' or '_' replaced
+    /// by '_', so it is a valid name in any file system.
+    pub fn directory(&self) -> String {
+        match &self.fragment.holder {
+            Some(holder) => {
+                let holder: String = holder
+                    .chars()
+                    .map(|c| {
+                        if c.is_alphanumeric() || c == '-' || c == '_' {
+                            c
+                        } else {
+                            '_'
+                        }
+                    })
+                    .collect();
+                format!("{}-{}", self.fragment.share.0, holder)
+            }
+            None => self.fragment.share.0.to_string(),
+        }
+    }
+
+    /// Writes the share of this envelope as a shares file with a
+    /// single share.
+    ///
+    /// # Parameters
+    ///
+    /// - writer: The writer where the file is written.
+    ///
+    /// # Errors
+    ///
+    /// This method returns an error if an error occurs while writing.
+    pub fn write_share(&self, writer: &mut impl Write) -> io::Result<()> {
+        fragments::write_fragments(std::slice::from_ref(self.fragment), Format::Lines, writer)
+    }
+
+    /// Writes the cover sheet of this envelope, a text with the
+    /// holder, the scheme, the fingerprints of the share and of the
+    /// set, the instructions to recover the file and the contact
+    /// policy.
+    ///
+    /// # Parameters
+    ///
+    /// - writer: The writer where the cover sheet is written.
+    ///
+    /// # Errors
+    ///
+    /// This method returns an error if an error occurs while writing.
+    pub fn write_cover(&self, writer: &mut impl Write) -> io::Result<()> {
+        writeln!(writer, "Shared Secrets - key share")?;
+        writeln!(writer)?;
+        if let Some(holder) = &self.fragment.holder {
+            writeln!(writer, "Holder: {}", holder)?;
+        }
+        writeln!(writer, "Share: {}", self.fragment.share.0)?;
+        writeln!(writer, "Encrypted file: {}", self.file_name)?;
+        writeln!(
+            writer,
+            "Scheme: {} of {} shares are needed to decrypt",
+            self.threshold, self.total
+        )?;
+        writeln!(writer, "Share fingerprint: {}", self.fragment.fingerprint())?;
+        writeln!(writer, "Share set fingerprint: {}", self.set_fingerprint)?;
+        writeln!(writer)?;
+        writeln!(
+            writer,
+            "The share is in {}. To confirm you hold the right share, read",
+            SHARE_FILE
+        )?;
+        writeln!(
+            writer,
+            "its fingerprint, or the one of the set, to whoever keeps the"
+        )?;
+        writeln!(writer, "shares file, who checks it with:")?;
+        writeln!(
+            writer,
+            "    shared_secrets inspect {} <shares file> --fingerprint <fingerprint>",
+            self.file_name
+        )?;
+        writeln!(writer)?;
+        writeln!(
+            writer,
+            "To recover the file, gather at least {} shares into a shares",
+            self.threshold
+        )?;
+        writeln!(writer, "file, one share per line, and run:")?;
+        writeln!(
+            writer,
+            "    shared_secrets d {} <shares file>",
+            self.file_name
+        )?;
+        if let Some(contact) = self.contact {
+            writeln!(writer)?;
+            writeln!(writer, "Contact: {}", contact)?;
+        }
+        writeln!(writer)?;
+        writeln!(writer, "Keep this envelope sealed and in a safe place.")
+    }
+}
+
+/////////////////////////////////
+// Unit testing of the module. //
+/////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn envelope() {
+        let fragment = Fragment::parse("2:3b:holder=Ana María/2").unwrap();
+        let mut envelope = Envelope {
+            fragment: &fragment,
+            file_name: "secret.aes",
+            threshold: 2,
+            total: 3,
+            set_fingerprint: "0a1b2c3d",
+            contact: Some("call the notary"),
+        };
+        assert_eq!(envelope.directory(), "2-Ana_María_2");
+        let mut share = Vec::new();
+        envelope.write_share(&mut share).unwrap();
+        let share = String::from_utf8(share).unwrap();
+        assert_eq!(
+            fragments::parse_fragments(&share).unwrap(),
+            std::slice::from_ref(&fragment)
+        );
+        let mut cover = Vec::new();
+        envelope.write_cover(&mut cover).unwrap();
+        let cover = String::from_utf8(cover).unwrap();
+        assert!(cover.contains("Holder: Ana María/2\n"));
+        assert!(cover.contains("2 of 3 shares"));
+        assert!(cover.contains(&format!("Share fingerprint: {}\n", fragment.fingerprint())));
+        assert!(cover.contains("Share set fingerprint: 0a1b2c3d\n"));
+        assert!(cover.contains("Contact: call the notary\n"));
+
+        let unnamed = Fragment::parse("3:4c").unwrap();
+        envelope.fragment = &unnamed;
+        envelope.contact = None;
+        assert_eq!(envelope.directory(), "3");
+        let mut cover = Vec::new();
+        envelope.write_cover(&mut cover).unwrap();
+        let cover = String::from_utf8(cover).unwrap();
+        assert!(!cover.contains("Holder:"));
+        assert!(!cover.contains("Contact:"));
+    }
+}
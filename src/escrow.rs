@@ -0,0 +1,238 @@
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+
+use zeroize::Zeroize;
+
+use crate::container::{RecoverySlot, PUBLIC_KEY_SIZE};
+use crate::crypto::error::CipherError;
+use crate::crypto::{Cipher, CipherSuite, SecretKey};
+use crate::encoding::{from_hex, to_hex};
+use crate::error::{ArgumentError, CorruptFileError};
+use crate::math::curve25519::{x25519, BASEPOINT};
+
+/// Size in bytes of the key of a recovery agent.
+pub const KEY_SIZE: usize = 32;
+
+// Context of the key that wraps the data key for a recovery agent
+const WRAP_CONTEXT: &str = "shared_secrets recovery agent wrapping key v1";
+
+/// Generates the X25519 key of a recovery agent, such as the IT
+/// department of a company, and saves it in a new file readable
+/// only by its owner, as a line of hexadecimal.
+///
+/// The files encrypted with the public key of the agent, see wrap,
+/// can be decrypted with the key alone, without the shares.
+///
+/// # Parameters
+///
+/// - path: The path of the key file, which must not exist.
+///
+/// # Returns
+///
+/// The public key in hexadecimal, which is given when encrypting.
+///
+/// # Errors
+///
+/// An io::Error if the file exists or cannot be written, or a
+/// CipherError if the key cannot be generated.
+pub fn generate_key(path: &str) -> Result<String, Box<dyn Error>> {
+    let key = SecretKey::generate(KEY_SIZE)?;
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let mut file = options.open(path)?;
+    let mut hex = to_hex(key.expose());
+    let written = writeln!(file, "{}", hex).and_then(|_| file.sync_all());
+    hex.zeroize();
+    written?;
+    Ok(to_hex(&public_key(&key)))
+}
+
+/// Reads the key of a recovery agent saved by generate_key.
+///
+/// # Parameters
+///
+/// - path: The path of the key file.
+///
+/// # Errors
+///
+/// An io::Error if the file cannot be read, or a CorruptFileError
+/// if it does not hold a key.
+pub fn read_key(path: &str) -> Result<SecretKey, Box<dyn Error>> {
+    let mut content = fs::read_to_string(path)?;
+    let key = from_hex(content.trim()).filter(|key| key.len() == KEY_SIZE);
+    content.zeroize();
+    match key {
+        Some(key) => Ok(SecretKey::new(key)),
+        None => Err(Box::new(CorruptFileError(format!(
+            "{} is not the key of a recovery agent",
+            path
+        )))),
+    }
+}
+
+/// Returns the X25519 public key of the key of a recovery agent.
+///
+/// # Parameters
+///
+/// - key: The key of the agent, of KEY_SIZE bytes.
+pub fn public_key(key: &SecretKey) -> [u8; PUBLIC_KEY_SIZE] {
+    let mut secret = scalar(key);
+    let public_key = x25519(&secret, &BASEPOINT);
+    secret.zeroize();
+    public_key
+}
+
+/// Parses the public key of a recovery agent.
+///
+/// # Parameters
+///
+/// - hex: The public key in hexadecimal, as generate_key returns it.
+///
+/// # Errors
+///
+/// An ArgumentError if the text is not a public key.
+pub fn parse_public_key(hex: &str) -> Result<[u8; PUBLIC_KEY_SIZE], ArgumentError> {
+    from_hex(hex.trim())
+        .and_then(|key| <[u8; PUBLIC_KEY_SIZE]>::try_from(key).ok())
+        .ok_or_else(|| {
+            ArgumentError(format!(
+                "'{}' is not the public key of a recovery agent",
+                hex
+            ))
+        })
+}
+
+/// Wraps the data key of a file for a recovery agent.
+///
+/// A random ephemeral key agrees a secret with the public key of
+/// the agent by X25519, and the data key is wrapped with a key
+/// derived from it with BLAKE3, authenticated with the associated
+/// data of the file as the key slots are.
+///
+/// # Parameters
+///
+/// - data_key: The cipher of the content of the file.
+/// - public_key: The public key of the agent.
+/// - aad: The associated data of the header of the file.
+/// - suite: The cipher suite of the file.
+///
+/// # Errors
+///
+/// A CipherError if the random ephemeral key cannot be generated or
+/// the data key cannot be wrapped.
+pub fn wrap(
+    data_key: &Cipher,
+    public_key: &[u8; PUBLIC_KEY_SIZE],
+    aad: &[u8],
+    suite: CipherSuite,
+) -> Result<RecoverySlot, CipherError> {
+    let ephemeral = SecretKey::generate(KEY_SIZE)?;
+    let mut secret = scalar(&ephemeral);
+    let ephemeral_key = x25519(&secret, &BASEPOINT);
+    let mut shared = x25519(&secret, public_key);
+    secret.zeroize();
+    let cipher = wrapping_cipher(&shared, &ephemeral_key, public_key, suite);
+    shared.zeroize();
+    Ok(RecoverySlot {
+        public_key: *public_key,
+        ephemeral_key,
+        wrapped_key: cipher.wrap_key(data_key, aad)?,
+    })
+}
+
+/// Unwraps the data key of a file with the key of one of its
+/// recovery agents.
+///
+/// # Parameters
+///
+/// - slots: The recovery slots of the file.
+/// - key: The key of the agent.
+/// - aad: The associated data of the header of the file.
+/// - suite: The cipher suite of the file.
+///
+/// # Errors
+///
+/// An ArgumentError if the agent is not a recovery agent of the
+/// file, or a CipherError if its slot does not unwrap.
+pub fn unwrap(
+    slots: &[RecoverySlot],
+    key: &SecretKey,
+    aad: &[u8],
+    suite: CipherSuite,
+) -> Result<Cipher, Box<dyn Error>> {
+    let public_key = public_key(key);
+    let slot = slots
+        .iter()
+        .find(|slot| slot.public_key == public_key)
+        .ok_or_else(|| {
+            ArgumentError(format!(
+                "{} is not a recovery agent of the file",
+                to_hex(&public_key)
+            ))
+        })?;
+    let mut secret = scalar(key);
+    let mut shared = x25519(&secret, &slot.ephemeral_key);
+    secret.zeroize();
+    let cipher = wrapping_cipher(&shared, &slot.ephemeral_key, &public_key, suite);
+    shared.zeroize();
+    Ok(cipher.unwrap_key(&slot.wrapped_key, aad)?)
+}
+
+// the key that wraps the data key, bound to both public keys
+fn wrapping_cipher(
+    shared: &[u8; 32],
+    ephemeral_key: &[u8; PUBLIC_KEY_SIZE],
+    public_key: &[u8; PUBLIC_KEY_SIZE],
+    suite: CipherSuite,
+) -> Cipher {
+    let mut material = [&shared[..], ephemeral_key, public_key].concat();
+    let mut key = blake3::derive_key(WRAP_CONTEXT, &material);
+    material.zeroize();
+    let cipher = Cipher::with_key(SecretKey::new(key[..suite.key_size()].to_vec()), suite);
+    key.zeroize();
+    cipher
+}
+
+// the scalar of an X25519 key
+fn scalar(key: &SecretKey) -> [u8; 32] {
+    let mut scalar = [0u8; 32];
+    scalar.copy_from_slice(&key.expose()[..32]);
+    scalar
+}
+
+/////////////////////////////////
+// Unit testing of the module. //
+/////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wrap_for_agent() {
+        let suite = CipherSuite::Aes256Gcm;
+        let agent = SecretKey::new(vec![7u8; KEY_SIZE]);
+        let other = SecretKey::new(vec![8u8; KEY_SIZE]);
+        let public = public_key(&agent);
+        assert_eq!(parse_public_key(&to_hex(&public)).unwrap(), public);
+        assert!(parse_public_key("00").is_err());
+
+        let data_key = Cipher::random(suite).unwrap();
+        let slot = wrap(&data_key, &public, b"header", suite).unwrap();
+        assert_ne!(slot.ephemeral_key, public);
+        let slots = [
+            wrap(&data_key, &public_key(&other), b"header", suite).unwrap(),
+            slot,
+        ];
+        let unwrapped = unwrap(&slots, &agent, b"header", suite).unwrap();
+        assert_eq!(unwrapped.key().expose(), data_key.key().expose());
+        // another header, or a key that is not an agent of the file
+        assert!(unwrap(&slots, &agent, b"other", suite).is_err());
+        let stranger = SecretKey::new(vec![9u8; KEY_SIZE]);
+        assert!(unwrap(&slots, &stranger, b"header", suite).is_err());
+    }
+}
@@ -0,0 +1,110 @@
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::str::FromStr;
+
+use crate::encoding::{to_base64, to_hex};
+use crate::error::ArgumentError;
+
+/// Format in which a recovered key is written for other programs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyFormat {
+    /// Lowercase hexadecimal, followed by a newline.
+    Hex,
+    /// Standard base64 with padding, followed by a newline.
+    Base64,
+    /// The bytes of the key, as they are.
+    Raw,
+}
+
+impl FromStr for KeyFormat {
+    type Err = ArgumentError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "hex" => Ok(KeyFormat::Hex),
+            "base64" => Ok(KeyFormat::Base64),
+            "raw" => Ok(KeyFormat::Raw),
+            _ => Err(ArgumentError(format!("unknown key format '{}'", s))),
+        }
+    }
+}
+
+/// Encodes a key in a format.
+///
+/// # Parameters
+///
+/// - key: The bytes of the key.
+/// - format: The format to write the key in.
+///
+/// # Returns
+///
+/// The bytes to write, which the caller should zeroize.
+pub fn encode(key: &[u8], format: KeyFormat) -> Vec<u8> {
+    match format {
+        KeyFormat::Hex => {
+            let mut hex = to_hex(key);
+            hex.push('\n');
+            hex.into_bytes()
+        }
+        KeyFormat::Base64 => {
+            let mut base64 = to_base64(key);
+            base64.push('\n');
+            base64.into_bytes()
+        }
+        KeyFormat::Raw => key.to_vec(),
+    }
+}
+
+/// Writes bytes to an open file descriptor of the process, such as
+/// one a shell redirects with `3>key.bin` for another program.
+///
+/// # Parameters
+///
+/// - bytes: The bytes to write.
+/// - fd: The file descriptor, 1 for the standard output and 2 for the standard error.
+///
+/// # Errors
+///
+/// An error if the file descriptor is not open for writing or, on
+/// systems without `/dev/fd`, if it is not 1 or 2.
+pub fn write_to_fd(bytes: &[u8], fd: u32) -> io::Result<()> {
+    match fd {
+        1 => {
+            let mut stdout = io::stdout();
+            stdout.write_all(bytes)?;
+            stdout.flush()
+        }
+        2 => io::stderr().write_all(bytes),
+        _ if cfg!(unix) => {
+            // the descriptor is opened again through its name, so
+            // it is not closed when the file is dropped
+            let mut file = OpenOptions::new()
+                .write(true)
+                .open(format!("/dev/fd/{}", fd))?;
+            file.write_all(bytes)?;
+            file.flush()
+        }
+        _ => Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "only the file descriptors 1 and 2 can be written on this system",
+        )),
+    }
+}
+
+/////////////////////////////////
+// Unit testing of the module. //
+/////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats() {
+        assert_eq!(encode(&[0x00, 0xab, 0x10], KeyFormat::Hex), b"00ab10\n");
+        assert_eq!(encode(&[0x00, 0xab], KeyFormat::Raw), vec![0x00, 0xab]);
+        assert_eq!("base64".parse::<KeyFormat>().unwrap(), KeyFormat::Base64);
+        assert!("pem".parse::<KeyFormat>().is_err());
+        assert_eq!(encode(b"fo", KeyFormat::Base64), b"Zm8=\n");
+    }
+}
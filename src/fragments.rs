@@ -0,0 +1,969 @@
+use std::cmp::Ordering;
+use std::error::Error;
+use std::fs;
+use std::io::Write;
+use std::str::FromStr;
+
+use sha2::{Digest, Sha256};
+
+use crate::bundle;
+use crate::crypto::Share;
+use crate::encoding::to_hex;
+use crate::error::{ArgumentError, CorruptFileError};
+use crate::math::prime;
+
+/// First line of an armored fragment.
+pub const ARMOR_BEGIN: &str = "-----BEGIN SHARED SECRETS SHARE-----";
+
+/// Last line of an armored fragment.
+pub const ARMOR_END: &str = "-----END SHARED SECRETS SHARE-----";
+
+/// Version of the shares file format written by this program.
+///
+/// Version 0 files, written before the version was recorded, have
+/// no version line; since version 1 the first line of every shares
+/// file is the version line, see version_line, a comment to the
+/// programs that read version 0.
+pub const FORMAT_VERSION: u8 = 1;
+
+// Start of the line with the version of a shares file
+const VERSION_PREFIX: &str = "# shared_secrets shares format ";
+
+// Separator of the version of the format and the version of the
+// program in the version line
+const WRITTEN_BY: &str = ", written by shared_secrets ";
+
+// Character that starts the comments of a shares file
+const COMMENT: char = '#';
+
+// Byte order mark that some editors write at the start of a file
+const BYTE_ORDER_MARK: char = '\u{feff}';
+
+// Number of characters of a line shown in the errors of a shares file
+const SNIPPET_LEN: usize = 24;
+
+// Maximum length of the lines inside an armored fragment
+const ARMOR_LINE_LEN: usize = 64;
+
+// Columns of a table of shares, in the order they are written
+const COLUMNS: [&str; 10] = [
+    "index", "x", "y", "checksum", "holder", "scheme", "field", "len", "mac", "pin",
+];
+
+// Number of bytes of the checksum of a share in a table
+const CHECKSUM_SIZE: usize = 4;
+
+/// Format of a shares file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// One fragment per line, see Fragment::parse.
+    Lines,
+    /// A table of comma separated values with a header row.
+    Csv,
+    /// A table of tab separated values with a header row.
+    Tsv,
+}
+
+impl Format {
+    /// Returns the extension of the shares files of this format.
+    pub fn extension(self) -> &'static str {
+        match self {
+            Format::Lines => "frg",
+            Format::Csv => "csv",
+            Format::Tsv => "tsv",
+        }
+    }
+
+    /// Returns the format of the text of a shares file.
+    ///
+    /// The tables are told apart by their header, whose first
+    /// column is the index; any other text is read as lines.
+    ///
+    /// # Parameters
+    ///
+    /// - text: The content of the shares file.
+    pub fn detect(text: &str) -> Format {
+        let header = text
+            .trim_start_matches(BYTE_ORDER_MARK)
+            .lines()
+            .map(str::trim)
+            .find(|line| !line.is_empty() && !line.starts_with(COMMENT));
+        match header {
+            Some(header) if header.starts_with("index,") => Format::Csv,
+            Some(header) if header.starts_with("index\t") => Format::Tsv,
+            _ => Format::Lines,
+        }
+    }
+
+    // the character between the values of a row of a table
+    fn separator(self) -> char {
+        match self {
+            Format::Tsv => '\t',
+            _ => ',',
+        }
+    }
+}
+
+impl FromStr for Format {
+    type Err = ArgumentError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "lines" => Ok(Format::Lines),
+            "csv" => Ok(Format::Csv),
+            "tsv" => Ok(Format::Tsv),
+            _ => Err(ArgumentError(format!("unknown format '{}'", s))),
+        }
+    }
+}
+
+/// A share as stored in a shares file, together with
+/// the name of the person it was generated for.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fragment {
+    pub share: Share,
+    pub holder: Option<String>,
+    /// Name of the secret sharing scheme the share was split
+    /// with, None for the default scheme, see crypto::scheme.
+    pub scheme: Option<String>,
+    /// Prime of the field the share was split in, in decimal or by
+    /// its identifier in math::prime::registry, None for the default
+    /// prime.
+    pub field: Option<String>,
+    /// Length in bytes of the secret, which keeps its leading zero
+    /// bytes, None in files written before it was recorded.
+    pub length: Option<usize>,
+    /// MAC of the share under a key derived from the secret,
+    /// see Cipher::share_mac, None in files written before
+    /// shares were authenticated.
+    pub mac: Option<String>,
+    /// Cost, salt and check of the PIN that masks the share, see
+    /// pin::protect, None for a share without PIN.
+    pub pin: Option<String>,
+}
+
+impl Fragment {
+    /// Parses a line of a shares file.
+    ///
+    /// Each line has the form `x:y` optionally followed by
+    /// `:holder=<name>`, then by `:scheme=<name>`, then by
+    /// `:field=<prime>`, then by
+    /// `:len=<bytes>`, then by `:mac=<hex>` and then by
+    /// `:pin=<cost>.<salt>.<check>`.
+    ///
+    /// # Parameters
+    ///
+    /// - line: The line to parse.
+    ///
+    /// # Returns
+    ///
+    /// The fragment represented by the line.
+    ///
+    /// # Errors
+    ///
+    /// A CorruptFileError if the line is malformed, telling the
+    /// column where the line stops being a fragment.
+    pub fn parse(line: &str) -> Result<Self, CorruptFileError> {
+        Fragment::parse_at(line).map_err(|(column, reason)| {
+            CorruptFileError(format!("{} at column {}", reason, column))
+        })
+    }
+
+    // parses a line, returning on error the column where the line
+    // stops being a fragment and the reason
+    fn parse_at(line: &str) -> Result<Self, (usize, String)> {
+        // the fields of the line, trimmed, with the column they start at
+        let mut column = 1;
+        let mut fields = Vec::new();
+        for field in line.split(':') {
+            let leading = field.chars().take_while(|c| c.is_whitespace()).count();
+            fields.push((column + leading, field.trim()));
+            column += field.chars().count() + 1;
+        }
+        let end = column - 1;
+        match fields[..] {
+            [_] => return Err((end, "missing ':' between index and value".into())),
+            [(column, ""), ..] => return Err((column, "missing index".into())),
+            [_, (column, ""), ..] => return Err((column, "missing value".into())),
+            _ => (),
+        }
+        let mut optional = fields[2..].iter().peekable();
+        // each attribute may be missing, but they come in this order
+        let mut attribute = |name: &str, valid: fn(&str) -> bool| match optional.peek() {
+            Some(&&(column, field)) => match field.strip_prefix(name) {
+                Some(value) if !value.is_empty() && valid(value) => {
+                    optional.next();
+                    Ok(Some(value.to_string()))
+                }
+                Some(_) => Err((column, format!("invalid {}", name.trim_end_matches('=')))),
+                None => Ok(None),
+            },
+            None => Ok(None),
+        };
+        let holder = attribute("holder=", |_| true)?;
+        let scheme = attribute("scheme=", |v| {
+            v.chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+        })?;
+        // a prime in decimal or the identifier of a named prime
+        let field = attribute("field=", |v| {
+            v.chars().all(|c| c.is_ascii_digit()) || prime::named(v).is_some()
+        })?;
+        let length = attribute("len=", |v| v.parse::<usize>().is_ok())?;
+        let mac = attribute("mac=", |v| v.chars().all(|c| c.is_ascii_hexdigit()))?;
+        let pin = attribute("pin=", |v| {
+            v.chars().all(|c| c.is_ascii_hexdigit() || c == '.')
+        })?;
+        if let Some((column, _)) = optional.next() {
+            return Err((*column, "unexpected field".into()));
+        }
+        Ok(Fragment {
+            share: Share(fields[0].1.to_string(), fields[1].1.to_string()),
+            holder,
+            scheme,
+            field,
+            length: length.map(|length| length.parse().unwrap()),
+            mac,
+            pin,
+        })
+    }
+
+    /// Writes this fragment as a line of a shares file.
+    ///
+    /// # Parameters
+    ///
+    /// - writer: The writer where the line is written.
+    ///
+    /// # Errors
+    ///
+    /// This method returns an error if an error occurs while writing.
+    pub fn write_to(&self, writer: &mut impl Write) -> std::io::Result<()> {
+        let Share(x, y) = &self.share;
+        write!(writer, "{}:{}", x, y)?;
+        if let Some(holder) = &self.holder {
+            write!(writer, ":holder={}", holder)?;
+        }
+        if let Some(scheme) = &self.scheme {
+            write!(writer, ":scheme={}", scheme)?;
+        }
+        if let Some(field) = &self.field {
+            write!(writer, ":field={}", field)?;
+        }
+        if let Some(length) = self.length {
+            write!(writer, ":len={}", length)?;
+        }
+        if let Some(mac) = &self.mac {
+            write!(writer, ":mac={}", mac)?;
+        }
+        if let Some(pin) = &self.pin {
+            write!(writer, ":pin={}", pin)?;
+        }
+        writer.write_all(b"\n")
+    }
+
+    /// Returns the line that represents this fragment in
+    /// a shares file, without the newline.
+    pub fn to_line(&self) -> String {
+        let mut line = Vec::new();
+        self.write_to(&mut line).unwrap();
+        line.pop();
+        String::from_utf8(line).unwrap()
+    }
+
+    /// Returns the fingerprint of the share of this fragment, the
+    /// first bytes of its SHA-256 in hexadecimal, which tells shares
+    /// apart without revealing them.
+    pub fn fingerprint(&self) -> String {
+        checksum(&self.share)
+    }
+
+    /// Returns this fragment as armored text, suitable for
+    /// printing or pasting into e-mails.
+    ///
+    /// The line of the fragment is wrapped between the
+    /// ARMOR_BEGIN and ARMOR_END lines.
+    pub fn armor(&self) -> String {
+        let line = self.to_line();
+        let mut armored = String::from(ARMOR_BEGIN);
+        armored.push('\n');
+        for chunk in line.as_bytes().chunks(ARMOR_LINE_LEN) {
+            armored.push_str(std::str::from_utf8(chunk).unwrap());
+            armored.push('\n');
+        }
+        armored.push_str(ARMOR_END);
+        armored.push('\n');
+        armored
+    }
+
+    /// Parses an armored fragment.
+    ///
+    /// # Parameters
+    ///
+    /// - text: The armored text, as returned by armor.
+    ///
+    /// # Returns
+    ///
+    /// The fragment represented by the text.
+    ///
+    /// # Errors
+    ///
+    /// A CorruptFileError if the text is not an armored fragment.
+    pub fn from_armor(text: &str) -> Result<Self, CorruptFileError> {
+        let mut lines = text.lines().map(str::trim).skip_while(|l| l.is_empty());
+        if lines.next() != Some(ARMOR_BEGIN) {
+            return Err(CorruptFileError("armored share has no begin line".into()));
+        }
+        let mut line = String::new();
+        for l in &mut lines {
+            if l == ARMOR_END {
+                return Fragment::parse(&line);
+            }
+            line.push_str(l);
+        }
+        Err(CorruptFileError("armored share has no end line".into()))
+    }
+}
+
+/// Checks that a holder name can be stored in a shares file.
+///
+/// # Parameters
+///
+/// - holder: The name to check.
+///
+/// # Returns
+///
+/// True if the name is not empty and does not contain
+/// `:`, `=` or control characters.
+pub fn is_valid_holder(holder: &str) -> bool {
+    !holder.trim().is_empty()
+        && holder.trim() == holder
+        && !holder.contains(|c: char| c == ':' || c == '=' || c.is_control())
+}
+
+/// Reads all the fragments in a shares file.
+///
+/// # Parameters
+///
+/// - path: The path of the shares file.
+///
+/// # Returns
+///
+/// A vector with the fragments in the file.
+///
+/// # Errors
+///
+/// This method returns an error if the file cannot be read
+/// or if it is corrupt.
+pub fn read_fragments(path: &str) -> Result<Vec<Fragment>, Box<dyn Error>> {
+    parse_fragments(&fs::read_to_string(path)?)
+        .map_err(|e| Box::new(CorruptFileError(format!("{}: {}", path, e.0))) as Box<dyn Error>)
+}
+
+/// Writes fragments as a shares file, after the line with the
+/// version of its format.
+///
+/// The file is canonical: the fragments are written in the order of
+/// the indices of their shares, see canonical_order, and the fields
+/// of each one always in the same order, so the same fragments give
+/// the same bytes however they were gathered.
+///
+/// The tables have a header row and the columns index, x, y,
+/// checksum, holder, scheme, field, len, mac and pin, where the index is the
+/// position of the share in the file and the checksum detects typos
+/// in x and y when the table is edited by hand.
+///
+/// # Parameters
+///
+/// - fragments: The fragments to write.
+/// - format: The format of the shares file.
+/// - writer: The writer where the file is written.
+///
+/// # Errors
+///
+/// This method returns an error if an error occurs while writing.
+pub fn write_fragments(
+    fragments: &[Fragment],
+    format: Format,
+    writer: &mut impl Write,
+) -> std::io::Result<()> {
+    writeln!(writer, "{}", version_line())?;
+    let mut fragments: Vec<&Fragment> = fragments.iter().collect();
+    fragments.sort_by(|a, b| canonical_order(a, b));
+    if format == Format::Lines {
+        for fragment in fragments {
+            fragment.write_to(writer)?;
+        }
+        return Ok(());
+    }
+    let separator = format.separator().to_string();
+    writeln!(writer, "{}", COLUMNS.join(&separator))?;
+    for (i, fragment) in fragments.iter().enumerate() {
+        let Share(x, y) = &fragment.share;
+        let row = [
+            (i + 1).to_string(),
+            x.clone(),
+            y.clone(),
+            checksum(&fragment.share),
+            match (&fragment.holder, format) {
+                (Some(holder), Format::Csv) => quote(holder),
+                (holder, _) => holder.clone().unwrap_or_default(),
+            },
+            fragment.scheme.clone().unwrap_or_default(),
+            fragment.field.clone().unwrap_or_default(),
+            fragment.length.map_or(String::new(), |l| l.to_string()),
+            fragment.mac.clone().unwrap_or_default(),
+            fragment.pin.clone().unwrap_or_default(),
+        ];
+        writeln!(writer, "{}", row.join(&separator))?;
+    }
+    Ok(())
+}
+
+/// Parses the fragments in the text of a shares file, in any of
+/// the formats, see Format::detect, or in a bundle, see
+/// bundle::write_bundle.
+///
+/// Blank lines, lines starting with `#` and a byte order mark at
+/// the start are skipped, and the other lines are trimmed.
+///
+/// # Parameters
+///
+/// - text: The content of the shares file.
+///
+/// # Returns
+///
+/// A vector with the fragments in the text.
+///
+/// # Errors
+///
+/// A CorruptFileError if a line is not a fragment, telling the line
+/// and column where it stops being one and the start of the line, if
+/// a row of a table does not match its checksum or if the format of
+/// the file is newer than FORMAT_VERSION.
+pub fn parse_fragments(text: &str) -> Result<Vec<Fragment>, CorruptFileError> {
+    if bundle::is_bundle(text) {
+        return Ok(bundle::read_bundle(text)?.fragments);
+    }
+    format_version(text)?;
+    let format = Format::detect(text);
+    if format != Format::Lines {
+        return parse_table(text, format.separator());
+    }
+    let mut fragments = Vec::new();
+    for (i, line) in text.trim_start_matches(BYTE_ORDER_MARK).lines().enumerate() {
+        if line.trim().is_empty() || line.trim_start().starts_with(COMMENT) {
+            continue;
+        }
+        let fragment = Fragment::parse_at(line).map_err(|(column, reason)| {
+            CorruptFileError(format!(
+                "line {}, column {}: {} in '{}'",
+                i + 1,
+                column,
+                reason,
+                snippet(line.trim())
+            ))
+        })?;
+        fragments.push(fragment);
+    }
+    Ok(fragments)
+}
+
+/// Returns the version of the format of the text of a shares file,
+/// see FORMAT_VERSION.
+///
+/// # Parameters
+///
+/// - text: The content of the shares file.
+///
+/// # Errors
+///
+/// A CorruptFileError if the version line is invalid or the version
+/// is newer than the ones this program reads.
+pub fn format_version(text: &str) -> Result<u8, CorruptFileError> {
+    let version = match read_version_line(text) {
+        Some(line) => {
+            let version = line.split(',').next().unwrap().trim();
+            version
+                .parse()
+                .map_err(|_| CorruptFileError(format!("invalid format version '{}'", version)))?
+        }
+        None => return Ok(0),
+    };
+    if version > FORMAT_VERSION {
+        return Err(CorruptFileError(format!(
+            "the shares file has format version {}, this program reads versions 0 to {}; \
+             use a newer version of shared_secrets",
+            version, FORMAT_VERSION
+        )));
+    }
+    Ok(version)
+}
+
+/// Returns the version of the program that wrote a shares file, or
+/// None if it was not recorded, as in the files before version 1.
+///
+/// # Parameters
+///
+/// - text: The content of the shares file.
+pub fn program_version(text: &str) -> Option<&str> {
+    let (_, version) = read_version_line(text)?.split_once(WRITTEN_BY.trim_start())?;
+    Some(version.trim())
+}
+
+/// Returns the first line of the shares files written by this
+/// program: the version of the format and the version of the
+/// program, such as
+/// `# shared_secrets shares format 1, written by shared_secrets 0.1.0`.
+pub fn version_line() -> String {
+    format!(
+        "{}{}{}{}",
+        VERSION_PREFIX,
+        FORMAT_VERSION,
+        WRITTEN_BY,
+        crate::VERSION
+    )
+}
+
+// the rest of the version line of a shares file after the prefix,
+// or None if the file has none
+fn read_version_line(text: &str) -> Option<&str> {
+    text.trim_start_matches(BYTE_ORDER_MARK)
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty())?
+        .strip_prefix(VERSION_PREFIX.trim_end())
+}
+
+// parses the rows of a table of shares, finding the columns by the
+// names in its header, so they may be in any order or missing
+fn parse_table(text: &str, separator: char) -> Result<Vec<Fragment>, CorruptFileError> {
+    let mut rows = text
+        .trim_start_matches(BYTE_ORDER_MARK)
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty() && !line.trim_start().starts_with(COMMENT));
+    let (i, header) = rows.next().unwrap();
+    let corrupt = |i: usize, reason: &str| CorruptFileError(format!("line {}: {}", i + 1, reason));
+    let header = split_row(header, separator).map_err(|reason| corrupt(i, reason))?;
+    let column = |name: &str| {
+        header
+            .iter()
+            .position(|column| column.trim().eq_ignore_ascii_case(name))
+    };
+    let columns: Vec<_> = COLUMNS.iter().map(|name| column(name)).collect();
+    for name in ["x", "y"] {
+        if column(name).is_none() {
+            return Err(corrupt(i, &format!("missing column {}", name)));
+        }
+    }
+    let mut fragments = Vec::new();
+    for (i, row) in rows {
+        let values = split_row(row, separator).map_err(|reason| corrupt(i, reason))?;
+        let value = |name: &str| {
+            let position = COLUMNS.iter().position(|column| *column == name).unwrap();
+            columns[position]
+                .and_then(|column| values.get(column))
+                .map(|value| value.trim())
+                .filter(|value| !value.is_empty())
+        };
+        // the attributes are checked as in a line of a shares file
+        let mut line = format!("{}:{}", value("x").unwrap_or(""), value("y").unwrap_or(""));
+        for name in ["holder", "scheme", "field", "len", "mac", "pin"] {
+            if let Some(value) = value(name) {
+                line.push_str(&format!(":{}={}", name, value));
+            }
+        }
+        let fragment = Fragment::parse_at(&line).map_err(|(_, reason)| corrupt(i, &reason))?;
+        if let Some(checksum) = value("checksum") {
+            if !checksum.eq_ignore_ascii_case(&self::checksum(&fragment.share)) {
+                return Err(corrupt(i, "wrong checksum, check x and y"));
+            }
+        }
+        fragments.push(fragment);
+    }
+    Ok(fragments)
+}
+
+// splits a row of a table in its values, removing the quotes
+// around the values of a csv table
+fn split_row(row: &str, separator: char) -> Result<Vec<String>, &'static str> {
+    let mut values = vec![String::new()];
+    let mut quoted = false;
+    let mut chars = row.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                values.last_mut().unwrap().push('"');
+            }
+            '"' if separator == ',' => quoted = !quoted,
+            c if c == separator && !quoted => values.push(String::new()),
+            c => values.last_mut().unwrap().push(c),
+        }
+    }
+    if quoted {
+        return Err("unclosed quote");
+    }
+    Ok(values)
+}
+
+// quotes a value of a csv table if it has commas or quotes
+fn quote(value: &str) -> String {
+    if value.contains([',', '"']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Compares two fragments by the index of their shares, as numbers,
+/// which is the order of the fragments in a shares file. The indices
+/// are compared without leading zeros and case, so the order does
+/// not depend on how they were typed.
+///
+/// # Parameters
+///
+/// - a: A fragment.
+/// - b: Another fragment.
+pub fn canonical_order(a: &Fragment, b: &Fragment) -> Ordering {
+    let key = |fragment: &Fragment| {
+        let index = fragment.share.0.trim_start_matches('0').to_lowercase();
+        (index.len(), index)
+    };
+    key(a).cmp(&key(b))
+}
+
+/// Returns the fingerprint of a set of shares, the first bytes of
+/// the SHA-256 of all their shares in hexadecimal, as fingerprint
+/// does for one share.
+///
+/// The fingerprint does not depend on the order of the shares or on
+/// their holders, so the holders of the shares of a split can read
+/// it to each other to confirm they hold shares of the same split.
+///
+/// # Parameters
+///
+/// - fragments: The fragments of the shares.
+pub fn set_fingerprint(fragments: &[Fragment]) -> String {
+    let mut shares: Vec<String> = fragments
+        .iter()
+        .map(|fragment| format!("{}:{}", fragment.share.0, fragment.share.1).to_lowercase())
+        .collect();
+    shares.sort();
+    let digest = Sha256::digest(shares.join("\n").as_bytes());
+    to_hex(&digest[..CHECKSUM_SIZE])
+}
+
+// the first bytes of the SHA-256 of a share, in hexadecimal
+fn checksum(Share(x, y): &Share) -> String {
+    let digest = Sha256::digest(format!("{}:{}", x, y).as_bytes());
+    to_hex(&digest[..CHECKSUM_SIZE])
+}
+
+// the start of a line, short enough for an error message
+fn snippet(line: &str) -> String {
+    match line.char_indices().nth(SNIPPET_LEN) {
+        Some((end, _)) => format!("{}...", &line[..end]),
+        None => line.to_string(),
+    }
+}
+
+/////////////////////////////////
+// Unit testing of the module. //
+/////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fragment_parse() {
+        let fragment = Fragment::parse("1a:2b").unwrap();
+        assert_eq!(fragment.share, Share("1a".into(), "2b".into()));
+        assert_eq!(fragment.holder, None);
+    }
+
+    #[test]
+    fn fragment_parse_holder() {
+        let fragment = Fragment::parse("1a:2b:holder=alice").unwrap();
+        assert_eq!(fragment.share, Share("1a".into(), "2b".into()));
+        assert_eq!(fragment.holder, Some("alice".into()));
+    }
+
+    #[test]
+    fn fragment_parse_mac() {
+        let fragment = Fragment::parse("1a:2b:holder=alice:mac=09af").unwrap();
+        assert_eq!(fragment.holder, Some("alice".into()));
+        assert_eq!(fragment.mac, Some("09af".into()));
+        let fragment = Fragment::parse("1a:2b:mac=09af").unwrap();
+        assert_eq!(fragment.holder, None);
+        assert_eq!(fragment.mac, Some("09af".into()));
+        assert_eq!(Fragment::parse(&fragment.to_line()).unwrap(), fragment);
+        assert!(Fragment::parse("1a:2b:mac=09ag").is_err());
+        assert!(Fragment::parse("1a:2b:mac=09af:holder=alice").is_err());
+        assert!(Fragment::parse("1a:2b:holder=alice:mac=09af:mac=09af").is_err());
+    }
+
+    #[test]
+    fn fragment_parse_scheme() {
+        let fragment = Fragment::parse("1a:2b:holder=alice:scheme=shamir:field=65537").unwrap();
+        assert_eq!(fragment.scheme, Some("shamir".into()));
+        assert_eq!(fragment.field, Some("65537".into()));
+        assert_eq!(
+            fragment.to_line(),
+            "1a:2b:holder=alice:scheme=shamir:field=65537"
+        );
+        assert_eq!(Fragment::parse("1a:2b").unwrap().scheme, None);
+        assert!(Fragment::parse("1a:2b:scheme=Shamir").is_err());
+        let checked = Fragment::parse("1a:2b:scheme=shamir-checked").unwrap();
+        assert_eq!(checked.scheme, Some("shamir-checked".into()));
+        assert!(Fragment::parse("1a:2b:field=65537:scheme=shamir").is_err());
+    }
+
+    #[test]
+    fn fragment_parse_field() {
+        let fragment = Fragment::parse("1a:2b.3c:holder=alice:field=65537:mac=09af").unwrap();
+        assert_eq!(fragment.field, Some("65537".into()));
+        assert_eq!(fragment.mac, Some("09af".into()));
+        assert_eq!(Fragment::parse(&fragment.to_line()).unwrap(), fragment);
+        let fragment = Fragment::parse("1a:2b:field=65537").unwrap();
+        assert_eq!(fragment.holder, None);
+        assert_eq!(fragment.mac, None);
+        assert!(Fragment::parse("1a:2b:field=x").is_err());
+        let fragment = Fragment::parse("1a:2b:field=ed25519-order").unwrap();
+        assert_eq!(fragment.field, Some("ed25519-order".into()));
+        assert!(Fragment::parse("1a:2b:mac=09af:field=65537").is_err());
+        let fragment = Fragment::parse("1a:2b:field=65537:len=32:mac=09af").unwrap();
+        assert_eq!(fragment.length, Some(32));
+        assert_eq!(Fragment::parse(&fragment.to_line()).unwrap(), fragment);
+        assert!(Fragment::parse("1a:2b:len=").is_err());
+        assert!(Fragment::parse("1a:2b:len=32:field=65537").is_err());
+    }
+
+    #[test]
+    fn fragment_parse_err() {
+        assert!(Fragment::parse("1a").is_err());
+        assert!(Fragment::parse("1a:").is_err());
+        assert!(Fragment::parse("1a:2b:alice").is_err());
+        assert!(Fragment::parse("1a:2b:holder=").is_err());
+    }
+
+    #[test]
+    fn fragment_round_trip() {
+        let fragment = Fragment {
+            share: Share("1a".into(), "2b".into()),
+            holder: Some("bob".into()),
+            scheme: None,
+            field: None,
+            length: None,
+            mac: None,
+            pin: None,
+        };
+        let mut line = Vec::new();
+        fragment.write_to(&mut line).unwrap();
+        assert_eq!(line, b"1a:2b:holder=bob\n");
+        let line = String::from_utf8(line).unwrap();
+        assert_eq!(Fragment::parse(line.trim_end()).unwrap(), fragment);
+    }
+
+    #[test]
+    fn fingerprints() {
+        let alice = Fragment::parse("1:2a:holder=alice").unwrap();
+        let bob = Fragment::parse("2:3b").unwrap();
+        assert_eq!(alice.fingerprint().len(), 2 * CHECKSUM_SIZE);
+        assert_ne!(alice.fingerprint(), bob.fingerprint());
+        let fingerprint = set_fingerprint(&[alice.clone(), bob.clone()]);
+        assert_eq!(fingerprint.len(), 2 * CHECKSUM_SIZE);
+        assert_eq!(fingerprint, set_fingerprint(&[bob.clone(), alice]));
+        let unnamed = Fragment::parse("1:2A").unwrap();
+        assert_eq!(fingerprint, set_fingerprint(&[unnamed, bob.clone()]));
+        assert_ne!(fingerprint, set_fingerprint(&[bob]));
+    }
+
+    #[test]
+    fn fragment_armor() {
+        let fragment = Fragment {
+            share: Share("1a".repeat(40), "2b".repeat(40)),
+            holder: Some("carol".into()),
+            scheme: None,
+            field: None,
+            length: None,
+            mac: None,
+            pin: None,
+        };
+        let armored = fragment.armor();
+        assert!(armored.starts_with(ARMOR_BEGIN));
+        assert!(armored
+            .lines()
+            .all(|l| l.len() <= ARMOR_LINE_LEN.max(ARMOR_BEGIN.len())));
+        assert_eq!(Fragment::from_armor(&armored).unwrap(), fragment);
+    }
+
+    #[test]
+    fn fragment_armor_err() {
+        assert!(Fragment::from_armor("1a:2b").is_err());
+        assert!(Fragment::from_armor(&format!("{}\n1a:2b\n", ARMOR_BEGIN)).is_err());
+    }
+
+    #[test]
+    fn tolerant_parsing() {
+        let text =
+            "\u{feff}# shares of the backup\r\n\r\n  1a:2b:holder=alice  \r\n\n# bob\n3c:4d\n";
+        let fragments = parse_fragments(text).unwrap();
+        assert_eq!(fragments.len(), 2);
+        assert_eq!(fragments[0].holder, Some("alice".into()));
+        assert_eq!(fragments[1].share, Share("3c".into(), "4d".into()));
+        assert_eq!(parse_fragments("\n# nothing\n").unwrap(), vec![]);
+        assert!(parse_fragments("1a:2b\n3c\n").is_err());
+    }
+
+    #[test]
+    fn error_positions() {
+        let error = |line: &str| Fragment::parse(line).unwrap_err().0;
+        assert_eq!(
+            error("1a"),
+            "missing ':' between index and value at column 3"
+        );
+        assert_eq!(error(" :2b"), "missing index at column 2");
+        assert_eq!(error("1a: "), "missing value at column 5");
+        assert_eq!(error("1a:2b:holder="), "invalid holder at column 7");
+        assert_eq!(error("1a:2b:len=x"), "invalid len at column 7");
+        assert_eq!(error("1a:2b:mac=09af:x"), "unexpected field at column 16");
+        let text = "# shares\n1a:2b\n\n3c:4d:holder=bob:nonsense-that-goes-on-and-on\n";
+        assert_eq!(
+            parse_fragments(text).unwrap_err().0,
+            "line 4, column 18: unexpected field in '3c:4d:holder=bob:nonsens...'"
+        );
+    }
+
+    #[test]
+    fn tables() {
+        let fragments = vec![
+            Fragment {
+                share: Share("1".into(), "2b.3c".into()),
+                holder: Some("Smith, \"Al\"".into()),
+                scheme: None,
+                field: Some("65537".into()),
+                length: Some(32),
+                mac: Some("09af".into()),
+                pin: Some("15.00ff.0102".into()),
+            },
+            Fragment {
+                share: Share("2".into(), "4d.5e".into()),
+                holder: None,
+                scheme: None,
+                field: None,
+                length: None,
+                mac: None,
+                pin: None,
+            },
+        ];
+        for format in [Format::Csv, Format::Tsv] {
+            let mut text = Vec::new();
+            write_fragments(&fragments, format, &mut text).unwrap();
+            let text = String::from_utf8(text).unwrap();
+            assert_eq!(Format::detect(&text), format);
+            assert_eq!(parse_fragments(&text).unwrap(), fragments);
+        }
+        // columns in another order, some missing, as in a spreadsheet
+        let text = "\u{feff}# custody\r\nholder,y,x\r\nalice,2b,1a\r\n,4d,3c\r\n";
+        assert_eq!(Format::detect(text), Format::Lines);
+        let text = text.replace("holder,y,x", "index,holder,y,x");
+        let text = text
+            .replace("alice,", "1,alice,")
+            .replace("\n,4d", "\n2,,4d");
+        let parsed = parse_fragments(&text).unwrap();
+        assert_eq!(parsed[0].share, Share("1a".into(), "2b".into()));
+        assert_eq!(parsed[0].holder, Some("alice".into()));
+        assert_eq!(parsed[1].holder, None);
+    }
+
+    #[test]
+    fn canonical_files() {
+        let fragments = parse_fragments("a:1\n02:2:holder=bob\nB:3\n1:4\n").unwrap();
+        let mut reversed = fragments.clone();
+        reversed.reverse();
+        for format in [Format::Lines, Format::Csv] {
+            let mut text = Vec::new();
+            write_fragments(&fragments, format, &mut text).unwrap();
+            let mut again = Vec::new();
+            write_fragments(&reversed, format, &mut again).unwrap();
+            assert_eq!(text, again);
+            let indices: Vec<String> = parse_fragments(std::str::from_utf8(&text).unwrap())
+                .unwrap()
+                .into_iter()
+                .map(|fragment| fragment.share.0)
+                .collect();
+            assert_eq!(indices, ["1", "02", "a", "B"]);
+        }
+    }
+
+    #[test]
+    fn table_errors() {
+        let mut text = Vec::new();
+        let fragment = Fragment::parse("1:2b").unwrap();
+        write_fragments(&[fragment], Format::Csv, &mut text).unwrap();
+        let text = String::from_utf8(text).unwrap();
+        assert!(parse_fragments(&text).is_ok());
+        assert_eq!(
+            parse_fragments(&text.replace("2b", "2c")).unwrap_err().0,
+            "line 3: wrong checksum, check x and y"
+        );
+        assert_eq!(
+            parse_fragments("index,x\n1,1a\n").unwrap_err().0,
+            "line 1: missing column y"
+        );
+        assert_eq!(
+            parse_fragments("index,x,y,holder\n1,1a,2b,\"bob\n")
+                .unwrap_err()
+                .0,
+            "line 2: unclosed quote"
+        );
+        assert!(parse_fragments("index,x,y,len\n1,1a,2b,x\n").is_err());
+        assert_eq!("tsv".parse::<Format>().unwrap(), Format::Tsv);
+        assert!("xlsx".parse::<Format>().is_err());
+    }
+
+    #[test]
+    fn format_versions() {
+        let fragment = Fragment::parse("1:2b").unwrap();
+        for format in [Format::Lines, Format::Csv] {
+            let mut text = Vec::new();
+            write_fragments(std::slice::from_ref(&fragment), format, &mut text).unwrap();
+            let text = String::from_utf8(text).unwrap();
+            assert!(text.starts_with(&format!(
+                "# shared_secrets shares format 1, written by shared_secrets {}\n",
+                crate::VERSION
+            )));
+            assert_eq!(format_version(&text).unwrap(), FORMAT_VERSION);
+            assert_eq!(program_version(&text), Some(crate::VERSION));
+            assert_eq!(Format::detect(&text), format);
+        }
+        assert_eq!(format_version("1:2b\n").unwrap(), 0);
+        assert_eq!(program_version("1:2b\n"), None);
+        let text = "# shared_secrets shares format 1\n1:2b\n";
+        assert_eq!(format_version(text).unwrap(), 1);
+        assert_eq!(program_version(text), None);
+        assert_eq!(format_version("# shares of alice\n1:2b\n").unwrap(), 0);
+        assert_eq!(format_version("").unwrap(), 0);
+        assert!(format_version("# shared_secrets shares format x\n").is_err());
+        assert_eq!(
+            parse_fragments("# shared_secrets shares format 2\n1:2b\n")
+                .unwrap_err()
+                .0,
+            "the shares file has format version 2, this program reads versions 0 to 1; \
+             use a newer version of shared_secrets"
+        );
+    }
+
+    #[test]
+    fn holder_validation() {
+        assert!(is_valid_holder("alice"));
+        assert!(is_valid_holder("Alice Smith"));
+        assert!(!is_valid_holder(""));
+        assert!(!is_valid_holder(" alice"));
+        assert!(!is_valid_holder("a:b"));
+        assert!(!is_valid_holder("a=b"));
+        assert!(!is_valid_holder("a\nb"));
+    }
+}
@@ -0,0 +1,162 @@
+use std::error::Error;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::crypto::SecretKey;
+use crate::encoding;
+use crate::error::ArgumentError;
+use crate::fragments::{self, Format, Fragment};
+use crate::scan;
+
+/// Splits a secret typed in the front-end into shares that record
+/// its length, so recover returns it as it was.
+///
+/// # Parameters
+///
+/// - secret: The secret, which must not be empty.
+/// - total_evals: The number of shares.
+/// - min_required_evals: The number of shares needed to recover it.
+///
+/// # Errors
+///
+/// An ArgumentError if the secret is empty or the scheme is not
+/// valid.
+pub fn split(
+    secret: &[u8],
+    total_evals: usize,
+    min_required_evals: usize,
+) -> Result<Vec<Fragment>, Box<dyn Error>> {
+    if secret.is_empty() {
+        return Err(Box::new(ArgumentError("the secret is empty".into())));
+    }
+    crate::check_scheme(total_evals, min_required_evals)?;
+    let secret = SecretKey::new(secret.to_vec());
+    crate::key_fragments(&secret, total_evals, min_required_evals, None)
+}
+
+/// Writes a share in a shares file of its own, share-INDEX.frg in
+/// the directory, to give it to its holder.
+///
+/// # Parameters
+///
+/// - dir: The directory of the file.
+/// - fragment: The share.
+///
+/// # Returns
+///
+/// The path of the file.
+///
+/// # Errors
+///
+/// An io::Error if the file exists or cannot be written.
+pub fn write_share(dir: &Path, fragment: &Fragment) -> Result<PathBuf, Box<dyn Error>> {
+    let path = dir.join(format!("share-{}.frg", fragment.share.0));
+    crate::write_atomically(&path, |writer| {
+        Ok(fragments::write_fragments(
+            std::slice::from_ref(fragment),
+            Format::Lines,
+            writer,
+        )?)
+    })?;
+    Ok(path)
+}
+
+/// Reads the shares of a file dropped on the front-end: a shares
+/// file in any format, a bundle, or an image with QR codes, see
+/// scan::read_image.
+///
+/// # Parameters
+///
+/// - path: The path of the file.
+///
+/// # Errors
+///
+/// An io::Error if the file cannot be read, or the error of the
+/// shares or of the image.
+pub fn read_shares(path: &Path) -> Result<Vec<Fragment>, Box<dyn Error>> {
+    let content = fs::read(path)?;
+    match std::str::from_utf8(&content) {
+        Ok(text) => Ok(fragments::parse_fragments(text)?),
+        // any file that is not text is taken as an image
+        Err(_) => scan::read_image(&path.to_string_lossy()),
+    }
+}
+
+/// Recovers the secret of the shares dropped on the front-end.
+///
+/// # Parameters
+///
+/// - fragments: The shares, which may have copies of the same one.
+///
+/// # Errors
+///
+/// An ArgumentError if the shares do not recover the secret.
+pub fn recover(fragments: Vec<Fragment>) -> Result<SecretKey, Box<dyn Error>> {
+    crate::fragments_key(fragments)
+}
+
+/// Writes a recovered secret in a new file.
+///
+/// # Parameters
+///
+/// - path: The path of the file, which must not exist.
+/// - secret: The secret.
+///
+/// # Errors
+///
+/// An io::Error if the file exists or cannot be written.
+pub fn write_secret(path: &Path, secret: &SecretKey) -> Result<(), Box<dyn Error>> {
+    crate::write_atomically(path, |writer| Ok(writer.write_all(secret.expose())?))
+}
+
+/// Returns a recovered secret as it is shown: as text if it is
+/// UTF-8, and in hexadecimal otherwise.
+///
+/// # Parameters
+///
+/// - secret: The secret.
+pub fn display(secret: &SecretKey) -> String {
+    match std::str::from_utf8(secret.expose()) {
+        Ok(text) => text.to_string(),
+        Err(_) => encoding::to_hex(secret.expose()),
+    }
+}
+
+/////////////////////////////////
+// Unit testing of the module. //
+/////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_and_recover() {
+        let dir = std::env::temp_dir().join(format!("gui-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let fragments = split(b"message", 3, 2).unwrap();
+        let paths: Vec<_> = fragments
+            .iter()
+            .map(|fragment| write_share(&dir, fragment).unwrap())
+            .collect();
+        assert!(paths[0].ends_with("share-1.frg"));
+        assert!(write_share(&dir, &fragments[0]).is_err());
+        let mut dropped = read_shares(&paths[2]).unwrap();
+        assert!(recover(dropped.clone()).is_err());
+        dropped.extend(read_shares(&paths[0]).unwrap());
+        let secret = recover(dropped).unwrap();
+        assert_eq!(display(&secret), "message");
+        write_secret(&dir.join("secret.bin"), &secret).unwrap();
+        assert_eq!(fs::read(dir.join("secret.bin")).unwrap(), b"message");
+        assert!(write_secret(&dir.join("secret.bin"), &secret).is_err());
+        assert!(split(b"", 3, 2).is_err());
+        assert!(split(b"message", 2, 3).is_err());
+
+        let table = dir.join("table.csv");
+        fs::write(&table, "index,x,y\n1,1a,2b\n2,3c,4d\n").unwrap();
+        assert_eq!(read_shares(&table).unwrap().len(), 2);
+        assert_eq!(display(&SecretKey::new(vec![0xff, 0x01])), "ff01");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
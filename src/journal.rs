@@ -0,0 +1,97 @@
+use std::error::Error;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::Path;
+
+use crate::crypto::chunked::SALT_SIZE;
+use crate::error::CorruptFileError;
+
+/// The progress of an encryption, saved so that it can
+/// be resumed if it is interrupted.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Checkpoint {
+    /// The salt of the chunked stream being written.
+    pub salt: [u8; SALT_SIZE],
+    /// Number of chunks already written to disk.
+    pub chunks: u64,
+}
+
+impl Checkpoint {
+    /// Saves this checkpoint in the journal at the given path.
+    ///
+    /// The journal is replaced atomically, so a crash while saving
+    /// leaves either the previous or the new checkpoint.
+    ///
+    /// # Parameters
+    ///
+    /// - path: The path of the journal.
+    ///
+    /// # Errors
+    ///
+    /// This method returns an error if an error occurs while writing.
+    pub fn save(&self, path: &str) -> std::io::Result<()> {
+        let dir = match Path::new(path).parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        let mut file = tempfile::Builder::new().suffix(".tmp").tempfile_in(dir)?;
+        file.write_all(&self.salt)?;
+        file.write_all(&self.chunks.to_le_bytes())?;
+        file.as_file().sync_all()?;
+        file.persist(path)?;
+        Ok(())
+    }
+
+    /// Reads the checkpoint saved in the journal at the given path.
+    ///
+    /// # Parameters
+    ///
+    /// - path: The path of the journal.
+    ///
+    /// # Returns
+    ///
+    /// The checkpoint saved.
+    ///
+    /// # Errors
+    ///
+    /// This method returns an error if the journal cannot be
+    /// read or if it is corrupt.
+    pub fn load(path: &str) -> Result<Self, Box<dyn Error>> {
+        let mut bytes = Vec::new();
+        File::open(path)?.read_to_end(&mut bytes)?;
+        if bytes.len() != SALT_SIZE + 8 {
+            return Err(Box::new(CorruptFileError("journal is corrupt".into())));
+        }
+        let mut salt = [0u8; SALT_SIZE];
+        salt.copy_from_slice(&bytes[..SALT_SIZE]);
+        let mut chunks = [0u8; 8];
+        chunks.copy_from_slice(&bytes[SALT_SIZE..]);
+        Ok(Checkpoint {
+            salt,
+            chunks: u64::from_le_bytes(chunks),
+        })
+    }
+}
+
+/////////////////////////////////
+// Unit testing of the module. //
+/////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn checkpoint_round_trip() {
+        let checkpoint = Checkpoint {
+            salt: [0x17u8; SALT_SIZE],
+            chunks: 513,
+        };
+        checkpoint.save("checkpoint.journal").unwrap();
+        assert_eq!(Checkpoint::load("checkpoint.journal").unwrap(), checkpoint);
+        fs::write("checkpoint.journal", b"short").unwrap();
+        assert!(Checkpoint::load("checkpoint.journal").is_err());
+        fs::remove_file("checkpoint.journal").unwrap();
+    }
+}
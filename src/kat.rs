@@ -0,0 +1,371 @@
+use std::error::Error;
+use std::fmt::Write;
+
+use crate::container::{Header, KeySlot, UnlockMethod, FORMAT_VERSION};
+use crate::crypto::chunked::{CHUNK_SIZE, SALT_SIZE};
+use crate::crypto::{Cipher, CipherSuite, WRAP_NONCE_SIZE};
+use crate::encoding::to_hex;
+use crate::error::CorruptFileError;
+use crate::fragments::Fragment;
+use crate::math::random::SEED_SIZE;
+use crate::report::json_string;
+
+/// Version of the program recorded in the header of the known
+/// answers, fixed so they do not change with every release.
+pub const PROGRAM_VERSION: &str = "0.1.0";
+
+/// The inputs of a known-answer test.
+///
+/// Everything that is random when encrypting a file is fixed
+/// here: the keys, the nonce used to wrap the data key, the
+/// salt of the chunked stream (from which the nonces of the
+/// chunks are derived) and the key of the ChaCha20 generator used
+/// to split the key, so the same .aes and .frg files are produced
+/// every time, by this crate or by any other implementation of
+/// ChaCha20.
+#[derive(Debug, Clone)]
+pub struct KnownAnswer {
+    /// The key recovered from the shares.
+    pub key: Vec<u8>,
+    /// The key the content is encrypted with.
+    pub data_key: Vec<u8>,
+    /// The nonce used to wrap the data key.
+    pub wrap_nonce: [u8; WRAP_NONCE_SIZE],
+    /// The AEAD algorithm of the cipher.
+    pub suite: CipherSuite,
+    /// Whether each chunk is encrypted with its own subkey.
+    pub subkeys: bool,
+    /// The salt of the chunked stream.
+    pub salt: [u8; SALT_SIZE],
+    /// The key of the ChaCha20 generator used to split the key,
+    /// see Rng::from_seed.
+    pub seed: [u8; SEED_SIZE],
+    /// Name of the file stored in the header.
+    pub file_name: String,
+    /// Names of the holders of the shares.
+    pub holders: Vec<String>,
+    /// Minimum number of shares needed to decrypt the file.
+    pub threshold: usize,
+    /// Total number of shares.
+    pub total: usize,
+    /// Whether the digest of the plaintext is stored in the header.
+    pub digest: bool,
+    /// The content of the encrypted file.
+    pub plaintext: Vec<u8>,
+}
+
+impl KnownAnswer {
+    /// Returns the header of the encrypted file.
+    ///
+    /// # Errors
+    ///
+    /// This method returns an error if a key does not have the
+    /// key size of the suite.
+    pub fn header(&self) -> Result<Header, Box<dyn Error>> {
+        let mut header = Header::new(
+            self.file_name.clone().into_bytes(),
+            self.holders.clone(),
+            self.threshold,
+            self.total,
+            self.suite,
+        );
+        header.program_version = Some(PROGRAM_VERSION.into());
+        if self.digest {
+            header.digest = Some(*blake3::hash(&self.plaintext).as_bytes());
+        }
+        header.length = Some(self.plaintext.len() as u64);
+        let cipher = Cipher::from_key(&self.key, self.suite)?;
+        let data_key = Cipher::from_key(&self.data_key, self.suite)?;
+        let aad = header.associated_data();
+        header.key_slots.push(KeySlot {
+            method: UnlockMethod::Shares,
+            kdf: None,
+            wrapped_key: cipher.wrap_key_with_nonce(&data_key, &aad, self.wrap_nonce)?,
+        });
+        Ok(header)
+    }
+
+    /// Returns the content of the .aes file.
+    ///
+    /// # Errors
+    ///
+    /// This method returns an error if a key does not have the
+    /// key size of the suite or if an error occurs while encrypting.
+    pub fn encrypted_file(&self) -> Result<Vec<u8>, Box<dyn Error>> {
+        let data_key = Cipher::from_key(&self.data_key, self.suite)?;
+        let header = self.header()?;
+        let encryptor = data_key.encryptor(self.subkeys, Some(self.salt))?;
+        let mut encrypted = Vec::new();
+        header.write_to(&mut encrypted)?;
+        encrypted.extend_from_slice(&encryptor.header());
+        encryptor.encrypt_from(
+            &mut &self.plaintext[..],
+            &mut encrypted,
+            &header.associated_data(),
+            0,
+            |_, _| Ok(()),
+        )?;
+        Ok(encrypted)
+    }
+
+    /// Returns the content of the .frg file.
+    ///
+    /// # Errors
+    ///
+    /// This method returns an error if the key does not have
+    /// the key size of the suite or if the scheme parameters are
+    /// not valid, see SecretKey::split.
+    pub fn shares_file(&self) -> Result<Vec<u8>, Box<dyn Error>> {
+        let cipher = Cipher::from_key(&self.key, self.suite)?;
+        let mut holders = self.holders.iter().cloned();
+        let mut shares = Vec::new();
+        for share in cipher
+            .key()
+            .split_seeded(self.total, self.threshold, self.seed)?
+        {
+            let fragment = Fragment {
+                mac: Some(cipher.share_mac(&share)),
+                pin: None,
+                share,
+                holder: holders.next(),
+                scheme: None,
+                field: None,
+                length: Some(self.key.len()),
+            };
+            fragment.write_to(&mut shares)?;
+        }
+        Ok(shares)
+    }
+
+    /// Checks that the given files are the expected answer.
+    ///
+    /// # Parameters
+    ///
+    /// - encrypted: The content of the .aes file.
+    /// - shares: The content of the .frg file.
+    ///
+    /// # Errors
+    ///
+    /// A CorruptFileError if a file differs from the one produced
+    /// by this crate, or any error while producing them.
+    pub fn check(&self, encrypted: &[u8], shares: &[u8]) -> Result<(), Box<dyn Error>> {
+        if self.encrypted_file()? != encrypted {
+            return Err(Box::new(CorruptFileError(
+                "encrypted file differs from the known answer".into(),
+            )));
+        }
+        if self.shares_file()? != shares {
+            return Err(Box::new(CorruptFileError(
+                "shares file differs from the known answer".into(),
+            )));
+        }
+        Ok(())
+    }
+
+    /// Returns this known answer as a JSON object with its
+    /// inputs and the expected files.
+    ///
+    /// Byte strings are written in hexadecimal and the shares
+    /// file as the list of its lines.
+    ///
+    /// # Errors
+    ///
+    /// This method returns an error if an error occurs while
+    /// producing the expected files.
+    pub fn to_json(&self) -> Result<String, Box<dyn Error>> {
+        let mut header = Vec::new();
+        self.header()?.write_to(&mut header)?;
+        let shares = String::from_utf8(self.shares_file()?)?;
+        let holders: Vec<String> = self.holders.iter().map(|h| json_string(h)).collect();
+        let shares: Vec<String> = shares.lines().map(json_string).collect();
+        let mut json = String::from("{");
+        write!(json, "\"key\":\"{}\",", to_hex(&self.key))?;
+        write!(json, "\"data_key\":\"{}\",", to_hex(&self.data_key))?;
+        write!(json, "\"wrap_nonce\":\"{}\",", to_hex(&self.wrap_nonce))?;
+        write!(json, "\"cipher\":\"{}\",", self.suite)?;
+        write!(json, "\"subkeys\":{},", self.subkeys)?;
+        write!(json, "\"salt\":\"{}\",", to_hex(&self.salt))?;
+        write!(json, "\"seed\":\"{}\",", to_hex(&self.seed))?;
+        write!(json, "\"file_name\":{},", json_string(&self.file_name))?;
+        write!(json, "\"holders\":[{}],", holders.join(","))?;
+        write!(json, "\"threshold\":{},", self.threshold)?;
+        write!(json, "\"total\":{},", self.total)?;
+        write!(json, "\"digest\":{},", self.digest)?;
+        write!(
+            json,
+            "\"program_version\":{},",
+            json_string(PROGRAM_VERSION)
+        )?;
+        write!(json, "\"plaintext\":\"{}\",", to_hex(&self.plaintext))?;
+        write!(json, "\"header\":\"{}\",", to_hex(&header))?;
+        write!(
+            json,
+            "\"encrypted\":\"{}\",",
+            to_hex(&self.encrypted_file()?)
+        )?;
+        write!(json, "\"shares\":[{}]}}", shares.join(","))?;
+        Ok(json)
+    }
+}
+
+/// Returns the official suite of known answers.
+///
+/// Every cipher suite is used with and without subkeys, to
+/// encrypt an empty file, a short file and a file a byte
+/// longer than a chunk, so the last chunk is nearly empty.
+pub fn official() -> Vec<KnownAnswer> {
+    let suites = [
+        CipherSuite::Aes256Gcm,
+        CipherSuite::Aes256GcmSiv,
+        CipherSuite::Aes128Gcm,
+    ];
+    let lengths = [0, 17, CHUNK_SIZE + 1];
+    let mut answers = Vec::new();
+    for &suite in &suites {
+        for &subkeys in &[false, true] {
+            for &length in &lengths {
+                let index = answers.len();
+                // the first byte of the key is never zero
+                let key = (0..suite.key_size())
+                    .map(|i| (index + i + 1) as u8)
+                    .collect();
+                let data_key = (0..suite.key_size())
+                    .map(|i| (0x80 + index + i) as u8)
+                    .collect();
+                answers.push(KnownAnswer {
+                    key,
+                    data_key,
+                    wrap_nonce: [(0x40 + index) as u8; WRAP_NONCE_SIZE],
+                    suite,
+                    subkeys,
+                    salt: [index as u8; SALT_SIZE],
+                    seed: [index as u8; SEED_SIZE],
+                    file_name: format!("vector-{}.bin", index),
+                    holders: vec!["alice".into(), "bob".into(), "carol".into()],
+                    threshold: 2,
+                    total: 3,
+                    digest: index % 2 == 0,
+                    plaintext: (0..length).map(|i| (i % 251) as u8).collect(),
+                });
+            }
+        }
+    }
+    answers
+}
+
+/// Returns the official suite of known answers as a JSON document.
+///
+/// # Errors
+///
+/// This method returns an error if an error occurs while
+/// producing the expected files.
+pub fn official_json() -> Result<String, Box<dyn Error>> {
+    let vectors = official()
+        .iter()
+        .map(KnownAnswer::to_json)
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(format!(
+        "{{\"format_version\":{},\"vectors\":[\n{}\n]}}\n",
+        FORMAT_VERSION,
+        vectors.join(",\n")
+    ))
+}
+
+/////////////////////////////////
+// Unit testing of the module. //
+/////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::container;
+    use crate::fragments;
+
+    fn known_answer() -> KnownAnswer {
+        KnownAnswer {
+            key: (1u8..33).collect(),
+            data_key: (0x80u8..0xa0).collect(),
+            wrap_nonce: [0x24u8; WRAP_NONCE_SIZE],
+            suite: CipherSuite::Aes256Gcm,
+            subkeys: false,
+            salt: [0x5au8; SALT_SIZE],
+            seed: [42; SEED_SIZE],
+            file_name: "message.txt".into(),
+            holders: vec!["alice".into(), "bob".into(), "carol".into()],
+            threshold: 2,
+            total: 3,
+            digest: true,
+            plaintext: b"This is a message".to_vec(),
+        }
+    }
+
+    #[test]
+    fn deterministic() {
+        let kat = known_answer();
+        let encrypted = kat.encrypted_file().unwrap();
+        let shares = kat.shares_file().unwrap();
+        assert!(kat.check(&encrypted, &shares).is_ok());
+        let mut other = known_answer();
+        other.salt[0] ^= 0x01;
+        assert!(other.check(&encrypted, &shares).is_err());
+        other = known_answer();
+        other.seed[0] ^= 0x01;
+        assert!(other.check(&encrypted, &shares).is_err());
+    }
+
+    #[test]
+    fn pinned_answer() {
+        let kat = known_answer();
+        let encrypted = kat.encrypted_file().unwrap();
+        assert_eq!(
+            blake3::hash(&encrypted).to_hex().as_str(),
+            "8ff072bc7271f87a0d6f16a3ea515b8d2c8912efff815d07048da97dc2bdb583"
+        );
+        let shares = String::from_utf8(kat.shares_file().unwrap()).unwrap();
+        assert_eq!(
+            shares.lines().next().unwrap(),
+            "1:ng1bnbo37z10riobomaqxbeu5x2pf43rpreq5qxz2naxuh0gr:holder=alice:\
+             len=32:mac=a8731da2cedbaef8968e6cdc90d87854"
+        );
+    }
+
+    #[test]
+    fn official_vectors() {
+        let answers = official();
+        assert_eq!(answers.len(), 18);
+        let json = official_json().unwrap();
+        assert!(json.starts_with("{\"format_version\":1,"));
+        assert_eq!(json.matches("\"encrypted\":").count(), answers.len());
+        assert_eq!(json_string("a\"b\\\n"), "\"a\\\"b\\\\\\u000a\"");
+    }
+
+    #[test]
+    fn known_answer_decrypts() {
+        let kat = known_answer();
+        let encrypted = kat.encrypted_file().unwrap();
+        let shares = kat.shares_file().unwrap();
+        let fragments = String::from_utf8(shares)
+            .unwrap()
+            .lines()
+            .map(fragments::Fragment::parse)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let mut reader = &encrypted[..];
+        let header = container::Header::read_from(&mut reader).unwrap();
+        assert_eq!(header, kat.header().unwrap());
+        let cipher = Cipher::from_shares(
+            fragments.into_iter().skip(1).map(|f| f.share),
+            header.suite,
+            None,
+            Some(32),
+        )
+        .unwrap()
+        .unwrap_key(&header.key_slots[0].wrapped_key, &header.associated_data())
+        .unwrap();
+        let mut plaintext = Vec::new();
+        cipher
+            .decrypt_stream(&mut reader, &mut plaintext, &header.associated_data())
+            .unwrap();
+        assert_eq!(plaintext, kat.plaintext);
+    }
+}
@@ -0,0 +1,143 @@
+use std::io::{self, Read, Write};
+use std::process::{Child, Command, Stdio};
+
+/// Name of the service of the entries this program stores in the
+/// keychain.
+pub const SERVICE: &str = "shared_secrets";
+
+/// Stores a secret in the keychain of the system, with the first
+/// keychain program installed: secret-tool of libsecret on Linux
+/// or security on macOS. An entry with the same account is replaced.
+///
+/// The secret is given to the program through its input, so it
+/// never appears in its arguments.
+///
+/// # Parameters
+///
+/// - account: The name of the entry, such as the encrypted file.
+/// - secret: The secret to store, a line of text.
+///
+/// # Errors
+///
+/// An error of kind NotFound if no keychain program is installed,
+/// or an error if the program fails.
+pub fn store(account: &str, secret: &str) -> io::Result<()> {
+    let label = format!("{} {}", SERVICE, account);
+    let secret_tool = [
+        "store", "--label", &label, "service", SERVICE, "account", account,
+    ];
+    if let Some(result) = run("secret-tool", &secret_tool, secret.as_bytes()) {
+        return result.map(|_| ());
+    }
+    // security only reads the password from its arguments, so the
+    // command is given to its interactive mode instead
+    let command = format!(
+        "add-generic-password -U -s {} -a {} -w {}\n",
+        quote(SERVICE),
+        quote(account),
+        quote(secret)
+    );
+    if let Some(result) = run("security", &["-i"], command.as_bytes()) {
+        return result.map(|_| ());
+    }
+    Err(not_found())
+}
+
+/// Reads a secret stored with store from the keychain of the system.
+///
+/// # Parameters
+///
+/// - account: The name of the entry.
+///
+/// # Returns
+///
+/// The secret, without the line break some programs add.
+///
+/// # Errors
+///
+/// An error of kind NotFound if no keychain program is installed or
+/// the entry does not exist, or an error if the program fails.
+pub fn load(account: &str) -> io::Result<String> {
+    let secret_tool = ["lookup", "service", SERVICE, "account", account];
+    let security = ["find-generic-password", "-s", SERVICE, "-a", account, "-w"];
+    let result = match run("secret-tool", &secret_tool, b"") {
+        Some(result) => result,
+        None => run("security", &security, b"").unwrap_or_else(|| Err(not_found())),
+    };
+    let secret = String::from_utf8(result?)
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "the secret is not text"))?;
+    let secret = secret.trim_end_matches(['\r', '\n']);
+    if secret.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("the keychain has no entry for {}", account),
+        ));
+    }
+    Ok(secret.to_string())
+}
+
+// runs a program with the input, returning its output, or None if
+// the program is not installed
+fn run(program: &str, args: &[&str], input: &[u8]) -> Option<io::Result<Vec<u8>>> {
+    let mut child = match Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+    {
+        Ok(child) => child,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return None,
+        Err(e) => return Some(Err(e)),
+    };
+    Some(communicate(&mut child, program, input))
+}
+
+// writes the input of a program and reads its output until it ends
+fn communicate(child: &mut Child, program: &str, input: &[u8]) -> io::Result<Vec<u8>> {
+    child.stdin.take().unwrap().write_all(input)?;
+    let mut output = Vec::new();
+    child.stdout.take().unwrap().read_to_end(&mut output)?;
+    if !child.wait()?.success() {
+        return Err(io::Error::other(format!(
+            "{} could not use the keychain",
+            program
+        )));
+    }
+    Ok(output)
+}
+
+// quotes an argument of a command of the interactive mode of security
+fn quote(argument: &str) -> String {
+    format!(
+        "\"{}\"",
+        argument.replace('\\', "\\\\").replace('"', "\\\"")
+    )
+}
+
+fn not_found() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::NotFound,
+        "no keychain program was found (secret-tool or security)",
+    )
+}
+
+/////////////////////////////////
+// Unit testing of the module. //
+/////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quote_arguments() {
+        assert_eq!(quote("notes.aes"), "\"notes.aes\"");
+        assert_eq!(quote("a \"b\" \\c"), "\"a \\\"b\\\" \\\\c\"");
+    }
+
+    #[test]
+    fn missing_program() {
+        assert!(run("shared_secrets-no-such-program", &[], b"").is_none());
+    }
+}
@@ -1,34 +1,476 @@
-use std::convert::TryInto;
 use std::error::Error;
 use std::fs;
 use std::fs::File;
 use std::fs::OpenOptions;
-use std::io::{BufRead, BufReader, BufWriter, Read, Write};
-use std::path::Path;
+use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 use clap::ArgMatches;
-use rpassword;
 
-use crypto::{Cipher, Share};
+use concurrency::Concurrency;
+use container::{
+    Header, KeySlot, PasswordKdf, UnlockMethod, DIGEST_SIZE, FORMAT_VERSION, KEY_SLOTS,
+    MAX_RECOVERY_SLOTS, PUBLIC_KEY_SIZE,
+};
+use crypto::chunked::{Encryptor, CHUNK_SIZE, SEALED_CHUNK_SIZE};
+use crypto::error::ShamirError;
+use crypto::shamir::ShareIter;
+use crypto::{scheme, shamir, Cipher, CipherSuite, SecretKey};
 use error::*;
+use fragments::{Format, Fragment};
+use journal::Checkpoint;
+use math::Prime;
+use policy::Policy;
+use report::{Json, Report};
+use secure::SecureString;
+use settings::Settings;
+use webhook::Webhooks;
+use zeroize::Zeroize;
 
+pub mod age;
+#[cfg(unix)]
+pub mod agent;
+pub mod archive;
+pub mod bech32;
+pub mod bundle;
+#[cfg(feature = "ceremony")]
+pub mod ceremony;
+pub mod clipboard;
+pub mod codex32;
+pub mod concurrency;
+pub mod container;
 pub mod crypto;
+pub mod doctor;
+pub mod encoding;
+pub mod entries;
+pub mod envelope;
 pub mod error;
+pub mod escrow;
+pub mod export;
+pub mod fragments;
+#[cfg(feature = "gui")]
+pub mod gui;
+pub mod journal;
+#[cfg(any(test, feature = "deterministic"))]
+pub mod kat;
+pub mod keychain;
+pub mod manifest;
 pub mod math;
+pub mod offline;
+#[cfg(feature = "paper")]
+pub mod paper;
+pub mod passphrase;
+pub mod pin;
+pub mod policy;
+pub mod registry;
+pub mod report;
+pub mod scan;
+pub mod secure;
+pub mod service;
+pub mod settings;
+pub mod transcript;
+pub mod wallet;
+pub mod webhook;
+
+/// Version of this program, recorded in the files it writes.
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
 
 /// Configuration when working in encrypt (c) mode.
 pub struct EncryptConfig {
     total_evals: usize,
     min_required_evals: usize,
-    input_file: String,
+    input_file: PathBuf,
+    output_dir: String,
     output_file: String,
-    password: String,
+    password: SecureString,
+    holders: Vec<String>,
+    paper: bool,
+    bundle: bool,
+    // the PIN of each share, empty for the shares without PIN
+    pins: Vec<String>,
+    suite: CipherSuite,
+    subkeys: bool,
+    digest: bool,
+    resume: bool,
+    threads: usize,
+    keyfile: Option<String>,
+    policy: Option<Policy>,
+    field: Option<Prime>,
+    scheme: Option<String>,
+    format: Format,
+    copy: Option<Duration>,
+    manifest_key: Option<String>,
+    // whether to write the public registry of the shares
+    registry: bool,
+    // where the owner keeps a share that unlocks the file alone
+    to_self: Option<SelfStore>,
+    // the public keys of the recovery agents of the file
+    recovery_agents: Vec<[u8; PUBLIC_KEY_SIZE]>,
+    // whether the file holds named secrets, see entries::parse
+    entries: bool,
+}
+
+// Where the share kept by the owner of a file is stored
+enum SelfStore {
+    // the keychain of the system, under the name of the file
+    Keychain,
+    Keyfile(String),
+}
+
+impl EncryptConfig {
+    /// Creates the configuration to encrypt a file with a password
+    /// and split its key into shares, with every other option as the
+    /// command line leaves it by default.
+    ///
+    /// # Parameters
+    ///
+    /// - input_file: The file to encrypt.
+    /// - output_dir: The directory of the encrypted file and the shares.
+    /// - output_file: The name of the encrypted file and the shares
+    ///   file, without their extensions.
+    /// - password: The password the key of the shares is derived from.
+    /// - total_evals: The number of shares, n.
+    /// - min_required_evals: The number of shares that recover the key, k.
+    pub fn new(
+        input_file: impl Into<PathBuf>,
+        output_dir: &str,
+        output_file: &str,
+        password: SecureString,
+        total_evals: usize,
+        min_required_evals: usize,
+    ) -> Self {
+        EncryptConfig {
+            total_evals,
+            min_required_evals,
+            input_file: input_file.into(),
+            output_dir: output_dir.into(),
+            output_file: output_file.into(),
+            password,
+            holders: Vec::new(),
+            paper: false,
+            bundle: false,
+            pins: Vec::new(),
+            suite: CipherSuite::Aes256Gcm,
+            subkeys: false,
+            digest: false,
+            resume: false,
+            threads: Concurrency::global().threads(),
+            keyfile: None,
+            policy: None,
+            field: None,
+            scheme: None,
+            format: Format::Lines,
+            copy: None,
+            manifest_key: None,
+            registry: false,
+            to_self: None,
+            recovery_agents: Vec::new(),
+            entries: false,
+        }
+    }
+
+    // the path of an output file, the output name with a suffix
+    // in the output directory
+    fn output_path(&self, suffix: &str) -> String {
+        format!("{}/{}{}", self.output_dir, self.output_file, suffix)
+    }
 }
 
 /// Configuration when working in decrypt (d) mode.
 pub struct DecryptConfig {
+    encrypted_file: String,
+    unlock: Unlock,
+    check: bool,
+    cross_check: bool,
+    copy: Option<Duration>,
+    max_memory: Option<u64>,
+    // the named secret printed instead of writing the file, see
+    // entries::get
+    get: Option<String>,
+    // the part of the file written to the standard output instead
+    reveal: Option<Reveal>,
+}
+
+impl DecryptConfig {
+    /// Creates the configuration to decrypt a file with its shares
+    /// into the current directory, under its original name, with
+    /// every other option as the command line leaves it by default.
+    ///
+    /// # Parameters
+    ///
+    /// - encrypted_file: The file to decrypt.
+    /// - shares_file: The file of the shares that recover its key.
+    pub fn new(encrypted_file: &str, shares_file: &str) -> Self {
+        DecryptConfig {
+            encrypted_file: encrypted_file.into(),
+            unlock: Unlock::Shares(shares_file.into()),
+            check: false,
+            cross_check: false,
+            copy: None,
+            max_memory: None,
+            get: None,
+            reveal: None,
+        }
+    }
+}
+
+// The part of an encrypted file decrypted instead of the whole file
+enum Reveal {
+    // the bytes from the start to the end, which is excluded, or to
+    // the end of the file
+    Range(u64, Option<u64>),
+    // a file of a tar archive, see archive::find
+    Entry(String),
+}
+
+/// Configuration when adding or removing the unlock
+/// methods of an encrypted file.
+pub struct MethodsConfig {
+    encrypted_file: String,
+    unlock: Unlock,
+    add_password: Option<SecureString>,
+    add_keyfile: Option<String>,
+    remove: Vec<UnlockMethod>,
+}
+
+// The secret given to unlock an encrypted file
+enum Unlock {
+    Shares(String),
+    // photos of QR codes, optionally with a shares file
+    Images(Option<String>, Vec<String>),
+    Password(SecureString),
+    Keyfile(String),
+    // the share kept in the keychain, under the name of the file
+    Keychain(String),
+    // the key file of a recovery agent
+    RecoveryKey(String),
+    // shares entered by their holders instead of read from a file
+    #[cfg(feature = "ceremony")]
+    Fragments(Vec<Fragment>),
+    // an agent that decrypts the file, with its socket and the file
+    #[cfg(unix)]
+    Agent(String, String),
+}
+
+/// Configuration when changing the password of an encrypted file.
+pub struct RotateConfig {
+    encrypted_file: String,
+    shares_file: String,
+    password: SecureString,
+}
+
+/// Configuration when recovering a file in a ceremony, with
+/// the holders entering their shares in the terminal.
+#[cfg_attr(not(feature = "ceremony"), allow(dead_code))]
+pub struct CeremonyConfig {
+    encrypted_file: String,
+    // where the transcript of the recovery is written, see transcript
+    transcript: Option<String>,
+}
+
+/// Configuration when splitting a passphrase.
+pub struct SplitPassphraseConfig {
+    passphrase: SecureString,
+    total_evals: usize,
+    min_required_evals: usize,
+}
+
+/// Configuration when recovering a passphrase from its shares.
+pub struct CombinePassphraseConfig {
+    shares: Vec<String>,
+    copy: Option<Duration>,
+}
+
+/// Configuration when splitting the private key of a wallet.
+pub struct SplitWalletConfig {
+    key: String,
+    format: wallet::Format,
+    total_evals: usize,
+    min_required_evals: usize,
+}
+
+/// Configuration when recovering the private key of a wallet.
+pub struct CombineWalletConfig {
+    shares: Vec<String>,
+    format: wallet::Format,
+    copy: Option<Duration>,
+}
+
+/// Configuration when splitting a secret typed in the terminal or
+/// read from the standard input.
+pub struct SplitConfig {
+    secret: SecretKey,
+    total_evals: usize,
+    min_required_evals: usize,
+    // the seed the shares are derived from, see
+    // shamir::split_secret_from_seed, and the only share printed
+    seed: Option<SecretKey>,
+    share: Option<usize>,
+}
+
+/// Configuration when generating a random key split into shares.
+pub struct KeygenConfig {
+    length: usize,
+    total_evals: usize,
+    min_required_evals: usize,
+    field: Option<Prime>,
+}
+
+/// Configuration when splitting one share again among the members
+/// of a group, such as a department that holds a single share.
+pub struct ResplitConfig {
+    shares_file: String,
+    share: String,
+    output_file: String,
+    total_evals: usize,
+    min_required_evals: usize,
+    holders: Vec<String>,
+}
+
+/// Configuration when recovering a share from the shares of its
+/// group.
+pub struct CombineGroupConfig {
+    shares_file: String,
+}
+
+/// Configuration when writing a key recovered from its shares
+/// for other programs.
+pub struct ShowKeyConfig {
+    shares_file: String,
+    format: export::KeyFormat,
+    fd: u32,
+}
+
+/// Configuration when printing the age recipient and identity
+/// of a key split into shares.
+pub struct AgeRecipientConfig {
+    shares_file: String,
+}
+
+/// Configuration when generating the operator key that signs
+/// the manifests.
+pub struct OperatorKeyConfig {
+    key_file: String,
+}
+
+/// Configuration when generating the key of a recovery agent.
+pub struct RecoveryAgentKeyConfig {
+    key_file: String,
+}
+
+/// Configuration when enrolling a holder in the holder tokens file
+/// of a server.
+pub struct EnrollHolderConfig {
+    tokens_file: String,
+    holder: String,
+    index: String,
+}
+
+/// Configuration when checking shares against the public registry
+/// of their encryption.
+pub struct CheckRegistryConfig {
+    registry: String,
+    shares_file: String,
+}
+
+/// Configuration when verifying a signed manifest and the files
+/// it lists.
+pub struct VerifyManifestConfig {
+    manifest: String,
+    public_key: Option<String>,
+}
+
+/// Configuration when a participant of a recovery signs its
+/// transcript.
+pub struct SignTranscriptConfig {
+    transcript: String,
+    key_file: String,
+    participant: String,
+}
+
+/// Configuration when verifying the signatures of the transcript
+/// of a recovery.
+pub struct VerifyTranscriptConfig {
+    transcript: String,
+    // the public key expected from some participants
+    public_keys: Vec<(String, String)>,
+}
+
+/// Configuration when holding a key in an agent, or stopping it.
+#[cfg_attr(not(unix), allow(dead_code))]
+pub struct AgentConfig {
+    encrypted_file: Option<String>,
+    shares_file: Option<String>,
+    socket: String,
+    stop: bool,
+    webhooks: Webhooks,
+}
+
+/// Configuration when serving the operations over HTTP.
+#[cfg_attr(not(feature = "server"), allow(dead_code))]
+pub struct ServeConfig {
+    address: String,
+    token: String,
+    recovery: service::RecoveryPolicy,
+    limits: service::ClientLimits,
+    webhooks: Webhooks,
+    holders: Vec<service::EnrolledHolder>,
+    insecure_http: bool,
+}
+
+/// Configuration when calling an operation of a server.
+pub struct RemoteConfig {
+    operation: service::Operation,
+    file: String,
+    shares_file: Option<String>,
+    output: Option<String>,
+    scheme: Option<(usize, usize)>,
+    server: String,
+    token: String,
+    holder_tokens_file: Option<String>,
+    insecure_http: bool,
+}
+
+/// Configuration when working in inspect mode.
+pub struct InspectConfig {
+    encrypted_file: String,
+    shares_file: Option<String>,
+    fingerprint: Option<String>,
+}
+
+/// Configuration when looking for the reasons a file cannot be
+/// recovered.
+pub struct DoctorConfig {
+    encrypted_file: String,
+    shares_file: Option<String>,
+}
+
+/// Configuration when upgrading an encrypted file and its shares
+/// file to the current formats.
+pub struct MigrateConfig {
+    encrypted_file: String,
+    shares_file: String,
+    total_evals: Option<usize>,
+}
+
+/// Configuration when packing the share of each holder into an
+/// envelope with a cover sheet.
+pub struct EnvelopesConfig {
     encrypted_file: String,
     shares_file: String,
+    output_dir: String,
+    contact: Option<String>,
+}
+
+/// Configuration when writing a directory to recover an encrypted
+/// file on a computer without network.
+pub struct ExportOfflineConfig {
+    encrypted_file: String,
+    output_dir: String,
+    shares_file: Option<String>,
+    registry: Option<String>,
+    binary: Option<String>,
 }
 
 /// This enum represents a configuration to execute
@@ -36,189 +478,5401 @@ pub struct DecryptConfig {
 ///
 /// Since the cipher can either work in encrypt or decrypt
 /// mode, this enum contains to options Encrypt and Decrypt
-/// that wraps each of these modes.
+/// that wraps each of these modes. Inspect mode only reports
+/// information about an encrypted file and its shares, Doctor
+/// mode looks for the reasons they do not recover it, Migrate mode
+/// upgrades them to the current formats, ExportOffline mode writes
+/// what recovers a file on a computer without network, Envelopes
+/// mode packs the
+/// shares for their holders, Rotate
+/// mode replaces the password and shares of an encrypted file,
+/// Methods mode changes the ways an encrypted file can be
+/// unlocked, Ceremony mode decrypts a file with shares entered by
+/// their holders, SplitPassphrase and CombinePassphrase modes share
+/// a passphrase instead of a file, SplitWallet and CombineWallet
+/// modes share the private key of a wallet, Split mode splits a
+/// secret typed or piped to the program, Keygen mode splits a
+/// random key that is never shown, Resplit mode splits a share
+/// among the members of a group and CombineGroup mode recovers it,
+/// ShowKey mode writes a key
+/// recovered from its shares, AgeRecipient mode prints the age
+/// recipient and identity of a key, OperatorKey and VerifyManifest
+/// modes sign and check manifests, CheckRegistry mode checks shares
+/// against their public registry, RecoveryAgentKey mode generates
+/// the key of a recovery agent, SignTranscript and
+/// VerifyTranscript modes sign and check the transcript of a
+/// ceremony, Agent mode holds a recovered
+/// key to decrypt files without the shares, Serve mode serves the
+/// operations over HTTP, EnrollHolder mode enrolls the holders it
+/// takes shares from and Remote mode calls them, InitSettings mode writes
+/// the configuration file and Vectors mode prints the known-answer
+/// test vectors.
+// a configuration is made once for each run, so the size of the
+// largest variant does not matter
+#[allow(clippy::large_enum_variant)]
 pub enum Config {
     Encrypt(EncryptConfig),
     Decrypt(DecryptConfig),
+    Inspect(InspectConfig),
+    Doctor(DoctorConfig),
+    Migrate(MigrateConfig),
+    Envelopes(EnvelopesConfig),
+    ExportOffline(ExportOfflineConfig),
+    Rotate(RotateConfig),
+    Methods(MethodsConfig),
+    Ceremony(CeremonyConfig),
+    SplitPassphrase(SplitPassphraseConfig),
+    CombinePassphrase(CombinePassphraseConfig),
+    SplitWallet(SplitWalletConfig),
+    CombineWallet(CombineWalletConfig),
+    Split(SplitConfig),
+    Keygen(KeygenConfig),
+    Resplit(ResplitConfig),
+    CombineGroup(CombineGroupConfig),
+    ShowKey(ShowKeyConfig),
+    AgeRecipient(AgeRecipientConfig),
+    OperatorKey(OperatorKeyConfig),
+    RecoveryAgentKey(RecoveryAgentKeyConfig),
+    VerifyManifest(VerifyManifestConfig),
+    CheckRegistry(CheckRegistryConfig),
+    SignTranscript(SignTranscriptConfig),
+    VerifyTranscript(VerifyTranscriptConfig),
+    Agent(AgentConfig),
+    Serve(ServeConfig),
+    EnrollHolder(EnrollHolderConfig),
+    Remote(RemoteConfig),
+    InitSettings,
+    Vectors,
+    ClearClipboard(Duration),
 }
 
 impl Config {
     /// Creates a new configuration instance by parsing
     /// the arguments provided.
+    ///
+    /// The number of threads given with --threads is set as the
+    /// global Concurrency of the program.
     pub fn new(args: ArgMatches) -> Result<Config, Box<dyn Error>> {
+        parse_concurrency(&args)?.set_global();
         match args.subcommand() {
             ("c", Some(c_matches)) => {
-                let password = rpassword::read_password_from_tty(Some("Password to encrypt: "))?;
-                let total_evals = c_matches.value_of("N").unwrap().parse()?;
-                if total_evals <= 2 {
-                    return Err(Box::new(ArgumentError("N must be greater than 2".into())));
+                let settings = Settings::load()?;
+                let password = read_password("Password to encrypt: ")?;
+                let policy = utf8_value(c_matches, "POLICY")?
+                    .map(Policy::parse)
+                    .transpose()?;
+                let (total_evals, min_required_evals, holders) = match &policy {
+                    // the holders and the scheme come from the policy
+                    Some(policy) => (
+                        policy.holders().len(),
+                        policy.min_holders(),
+                        policy.holders().into_iter().map(String::from).collect(),
+                    ),
+                    None => {
+                        let (total_evals, min_required_evals) = parse_scheme(c_matches, &settings)?;
+                        let holders = parse_holders(c_matches, total_evals)?;
+                        (total_evals, min_required_evals, holders)
+                    }
+                };
+                let threads = Concurrency::global().threads();
+                let threads = match parse_max_memory(c_matches, &settings)? {
+                    Some(max_memory) => budget_threads(threads, max_memory)?,
+                    None => threads,
+                };
+                let mut scheme = utf8_value(c_matches, "SCHEME")?.map(String::from);
+                // the number of pieces of a ramp scheme is part of its name
+                match (scheme.as_deref(), utf8_value(c_matches, "PIECES")?) {
+                    (Some(scheme::RAMP_SCHEME), Some(pieces)) => {
+                        let pieces: usize = pieces.parse()?;
+                        if pieces == 0 || pieces >= min_required_evals {
+                            return Err(Box::new(ArgumentError(
+                                "L must be greater than 0 and lower than K".into(),
+                            )));
+                        }
+                        scheme = Some(format!("{}{}", scheme::RAMP_SCHEME, pieces));
+                    }
+                    (Some(scheme::RAMP_SCHEME), None) => {
+                        return Err(Box::new(ArgumentError(
+                            "the ramp scheme needs the number of pieces L, see --pieces".into(),
+                        )));
+                    }
+                    (_, Some(_)) => {
+                        return Err(Box::new(ArgumentError(
+                            "--pieces is only used by the ramp scheme".into(),
+                        )));
+                    }
+                    _ => (),
+                }
+                if c_matches.is_present("DETECT_CHEATERS") {
+                    let name = scheme.as_deref().unwrap_or(scheme::DEFAULT_SCHEME);
+                    scheme = Some(format!("{}{}", name, scheme::CHECKED_SUFFIX));
                 }
-                let min_required_evals = c_matches.value_of("K").unwrap().parse()?;
-                if min_required_evals <= 0 || min_required_evals > total_evals {
+                if let Some(name) = scheme.as_deref() {
+                    let chosen = scheme::from_name(name, None)?;
+                    if chosen.needs_all_shares() && min_required_evals != total_evals {
+                        return Err(Box::new(ArgumentError(format!(
+                            "the {} scheme needs all the shares, K must be equal to N",
+                            name
+                        ))));
+                    }
+                    if !chosen.uses_field() && c_matches.is_present("FIELD") {
+                        return Err(Box::new(ArgumentError(format!(
+                            "the {} scheme does not use a field",
+                            name.trim_end_matches(scheme::CHECKED_SUFFIX)
+                        ))));
+                    }
+                }
+                let pins = if c_matches.is_present("PIN") {
+                    read_pins(&holders, total_evals)?
+                } else {
+                    Vec::new()
+                };
+                let paper = c_matches.is_present("PAPER");
+                if paper && !cfg!(feature = "paper") {
                     return Err(Box::new(ArgumentError(
-                        "K must be greater than 0 and not greater than N".into(),
+                        "this program was compiled without support for paper backups".into(),
                     )));
                 }
                 Ok(Config::Encrypt(EncryptConfig {
-                    input_file: String::from(c_matches.value_of("INPUT").unwrap()),
-                    output_file: String::from(c_matches.value_of("OUTPUT_NAME").unwrap()),
+                    input_file: PathBuf::from(c_matches.value_of_os("INPUT").unwrap()),
+                    output_dir: match utf8_value(c_matches, "OUTPUT_DIR")? {
+                        Some(dir) => dir.into(),
+                        None => settings.output_dir.unwrap_or_else(|| ".".into()),
+                    },
+                    output_file: String::from(utf8_value(c_matches, "OUTPUT_NAME")?.unwrap()),
                     total_evals,
                     min_required_evals,
                     password,
+                    holders,
+                    paper,
+                    bundle: c_matches.is_present("BUNDLE"),
+                    pins,
+                    suite: match utf8_value(c_matches, "CIPHER")? {
+                        Some(suite) => suite.parse()?,
+                        None => settings.cipher.unwrap_or(CipherSuite::Aes256Gcm),
+                    },
+                    subkeys: c_matches.is_present("SUBKEYS"),
+                    digest: c_matches.is_present("DIGEST"),
+                    resume: c_matches.is_present("RESUME"),
+                    threads,
+                    keyfile: utf8_value(c_matches, "KEYFILE")?.map(String::from),
+                    policy,
+                    field: utf8_value(c_matches, "FIELD")?
+                        .map(shamir::parse_field)
+                        .transpose()?,
+                    scheme,
+                    format: match utf8_value(c_matches, "FORMAT")? {
+                        Some(format) => format.parse()?,
+                        None => settings.format.unwrap_or(Format::Lines),
+                    },
+                    copy: parse_copy(c_matches)?,
+                    manifest_key: utf8_value(c_matches, "MANIFEST_KEY")?.map(String::from),
+                    registry: c_matches.is_present("REGISTRY"),
+                    to_self: parse_to_self(c_matches, total_evals, min_required_evals)?,
+                    recovery_agents: parse_recovery_agents(c_matches)?,
+                    entries: c_matches.is_present("ENTRIES"),
+                }))
+            }
+            ("d", Some(d_matches)) => {
+                let settings = Settings::load()?;
+                Ok(Config::Decrypt(DecryptConfig {
+                    encrypted_file: String::from(d_matches.value_of("ENCRYPTED_FILE").unwrap()),
+                    unlock: parse_unlock(d_matches)?,
+                    check: d_matches.is_present("CHECK"),
+                    cross_check: d_matches.is_present("CROSS_CHECK"),
+                    copy: parse_copy(d_matches)?,
+                    max_memory: parse_max_memory(d_matches, &settings)?,
+                    get: utf8_value(d_matches, "GET")?.map(String::from),
+                    reveal: parse_reveal(d_matches)?,
                 }))
             }
-            ("d", Some(d_matches)) => Ok(Config::Decrypt(DecryptConfig {
-                shares_file: String::from(d_matches.value_of("SHARES").unwrap()),
+            ("inspect", Some(i_matches)) => Ok(Config::Inspect(InspectConfig {
+                encrypted_file: String::from(i_matches.value_of("ENCRYPTED_FILE").unwrap()),
+                shares_file: i_matches.value_of("SHARES").map(String::from),
+                fingerprint: i_matches.value_of("FINGERPRINT").map(String::from),
+            })),
+            ("doctor", Some(d_matches)) => Ok(Config::Doctor(DoctorConfig {
                 encrypted_file: String::from(d_matches.value_of("ENCRYPTED_FILE").unwrap()),
+                shares_file: d_matches.value_of("SHARES").map(String::from),
+            })),
+            ("migrate", Some(m_matches)) => Ok(Config::Migrate(MigrateConfig {
+                encrypted_file: String::from(m_matches.value_of("ENCRYPTED_FILE").unwrap()),
+                shares_file: String::from(m_matches.value_of("SHARES").unwrap()),
+                total_evals: m_matches.value_of("N").map(str::parse).transpose()?,
+            })),
+            ("envelopes", Some(e_matches)) => Ok(Config::Envelopes(EnvelopesConfig {
+                encrypted_file: String::from(e_matches.value_of("ENCRYPTED_FILE").unwrap()),
+                shares_file: String::from(e_matches.value_of("SHARES").unwrap()),
+                output_dir: String::from(e_matches.value_of("OUTPUT_DIR").unwrap()),
+                contact: utf8_value(e_matches, "CONTACT")?.map(String::from),
+            })),
+            ("export-offline", Some(e_matches)) => Ok(Config::ExportOffline(ExportOfflineConfig {
+                encrypted_file: String::from(e_matches.value_of("ENCRYPTED_FILE").unwrap()),
+                output_dir: String::from(e_matches.value_of("OUTPUT_DIR").unwrap()),
+                shares_file: e_matches.value_of("SHARES").map(String::from),
+                registry: e_matches.value_of("REGISTRY").map(String::from),
+                binary: e_matches.value_of("BINARY").map(String::from),
+            })),
+            ("rotate-password", Some(r_matches)) => {
+                let password = read_password("New password: ")?;
+                Ok(Config::Rotate(RotateConfig {
+                    encrypted_file: String::from(r_matches.value_of("ENCRYPTED_FILE").unwrap()),
+                    shares_file: String::from(r_matches.value_of("SHARES").unwrap()),
+                    password,
+                }))
+            }
+            ("methods", Some(m_matches)) => {
+                let unlock = parse_unlock(m_matches)?;
+                let add_password = if m_matches.is_present("ADD_PASSWORD") {
+                    Some(read_password("Password to add: ")?)
+                } else {
+                    None
+                };
+                let remove = m_matches
+                    .values_of("REMOVE")
+                    .map_or_else(Vec::new, |values| {
+                        values.filter_map(UnlockMethod::from_name).collect()
+                    });
+                Ok(Config::Methods(MethodsConfig {
+                    encrypted_file: String::from(m_matches.value_of("ENCRYPTED_FILE").unwrap()),
+                    unlock,
+                    add_password,
+                    add_keyfile: m_matches.value_of("ADD_KEYFILE").map(String::from),
+                    remove,
+                }))
+            }
+            ("ceremony", Some(c_matches)) => {
+                if !cfg!(feature = "ceremony") {
+                    return Err(Box::new(ArgumentError(
+                        "this program was compiled without support for ceremonies".into(),
+                    )));
+                }
+                Ok(Config::Ceremony(CeremonyConfig {
+                    encrypted_file: String::from(c_matches.value_of("ENCRYPTED_FILE").unwrap()),
+                    transcript: c_matches.value_of("TRANSCRIPT").map(String::from),
+                }))
+            }
+            ("split-passphrase", Some(s_matches)) => {
+                let passphrase = read_password("Passphrase: ")?;
+                let repeated = read_password("Repeat the passphrase: ")?;
+                if passphrase != repeated {
+                    return Err(Box::new(ArgumentError(
+                        "the passphrases do not match".into(),
+                    )));
+                }
+                Ok(Config::SplitPassphrase(SplitPassphraseConfig {
+                    passphrase,
+                    total_evals: s_matches.value_of("N").unwrap().parse()?,
+                    min_required_evals: s_matches.value_of("K").unwrap().parse()?,
+                }))
+            }
+            ("combine-passphrase", Some(c_matches)) => {
+                let shares = read_typed_shares()?;
+                Ok(Config::CombinePassphrase(CombinePassphraseConfig {
+                    shares,
+                    copy: parse_copy(c_matches)?,
+                }))
+            }
+            ("split", Some(s_matches)) => {
+                let (total_evals, min_required_evals) =
+                    parse_scheme(s_matches, &Settings::load()?)?;
+                let secret = if s_matches.is_present("PROMPT") {
+                    let secret = read_password("Secret: ")?;
+                    let repeated = read_password("Repeat the secret: ")?;
+                    if secret != repeated {
+                        return Err(Box::new(ArgumentError("the secrets do not match".into())));
+                    }
+                    SecretKey::new(trim_newlines(&secret).as_bytes().to_vec())
+                } else {
+                    let mut secret = Vec::new();
+                    io::stdin().read_to_end(&mut secret)?;
+                    SecretKey::new(secret)
+                };
+                if secret.is_empty() {
+                    return Err(Box::new(ArgumentError("the secret is empty".into())));
+                }
+                let seed = if s_matches.is_present("SEED") {
+                    let seed = read_password("Seed (hexadecimal): ")?;
+                    let seed = encoding::from_hex(seed.trim())
+                        .ok_or_else(|| ArgumentError("the seed must be in hexadecimal".into()))?;
+                    Some(SecretKey::new(seed))
+                } else {
+                    None
+                };
+                let share = utf8_value(s_matches, "SHARE")?
+                    .map(str::parse)
+                    .transpose()?;
+                if share.is_some() && seed.is_none() {
+                    return Err(Box::new(ArgumentError(
+                        "--share makes a share again, it needs the --seed".into(),
+                    )));
+                }
+                Ok(Config::Split(SplitConfig {
+                    secret,
+                    total_evals,
+                    min_required_evals,
+                    seed,
+                    share,
+                }))
+            }
+            ("keygen", Some(k_matches)) => {
+                let (total_evals, min_required_evals) =
+                    parse_scheme(k_matches, &Settings::load()?)?;
+                let length: usize = k_matches.value_of("LENGTH").unwrap().parse()?;
+                if length == 0 {
+                    return Err(Box::new(ArgumentError(
+                        "the length of the key must be greater than 0".into(),
+                    )));
+                }
+                Ok(Config::Keygen(KeygenConfig {
+                    length,
+                    total_evals,
+                    min_required_evals,
+                    field: utf8_value(k_matches, "FIELD")?
+                        .map(shamir::parse_field)
+                        .transpose()?,
+                }))
+            }
+            ("resplit", Some(r_matches)) => {
+                let (total_evals, min_required_evals) =
+                    parse_scheme(r_matches, &Settings::load()?)?;
+                Ok(Config::Resplit(ResplitConfig {
+                    shares_file: String::from(r_matches.value_of("SHARES").unwrap()),
+                    share: utf8_value(r_matches, "SHARE")?.unwrap().to_string(),
+                    output_file: String::from(r_matches.value_of("OUTPUT").unwrap()),
+                    total_evals,
+                    min_required_evals,
+                    holders: parse_holders(r_matches, total_evals)?,
+                }))
+            }
+            ("combine-group", Some(c_matches)) => Ok(Config::CombineGroup(CombineGroupConfig {
+                shares_file: String::from(c_matches.value_of("SHARES").unwrap()),
+            })),
+            ("show-key", Some(s_matches)) => {
+                if !s_matches.is_present("I_KNOW") {
+                    return Err(Box::new(ArgumentError(
+                        "show-key writes the key itself, confirm it with --i-know-this-prints-a-secret"
+                            .into(),
+                    )));
+                }
+                Ok(Config::ShowKey(ShowKeyConfig {
+                    shares_file: String::from(s_matches.value_of("SHARES").unwrap()),
+                    format: s_matches.value_of("FORMAT").unwrap().parse()?,
+                    fd: s_matches.value_of("FD").unwrap().parse()?,
+                }))
+            }
+            ("age-recipient", Some(a_matches)) => Ok(Config::AgeRecipient(AgeRecipientConfig {
+                shares_file: String::from(a_matches.value_of("SHARES").unwrap()),
+            })),
+            ("operator-key", Some(o_matches)) => Ok(Config::OperatorKey(OperatorKeyConfig {
+                key_file: String::from(o_matches.value_of("KEY_FILE").unwrap()),
+            })),
+            ("recovery-agent-key", Some(r_matches)) => {
+                Ok(Config::RecoveryAgentKey(RecoveryAgentKeyConfig {
+                    key_file: String::from(r_matches.value_of("KEY_FILE").unwrap()),
+                }))
+            }
+            ("verify-manifest", Some(v_matches)) => {
+                Ok(Config::VerifyManifest(VerifyManifestConfig {
+                    manifest: String::from(v_matches.value_of("MANIFEST").unwrap()),
+                    public_key: v_matches.value_of("PUBLIC_KEY").map(String::from),
+                }))
+            }
+            ("check-registry", Some(c_matches)) => Ok(Config::CheckRegistry(CheckRegistryConfig {
+                registry: String::from(c_matches.value_of("REGISTRY").unwrap()),
+                shares_file: String::from(c_matches.value_of("SHARES").unwrap()),
+            })),
+            ("sign-transcript", Some(s_matches)) => {
+                Ok(Config::SignTranscript(SignTranscriptConfig {
+                    transcript: String::from(s_matches.value_of("TRANSCRIPT").unwrap()),
+                    key_file: String::from(s_matches.value_of("KEY_FILE").unwrap()),
+                    participant: String::from(s_matches.value_of("PARTICIPANT").unwrap()),
+                }))
+            }
+            ("verify-transcript", Some(v_matches)) => {
+                let public_keys = v_matches
+                    .values_of("PUBLIC_KEY")
+                    .into_iter()
+                    .flatten()
+                    .map(|value| match value.split_once('=') {
+                        Some((participant, key)) if !participant.is_empty() => {
+                            Ok((participant.to_string(), key.to_string()))
+                        }
+                        _ => Err(ArgumentError(format!(
+                            "'{}' is not <participant>=<public key>",
+                            value
+                        ))),
+                    })
+                    .collect::<Result<_, _>>()?;
+                Ok(Config::VerifyTranscript(VerifyTranscriptConfig {
+                    transcript: String::from(v_matches.value_of("TRANSCRIPT").unwrap()),
+                    public_keys,
+                }))
+            }
+            ("agent", Some(a_matches)) => {
+                if !cfg!(unix) {
+                    return Err(Box::new(ArgumentError(
+                        "the agent needs Unix sockets, which this system does not have".into(),
+                    )));
+                }
+                let shares_file = a_matches.value_of("SHARES").map(String::from);
+                if shares_file.is_none()
+                    && !a_matches.is_present("STOP")
+                    && !cfg!(feature = "ceremony")
+                {
+                    return Err(Box::new(ArgumentError(
+                        "this program was compiled without support for ceremonies, give the shares file"
+                            .into(),
+                    )));
+                }
+                Ok(Config::Agent(AgentConfig {
+                    encrypted_file: a_matches.value_of("ENCRYPTED_FILE").map(String::from),
+                    shares_file,
+                    socket: String::from(a_matches.value_of("SOCKET").unwrap()),
+                    stop: a_matches.is_present("STOP"),
+                    webhooks: parse_webhooks(a_matches)?,
+                }))
+            }
+            ("serve", Some(s_matches)) => {
+                if !cfg!(feature = "server") {
+                    return Err(Box::new(ArgumentError(
+                        "this program was compiled without support for the server".into(),
+                    )));
+                }
+                let delay = match utf8_value(s_matches, "RECOVERY_DELAY")? {
+                    Some(seconds) => Duration::from_secs(seconds.parse()?),
+                    None => Duration::ZERO,
+                };
+                Ok(Config::Serve(ServeConfig {
+                    address: String::from(s_matches.value_of("LISTEN").unwrap()),
+                    token: parse_token(s_matches)?,
+                    recovery: service::RecoveryPolicy {
+                        delay,
+                        notify: utf8_value(s_matches, "NOTIFY")?.map(String::from),
+                    },
+                    limits: parse_client_limits(s_matches)?,
+                    webhooks: parse_webhooks(s_matches)?,
+                    holders: match s_matches.value_of("HOLDER_TOKENS") {
+                        Some(path) => service::read_holder_tokens(path)?,
+                        None => Vec::new(),
+                    },
+                    insecure_http: s_matches.is_present("INSECURE_HTTP"),
+                }))
+            }
+            ("enroll-holder", Some(e_matches)) => Ok(Config::EnrollHolder(EnrollHolderConfig {
+                tokens_file: String::from(e_matches.value_of("TOKENS_FILE").unwrap()),
+                holder: String::from(utf8_value(e_matches, "HOLDER")?.unwrap()),
+                index: String::from(e_matches.value_of("INDEX").unwrap()),
             })),
+            ("remote", Some(r_matches)) => {
+                let operation: service::Operation =
+                    r_matches.value_of("OPERATION").unwrap().parse()?;
+                let shares_file = r_matches.value_of("SHARES").map(String::from);
+                let output = r_matches.value_of("OUTPUT").map(String::from);
+                let scheme = match operation {
+                    service::Operation::Split | service::Operation::Encrypt => {
+                        Some(parse_scheme(r_matches, &Settings::load()?)?)
+                    }
+                    _ => None,
+                };
+                if operation == service::Operation::Decrypt && shares_file.is_none() {
+                    return Err(Box::new(ArgumentError(
+                        "decrypt needs the shares file".into(),
+                    )));
+                }
+                if operation == service::Operation::Combine && output.is_none() {
+                    return Err(Box::new(ArgumentError(
+                        "combine writes the secret in a file, give it with --output".into(),
+                    )));
+                }
+                Ok(Config::Remote(RemoteConfig {
+                    operation,
+                    file: String::from(r_matches.value_of("FILE").unwrap()),
+                    shares_file,
+                    output,
+                    scheme,
+                    server: String::from(r_matches.value_of("SERVER").unwrap()),
+                    token: parse_token(r_matches)?,
+                    holder_tokens_file: r_matches.value_of("HOLDER_TOKENS").map(String::from),
+                    insecure_http: r_matches.is_present("INSECURE_HTTP"),
+                }))
+            }
+            ("split-wallet-key", Some(s_matches)) => {
+                let key = rpassword::read_password_from_tty(Some("Key (hexadecimal): "))?;
+                Ok(Config::SplitWallet(SplitWalletConfig {
+                    key,
+                    format: parse_wallet_format(s_matches)?,
+                    total_evals: s_matches.value_of("N").unwrap().parse()?,
+                    min_required_evals: s_matches.value_of("K").unwrap().parse()?,
+                }))
+            }
+            ("combine-wallet-key", Some(c_matches)) => {
+                let format = parse_wallet_format(c_matches)?;
+                let shares = read_typed_shares()?;
+                Ok(Config::CombineWallet(CombineWalletConfig {
+                    shares,
+                    format,
+                    copy: parse_copy(c_matches)?,
+                }))
+            }
+            ("config", Some(c_matches)) => match c_matches.subcommand() {
+                ("init", Some(_)) => Ok(Config::InitSettings),
+                _ => panic!(),
+            },
+            ("vectors", Some(_)) => {
+                if !cfg!(feature = "deterministic") {
+                    return Err(Box::new(ArgumentError(
+                        "this program was compiled without support for test vectors".into(),
+                    )));
+                }
+                Ok(Config::Vectors)
+            }
+            ("clear-clipboard", Some(c_matches)) => {
+                let seconds = utf8_value(c_matches, "TIMEOUT")?.unwrap().parse()?;
+                Ok(Config::ClearClipboard(Duration::from_secs(seconds)))
+            }
             _ => panic!(),
         }
     }
 }
 
-/// Runs the program, encrypting or decrypting the file according
-/// to the configuration passed.
-///
-/// # Parameters
-///
-/// - config: The enum with the given configuration mode.
-///
-/// # Errors
-///
-/// This method returns an error if an error occurs
-/// while encrypting or decrypting
-pub fn run(config: Config) -> Result<(), Box<dyn Error>> {
-    match config {
-        Config::Encrypt(config) => run_encrypt(config),
-        Config::Decrypt(config) => run_decrypt(config),
+// Reads the value of an argument that must be valid UTF-8; the
+// encrypt command accepts invalid UTF-8 only in the input path
+fn utf8_value<'a>(matches: &'a ArgMatches, name: &str) -> Result<Option<&'a str>, ArgumentError> {
+    match matches.value_of_os(name) {
+        Some(value) => match value.to_str() {
+            Some(value) => Ok(Some(value)),
+            None => Err(ArgumentError(format!("{} must be valid UTF-8", name))),
+        },
+        None => Ok(None),
     }
 }
 
-// Runs the program in encrypt mode
-fn run_encrypt(config: EncryptConfig) -> Result<(), Box<dyn Error>> {
-    let cipher = Cipher::new(&config.password);
-    encrypt_file(&config, &cipher)?;
-    save_shares(&config, &cipher)?;
-    Ok(())
+// Reads where the owner keeps their share, which leaves the others
+// to recover the file, so they must be enough
+fn parse_to_self(
+    matches: &ArgMatches,
+    total_evals: usize,
+    min_required_evals: usize,
+) -> Result<Option<SelfStore>, ArgumentError> {
+    let store = match utf8_value(matches, "TO_SELF")? {
+        Some("keychain") => SelfStore::Keychain,
+        Some(path) => SelfStore::Keyfile(path.into()),
+        None => return Ok(None),
+    };
+    if total_evals <= min_required_evals {
+        return Err(ArgumentError(
+            "--to-self keeps one share, so N must be greater than K for the others to recover the file"
+                .into(),
+        ));
+    }
+    Ok(Some(store))
 }
 
-// Reads, encrypts and saves the result
-fn encrypt_file(config: &EncryptConfig, cipher: &Cipher) -> Result<(), Box<dyn Error>> {
-    let mut file_content = fs::read(&config.input_file)?;
-    cipher.encrypt(&mut file_content)?;
-    let output_file = create_file(format!("./{}.aes", config.output_file))?;
-    let mut writer = BufWriter::new(output_file);
-    let original_name = Path::new(&config.input_file)
-        .file_name()
-        .unwrap()
-        .to_str()
-        .unwrap();
-    // write name as first line
-    writer.write_all(original_name.as_bytes())?;
-    writer.write_all(b"\n")?;
-    // write encrypted file
-    writer.write_all(&file_content)?;
-    writer.flush()?;
-    Ok(())
+// Reads the public keys of the recovery agents given when encrypting
+fn parse_recovery_agents(
+    matches: &ArgMatches,
+) -> Result<Vec<[u8; PUBLIC_KEY_SIZE]>, ArgumentError> {
+    let agents = matches
+        .values_of("RECOVERY_AGENT")
+        .map_or_else(Vec::new, |values| values.collect())
+        .into_iter()
+        .map(escrow::parse_public_key)
+        .collect::<Result<Vec<_>, _>>()?;
+    if agents.len() > MAX_RECOVERY_SLOTS {
+        return Err(ArgumentError(format!(
+            "a file can have at most {} recovery agents",
+            MAX_RECOVERY_SLOTS
+        )));
+    }
+    Ok(agents)
 }
 
-// Save the shares in the disk
-fn save_shares(config: &EncryptConfig, cipher: &Cipher) -> Result<(), Box<dyn Error>> {
-    let shares_file = create_file(format!("./{}.frg", config.output_file))?;
-    let mut writer = BufWriter::new(shares_file);
-    for (x, y) in cipher.split_key(config.total_evals, config.min_required_evals) {
-        writer.write(x.as_bytes())?;
-        writer.write(b":")?;
-        writer.write(y.as_bytes())?;
-        writer.write(b"\n")?;
+// Reads the number of threads, given before or after the subcommand
+fn parse_concurrency(args: &ArgMatches) -> Result<Concurrency, ArgumentError> {
+    let threads = match args.subcommand() {
+        (_, Some(matches)) => utf8_value(matches, "THREADS")?,
+        _ => None,
+    };
+    let threads = match threads {
+        Some(threads) => Some(threads),
+        None => utf8_value(args, "THREADS")?,
+    };
+    Concurrency::parse(threads)
+}
+
+// Reads a password from the terminal without echo, into locked memory
+fn read_password(prompt: &str) -> io::Result<SecureString> {
+    rpassword::read_password_from_tty(Some(prompt)).map(SecureString::from)
+}
+
+// Reads the shares typed in the terminal without echo, one per line
+// up to an empty one
+fn read_typed_shares() -> Result<Vec<String>, Box<dyn Error>> {
+    let mut prompt = "Enter the shares one per line, and an empty line to finish\nShare: ";
+    let mut shares = Vec::new();
+    loop {
+        let share = rpassword::read_password_from_tty(Some(prompt))?;
+        if share.trim().is_empty() {
+            return Ok(shares);
+        }
+        shares.push(share);
+        prompt = "Share: ";
     }
-    writer.flush()?;
-    Ok(())
 }
 
-// creates a file failing if already exists
-fn create_file(path: String) -> Result<fs::File, std::io::Error> {
-    OpenOptions::new()
-        .create_new(true)
-        .read(true)
-        .write(true)
-        .open(path)
+// Reads the secret given to unlock a file, which must be
+// either the shares file, a password or a key file
+fn parse_unlock(matches: &ArgMatches) -> Result<Unlock, Box<dyn Error>> {
+    let shares_file = matches.value_of("SHARES");
+    let keyfile = matches.value_of("KEYFILE");
+    let password = matches.is_present("PASSWORD");
+    let images: Vec<String> = matches
+        .values_of("SHARE_IMAGE")
+        .map_or_else(Vec::new, |values| values.map(String::from).collect());
+    if let Some(socket) = matches.value_of("AGENT") {
+        if shares_file.is_some() || keyfile.is_some() || password || !images.is_empty() {
+            return Err(Box::new(ArgumentError(
+                "--agent cannot be used with the shares, --password or --keyfile".into(),
+            )));
+        }
+        return agent_unlock(socket, matches);
+    }
+    if let Some(key_file) = matches.value_of("RECOVERY_KEY") {
+        if shares_file.is_some() || keyfile.is_some() || password || !images.is_empty() {
+            return Err(Box::new(ArgumentError(
+                "--recovery-key cannot be used with the shares, --password or --keyfile".into(),
+            )));
+        }
+        return Ok(Unlock::RecoveryKey(key_file.into()));
+    }
+    if matches.is_present("KEYCHAIN") {
+        if shares_file.is_some() || keyfile.is_some() || password || !images.is_empty() {
+            return Err(Box::new(ArgumentError(
+                "--keychain cannot be used with the shares, --password or --keyfile".into(),
+            )));
+        }
+        return Ok(Unlock::Keychain(file_name(
+            matches.value_of("ENCRYPTED_FILE").unwrap(),
+        )));
+    }
+    match (shares_file, keyfile, password) {
+        (shares_file, None, false) if !images.is_empty() => {
+            Ok(Unlock::Images(shares_file.map(String::from), images))
+        }
+        (Some(shares_file), None, false) => Ok(Unlock::Shares(shares_file.into())),
+        (None, Some(keyfile), false) => Ok(Unlock::Keyfile(keyfile.into())),
+        (None, None, true) => Ok(Unlock::Password(read_password("Password to decrypt: ")?)),
+        _ => Err(Box::new(ArgumentError(
+            "give exactly one of the shares, --password or --keyfile".into(),
+        ))),
+    }
 }
 
-// Runs the program in decrypt mode
-fn run_decrypt(config: DecryptConfig) -> Result<(), Box<dyn Error>> {
-    let cipher = Cipher::from_shares(recover_key(&config)?.into_iter())?;
-    decrypt_file(&config, &cipher)?;
-    Ok(())
+// Decrypts a file with an agent that holds its key
+#[cfg(unix)]
+fn agent_unlock(socket: &str, matches: &ArgMatches) -> Result<Unlock, Box<dyn Error>> {
+    Ok(Unlock::Agent(
+        socket.into(),
+        matches.value_of("ENCRYPTED_FILE").unwrap().into(),
+    ))
 }
 
-// Recovers the key from the shares file
-fn recover_key(config: &DecryptConfig) -> Result<Vec<Share>, Box<dyn Error>> {
-    let reader = BufReader::new(File::open(&config.shares_file)?);
-    Ok(reader
-        .lines()
-        .map::<Result<Share, Box<dyn Error>>, _>(|l| {
-            let line = l?;
-            let eval: Vec<&str> = line.split(":").collect();
-            let x = eval[0].trim().to_string();
-            let y = eval[1].trim().to_string();
-            if x.len() == 0 || y.len() == 0 || eval.len() != 2 {
-                return Err(Box::new(CorruptFileError(
-                    "fragments file is corrupt".into(),
-                )));
-            }
-            Ok((x, y))
-        })
-        .collect::<Result<Vec<Share>, _>>()?)
+#[cfg(not(unix))]
+fn agent_unlock(_: &str, _: &ArgMatches) -> Result<Unlock, Box<dyn Error>> {
+    Err(Box::new(ArgumentError(
+        "the agent needs Unix sockets, which this system does not have".into(),
+    )))
 }
 
-// decrypts the file and writes the result in disk
-fn decrypt_file(config: &DecryptConfig, cipher: &Cipher) -> Result<(), Box<dyn Error>> {
-    let mut reader = BufReader::new(File::open(&config.encrypted_file)?);
-    // read original name
-    let mut original_name = String::new();
-    reader.read_line(&mut original_name)?;
-    // remove the newline char
-    let original_name = original_name.replace("\n", "");
-    // Read rest of file
-    let file_length = fs::metadata(&config.encrypted_file)?.len();
-    let mut file_content = Vec::with_capacity(file_length.try_into()?);
-    reader.read_to_end(&mut file_content)?;
-    // decrypt file
-    cipher.decrypt(&mut file_content)?;
-    // save file
-    let output_file = create_file(format!("{}", original_name))?;
-    let mut writer = BufWriter::new(output_file);
-    writer.write_all(&file_content)?;
-    writer.flush()?;
-    Ok(())
+// Reads the token of the server from the file given, without the
+// whitespace at its ends
+fn parse_token(matches: &ArgMatches) -> Result<String, Box<dyn Error>> {
+    let content = read_keyfile(matches.value_of("TOKEN_FILE").unwrap())?;
+    let token = String::from_utf8(content)
+        .map_err(|_| ArgumentError("the token must be valid UTF-8".into()))?
+        .trim()
+        .to_string();
+    if token.is_empty() || token.contains(char::is_whitespace) {
+        return Err(Box::new(ArgumentError(
+            "the token file must have a single word".into(),
+        )));
+    }
+    Ok(token)
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+// Reads how many requests the server takes from each client; 0
+// means no limit
+fn parse_client_limits(matches: &ArgMatches) -> Result<service::ClientLimits, Box<dyn Error>> {
+    let value = |name| -> Result<Option<u32>, Box<dyn Error>> {
+        let value: u32 = matches.value_of(name).unwrap().parse()?;
+        Ok(Some(value).filter(|&value| value > 0))
+    };
+    Ok(service::ClientLimits {
+        requests_per_minute: value("RATE_LIMIT")?,
+        max_failures: value("MAX_FAILURES")?,
+        lockout: Duration::from_secs(matches.value_of("LOCKOUT").unwrap().parse()?),
+    })
+}
 
-    #[test]
-    fn integration() {
-        let encrypt_config = EncryptConfig {
-            total_evals: 5,
-            min_required_evals: 4,
-            input_file: "test_data/msg1.txt".into(),
-            output_file: "ciphered".into(),
-            password: "secure password".into(),
-        };
+// Reads the URLs of the webhooks told of the events
+fn parse_webhooks(matches: &ArgMatches) -> Result<Webhooks, Box<dyn Error>> {
+    let urls = matches
+        .values_of("WEBHOOK")
+        .map_or_else(Vec::new, |values| values.map(String::from).collect());
+    Ok(Webhooks::parse(&urls)?)
+}
+
+// Reads how long the copied shares or file stay in the
+// clipboard, None if nothing is copied
+fn parse_copy(matches: &ArgMatches) -> Result<Option<Duration>, Box<dyn Error>> {
+    if !matches.is_present("COPY") {
+        return Ok(None);
+    }
+    let seconds = utf8_value(matches, "COPY_TIMEOUT")?.unwrap().parse()?;
+    Ok(Some(Duration::from_secs(seconds)))
+}
+
+// Reads the part of the file of the arguments --range and --entry
+fn parse_reveal(matches: &ArgMatches) -> Result<Option<Reveal>, Box<dyn Error>> {
+    if let Some(entry) = utf8_value(matches, "ENTRY")? {
+        return Ok(Some(Reveal::Entry(entry.to_string())));
+    }
+    let range = match utf8_value(matches, "RANGE")? {
+        Some(range) => range,
+        None => return Ok(None),
+    };
+    let invalid = || ArgumentError(format!("invalid range: {}, use START:END", range));
+    let (start, end) = range.split_once(':').ok_or_else(invalid)?;
+    let start = settings::parse_size(start).ok_or_else(invalid)?;
+    let end = match end {
+        "" => None,
+        end => Some(
+            settings::parse_size(end)
+                .filter(|&end| end >= start)
+                .ok_or_else(invalid)?,
+        ),
+    };
+    Ok(Some(Reveal::Range(start, end)))
+}
+
+// Reads the memory budget of the argument --max-memory, or else
+// of the settings
+fn parse_max_memory(
+    matches: &ArgMatches,
+    settings: &Settings,
+) -> Result<Option<u64>, Box<dyn Error>> {
+    match utf8_value(matches, "MAX_MEMORY")? {
+        Some(size) => match settings::parse_size(size) {
+            Some(max_memory) => Ok(Some(max_memory)),
+            None => Err(Box::new(ArgumentError(format!(
+                "invalid amount of memory: {}",
+                size
+            )))),
+        },
+        None => Ok(settings.max_memory),
+    }
+}
+
+// The number of threads whose chunks fit in the memory budget, as
+// each one holds a chunk being encrypted and another waiting for it;
+// the encryption streams, so the size of the input does not matter
+fn budget_threads(threads: usize, max_memory: u64) -> Result<usize, Box<dyn Error>> {
+    let per_thread = 2 * SEALED_CHUNK_SIZE as u64;
+    if max_memory < per_thread {
+        return Err(Box::new(ArgumentError(format!(
+            "the memory budget must be at least {} bytes, enough for the chunks of one thread",
+            per_thread
+        ))));
+    }
+    Ok(threads.min((max_memory / per_thread) as usize))
+}
+
+// Reads how the key of a wallet is split, which requires the
+// curve of the key unless it is a codex32 seed
+fn parse_wallet_format(matches: &ArgMatches) -> Result<wallet::Format, Box<dyn Error>> {
+    match (matches.value_of("FORMAT"), matches.value_of("CURVE")) {
+        (Some("codex32"), None) => Ok(wallet::Format::Codex32),
+        (Some("codex32"), Some(_)) => Err(Box::new(ArgumentError(
+            "codex32 seeds do not have a curve".into(),
+        ))),
+        (_, Some(curve)) => Ok(wallet::Format::Scalar(curve.parse()?)),
+        (_, None) => Err(Box::new(ArgumentError(
+            "the curve of the key is required".into(),
+        ))),
+    }
+}
+
+// Reads N and K, taking the ones missing in the arguments from the
+// configuration file
+fn parse_scheme(
+    matches: &ArgMatches,
+    settings: &Settings,
+) -> Result<(usize, usize), Box<dyn Error>> {
+    let value = |name: &str, default: Option<usize>| match utf8_value(matches, name)? {
+        Some(value) => Ok::<_, Box<dyn Error>>(Some(value.parse()?)),
+        None => Ok(default),
+    };
+    let (total_evals, min_required_evals) = match (value("N", settings.n)?, value("K", settings.k)?)
+    {
+        (Some(n), Some(k)) => (n, k),
+        _ => {
+            return Err(Box::new(ArgumentError(
+                "N and K are required, as arguments or in the configuration file".into(),
+            )))
+        }
+    };
+    check_scheme(total_evals, min_required_evals)?;
+    Ok((total_evals, min_required_evals))
+}
+
+// Checks the number of shares and the threshold of a scheme
+fn check_scheme(total_evals: usize, min_required_evals: usize) -> Result<(), ArgumentError> {
+    if total_evals <= 2 {
+        return Err(ArgumentError("N must be greater than 2".into()));
+    }
+    if total_evals > shamir::MAX_SHARES {
+        return Err(ArgumentError(format!(
+            "N must not be greater than {}",
+            shamir::MAX_SHARES
+        )));
+    }
+    if min_required_evals == 0 || min_required_evals > total_evals {
+        return Err(ArgumentError(
+            "K must be greater than 0 and not greater than N".into(),
+        ));
+    }
+    Ok(())
+}
+
+// Reads the holder names, requiring one per share
+fn parse_holders(matches: &ArgMatches, total_evals: usize) -> Result<Vec<String>, ArgumentError> {
+    let holders: Vec<String> = match matches.values_of_os("HOLDER") {
+        Some(values) => values
+            .map(|value| match value.to_str() {
+                Some(holder) => Ok(String::from(holder)),
+                None => Err(ArgumentError("holder names must be valid UTF-8".into())),
+            })
+            .collect::<Result<_, _>>()?,
+        None => return Ok(Vec::new()),
+    };
+    if holders.len() != total_evals {
+        return Err(ArgumentError(format!(
+            "expected {} holders but {} were given",
+            total_evals,
+            holders.len()
+        )));
+    }
+    for (i, holder) in holders.iter().enumerate() {
+        if !fragments::is_valid_holder(holder) {
+            return Err(ArgumentError(format!("invalid holder name '{}'", holder)));
+        }
+        if holders[..i].contains(holder) {
+            return Err(ArgumentError(format!("duplicated holder '{}'", holder)));
+        }
+    }
+    Ok(holders)
+}
+
+// Asks the PIN of each share, empty for a share without PIN,
+// typed twice so a typo does not lock the share
+fn read_pins(holders: &[String], total_evals: usize) -> Result<Vec<String>, Box<dyn Error>> {
+    let mut pins = Vec::with_capacity(total_evals);
+    for i in 0..total_evals {
+        let name = match holders.get(i) {
+            Some(holder) => format!("the share of {}", holder),
+            None => format!("share {}", i + 1),
+        };
+        let pin = rpassword::read_password_from_tty(Some(&format!(
+            "PIN of {} (empty for none): ",
+            name
+        )))?;
+        if !pin.is_empty() {
+            if !pin::is_valid_pin(&pin) {
+                return Err(Box::new(ArgumentError(format!(
+                    "a PIN must have between {} and {} digits",
+                    pin::MIN_PIN_LEN,
+                    pin::MAX_PIN_LEN
+                ))));
+            }
+            let mut repeated = rpassword::read_password_from_tty(Some("Repeat the PIN: "))?;
+            let same = pin == repeated;
+            repeated.zeroize();
+            if !same {
+                return Err(Box::new(ArgumentError(format!(
+                    "the PINs of {} do not match",
+                    name
+                ))));
+            }
+        }
+        pins.push(pin);
+    }
+    Ok(pins)
+}
+
+/// Runs the program, encrypting or decrypting the file according
+/// to the configuration passed.
+///
+/// # Parameters
+///
+/// - config: The enum with the given configuration mode.
+///
+/// # Errors
+///
+/// This method returns an error if an error occurs
+/// while encrypting or decrypting
+pub fn run(config: Config) -> Result<Report, Box<dyn Error>> {
+    match config {
+        Config::Encrypt(config) => run_encrypt(config),
+        Config::Decrypt(config) => run_decrypt(config),
+        Config::Inspect(config) => run_inspect(config),
+        Config::Doctor(config) => run_doctor(config),
+        Config::Migrate(config) => run_migrate(config),
+        Config::Envelopes(config) => run_envelopes(config),
+        Config::ExportOffline(config) => run_export_offline(config),
+        Config::Rotate(config) => run_rotate(config),
+        Config::Methods(config) => run_methods(config),
+        Config::Ceremony(config) => run_ceremony(config),
+        Config::SplitPassphrase(config) => run_split_passphrase(config),
+        Config::CombinePassphrase(config) => run_combine_passphrase(config),
+        Config::SplitWallet(config) => run_split_wallet(config),
+        Config::CombineWallet(config) => run_combine_wallet(config),
+        Config::Split(config) => run_split(config),
+        Config::Keygen(config) => run_keygen(config),
+        Config::Resplit(config) => run_resplit(config),
+        Config::CombineGroup(config) => run_combine_group(config),
+        Config::ShowKey(config) => run_show_key(config),
+        Config::AgeRecipient(config) => run_age_recipient(config),
+        Config::OperatorKey(config) => run_operator_key(config),
+        Config::RecoveryAgentKey(config) => run_recovery_agent_key(config),
+        Config::VerifyManifest(config) => run_verify_manifest(config),
+        Config::CheckRegistry(config) => run_check_registry(config),
+        Config::SignTranscript(config) => run_sign_transcript(config),
+        Config::VerifyTranscript(config) => run_verify_transcript(config),
+        Config::Agent(config) => run_agent(config),
+        Config::Serve(config) => run_serve(config),
+        Config::EnrollHolder(config) => run_enroll_holder(config),
+        Config::Remote(config) => run_remote(config),
+        Config::InitSettings => run_init_settings(),
+        Config::Vectors => run_vectors(),
+        Config::ClearClipboard(timeout) => run_clear_clipboard(timeout),
+    }
+}
+
+// Runs the program in encrypt mode
+fn run_encrypt(config: EncryptConfig) -> Result<Report, Box<dyn Error>> {
+    // the operator key is read before encrypting, so a wrong key
+    // does not leave files without a manifest
+    let manifest_key = match &config.manifest_key {
+        Some(_) if is_special_file(config.output_path(".aes")) => {
+            return Err(Box::new(ArgumentError(format!(
+                "{} is not a regular file, it cannot be listed in a manifest",
+                config.output_path(".aes")
+            ))));
+        }
+        Some(path) => Some(manifest::read_key(path)?),
+        None => None,
+    };
+    // the names of the secrets, checked before encrypting so a file
+    // that cannot be read back by name is not encrypted
+    let entries = if config.entries {
+        let mut text = String::from_utf8(fs::read(&config.input_file)?)
+            .map_err(|_| ArgumentError(format!("{} is not text", config.input_file.display())))?;
+        let entries = entries::parse(&text);
+        text.zeroize();
+        let names: Vec<String> = entries?
+            .into_iter()
+            .map(|(name, mut value)| {
+                value.zeroize();
+                name
+            })
+            .collect();
+        Some(names)
+    } else {
+        None
+    };
+    let kdf = encryption_kdf(&config)?;
+    let cipher = kdf.cipher(&config.password, config.suite);
+    // copied shares are never written to disk, and they are copied
+    // before encrypting, so an interrupted copy leaves no file that
+    // its shares cannot open
+    let mut fragments = match config.copy {
+        Some(timeout) => {
+            let fragments = new_fragments(&config, &cipher)?;
+            copy_shares(&fragments, timeout)?;
+            encrypt_file(&config, &cipher, kdf)?;
+            fragments
+        }
+        None => {
+            encrypt_file(&config, &cipher, kdf)?;
+            save_shares(&config, &cipher)?
+        }
+    };
+    let kept = match &config.to_self {
+        Some(store) => Some(keep_share(&config, &cipher, store, &mut fragments)?),
+        None => None,
+    };
+    let mut report = Report::new();
+    let mut outputs = vec![config.output_path(".aes")];
+    report.field("encrypted_file", config.output_path(".aes"));
+    let extension = format!(".{}", config.format.extension());
+    if config.copy.is_none() {
+        outputs.push(config.output_path(&extension));
+        report.field("shares_file", config.output_path(&extension));
+    }
+    if config.bundle {
+        outputs.push(config.output_path(bundle::EXTENSION));
+        report.field("bundle_file", config.output_path(bundle::EXTENSION));
+    }
+    if config.paper {
+        let sheets = (1..=fragments.len())
+            .map(|i| config.output_path(&format!("-{}.pdf", i)))
+            .collect::<Vec<_>>();
+        outputs.extend(sheets.iter().cloned());
+        report.field("sheets", sheets);
+    }
+    report.field("shares", shares_json(&fragments));
+    report.success(format!(
+        "encrypted {} to {}",
+        config.input_file.display(),
+        config.output_path(".aes")
+    ));
+    report.success(match config.copy {
+        Some(timeout) => format!(
+            "copied {} shares (threshold {}) to the clipboard, which is cleared in {} seconds",
+            fragments.len(),
+            config.min_required_evals,
+            timeout.as_secs()
+        ),
+        None => format!(
+            "wrote {} shares (threshold {}) to {}/",
+            fragments.len(),
+            config.min_required_evals,
+            config.output_dir
+        ),
+    });
+    for line in fingerprint_lines(&fragments) {
+        report.line(line);
+    }
+    report.field("set_fingerprint", fragments::set_fingerprint(&fragments));
+    if let Some(names) = entries {
+        report.line(format!("{} secrets: {}", names.len(), names.join(", ")));
+        report.field("entries", names);
+    }
+    if let Some((share, place)) = kept {
+        report.success(format!(
+            "kept share {} in {}, which decrypts the file alone",
+            share, place
+        ));
+        report.field("kept_share", place);
+    }
+    if config.registry {
+        let path = config.output_path(registry::EXTENSION);
+        let file_name = file_name(&config.output_path(".aes"));
+        let registry = registry::new(&file_name, config.min_required_evals, &fragments);
+        registry::write(&path, &registry)?;
+        outputs.push(path.clone());
+        report.line(format!("share registry: {}", path));
+        report.field("registry", path);
+    }
+    if let Some(key) = manifest_key {
+        let path = config.output_path(manifest::EXTENSION);
+        let scheme = match &config.policy {
+            Some(policy) => policy.to_string(),
+            None => config
+                .scheme
+                .clone()
+                .unwrap_or_else(|| scheme::DEFAULT_SCHEME.into()),
+        };
+        let fields = vec![
+            ("operation", "encrypt".into()),
+            ("scheme", scheme.into()),
+            ("threshold", config.min_required_evals.into()),
+            ("total", config.total_evals.into()),
+            ("cipher", config.suite.name().into()),
+            ("shares", shares_json(&fragments)),
+        ];
+        manifest::write(&path, fields, &outputs, &key)?;
+        report.line(format!("manifest: {}", path));
+        report.field("manifest", path);
+    }
+    Ok(report)
+}
+
+// The index, holder and fingerprint of each share, for the reports
+fn shares_json(fragments: &[Fragment]) -> Json {
+    Json::Array(
+        fragments
+            .iter()
+            .map(|fragment| {
+                Json::object(vec![
+                    ("index", fragment.share.0.as_str().into()),
+                    ("holder", fragment.holder.clone().into()),
+                    ("fingerprint", fragment.fingerprint().into()),
+                    ("pin", fragment.pin.is_some().into()),
+                ])
+            })
+            .collect(),
+    )
+}
+
+// The fingerprint of each share and of the whole set, which holders
+// read to each other to confirm they hold the right shares
+fn fingerprint_lines(fragments: &[Fragment]) -> Vec<String> {
+    let mut lines: Vec<String> = fragments
+        .iter()
+        .map(|fragment| {
+            format!(
+                "  share {}: {} ({})",
+                fragment.share.0,
+                fragment.holder.as_deref().unwrap_or("no holder"),
+                fragment.fingerprint()
+            )
+        })
+        .collect();
+    lines.push(format!(
+        "share set fingerprint: {}",
+        fragments::set_fingerprint(fragments)
+    ));
+    lines
+}
+
+// The derivation of the key of the password of a new encryption, or
+// the one of the interrupted encryption when it is resumed, so the
+// password gives the same key
+fn encryption_kdf(config: &EncryptConfig) -> Result<PasswordKdf, Box<dyn Error>> {
+    let output_path = config.output_path(".aes");
+    if config.resume && Path::new(&format!("{}.journal", output_path)).exists() {
+        // a missing part or one of another encryption is rejected
+        // when resuming
+        if let Ok(part) = File::open(format!("{}.part", output_path)) {
+            if let Ok(header) = Header::read_from(&mut BufReader::new(part)) {
+                if let Some(kdf) = header.key_slots.first().and_then(|slot| slot.kdf) {
+                    return Ok(kdf);
+                }
+            }
+        }
+    }
+    Ok(PasswordKdf::generate()?)
+}
+
+// Reads, encrypts and saves the result, keeping a journal
+// so that the encryption can be resumed if it is interrupted
+fn encrypt_file(
+    config: &EncryptConfig,
+    cipher: &Cipher,
+    kdf: PasswordKdf,
+) -> Result<(), Box<dyn Error>> {
+    let output_path = config.output_path(".aes");
+    let part_path = format!("{}.part", output_path);
+    let journal_path = format!("{}.journal", output_path);
+    // a FIFO or a device is written in place, without a journal
+    let special_output = is_special_file(&output_path);
+    if Path::new(&output_path).exists() && !special_output {
+        return Err(Box::new(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("{} already exists", output_path),
+        )));
+    }
+    if is_special_file(&config.input_file) && (config.digest || config.resume) {
+        return Err(Box::new(ArgumentError(format!(
+            "{} can only be read once, it cannot be used with --digest or --resume",
+            config.input_file.display()
+        ))));
+    }
+    if special_output && config.resume {
+        return Err(Box::new(ArgumentError(format!(
+            "{} is not a regular file, the encryption cannot be resumed",
+            output_path
+        ))));
+    }
+    let mut header = encrypted_header(config)?;
+    let mut reader = BufReader::new(File::open(&config.input_file)?);
+    let (mut writer, encryptor, first) = if config.resume && Path::new(&journal_path).exists() {
+        resume_encryption(
+            config,
+            cipher,
+            &mut header,
+            &part_path,
+            &journal_path,
+            &mut reader,
+        )?
+    } else {
+        let file = if special_output {
+            OpenOptions::new().write(true).open(&output_path)?
+        } else {
+            create_file(part_path.clone())?
+        };
+        let mut writer = BufWriter::new(file);
+        // the content is encrypted with a random key, stored wrapped
+        // by the key of the password
+        let data_key = Cipher::random(config.suite)?;
+        let aad = header.associated_data();
+        header.key_slots.push(KeySlot {
+            method: UnlockMethod::Shares,
+            kdf: Some(kdf),
+            wrapped_key: cipher.wrap_key(&data_key, &aad)?,
+        });
+        if let Some(keyfile) = &config.keyfile {
+            let keyfile_cipher = Cipher::from_keyfile(&read_keyfile(keyfile)?, config.suite);
+            header.key_slots.push(KeySlot {
+                method: UnlockMethod::Keyfile,
+                kdf: None,
+                wrapped_key: keyfile_cipher.wrap_key(&data_key, &aad)?,
+            });
+        }
+        for public_key in &config.recovery_agents {
+            let slot = escrow::wrap(&data_key, public_key, &aad, config.suite)?;
+            header.recovery_slots.push(slot);
+        }
+        let encryptor = data_key
+            .encryptor(config.subkeys, None)?
+            .with_threads(config.threads);
+        // write the header before the content
+        header.write_to(&mut writer)?;
+        writer.write_all(&encryptor.header())?;
+        (writer, encryptor, 0)
+    };
+    let checkpoint = |writer: &mut BufWriter<File>, chunks| -> Result<(), Box<dyn Error>> {
+        if special_output {
+            return Ok(());
+        }
+        // the chunks must be on disk before the journal says so
+        writer.flush()?;
+        writer.get_ref().sync_data()?;
+        let salt = encryptor.salt();
+        Ok(Checkpoint { salt, chunks }.save(&journal_path)?)
+    };
+    checkpoint(&mut writer, first)?;
+    // encrypt the file chunk by chunk, authenticating the header
+    let mut reader = CountingReader {
+        inner: reader,
+        length: first * CHUNK_SIZE as u64,
+    };
+    encryptor.encrypt_from(
+        &mut reader,
+        &mut writer,
+        &header.associated_data(),
+        first,
+        &checkpoint,
+    )?;
+    if header.length.is_some_and(|length| length != reader.length) {
+        return Err(Box::new(ArgumentError(format!(
+            "{} changed while it was encrypted",
+            config.input_file.display()
+        ))));
+    }
+    writer.flush()?;
+    if !special_output {
+        writer.get_ref().sync_all()?;
+        fs::rename(&part_path, &output_path)?;
+        fs::remove_file(&journal_path)?;
+    }
+    Ok(())
+}
+
+// Creates the header of the encrypted file
+fn encrypted_header(config: &EncryptConfig) -> Result<Header, Box<dyn Error>> {
+    let original_name = match config.input_file.file_name() {
+        Some(name) => name,
+        None => {
+            return Err(Box::new(ArgumentError(format!(
+                "{} is not a file",
+                config.input_file.display()
+            ))))
+        }
+    };
+    let mut header = Header::new(
+        container::name_to_bytes(original_name),
+        config.holders.clone(),
+        config.min_required_evals,
+        config.total_evals,
+        config.suite,
+    );
+    header.policy = config.policy.as_ref().map(Policy::to_string);
+    if config.digest {
+        // the digest goes in the header, so the file is read twice
+        let mut hasher = DigestWriter::new(io::sink());
+        io::copy(&mut File::open(&config.input_file)?, &mut hasher)?;
+        header.digest = Some(hasher.digest());
+        header.length = Some(hasher.length);
+    } else if !is_special_file(&config.input_file) {
+        // checked against the bytes read once the file is encrypted
+        header.length = Some(fs::metadata(&config.input_file)?.len());
+    }
+    Ok(header)
+}
+
+// Reopens the partial output of an interrupted encryption, leaving
+// the reader and the writer at the chunk of the last checkpoint
+fn resume_encryption(
+    config: &EncryptConfig,
+    cipher: &Cipher,
+    header: &mut Header,
+    part_path: &str,
+    journal_path: &str,
+    reader: &mut BufReader<File>,
+) -> Result<(BufWriter<File>, Encryptor, u64), Box<dyn Error>> {
+    let mismatch = || {
+        ArgumentError("the interrupted encryption used another file, password or options".into())
+    };
+    let checkpoint = Checkpoint::load(journal_path)?;
+    let mut file = OpenOptions::new().read(true).write(true).open(part_path)?;
+    // the data key of the interrupted encryption is in its header
+    let found = Header::read_from(&mut BufReader::new(&mut file)).map_err(|_| mismatch())?;
+    if found.associated_data() != header.associated_data() {
+        return Err(Box::new(mismatch()));
+    }
+    // the recovery slots were wrapped with ephemeral keys, so they
+    // are taken from the file once they are for the same agents
+    let agents: Vec<_> = found
+        .recovery_slots
+        .iter()
+        .map(|slot| slot.public_key)
+        .collect();
+    if agents != config.recovery_agents {
+        return Err(Box::new(mismatch()));
+    }
+    header.key_slots = found.key_slots;
+    header.recovery_slots = found.recovery_slots;
+    let data_key = unwrap_data_key(header, cipher).map_err(|_| mismatch())?;
+    let encryptor = data_key
+        .encryptor(config.subkeys, Some(checkpoint.salt))?
+        .with_threads(config.threads);
+    let mut expected = Vec::new();
+    header.write_to(&mut expected)?;
+    expected.extend_from_slice(&encryptor.header());
+    let offset = expected.len() as u64 + checkpoint.chunks * SEALED_CHUNK_SIZE as u64;
+    let mut found = vec![0u8; expected.len()];
+    file.seek(SeekFrom::Start(0))?;
+    if file.metadata()?.len() < offset || file.read_exact(&mut found).is_err() || found != expected
+    {
+        return Err(Box::new(mismatch()));
+    }
+    file.set_len(offset)?;
+    file.seek(SeekFrom::Start(offset))?;
+    reader.seek(SeekFrom::Start(checkpoint.chunks * CHUNK_SIZE as u64))?;
+    Ok((BufWriter::new(file), encryptor, checkpoint.chunks))
+}
+
+// Save the shares in the disk
+fn save_shares(config: &EncryptConfig, cipher: &Cipher) -> Result<Vec<Fragment>, Box<dyn Error>> {
+    let path = config.output_path(&format!(".{}", config.format.extension()));
+    write_atomically(path, |writer| {
+        let fragments = new_fragments(config, cipher)?;
+        fragments::write_fragments(&fragments, config.format, writer)?;
+        if config.bundle {
+            write_atomically(config.output_path(bundle::EXTENSION), |writer| {
+                Ok(bundle::write_bundle(&fragments, writer)?)
+            })?;
+        }
+        if config.paper {
+            for (i, fragment) in fragments.iter().enumerate() {
+                save_sheet(config, fragment, i + 1)?;
+            }
+        }
+        Ok(fragments)
+    })
+}
+
+// Splits the key of the cipher into the shares of the holders, each
+// masked with its PIN
+fn new_fragments(config: &EncryptConfig, cipher: &Cipher) -> Result<Vec<Fragment>, Box<dyn Error>> {
+    let fragments = split_fragments(
+        cipher,
+        config.policy.as_ref(),
+        config.total_evals,
+        config.min_required_evals,
+        &config.holders,
+        config.field.as_ref(),
+        config.scheme.as_deref(),
+    )?;
+    Ok(protect_fragments(fragments, &config.pins)?)
+}
+
+// Keeps the first share for the owner of the file, in the keychain or
+// in a new key file, and adds a key slot so it unlocks the file alone
+// as a key file does; the shares file is written again with the other
+// shares, which still recover the file if the share is lost
+fn keep_share(
+    config: &EncryptConfig,
+    cipher: &Cipher,
+    store: &SelfStore,
+    fragments: &mut Vec<Fragment>,
+) -> Result<(String, String), Box<dyn Error>> {
+    let fragment = fragments.remove(0);
+    let line = fragment.to_line();
+    let place = match store {
+        SelfStore::Keychain => {
+            keychain::store(&format!("{}.aes", config.output_file), &line)?;
+            String::from("the keychain")
+        }
+        SelfStore::Keyfile(path) => {
+            let mut options = OpenOptions::new();
+            options.write(true).create_new(true);
+            #[cfg(unix)]
+            std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+            let mut file = options.open(path)?;
+            file.write_all(line.as_bytes())?;
+            file.sync_all()?;
+            path.clone()
+        }
+    };
+    let mut file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(config.output_path(".aes"))?;
+    let mut header = Header::read_from(&mut BufReader::new(&mut file))?;
+    let offset = header.key_slots_offset();
+    let data_key = unwrap_data_key(&header, cipher)?;
+    let aad = header.associated_data();
+    header.key_slots.push(KeySlot {
+        method: UnlockMethod::Keyfile,
+        kdf: None,
+        wrapped_key: Cipher::from_keyfile(line.as_bytes(), config.suite)
+            .wrap_key(&data_key, &aad)?,
+    });
+    if header.key_slots.len() > KEY_SLOTS {
+        return Err(Box::new(ArgumentError(format!(
+            "a file can have at most {} unlock methods",
+            KEY_SLOTS
+        ))));
+    }
+    file.seek(SeekFrom::Start(offset))?;
+    header.write_key_slots(&mut file)?;
+    file.sync_all()?;
+    let path = config.output_path(&format!(".{}", config.format.extension()));
+    replace_atomically(path, |writer| {
+        Ok(fragments::write_fragments(
+            fragments,
+            config.format,
+            writer,
+        )?)
+    })?;
+    Ok((fragment.share.0, place))
+}
+
+// masks each share with its PIN, leaving the shares whose PIN is
+// empty as they are
+fn protect_fragments(
+    fragments: Vec<Fragment>,
+    pins: &[String],
+) -> Result<Vec<Fragment>, ArgumentError> {
+    let mut pins = pins.iter();
+    fragments
+        .into_iter()
+        .map(|fragment| match pins.next() {
+            Some(pin) if !pin.is_empty() => pin::protect(&fragment, pin),
+            _ => Ok(fragment),
+        })
+        .collect()
+}
+
+// Copies the shares to the clipboard one by one, waiting for each
+// holder to be ready; each share is cleared after the timeout unless
+// the next one replaced it
+fn copy_shares(fragments: &[Fragment], timeout: Duration) -> Result<(), Box<dyn Error>> {
+    for (i, fragment) in fragments.iter().enumerate() {
+        let name = match &fragment.holder {
+            Some(holder) => format!("the share of {}", holder),
+            None => format!("share {}", i + 1),
+        };
+        eprint!("Press Enter to copy {} of {}: ", name, fragments.len());
+        io::stderr().flush()?;
+        io::stdin().read_line(&mut String::new())?;
+        clipboard::copy_for(&fragment.to_line(), timeout)?;
+    }
+    Ok(())
+}
+
+// splits the key of the cipher with the policy, if there is one,
+// or else in k of n shares of the scheme and field given to the
+// holders in order
+fn split_fragments(
+    cipher: &Cipher,
+    policy: Option<&Policy>,
+    total: usize,
+    threshold: usize,
+    holders: &[String],
+    field: Option<&Prime>,
+    scheme: Option<&str>,
+) -> Result<Vec<Fragment>, ShamirError> {
+    Ok(match policy {
+        Some(policy) => cipher
+            .key()
+            .split_by(policy)
+            .into_iter()
+            .map(|(holder, share)| Fragment {
+                mac: Some(cipher.share_mac(&share)),
+                pin: None,
+                share,
+                holder: Some(holder),
+                scheme: None,
+                field: None,
+                length: Some(cipher.suite().key_size()),
+            })
+            .collect(),
+        None => {
+            let mut holders = holders.iter().cloned();
+            // the shares of the default scheme do not record it
+            let scheme = scheme.filter(|&name| name != scheme::DEFAULT_SCHEME);
+            let shares = match scheme {
+                Some(name) => {
+                    let scheme = scheme::from_name(name, field)?;
+                    cipher.key().split_with(scheme.as_ref(), total, threshold)?
+                }
+                None => cipher.key().split(total, threshold, field)?.evaluate_all(),
+            };
+            shares
+                .into_iter()
+                .map(|share| Fragment {
+                    mac: Some(cipher.share_mac(&share)),
+                    pin: None,
+                    share,
+                    holder: holders.next(),
+                    scheme: scheme.map(String::from),
+                    field: field.map(Prime::to_string),
+                    length: Some(cipher.suite().key_size()),
+                })
+                .collect()
+        }
+    })
+}
+
+// Saves the paper backup of a share
+#[cfg(feature = "paper")]
+fn save_sheet(
+    config: &EncryptConfig,
+    fragment: &Fragment,
+    index: usize,
+) -> Result<(), Box<dyn Error>> {
+    let sheet = paper::Sheet {
+        fragment,
+        file_name: &format!("{}.aes", config.output_file),
+        threshold: config.min_required_evals,
+        total: config.total_evals,
+    };
+    let sheet_path = config.output_path(&format!("-{}.pdf", index));
+    write_atomically(sheet_path, |writer| Ok(paper::write_sheet(&sheet, writer)?))
+}
+
+#[cfg(not(feature = "paper"))]
+fn save_sheet(_: &EncryptConfig, _: &Fragment, _: usize) -> Result<(), Box<dyn Error>> {
+    unreachable!("paper backups are rejected when parsing the arguments")
+}
+
+// writes a file through a temporary file NAME.RANDOM.tmp in the same
+// directory, which is renamed to the path once everything was written,
+// without replacing a file created meanwhile, and removed if writing
+// fails, so a crash never leaves a truncated file at the path
+fn write_atomically<T>(
+    path: impl AsRef<Path>,
+    write: impl FnOnce(&mut BufWriter<File>) -> Result<T, Box<dyn Error>>,
+) -> Result<T, Box<dyn Error>> {
+    let path = path.as_ref();
+    // a FIFO or a device cannot be replaced, so it is written in place
+    if is_special_file(path) {
+        let mut writer = BufWriter::new(OpenOptions::new().write(true).open(path)?);
+        let value = write(&mut writer)?;
+        writer.flush()?;
+        return Ok(value);
+    }
+    // fails before writing anything, the rename still refuses to
+    // replace a file created while writing
+    if path.exists() {
+        return Err(Box::new(already_exists(path)));
+    }
+    write_through_temporary(path, false, write)
+}
+
+// writes a file through a temporary file like write_atomically,
+// replacing the file at the path if there is one
+fn replace_atomically<T>(
+    path: impl AsRef<Path>,
+    write: impl FnOnce(&mut BufWriter<File>) -> Result<T, Box<dyn Error>>,
+) -> Result<T, Box<dyn Error>> {
+    write_through_temporary(path.as_ref(), true, write)
+}
+
+// writes a new temporary file, created with a random name that no
+// other file has, and renames it to the path; the temporary file is
+// removed when it is dropped before being renamed
+fn write_through_temporary<T>(
+    path: &Path,
+    replace: bool,
+    write: impl FnOnce(&mut BufWriter<File>) -> Result<T, Box<dyn Error>>,
+) -> Result<T, Box<dyn Error>> {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let mut prefix = path.file_name().unwrap_or_default().to_owned();
+    prefix.push(".");
+    let (file, tmp_path) = tempfile::Builder::new()
+        .prefix(&prefix)
+        .suffix(".tmp")
+        .tempfile_in(dir)?
+        .into_parts();
+    let mut writer = BufWriter::new(file);
+    let value = write(&mut writer)?;
+    writer.flush()?;
+    writer.get_ref().sync_all()?;
+    let persisted = if replace {
+        tmp_path.persist(path)
+    } else {
+        tmp_path.persist_noclobber(path)
+    };
+    match persisted {
+        Ok(()) => Ok(value),
+        Err(e) if e.error.kind() == io::ErrorKind::AlreadyExists => {
+            Err(Box::new(already_exists(path)))
+        }
+        Err(e) => Err(Box::new(e.error)),
+    }
+}
+
+// the error of an output file that is not replaced
+fn already_exists(path: &Path) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::AlreadyExists,
+        format!("{} already exists", path.display()),
+    )
+}
+
+// the name of the file of a path, without its directory
+fn file_name(path: &str) -> String {
+    Path::new(path)
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+// reads a key file to the end, which can also be a FIFO such as the
+// one of a process substitution, so its length is not known before
+fn read_keyfile(path: impl AsRef<Path>) -> io::Result<Vec<u8>> {
+    let mut content = Vec::new();
+    File::open(path)?.read_to_end(&mut content)?;
+    Ok(content)
+}
+
+// whether the path is neither a regular file nor a directory,
+// such as a FIFO or a character device
+fn is_special_file(path: impl AsRef<Path>) -> bool {
+    fs::metadata(path).is_ok_and(|metadata| !metadata.is_file() && !metadata.is_dir())
+}
+
+// creates a file failing if already exists
+fn create_file(path: String) -> Result<fs::File, std::io::Error> {
+    OpenOptions::new()
+        .create_new(true)
+        .read(true)
+        .write(true)
+        .open(path)
+}
+
+// Runs the program in decrypt mode
+fn run_decrypt(config: DecryptConfig) -> Result<Report, Box<dyn Error>> {
+    let mut reader = BufReader::new(File::open(&config.encrypted_file)?);
+    let header = Header::read_from(&mut reader)?;
+    if let Some(max_memory) = config.max_memory {
+        check_memory(&config, max_memory)?;
+    }
+    // the agent reads and decrypts the file itself, its keys never
+    // leave it
+    #[cfg(unix)]
+    if let Unlock::Agent(socket, encrypted_file) = &config.unlock {
+        if config.reveal.is_some() {
+            return Err(Box::new(ArgumentError(
+                "--reveal cannot be used with --agent, which decrypts the whole file".into(),
+            )));
+        }
+        return write_decrypted(&config, &header, |mut output| {
+            agent::decrypt(socket, encrypted_file, &mut output)
+        });
+    }
+    let cipher = unlock(&config.unlock, &header, config.cross_check)?;
+    if config.check {
+        // nothing is written, so the file is only verified
+        return write_decrypted(&config, &header, |_| {
+            verify_file(&header, &cipher, &mut reader)
+        });
+    }
+    if let Some(reveal) = &config.reveal {
+        let mut report = Report::new();
+        report.field("encrypted_file", config.encrypted_file.as_str());
+        let (offset, length) =
+            reveal_part(&header, &cipher, reader, reveal, &mut io::stdout().lock())?;
+        report.note(format!(
+            "{} bytes from offset {} of {} were decrypted; the digest of the whole file is not checked",
+            length, offset, config.encrypted_file
+        ));
+        if let Reveal::Entry(name) = reveal {
+            report.field("entry", name.as_str());
+        }
+        report.field("offset", Json::Number(offset));
+        report.field("length", Json::Number(length));
+        return Ok(report);
+    }
+    write_decrypted(&config, &header, |mut output| {
+        decrypt_into(&header, &cipher, &mut reader, &mut output)
+    })
+}
+
+// writes the content of the file where the configuration asks, the
+// file of its original name, the clipboard or one of its entries,
+// and reports it; with --check it is decrypted into nothing
+fn write_decrypted(
+    config: &DecryptConfig,
+    header: &Header,
+    decrypt: impl FnOnce(&mut dyn Write) -> Result<(), Box<dyn Error>>,
+) -> Result<Report, Box<dyn Error>> {
+    let mut report = Report::new();
+    report.field("encrypted_file", config.encrypted_file.as_str());
+    if config.check {
+        decrypt(&mut io::sink())?;
+        report.success(format!("{}: ok", config.encrypted_file));
+        report.field("verified", true);
+    } else if let Some(name) = &config.get {
+        let mut content = Vec::new();
+        let decrypted = decrypt(&mut content);
+        let value = decrypted.and_then(|_| {
+            let text = std::str::from_utf8(&content)
+                .map_err(|_| ArgumentError("only text files have named secrets".into()))?;
+            entries::get(text, name)
+        });
+        content.zeroize();
+        let mut value = value?;
+        match config.copy {
+            Some(timeout) => {
+                clipboard::copy_for(&value, timeout)?;
+                report.line(format!(
+                    "{} was copied to the clipboard, which is cleared in {} seconds",
+                    name,
+                    timeout.as_secs()
+                ));
+                report.field("copied", true);
+            }
+            None => report.line(value.as_str()),
+        }
+        value.zeroize();
+        report.field("entry", name.as_str());
+    } else if let Some(timeout) = config.copy {
+        let mut content = Vec::new();
+        let decrypted = decrypt(&mut content);
+        let text = decrypted.and_then(|_| {
+            String::from_utf8(content).map_err(|e| {
+                e.into_bytes().zeroize();
+                Box::new(ArgumentError(
+                    "only text files can be copied to the clipboard".into(),
+                )) as Box<dyn Error>
+            })
+        });
+        let mut text = text?;
+        let copied = clipboard::copy_for(&text, timeout);
+        text.zeroize();
+        copied?;
+        report.line(format!(
+            "{} was copied to the clipboard, which is cleared in {} seconds",
+            String::from_utf8_lossy(&header.file_name),
+            timeout.as_secs()
+        ));
+        report.field("copied", true);
+    } else {
+        let output = header.output_name()?;
+        write_atomically(&output, |file| decrypt(file))?;
+        report.success(format!(
+            "decrypted {} to {}",
+            config.encrypted_file,
+            output.to_string_lossy()
+        ));
+        report.field("decrypted_file", output.to_string_lossy().into_owned());
+    }
+    Ok(report)
+}
+
+// Returns the cipher of the content of the file, using the
+// secret given to unlock it, see shares_cipher for cross_check
+fn unlock(unlock: &Unlock, header: &Header, cross_check: bool) -> Result<Cipher, Box<dyn Error>> {
+    let cipher = match unlock {
+        Unlock::Shares(shares_file) => {
+            shares_cipher(read_shares(shares_file)?, header, cross_check)?
+        }
+        Unlock::Images(shares_file, images) => {
+            let mut fragments = match shares_file {
+                Some(shares_file) => fragments::read_fragments(shares_file)?,
+                None => Vec::new(),
+            };
+            for image in images {
+                fragments.extend(scan::read_image(image)?);
+            }
+            shares_cipher(enter_pins(fragments)?, header, cross_check)?
+        }
+        #[cfg(feature = "ceremony")]
+        Unlock::Fragments(fragments) => shares_cipher(fragments.clone(), header, cross_check)?,
+        Unlock::Password(password) => return unlock_password(header, password),
+        Unlock::Keyfile(keyfile) => Cipher::from_keyfile(&read_keyfile(keyfile)?, header.suite),
+        Unlock::Keychain(account) => {
+            Cipher::from_keyfile(keychain::load(account)?.as_bytes(), header.suite)
+        }
+        // the recovery agents have their own slots
+        Unlock::RecoveryKey(key_file) => {
+            return escrow::unwrap(
+                &header.recovery_slots,
+                &escrow::read_key(key_file)?,
+                &header.associated_data(),
+                header.suite,
+            );
+        }
+        // the agent never gives its keys away, it only decrypts
+        #[cfg(unix)]
+        Unlock::Agent(..) => {
+            return Err(Box::new(ArgumentError(
+                "the agent can only decrypt files, not unlock them".into(),
+            )));
+        }
+    };
+    unwrap_data_key(header, &cipher)
+}
+
+// Returns the cipher of the content of a file unlocked with a
+// password, the one given when encrypting or an added one, deriving
+// its key with the cost and the salt of each slot
+fn unlock_password(header: &Header, password: &str) -> Result<Cipher, Box<dyn Error>> {
+    let aad = header.associated_data();
+    header
+        .key_slots
+        .iter()
+        .filter(|slot| slot.method != UnlockMethod::Keyfile)
+        .find_map(|slot| {
+            password_cipher(slot.kdf.as_ref(), password, header.suite)
+                .unwrap_key(&slot.wrapped_key, &aad)
+                .ok()
+        })
+        .ok_or_else(|| {
+            Box::new(ArgumentError(
+                "the secret given does not unlock the file".into(),
+            )) as Box<dyn Error>
+        })
+}
+
+// The cipher of the key of a password, derived with scrypt or, for
+// the slots of the files migrated from version 0, the SHA-256 of the
+// password
+fn password_cipher(kdf: Option<&PasswordKdf>, password: &str, suite: CipherSuite) -> Cipher {
+    match kdf {
+        Some(kdf) => kdf.cipher(password, suite),
+        None => Cipher::from_keyfile(password.as_bytes(), suite),
+    }
+}
+
+// reads the fragments of a shares file, asking for the PIN of the
+// shares protected with one
+fn read_shares(path: &str) -> Result<Vec<Fragment>, Box<dyn Error>> {
+    enter_pins(fragments::read_fragments(path)?)
+}
+
+// removes the PIN of the protected fragments, asking the holder of
+// each one for it in the terminal
+fn enter_pins(fragments: Vec<Fragment>) -> Result<Vec<Fragment>, Box<dyn Error>> {
+    unprotect_fragments(fragments, |fragment| {
+        let prompt = match &fragment.holder {
+            Some(holder) => format!("PIN of the share of {}: ", holder),
+            None => format!("PIN of share {}: ", fragment.share.0),
+        };
+        rpassword::read_password_from_tty(Some(&prompt))
+    })
+}
+
+// removes the PIN of the protected fragments with the PINs read_pin
+// gives for them
+fn unprotect_fragments(
+    fragments: Vec<Fragment>,
+    mut read_pin: impl FnMut(&Fragment) -> io::Result<String>,
+) -> Result<Vec<Fragment>, Box<dyn Error>> {
+    fragments
+        .into_iter()
+        .map(|fragment| {
+            if fragment.pin.is_none() {
+                return Ok(fragment);
+            }
+            let mut pin = read_pin(&fragment)?;
+            let unprotected = pin::unprotect(&fragment, pin.trim());
+            pin.zeroize();
+            unprotected
+        })
+        .collect()
+}
+
+// fails if a share is still masked with its PIN, which would
+// recover a wrong key
+fn check_pins(fragments: &[Fragment]) -> Result<(), ArgumentError> {
+    match fragments.iter().find(|fragment| fragment.pin.is_some()) {
+        Some(fragment) => Err(ArgumentError(match &fragment.holder {
+            Some(holder) => format!("the share of {} is protected with a PIN", holder),
+            None => format!("share {} is protected with a PIN", fragment.share.0),
+        })),
+        None => Ok(()),
+    }
+}
+
+// recovers the key of the shares, following the policy of the
+// file if it has one; shares without MAC are recovered from the
+// lowest indices, and with cross_check also from the highest ones
+fn shares_cipher(
+    fragments: Vec<Fragment>,
+    header: &Header,
+    cross_check: bool,
+) -> Result<Cipher, Box<dyn Error>> {
+    check_pins(&fragments)?;
+    let fragments = distinct_fragments(fragments)?;
+    // too few shares would recover a wrong key instead of failing
+    if let (None, Some((threshold, _))) = (&header.policy, header.scheme) {
+        if fragments.len() < threshold {
+            return Err(Box::new(ArgumentError(format!(
+                "found {} shares, need at least {}",
+                fragments.len(),
+                threshold
+            ))));
+        }
+    }
+    let field = fragments_field(&fragments)?;
+    let length = common_attribute(&fragments, |fragment| &fragment.length, "key length")?;
+    // shares of the default scheme do not record it
+    let scheme = common_attribute(&fragments, |fragment| &fragment.scheme, "scheme")?
+        .map(|name| scheme::from_name(&name, field.as_ref()))
+        .transpose()?
+        .filter(|found| found.name() != scheme::DEFAULT_SCHEME);
+    // shares written before they were authenticated have no MAC
+    let macs: Option<Vec<String>> = fragments.iter().map(|f| f.mac.clone()).collect();
+    let macs = macs.filter(|macs| !macs.is_empty());
+    let (cipher, forged) = match (&header.policy, header.scheme, &macs) {
+        (None, Some((threshold, _)), Some(macs)) if scheme.is_none() => {
+            let shares: Vec<_> = fragments
+                .iter()
+                .map(|fragment| fragment.share.clone())
+                .zip(macs.iter().cloned())
+                .collect();
+            Cipher::from_authenticated_shares(
+                &shares,
+                threshold,
+                header.suite,
+                field.as_ref(),
+                length,
+            )?
+        }
+        _ => {
+            let cipher = match &header.policy {
+                Some(policy) => {
+                    // the holder of each share tells its place in the policy
+                    let shares: Vec<_> = fragments
+                        .iter()
+                        .filter_map(|f| Some((f.holder.clone()?, f.share.clone())))
+                        .collect();
+                    Cipher::from_policy(&Policy::parse(policy)?, &shares, header.suite, length)?
+                }
+                None => {
+                    let shares: Vec<_> = fragments.iter().map(|f| f.share.clone()).collect();
+                    match (header.scheme, &scheme) {
+                        (_, Some(scheme)) => {
+                            SecretKey::recover_with(scheme.as_ref(), &shares, length)?
+                                .into_cipher(header.suite)?
+                        }
+                        (Some((threshold, _)), None) => Cipher::from_lowest_shares(
+                            &shares,
+                            threshold,
+                            header.suite,
+                            field.as_ref(),
+                            length,
+                            cross_check,
+                        )?,
+                        (None, None) => Cipher::from_shares(
+                            shares.into_iter(),
+                            header.suite,
+                            field.as_ref(),
+                            length,
+                        )?,
+                    }
+                }
+            };
+            let forged: Vec<usize> = match &macs {
+                Some(macs) => (0..fragments.len())
+                    .filter(|&i| !cipher.verify_share(&fragments[i].share, &macs[i]))
+                    .collect(),
+                None => Vec::new(),
+            };
+            if !forged.is_empty() && forged.len() == fragments.len() {
+                return Err(Box::new(ArgumentError(
+                    "the shares do not recover the key, some of them are forged or corrupt".into(),
+                )));
+            }
+            (cipher, forged)
+        }
+    };
+    for i in forged {
+        match &fragments[i].holder {
+            Some(holder) => report::warn(format!("the share of {} is forged or corrupt", holder)),
+            None => report::warn(format!(
+                "share {} is forged or corrupt",
+                fragments[i].share.0
+            )),
+        }
+    }
+    Ok(cipher)
+}
+
+// discards the fragments given more than once, warning about each
+// of them, and fails if two different shares have the same index
+fn distinct_fragments(fragments: Vec<Fragment>) -> Result<Vec<Fragment>, ArgumentError> {
+    let mut distinct: Vec<Fragment> = Vec::with_capacity(fragments.len());
+    for fragment in fragments {
+        let x = &fragment.share.0;
+        match distinct.iter().find(|other| other.share.0 == *x) {
+            Some(other) if other.share == fragment.share => match &fragment.holder {
+                Some(holder) => {
+                    report::warn(format!("discarded a copy of the share of {}", holder))
+                }
+                None => report::warn(format!("discarded a copy of share {}", x)),
+            },
+            Some(_) => {
+                return Err(ArgumentError(format!(
+                    "two different shares have the index {}",
+                    x
+                )))
+            }
+            None => distinct.push(fragment),
+        }
+    }
+    Ok(distinct)
+}
+
+// the prime of the field the fragments were split in, the same
+// for all of them, or None for the default prime
+fn fragments_field(fragments: &[Fragment]) -> Result<Option<Prime>, Box<dyn Error>> {
+    let field = common_attribute(fragments, |fragment| &fragment.field, "field")?;
+    Ok(field.as_deref().map(shamir::field_prime).transpose()?)
+}
+
+// the value of an attribute of the fragments, which must be the
+// same for all of them
+fn common_attribute<T: Clone + PartialEq>(
+    fragments: &[Fragment],
+    attribute: impl Fn(&Fragment) -> &Option<T>,
+    name: &str,
+) -> Result<Option<T>, CorruptFileError> {
+    let value = fragments
+        .first()
+        .and_then(|fragment| attribute(fragment).clone());
+    if fragments
+        .iter()
+        .any(|fragment| *attribute(fragment) != value)
+    {
+        return Err(CorruptFileError(format!(
+            "the shares do not have the same {}",
+            name
+        )));
+    }
+    Ok(value)
+}
+
+// unwraps the data key with the first key slot the cipher opens
+fn unwrap_data_key(header: &Header, cipher: &Cipher) -> Result<Cipher, Box<dyn Error>> {
+    let aad = header.associated_data();
+    header
+        .key_slots
+        .iter()
+        .find_map(|slot| cipher.unwrap_key(&slot.wrapped_key, &aad).ok())
+        .ok_or_else(|| {
+            Box::new(ArgumentError(
+                "the secret given does not unlock the file".into(),
+            )) as Box<dyn Error>
+        })
+}
+
+// decrypts the rest of the file after the header and writes the result in disk
+#[cfg(feature = "ceremony")]
+fn decrypt_file(
+    header: &Header,
+    cipher: &Cipher,
+    reader: &mut impl Read,
+) -> Result<(), Box<dyn Error>> {
+    write_atomically(header.output_name()?, |output| {
+        decrypt_into(header, cipher, reader, output)
+    })
+}
+
+// fails before decrypting if the whole content would be held in
+// memory and it does not fit in the budget: a named secret is read
+// from the whole text and the clipboard takes the whole text, the
+// rest is decrypted chunk by chunk
+fn check_memory(config: &DecryptConfig, max_memory: u64) -> Result<(), Box<dyn Error>> {
+    let reason = if config.get.is_some() {
+        "its secrets are read in memory to print one"
+    } else if config.copy.is_some() {
+        "it would be copied to the clipboard at once, decrypt it to a file instead"
+    } else {
+        return Ok(());
+    };
+    let size = fs::metadata(&config.encrypted_file)?.len();
+    if size > max_memory {
+        return Err(Box::new(ArgumentError(format!(
+            "{} is larger than the memory budget of {} bytes and {}",
+            config.encrypted_file, max_memory, reason
+        ))));
+    }
+    Ok(())
+}
+
+// decrypts the rest of the file after the header into the output
+fn decrypt_into(
+    header: &Header,
+    cipher: &Cipher,
+    reader: &mut impl Read,
+    output: &mut impl Write,
+) -> Result<(), Box<dyn Error>> {
+    let mut writer = DigestWriter::new(output);
+    cipher.decrypt_stream(reader, &mut writer, &header.associated_data())?;
+    check_digest(header, writer.digest())?;
+    check_length(header, writer.length)
+}
+
+// decrypts only a part of the content of a file, reading the chunks
+// that hold it and, for an entry, the headers of the archive before
+// it, and writes it to the output; returns its offset and length
+fn reveal_part<R: Read + Seek>(
+    header: &Header,
+    cipher: &Cipher,
+    reader: R,
+    reveal: &Reveal,
+    output: &mut impl Write,
+) -> Result<(u64, u64), Box<dyn Error>> {
+    let mut content = cipher.range_reader(reader, &header.associated_data())?;
+    let (offset, length) = match reveal {
+        Reveal::Range(start, end) => {
+            let end = end.unwrap_or(content.len()).min(content.len());
+            (*start, end.saturating_sub(*start))
+        }
+        Reveal::Entry(name) => {
+            let length = content.len();
+            let entry = archive::find(
+                |offset, length| {
+                    let mut part = Vec::new();
+                    content.read_range(offset, length, &mut part)?;
+                    Ok(part)
+                },
+                length,
+                name,
+            )?;
+            (entry.offset, entry.size)
+        }
+    };
+    content.read_range(offset, length, output)?;
+    output.flush()?;
+    Ok((offset, length))
+}
+
+// checks the rest of the file after the header without writing the result
+fn verify_file(
+    header: &Header,
+    cipher: &Cipher,
+    reader: &mut impl Read,
+) -> Result<(), Box<dyn Error>> {
+    if header.digest.is_some() || header.length.is_some() {
+        let mut sink = DigestWriter::new(io::sink());
+        cipher.decrypt_stream(reader, &mut sink, &header.associated_data())?;
+        check_digest(header, sink.digest())?;
+        check_length(header, sink.length)
+    } else {
+        cipher.verify(reader, &header.associated_data())
+    }
+}
+
+// fails if the header has a digest different to the one given
+fn check_digest(header: &Header, digest: [u8; DIGEST_SIZE]) -> Result<(), Box<dyn Error>> {
+    match header.digest {
+        Some(expected) if expected != digest => Err(Box::new(CorruptFileError(
+            "the digest of the decrypted file does not match".into(),
+        ))),
+        _ => Ok(()),
+    }
+}
+
+// fails if the header has a length different to the one given
+fn check_length(header: &Header, length: u64) -> Result<(), Box<dyn Error>> {
+    match header.length {
+        Some(expected) if expected != length => Err(Box::new(CorruptFileError(format!(
+            "the decrypted file has {} bytes, the header says {}",
+            length, expected
+        )))),
+        _ => Ok(()),
+    }
+}
+
+// A writer that computes the BLAKE3 digest and the length of
+// everything written
+struct DigestWriter<W: Write> {
+    inner: W,
+    hasher: blake3::Hasher,
+    length: u64,
+}
+
+impl<W: Write> DigestWriter<W> {
+    fn new(inner: W) -> Self {
+        DigestWriter {
+            inner,
+            hasher: blake3::Hasher::new(),
+            length: 0,
+        }
+    }
+
+    fn digest(&self) -> [u8; DIGEST_SIZE] {
+        self.hasher.finalize().into()
+    }
+}
+
+impl<W: Write> Write for DigestWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.hasher.update(&buf[..written]);
+        self.length += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+// A reader that counts the bytes read, in 64 bits on every platform
+struct CountingReader<R: Read> {
+    inner: R,
+    length: u64,
+}
+
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.length += read as u64;
+        Ok(read)
+    }
+}
+
+// Wraps the data key of a file with a new password and replaces its
+// shares, without encrypting the content again
+fn run_rotate(config: RotateConfig) -> Result<Report, Box<dyn Error>> {
+    let mut file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(&config.encrypted_file)?;
+    let mut header = Header::read_from(&mut BufReader::new(&mut file))?;
+    let offset = header.key_slots_offset();
+    let (threshold, total) = header
+        .scheme
+        .ok_or_else(|| CorruptFileError("the encrypted file has no scheme".into()))?;
+    let data_key = unlock(&Unlock::Shares(config.shares_file.clone()), &header, false)?;
+    let kdf = PasswordKdf::generate()?;
+    let cipher = kdf.cipher(&config.password, header.suite);
+    // the other unlock methods are kept
+    header
+        .key_slots
+        .retain(|slot| slot.method != UnlockMethod::Shares);
+    header.key_slots.insert(
+        0,
+        KeySlot {
+            method: UnlockMethod::Shares,
+            kdf: Some(kdf),
+            wrapped_key: cipher.wrap_key(&data_key, &header.associated_data())?,
+        },
+    );
+    // the new shares are saved before the file stops accepting the old ones
+    let new_shares_file = format!("{}.new", config.shares_file);
+    let policy = header.policy.as_deref().map(Policy::parse).transpose()?;
+    // the new shares are split in the scheme and field and written
+    // in the format of the old ones
+    let old_fragments = fragments::read_fragments(&config.shares_file)?;
+    let field = fragments_field(&old_fragments)?;
+    let scheme = common_attribute(&old_fragments, |fragment| &fragment.scheme, "scheme")?;
+    let format = Format::detect(&fs::read_to_string(&config.shares_file)?);
+    let fragments = write_atomically(&new_shares_file, |writer| {
+        let fragments = split_fragments(
+            &cipher,
+            policy.as_ref(),
+            total,
+            threshold,
+            &header.holders,
+            field.as_ref(),
+            scheme.as_deref(),
+        )?;
+        fragments::write_fragments(&fragments, format, writer)?;
+        Ok(fragments)
+    })?;
+    file.seek(SeekFrom::Start(offset))?;
+    header.write_key_slots(&mut file)?;
+    file.sync_all()?;
+    fs::rename(&new_shares_file, &config.shares_file)?;
+    let mut report = Report::new();
+    report.field("encrypted_file", config.encrypted_file.as_str());
+    report.field("shares_file", config.shares_file.as_str());
+    report.field("shares", shares_json(&fragments));
+    Ok(report)
+}
+
+// Writes a directory for each holder with their share and a cover
+// sheet, ready to be sealed in an envelope
+fn run_envelopes(config: EnvelopesConfig) -> Result<Report, Box<dyn Error>> {
+    let mut reader = BufReader::new(File::open(&config.encrypted_file)?);
+    let header = Header::read_from(&mut reader)?;
+    let (threshold, total) = header.scheme.ok_or_else(|| {
+        ArgumentError(format!(
+            "{} does not record how many shares decrypt it",
+            config.encrypted_file
+        ))
+    })?;
+    let fragments = fragments::read_fragments(&config.shares_file)?;
+    let set_fingerprint = fragments::set_fingerprint(&fragments);
+    let file_name = file_name(&config.encrypted_file);
+    let mut report = Report::new();
+    let mut directories = Vec::new();
+    for fragment in &fragments {
+        let envelope = envelope::Envelope {
+            fragment,
+            file_name: &file_name,
+            threshold,
+            total,
+            set_fingerprint: &set_fingerprint,
+            contact: config.contact.as_deref(),
+        };
+        let directory = Path::new(&config.output_dir).join(envelope.directory());
+        fs::create_dir_all(&directory)?;
+        write_atomically(directory.join(envelope::SHARE_FILE), |writer| {
+            Ok(envelope.write_share(writer)?)
+        })?;
+        write_atomically(directory.join(envelope::COVER_FILE), |writer| {
+            Ok(envelope.write_cover(writer)?)
+        })?;
+        let directory = directory.to_string_lossy().into_owned();
+        report.line(format!(
+            "  {}: {}",
+            fragment.holder.as_deref().unwrap_or("no holder"),
+            directory
+        ));
+        directories.push(directory);
+    }
+    report.success(format!(
+        "wrote {} envelopes to {}/",
+        fragments.len(),
+        config.output_dir
+    ));
+    report.line(format!("share set fingerprint: {}", set_fingerprint));
+    report.field("envelopes", directories);
+    report.field("set_fingerprint", set_fingerprint);
+    Ok(report)
+}
+
+// Writes a directory to recover an encrypted file on a computer
+// without network: the file, the fingerprints of its shares, the
+// instructions, the program if it is given, and the checksums of
+// all of them
+fn run_export_offline(config: ExportOfflineConfig) -> Result<Report, Box<dyn Error>> {
+    let header = Header::read_from(&mut BufReader::new(File::open(&config.encrypted_file)?))?;
+    let mut fingerprints = Vec::new();
+    let mut set_fingerprint = None;
+    if let Some(shares_file) = &config.shares_file {
+        let fragments = fragments::read_fragments(shares_file)?;
+        for fragment in &fragments {
+            let holder = fragment
+                .holder
+                .clone()
+                .unwrap_or_else(|| fragment.share.0.to_string());
+            fingerprints.push((holder, fragment.fingerprint()));
+        }
+        set_fingerprint = Some(fragments::set_fingerprint(&fragments));
+    } else if let Some(path) = &config.registry {
+        // the registry has no shares, but it has their fingerprints
+        let registry = Json::parse(&fs::read_to_string(path)?)?;
+        let invalid = || CorruptFileError(format!("{} is not a share registry", path));
+        let shares = match registry.get("shares") {
+            Some(Json::Array(shares)) => shares,
+            _ => return Err(Box::new(invalid())),
+        };
+        for share in shares {
+            let holder = share
+                .get("holder")
+                .and_then(Json::as_str)
+                .or_else(|| share.get("index").and_then(Json::as_str))
+                .ok_or_else(invalid)?;
+            let fingerprint = share
+                .get("fingerprint")
+                .and_then(Json::as_str)
+                .ok_or_else(invalid)?;
+            fingerprints.push((holder.to_string(), fingerprint.to_string()));
+        }
+        set_fingerprint = registry
+            .get("set_fingerprint")
+            .and_then(Json::as_str)
+            .map(String::from);
+    }
+    // the directory must be new, so nothing but the bundle is in it
+    let dir = Path::new(&config.output_dir);
+    fs::create_dir(dir)?;
+    let file_name = file_name(&config.encrypted_file);
+    fs::copy(&config.encrypted_file, dir.join(&file_name))?;
+    let mut files = vec![file_name.clone()];
+    if let Some(registry) = &config.registry {
+        let name = self::file_name(registry);
+        fs::copy(registry, dir.join(&name))?;
+        files.push(name);
+    }
+    let binary = config.binary.as_deref().map(offline::Bundle::binary_path);
+    if let (Some(source), Some(binary)) = (&config.binary, &binary) {
+        let path = dir.join(binary);
+        fs::create_dir_all(path.parent().unwrap())?;
+        fs::copy(source, &path)?;
+        files.push(binary.clone());
+    }
+    let bundle = offline::Bundle {
+        file_name: &file_name,
+        scheme: header.scheme,
+        holders: &header.holders,
+        fingerprints: &fingerprints,
+        set_fingerprint: set_fingerprint.as_deref(),
+        binary: binary.as_deref(),
+    };
+    if !fingerprints.is_empty() {
+        write_atomically(dir.join(offline::FINGERPRINTS_FILE), |writer| {
+            Ok(bundle.write_fingerprints(writer)?)
+        })?;
+        files.push(offline::FINGERPRINTS_FILE.into());
+    }
+    write_atomically(dir.join(offline::INSTRUCTIONS_FILE), |writer| {
+        Ok(bundle.write_instructions(writer)?)
+    })?;
+    files.push(offline::INSTRUCTIONS_FILE.into());
+    write_atomically(dir.join(offline::CHECKSUMS_FILE), |writer| {
+        Ok(offline::write_checksums(dir, &files, writer)?)
+    })?;
+    files.push(offline::CHECKSUMS_FILE.into());
+    let mut report = Report::new();
+    report.success(format!(
+        "wrote the offline bundle of {} to {}/",
+        file_name, config.output_dir
+    ));
+    for file in &files {
+        report.line(format!("  {}", file));
+    }
+    if binary.is_none() {
+        report.line("the program is not in the bundle, give it with --binary");
+    }
+    report.field("output_dir", config.output_dir);
+    report.field("files", files);
+    Ok(report)
+}
+
+// Upgrades an encrypted file without header and a shares file
+// without version line to the current formats, in place
+fn run_migrate(config: MigrateConfig) -> Result<Report, Box<dyn Error>> {
+    let mut reader = BufReader::new(File::open(&config.encrypted_file)?);
+    let header = Header::read_legacy_from(&mut reader)?;
+    let text = fs::read_to_string(&config.shares_file)?;
+    let shares_version = fragments::format_version(&text)?;
+    let mut report = Report::new();
+    report.field("encrypted_file", config.encrypted_file.as_str());
+    report.field("shares_file", config.shares_file.as_str());
+    if header.version == 0 {
+        let fragments = read_shares(&config.shares_file)?;
+        migrate_encrypted_file(
+            &config.encrypted_file,
+            &header,
+            &mut reader,
+            fragments,
+            config.total_evals,
+        )?;
+        report.success(format!(
+            "{}: migrated from format version 0 to {}",
+            config.encrypted_file, FORMAT_VERSION
+        ));
+    } else {
+        report.line(format!(
+            "{}: format version {}, nothing to migrate",
+            config.encrypted_file, header.version
+        ));
+    }
+    // a bundle records the version of its own format
+    if shares_version == 0 && !bundle::is_bundle(&text) {
+        // the version line is added before the old content, which
+        // keeps its comments
+        replace_atomically(&config.shares_file, |writer| {
+            writeln!(writer, "{}", fragments::version_line())?;
+            Ok(writer.write_all(text.trim_start_matches('\u{feff}').as_bytes())?)
+        })?;
+        report.success(format!(
+            "{}: migrated from format version 0 to {}",
+            config.shares_file,
+            fragments::FORMAT_VERSION
+        ));
+    } else {
+        report.line(format!(
+            "{}: format version {}, nothing to migrate",
+            config.shares_file, shares_version
+        ));
+    }
+    report.field("encrypted_file_version", usize::from(FORMAT_VERSION));
+    report.field(
+        "shares_file_version",
+        usize::from(fragments::FORMAT_VERSION),
+    );
+    Ok(report)
+}
+
+// encrypts the content of a file without header again in the current
+// format, with a random data key wrapped by the key of the shares so
+// the same shares open it. These files do not store the scheme: the
+// threshold is the fewest shares of the lowest and of the highest
+// indices that recover the same key, and the total must be given,
+// as the shares at hand may not be all of them
+fn migrate_encrypted_file(
+    path: &str,
+    header: &Header,
+    reader: &mut impl Read,
+    fragments: Vec<Fragment>,
+    total_evals: Option<usize>,
+) -> Result<(), Box<dyn Error>> {
+    check_pins(&fragments)?;
+    let fragments = distinct_fragments(fragments)?;
+    let field = fragments_field(&fragments)?;
+    let length = common_attribute(&fragments, |fragment| &fragment.length, "key length")?;
+    let shares: Vec<_> = fragments.iter().map(|f| f.share.clone()).collect();
+    let (threshold, cipher) = (1..=shares.len())
+        .find_map(|k| {
+            let cipher =
+                Cipher::from_lowest_shares(&shares, k, header.suite, field.as_ref(), length, true);
+            Some((k, cipher.ok()?))
+        })
+        .ok_or_else(|| ArgumentError("the shares do not recover a key".into()))?;
+    let total_evals = total_evals
+        .or(header.scheme.map(|(_, total_evals)| total_evals))
+        .ok_or_else(|| {
+            ArgumentError(
+                "the file does not record how many shares were generated, give it with --total"
+                    .into(),
+            )
+        })?;
+    if total_evals < fragments.len() {
+        return Err(Box::new(ArgumentError(format!(
+            "there are {} shares, more than the total of {}",
+            fragments.len(),
+            total_evals
+        ))));
+    }
+    // the content is a single encrypted block; any text whose first
+    // line is short reads as a file without header, so one the shares
+    // do not open is most likely another file
+    let mut content = Vec::new();
+    reader.read_to_end(&mut content)?;
+    if cipher.decrypt(&mut content).is_err() {
+        content.zeroize();
+        return Err(Box::new(ArgumentError(format!(
+            "not a shared_secrets file (accepted version {}): {} has no header and \
+             the shares do not decrypt it as a file of format version 0",
+            FORMAT_VERSION, path
+        ))));
+    }
+    // the holders are kept if every share has one
+    let holders: Option<Vec<String>> = fragments.iter().map(|f| f.holder.clone()).collect();
+    let mut migrated = Header::new(
+        header.file_name.clone(),
+        holders.unwrap_or_default(),
+        threshold,
+        total_evals,
+        header.suite,
+    );
+    migrated.length = Some(content.len() as u64);
+    let data_key = Cipher::random(header.suite)?;
+    let aad = migrated.associated_data();
+    // the shares recover the SHA-256 of the password of the old file
+    migrated.key_slots.push(KeySlot {
+        method: UnlockMethod::Shares,
+        kdf: None,
+        wrapped_key: cipher.wrap_key(&data_key, &aad)?,
+    });
+    let result = replace_atomically(path, |writer| {
+        migrated.write_to(writer)?;
+        data_key.encrypt_stream(&mut &content[..], writer, false, &aad)
+    });
+    content.zeroize();
+    result
+}
+
+// Adds and removes the unlock methods of a file, rewriting its
+// key slots without encrypting the content again
+fn run_methods(config: MethodsConfig) -> Result<Report, Box<dyn Error>> {
+    let mut file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(&config.encrypted_file)?;
+    let mut header = Header::read_from(&mut BufReader::new(&mut file))?;
+    let offset = header.key_slots_offset();
+    let data_key = unlock(&config.unlock, &header, false)?;
+    let aad = header.associated_data();
+    header
+        .key_slots
+        .retain(|slot| !config.remove.contains(&slot.method));
+    if let Some(password) = &config.add_password {
+        let kdf = PasswordKdf::generate()?;
+        let cipher = kdf.cipher(password, header.suite);
+        header.key_slots.push(KeySlot {
+            method: UnlockMethod::Password,
+            kdf: Some(kdf),
+            wrapped_key: cipher.wrap_key(&data_key, &aad)?,
+        });
+    }
+    if let Some(keyfile) = &config.add_keyfile {
+        let cipher = Cipher::from_keyfile(&read_keyfile(keyfile)?, header.suite);
+        header.key_slots.push(KeySlot {
+            method: UnlockMethod::Keyfile,
+            kdf: None,
+            wrapped_key: cipher.wrap_key(&data_key, &aad)?,
+        });
+    }
+    if header.key_slots.is_empty() {
+        return Err(Box::new(ArgumentError(
+            "at least one unlock method must remain".into(),
+        )));
+    }
+    if header.key_slots.len() > KEY_SLOTS {
+        return Err(Box::new(ArgumentError(format!(
+            "a file can have at most {} unlock methods",
+            KEY_SLOTS
+        ))));
+    }
+    file.seek(SeekFrom::Start(offset))?;
+    header.write_key_slots(&mut file)?;
+    file.sync_all()?;
+    let mut report = Report::new();
+    report.field("encrypted_file", config.encrypted_file.as_str());
+    report.field("unlock_methods", unlock_methods(&header));
+    Ok(report)
+}
+
+// Prints the shares of a passphrase, one per line
+fn run_split_passphrase(config: SplitPassphraseConfig) -> Result<Report, Box<dyn Error>> {
+    let shares = passphrase::split(
+        &config.passphrase,
+        config.total_evals,
+        config.min_required_evals,
+    )?;
+    Ok(shares_report(shares))
+}
+
+// The report of the shares of a passphrase or of a wallet, one per line
+fn shares_report(shares: Vec<String>) -> Report {
+    let mut report = Report::new();
+    for share in &shares {
+        report.line(share.as_str());
+    }
+    report.field("shares", shares);
+    report
+}
+
+// Recovers a passphrase and prints it or copies it to the clipboard
+fn run_combine_passphrase(config: CombinePassphraseConfig) -> Result<Report, Box<dyn Error>> {
+    let shares: Vec<&str> = config.shares.iter().map(String::as_str).collect();
+    let passphrase = passphrase::combine(&shares)?;
+    secret_report("passphrase", passphrase, config.copy)
+}
+
+// The report of a recovered secret, which is printed, or copied to
+// the clipboard and cleared after the timeout
+fn secret_report(
+    name: &str,
+    secret: String,
+    copy: Option<Duration>,
+) -> Result<Report, Box<dyn Error>> {
+    let mut report = Report::new();
+    match copy {
+        Some(timeout) => {
+            clipboard::copy_for(&secret, timeout)?;
+            report.line(format!(
+                "The {} was copied to the clipboard, which is cleared in {} seconds",
+                name,
+                timeout.as_secs()
+            ));
+            report.field("copied", true);
+        }
+        None => {
+            report.line(secret.as_str());
+            report.field(name, secret);
+        }
+    }
+    Ok(report)
+}
+
+// Prints the shares of the key of a wallet, one per line
+fn run_split_wallet(config: SplitWalletConfig) -> Result<Report, Box<dyn Error>> {
+    let shares = wallet::split(
+        &config.key,
+        config.format,
+        config.total_evals,
+        config.min_required_evals,
+    )?;
+    Ok(shares_report(shares))
+}
+
+// Splits a secret and prints its shares, one per line
+fn run_split(config: SplitConfig) -> Result<Report, Box<dyn Error>> {
+    let (total_evals, min_required_evals) = (config.total_evals, config.min_required_evals);
+    let shares = match &config.seed {
+        Some(seed) => {
+            config
+                .secret
+                .split_from_seed(total_evals, min_required_evals, seed.expose(), None)?
+        }
+        None => config.secret.split(total_evals, min_required_evals, None)?,
+    };
+    let mut fragments = length_fragments(shares, config.secret.len(), None);
+    // the share of a holder made again from the seed
+    if let Some(position) = config.share {
+        if position == 0 || position > fragments.len() {
+            return Err(Box::new(ArgumentError(format!(
+                "the share must be in the range 1 to {}",
+                fragments.len()
+            ))));
+        }
+        let fragment = fragments.remove(position - 1);
+        let mut report = shares_report(vec![fragment.to_line()]);
+        report.field("length", config.secret.len());
+        report.note(format!("fingerprint: {}", fragment.fingerprint()));
+        report.field("fingerprint", fragment.fingerprint());
+        return Ok(report);
+    }
+    let mut report = shares_report(fragments.iter().map(Fragment::to_line).collect());
+    report.field("length", config.secret.len());
+    fingerprints_fields(&mut report, &fragments);
+    Ok(report)
+}
+
+// The text typed in the terminal without the line breaks at its end,
+// which some terminals send with the secret
+fn trim_newlines(text: &str) -> &str {
+    text.trim_end_matches(['\r', '\n'])
+}
+
+// Generates a random key and prints only its shares, one per line
+fn run_keygen(config: KeygenConfig) -> Result<Report, Box<dyn Error>> {
+    let fragments = keygen_fragments(&config)?;
+    let mut report = shares_report(fragments.iter().map(Fragment::to_line).collect());
+    report.field("length", config.length);
+    fingerprints_fields(&mut report, &fragments);
+    Ok(report)
+}
+
+// Splits a share again, as a secret, into the shares of a group,
+// whose holders name the share they belong to, so the levels stay
+// linked and a share of the group can be split again in turn
+fn run_resplit(config: ResplitConfig) -> Result<Report, Box<dyn Error>> {
+    let fragments = fragments::read_fragments(&config.shares_file)?;
+    let parent = fragments
+        .iter()
+        .find(|fragment| {
+            fragment.share.0 == config.share || fragment.holder.as_ref() == Some(&config.share)
+        })
+        .ok_or_else(|| {
+            ArgumentError(format!(
+                "{} has no share {}",
+                config.shares_file, config.share
+            ))
+        })?;
+    let group = parent
+        .holder
+        .clone()
+        .unwrap_or_else(|| format!("share {}", parent.share.0));
+    let secret = SecretKey::new(parent.to_line().into_bytes());
+    let mut group_fragments =
+        key_fragments(&secret, config.total_evals, config.min_required_evals, None)?;
+    for (i, fragment) in group_fragments.iter_mut().enumerate() {
+        let member = match config.holders.get(i) {
+            Some(holder) => holder.clone(),
+            None => fragment.share.0.clone(),
+        };
+        fragment.holder = Some(format!("{}/{}", group, member));
+    }
+    write_atomically(&config.output_file, |writer| {
+        Ok(fragments::write_fragments(
+            &group_fragments,
+            Format::Lines,
+            writer,
+        )?)
+    })?;
+    let mut report = Report::new();
+    report.success(format!(
+        "split the share of {} into {} shares, {} of them recover it, in {}",
+        group, config.total_evals, config.min_required_evals, config.output_file
+    ));
+    report.line(format!("share fingerprint: {}", parent.fingerprint()));
+    for line in fingerprint_lines(&group_fragments) {
+        report.line(line);
+    }
+    report.field("share", parent.share.0.as_str());
+    report.field("fingerprint", parent.fingerprint());
+    report.field("shares_file", config.output_file.as_str());
+    report.field(
+        "fingerprints",
+        group_fragments
+            .iter()
+            .map(Fragment::fingerprint)
+            .collect::<Vec<_>>(),
+    );
+    report.field(
+        "set_fingerprint",
+        fragments::set_fingerprint(&group_fragments),
+    );
+    Ok(report)
+}
+
+// Recovers a share split by resplit from the shares of its group
+// and prints it, with its fingerprint in the standard error to
+// compare with the one resplit printed
+fn run_combine_group(config: CombineGroupConfig) -> Result<Report, Box<dyn Error>> {
+    let secret = fragments_key(read_shares(&config.shares_file)?)?;
+    let line = std::str::from_utf8(secret.expose())
+        .ok()
+        .and_then(|line| Fragment::parse(line).ok())
+        .ok_or_else(|| {
+            CorruptFileError(format!(
+                "the shares of {} do not recover a share",
+                config.shares_file
+            ))
+        })?;
+    let mut report = Report::new();
+    report.note(format!("fingerprint: {}", line.fingerprint()));
+    report.line(line.to_line());
+    report.field("share", line.to_line());
+    report.field("fingerprint", line.fingerprint());
+    Ok(report)
+}
+
+// Adds the fingerprints of the shares to the notes of the report, so
+// the standard output keeps only the shares, and to its fields
+fn fingerprints_fields(report: &mut Report, fragments: &[Fragment]) {
+    for line in fingerprint_lines(fragments) {
+        report.note(line);
+    }
+    report.field(
+        "fingerprints",
+        fragments
+            .iter()
+            .map(Fragment::fingerprint)
+            .collect::<Vec<_>>(),
+    );
+    report.field("set_fingerprint", fragments::set_fingerprint(fragments));
+}
+
+// splits a random key of the configured length, which is
+// zeroized when it is dropped once its shares are made
+fn keygen_fragments(config: &KeygenConfig) -> Result<Vec<Fragment>, Box<dyn Error>> {
+    let key = SecretKey::generate(config.length)?;
+    key_fragments(
+        &key,
+        config.total_evals,
+        config.min_required_evals,
+        config.field.as_ref(),
+    )
+}
+
+// splits a key into fragments that record its length and field,
+// so fragments_key recovers it
+fn key_fragments(
+    key: &SecretKey,
+    total_evals: usize,
+    min_required_evals: usize,
+    field: Option<&Prime>,
+) -> Result<Vec<Fragment>, Box<dyn Error>> {
+    let shares = key.split(total_evals, min_required_evals, field)?;
+    Ok(length_fragments(shares, key.len(), field))
+}
+
+// the fragments of the shares of a key of the given length
+fn length_fragments(shares: ShareIter, length: usize, field: Option<&Prime>) -> Vec<Fragment> {
+    shares
+        .evaluate_all()
+        .into_iter()
+        .map(|share| Fragment {
+            share,
+            holder: None,
+            scheme: None,
+            field: field.map(Prime::to_string),
+            length: Some(length),
+            mac: None,
+            pin: None,
+        })
+        .collect()
+}
+
+// Recovers a key from a shares file and writes it to a file descriptor
+fn run_show_key(config: ShowKeyConfig) -> Result<Report, Box<dyn Error>> {
+    let key = fragments_key(read_shares(&config.shares_file)?)?;
+    let mut encoded = export::encode(key.expose(), config.format);
+    let written = export::write_to_fd(&encoded, config.fd);
+    encoded.zeroize();
+    written?;
+    let mut report = Report::new();
+    report.note(format!(
+        "The key was written to the file descriptor {}",
+        config.fd
+    ));
+    report.field("length", key.len());
+    report.field("fd", config.fd as usize);
+    Ok(report)
+}
+
+// Prints the age recipient of the key of a shares file, and the
+// identity that recovers it from the file
+fn run_age_recipient(config: AgeRecipientConfig) -> Result<Report, Box<dyn Error>> {
+    let key = fragments_key(read_shares(&config.shares_file)?)?;
+    let recipient = age::recipient(&key);
+    let identity = age::identity(&config.shares_file)?;
+    let mut report = Report::new();
+    report.line(format!("# recipient: {}", recipient));
+    report.line(identity.clone());
+    report.field("recipient", recipient);
+    report.field("identity", identity);
+    Ok(report)
+}
+
+// Generates the key of an operator, printing its public key
+fn run_operator_key(config: OperatorKeyConfig) -> Result<Report, Box<dyn Error>> {
+    let public_key = manifest::generate_key(&config.key_file)?;
+    let mut report = Report::new();
+    report.line(format!("public key: {}", public_key));
+    report.field("key_file", config.key_file);
+    report.field("public_key", public_key);
+    Ok(report)
+}
+
+// Generates the key of a recovery agent and prints its public key
+fn run_recovery_agent_key(config: RecoveryAgentKeyConfig) -> Result<Report, Box<dyn Error>> {
+    let public_key = escrow::generate_key(&config.key_file)?;
+    let mut report = Report::new();
+    report.line(format!("public key: {}", public_key));
+    report.field("key_file", config.key_file);
+    report.field("public_key", public_key);
+    Ok(report)
+}
+
+// Checks that the shares of a file are the ones of the public
+// registry written when it was encrypted
+fn run_check_registry(config: CheckRegistryConfig) -> Result<Report, Box<dyn Error>> {
+    let registry = Json::parse(&fs::read_to_string(&config.registry)?)?;
+    let fragments = fragments::read_fragments(&config.shares_file)?;
+    let holders = registry::check(&registry, &fragments)?;
+    let mut report = Report::new();
+    for holder in &holders {
+        report.line(format!("  {}: ok", holder));
+    }
+    report.success(format!(
+        "{} shares match {}",
+        holders.len(),
+        config.registry
+    ));
+    report.field("shares", holders);
+    Ok(report)
+}
+
+// Verifies a manifest and the files it lists
+fn run_verify_manifest(config: VerifyManifestConfig) -> Result<Report, Box<dyn Error>> {
+    let body = manifest::verify(&config.manifest, config.public_key.as_deref())?;
+    let mut report = Report::new();
+    let public_key = body.get("public_key").and_then(Json::as_str).unwrap_or("");
+    report.line(format!("{}: ok", config.manifest));
+    report.line(format!("signed by: {}", public_key));
+    if let Some(Json::Array(outputs)) = body.get("outputs") {
+        for output in outputs {
+            let name = output.get("file").and_then(Json::as_str).unwrap_or("");
+            report.line(format!("{}: ok", name));
+        }
+    }
+    report.field("verified", true);
+    report.field("manifest", body);
+    Ok(report)
+}
+
+// Adds the signature of a participant to the transcript of a recovery
+fn run_sign_transcript(config: SignTranscriptConfig) -> Result<Report, Box<dyn Error>> {
+    let transcript = Json::parse(&fs::read_to_string(&config.transcript)?)?;
+    let key = manifest::read_key(&config.key_file)?;
+    let signed = transcript::sign(&transcript, &config.participant, &key)?;
+    replace_atomically(&config.transcript, |writer| {
+        Ok(writeln!(writer, "{}", signed)?)
+    })?;
+    let mut report = Report::new();
+    report.success(format!(
+        "{}: signed by {}",
+        config.transcript, config.participant
+    ));
+    report.field("transcript", config.transcript);
+    report.field("participant", config.participant);
+    Ok(report)
+}
+
+// Verifies the signatures of the transcript of a recovery and
+// tells which participants have not signed it yet
+fn run_verify_transcript(config: VerifyTranscriptConfig) -> Result<Report, Box<dyn Error>> {
+    let transcript = Json::parse(&fs::read_to_string(&config.transcript)?)?;
+    let signers = transcript::verify(&transcript, &config.public_keys)?;
+    let mut report = Report::new();
+    report.line(format!("{}: ok", config.transcript));
+    for signer in &signers {
+        report.success(format!(
+            "signed by {}: {}",
+            signer.participant, signer.public_key
+        ));
+    }
+    let unsigned: Vec<String> = transcript::participants(&transcript)?
+        .into_iter()
+        .filter(|participant| {
+            signers
+                .iter()
+                .all(|signer| &signer.participant != participant)
+        })
+        .collect();
+    for participant in &unsigned {
+        report.warning(format!("not signed by {}", participant));
+    }
+    report.field("verified", true);
+    report.field(
+        "signed_by",
+        signers
+            .iter()
+            .map(|signer| signer.participant.as_str())
+            .collect::<Vec<_>>(),
+    );
+    report.field("not_signed_by", unsigned);
+    report.field(
+        "transcript",
+        transcript.get("transcript").cloned().unwrap_or(Json::Null),
+    );
+    Ok(report)
+}
+
+// recovers the key split in the fragments of a shares file with
+// the scheme, field and length they record
+fn fragments_key(fragments: Vec<Fragment>) -> Result<SecretKey, Box<dyn Error>> {
+    check_pins(&fragments)?;
+    let fragments = distinct_fragments(fragments)?;
+    if fragments.is_empty() {
+        return Err(Box::new(ArgumentError("no shares were given".into())));
+    }
+    let field = fragments_field(&fragments)?;
+    let length = common_attribute(&fragments, |fragment| &fragment.length, "key length")?;
+    let name = common_attribute(&fragments, |fragment| &fragment.scheme, "scheme")?;
+    let scheme = scheme::from_name(
+        name.as_deref().unwrap_or(scheme::DEFAULT_SCHEME),
+        field.as_ref(),
+    )?;
+    let shares: Vec<_> = fragments.into_iter().map(|f| f.share).collect();
+    Ok(SecretKey::recover_with(scheme.as_ref(), &shares, length)?)
+}
+
+// Recovers the key of a wallet and prints it or copies it to the clipboard
+fn run_combine_wallet(config: CombineWalletConfig) -> Result<Report, Box<dyn Error>> {
+    let shares: Vec<&str> = config.shares.iter().map(String::as_str).collect();
+    let key = wallet::combine(&shares, config.format)?;
+    secret_report("key", key, config.copy)
+}
+
+// Runs the program in inspect mode
+fn run_inspect(config: InspectConfig) -> Result<Report, Box<dyn Error>> {
+    let mut reader = BufReader::new(File::open(&config.encrypted_file)?);
+    let header = Header::read_from(&mut reader)?;
+    let mut report = Report::new();
+    let original_file = String::from_utf8_lossy(&header.file_name).into_owned();
+    report.line(format!("original file: {}", original_file));
+    report.field("original_file", original_file);
+    report.line(format!("format version: {}", header.version));
+    report.field("format_version", usize::from(header.version));
+    if let Some(program_version) = &header.program_version {
+        report.line(format!("written by: shared_secrets {}", program_version));
+    }
+    report.field("program_version", header.program_version.clone());
+    if let Some((threshold, total)) = header.scheme {
+        report.line(format!("scheme: {} of {} shares", threshold, total));
+        report.field("threshold", threshold);
+        report.field("total", total);
+    }
+    if let Some(policy) = &header.policy {
+        report.line(format!("policy: {}", policy));
+    }
+    report.field("policy", header.policy.clone());
+    report.line(format!("cipher: {}", header.suite));
+    report.field("cipher", header.suite.name());
+    if let Some(length) = header.length {
+        report.line(format!("length: {} bytes", length));
+    }
+    report.field("length", header.length.map(Json::Number));
+    let digest = header
+        .digest
+        .map(|digest| blake3::Hash::from(digest).to_hex().to_string());
+    if let Some(digest) = &digest {
+        report.line(format!("digest: {}", digest));
+    }
+    report.field("digest", digest);
+    let methods = unlock_methods(&header);
+    if !methods.is_empty() {
+        report.line(format!("unlock methods: {}", methods.join(", ")));
+    }
+    report.field("unlock_methods", methods);
+    let agents: Vec<String> = header
+        .recovery_slots
+        .iter()
+        .map(|slot| encoding::to_hex(&slot.public_key))
+        .collect();
+    if !agents.is_empty() {
+        report.line(format!("recovery agents: {}", agents.join(", ")));
+    }
+    report.field("recovery_agents", agents);
+    if !header.holders.is_empty() {
+        report.line(format!("holders: {}", header.holders.join(", ")));
+    }
+    report.field("holders", header.holders.clone());
+    let shares_file = match config.shares_file {
+        Some(shares_file) => shares_file,
+        None => return Ok(report),
+    };
+    let text = fs::read_to_string(&shares_file)?;
+    let fragments = if bundle::is_bundle(&text) {
+        let bundle = bundle::read_bundle(&text)
+            .map_err(|e| CorruptFileError(format!("{}: {}", shares_file, e.0)))?;
+        report.line(format!(
+            "bundle: {} bytes of shares compressed to {}",
+            bundle.length, bundle.compressed
+        ));
+        bundle.fragments
+    } else {
+        let version = fragments::format_version(&text)
+            .map_err(|e| CorruptFileError(format!("{}: {}", shares_file, e.0)))?;
+        report.line(format!("shares format version: {}", version));
+        report.field("shares_format_version", usize::from(version));
+        let program_version = fragments::program_version(&text);
+        if let Some(program_version) = program_version {
+            report.line(format!(
+                "shares written by: shared_secrets {}",
+                program_version
+            ));
+        }
+        report.field("shares_program_version", program_version);
+        fragments::read_fragments(&shares_file)?
+    };
+    report.line(format!("shares found: {}", fragments.len()));
+    for line in fingerprint_lines(&fragments) {
+        report.line(line);
+    }
+    report.field("shares", shares_json(&fragments));
+    report.field("set_fingerprint", fragments::set_fingerprint(&fragments));
+    if let Some(fingerprint) = &config.fingerprint {
+        report.success(check_fingerprint(fingerprint, &fragments)?);
+    }
+    if header.holders.is_empty() {
+        return Ok(report);
+    }
+    let (present, missing) = holders_report(&header.holders, &fragments);
+    report.line(format!("present: {}", present.join(", ")));
+    report.line(format!("missing: {}", missing.join(", ")));
+    let unknown: Vec<&str> = fragments
+        .iter()
+        .filter_map(|f| f.holder.as_deref())
+        .filter(|h| !header.holders.iter().any(|holder| holder == h))
+        .collect();
+    if !unknown.is_empty() {
+        report.line(format!("unknown holders: {}", unknown.join(", ")));
+    }
+    report.field("present", present);
+    report.field("missing", missing);
+    report.field("unknown_holders", unknown);
+    Ok(report)
+}
+
+// Finds the share, or the whole set, of a fingerprint read by a
+// holder, failing if none has it
+fn check_fingerprint(fingerprint: &str, fragments: &[Fragment]) -> Result<String, ArgumentError> {
+    let fingerprint: String = fingerprint
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != '-')
+        .collect();
+    if fingerprint.eq_ignore_ascii_case(&fragments::set_fingerprint(fragments)) {
+        return Ok(format!(
+            "{} is the fingerprint of the share set",
+            fingerprint
+        ));
+    }
+    match fragments
+        .iter()
+        .find(|fragment| fingerprint.eq_ignore_ascii_case(&fragment.fingerprint()))
+    {
+        Some(fragment) => Ok(format!(
+            "{} is the fingerprint of share {}{}",
+            fingerprint,
+            fragment.share.0,
+            fragment
+                .holder
+                .as_ref()
+                .map(|holder| format!(" ({})", holder))
+                .unwrap_or_default()
+        )),
+        None => Err(ArgumentError(format!(
+            "{} is not the fingerprint of any share or of the share set",
+            fingerprint
+        ))),
+    }
+}
+
+// Reports the findings of the doctor, each problem with a suggestion
+fn run_doctor(config: DoctorConfig) -> Result<Report, Box<dyn Error>> {
+    let findings = doctor::examine(&config.encrypted_file, config.shares_file.as_deref());
+    let mut report = Report::new();
+    for finding in &findings {
+        let line = format!("{}: {}", finding.severity.name(), finding.message);
+        match finding.severity {
+            doctor::Severity::Ok => report.success(line),
+            doctor::Severity::Warning => report.warning(line),
+            doctor::Severity::Problem => report.error(line),
+        }
+        if let Some(suggestion) = &finding.suggestion {
+            report.line(format!("  suggestion: {}", suggestion));
+        }
+    }
+    let problems = findings
+        .iter()
+        .filter(|finding| finding.severity == doctor::Severity::Problem)
+        .count();
+    match problems {
+        0 => report.success("no problems found"),
+        1 => report.error("1 problem found"),
+        n => report.error(format!("{} problems found", n)),
+    }
+    report.field(
+        "findings",
+        Json::Array(
+            findings
+                .into_iter()
+                .map(|finding| {
+                    Json::object(vec![
+                        ("severity", finding.severity.name().into()),
+                        ("message", finding.message.into()),
+                        ("suggestion", finding.suggestion.into()),
+                    ])
+                })
+                .collect(),
+        ),
+    );
+    report.field("problems", problems);
+    Ok(report)
+}
+
+// The names of the unlock methods of a file, in the order of its key slots
+fn unlock_methods(header: &Header) -> Vec<&'static str> {
+    header
+        .key_slots
+        .iter()
+        .map(|slot| slot.method.name())
+        .collect()
+}
+
+// Guides the holders through entering their shares one by one,
+// and decrypts the file once the shares recover its key
+#[cfg(feature = "ceremony")]
+fn run_ceremony(config: CeremonyConfig) -> Result<Report, Box<dyn Error>> {
+    let mut reader = BufReader::new(File::open(&config.encrypted_file)?);
+    let header = Header::read_from(&mut reader)?;
+    let (ceremony, cipher) = enter_shares(&header, |fragments| {
+        unlock(&Unlock::Fragments(fragments), &header, false)
+    })?;
+    // the screen goes to stderr, leaving stdout to the report
+    let mut stdout = io::stderr();
+    let cipher = match cipher {
+        Some(cipher) => cipher,
+        None => {
+            ceremony.render(&mut stdout, "recovery failed: the file was not decrypted")?;
+            return Err(Box::new(ArgumentError(
+                "the shares entered do not recover the key".into(),
+            )));
+        }
+    };
+    decrypt_file(&header, &cipher, &mut reader)?;
+    let output = header.output_name()?.to_string_lossy().into_owned();
+    ceremony.render(
+        &mut stdout,
+        &format!("recovery succeeded: decrypted to {}", output),
+    )?;
+    let mut report = Report::new();
+    report.field("encrypted_file", config.encrypted_file.as_str());
+    report.field("decrypted_file", output.as_str());
+    report.field("shares", shares_json(ceremony.fragments()));
+    if let Some(path) = &config.transcript {
+        let transcript = transcript::new(&config.encrypted_file, &output, ceremony.fragments())?;
+        write_atomically(path, |writer| Ok(writeln!(writer, "{}", transcript)?))?;
+        report.line(format!(
+            "transcript written to {}, each participant signs it with sign-transcript",
+            path
+        ));
+        report.field("transcript", path.as_str());
+    }
+    Ok(report)
+}
+
+#[cfg(not(feature = "ceremony"))]
+fn run_ceremony(_: CeremonyConfig) -> Result<Report, Box<dyn Error>> {
+    unreachable!("ceremonies are rejected when parsing the arguments")
+}
+
+// asks the holders for their shares one by one until recover
+// accepts the shares entered, or None if they give up with an
+// empty line
+#[cfg(feature = "ceremony")]
+fn enter_shares<'a, T>(
+    header: &'a Header,
+    recover: impl Fn(Vec<Fragment>) -> Result<T, Box<dyn Error>>,
+) -> Result<(ceremony::Ceremony<'a>, Option<T>), Box<dyn Error>> {
+    let mut ceremony = ceremony::Ceremony::new(header);
+    // the screen goes to stderr, leaving stdout to the report
+    let mut stdout = io::stderr();
+    let mut message = String::from("enter the shares one by one, or an empty line to stop");
+    let value = loop {
+        ceremony.render(&mut stdout, &message)?;
+        // the shares are not echoed, so nobody else in the room sees them
+        let line = rpassword::read_password_from_tty(Some("share: "))?;
+        if line.trim().is_empty() {
+            break None;
+        }
+        message = match ceremony.enter(&line) {
+            Ok(Fragment {
+                holder: Some(holder),
+                ..
+            }) => format!("share of {} accepted", holder),
+            Ok(_) => "share accepted".into(),
+            Err(e) => {
+                message = format!("share rejected: {}", e);
+                continue;
+            }
+        };
+        if ceremony.ready() {
+            match recover(ceremony.fragments().to_vec()) {
+                Ok(value) => break Some(value),
+                Err(e) => message = format!("{}, but the key is not recovered yet: {}", message, e),
+            }
+        }
+    };
+    Ok((ceremony, value))
+}
+
+// Recovers the key of the shares once, in a ceremony or from a
+// shares file, and decrypts the files it opens until stopped
+#[cfg(unix)]
+fn run_agent(config: AgentConfig) -> Result<Report, Box<dyn Error>> {
+    let mut report = Report::new();
+    let encrypted_file = match &config.encrypted_file {
+        Some(encrypted_file) if !config.stop => encrypted_file,
+        _ => {
+            agent::stop(&config.socket)?;
+            report.line(format!("the agent of {} was stopped", config.socket));
+            return Ok(report);
+        }
+    };
+    let header = Header::read_from(&mut BufReader::new(File::open(encrypted_file)?))?;
+    let key = match &config.shares_file {
+        Some(shares_file) => agent_key(read_shares(shares_file)?, &header)?,
+        None => agent_ceremony_key(&header)?,
+    };
+    // the key is held until the agent stops, so it must not be swapped
+    if !key.key().is_locked() {
+        report::warn("the key of the agent is not in locked memory, it may be written to swap");
+    }
+    report::status(format!(
+        "The key was recovered, the agent listens on {}",
+        config.socket
+    ));
+    config.webhooks.send(
+        "threshold_reached",
+        vec![
+            ("operation", "agent".into()),
+            ("file", file_name(encrypted_file).into()),
+        ],
+    );
+    let decrypted = agent::serve(&config.socket, &key, &config.webhooks)?;
+    report.line(format!("files decrypted: {}", decrypted));
+    report.field("decrypted", decrypted);
+    Ok(report)
+}
+
+#[cfg(not(unix))]
+fn run_agent(_: AgentConfig) -> Result<Report, Box<dyn Error>> {
+    unreachable!("the agent is rejected when parsing the arguments")
+}
+
+// the key of the shares entered by their holders
+#[cfg(all(unix, feature = "ceremony"))]
+fn agent_ceremony_key(header: &Header) -> Result<Cipher, Box<dyn Error>> {
+    let (ceremony, key) = enter_shares(header, |fragments| agent_key(fragments, header))?;
+    let message = match key {
+        Some(_) => "recovery succeeded: the agent holds the key",
+        None => "recovery failed: the agent was not started",
+    };
+    ceremony.render(&mut io::stderr(), message)?;
+    key.ok_or_else(|| {
+        Box::new(ArgumentError(
+            "the shares entered do not recover the key".into(),
+        )) as _
+    })
+}
+
+#[cfg(all(unix, not(feature = "ceremony")))]
+fn agent_ceremony_key(_: &Header) -> Result<Cipher, Box<dyn Error>> {
+    unreachable!("agents without a shares file are rejected when parsing the arguments")
+}
+
+// the key of the shares, which must open the file it was recovered for
+#[cfg(unix)]
+fn agent_key(fragments: Vec<Fragment>, header: &Header) -> Result<Cipher, Box<dyn Error>> {
+    let key = shares_cipher(fragments, header, false)?;
+    unwrap_data_key(header, &key)?;
+    Ok(key)
+}
+
+// Serves the operations over HTTP until the process is stopped
+#[cfg(feature = "server")]
+fn run_serve(config: ServeConfig) -> Result<Report, Box<dyn Error>> {
+    service::check_loopback(&config.address, config.insecure_http)?;
+    let listener = std::net::TcpListener::bind(&config.address)?;
+    report::status(format!("Serving on {}", listener.local_addr()?));
+    if config.recovery.delay > Duration::ZERO {
+        report::status(format!(
+            "Recoveries wait {} seconds and can be vetoed at {}",
+            config.recovery.delay.as_secs(),
+            service::VETO_PATH
+        ));
+    }
+    if !config.holders.is_empty() {
+        report::status(format!(
+            "Only the shares of the {} enrolled holders are taken",
+            config.holders.len()
+        ));
+    }
+    if config.recovery.delay > Duration::ZERO && config.holders.is_empty() {
+        report::warn(
+            "only enrolled holders can veto a recovery, and none was given with --holder-tokens",
+        );
+    }
+    let server = service::Server::new(config.recovery, config.limits, config.webhooks)
+        .with_holders(config.holders);
+    service::serve(listener, &config.token, server)?;
+    Ok(Report::new())
+}
+
+#[cfg(not(feature = "server"))]
+fn run_serve(_: ServeConfig) -> Result<Report, Box<dyn Error>> {
+    unreachable!("the server is rejected when parsing the arguments")
+}
+
+// Enrolls a holder in the holder tokens file of a server and prints
+// their token, which is not saved anywhere
+fn run_enroll_holder(config: EnrollHolderConfig) -> Result<Report, Box<dyn Error>> {
+    let token = service::enroll_holder(&config.tokens_file, &config.holder, &config.index)?;
+    let mut report = Report::new();
+    report.line(format!(
+        "token of {}, for share {}: {}",
+        config.holder, config.index, token
+    ));
+    report.line("give it only to the holder, it cannot be shown again");
+    report.field("holder", config.holder);
+    report.field("index", config.index);
+    report.field("tokens_file", config.tokens_file);
+    report.field("token", token);
+    Ok(report)
+}
+
+// Calls an operation of a server and writes its result in the same
+// files the command line writes
+fn run_remote(config: RemoteConfig) -> Result<Report, Box<dyn Error>> {
+    use service::Operation;
+    service::check_loopback(&config.server, config.insecure_http)?;
+    let body = read_keyfile(&config.file)?;
+    let mut query = Vec::new();
+    if let Some((total_evals, min_required_evals)) = config.scheme {
+        query.push(("n", total_evals.to_string()));
+        query.push(("k", min_required_evals.to_string()));
+    }
+    let name = Path::new(&config.file)
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "file".into());
+    if config.operation == Operation::Encrypt {
+        query.push(("name", name.clone()));
+    }
+    let shares = match &config.shares_file {
+        Some(shares_file) => Some(String::from_utf8(read_keyfile(shares_file)?)?),
+        None => None,
+    };
+    let holder_tokens = match &config.holder_tokens_file {
+        Some(path) => Some(String::from_utf8(read_keyfile(path)?)?),
+        None => None,
+    };
+    let response = service::call(
+        &config.server,
+        &config.token,
+        config.operation,
+        &query,
+        body,
+        shares.as_deref(),
+        holder_tokens.as_deref(),
+    )?;
+    let mut report = Report::new();
+    match config.operation {
+        Operation::Split => {
+            let text = String::from_utf8(response.body)?;
+            report = shares_report(text.lines().map(String::from).collect());
+        }
+        Operation::Combine => {
+            let output = config.output.unwrap();
+            let mut secret = response.body;
+            let written = write_atomically(&output, |writer| Ok(writer.write_all(&secret)?));
+            secret.zeroize();
+            written?;
+            report.line(format!("the secret was written to {}", output));
+            report.field("output", output);
+        }
+        Operation::Encrypt => {
+            let dir = config.output.unwrap_or_else(|| ".".into());
+            let encrypted_file = format!("{}/{}.aes", dir, name);
+            let shares_file = format!("{}/{}.frg", dir, name);
+            let shares = response
+                .header(service::SHARES_HEADER)
+                .ok_or_else(|| ArgumentError("the server did not send the shares".into()))?;
+            let shares = service::decode_header(shares)?;
+            write_atomically(&encrypted_file, |writer| {
+                Ok(writer.write_all(&response.body)?)
+            })?;
+            write_atomically(&shares_file, |writer| Ok(writer.write_all(&shares)?))?;
+            report.field("encrypted_file", encrypted_file);
+            report.field("shares_file", shares_file);
+        }
+        Operation::Decrypt => {
+            let file_name = match response.header(service::FILE_NAME_HEADER) {
+                Some(file_name) => service::decode_header(file_name)?,
+                None => name.trim_end_matches(".aes").as_bytes().to_vec(),
+            };
+            // only the name is kept, so the server cannot choose the directory
+            let file_name = String::from_utf8_lossy(&file_name).into_owned();
+            let file_name = Path::new(&file_name)
+                .file_name()
+                .ok_or_else(|| ArgumentError("the server sent an invalid file name".into()))?;
+            let output = Path::new(config.output.as_deref().unwrap_or(".")).join(file_name);
+            write_atomically(&output, |writer| Ok(writer.write_all(&response.body)?))?;
+            report.field("decrypted_file", output.display().to_string());
+        }
+    }
+    Ok(report)
+}
+
+// Writes the configuration file with every setting commented out
+fn run_init_settings() -> Result<Report, Box<dyn Error>> {
+    let path = Settings::path().ok_or_else(|| {
+        ArgumentError("the configuration directory is unknown, set HOME or XDG_CONFIG_HOME".into())
+    })?;
+    fs::create_dir_all(path.parent().unwrap())?;
+    write_atomically(&path, |writer| {
+        Ok(writer.write_all(settings::TEMPLATE.as_bytes())?)
+    })?;
+    let mut report = Report::new();
+    report.line(format!("Created {}", path.display()));
+    report.field("settings_file", path.display().to_string());
+    Ok(report)
+}
+
+// Prints the official known-answer test vectors as JSON
+#[cfg(feature = "deterministic")]
+fn run_vectors() -> Result<Report, Box<dyn Error>> {
+    let vectors = kat::official_json()?;
+    let mut report = Report::new();
+    report.line(vectors.trim_end());
+    report.field("vectors", Json::Raw(vectors));
+    Ok(report)
+}
+
+#[cfg(not(feature = "deterministic"))]
+fn run_vectors() -> Result<Report, Box<dyn Error>> {
+    unreachable!("test vectors are rejected when parsing the arguments")
+}
+
+// Empties the clipboard after the timeout if it still holds the text
+// copied by another command, whose hash is read from the standard input
+fn run_clear_clipboard(timeout: Duration) -> Result<Report, Box<dyn Error>> {
+    let mut hash = String::new();
+    io::stdin().read_to_string(&mut hash)?;
+    clipboard::clear_after(hash.trim(), timeout)?;
+    Ok(Report::new())
+}
+
+// Splits the holders into the ones with a fragment and the ones without
+fn holders_report<'a>(
+    holders: &'a [String],
+    fragments: &[Fragment],
+) -> (Vec<&'a str>, Vec<&'a str>) {
+    holders.iter().map(String::as_str).partition(|holder| {
+        fragments
+            .iter()
+            .any(|f| f.holder.as_deref() == Some(*holder))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::Share;
+    use std::thread;
+
+    // Encrypts test_data/msg1.txt to <output_file>.aes in the current
+    // directory, in 2 of 3 shares; each test changes what it needs
+    fn test_config(output_file: &str) -> EncryptConfig {
+        EncryptConfig {
+            threads: 1,
+            ..EncryptConfig::new(
+                "test_data/msg1.txt",
+                ".",
+                output_file,
+                "secure password".into(),
+                3,
+                2,
+            )
+        }
+    }
+
+    // the temporary files of write_atomically left for a file
+    fn temporary_files(dir: &str, name: &str) -> Vec<PathBuf> {
+        fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .filter(|file| file.starts_with(&format!("{}.", name)) && file.ends_with(".tmp"))
+            .map(|file| Path::new(dir).join(file))
+            .collect()
+    }
+
+    #[test]
+    fn integration() {
+        let encrypt_config = EncryptConfig {
+            total_evals: 5,
+            min_required_evals: 4,
+            digest: true,
+            threads: 2,
+            ..test_config("ciphered")
+        };
+        let check_config = DecryptConfig {
+            unlock: Unlock::Shares("ciphered.frg".into()),
+            encrypted_file: "ciphered.aes".into(),
+            check: true,
+            cross_check: false,
+            copy: None,
+            max_memory: None,
+            get: None,
+            reveal: None,
+        };
         let decrypt_config = DecryptConfig {
-            shares_file: "ciphered.frg".into(),
+            unlock: Unlock::Shares("ciphered.frg".into()),
             encrypted_file: "ciphered.aes".into(),
+            check: false,
+            cross_check: false,
+            copy: None,
+            max_memory: None,
+            get: None,
+            reveal: None,
+        };
+        let report = run(Config::Encrypt(encrypt_config)).unwrap();
+        let json = report.to_json();
+        assert!(json.starts_with("{\"ok\":true,\"encrypted_file\":\"./ciphered.aes\""));
+        assert!(json.contains("\"shares_file\":\"./ciphered.frg\""));
+        assert_eq!(json.matches("\"fingerprint\"").count(), 5);
+        let report = run(Config::Decrypt(check_config)).unwrap();
+        assert_eq!(report.text(), "ciphered.aes: ok\n");
+        assert!(!Path::new("msg1.txt").exists());
+        let report = run(Config::Decrypt(decrypt_config)).unwrap();
+        assert!(report.to_json().contains("\"decrypted_file\":\"msg1.txt\""));
+        assert_eq!(
+            fs::read("test_data/msg1.txt").unwrap(),
+            fs::read("msg1.txt").unwrap()
+        );
+        fs::remove_file("ciphered.aes").unwrap();
+        fs::remove_file("ciphered.frg").unwrap();
+        fs::remove_file("msg1.txt").unwrap();
+    }
+
+    #[test]
+    fn tampered_header() {
+        let encrypt_config = EncryptConfig {
+            resume: true,
+            ..test_config("tampered")
+        };
+        run(Config::Encrypt(encrypt_config)).unwrap();
+        // rename the original file from msg1.txt to nsg1.txt
+        let mut encrypted = fs::read("tampered.aes").unwrap();
+        let name_start = container::MAGIC.len() + 1 + 4;
+        encrypted[name_start] = b'n';
+        fs::write("tampered.aes", encrypted).unwrap();
+        let decrypt_config = DecryptConfig {
+            unlock: Unlock::Shares("tampered.frg".into()),
+            encrypted_file: "tampered.aes".into(),
+            check: false,
+            cross_check: false,
+            copy: None,
+            max_memory: None,
+            get: None,
+            reveal: None,
+        };
+        assert!(run(Config::Decrypt(decrypt_config)).is_err());
+        // nothing is left behind when decryption fails
+        assert!(!Path::new("nsg1.txt").exists());
+        assert!(temporary_files(".", "nsg1.txt").is_empty());
+        fs::remove_file("tampered.aes").unwrap();
+        fs::remove_file("tampered.frg").unwrap();
+    }
+
+    #[test]
+    fn signed_manifest() {
+        let _ = fs::remove_file("operator.key");
+        let key_config = OperatorKeyConfig {
+            key_file: "operator.key".into(),
+        };
+        let report = run(Config::OperatorKey(key_config)).unwrap();
+        let public_key = report
+            .text()
+            .trim_start_matches("public key: ")
+            .trim()
+            .to_string();
+        let encrypt_config = EncryptConfig {
+            manifest_key: Some("operator.key".into()),
+            ..test_config("manifested")
+        };
+        run(Config::Encrypt(encrypt_config)).unwrap();
+        let verify_config = |public_key: &str| VerifyManifestConfig {
+            manifest: "manifested.manifest.json".into(),
+            public_key: Some(public_key.into()),
+        };
+        let report = run(Config::VerifyManifest(verify_config(&public_key))).unwrap();
+        assert!(report.text().contains("manifested.aes: ok"));
+        assert!(report.text().contains("manifested.frg: ok"));
+        // the shares were replaced after the ceremony
+        let shares = fs::read_to_string("manifested.frg").unwrap();
+        fs::write("manifested.frg", shares.replacen('1', "2", 1)).unwrap();
+        assert!(run(Config::VerifyManifest(verify_config(&public_key))).is_err());
+        fs::write("manifested.frg", shares).unwrap();
+        assert!(run(Config::VerifyManifest(verify_config(&"0".repeat(64)))).is_err());
+        for file in &[
+            "operator.key",
+            "manifested.aes",
+            "manifested.frg",
+            "manifested.manifest.json",
+        ] {
+            fs::remove_file(file).unwrap();
+        }
+    }
+
+    #[test]
+    fn share_registry() {
+        let encrypt_config = EncryptConfig {
+            holders: vec!["alice".into(), "bob".into(), "carol".into()],
+            registry: true,
+            ..test_config("registered")
+        };
+        let report = run(Config::Encrypt(encrypt_config)).unwrap();
+        assert!(report
+            .text()
+            .contains("share registry: ./registered.registry.json"));
+        let registry = fs::read_to_string("registered.registry.json").unwrap();
+        let mut fragments = fragments::read_fragments("registered.frg").unwrap();
+        assert!(!registry.contains(&fragments[0].share.1));
+        let check_config = || CheckRegistryConfig {
+            registry: "registered.registry.json".into(),
+            shares_file: "registered.two.frg".into(),
+        };
+        let mut file = File::create("registered.two.frg").unwrap();
+        fragments::write_fragments(&fragments[1..], Format::Lines, &mut file).unwrap();
+        let report = run(Config::CheckRegistry(check_config())).unwrap();
+        assert!(report.text().contains("  bob: ok\n  carol: ok\n"));
+        assert!(report
+            .text()
+            .contains("2 shares match registered.registry.json"));
+        // a share submitted later that is not the one of the ceremony
+        let y = &mut fragments[2].share.1;
+        let first = if y.starts_with('0') { "1" } else { "0" };
+        y.replace_range(..1, first);
+        let mut file = File::create("registered.two.frg").unwrap();
+        fragments::write_fragments(&fragments[1..], Format::Lines, &mut file).unwrap();
+        assert!(run(Config::CheckRegistry(check_config())).is_err());
+        for file in &[
+            "registered.aes",
+            "registered.frg",
+            "registered.registry.json",
+            "registered.two.frg",
+        ] {
+            fs::remove_file(file).unwrap();
+        }
+    }
+
+    #[test]
+    fn bundled_shares() {
+        let encrypt_config = EncryptConfig {
+            holders: vec!["alice".into(), "bob".into(), "carol".into()],
+            bundle: true,
+            format: Format::Csv,
+            ..test_config("bundled")
+        };
+        let report = run(Config::Encrypt(encrypt_config)).unwrap();
+        assert!(report
+            .to_json()
+            .contains("\"bundle_file\":\"./bundled.bundle\""));
+        let bundle = fs::read_to_string("bundled.bundle").unwrap();
+        assert!(bundle.starts_with(bundle::BUNDLE_BEGIN));
+        assert_eq!(
+            bundle::read_bundle(&bundle).unwrap().fragments,
+            fragments::read_fragments("bundled.csv").unwrap()
+        );
+        let inspect_config = InspectConfig {
+            encrypted_file: "bundled.aes".into(),
+            shares_file: Some("bundled.bundle".into()),
+            fingerprint: None,
+        };
+        let report = run(Config::Inspect(inspect_config)).unwrap();
+        assert!(report.text().contains("bytes of shares compressed to"));
+        assert!(report.text().contains("  share 2: bob ("));
+        assert!(report.text().contains("missing: \n"));
+        let fragments = fragments::read_fragments("bundled.csv").unwrap();
+        let set_fingerprint = fragments::set_fingerprint(&fragments);
+        assert!(report
+            .text()
+            .contains(&format!("share set fingerprint: {}", set_fingerprint)));
+        let inspect_fingerprint = |fingerprint: &str| {
+            run(Config::Inspect(InspectConfig {
+                encrypted_file: "bundled.aes".into(),
+                shares_file: Some("bundled.csv".into()),
+                fingerprint: Some(fingerprint.into()),
+            }))
+        };
+        let report = inspect_fingerprint(&fragments[1].fingerprint().to_uppercase()).unwrap();
+        assert!(report
+            .text()
+            .contains("is the fingerprint of share 2 (bob)"));
+        let report = inspect_fingerprint(&set_fingerprint).unwrap();
+        assert!(report.text().contains("of the share set"));
+        assert!(inspect_fingerprint("00000000").is_err());
+        let check_config = DecryptConfig {
+            unlock: Unlock::Shares("bundled.bundle".into()),
+            encrypted_file: "bundled.aes".into(),
+            check: true,
+            cross_check: false,
+            copy: None,
+            max_memory: None,
+            get: None,
+            reveal: None,
+        };
+        run(Config::Decrypt(check_config)).unwrap();
+        for file in &["bundled.aes", "bundled.csv", "bundled.bundle"] {
+            fs::remove_file(file).unwrap();
+        }
+    }
+
+    #[test]
+    fn envelopes() {
+        let encrypt_config = EncryptConfig {
+            holders: vec!["alice".into(), "bob".into(), "carol".into()],
+            ..test_config("enveloped")
+        };
+        run(Config::Encrypt(encrypt_config)).unwrap();
+        let envelopes_config = EnvelopesConfig {
+            encrypted_file: "enveloped.aes".into(),
+            shares_file: "enveloped.frg".into(),
+            output_dir: "envelopes".into(),
+            contact: Some("the notary".into()),
+        };
+        let report = run(Config::Envelopes(envelopes_config)).unwrap();
+        assert!(report.text().contains("wrote 3 envelopes to envelopes/"));
+        let fragments = fragments::read_fragments("enveloped.frg").unwrap();
+        let share = fragments::read_fragments("envelopes/2-bob/share.txt").unwrap();
+        assert_eq!(share, [fragments[1].clone()]);
+        let cover = fs::read_to_string("envelopes/2-bob/cover.txt").unwrap();
+        assert!(cover.contains("Encrypted file: enveloped.aes\n"));
+        assert!(cover.contains("2 of 3 shares"));
+        assert!(cover.contains(&fragments::set_fingerprint(&fragments)));
+        assert!(cover.contains("Contact: the notary\n"));
+        // two holders are enough to decrypt
+        let shares = fs::read_to_string("envelopes/1-alice/share.txt").unwrap()
+            + &fs::read_to_string("envelopes/3-carol/share.txt").unwrap();
+        fs::write("enveloped.two.frg", shares).unwrap();
+        run(Config::Decrypt(DecryptConfig {
+            unlock: Unlock::Shares("enveloped.two.frg".into()),
+            encrypted_file: "enveloped.aes".into(),
+            check: true,
+            cross_check: false,
+            copy: None,
+            max_memory: None,
+            get: None,
+            reveal: None,
+        }))
+        .unwrap();
+        fs::remove_dir_all("envelopes").unwrap();
+        for file in &["enveloped.aes", "enveloped.frg", "enveloped.two.frg"] {
+            fs::remove_file(file).unwrap();
+        }
+    }
+
+    #[test]
+    fn named_secrets() {
+        fs::create_dir_all("named").unwrap();
+        fs::write(
+            "named/secrets.env",
+            "DB_USER=admin\nDB_PASSWORD=\"p@ss word\"\n",
+        )
+        .unwrap();
+        fs::write("named/broken.env", "DB_USER\n").unwrap();
+        let encrypt_config = |input: &str| EncryptConfig {
+            input_file: input.into(),
+            output_dir: "named".into(),
+            entries: true,
+            ..test_config("secrets")
+        };
+        // a file that is not of named secrets is not encrypted
+        assert!(run(Config::Encrypt(encrypt_config("named/broken.env"))).is_err());
+        assert!(!Path::new("named/secrets.aes").exists());
+        let report = run(Config::Encrypt(encrypt_config("named/secrets.env"))).unwrap();
+        assert!(report.text().contains("2 secrets: DB_USER, DB_PASSWORD\n"));
+        assert!(!report.text().contains("p@ss"));
+        fs::remove_file("named/secrets.env").unwrap();
+
+        let decrypt_config = |get: &str| DecryptConfig {
+            unlock: Unlock::Shares("named/secrets.frg".into()),
+            encrypted_file: "named/secrets.aes".into(),
+            check: false,
+            cross_check: false,
+            copy: None,
+            max_memory: None,
+            get: Some(get.into()),
+            reveal: None,
+        };
+        let report = run(Config::Decrypt(decrypt_config("DB_PASSWORD"))).unwrap();
+        assert_eq!(report.text(), "p@ss word\n");
+        // the file is never written
+        assert!(!Path::new("secrets.env").exists());
+        assert!(!Path::new("named/secrets.env").exists());
+        let error = run(Config::Decrypt(decrypt_config("API_KEY"))).unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("the secrets are: DB_USER, DB_PASSWORD"));
+        fs::remove_dir_all("named").unwrap();
+    }
+
+    #[test]
+    fn reveal_parts() {
+        // a tar archive with a small file after a large one
+        let mut tar = Vec::new();
+        let mut push = |name: &str, content: &[u8]| {
+            let mut block = vec![0u8; 512];
+            block[..name.len()].copy_from_slice(name.as_bytes());
+            block[124..135].copy_from_slice(format!("{:011o}", content.len()).as_bytes());
+            block[156] = b'0';
+            block[148..156].copy_from_slice(b"        ");
+            let checksum: u32 = block.iter().map(|&byte| byte as u32).sum();
+            block[148..155].copy_from_slice(format!("{:06o}\0", checksum).as_bytes());
+            tar.extend(block);
+            tar.extend(content);
+            tar.resize(tar.len().div_ceil(512) * 512, 0);
+        };
+        let large: Vec<u8> = (0..3 * CHUNK_SIZE as u32).map(|i| i as u8).collect();
+        push("data/large.bin", &large);
+        push("config/id_rsa", b"private key");
+        tar.resize(tar.len() + 1024, 0);
+        fs::create_dir_all("reveal").unwrap();
+        fs::write("reveal/backup.tar", &tar).unwrap();
+        let encrypt_config = EncryptConfig {
+            input_file: "reveal/backup.tar".into(),
+            output_dir: "reveal".into(),
+            ..test_config("backup")
+        };
+        run(Config::Encrypt(encrypt_config)).unwrap();
+
+        let reveal = |reveal: Reveal| {
+            let mut reader = BufReader::new(File::open("reveal/backup.aes").unwrap());
+            let header = Header::read_from(&mut reader).unwrap();
+            let cipher =
+                unlock(&Unlock::Shares("reveal/backup.frg".into()), &header, false).unwrap();
+            let mut part = Vec::new();
+            reveal_part(&header, &cipher, reader, &reveal, &mut part).map(|_| part)
+        };
+        let start = CHUNK_SIZE as u64 - 10;
+        let part = reveal(Reveal::Range(start, Some(start + 4096))).unwrap();
+        assert_eq!(part, &tar[start as usize..start as usize + 4096]);
+        let part = reveal(Reveal::Range(tar.len() as u64 - 100, None)).unwrap();
+        assert_eq!(part, &tar[tar.len() - 100..]);
+        assert!(reveal(Reveal::Range(tar.len() as u64 + 1, None)).is_err());
+        let part = reveal(Reveal::Entry("config/id_rsa".into())).unwrap();
+        assert_eq!(part, b"private key");
+        assert!(reveal(Reveal::Entry("config/missing".into())).is_err());
+        fs::remove_dir_all("reveal").unwrap();
+    }
+
+    #[test]
+    fn export_offline() {
+        let encrypt_config = EncryptConfig {
+            holders: vec!["alice".into(), "bob".into(), "carol".into()],
+            registry: true,
+            ..test_config("offline")
         };
         run(Config::Encrypt(encrypt_config)).unwrap();
-        run(Config::Decrypt(decrypt_config)).unwrap();
+        let export = |shares_file: Option<&str>, binary: Option<&str>| {
+            run(Config::ExportOffline(ExportOfflineConfig {
+                encrypted_file: "offline.aes".into(),
+                output_dir: "offline".into(),
+                shares_file: shares_file.map(String::from),
+                registry: Some("offline.registry.json".into()),
+                binary: binary.map(String::from),
+            }))
+        };
+        let report = export(Some("offline.frg"), Some("test_data/msg1.txt")).unwrap();
+        assert!(report
+            .text()
+            .contains("wrote the offline bundle of offline.aes to offline/"));
+        // a directory that exists is not overwritten
+        assert!(export(None, None).is_err());
+        let binary = offline::Bundle::binary_path("test_data/msg1.txt");
         assert_eq!(
-            fs::read("test_data/msg1.txt").unwrap(),
-            fs::read("msg1.txt").unwrap()
+            fs::read("offline/offline.aes").unwrap(),
+            fs::read("offline.aes").unwrap()
         );
-        fs::remove_file("ciphered.aes").unwrap();
-        fs::remove_file("ciphered.frg").unwrap();
-        fs::remove_file("msg1.txt").unwrap();
+        assert_eq!(
+            fs::read(format!("offline/{}", binary)).unwrap(),
+            fs::read("test_data/msg1.txt").unwrap()
+        );
+        let instructions = fs::read_to_string("offline/RECOVERY.txt").unwrap();
+        assert!(instructions.contains("2 of 3 shares"));
+        assert!(instructions.contains(&format!("{} d offline.aes <shares file> --check", binary)));
+        let fragments = fragments::read_fragments("offline.frg").unwrap();
+        let fingerprints = fs::read_to_string("offline/FINGERPRINTS.txt").unwrap();
+        assert!(fingerprints.starts_with(&format!("alice: {}\n", fragments[0].fingerprint())));
+        // the shares are not in the bundle
+        assert!(!fingerprints.contains(&fragments[0].share.1));
+        let checksums = fs::read_to_string("offline/SHA256SUMS").unwrap();
+        let files: Vec<&str> = checksums.lines().map(|line| &line[66..]).collect();
+        assert_eq!(
+            files,
+            [
+                "offline.aes",
+                "offline.registry.json",
+                &binary,
+                "FINGERPRINTS.txt",
+                "RECOVERY.txt"
+            ]
+        );
+        fs::remove_dir_all("offline").unwrap();
+
+        // the fingerprints can also come from the registry
+        export(None, None).unwrap();
+        let from_registry = fs::read_to_string("offline/FINGERPRINTS.txt").unwrap();
+        assert_eq!(from_registry, fingerprints);
+        assert!(fs::read_to_string("offline/RECOVERY.txt")
+            .unwrap()
+            .contains("static binary"));
+        fs::remove_dir_all("offline").unwrap();
+        for file in &["offline.aes", "offline.frg", "offline.registry.json"] {
+            fs::remove_file(file).unwrap();
+        }
+    }
+
+    #[test]
+    fn encrypt_to_self() {
+        let encrypt_config = EncryptConfig {
+            to_self: Some(SelfStore::Keyfile("to_self.key".into())),
+            ..test_config("to_self")
+        };
+        let report = run(Config::Encrypt(encrypt_config)).unwrap();
+        assert!(report.text().contains("kept share 1 in to_self.key"));
+        let fragments = fragments::read_fragments("to_self.frg").unwrap();
+        assert_eq!(fragments.len(), 2);
+        assert!(fragments.iter().all(|fragment| fragment.share.0 != "1"));
+        let check = |unlock| {
+            run(Config::Decrypt(DecryptConfig {
+                unlock,
+                encrypted_file: "to_self.aes".into(),
+                check: true,
+                cross_check: false,
+                copy: None,
+                max_memory: None,
+                get: None,
+                reveal: None,
+            }))
+        };
+        // the owner alone, or the other shares if the key is lost
+        check(Unlock::Keyfile("to_self.key".into())).unwrap();
+        check(Unlock::Shares("to_self.frg".into())).unwrap();
+        // the kept share is still a share of the key
+        let kept = fs::read_to_string("to_self.key").unwrap();
+        fs::write(
+            "to_self.two.frg",
+            format!("{}\n{}\n", kept, fragments[0].to_line()),
+        )
+        .unwrap();
+        check(Unlock::Shares("to_self.two.frg".into())).unwrap();
+        for file in &[
+            "to_self.aes",
+            "to_self.frg",
+            "to_self.key",
+            "to_self.two.frg",
+        ] {
+            fs::remove_file(file).unwrap();
+        }
+    }
+
+    #[test]
+    fn recovery_agents() {
+        let report = run(Config::RecoveryAgentKey(RecoveryAgentKeyConfig {
+            key_file: "agent.key".into(),
+        }))
+        .unwrap();
+        let public_key = report
+            .text()
+            .trim_start_matches("public key: ")
+            .trim()
+            .to_string();
+        let encrypt_config = EncryptConfig {
+            recovery_agents: vec![escrow::parse_public_key(&public_key).unwrap()],
+            ..test_config("escrowed")
+        };
+        run(Config::Encrypt(encrypt_config)).unwrap();
+        let report = run(Config::Inspect(InspectConfig {
+            encrypted_file: "escrowed.aes".into(),
+            shares_file: None,
+            fingerprint: None,
+        }))
+        .unwrap();
+        assert!(report
+            .text()
+            .contains(&format!("recovery agents: {}\n", public_key)));
+        let check = |key_file: &str| {
+            run(Config::Decrypt(DecryptConfig {
+                unlock: Unlock::RecoveryKey(key_file.into()),
+                encrypted_file: "escrowed.aes".into(),
+                check: true,
+                cross_check: false,
+                copy: None,
+                max_memory: None,
+                get: None,
+                reveal: None,
+            }))
+        };
+        check("agent.key").unwrap();
+        // the key of another agent
+        fs::write("other.key", "07".repeat(escrow::KEY_SIZE)).unwrap();
+        assert!(check("other.key").is_err());
+        for file in &["agent.key", "other.key", "escrowed.aes", "escrowed.frg"] {
+            fs::remove_file(file).unwrap();
+        }
+    }
+
+    #[test]
+    fn pin_protected_shares() {
+        let encrypt_config = EncryptConfig {
+            holders: vec!["alice".into(), "bob".into(), "carol".into()],
+            pins: vec!["".into(), "1357".into(), "".into()],
+            ..test_config("pins")
+        };
+        let report = run(Config::Encrypt(encrypt_config)).unwrap();
+        assert!(report
+            .to_json()
+            .contains("\"holder\":\"bob\",\"fingerprint\""));
+        let protected = fragments::read_fragments("pins.frg").unwrap();
+        assert!(protected[0].pin.is_none() && protected[2].pin.is_none());
+        assert!(protected[1].pin.is_some());
+        // the masked share does not recover the key
+        let header =
+            Header::read_from(&mut BufReader::new(File::open("pins.aes").unwrap())).unwrap();
+        let error = shares_cipher(protected[..2].to_vec(), &header, false);
+        assert_eq!(
+            error.err().unwrap().to_string(),
+            "the share of bob is protected with a PIN"
+        );
+        let wrong = unprotect_fragments(protected.clone(), |_| Ok("1358".into()));
+        assert!(wrong.is_err());
+        let mut asked = Vec::new();
+        let fragments = unprotect_fragments(protected, |fragment| {
+            asked.push(fragment.holder.clone().unwrap());
+            Ok("1357\n".into())
+        })
+        .unwrap();
+        assert_eq!(asked, vec!["bob"]);
+        assert!(fragments.iter().all(|fragment| fragment.pin.is_none()));
+        let mut writer = File::create("pins.frg").unwrap();
+        fragments::write_fragments(&fragments[1..], Format::Lines, &mut writer).unwrap();
+        let check_config = DecryptConfig {
+            unlock: Unlock::Shares("pins.frg".into()),
+            encrypted_file: "pins.aes".into(),
+            check: true,
+            cross_check: false,
+            copy: None,
+            max_memory: None,
+            get: None,
+            reveal: None,
+        };
+        run(Config::Decrypt(check_config)).unwrap();
+        fs::remove_file("pins.aes").unwrap();
+        fs::remove_file("pins.frg").unwrap();
+    }
+
+    #[test]
+    fn doctor_findings() {
+        let encrypt_config = EncryptConfig {
+            holders: vec!["alice".into(), "bob".into(), "carol".into()],
+            ..test_config("doctor")
+        };
+        run(Config::Encrypt(encrypt_config)).unwrap();
+        let doctor = |encrypted_file: &str, shares_file: &str| {
+            run(Config::Doctor(DoctorConfig {
+                encrypted_file: encrypted_file.into(),
+                shares_file: Some(shares_file.into()),
+            }))
+            .unwrap()
+            .text()
+        };
+        let text = doctor("doctor.aes", "doctor.frg");
+        assert!(text.contains("ok: the shares open the file\n"));
+        assert!(text.ends_with("no problems found\n"));
+
+        let content = fs::read("doctor.aes").unwrap();
+        fs::write("doctor-truncated.aes", &content[..content.len() - 10]).unwrap();
+        let text = doctor("doctor-truncated.aes", "doctor.frg");
+        assert!(text.contains("problem: doctor-truncated.aes is truncated"));
+        assert!(text.contains("  suggestion: copy it again from a backup"));
+
+        // one share of this file, a copy of it and one of another file
+        let shares = fs::read_to_string("doctor.frg").unwrap();
+        let alice = shares.lines().nth(1).unwrap();
+        fs::write(
+            "doctor-wrong.frg",
+            format!("{}\n{}\n9:1f:holder=dave:len=32\n", alice, alice),
+        )
+        .unwrap();
+        let text = doctor("doctor.aes", "doctor-wrong.frg");
+        assert!(text.contains("warning: the share of alice is given more than once"));
+        assert!(text.contains("problem: the shares of dave are not of this file"));
+        assert!(text.ends_with("1 problem found\n"));
+        fs::write("doctor-wrong.frg", format!("{}\n", alice)).unwrap();
+        let text = doctor("doctor.aes", "doctor-wrong.frg");
+        assert!(text.contains(
+            "problem: found 1 shares, at least 2 are needed, missing the shares of bob, carol"
+        ));
+        for file in &[
+            "doctor.aes",
+            "doctor.frg",
+            "doctor-truncated.aes",
+            "doctor-wrong.frg",
+        ] {
+            fs::remove_file(file).unwrap();
+        }
+    }
+
+    #[test]
+    fn migrate_legacy_files() {
+        // a file and shares as written before the header and the
+        // version line, 2 of 3 shares
+        let key = SecretKey::new(vec![7u8; 32]);
+        let fragments = key_fragments(&key, 3, 2, None).unwrap();
+        let mut content = fs::read("test_data/msg1.txt").unwrap();
+        key.into_cipher(CipherSuite::Aes256Gcm)
+            .unwrap()
+            .encrypt(&mut content)
+            .unwrap();
+        let mut legacy = b"msg1.txt\n".to_vec();
+        legacy.extend(content);
+        fs::write("legacy.aes", legacy).unwrap();
+        let lines: Vec<String> = fragments.iter().map(Fragment::to_line).collect();
+        fs::write(
+            "legacy.frg",
+            format!("# old shares\n{}\n", lines.join("\n")),
+        )
+        .unwrap();
+
+        let migrate = |total_evals| {
+            run(Config::Migrate(MigrateConfig {
+                encrypted_file: "legacy.aes".into(),
+                shares_file: "legacy.frg".into(),
+                total_evals,
+            }))
+        };
+        // the file does not record the total, nor do the shares at hand
+        assert!(migrate(None).is_err());
+        assert!(migrate(Some(2)).is_err());
+        let report = migrate(Some(4)).unwrap();
+        assert_eq!(
+            report.text(),
+            format!(
+                "legacy.aes: migrated from format version 0 to {}\n\
+                 legacy.frg: migrated from format version 0 to 1\n",
+                FORMAT_VERSION
+            )
+        );
+        let header = Header::read_from(&mut BufReader::new(File::open("legacy.aes").unwrap()));
+        assert_eq!(header.unwrap().scheme, Some((2, 4)));
+        let shares = fs::read_to_string("legacy.frg").unwrap();
+        assert!(shares.starts_with(&format!("{}\n# old shares\n", fragments::version_line())));
+        assert!(migrate(None).unwrap().text().contains("nothing to migrate"));
+        let report = run(Config::Inspect(InspectConfig {
+            encrypted_file: "legacy.aes".into(),
+            shares_file: Some("legacy.frg".into()),
+            fingerprint: None,
+        }))
+        .unwrap();
+        let text = report.text();
+        assert!(text.contains(&format!("format version: {}\n", FORMAT_VERSION)));
+        assert!(text.contains(&format!("written by: shared_secrets {}\n", VERSION)));
+        assert!(text.contains("shares format version: 1\n"));
+        assert!(text.contains(&format!("shares written by: shared_secrets {}\n", VERSION)));
+        assert!(report
+            .to_json()
+            .contains(&format!("\"program_version\":\"{}\"", VERSION)));
+
+        // two of the shares still open the file
+        fs::write("legacy-two.frg", format!("{}\n{}\n", lines[0], lines[2])).unwrap();
+        let report = run(Config::Decrypt(DecryptConfig {
+            unlock: Unlock::Shares("legacy-two.frg".into()),
+            encrypted_file: "legacy.aes".into(),
+            check: true,
+            cross_check: false,
+            copy: None,
+            max_memory: None,
+            get: None,
+            reveal: None,
+        }))
+        .unwrap();
+        assert_eq!(report.text(), "legacy.aes: ok\n");
+
+        for file in &["legacy.aes", "legacy.frg", "legacy-two.frg"] {
+            fs::remove_file(file).unwrap();
+        }
+    }
+
+    #[test]
+    fn memory_budget() {
+        let chunks = 2 * SEALED_CHUNK_SIZE as u64;
+        assert_eq!(budget_threads(8, 4 * chunks).unwrap(), 4);
+        assert_eq!(budget_threads(2, 4 * chunks).unwrap(), 2);
+        assert_eq!(budget_threads(8, chunks).unwrap(), 1);
+        assert!(budget_threads(8, chunks - 1).is_err());
+
+        let encrypt_config = test_config("budget");
+        run(Config::Encrypt(encrypt_config)).unwrap();
+        let decrypt_config = |copy, max_memory| DecryptConfig {
+            unlock: Unlock::Shares("budget.frg".into()),
+            encrypted_file: "budget.aes".into(),
+            check: true,
+            cross_check: false,
+            copy,
+            max_memory,
+            get: None,
+            reveal: None,
+        };
+        // the clipboard takes the whole file
+        let error = run(Config::Decrypt(decrypt_config(
+            Some(Duration::from_secs(1)),
+            Some(16),
+        )))
+        .err()
+        .unwrap();
+        assert!(error.to_string().contains("memory budget of 16 bytes"));
+        // the file is checked chunk by chunk
+        run(Config::Decrypt(decrypt_config(None, Some(16)))).unwrap();
+        fs::remove_file("budget.aes").unwrap();
+        fs::remove_file("budget.frg").unwrap();
+    }
+
+    #[test]
+    fn golden_files() {
+        // files written by an earlier build, which every platform
+        // must keep reading the same
+        let decrypt = |encrypted: &str, shares: &str| {
+            let mut reader = BufReader::new(File::open(encrypted).unwrap());
+            let header = Header::read_from(&mut reader).unwrap();
+            let cipher = unlock(&Unlock::Shares(shares.into()), &header, true).unwrap();
+            let mut plaintext = Vec::new();
+            decrypt_into(&header, &cipher, &mut reader, &mut plaintext).unwrap();
+            (header, plaintext)
+        };
+        let expected = fs::read("test_data/msg1.txt").unwrap();
+        let (header, plaintext) = decrypt("test_data/golden/msg1.aes", "test_data/golden/msg1.frg");
+        assert_eq!(header.version, FORMAT_VERSION);
+        assert_eq!(header.holders, ["alice", "bob", "carol"]);
+        assert!(header.digest.is_some());
+        assert_eq!(header.length, Some(expected.len() as u64));
+        assert_eq!(plaintext, expected);
+        let (_, plaintext) = decrypt(
+            "test_data/golden/msg1-secp256k1.aes",
+            "test_data/golden/msg1-secp256k1.csv",
+        );
+        assert_eq!(plaintext, expected);
+        // a key longer than a block of the field of 2^127 - 1
+        let fragments = fragments::read_fragments("test_data/golden/key-127.frg").unwrap();
+        let key = fragments_key(fragments).unwrap();
+        assert_eq!(
+            encoding::to_hex(key.expose()),
+            "7be8ebcb2c9fe9100665da209e5bb5cc1032ec1e8b1bd24e\
+             5736a4f51778b8dd58038d08df614e0024a300709be9b5d8"
+        );
+    }
+
+    #[test]
+    fn resume_interrupted() {
+        let public_key = escrow::generate_key("resumed.agent").unwrap();
+        let agents = vec![escrow::parse_public_key(&public_key).unwrap()];
+        let encrypt_config = |recovery_agents: &[[u8; PUBLIC_KEY_SIZE]]| EncryptConfig {
+            subkeys: true,
+            resume: true,
+            recovery_agents: recovery_agents.to_vec(),
+            ..test_config("resumed")
+        };
+        run(Config::Encrypt(encrypt_config(&agents))).unwrap();
+        // leave the output as if the encryption was interrupted
+        fs::remove_file("resumed.frg").unwrap();
+        fs::rename("resumed.aes", "resumed.aes.part").unwrap();
+        let mut reader = BufReader::new(File::open("resumed.aes.part").unwrap());
+        Header::read_from(&mut reader).unwrap();
+        let mut salt = [0u8; crypto::chunked::SALT_SIZE];
+        reader.read_exact(&mut salt).unwrap();
+        Checkpoint { salt, chunks: 0 }
+            .save("./resumed.aes.journal")
+            .unwrap();
+        // other recovery agents are another encryption
+        assert!(run(Config::Encrypt(encrypt_config(&[]))).is_err());
+        run(Config::Encrypt(encrypt_config(&agents))).unwrap();
+        assert!(!Path::new("resumed.aes.journal").exists());
+        let check_config = |unlock| DecryptConfig {
+            unlock,
+            encrypted_file: "resumed.aes".into(),
+            check: true,
+            cross_check: false,
+            copy: None,
+            max_memory: None,
+            get: None,
+            reveal: None,
+        };
+        run(Config::Decrypt(check_config(Unlock::Shares(
+            "resumed.frg".into(),
+        ))))
+        .unwrap();
+        let recovery_key = Unlock::RecoveryKey("resumed.agent".into());
+        run(Config::Decrypt(check_config(recovery_key))).unwrap();
+        fs::remove_file("resumed.aes").unwrap();
+        fs::remove_file("resumed.frg").unwrap();
+        fs::remove_file("resumed.agent").unwrap();
+    }
+
+    #[test]
+    fn rotate_password() {
+        let encrypt_config = EncryptConfig {
+            suite: CipherSuite::Aes256GcmSiv,
+            ..test_config("rotated")
+        };
+        run(Config::Encrypt(encrypt_config)).unwrap();
+        fs::copy("rotated.frg", "rotated-old.frg").unwrap();
+        let content = fs::read("rotated.aes").unwrap();
+        let rotate_config = RotateConfig {
+            encrypted_file: "rotated.aes".into(),
+            shares_file: "rotated.frg".into(),
+            password: "new password".into(),
+        };
+        run(Config::Rotate(rotate_config)).unwrap();
+        let rotated = fs::read("rotated.aes").unwrap();
+        // only the wrapped key changes
+        assert_eq!(content.len(), rotated.len());
+        assert_ne!(content, rotated);
+        let check = |shares_file: &str| {
+            run(Config::Decrypt(DecryptConfig {
+                unlock: Unlock::Shares(shares_file.into()),
+                encrypted_file: "rotated.aes".into(),
+                check: true,
+                cross_check: false,
+                copy: None,
+                max_memory: None,
+                get: None,
+                reveal: None,
+            }))
+        };
+        assert!(check("rotated-old.frg").is_err());
+        assert!(check("rotated.frg").is_ok());
+        fs::remove_file("rotated.aes").unwrap();
+        fs::remove_file("rotated.frg").unwrap();
+        fs::remove_file("rotated-old.frg").unwrap();
+    }
+
+    #[test]
+    fn forged_shares() {
+        let header = Header::new(
+            b"msg.txt".to_vec(),
+            Vec::new(),
+            2,
+            3,
+            CipherSuite::Aes256Gcm,
+        );
+        let cipher = Cipher::from_key(&[0x21u8; 32], CipherSuite::Aes256Gcm).unwrap();
+        let mut fragments = split_fragments(&cipher, None, 3, 2, &[], None, None).unwrap();
+        fragments[0].share.1 = fragments[1].share.1.clone();
+        let mac = fragments[2].mac.clone().unwrap();
+        let recovered = shares_cipher(fragments.clone(), &header, false).unwrap();
+        assert!(recovered.verify_share(&fragments[2].share, &mac));
+        // without MACs the forged share recovers a wrong key, if any
+        let unauthenticated: Vec<_> = fragments
+            .iter()
+            .map(|f| Fragment {
+                mac: None,
+                pin: None,
+                ..f.clone()
+            })
+            .collect();
+        if let Ok(recovered) = shares_cipher(unauthenticated, &header, false) {
+            assert!(!recovered.verify_share(&fragments[2].share, &mac));
+        }
+        assert!(shares_cipher(fragments[..2].to_vec(), &header, false).is_err());
+    }
+
+    #[test]
+    fn not_enough_shares() {
+        let header = Header::new(
+            b"msg.txt".to_vec(),
+            Vec::new(),
+            3,
+            4,
+            CipherSuite::Aes256Gcm,
+        );
+        let cipher = Cipher::from_key(&[0x23u8; 32], CipherSuite::Aes256Gcm).unwrap();
+        let fragments = split_fragments(&cipher, None, 4, 3, &[], None, None).unwrap();
+        let copies = vec![
+            fragments[0].clone(),
+            fragments[1].clone(),
+            fragments[0].clone(),
+        ];
+        let error = shares_cipher(copies, &header, false).err().unwrap();
+        assert_eq!(error.to_string(), "found 2 shares, need at least 3");
+        let copies = [&fragments[..3], &fragments[1..2]].concat();
+        assert!(shares_cipher(copies, &header, false).is_ok());
+        let mut conflicting = fragments[..3].to_vec();
+        conflicting[2].share.0 = conflicting[0].share.0.clone();
+        let error = shares_cipher(conflicting, &header, false).err().unwrap();
+        assert!(error.to_string().contains("index 1"));
+    }
+
+    #[test]
+    fn field_shares() {
+        let header = Header::new(
+            b"msg.txt".to_vec(),
+            Vec::new(),
+            2,
+            3,
+            CipherSuite::Aes256Gcm,
+        );
+        let cipher = Cipher::from_key(&[0x22u8; 32], CipherSuite::Aes256Gcm).unwrap();
+        let field = shamir::parse_field("127").unwrap();
+        let mut fragments = split_fragments(&cipher, None, 3, 2, &[], Some(&field), None).unwrap();
+        assert_eq!(fragments[0].field, Some(field.to_string()));
+        let recovered = shares_cipher(fragments[1..].to_vec(), &header, false).unwrap();
+        let mac = fragments[0].mac.clone().unwrap();
+        assert!(recovered.verify_share(&fragments[0].share, &mac));
+        fragments[0].field = None;
+        assert!(shares_cipher(fragments, &header, false).is_err());
+    }
+
+    #[test]
+    fn keygen() {
+        let config = KeygenConfig {
+            length: 48,
+            total_evals: 5,
+            min_required_evals: 3,
+            field: None,
+        };
+        let fragments = keygen_fragments(&config).unwrap();
+        assert_eq!(fragments.len(), 5);
+        assert_eq!(fragments[0].length, Some(48));
+        fn shares(some: &[Fragment]) -> impl Iterator<Item = crypto::Share> + '_ {
+            some.iter().map(|fragment| fragment.share.clone())
+        }
+        let first = SecretKey::recover(shares(&fragments[..3]), None, Some(48)).unwrap();
+        let last = SecretKey::recover(shares(&fragments[2..]), None, Some(48)).unwrap();
+        assert_eq!(first.len(), 48);
+        assert_eq!(first, last);
+        let other = keygen_fragments(&config).unwrap();
+        let another = SecretKey::recover(shares(&other[..3]), None, Some(48)).unwrap();
+        assert_ne!(first, another);
+    }
+
+    #[test]
+    fn resplit() {
+        let key = SecretKey::new(b"organization key".to_vec());
+        let mut organization = key_fragments(&key, 3, 2, None).unwrap();
+        for (fragment, holder) in organization.iter_mut().zip(&["legal", "finance", "it"]) {
+            fragment.holder = Some(holder.to_string());
+        }
+        let mut file = File::create("resplit.frg").unwrap();
+        fragments::write_fragments(&organization, Format::Lines, &mut file).unwrap();
+        let config = |shares_file: &str, share: &str, output_file: &str, holders: Vec<String>| {
+            ResplitConfig {
+                shares_file: shares_file.into(),
+                share: share.into(),
+                output_file: output_file.into(),
+                total_evals: 3,
+                min_required_evals: 2,
+                holders,
+            }
+        };
+        let holders = vec!["ana".into(), "luis".into(), "eva".into()];
+        let report = run(Config::Resplit(config(
+            "resplit.frg",
+            "finance",
+            "resplit.finance.frg",
+            holders,
+        )))
+        .unwrap();
+        assert!(report.text().contains(&organization[1].fingerprint()));
+        let finance = fragments::read_fragments("resplit.finance.frg").unwrap();
+        assert_eq!(finance[2].holder.as_deref(), Some("finance/eva"));
+        // a share of the group is split again, by its index
+        run(Config::Resplit(config(
+            "resplit.finance.frg",
+            "1",
+            "resplit.ana.frg",
+            Vec::new(),
+        )))
+        .unwrap();
+        let ana = fragments::read_fragments("resplit.ana.frg").unwrap();
+        assert_eq!(ana[1].holder.as_deref(), Some("finance/ana/2"));
+        assert!(run(Config::Resplit(config(
+            "resplit.frg",
+            "hr",
+            "resplit.hr.frg",
+            Vec::new(),
+        )))
+        .is_err());
+
+        // each level recovers the share of the level above
+        let mut file = File::create("resplit.two.frg").unwrap();
+        fragments::write_fragments(&ana[1..], Format::Lines, &mut file).unwrap();
+        let report = run(Config::CombineGroup(CombineGroupConfig {
+            shares_file: "resplit.two.frg".into(),
+        }))
+        .unwrap();
+        assert_eq!(report.text(), format!("{}\n", finance[0].to_line()));
+        let mut file = File::create("resplit.two.frg").unwrap();
+        fragments::write_fragments(
+            &[finance[0].clone(), finance[2].clone()],
+            Format::Lines,
+            &mut file,
+        )
+        .unwrap();
+        let report = run(Config::CombineGroup(CombineGroupConfig {
+            shares_file: "resplit.two.frg".into(),
+        }))
+        .unwrap();
+        let recovered = fragments::parse_fragments(&report.text()).unwrap();
+        assert_eq!(recovered, [organization[1].clone()]);
+        let shares = [organization[0].clone(), recovered[0].clone()];
+        assert_eq!(fragments_key(shares.to_vec()).unwrap(), key);
+        // the organization shares recover a key, not a share
+        assert!(run(Config::CombineGroup(CombineGroupConfig {
+            shares_file: "resplit.frg".into(),
+        }))
+        .is_err());
+        for file in &[
+            "resplit.frg",
+            "resplit.finance.frg",
+            "resplit.ana.frg",
+            "resplit.two.frg",
+        ] {
+            fs::remove_file(file).unwrap();
+        }
+    }
+
+    #[test]
+    fn split() {
+        assert_eq!(trim_newlines("secret\r\n\n"), "secret");
+        assert_eq!(trim_newlines("two\nlines\n"), "two\nlines");
+        let config = |seed: Option<&[u8]>, share| SplitConfig {
+            secret: SecretKey::new(b"secret".to_vec()),
+            total_evals: 4,
+            min_required_evals: 3,
+            seed: seed.map(|seed| SecretKey::new(seed.to_vec())),
+            share,
+        };
+        let text = run_split(config(None, None)).unwrap().text();
+        assert_eq!(text.lines().count(), 4);
+        let fragments = fragments::parse_fragments(&text).unwrap();
+        let key = fragments_key(fragments[1..].to_vec()).unwrap();
+        assert_eq!(key.expose(), b"secret");
+
+        // a share made again from the seed is the same
+        let seed = [0x5au8; 32];
+        let text = run_split(config(Some(&seed), None)).unwrap().text();
+        assert_eq!(run_split(config(Some(&seed), None)).unwrap().text(), text);
+        let again = run_split(config(Some(&seed), Some(3))).unwrap().text();
+        assert_eq!(again, format!("{}\n", text.lines().nth(2).unwrap()));
+        let fragments = fragments::parse_fragments(&text).unwrap();
+        let key = fragments_key(fragments[..3].to_vec()).unwrap();
+        assert_eq!(key.expose(), b"secret");
+        assert!(run_split(config(Some(&seed), Some(5))).is_err());
+        assert!(run_split(config(Some(&seed[..8]), None)).is_err());
+    }
+
+    #[test]
+    fn show_key() {
+        let config = KeygenConfig {
+            length: 20,
+            total_evals: 4,
+            min_required_evals: 2,
+            field: Some(shamir::parse_field("127").unwrap()),
+        };
+        let fragments = keygen_fragments(&config).unwrap();
+        let key = fragments_key(fragments[2..].to_vec()).unwrap();
+        assert_eq!(key.len(), 20);
+        assert_eq!(fragments_key(fragments[..2].to_vec()).unwrap(), key);
+        let mut other = fragments[..2].to_vec();
+        other[1].field = None;
+        assert!(fragments_key(other).is_err());
+        assert!(fragments_key(Vec::new()).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn agent() {
+        let encrypt_config = EncryptConfig {
+            password: "agent password".into(),
+            ..test_config("agent")
+        };
+        run(Config::Encrypt(encrypt_config)).unwrap();
+        let agent_config = |stop| AgentConfig {
+            encrypted_file: Some("agent.aes".into()),
+            shares_file: Some("agent.frg".into()),
+            socket: "agent.sock".into(),
+            stop,
+            webhooks: Webhooks::default(),
+        };
+        let agent = thread::spawn(move || run(Config::Agent(agent_config(false))).unwrap());
+        while !Path::new("agent.sock").exists() {
+            thread::sleep(Duration::from_millis(10));
+        }
+        let decrypt_config = |check, reveal| DecryptConfig {
+            unlock: Unlock::Agent("agent.sock".into(), "agent.aes".into()),
+            encrypted_file: "agent.aes".into(),
+            check,
+            cross_check: false,
+            copy: None,
+            max_memory: None,
+            get: None,
+            reveal,
+        };
+        let report = run(Config::Decrypt(decrypt_config(true, None))).unwrap();
+        assert_eq!(report.text(), "agent.aes: ok\n");
+        // the agent only decrypts, it gives no key to unlock a file
+        let header = Header::read_from(&mut BufReader::new(File::open("agent.aes").unwrap()));
+        let agent_unlock = decrypt_config(true, None).unlock;
+        assert!(unlock(&agent_unlock, &header.unwrap(), false).is_err());
+        let reveal = Some(Reveal::Range(0, None));
+        assert!(run(Config::Decrypt(decrypt_config(false, reveal))).is_err());
+        run(Config::Agent(agent_config(true))).unwrap();
+        let report = agent.join().unwrap();
+        assert_eq!(report.text(), "files decrypted: 1\n");
+        assert!(!Path::new("agent.sock").exists());
+        fs::remove_file("agent.aes").unwrap();
+        fs::remove_file("agent.frg").unwrap();
+    }
+
+    #[cfg(feature = "server")]
+    #[test]
+    fn server() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let server = listener.local_addr().unwrap().to_string();
+        thread::spawn(|| {
+            let server = service::Server::new(
+                service::RecoveryPolicy::default(),
+                service::ClientLimits::default(),
+                Webhooks::default(),
+            );
+            service::serve(listener, "token", server).unwrap()
+        });
+        let remote = |operation, file: &str, shares_file: Option<&str>, output: &str, token| {
+            run(Config::Remote(RemoteConfig {
+                operation,
+                file: file.into(),
+                shares_file: shares_file.map(String::from),
+                output: Some(output.into()),
+                scheme: Some((3, 2)),
+                server: server.clone(),
+                token: String::from(token),
+                holder_tokens_file: None,
+                insecure_http: false,
+            }))
+        };
+        fs::create_dir_all("remote").unwrap();
+        let report = remote(
+            service::Operation::Encrypt,
+            "test_data/msg1.txt",
+            None,
+            "remote",
+            "token",
+        )
+        .unwrap();
+        assert!(report
+            .to_json()
+            .contains("\"shares_file\":\"remote/msg1.txt.frg\""));
+        // the files are the same the command line writes
+        let header = Header::read_from(&mut BufReader::new(
+            File::open("remote/msg1.txt.aes").unwrap(),
+        ))
+        .unwrap();
+        assert_eq!(header.file_name, b"msg1.txt");
+        assert_eq!(
+            fragments::read_fragments("remote/msg1.txt.frg")
+                .unwrap()
+                .len(),
+            3
+        );
+        remote(
+            service::Operation::Decrypt,
+            "remote/msg1.txt.aes",
+            Some("remote/msg1.txt.frg"),
+            "remote",
+            "token",
+        )
+        .unwrap();
+        assert_eq!(
+            fs::read("remote/msg1.txt").unwrap(),
+            fs::read("test_data/msg1.txt").unwrap()
+        );
+        let error = remote(
+            service::Operation::Split,
+            "test_data/msg1.txt",
+            None,
+            "remote",
+            "other",
+        )
+        .unwrap_err();
+        assert!(error.to_string().contains("401"));
+        let report = remote(
+            service::Operation::Split,
+            "test_data/msg1.txt",
+            None,
+            "remote",
+            "token",
+        )
+        .unwrap();
+        fs::write("remote/secret.frg", report.text()).unwrap();
+        remote(
+            service::Operation::Combine,
+            "remote/secret.frg",
+            None,
+            "remote/secret",
+            "token",
+        )
+        .unwrap();
+        assert_eq!(
+            fs::read("remote/secret").unwrap(),
+            fs::read("test_data/msg1.txt").unwrap()
+        );
+        fs::remove_dir_all("remote").unwrap();
+    }
+
+    #[test]
+    fn age_plugin() {
+        let config = KeygenConfig {
+            length: 32,
+            total_evals: 3,
+            min_required_evals: 2,
+            field: None,
+        };
+        let fragments = keygen_fragments(&config).unwrap();
+        let lines: Vec<String> = fragments[1..].iter().map(Fragment::to_line).collect();
+        fs::write("age.frg", lines.join("\n") + "\n").unwrap();
+        let report = run(Config::AgeRecipient(AgeRecipientConfig {
+            shares_file: "age.frg".into(),
+        }))
+        .unwrap();
+        let text = report.text();
+        assert_eq!(text.lines().count(), 2);
+        let recipient = text
+            .lines()
+            .next()
+            .unwrap()
+            .trim_start_matches("# recipient: ");
+        let identity = text.lines().nth(1).unwrap();
+        assert!(identity.starts_with("AGE-PLUGIN-SHAREDSECRETS-1"));
+        // age wraps a file key to the recipient and unwraps it with the identity
+        let mut wrapped = Vec::new();
+        let input = format!(
+            "-> add-recipient {}\n\n-> wrap-file-key\nAAAAAAAAAAAAAAAAAAAAAA\n-> done\n\n-> ok\n\n",
+            recipient
+        );
+        age::run_plugin("recipient-v1", input.as_bytes(), &mut wrapped).unwrap();
+        let stanza = String::from_utf8(wrapped).unwrap();
+        let stanza = stanza.lines().take(2).collect::<Vec<_>>().join("\n");
+        let input = format!(
+            "-> add-identity {}\n\n{}\n-> done\n\n-> ok\n\n",
+            identity, stanza
+        );
+        let mut unwrapped = Vec::new();
+        age::run_plugin("identity-v1", input.as_bytes(), &mut unwrapped).unwrap();
+        fs::remove_file("age.frg").unwrap();
+        assert_eq!(
+            String::from_utf8(unwrapped).unwrap(),
+            "-> file-key 0\nAAAAAAAAAAAAAAAAAAAAAA\n-> done\n\n"
+        );
+    }
+
+    #[test]
+    fn xor_shares() {
+        let header = Header::new(
+            b"msg.txt".to_vec(),
+            Vec::new(),
+            3,
+            3,
+            CipherSuite::Aes256Gcm,
+        );
+        let cipher = Cipher::from_key(&[0x24u8; 32], CipherSuite::Aes256Gcm).unwrap();
+        let fragments = split_fragments(&cipher, None, 3, 3, &[], None, Some("xor")).unwrap();
+        assert_eq!(fragments[0].scheme, Some("xor".into()));
+        assert_eq!(fragments[0].share.1.len(), 64);
+        let recovered = shares_cipher(fragments.clone(), &header, false).unwrap();
+        assert!(recovered.verify_share(&fragments[0].share, fragments[0].mac.as_ref().unwrap()));
+        assert!(shares_cipher(fragments[1..].to_vec(), &header, false).is_err());
+        let mut unknown = fragments.clone();
+        for fragment in &mut unknown {
+            fragment.scheme = Some("blakley".into());
+        }
+        let error = shares_cipher(unknown, &header, false).err().unwrap();
+        assert_eq!(error.to_string(), "unknown secret sharing scheme 'blakley'");
+        assert!(split_fragments(&cipher, None, 3, 2, &[], None, Some("xor")).is_err());
+        let shamir = split_fragments(&cipher, None, 3, 2, &[], None, Some("shamir")).unwrap();
+        assert_eq!(shamir[0].scheme, None);
+        let field = shamir::parse_field("127").unwrap();
+        let additive =
+            split_fragments(&cipher, None, 3, 3, &[], Some(&field), Some("additive")).unwrap();
+        assert_eq!(additive[0].scheme, Some("additive".into()));
+        assert_eq!(additive[0].field, Some(field.to_string()));
+        let recovered = shares_cipher(additive.clone(), &header, false).unwrap();
+        assert!(recovered.verify_share(&additive[1].share, additive[1].mac.as_ref().unwrap()));
+        let header = Header::new(
+            b"msg.txt".to_vec(),
+            Vec::new(),
+            2,
+            3,
+            CipherSuite::Aes256Gcm,
+        );
+        let crt = split_fragments(&cipher, None, 3, 2, &[], None, Some("crt")).unwrap();
+        assert_eq!(crt[0].scheme, Some("crt".into()));
+        let recovered = shares_cipher(crt[1..].to_vec(), &header, false).unwrap();
+        assert!(recovered.verify_share(&crt[0].share, crt[0].mac.as_ref().unwrap()));
+        let field = shamir::parse_field("127").unwrap();
+        let ramp = split_fragments(&cipher, None, 3, 2, &[], Some(&field), Some("ramp1")).unwrap();
+        assert_eq!(ramp[0].scheme, Some("ramp1".into()));
+        let recovered = shares_cipher(ramp[..2].to_vec(), &header, false).unwrap();
+        assert!(recovered.verify_share(&ramp[2].share, ramp[2].mac.as_ref().unwrap()));
+        let checked =
+            split_fragments(&cipher, None, 3, 2, &[], None, Some("shamir-checked")).unwrap();
+        assert_eq!(checked[0].scheme, Some("shamir-checked".into()));
+        let recovered = shares_cipher(checked[1..].to_vec(), &header, false).unwrap();
+        assert!(recovered.verify_share(&checked[0].share, checked[0].mac.as_ref().unwrap()));
+    }
+
+    #[test]
+    fn policy_shares() {
+        let policy = Policy::parse("(2 of alice,bob,carol) and (1 of hsm,offline)").unwrap();
+        let encrypt_config = EncryptConfig {
+            total_evals: 5,
+            min_required_evals: 3,
+            holders: policy.holders().into_iter().map(String::from).collect(),
+            policy: Some(policy),
+            ..test_config("policy")
+        };
+        run(Config::Encrypt(encrypt_config)).unwrap();
+        let fragments = fragments::read_fragments("policy.frg").unwrap();
+        let check = |holders: &[&str]| {
+            let mut content = Vec::new();
+            for fragment in &fragments {
+                if holders.contains(&fragment.holder.as_deref().unwrap()) {
+                    fragment.write_to(&mut content).unwrap();
+                }
+            }
+            fs::write("policy-some.frg", content).unwrap();
+            run(Config::Decrypt(DecryptConfig {
+                unlock: Unlock::Shares("policy-some.frg".into()),
+                encrypted_file: "policy.aes".into(),
+                check: true,
+                cross_check: false,
+                copy: None,
+                max_memory: None,
+                get: None,
+                reveal: None,
+            }))
+        };
+        assert!(check(&["alice", "carol", "hsm"]).is_ok());
+        assert!(check(&["bob", "carol", "offline"]).is_ok());
+        assert!(check(&["alice", "bob", "carol"]).is_err());
+        assert!(check(&["alice", "hsm", "offline"]).is_err());
+        // the new shares follow the same policy
+        run(Config::Rotate(RotateConfig {
+            encrypted_file: "policy.aes".into(),
+            shares_file: "policy.frg".into(),
+            password: "new password".into(),
+        }))
+        .unwrap();
+        let fragments = fragments::read_fragments("policy.frg").unwrap();
+        assert_eq!(fragments.len(), 5);
+        let mut content = Vec::new();
+        for fragment in fragments.iter().skip(1).take(3) {
+            fragment.write_to(&mut content).unwrap();
+        }
+        fs::write("policy-some.frg", content).unwrap();
+        run(Config::Decrypt(DecryptConfig {
+            unlock: Unlock::Shares("policy-some.frg".into()),
+            encrypted_file: "policy.aes".into(),
+            check: true,
+            cross_check: false,
+            copy: None,
+            max_memory: None,
+            get: None,
+            reveal: None,
+        }))
+        .unwrap();
+        fs::remove_file("policy.aes").unwrap();
+        fs::remove_file("policy.frg").unwrap();
+        fs::remove_file("policy-some.frg").unwrap();
+    }
+
+    #[cfg(feature = "ceremony")]
+    #[test]
+    fn ceremony_fragments() {
+        let encrypt_config = EncryptConfig {
+            holders: vec!["alice".into(), "bob".into(), "carol".into()],
+            ..test_config("ceremony")
+        };
+        run(Config::Encrypt(encrypt_config)).unwrap();
+        let mut reader = BufReader::new(File::open("ceremony.aes").unwrap());
+        let header = Header::read_from(&mut reader).unwrap();
+        let mut ceremony = ceremony::Ceremony::new(&header);
+        for fragment in fragments::read_fragments("ceremony.frg")
+            .unwrap()
+            .iter()
+            .skip(1)
+        {
+            ceremony.enter(&fragment.to_line()).unwrap();
+        }
+        assert!(ceremony.ready());
+        let fragments = ceremony.fragments().to_vec();
+        let cipher = unlock(&Unlock::Fragments(fragments), &header, false).unwrap();
+        cipher
+            .verify(&mut reader, &header.associated_data())
+            .unwrap();
+        fs::remove_file("ceremony.aes").unwrap();
+        fs::remove_file("ceremony.frg").unwrap();
+    }
+
+    #[test]
+    fn unlock_methods() {
+        let encrypt_config = EncryptConfig {
+            keyfile: Some("test_data/msg1.txt".into()),
+            ..test_config("methods")
+        };
+        run(Config::Encrypt(encrypt_config)).unwrap();
+        let check = |unlock| {
+            run(Config::Decrypt(DecryptConfig {
+                encrypted_file: "methods.aes".into(),
+                unlock,
+                check: true,
+                cross_check: false,
+                copy: None,
+                max_memory: None,
+                get: None,
+                reveal: None,
+            }))
+        };
+        assert!(check(Unlock::Shares("methods.frg".into())).is_ok());
+        assert!(check(Unlock::Keyfile("test_data/msg1.txt".into())).is_ok());
+        assert!(check(Unlock::Password("secure password".into())).is_ok());
+        assert!(check(Unlock::Password("other password".into())).is_err());
+        let methods_config = MethodsConfig {
+            encrypted_file: "methods.aes".into(),
+            unlock: Unlock::Keyfile("test_data/msg1.txt".into()),
+            add_password: Some("other password".into()),
+            add_keyfile: None,
+            remove: vec![UnlockMethod::Shares],
+        };
+        run(Config::Methods(methods_config)).unwrap();
+        assert!(check(Unlock::Shares("methods.frg".into())).is_err());
+        assert!(check(Unlock::Password("secure password".into())).is_err());
+        assert!(check(Unlock::Password("other password".into())).is_ok());
+        assert!(check(Unlock::Keyfile("test_data/msg1.txt".into())).is_ok());
+        let methods_config = MethodsConfig {
+            encrypted_file: "methods.aes".into(),
+            unlock: Unlock::Password("other password".into()),
+            add_password: None,
+            add_keyfile: None,
+            remove: vec![UnlockMethod::Password, UnlockMethod::Keyfile],
+        };
+        assert!(run(Config::Methods(methods_config)).is_err());
+        fs::remove_file("methods.aes").unwrap();
+        fs::remove_file("methods.frg").unwrap();
+    }
+
+    #[test]
+    fn arguments() {
+        let yaml = clap::load_yaml!("../config/args.yaml");
+        let parse = |args: &[&str]| {
+            let matches = clap::App::from_yaml(yaml).get_matches_from_safe(args)?;
+            Config::new(matches)
+        };
+        match parse(&["shared_secrets", "d", "msg.aes", "msg.frg", "--check"]) {
+            Ok(Config::Decrypt(config)) => {
+                assert!(config.check);
+                assert!(!config.cross_check);
+                assert!(matches!(config.unlock, Unlock::Shares(ref f) if f == "msg.frg"));
+            }
+            _ => panic!("expected a decrypt configuration"),
+        }
+        assert!(parse(&["shared_secrets", "d", "msg.aes"]).is_err());
+        match parse(&["shared_secrets", "d", "msg.aes", "msg.frg", "--cross-check"]) {
+            Ok(Config::Decrypt(config)) => assert!(config.cross_check),
+            _ => panic!("expected a decrypt configuration"),
+        }
+        match parse(&["shared_secrets", "d", "msg.aes", "msg.frg", "--copy"]) {
+            Ok(Config::Decrypt(config)) => assert_eq!(config.copy, Some(Duration::from_secs(30))),
+            _ => panic!("expected a decrypt configuration"),
+        }
+        let images = &["shared_secrets", "d", "msg.aes", "--share-image", "a.png"];
+        match parse(images) {
+            Ok(Config::Decrypt(config)) => {
+                assert!(matches!(config.unlock, Unlock::Images(None, ref i) if i == &["a.png"]))
+            }
+            _ => panic!("expected a decrypt configuration"),
+        }
+        let copy_check = &[
+            "shared_secrets",
+            "d",
+            "msg.aes",
+            "msg.frg",
+            "--copy",
+            "--check",
+        ];
+        assert!(parse(copy_check).is_err());
+        let keyfile = &["shared_secrets", "methods", "msg.aes", "--keyfile", "key"];
+        assert!(matches!(parse(keyfile), Ok(Config::Methods(_))));
+        let init = &["shared_secrets", "config", "init"];
+        assert!(matches!(parse(init), Ok(Config::InitSettings)));
+        assert!(parse(&["shared_secrets", "config"]).is_err());
+        // encrypting reads a password, so only the matches are checked
+        let matches = |args: &[&str]| clap::App::from_yaml(yaml).get_matches_from_safe(args);
+        let policy = &[
+            "shared_secrets",
+            "c",
+            "msg.txt",
+            "msg",
+            "--policy",
+            "alice or bob",
+        ];
+        assert!(matches(policy).is_ok());
+        // N and K may come from the configuration file instead
+        let scheme = |args: &[&str], settings: &Settings| {
+            let matches = matches(args).unwrap();
+            parse_scheme(matches.subcommand_matches("c").unwrap(), settings)
+        };
+        let defaults = Settings {
+            n: Some(5),
+            k: Some(3),
+            ..Settings::default()
+        };
+        let no_scheme = &["shared_secrets", "c", "msg.txt", "msg"];
+        assert!(scheme(no_scheme, &Settings::default()).is_err());
+        assert_eq!(scheme(no_scheme, &defaults).unwrap(), (5, 3));
+        let scheme_args = &[no_scheme, &["4", "2"][..]].concat();
+        assert_eq!(scheme(scheme_args, &defaults).unwrap(), (4, 2));
+        let invalid = &[no_scheme, &["4", "5"][..]].concat();
+        assert!(scheme(invalid, &defaults).is_err());
+        assert!(matches(&[policy, &["3"][..]].concat()).is_err());
+        let field = &[
+            "shared_secrets",
+            "c",
+            "msg.txt",
+            "msg",
+            "3",
+            "2",
+            "--field",
+            "127",
+        ];
+        assert!(matches(field).is_ok());
+        assert!(matches(&[policy, &["--field", "127"][..]].concat()).is_err());
+        assert!(matches(&[field, &["--format", "csv"][..]].concat()).is_ok());
+        assert!(matches(&[field, &["--format", "xlsx"][..]].concat()).is_err());
+        let wallet = &["shared_secrets", "split-wallet-key", "3", "2"];
+        let format = |args: &[&str]| {
+            let matches = matches(args).unwrap();
+            parse_wallet_format(matches.subcommand_matches("split-wallet-key").unwrap())
+        };
+        assert!(format(wallet).is_err());
+        let curve = format(&[wallet, &["--curve", "secp256k1"][..]].concat());
+        assert_eq!(
+            curve.unwrap(),
+            wallet::Format::Scalar(crypto::shamir::Curve::Secp256k1)
+        );
+        assert!(matches(&[wallet, &["--curve", "p256"][..]].concat()).is_err());
+        let codex32 = format(&[wallet, &["--format", "codex32"][..]].concat());
+        assert_eq!(codex32.unwrap(), wallet::Format::Codex32);
+        let both = &[wallet, &["--format", "codex32", "--curve", "ed25519"][..]].concat();
+        assert!(format(both).is_err());
+        let show = &[
+            "shared_secrets",
+            "show-key",
+            "key.frg",
+            "--format",
+            "base64",
+        ];
+        assert!(parse(show).is_err());
+        match parse(&[show, &["--fd", "3", "--i-know-this-prints-a-secret"][..]].concat()) {
+            Ok(Config::ShowKey(config)) => {
+                assert_eq!(config.format, export::KeyFormat::Base64);
+                assert_eq!(config.fd, 3);
+            }
+            _ => panic!("expected a show-key configuration"),
+        }
+    }
+
+    #[test]
+    fn atomic_write() {
+        assert!(write_atomically("test_data/msg1.txt", |_| Ok(())).is_err());
+        let result: Result<(), _> = write_atomically("atomic.txt", |writer| {
+            writer.write_all(b"partial")?;
+            Err(Box::new(ArgumentError("interrupted".into())))
+        });
+        assert!(result.is_err());
+        assert!(!Path::new("atomic.txt").exists());
+        assert!(temporary_files(".", "atomic.txt").is_empty());
+        write_atomically("atomic.txt", |writer| Ok(writer.write_all(b"complete")?)).unwrap();
+        assert_eq!(fs::read("atomic.txt").unwrap(), b"complete");
+        // a file created while writing is not replaced
+        let result = write_atomically("raced.txt", |writer| {
+            fs::write("raced.txt", b"other")?;
+            Ok(writer.write_all(b"complete")?)
+        });
+        assert!(result.is_err());
+        assert_eq!(fs::read("raced.txt").unwrap(), b"other");
+        assert!(temporary_files(".", "raced.txt").is_empty());
+        replace_atomically("raced.txt", |writer| Ok(writer.write_all(b"replaced")?)).unwrap();
+        assert_eq!(fs::read("raced.txt").unwrap(), b"replaced");
+        fs::remove_file("atomic.txt").unwrap();
+        fs::remove_file("raced.txt").unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn special_files() {
+        assert!(is_special_file("/dev/null"));
+        assert!(!is_special_file("test_data/msg1.txt"));
+        assert!(!is_special_file("test_data"));
+        // a device is written in place instead of being replaced
+        write_atomically("/dev/null", |writer| Ok(writer.write_all(b"discarded")?)).unwrap();
+        assert!(temporary_files("/dev", "null").is_empty());
+        assert!(read_keyfile("/dev/null").unwrap().is_empty());
+    }
+
+    #[test]
+    fn digest_mismatch() {
+        let mut header = Header::new(
+            b"msg.txt".to_vec(),
+            Vec::new(),
+            2,
+            3,
+            CipherSuite::Aes256Gcm,
+        );
+        let mut writer = DigestWriter::new(Vec::new());
+        writer.write_all(b"message").unwrap();
+        assert!(check_digest(&header, writer.digest()).is_ok());
+        header.digest = Some(writer.digest());
+        assert!(check_digest(&header, writer.digest()).is_ok());
+        writer.write_all(b"!").unwrap();
+        assert!(check_digest(&header, writer.digest()).is_err());
+    }
+
+    #[cfg(feature = "expensive-tests")]
+    #[test]
+    fn larger_than_4_gib() {
+        // a sparse file whose length does not fit in 32 bits
+        let length = (4 << 30) + 12345;
+        File::create("large.bin").unwrap().set_len(length).unwrap();
+        let encrypt_config = EncryptConfig {
+            input_file: "large.bin".into(),
+            digest: true,
+            threads: 4,
+            ..test_config("large")
+        };
+        run(Config::Encrypt(encrypt_config)).unwrap();
+        let header =
+            Header::read_from(&mut BufReader::new(File::open("large.aes").unwrap())).unwrap();
+        assert_eq!(header.length, Some(length));
+        let chunks = length.div_ceil(CHUNK_SIZE as u64);
+        let encrypted = fs::metadata("large.aes").unwrap().len();
+        // one tag for each chunk, after the headers
+        let tags = chunks * (SEALED_CHUNK_SIZE - CHUNK_SIZE) as u64;
+        assert!(encrypted > length + tags);
+        let check_config = DecryptConfig {
+            unlock: Unlock::Shares("large.frg".into()),
+            encrypted_file: "large.aes".into(),
+            check: true,
+            cross_check: false,
+            copy: None,
+            max_memory: None,
+            get: None,
+            reveal: None,
+        };
+        run(Config::Decrypt(check_config)).unwrap();
+        for file in &["large.bin", "large.aes", "large.frg"] {
+            fs::remove_file(file).unwrap();
+        }
+    }
+
+    #[test]
+    fn length_mismatch() {
+        let mut header = Header::new(
+            b"msg.txt".to_vec(),
+            Vec::new(),
+            2,
+            3,
+            CipherSuite::Aes256Gcm,
+        );
+        assert!(check_length(&header, 7).is_ok());
+        header.length = Some(5 << 30);
+        assert!(check_length(&header, 5 << 30).is_ok());
+        // the length modulo 2^32 is not enough
+        assert!(check_length(&header, 1 << 30).is_err());
+    }
+
+    #[test]
+    fn holders_present_and_missing() {
+        let holders: Vec<String> = vec!["alice".into(), "bob".into(), "carol".into()];
+        let fragments = vec![
+            Fragment {
+                share: Share("1".into(), "2".into()),
+                holder: Some("carol".into()),
+                scheme: None,
+                field: None,
+                length: None,
+                mac: None,
+                pin: None,
+            },
+            Fragment {
+                share: Share("3".into(), "4".into()),
+                holder: Some("alice".into()),
+                scheme: None,
+                field: None,
+                length: None,
+                mac: None,
+                pin: None,
+            },
+        ];
+        let (present, missing) = holders_report(&holders, &fragments);
+        assert_eq!(present, vec!["alice", "carol"]);
+        assert_eq!(missing, vec!["bob"]);
     }
 }
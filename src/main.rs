@@ -1,20 +1,76 @@
+use std::error::Error;
+use std::io::{self, IsTerminal};
 use std::process;
 
-use clap;
-
+use shared_secrets::report::{self, Report, Tone};
 use shared_secrets::Config;
 
+// Suggestion printed when a file cannot be decrypted
+const DOCTOR_HINT: &str =
+    "To find the cause, run: shared_secrets doctor <encrypted file> <shares file>";
+
 fn main() {
     let yaml = clap::load_yaml!("../config/args.yaml");
     let app = clap::App::from_yaml(yaml);
+    let matches = app.get_matches();
+    let json = matches.is_present("JSON");
+    let no_color = matches.is_present("NO_COLOR");
+    let output = Output {
+        json,
+        color: report::use_color(no_color, io::stdout().is_terminal()),
+        error_color: report::use_color(no_color, io::stderr().is_terminal()),
+    };
+    report::set_color(output.error_color);
+    report::set_json(json);
+    let command = matches.subcommand_name().map(String::from);
 
-    let config = Config::new(app.get_matches()).unwrap_or_else(|e| {
-        eprintln!("Problem parsing argument: {}", e);
-        process::exit(1);
+    let config = Config::new(matches).unwrap_or_else(|e| {
+        output.fail("Problem parsing argument", e.as_ref(), None);
     });
 
-    if let Err(e) = shared_secrets::run(config) {
-        eprintln!("Application error: {}", e);
+    match shared_secrets::run(config) {
+        Ok(report) => output.print_report(&report),
+        Err(e) => {
+            let hint = match command.as_deref() {
+                Some("d") | Some("rotate-password") => Some(DOCTOR_HINT),
+                _ => None,
+            };
+            output.fail("Application error", e.as_ref(), hint)
+        }
+    }
+}
+
+// Where and how the result of the command is printed
+struct Output {
+    json: bool,
+    // whether stdout and stderr are colored
+    color: bool,
+    error_color: bool,
+}
+
+impl Output {
+    // Prints the result of the command as text or as JSON
+    fn print_report(&self, report: &Report) {
+        if self.json {
+            println!("{}", report.to_json());
+        } else {
+            eprint!("{}", report.notes());
+            print!("{}", report.render(self.color));
+        }
+    }
+
+    // Prints the error that stopped the command, with a hint of
+    // what to do, and exits
+    fn fail(&self, context: &str, error: &dyn Error, hint: Option<&str>) -> ! {
+        if self.json {
+            println!("{}", report::error_json(error));
+        } else {
+            let context = report::paint(Tone::Error, context, self.error_color);
+            eprintln!("{}: {}", context, error);
+            if let Some(hint) = hint {
+                eprintln!("{}", hint);
+            }
+        }
         process::exit(1);
-    };
+    }
 }
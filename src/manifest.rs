@@ -0,0 +1,319 @@
+use std::convert::TryFrom;
+use std::error::Error;
+use std::ffi::OsStr;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use zeroize::Zeroize;
+
+use crate::crypto::SecretKey;
+use crate::encoding::{from_hex, to_hex};
+use crate::error::{ArgumentError, CorruptFileError};
+use crate::math::ed25519::{self, KEY_SIZE, SIGNATURE_SIZE};
+use crate::report::Json;
+
+/// Suffix of the manifest of the files of an encryption.
+pub const EXTENSION: &str = ".manifest.json";
+
+/// Environment variable with the time, in seconds since the Unix
+/// epoch, written in manifests and transcripts instead of the
+/// current one, as in reproducible builds, so a ceremony run again
+/// with the same seed writes the same bytes.
+pub const SOURCE_DATE_EPOCH: &str = "SOURCE_DATE_EPOCH";
+
+/// Generates an operator key and saves it in a new file, readable
+/// only by its owner.
+///
+/// # Parameters
+///
+/// - path: The path of the key file, which must not exist.
+///
+/// # Returns
+///
+/// The public key in hexadecimal, which auditors use to verify
+/// the manifests.
+///
+/// # Errors
+///
+/// An io::Error if the file exists or cannot be written, or a
+/// CipherError if the key cannot be generated.
+pub fn generate_key(path: &str) -> Result<String, Box<dyn Error>> {
+    let key = SecretKey::generate(KEY_SIZE)?;
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let mut file = options.open(path)?;
+    let mut hex = to_hex(key.expose());
+    let written = writeln!(file, "{}", hex).and_then(|_| file.sync_all());
+    hex.zeroize();
+    written?;
+    let mut secret = secret(&key);
+    let public_key = ed25519::public_key(&secret);
+    secret.zeroize();
+    Ok(to_hex(&public_key))
+}
+
+/// Reads an operator key saved by generate_key.
+///
+/// # Parameters
+///
+/// - path: The path of the key file.
+///
+/// # Errors
+///
+/// An io::Error if the file cannot be read, or a CorruptFileError
+/// if it does not hold a key.
+pub fn read_key(path: &str) -> Result<SecretKey, Box<dyn Error>> {
+    let mut content = fs::read_to_string(path)?;
+    let key = from_hex(content.trim()).filter(|key| key.len() == KEY_SIZE);
+    content.zeroize();
+    match key {
+        Some(key) => Ok(SecretKey::new(key)),
+        None => Err(Box::new(CorruptFileError(format!(
+            "{} is not an operator key",
+            path
+        )))),
+    }
+}
+
+/// Writes the signed manifest of the files of an operation.
+///
+/// The manifest lists the name, size and BLAKE3 hash of each file,
+/// the fields given, the time, see SOURCE_DATE_EPOCH, and the
+/// version of the program, and
+/// the public key of the operator, and it is signed with Ed25519.
+///
+/// # Parameters
+///
+/// - path: The path of the manifest, which must not exist.
+/// - fields: The parameters of the operation.
+/// - outputs: The paths of the files, in the directory of the manifest.
+/// - key: The operator key.
+///
+/// # Errors
+///
+/// An io::Error if a file cannot be read or the manifest cannot be
+/// written.
+pub fn write(
+    path: &str,
+    fields: Vec<(&str, Json)>,
+    outputs: &[String],
+    key: &SecretKey,
+) -> Result<(), Box<dyn Error>> {
+    let mut secret = secret(key);
+    let created = created()?;
+    let mut body = vec![
+        ("tool", env!("CARGO_PKG_NAME").into()),
+        ("version", env!("CARGO_PKG_VERSION").into()),
+        ("created", Json::Number(created)),
+    ];
+    body.extend(fields);
+    let files = outputs
+        .iter()
+        .map(|output| {
+            let (size, hash) = hash_file(output)?;
+            let name = Path::new(output)
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            Ok(Json::object(vec![
+                ("file", name.into()),
+                ("size", Json::Number(size)),
+                ("blake3", hash.into()),
+            ]))
+        })
+        .collect::<io::Result<Vec<_>>>()?;
+    body.push(("outputs", Json::Array(files)));
+    body.push(("public_key", to_hex(&ed25519::public_key(&secret)).into()));
+    let body = Json::object(body);
+    let signature = ed25519::sign(&secret, body.to_string().as_bytes());
+    secret.zeroize();
+    let manifest = Json::object(vec![
+        ("manifest", body),
+        ("signature", to_hex(&signature).into()),
+    ]);
+    let mut file = OpenOptions::new().write(true).create_new(true).open(path)?;
+    writeln!(file, "{}", manifest)?;
+    file.sync_all()?;
+    Ok(())
+}
+
+/// Verifies a manifest: its signature, the key that signed it and
+/// the hash of each file it lists.
+///
+/// # Parameters
+///
+/// - path: The path of the manifest, with the files in its directory.
+/// - public_key: The public key of the operator in hexadecimal, or
+///   None to accept the key of the manifest.
+///
+/// # Returns
+///
+/// The signed content of the manifest.
+///
+/// # Errors
+///
+/// A CorruptFileError if the manifest is invalid, its signature is
+/// wrong or a file does not match it, or an ArgumentError if it was
+/// signed by another key.
+pub fn verify(path: &str, public_key: Option<&str>) -> Result<Json, Box<dyn Error>> {
+    let manifest = Json::parse(&fs::read_to_string(path)?)?;
+    let corrupt = || CorruptFileError(format!("{} is not a valid manifest", path));
+    let body = manifest.get("manifest").ok_or_else(corrupt)?;
+    let signer = body
+        .get("public_key")
+        .and_then(Json::as_str)
+        .ok_or_else(corrupt)?;
+    let signer_key = from_hex(signer)
+        .and_then(|key| <[u8; KEY_SIZE]>::try_from(key).ok())
+        .ok_or_else(corrupt)?;
+    let signature = manifest
+        .get("signature")
+        .and_then(Json::as_str)
+        .and_then(from_hex)
+        .and_then(|signature| <[u8; SIGNATURE_SIZE]>::try_from(signature).ok())
+        .ok_or_else(corrupt)?;
+    if !ed25519::verify(&signer_key, body.to_string().as_bytes(), &signature) {
+        return Err(Box::new(CorruptFileError(format!(
+            "the signature of {} is not valid",
+            path
+        ))));
+    }
+    if let Some(public_key) = public_key {
+        if !public_key.eq_ignore_ascii_case(signer) {
+            return Err(Box::new(ArgumentError(format!(
+                "{} was signed by another operator key: {}",
+                path, signer
+            ))));
+        }
+    }
+    let dir = Path::new(path).parent().unwrap_or_else(|| Path::new(""));
+    let outputs = match body.get("outputs") {
+        Some(Json::Array(outputs)) => outputs,
+        _ => return Err(Box::new(corrupt())),
+    };
+    for output in outputs {
+        let (name, size, hash) = match (
+            output.get("file").and_then(Json::as_str),
+            output.get("size"),
+            output.get("blake3").and_then(Json::as_str),
+        ) {
+            (Some(name), Some(Json::Number(size)), Some(hash)) => (name, *size, hash),
+            _ => return Err(Box::new(corrupt())),
+        };
+        // only names are listed, a path could point outside the directory
+        if Path::new(name).file_name() != Some(OsStr::new(name)) {
+            return Err(Box::new(corrupt()));
+        }
+        if hash_file(dir.join(name))? != (size, hash.to_string()) {
+            return Err(Box::new(CorruptFileError(format!(
+                "{} does not match the manifest, it was modified after the manifest was signed",
+                name
+            ))));
+        }
+    }
+    Ok(body.clone())
+}
+
+// the time written in manifests and transcripts, SOURCE_DATE_EPOCH
+// if it is set or else the current one
+pub(crate) fn created() -> Result<u64, Box<dyn Error>> {
+    source_date(std::env::var(SOURCE_DATE_EPOCH).ok().as_deref())
+}
+
+fn source_date(value: Option<&str>) -> Result<u64, Box<dyn Error>> {
+    match value.filter(|value| !value.is_empty()) {
+        Some(value) => value.trim().parse().map_err(|_| {
+            Box::new(ArgumentError(format!(
+                "{} must be a number of seconds, not '{}'",
+                SOURCE_DATE_EPOCH, value
+            ))) as _
+        }),
+        None => Ok(SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs()),
+    }
+}
+
+// the bytes of an operator key as an Ed25519 secret key
+pub(crate) fn secret(key: &SecretKey) -> [u8; KEY_SIZE] {
+    let mut secret = [0u8; KEY_SIZE];
+    secret.copy_from_slice(key.expose());
+    secret
+}
+
+// the size and the BLAKE3 hash in hexadecimal of a file
+pub(crate) fn hash_file(path: impl AsRef<Path>) -> io::Result<(u64, String)> {
+    let mut hasher = blake3::Hasher::new();
+    let size = io::copy(&mut File::open(path)?, &mut hasher)?;
+    Ok((size, hasher.finalize().to_hex().to_string()))
+}
+
+/////////////////////////////////
+// Unit testing of the module. //
+/////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn source_date_epoch() {
+        assert_eq!(source_date(Some("1700000000")).unwrap(), 1_700_000_000);
+        assert!(source_date(None).unwrap() > 1_700_000_000);
+        assert!(source_date(Some("")).unwrap() > 1_700_000_000);
+        assert!(source_date(Some("yesterday")).is_err());
+    }
+
+    #[test]
+    fn sign_and_verify() {
+        let dir = env::temp_dir().join(format!("manifest-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = |name: &str| dir.join(name).to_str().unwrap().to_string();
+        let key_path = path("operator.key");
+        let _ = fs::remove_file(&key_path);
+        let public_key = generate_key(&key_path).unwrap();
+        assert!(generate_key(&key_path).is_err());
+        let key = read_key(&key_path).unwrap();
+
+        fs::write(path("a.aes"), b"encrypted").unwrap();
+        fs::write(path("a.frg"), b"shares").unwrap();
+        let manifest = path("a.manifest.json");
+        let _ = fs::remove_file(&manifest);
+        let outputs = [path("a.aes"), path("a.frg")];
+        write(
+            &manifest,
+            vec![("threshold", 2usize.into())],
+            &outputs,
+            &key,
+        )
+        .unwrap();
+        let body = verify(&manifest, Some(&public_key)).unwrap();
+        assert_eq!(body.get("threshold"), Some(&Json::Number(2)));
+        assert_eq!(
+            body.get("version").and_then(Json::as_str),
+            Some(env!("CARGO_PKG_VERSION"))
+        );
+
+        // another operator
+        assert!(verify(&manifest, Some(&"00".repeat(KEY_SIZE))).is_err());
+
+        // a file changed after the manifest was signed
+        fs::write(path("a.frg"), b"Shares").unwrap();
+        assert!(verify(&manifest, None).is_err());
+        fs::write(path("a.frg"), b"shares").unwrap();
+        verify(&manifest, None).unwrap();
+
+        // a manifest changed after it was signed
+        let content = fs::read_to_string(&manifest).unwrap();
+        fs::write(
+            &manifest,
+            content.replace("\"threshold\":2", "\"threshold\":1"),
+        )
+        .unwrap();
+        assert!(verify(&manifest, None).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
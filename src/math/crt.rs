@@ -0,0 +1,97 @@
+use rug::ops::RemRounding;
+use rug::Integer;
+
+use crate::math::error::ValueError;
+
+/// Returns count pairwise coprime moduli, the consecutive primes
+/// greater than 2^bits, in increasing order.
+///
+/// # Parameters
+///
+/// - bits: The number of bits below which no modulus is.
+/// - count: The number of moduli to return.
+///
+/// # Returns
+///
+/// A vector with the count moduli.
+pub fn coprime_moduli(bits: u32, count: usize) -> Vec<Integer> {
+    let mut moduli = Vec::with_capacity(count);
+    let mut last = Integer::from(1) << bits;
+    for _ in 0..count {
+        last.next_prime_mut();
+        moduli.push(last.clone());
+    }
+    moduli
+}
+
+/// Solves a system of congruences with the chinese remainder theorem.
+///
+/// # Parameters
+///
+/// - congruences: Pairs of a residue and its modulus, the moduli
+///   being pairwise coprime.
+///
+/// # Returns
+///
+/// The only number x in the range 0 <= x < M, where M is the product
+/// of the moduli, congruent to every residue modulo its modulus.
+///
+/// # Errors
+///
+/// A ValueError if there are no congruences, if a modulus is lower
+/// than 2 or if two moduli are not coprime.
+pub fn chinese_remainder(congruences: &[(Integer, Integer)]) -> Result<Integer, ValueError> {
+    if congruences.is_empty() {
+        return Err(ValueError("Expected at least one congruence".into()));
+    }
+    let mut x = Integer::new();
+    let mut product = Integer::from(1);
+    for (residue, modulus) in congruences {
+        if *modulus < 2 {
+            return Err(ValueError("Expected moduli greater than 1".into()));
+        }
+        // x + product * t is congruent to residue modulo modulus
+        let inverse = Integer::from(
+            product
+                .invert_ref(modulus)
+                .ok_or_else(|| ValueError("Expected pairwise coprime moduli".into()))?,
+        );
+        let difference = Integer::from(residue - &x);
+        let t = (difference * inverse).rem_euc(modulus);
+        x += &product * t;
+        product *= modulus;
+    }
+    Ok(x)
+}
+
+/////////////////////////////////
+// Unit testing of the module. //
+/////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn moduli() {
+        let moduli = coprime_moduli(4, 4);
+        assert_eq!(moduli, vec![17, 19, 23, 29]);
+        assert!(coprime_moduli(64, 2)[0] > u64::MAX);
+    }
+
+    #[test]
+    fn remainders() {
+        let congruences: Vec<(Integer, Integer)> = [(2, 3), (3, 5), (2, 7)]
+            .iter()
+            .map(|&(r, m)| (Integer::from(r), Integer::from(m)))
+            .collect();
+        assert_eq!(chinese_remainder(&congruences).unwrap(), 23);
+        assert_eq!(chinese_remainder(&congruences[1..]).unwrap(), 23);
+        let not_coprime = vec![
+            (Integer::from(1), Integer::from(4)),
+            (Integer::from(3), Integer::from(6)),
+        ];
+        assert!(chinese_remainder(&not_coprime).is_err());
+        assert!(chinese_remainder(&[]).is_err());
+    }
+}
@@ -0,0 +1,52 @@
+/// The u coordinate of the base point of Curve25519.
+pub const BASEPOINT: [u8; 32] = x25519_dalek::X25519_BASEPOINT_BYTES;
+
+/// Computes the X25519 function of RFC 7748, the Diffie-Hellman
+/// function over Curve25519, in constant time with x25519-dalek.
+///
+/// # Parameters
+///
+/// - scalar: The private scalar, in little endian, which is clamped.
+/// - u: The u coordinate of the point, in little endian.
+///
+/// # Returns
+///
+/// The u coordinate of the product of the scalar and the point.
+pub fn x25519(scalar: &[u8; 32], u: &[u8; 32]) -> [u8; 32] {
+    x25519_dalek::x25519(*scalar, *u)
+}
+
+/////////////////////////////////
+// Unit testing of the module. //
+/////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::TryInto;
+
+    fn from_hex(hex: &str) -> [u8; 32] {
+        crate::encoding::from_hex(hex).unwrap().try_into().unwrap()
+    }
+
+    #[test]
+    fn diffie_hellman() {
+        // test vectors of section 6.1 of RFC 7748
+        let alice = from_hex("77076d0a7318a57d3c16c17251b26645df4c2f87ebc0992ab177fba51db92c2a");
+        let alice_public =
+            from_hex("8520f0098930a754748b7ddcb43ef75a0dbf3a0d26381af4eba4a98eaa9b4e6a");
+        let bob = from_hex("5dab087e624a8a4b79e17f8b83800ee66f3bb1292618b6fd1c2f8b27ff88e0eb");
+        let bob_public =
+            from_hex("de9edb7d7b7dc1b4d35b61c2ece435373f8343c85b78674dadfc7e146f882b4f");
+        let shared = from_hex("4a5d9d5ba4ce2de1728e3bf480350f25e07e21c947d19e3376f09b3c1e161742");
+        assert_eq!(x25519(&alice, &BASEPOINT), alice_public);
+        assert_eq!(x25519(&bob, &BASEPOINT), bob_public);
+        assert_eq!(x25519(&alice, &bob_public), shared);
+        assert_eq!(x25519(&bob, &alice_public), shared);
+    }
+
+    #[test]
+    fn small_order_point() {
+        assert_eq!(x25519(&BASEPOINT, &[0u8; 32]), [0u8; 32]);
+    }
+}
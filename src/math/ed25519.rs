@@ -0,0 +1,96 @@
+use ed25519_dalek::{Signature, Signer, SigningKey, VerifyingKey};
+
+/// Size in bytes of a secret key, a public key and half a signature.
+pub const KEY_SIZE: usize = 32;
+
+/// Size in bytes of a signature.
+pub const SIGNATURE_SIZE: usize = 64;
+
+/// Returns the public key of a secret key.
+///
+/// # Parameters
+///
+/// - secret: The 32 bytes of the secret key, its seed in RFC 8032.
+pub fn public_key(secret: &[u8; KEY_SIZE]) -> [u8; KEY_SIZE] {
+    SigningKey::from_bytes(secret).verifying_key().to_bytes()
+}
+
+/// Signs a message with Ed25519, as defined in RFC 8032, in
+/// constant time with ed25519-dalek.
+///
+/// # Parameters
+///
+/// - secret: The 32 bytes of the secret key.
+/// - message: The message to sign.
+///
+/// # Returns
+///
+/// The signature, the encoded point R followed by the scalar S.
+pub fn sign(secret: &[u8; KEY_SIZE], message: &[u8]) -> [u8; SIGNATURE_SIZE] {
+    SigningKey::from_bytes(secret).sign(message).to_bytes()
+}
+
+/// Verifies an Ed25519 signature of a message.
+///
+/// # Parameters
+///
+/// - public: The public key of the signer.
+/// - message: The message signed.
+/// - signature: The signature.
+///
+/// # Returns
+///
+/// Whether the signature is valid.
+pub fn verify(public: &[u8; KEY_SIZE], message: &[u8], signature: &[u8; SIGNATURE_SIZE]) -> bool {
+    match VerifyingKey::from_bytes(public) {
+        Ok(key) => key
+            .verify_strict(message, &Signature::from_bytes(signature))
+            .is_ok(),
+        Err(_) => false,
+    }
+}
+
+/////////////////////////////////
+// Unit testing of the module. //
+/////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn from_hex(hex: &str) -> Vec<u8> {
+        crate::encoding::from_hex(hex).unwrap()
+    }
+
+    #[test]
+    fn rfc_8032_vectors() {
+        // test 1 and test 2 of section 7.1 of RFC 8032
+        let vectors = [
+            (
+                "9d61b19deffd5a60ba844af492ec2cc44449c5697b326919703bac031cae7f60",
+                "d75a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f707511a",
+                "",
+                "e5564300c360ac729086e2cc806e828a84877f1eb8e5d974d873e06522490155\
+                 5fb8821590a33bacc61e39701cf9b46bd25bf5f0595bbe24655141438e7a100b",
+            ),
+            (
+                "4ccd089b28ff96da9db6c346ec114e0f5b8a319f35aba624da8cf6ed4fb8a6fb",
+                "3d4017c3e843895a92b70aa74d1b7ebc9c982ccf2ec4968cc0cd55f12af4660c",
+                "72",
+                "92a009a9f0d4cab8720e820b5f642540a2b27b5416503f8fb3762223ebdb69da\
+                 085ac1e43e15996e458f3613d0f11d8c387b2eaeb4302aeeb00d291612bb0c00",
+            ),
+        ];
+        for (secret, public, message, signature) in vectors.iter() {
+            let mut key = [0u8; KEY_SIZE];
+            key.copy_from_slice(&from_hex(secret));
+            let message = from_hex(message);
+            let signature_hex: String = signature.split_whitespace().collect();
+            assert_eq!(public_key(&key).to_vec(), from_hex(public));
+            let signed = sign(&key, &message);
+            assert_eq!(signed.to_vec(), from_hex(&signature_hex));
+            assert!(verify(&public_key(&key), &message, &signed));
+            assert!(!verify(&public_key(&key), b"other message", &signed));
+        }
+    }
+}
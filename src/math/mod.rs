@@ -11,8 +11,16 @@ mod polynomial;
 
 mod modular;
 
+pub mod crt;
+
+pub mod curve25519;
+
+pub mod ed25519;
+
 pub mod prime;
 
 pub mod random;
 
+pub mod scrypt;
+
 pub mod error;
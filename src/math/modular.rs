@@ -1,4 +1,4 @@
-use std::fmt::Display;
+use std::fmt::{Display, LowerHex};
 use std::ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Sub, SubAssign};
 
 use rug::{
@@ -6,8 +6,11 @@ use rug::{
     ops::{RemRounding, RemRoundingAssign},
     Integer,
 };
+use zeroize::Zeroize;
 
-use crate::math::{error::ParseIntegerError, random::Rng, Field, Prime};
+use crate::encoding::{from_base64, to_base64};
+use crate::math::error::{ParseIntegerError, ValueError};
+use crate::math::{random::Rng, Field, Prime};
 
 /// This structure represents a modular integer number.
 /// This type implements the Field trait in order to provide
@@ -35,7 +38,7 @@ use crate::math::{error::ParseIntegerError, random::Rng, Field, Prime};
 ///
 /// In order to perform Operations between two modular integers, they must have
 /// the same prime as modulus.
-#[derive(Debug, Eq, Hash)]
+#[derive(Debug, Clone, Eq, Hash)]
 pub struct ModInteger<'a> {
     value: Integer,
     prime: &'a Prime,
@@ -55,11 +58,63 @@ impl<'a> ModInteger<'a> {
     /// A wrapped modular integer with the given prime as modulus.
     pub fn random(prime: &'a Prime, rng: &mut Rng) -> Self {
         ModInteger {
-            value: Integer::from(prime.value.random_below_ref(rng.internal_rep())),
+            value: rng.below(&prime.value),
             prime,
         }
     }
 
+    /// Creates a random modular integer different from zero, drawing
+    /// numbers until one is not zero, so every non-zero number is
+    /// equally likely.
+    ///
+    /// # Parameters
+    ///
+    /// - prime: A prime from which to create a modular integer.
+    /// - rng: A random number generator.
+    ///
+    /// # Returns
+    ///
+    /// A wrapped modular integer in the range 0 < x < prime.
+    pub fn random_nonzero(prime: &'a Prime, rng: &mut Rng) -> Self {
+        loop {
+            let random = ModInteger::random(prime, rng);
+            if random.value != 0 {
+                return random;
+            }
+        }
+    }
+
+    /// Creates a random modular integer in a range, every number of
+    /// the range being equally likely.
+    ///
+    /// # Parameters
+    ///
+    /// - lo: The inclusive lower bound of the range.
+    /// - hi: The exclusive upper bound of the range.
+    /// - rng: A random number generator.
+    ///
+    /// # Returns
+    ///
+    /// A wrapped modular integer in the range lo <= x < hi.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if the bounds have different modulus or if
+    /// the range is empty.
+    pub fn random_range(lo: &ModInteger<'a>, hi: &ModInteger<'a>, rng: &mut Rng) -> Self {
+        if lo.prime != hi.prime {
+            panic!("Illegal operation between different modulus numbers");
+        }
+        if lo.value >= hi.value {
+            panic!("empty range");
+        }
+        let width = Integer::from(&hi.value - &lo.value);
+        ModInteger {
+            value: rng.below(&width) + &lo.value,
+            prime: lo.prime,
+        }
+    }
+
     /// Creates a new integer from the string.
     ///
     /// # Parameters
@@ -109,7 +164,7 @@ impl<'a> ModInteger<'a> {
     /// # Parameters
     ///
     /// - digits: A slice of bytes from which to create
-    /// a modular integer.
+    ///   a modular integer.
     /// - prime: A prime to have as modulus.
     ///
     /// # Returns
@@ -119,8 +174,10 @@ impl<'a> ModInteger<'a> {
     ///
     /// # Notes
     ///
-    /// This method assigns the most significant element first
-    /// and treats each byte as little endian.
+    /// The bytes are read in big-endian order, the most significant
+    /// byte first, on every platform. This order is part of the
+    /// format of the shares, a secret is split as the numbers of its
+    /// blocks of bytes, so it must never change.
     pub fn from_digits(digits: &[u8], prime: &'a Prime) -> Self {
         ModInteger {
             value: Integer::from_digits(digits, Order::MsfLe).rem_euc(&prime.value),
@@ -171,12 +228,131 @@ impl<'a> ModInteger<'a> {
     ///
     /// # Notes
     ///
-    /// The digits are returned in Most significant digit first order
-    /// and each byte is in litle endian.
+    /// The bytes are returned in big-endian order, the most
+    /// significant byte first and without leading zeros, on every
+    /// platform, as from_digits reads them.
     pub fn to_digits(&self) -> Vec<u8> {
         self.value.to_digits::<u8>(Order::MsfLe)
     }
 
+    /// Returns the bytes of this integer in big-endian order, padded
+    /// with leading zeros to a fixed width, so every number modulo
+    /// the same prime has the same length.
+    ///
+    /// # Parameters
+    ///
+    /// - len: The number of bytes, at least the byte_len of the prime.
+    ///
+    /// # Returns
+    ///
+    /// A vector of len bytes, the most significant first.
+    ///
+    /// # Errors
+    ///
+    /// A ValueError if len is lower than the byte_len of the prime,
+    /// so some numbers of the field would not fit in it.
+    pub fn to_bytes_be(&self, len: usize) -> Result<Vec<u8>, ValueError> {
+        if len < self.prime.byte_len() {
+            return Err(ValueError(format!(
+                "the numbers of the field need {} bytes",
+                self.prime.byte_len()
+            )));
+        }
+        let mut bytes = vec![0u8; len];
+        self.value.write_digits(&mut bytes, Order::MsfLe);
+        Ok(bytes)
+    }
+
+    /// Creates a modular integer from its bytes in big-endian order,
+    /// as written by to_bytes_be.
+    ///
+    /// # Parameters
+    ///
+    /// - bytes: The bytes of the number, the most significant first,
+    ///   with any number of leading zeros.
+    /// - prime: A prime to have as modulus.
+    ///
+    /// # Returns
+    ///
+    /// A wrapped modular integer with the given prime as modulus.
+    ///
+    /// # Errors
+    ///
+    /// A ValueError if the number is not lower than the prime, so
+    /// every number has a single serialization.
+    pub fn from_bytes_be(bytes: &[u8], prime: &'a Prime) -> Result<Self, ValueError> {
+        let value = Integer::from_digits(bytes, Order::MsfLe);
+        if value >= prime.value {
+            return Err(ValueError("the number is not lower than the prime".into()));
+        }
+        Ok(ModInteger { value, prime })
+    }
+
+    /// Returns this integer in lowercase hexadecimal, padded with
+    /// leading zeros to two digits for each byte of the prime.
+    pub fn to_hex(&self) -> String {
+        format!("{:0>1$x}", self, 2 * self.prime.byte_len())
+    }
+
+    /// Creates a modular integer from hexadecimal, as written by
+    /// to_hex.
+    ///
+    /// # Parameters
+    ///
+    /// - hex: The digits of the number, in upper or lower case and
+    ///   with any number of leading zeros.
+    /// - prime: A prime to have as modulus.
+    ///
+    /// # Returns
+    ///
+    /// A wrapped modular integer with the given prime as modulus.
+    ///
+    /// # Errors
+    ///
+    /// A ValueError if the string has no digits, has other characters
+    /// or the number is not lower than the prime.
+    pub fn from_hex(hex: &str, prime: &'a Prime) -> Result<Self, ValueError> {
+        if hex.is_empty() || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(ValueError(format!("'{}' is not a hexadecimal number", hex)));
+        }
+        let value = Integer::from_str_radix(hex, 16).unwrap();
+        if value >= prime.value {
+            return Err(ValueError("the number is not lower than the prime".into()));
+        }
+        Ok(ModInteger { value, prime })
+    }
+
+    /// Returns this integer in standard base64 with padding, the
+    /// encoding of its bytes written by to_bytes_be with the
+    /// byte_len of the prime.
+    pub fn to_base64(&self) -> String {
+        let bytes = self.to_bytes_be(self.prime.byte_len()).unwrap();
+        to_base64(&bytes)
+    }
+
+    /// Creates a modular integer from base64, as written by
+    /// to_base64.
+    ///
+    /// # Parameters
+    ///
+    /// - base64: The bytes of the number in standard base64, with or
+    ///   without padding.
+    /// - prime: A prime to have as modulus.
+    ///
+    /// # Returns
+    ///
+    /// A wrapped modular integer with the given prime as modulus.
+    ///
+    /// # Errors
+    ///
+    /// A ValueError if the string is not valid base64 or the number
+    /// is not lower than the prime.
+    pub fn from_base64(base64: &str, prime: &'a Prime) -> Result<Self, ValueError> {
+        let bytes = from_base64(base64)
+            .ok_or_else(|| ValueError(format!("'{}' is not valid base64", base64)))?;
+        ModInteger::from_bytes_be(&bytes, prime)
+    }
+
     /// Returns a string representation of the number for the given radix.
     ///
     /// # Parameters
@@ -269,7 +445,7 @@ macro_rules! base_operation {
             value: ($new_value).rem_euc(&$lhs.prime.value),
             prime: $lhs.prime,
         }
-    };};
+    }};
 }
 
 macro_rules! operation {
@@ -406,7 +582,7 @@ impl DivAssign<&ModInteger<'_>> for ModInteger<'_> {
             panic!("illegal division by 0");
         }
         self.value *= Integer::from(
-            (&rhs.value)
+            rhs.value
                 .invert_ref(&rhs.prime.value)
                 .expect("prime is not an actual prime"),
         );
@@ -414,6 +590,43 @@ impl DivAssign<&ModInteger<'_>> for ModInteger<'_> {
     }
 }
 
+// operations with a small scalar, which is reduced by the
+// modulus along with the result, without allocating an integer
+macro_rules! scalar_operation {
+    ($trait:ident, $method:ident, $assign_trait:ident, $assign_method:ident, $op:tt, $assign_op:tt) => {
+        impl $trait<u64> for ModInteger<'_> {
+            type Output = Self;
+
+            fn $method(mut self, rhs: u64) -> Self::Output {
+                self.value $assign_op rhs;
+                self.value.rem_euc_assign(&self.prime.value);
+                self
+            }
+        }
+
+        impl<'a> $trait<u64> for &ModInteger<'a> {
+            type Output = ModInteger<'a>;
+
+            fn $method(self, rhs: u64) -> Self::Output {
+                ModInteger {
+                    value: Integer::from(&self.value $op rhs).rem_euc(&self.prime.value),
+                    prime: self.prime,
+                }
+            }
+        }
+
+        impl $assign_trait<u64> for ModInteger<'_> {
+            fn $assign_method(&mut self, rhs: u64) {
+                self.value $assign_op rhs;
+                self.value.rem_euc_assign(&self.prime.value);
+            }
+        }
+    };
+}
+
+scalar_operation!(Add, add, AddAssign, add_assign, +, +=);
+scalar_operation!(Mul, mul, MulAssign, mul_assign, *, *=);
+
 impl PartialEq for ModInteger<'_> {
     /// Returns true if both numbers are equal and
     /// have the same modulus.
@@ -425,7 +638,29 @@ impl PartialEq for ModInteger<'_> {
 impl Display for ModInteger<'_> {
     /// Returns a string representation of this number.
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        self.value.fmt(f)
+        Display::fmt(&self.value, f)
+    }
+}
+
+impl Zeroize for ModInteger<'_> {
+    /// Overwrites every limb allocated for this number with zeros
+    /// and sets it to zero. Copies left by earlier reallocations of
+    /// GMP are not reached.
+    fn zeroize(&mut self) {
+        // the limbs beyond the size may hold an earlier larger value
+        unsafe {
+            let raw = self.value.as_raw_mut();
+            std::slice::from_raw_parts_mut((*raw).d.as_ptr(), (*raw).alloc as usize).zeroize();
+            (*raw).size = 0;
+        }
+    }
+}
+
+impl LowerHex for ModInteger<'_> {
+    /// Writes this number in lowercase hexadecimal, without padding
+    /// unless the formatter asks for it.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        LowerHex::fmt(&self.value, f)
     }
 }
 
@@ -437,7 +672,7 @@ impl Display for ModInteger<'_> {
 mod tests {
     use rug::Integer;
 
-    use crate::math::random::Rng;
+    use crate::math::{prime, random::Rng};
 
     use super::*;
 
@@ -452,13 +687,134 @@ mod tests {
     #[test]
     fn mod_int_random() {
         let prime = Prime::parse("7").unwrap();
-        let mut rng = Rng::new();
+        let mut rng = Rng::new().unwrap();
         for _ in 0..100 {
             let number = ModInteger::random(&prime, &mut rng);
             assert_valid_mod_int!(number, prime);
         }
     }
 
+    #[test]
+    fn mod_int_fixed_width_bytes() {
+        let prime = Prime::parse("65537").unwrap();
+        assert_eq!(prime.byte_len(), 3);
+        let number = ModInteger::parse("258", &prime).unwrap();
+        assert_eq!(number.to_bytes_be(3).unwrap(), vec![0, 1, 2]);
+        assert_eq!(number.to_bytes_be(5).unwrap(), vec![0, 0, 0, 1, 2]);
+        assert!(number.to_bytes_be(2).is_err());
+        assert_eq!(ModInteger::zero(&prime).to_bytes_be(3).unwrap(), vec![0; 3]);
+        assert_eq!(
+            ModInteger::from_bytes_be(&[0, 0, 0, 1, 2], &prime).unwrap(),
+            number
+        );
+        let largest = ModInteger::parse("65536", &prime).unwrap();
+        let bytes = largest.to_bytes_be(3).unwrap();
+        assert_eq!(bytes, vec![1, 0, 0]);
+        assert_eq!(ModInteger::from_bytes_be(&bytes, &prime).unwrap(), largest);
+        assert!(ModInteger::from_bytes_be(&[1, 0, 1], &prime).is_err());
+
+        let prime = prime::registry()[0].prime();
+        let mut rng = Rng::with_seed(3);
+        for _ in 0..20 {
+            let number = ModInteger::random(&prime, &mut rng);
+            let bytes = number.to_bytes_be(prime.byte_len()).unwrap();
+            assert_eq!(bytes.len(), 33);
+            assert_eq!(ModInteger::from_bytes_be(&bytes, &prime).unwrap(), number);
+        }
+    }
+
+    #[test]
+    fn mod_int_hex_and_base64() {
+        let prime = Prime::parse("65537").unwrap();
+        let number = ModInteger::parse("258", &prime).unwrap();
+        assert_eq!(format!("{:x}", number), "102");
+        assert_eq!(format!("{:#08x}", number), "0x000102");
+        assert_eq!(number.to_hex(), "000102");
+        assert_eq!(ModInteger::from_hex("000102", &prime).unwrap(), number);
+        assert_eq!(ModInteger::from_hex("102", &prime).unwrap(), number);
+        assert_eq!(
+            ModInteger::from_hex("FFFF", &prime).unwrap().to_hex(),
+            "00ffff"
+        );
+        assert!(ModInteger::from_hex("", &prime).is_err());
+        assert!(ModInteger::from_hex("-1", &prime).is_err());
+        assert!(ModInteger::from_hex("0x1", &prime).is_err());
+        assert!(ModInteger::from_hex("10001", &prime).is_err());
+
+        assert_eq!(number.to_base64(), "AAEC");
+        assert_eq!(ModInteger::from_base64("AAEC", &prime).unwrap(), number);
+        assert!(ModInteger::from_base64("AAE?", &prime).is_err());
+        assert!(ModInteger::from_base64("AQAB", &prime).is_err());
+
+        let prime = prime::registry()[0].prime();
+        let mut rng = Rng::with_seed(5);
+        for _ in 0..20 {
+            let number = ModInteger::random(&prime, &mut rng);
+            assert_eq!(number.to_hex().len(), 66);
+            assert_eq!(
+                ModInteger::from_hex(&number.to_hex(), &prime).unwrap(),
+                number
+            );
+            assert_eq!(
+                ModInteger::from_base64(&number.to_base64(), &prime).unwrap(),
+                number
+            );
+        }
+    }
+
+    #[test]
+    fn mod_int_zeroize() {
+        let prime = prime::registry()[0].prime();
+        let mut number = ModInteger::random_nonzero(&prime, &mut Rng::with_seed(1));
+        number.zeroize();
+        assert_eq!(number, ModInteger::zero(&prime));
+        assert_eq!(number.value.significant_bits(), 0);
+        // the number is still usable
+        number += ModInteger::parse("7", &prime).unwrap();
+        assert_eq!(number.to_string(), "7");
+    }
+
+    #[test]
+    fn mod_int_random_nonzero() {
+        let prime = Prime::parse("3").unwrap();
+        let mut rng = Rng::with_seed(7);
+        let mut counts = [0; 3];
+        for _ in 0..3000 {
+            let number = ModInteger::random_nonzero(&prime, &mut rng);
+            assert_valid_mod_int!(number, prime);
+            counts[number.value.to_usize().unwrap()] += 1;
+        }
+        // zero is rejected, and the other numbers are equally likely
+        assert_eq!(counts[0], 0);
+        assert!(counts[1] > 1300 && counts[2] > 1300, "{:?}", counts);
+    }
+
+    #[test]
+    fn mod_int_random_range() {
+        let prime = Prime::parse("101").unwrap();
+        let lo = ModInteger::parse("40", &prime).unwrap();
+        let hi = ModInteger::parse("44", &prime).unwrap();
+        let mut rng = Rng::with_seed(7);
+        let mut counts = [0; 4];
+        for _ in 0..4000 {
+            let number = ModInteger::random_range(&lo, &hi, &mut rng);
+            assert_valid_mod_int!(number, prime);
+            assert!(number.value >= 40 && number.value < 44);
+            counts[number.value.to_usize().unwrap() - 40] += 1;
+        }
+        assert!(counts.iter().all(|&count| count > 800), "{:?}", counts);
+        let single = ModInteger::random_range(&lo, &(lo.clone() + 1u64), &mut rng);
+        assert_eq!(single, lo);
+    }
+
+    #[test]
+    #[should_panic(expected = "empty range")]
+    fn mod_int_random_empty_range() {
+        let prime = Prime::parse("101").unwrap();
+        let lo = ModInteger::parse("40", &prime).unwrap();
+        ModInteger::random_range(&lo, &lo, &mut Rng::new().unwrap());
+    }
+
     macro_rules! parse_test {
         ($prime:expr, $num:expr, $mod_value:expr) => {
             let prime = Prime::parse(&$prime.to_string()).unwrap();
@@ -528,6 +884,17 @@ mod tests {
         test_to_digits!("66051", "1000003", [0x1, 0x2, 0x3]);
     }
 
+    #[test]
+    fn mod_int_digits_byte_order() {
+        // frozen by the format of the shares, whatever the platform
+        let prime = Prime::parse("5915587277").unwrap();
+        let number = ModInteger::from_digits(&[0x01, 0x00, 0x00, 0x02], &prime);
+        assert_eq!(number.value, 0x0100_0002);
+        assert_eq!(number.to_digits(), vec![0x01, 0x00, 0x00, 0x02]);
+        let number = ModInteger::from_digits(&[0x00, 0x00, 0x7f], &prime);
+        assert_eq!(number.to_digits(), vec![0x7f]);
+    }
+
     #[test]
     fn mod_int_digits() {
         // checks that to_digits is the inverse function of
@@ -544,7 +911,7 @@ mod tests {
     fn mod_int_digits_reverse() {
         // The same as before but in the reverse order.
         let prime = Prime::parse("1000003").unwrap();
-        let mut rng = Rng::new();
+        let mut rng = Rng::new().unwrap();
         let number = ModInteger::random(&prime, &mut rng);
         let result = ModInteger::from_digits(&number.to_digits(), &prime);
         assert_eq!(result.value, number.value);
@@ -751,4 +1118,34 @@ mod tests {
         all_ops_test!("7", "5", "5", /, 1);
         all_assign_ops_test!("7", "5", "5", /=, 1);
     }
+
+    macro_rules! scalar_ops_test {
+        ($prime:expr, $lhs:expr, $rhs:expr, $op:tt, $assign_op:tt, $expected:expr) => {
+            let prime = Prime::parse($prime).unwrap();
+            let mut lhs = ModInteger::parse($lhs, &prime).unwrap();
+            let result = &lhs $op $rhs;
+            assert_valid_mod_int!(result, prime);
+            assert_eq!(result.value, Integer::from($expected));
+            let result = ModInteger::parse($lhs, &prime).unwrap() $op $rhs;
+            assert_valid_mod_int!(result, prime);
+            assert_eq!(result.value, Integer::from($expected));
+            lhs $assign_op $rhs;
+            assert_valid_mod_int!(lhs, prime);
+            assert_eq!(lhs.value, Integer::from($expected));
+        };
+    }
+
+    #[test]
+    fn mod_int_add_scalar() {
+        scalar_ops_test!("11", "2", 5u64, +, +=, 7);
+        scalar_ops_test!("11", "10", 10u64, +, +=, 9);
+        scalar_ops_test!("11", "3", u64::MAX, +, +=, 7);
+    }
+
+    #[test]
+    fn mod_int_mul_scalar() {
+        scalar_ops_test!("13", "2", 3u64, *, *=, 6);
+        scalar_ops_test!("11", "10", 9u64, *, *=, 2);
+        scalar_ops_test!("13", "5", 0u64, *, *=, 0);
+    }
 }
@@ -1,8 +1,30 @@
 use std::collections::HashMap;
+use std::ops::Range;
+use std::sync::OnceLock;
+#[cfg(feature = "parallel")]
+use std::thread;
 
+use zeroize::Zeroize;
+
+#[cfg(feature = "parallel")]
+use crate::concurrency::Concurrency;
 use crate::math::error::ValueError;
 use crate::math::*;
 
+// Minimum number of evaluations of an interpolation polynomial for
+// its base polynomials and denominators to be evaluated in several
+// threads
+#[cfg(feature = "parallel")]
+const PARALLEL_THRESHOLD: usize = 64;
+
+// Minimum number of evaluations of an interpolation polynomial for
+// its denominators to be evaluated with product and remainder trees
+const PRODUCT_TREE_THRESHOLD: usize = 512;
+
+// Maximum length of the polynomials multiplied term by term, longer
+// ones are multiplied with karatsuba's algorithm
+const KARATSUBA_THRESHOLD: usize = 32;
+
 /// A modular Evaluation of a polynomial
 pub type Evaluation<'a> = (ModInteger<'a>, ModInteger<'a>);
 
@@ -12,8 +34,14 @@ pub struct CoeffPolynomial<'a> {
 }
 
 /// A Modular Integer interpolation polynomial
+///
+/// The inverses of the denominators of its Lagrange base polynomials
+/// depend only on the first values of the evaluations, so they are
+/// computed on the first evaluation and reused by the next ones.
 pub struct InterpolationPolynomial<'a> {
     evaluations: Vec<Evaluation<'a>>,
+    // the inverse of the product of x_i - x_j for every j but i
+    weights: OnceLock<Vec<ModInteger<'a>>>,
 }
 
 /// A polynomial, either created from coefficients
@@ -32,7 +60,7 @@ impl<'a> Polynomial<'a> {
     /// # Parameters
     ///
     /// - coefficients: A vector of coefficients. It is required that this
-    /// vector is not empty and that the last coefficient is not zero.
+    ///   vector is not empty and that the last coefficient is not zero.
     ///
     /// # Returns
     ///
@@ -50,7 +78,7 @@ impl<'a> Polynomial<'a> {
     /// # Parameters
     ///
     /// - evaluations: A vector of Evaluations. Each evaluation should have a unique
-    /// first value and the evaluations vector should not be empty.
+    ///   first value and the evaluations vector should not be empty.
     ///
     /// # Returns
     ///
@@ -88,7 +116,7 @@ impl<'a> CoeffPolynomial<'a> {
     /// # Parameters
     ///
     /// - coefficients: A vector of coefficients. It is required that this
-    /// vector is not empty and that the last coefficient is not zero.
+    ///   vector is not empty and that the last coefficient is not zero.
     ///
     /// # Returns
     ///
@@ -99,7 +127,7 @@ impl<'a> CoeffPolynomial<'a> {
     /// It is encouraged to create this type of polynomial using Polynomial::from_coefficients
     /// instead of using this method.
     pub fn new(coefficients: Vec<ModInteger<'a>>) -> Self {
-        if coefficients.len() == 0 {
+        if coefficients.is_empty() {
             panic!("Tried to create polynomial with zero elements");
         }
         if *coefficients.last().unwrap() == coefficients[0].zero() {
@@ -138,7 +166,7 @@ impl<'a> InterpolationPolynomial<'a> {
     /// # Parameters
     ///
     /// - evaluations: A vector of Evaluations. Each evaluation should have a unique
-    /// first value and the evaluations vector should not be empty.
+    ///   first value and the evaluations vector should not be empty.
     ///
     /// # Returns
     ///
@@ -149,7 +177,7 @@ impl<'a> InterpolationPolynomial<'a> {
     /// It is encouraged to create this type of polynomial using Polynomial::from_evals
     /// instead of using this method.
     pub fn new(evaluations: Vec<Evaluation<'a>>) -> Result<Self, ValueError> {
-        if evaluations.len() == 0 {
+        if evaluations.is_empty() {
             return Err(ValueError("No evaluations were provided".into()));
         }
         let mut unique_evals = HashMap::with_capacity(evaluations.len());
@@ -159,7 +187,10 @@ impl<'a> InterpolationPolynomial<'a> {
             }
             unique_evals.insert(x, y);
         }
-        Ok(InterpolationPolynomial { evaluations })
+        Ok(InterpolationPolynomial {
+            evaluations,
+            weights: OnceLock::new(),
+        })
     }
 
     /// Returns the result from evaluating this polynomial
@@ -168,7 +199,7 @@ impl<'a> InterpolationPolynomial<'a> {
     /// # Parameters
     ///
     /// - x: Modular integer representing the result of
-    /// an evaluation.
+    ///   an evaluation.
     ///
     /// # Returns
     ///
@@ -178,25 +209,303 @@ impl<'a> InterpolationPolynomial<'a> {
     ///
     /// Discouraged in favor of Polynomial.eval()
     pub fn eval(&self, x: ModInteger<'a>) -> Evaluation<'a> {
-        let y = self
-            .evaluations
+        let y = self.eval_barycentric(&x);
+        (x, y)
+    }
+
+    /// Same as eval, but evaluating the base polynomials
+    /// in the given number of threads.
+    ///
+    /// # Parameters
+    ///
+    /// - x: Modular integer representing the result of
+    ///   an evaluation.
+    /// - threads: The number of threads, at least 1.
+    ///
+    /// # Returns
+    ///
+    /// An evaluation from the given polynomial.
+    #[cfg(feature = "parallel")]
+    pub fn eval_with_threads(&self, x: ModInteger<'a>, threads: usize) -> Evaluation<'a> {
+        let len = self.evaluations.len();
+        let chunk = len.div_ceil(threads.max(1));
+        self.weights();
+        let y = thread::scope(|scope| {
+            let partial_sums: Vec<_> = (0..len)
+                .step_by(chunk)
+                .map(|start| {
+                    let x = &x;
+                    scope.spawn(move || self.sum_terms(x, start..(start + chunk).min(len)))
+                })
+                .collect();
+            partial_sums
+                .into_iter()
+                .fold(self.evaluations[0].0.zero(), |acc, sum| {
+                    acc + sum.join().unwrap()
+                })
+        });
+        (x, y)
+    }
+
+    // the inverses of the denominators of the base polynomials,
+    // computed on the first call
+    fn weights(&self) -> &[ModInteger<'a>] {
+        self.weights.get_or_init(|| {
+            let len = self.evaluations.len();
+            let denominators = if len >= PRODUCT_TREE_THRESHOLD {
+                self.denominators_product_tree()
+            } else {
+                self.denominators_in_threads()
+            };
+            denominators.iter().map(|d| d.one() / d).collect()
+        })
+    }
+
+    #[cfg(feature = "parallel")]
+    fn denominators_in_threads(&self) -> Vec<ModInteger<'a>> {
+        let len = self.evaluations.len();
+        if len < PARALLEL_THRESHOLD {
+            return self.denominators(0..len);
+        }
+        let threads = Concurrency::global().threads();
+        let chunk = len.div_ceil(threads);
+        thread::scope(|scope| {
+            let parts: Vec<_> = (0..len)
+                .step_by(chunk)
+                .map(|start| {
+                    scope.spawn(move || self.denominators(start..(start + chunk).min(len)))
+                })
+                .collect();
+            parts
+                .into_iter()
+                .flat_map(|part| part.join().unwrap())
+                .collect()
+        })
+    }
+
+    #[cfg(not(feature = "parallel"))]
+    fn denominators_in_threads(&self) -> Vec<ModInteger<'a>> {
+        self.denominators(0..self.evaluations.len())
+    }
+
+    // the products of x_i - x_j for every j but i, for i in the range
+    fn denominators(&self, range: Range<usize>) -> Vec<ModInteger<'a>> {
+        range
+            .map(|i| {
+                let x_i = &self.evaluations[i].0;
+                self.evaluations
+                    .iter()
+                    .enumerate()
+                    .filter(|(j, _)| i != *j)
+                    .fold(x_i.one(), |acc, (_, (x_j, _))| acc * (x_i - x_j))
+            })
+            .collect()
+    }
+
+    // the denominators in O(M(k) log k), M(k) being the cost of
+    // multiplying polynomials of degree k: they are the derivative of
+    // the product of (x - x_i) at each x_i, evaluated all at once with
+    // a remainder tree
+    fn denominators_product_tree(&self) -> Vec<ModInteger<'a>> {
+        let xs: Vec<&ModInteger<'a>> = self.evaluations.iter().map(|(x_i, _)| x_i).collect();
+        let tree = product_tree(&xs);
+        let product = &tree[tree.len() - 1][0];
+        let derivative: Vec<ModInteger<'a>> = product
             .iter()
             .enumerate()
-            .fold(self.evaluations[0].0.zero(), |acc, (i, (_, y))| {
-                acc + (y * self.eval_base_polynomial(&x, i))
-            });
-        (x, y)
+            .skip(1)
+            .map(|(i, c)| c * i as u64)
+            .collect();
+        remainder_tree(&derivative, &tree)
+    }
+
+    // evaluates the polynomial in O(k) with the weights, the
+    // numerators being the products of every x - x_j but one
+    fn eval_barycentric(&self, x: &ModInteger<'a>) -> ModInteger<'a> {
+        let weights = self.weights();
+        let differences: Vec<ModInteger<'a>> =
+            self.evaluations.iter().map(|(x_j, _)| x - x_j).collect();
+        let mut suffixes = vec![x.one(); differences.len() + 1];
+        for i in (0..differences.len()).rev() {
+            suffixes[i] = &suffixes[i + 1] * &differences[i];
+        }
+        let mut prefix = x.one();
+        let mut y = x.zero();
+        for (i, (_, y_i)) in self.evaluations.iter().enumerate() {
+            y += &prefix * &suffixes[i + 1] * &weights[i] * y_i;
+            prefix *= &differences[i];
+        }
+        y
+    }
+
+    // sums the terms of the lagrange interpolation in the range
+    #[cfg(any(test, feature = "parallel"))]
+    fn sum_terms(&self, x: &ModInteger<'a>, range: Range<usize>) -> ModInteger<'a> {
+        range.fold(self.evaluations[0].0.zero(), |acc, i| {
+            acc + (&self.evaluations[i].1 * self.eval_base_polynomial(x, i))
+        })
     }
 
     // evaluates the ith Lagrange base polynomial
+    #[cfg(any(test, feature = "parallel"))]
     fn eval_base_polynomial(&self, x: &ModInteger<'a>, i: usize) -> ModInteger<'a> {
-        let (x_i, _) = &self.evaluations[i];
         self.evaluations
             .iter()
             .enumerate()
             .filter(|(j, _)| i != *j)
-            .map(|(_, (v, _))| v)
-            .fold(x_i.one(), |acc, x_j| acc * (x - x_j) / (x_i - x_j))
+            .fold(self.weights()[i].clone(), |acc, (_, (x_j, _))| {
+                acc * (x - x_j)
+            })
+    }
+}
+
+// returns the levels of the product tree of the polynomials x - x_i,
+// the leaves first and the product of all of them last
+fn product_tree<'a>(xs: &[&ModInteger<'a>]) -> Vec<Vec<Vec<ModInteger<'a>>>> {
+    let leaves = xs
+        .iter()
+        .map(|&x_i| vec![x_i.zero() - x_i, x_i.one()])
+        .collect();
+    let mut tree: Vec<Vec<Vec<ModInteger<'a>>>> = vec![leaves];
+    while tree[tree.len() - 1].len() > 1 {
+        let level = tree[tree.len() - 1]
+            .chunks(2)
+            .map(|pair| match pair {
+                [left, right] => multiply(left, right),
+                _ => pair[0].clone(),
+            })
+            .collect();
+        tree.push(level);
+    }
+    tree
+}
+
+// evaluates a polynomial at the roots of the leaves of a product tree,
+// reducing it modulo each node from the root down
+fn remainder_tree<'a>(
+    polynomial: &[ModInteger<'a>],
+    tree: &[Vec<Vec<ModInteger<'a>>>],
+) -> Vec<ModInteger<'a>> {
+    let mut remainders = vec![remainder(polynomial, &tree[tree.len() - 1][0])];
+    for level in tree.iter().rev().skip(1) {
+        remainders = level
+            .iter()
+            .enumerate()
+            .map(|(i, node)| remainder(&remainders[i / 2], node))
+            .collect();
+    }
+    let zero = tree[0][0][1].zero();
+    remainders
+        .into_iter()
+        .map(|r| r.into_iter().next().unwrap_or_else(|| zero.clone()))
+        .collect()
+}
+
+// multiplies two polynomials given by their coefficients
+fn multiply<'a>(a: &[ModInteger<'a>], b: &[ModInteger<'a>]) -> Vec<ModInteger<'a>> {
+    if a.is_empty() || b.is_empty() {
+        return Vec::new();
+    }
+    if a.len().min(b.len()) <= KARATSUBA_THRESHOLD {
+        let mut product = vec![a[0].zero(); a.len() + b.len() - 1];
+        for (i, a_i) in a.iter().enumerate() {
+            for (j, b_j) in b.iter().enumerate() {
+                product[i + j] += a_i * b_j;
+            }
+        }
+        return product;
+    }
+    // a = a_0 + x^m a_1 and b = b_0 + x^m b_1
+    let m = a.len().max(b.len()) / 2;
+    let (a_0, a_1) = a.split_at(m.min(a.len()));
+    let (b_0, b_1) = b.split_at(m.min(b.len()));
+    let low = multiply(a_0, b_0);
+    let high = multiply(a_1, b_1);
+    let middle = subtract(
+        &subtract(&multiply(&add(a_0, a_1), &add(b_0, b_1)), &low),
+        &high,
+    );
+    let mut product = vec![a[0].zero(); a.len() + b.len() - 1];
+    for (offset, part) in [(0, &low), (m, &middle), (2 * m, &high)] {
+        for (i, c) in part.iter().enumerate() {
+            if offset + i < product.len() {
+                product[offset + i] += c;
+            }
+        }
+    }
+    product
+}
+
+// adds two polynomials given by their coefficients
+fn add<'a>(a: &[ModInteger<'a>], b: &[ModInteger<'a>]) -> Vec<ModInteger<'a>> {
+    let (long, short) = if a.len() >= b.len() { (a, b) } else { (b, a) };
+    let mut sum = long.to_vec();
+    for (i, c) in short.iter().enumerate() {
+        sum[i] += c;
+    }
+    sum
+}
+
+// subtracts two polynomials given by their coefficients
+fn subtract<'a>(a: &[ModInteger<'a>], b: &[ModInteger<'a>]) -> Vec<ModInteger<'a>> {
+    let mut difference = a.to_vec();
+    for (i, c) in b.iter().enumerate() {
+        match difference.get_mut(i) {
+            Some(d) => *d -= c,
+            None => difference.push(c.zero() - c),
+        }
+    }
+    difference
+}
+
+// returns the inverse of a polynomial modulo x^n by newton's
+// iteration, requires its constant term not to be zero
+fn inverse<'a>(f: &[ModInteger<'a>], n: usize) -> Vec<ModInteger<'a>> {
+    let mut g = vec![f[0].one() / &f[0]];
+    while g.len() < n {
+        let len = (2 * g.len()).min(n);
+        // g = g (2 - f g) modulo x^len
+        let mut error = multiply(&f[..len.min(f.len())], &g);
+        error.truncate(len);
+        let mut correction: Vec<ModInteger<'a>> = error.iter().map(|c| c.zero() - c).collect();
+        correction[0] += 2;
+        g = multiply(&g, &correction);
+        g.truncate(len);
+    }
+    g
+}
+
+// returns the remainder of dividing a by a monic polynomial b
+fn remainder<'a>(a: &[ModInteger<'a>], b: &[ModInteger<'a>]) -> Vec<ModInteger<'a>> {
+    if a.len() < b.len() {
+        return a.to_vec();
+    }
+    // the reversed quotient is the reversed a over the reversed b
+    let quotient_len = a.len() - b.len() + 1;
+    let reversed_a: Vec<_> = a.iter().rev().take(quotient_len).cloned().collect();
+    let reversed_b: Vec<_> = b.iter().rev().cloned().collect();
+    let mut quotient = multiply(&reversed_a, &inverse(&reversed_b, quotient_len));
+    quotient.truncate(quotient_len);
+    quotient.reverse();
+    let mut r = subtract(a, &multiply(b, &quotient));
+    r.truncate(b.len() - 1);
+    r
+}
+
+// the secret is the constant coefficient, and any k of the
+// evaluations reveal it, so both are wiped when dropped
+impl Drop for CoeffPolynomial<'_> {
+    fn drop(&mut self) {
+        self.coefficients.iter_mut().for_each(Zeroize::zeroize);
+    }
+}
+
+impl Drop for InterpolationPolynomial<'_> {
+    fn drop(&mut self) {
+        for (x, y) in &mut self.evaluations {
+            x.zeroize();
+            y.zeroize();
+        }
     }
 }
 
@@ -206,6 +515,7 @@ impl<'a> InterpolationPolynomial<'a> {
 
 #[cfg(test)]
 mod tests {
+    use crate::math::random::Rng;
     use crate::math::{Field, ModInteger, Prime};
 
     use super::*;
@@ -237,4 +547,103 @@ mod tests {
         assert_eq!(x, ModInteger::zero(&prime));
         assert_eq!(y, ModInteger::parse("1", &prime).unwrap());
     }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn test_lagrange_threads() {
+        let prime = Prime::parse("648863").unwrap();
+        let coefficients: Vec<_> = (1..=100u64).map(|c| ModInteger::zero(&prime) + c).collect();
+        let polynomial = Polynomial::from_coefficients(coefficients);
+        let evaluations: Vec<_> = (1..=100u64)
+            .map(|x| polynomial.eval(ModInteger::zero(&prime) + x))
+            .collect();
+        let interpolation = InterpolationPolynomial::new(evaluations).unwrap();
+        let (_, expected) = polynomial.eval(ModInteger::zero(&prime));
+        for &threads in &[1, 3, 8] {
+            let (_, y) = interpolation.eval_with_threads(ModInteger::zero(&prime), threads);
+            assert_eq!(y, expected);
+        }
+        let (_, y) = interpolation.eval(ModInteger::zero(&prime));
+        assert_eq!(y, expected);
+    }
+
+    #[test]
+    fn test_product_tree() {
+        let prime = Prime::parse("648863").unwrap();
+        let mut rng = Rng::with_seed(5);
+        for &k in &[1, 2, 7, 150] {
+            let mut seen = HashMap::new();
+            let evaluations: Vec<_> = std::iter::repeat_with(|| {
+                (
+                    ModInteger::random(&prime, &mut rng),
+                    ModInteger::random(&prime, &mut rng),
+                )
+            })
+            .filter(|(x, _)| seen.insert(x.clone(), ()).is_none())
+            .take(k)
+            .collect();
+            let at_root = evaluations[0].0.clone();
+            let polynomial = InterpolationPolynomial::new(evaluations).unwrap();
+            assert_eq!(
+                polynomial.denominators_product_tree(),
+                polynomial.denominators(0..k)
+            );
+            for x in [
+                ModInteger::zero(&prime),
+                ModInteger::zero(&prime) + 12345,
+                at_root,
+            ] {
+                let expected = polynomial.sum_terms(&x, 0..k);
+                assert_eq!(polynomial.eval_barycentric(&x), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn test_cached_weights() {
+        let prime = Prime::parse("648863").unwrap();
+        let coefficients: Vec<_> = (1..=20u64).map(|c| ModInteger::zero(&prime) + c).collect();
+        let polynomial = Polynomial::from_coefficients(coefficients);
+        let evaluations: Vec<_> = (1..=20u64)
+            .map(|x| polynomial.eval(ModInteger::zero(&prime) + x))
+            .collect();
+        let interpolation = InterpolationPolynomial::new(evaluations).unwrap();
+        assert!(interpolation.weights.get().is_none());
+        for x in 0..40u64 {
+            let x = ModInteger::zero(&prime) + x;
+            assert_eq!(interpolation.eval(x.clone()), polynomial.eval(x));
+        }
+        // the weights are computed once
+        let weights = interpolation.weights().as_ptr();
+        interpolation.eval(ModInteger::zero(&prime));
+        assert_eq!(interpolation.weights().as_ptr(), weights);
+    }
+
+    #[test]
+    fn test_karatsuba() {
+        let prime = Prime::parse("648863").unwrap();
+        let mut rng = Rng::with_seed(3);
+        let a: Vec<_> = (0..70)
+            .map(|_| ModInteger::random(&prime, &mut rng))
+            .collect();
+        let b: Vec<_> = (0..45)
+            .map(|_| ModInteger::random(&prime, &mut rng))
+            .collect();
+        let mut expected = vec![ModInteger::zero(&prime); a.len() + b.len() - 1];
+        for (i, a_i) in a.iter().enumerate() {
+            for (j, b_j) in b.iter().enumerate() {
+                expected[i + j] += a_i * b_j;
+            }
+        }
+        assert_eq!(multiply(&a, &b), expected);
+        let q: Vec<_> = (0..40)
+            .map(|_| ModInteger::random(&prime, &mut rng))
+            .collect();
+        let mut monic = b.clone();
+        monic.push(ModInteger::zero(&prime) + 1);
+        let r: Vec<_> = (0..45)
+            .map(|_| ModInteger::random(&prime, &mut rng))
+            .collect();
+        assert_eq!(remainder(&add(&multiply(&q, &monic), &r), &monic), r);
+    }
 }
@@ -1,15 +1,90 @@
 use std::error::Error;
+use std::fmt;
 
+use rug::integer::{IsPrime, Order};
 use rug::Integer;
 
 use crate::math::error::ValueError;
 
+/// A prime of the registry, known by a short identifier.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NamedPrime {
+    /// Identifier of the prime, for the `--field` option and the
+    /// metadata of the shares.
+    pub name: &'static str,
+    /// What the prime is.
+    pub description: &'static str,
+    /// The prime in decimal.
+    pub decimal: &'static str,
+}
+
+impl NamedPrime {
+    /// Returns the prime as a Prime.
+    pub fn prime(&self) -> Prime {
+        Prime::parse(self.decimal).unwrap()
+    }
+}
+
+// primes of the registry, the first one being the default field
+const REGISTRY: [NamedPrime; 6] = [
+    NamedPrime {
+        name: "257",
+        description: "prime of 257 bits, the default field",
+        decimal: "208351617316091241234326746312124448251235562226470491514186331217050270460481",
+    },
+    NamedPrime {
+        name: "129",
+        description: "2^128 + 51, the smallest prime of 129 bits",
+        decimal: "340282366920938463463374607431768211507",
+    },
+    NamedPrime {
+        name: "127",
+        description: "Mersenne prime 2^127 - 1",
+        decimal: "170141183460469231731687303715884105727",
+    },
+    NamedPrime {
+        name: "secp256k1",
+        description: "prime of the coordinates of secp256k1, 2^256 - 2^32 - 977",
+        decimal: "115792089237316195423570985008687907853269984665640564039457584007908834671663",
+    },
+    NamedPrime {
+        name: "secp256k1-order",
+        description: "order of the group of secp256k1",
+        decimal: "115792089237316195423570985008687907852837564279074904382605163141518161494337",
+    },
+    NamedPrime {
+        name: "ed25519-order",
+        description:
+            "order of the group of Ed25519, 2^252 + 27742317777372353535851937790883648493",
+        decimal: "7237005577332262213973186563042994240857116359379907606001950938285454250989",
+    },
+];
+
+/// Returns the registry of named primes, the first one being the
+/// prime of the default field.
+pub fn registry() -> &'static [NamedPrime] {
+    &REGISTRY
+}
+
+/// Finds a prime of the registry by its identifier.
+///
+/// # Parameters
+///
+/// - name: The identifier of the prime, for example `secp256k1-order`.
+///
+/// # Returns
+///
+/// The prime, or None if no prime of the registry has that identifier.
+pub fn named(name: &str) -> Option<&'static NamedPrime> {
+    REGISTRY.iter().find(|named| named.name == name)
+}
+
 /// Simple struct for representing prime numbers to use with modular
 /// integers.
 ///
 /// The objective of this type is to provide a unique instance from which
 /// references can be borrowed when creating and manipulating modular integers.
-#[derive(Debug, Eq, Hash)]
+#[derive(Debug, PartialEq, Eq, Hash)]
 pub struct Prime {
     /// Value of the prime.
     pub(in crate::math) value: Integer,
@@ -49,12 +124,86 @@ impl Prime {
             Ok(Prime { value })
         }
     }
+
+    /// Returns the smallest prime greater than a number.
+    ///
+    /// # Parameters
+    ///
+    /// - from: The number to start the search from, which is not
+    ///   itself returned even if it is a prime.
+    ///
+    /// # Returns
+    ///
+    /// The next prime, with a probability lower than 4^-30 of being
+    /// a composite number.
+    pub fn next_prime(from: Integer) -> Self {
+        Prime {
+            value: from.next_prime(),
+        }
+    }
+
+    /// Generates a random safe prime p = 2q + 1, where q is also a
+    /// prime, so the multiplicative group modulo p has a subgroup of
+    /// the known prime order q.
+    ///
+    /// # Parameters
+    ///
+    /// - bits: The number of bits of the prime, at least 3.
+    ///
+    /// # Returns
+    ///
+    /// A safe prime of exactly the given number of bits.
+    ///
+    /// # Errors
+    ///
+    /// A ValueError if bits is lower than 3 or the random number
+    /// generator of the system fails.
+    pub fn generate_safe(bits: u32) -> Result<Self, ValueError> {
+        if bits < 3 {
+            return Err(ValueError("A safe prime has at least 3 bits".into()));
+        }
+        let mut random = vec![0u8; (bits as usize + 6) / 8];
+        loop {
+            getrandom::getrandom(&mut random)
+                .map_err(|_| ValueError("Error while generating a random number".into()))?;
+            // a random number of bits - 1 bits, the highest one set
+            let mut start = Integer::from_digits(&random, Order::Msf);
+            start.keep_bits_mut(bits - 1);
+            start.set_bit(bits - 2, true);
+            // the candidate itself is a possible q
+            let q = (start - 1u32).next_prime();
+            if q.significant_bits() != bits - 1 {
+                continue;
+            }
+            let value = Integer::from(&q * 2u32) + 1u32;
+            if value.is_probably_prime(30) != IsPrime::No {
+                return Ok(Prime { value });
+            }
+        }
+    }
+
+    /// Returns whether this number is a prime, with a probability
+    /// lower than 4^-30 of accepting a composite number.
+    pub fn is_probably_prime(&self) -> bool {
+        self.value.is_probably_prime(30) != IsPrime::No
+    }
+
+    /// Returns the number of bits of this number.
+    pub fn bits(&self) -> u32 {
+        self.value.significant_bits()
+    }
+
+    /// Returns the number of bytes of this number, the fixed width
+    /// of the numbers modulo it written by ModInteger::to_bytes_be.
+    pub fn byte_len(&self) -> usize {
+        self.value.significant_bits().div_ceil(8) as usize
+    }
 }
 
-impl PartialEq for Prime {
-    /// Returns True if these prime number equals other.
-    fn eq(&self, other: &Self) -> bool {
-        self.value == other.value
+impl fmt::Display for Prime {
+    /// Writes this number in decimal.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.value)
     }
 }
 
@@ -69,21 +218,62 @@ mod tests {
     #[test]
     fn prime_parse_ok() {
         let mut prime = "31";
-        Prime::parse(&prime).unwrap();
+        Prime::parse(prime).unwrap();
         prime = "2";
-        Prime::parse(&prime).unwrap();
+        Prime::parse(prime).unwrap();
+    }
+
+    #[test]
+    fn primality() {
+        let prime = Prime::parse("170141183460469231731687303715884105727").unwrap();
+        assert!(prime.is_probably_prime());
+        assert_eq!(prime.bits(), 127);
+        assert_eq!(prime.to_string(), "170141183460469231731687303715884105727");
+        assert!(!Prime::parse("561").unwrap().is_probably_prime());
+    }
+
+    #[test]
+    fn next_and_safe_primes() {
+        assert_eq!(Prime::next_prime(Integer::from(0)).to_string(), "2");
+        assert_eq!(Prime::next_prime(Integer::from(7)).to_string(), "11");
+        assert_eq!(
+            Prime::next_prime(Integer::from(1) << 127).to_string(),
+            "170141183460469231731687303715884105757"
+        );
+        assert!(Prime::generate_safe(2).is_err());
+        for &bits in &[3, 4, 64, 256] {
+            let prime = Prime::generate_safe(bits).unwrap();
+            assert_eq!(prime.bits(), bits);
+            assert!(prime.is_probably_prime());
+            let q = Prime {
+                value: Integer::from(&prime.value - 1u32) / 2u32,
+            };
+            assert!(q.is_probably_prime());
+        }
+    }
+
+    #[test]
+    fn registry_primes() {
+        for named in registry() {
+            let prime = named.prime();
+            assert!(prime.is_probably_prime(), "{}", named.name);
+            assert_eq!(prime.to_string(), named.decimal);
+            assert_eq!(super::named(named.name), Some(named));
+        }
+        assert_eq!(registry()[0].prime().bits(), 257);
+        assert_eq!(super::named("129").unwrap().prime().bits(), 129);
+        assert_eq!(super::named("ed25519-order").unwrap().prime().bits(), 253);
+        assert_eq!(super::named("128"), None);
     }
 
     #[test]
     fn prime_parse_err() -> Result<(), String> {
-        match Prime::parse("-1") {
-            Ok(_) => return Err("expected to return error".into()),
-            _ => (),
-        };
-        match Prime::parse("1") {
-            Ok(_) => return Err("expected to return error".into()),
-            _ => (),
-        };
+        if Prime::parse("-1").is_ok() {
+            return Err("expected to return error".into());
+        }
+        if Prime::parse("1").is_ok() {
+            return Err("expected to return error".into());
+        }
         match Prime::parse("0") {
             Ok(_) => Err("expected to return error".into()),
             _ => Ok(()),
@@ -1,29 +1,293 @@
-use rug::rand::RandState;
+use std::sync::{Mutex, PoisonError};
+
+use rand_chacha::rand_core::{RngCore, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+use rug::integer::Order;
+use rug::Integer;
+use zeroize::Zeroize;
+
+/// Size in bytes of the seed of a generator, a ChaCha20 key.
+pub const SEED_SIZE: usize = 32;
+
+/// A source of random bytes for the generators of secret values,
+/// which can replace the ChaCha20 generator of a Rng, see
+/// Rng::from_secret_rng.
+pub trait SecretRng: Send + Sync {
+    /// Fills the bytes with random bytes.
+    fn fill_bytes(&mut self, bytes: &mut [u8]);
+}
+
+impl SecretRng for ChaCha20Rng {
+    fn fill_bytes(&mut self, bytes: &mut [u8]) {
+        RngCore::fill_bytes(self, bytes)
+    }
+}
 
 /// Provides a random number generator that can
 /// be use to generate ModIntegers.
-pub struct Rng<'a> {
-    inner: RandState<'a>,
+///
+/// The numbers are drawn from the bytes of a SecretRng, a ChaCha20
+/// generator of rand_chacha unless another one is given, and never
+/// from the generator of GMP, a Mersenne Twister that is predictable
+/// from its output. The same bytes always give the same numbers,
+/// whatever the version of GMP.
+pub struct Rng {
+    inner: Box<dyn SecretRng>,
 }
 
-impl<'a> Rng<'a> {
-    /// Creates a new random number generator.
+impl Rng {
+    /// Creates a random number generator that always produces
+    /// the same numbers for the same seed, for known-answer tests,
+    /// a ChaCha20 generator whose key is the seed in little endian
+    /// followed by zeros.
+    ///
+    /// # Parameters
+    ///
+    /// - seed: The seed of the generator.
     ///
     /// # Returns
     ///
     /// A new number generator.
-    pub fn new() -> Self {
+    #[cfg(any(test, feature = "deterministic"))]
+    pub fn with_seed(seed: u64) -> Self {
+        let mut key = [0u8; SEED_SIZE];
+        key[..8].copy_from_slice(&seed.to_le_bytes());
+        Rng::from_seed(key)
+    }
+
+    /// Creates a ChaCha20 generator from a seed, so the same seed
+    /// always gives the same numbers. The generator is only as
+    /// secret as its seed.
+    ///
+    /// # Parameters
+    ///
+    /// - seed: The key of ChaCha20, which the caller should zeroize.
+    ///
+    /// # Returns
+    ///
+    /// A new number generator.
+    pub fn from_seed(seed: [u8; SEED_SIZE]) -> Self {
+        Rng::from_secret_rng(ChaCha20Rng::from_seed(seed))
+    }
+
+    /// Creates a new random number generator, a ChaCha20 generator
+    /// seeded with random bytes of the system.
+    ///
+    /// # Returns
+    ///
+    /// A new number generator.
+    ///
+    /// # Errors
+    ///
+    /// This method returns an error if the random number generator
+    /// of the system fails.
+    pub fn new() -> Result<Self, getrandom::Error> {
+        let mut seed = [0u8; SEED_SIZE];
+        getrandom::getrandom(&mut seed)?;
+        let rng = Rng::from_seed(seed);
+        seed.zeroize();
+        Ok(rng)
+    }
+
+    /// Creates a random number generator that draws its numbers
+    /// from another source of random bytes.
+    ///
+    /// # Parameters
+    ///
+    /// - rng: The source of random bytes.
+    ///
+    /// # Returns
+    ///
+    /// A new number generator.
+    pub fn from_secret_rng<R: SecretRng + 'static>(rng: R) -> Self {
         Rng {
-            inner: RandState::new(),
+            inner: Box::new(rng),
         }
     }
 
-    /// Utility method to retrieve the internal wrapped instance of RandState
+    /// Fills the bytes with the next bytes of the generator.
+    ///
+    /// # Parameters
+    ///
+    /// - bytes: The bytes to fill.
+    pub fn fill_bytes(&mut self, bytes: &mut [u8]) {
+        self.inner.fill_bytes(bytes)
+    }
+
+    /// Returns a random integer in the range 0 <= x < bound.
+    ///
+    /// Numbers of as many bits as the bound are drawn, in big
+    /// endian, until one is below it, so every number is equally
+    /// likely and at most two numbers are drawn on average.
+    ///
+    /// # Parameters
+    ///
+    /// - bound: The exclusive upper bound, greater than 0.
+    ///
+    /// # Panics
+    ///
+    /// If the bound is not greater than 0.
+    pub fn below(&mut self, bound: &Integer) -> Integer {
+        assert!(*bound > 0, "the bound must be greater than 0");
+        let bits = bound.significant_bits() as usize;
+        let mut bytes = vec![0u8; bits.div_ceil(8)];
+        loop {
+            self.fill_bytes(&mut bytes);
+            // the bits above the ones of the bound are cleared
+            if bits % 8 != 0 {
+                bytes[0] &= (1u8 << (bits % 8)) - 1;
+            }
+            let number = Integer::from_digits(&bytes, Order::Msf);
+            if number < *bound {
+                bytes.zeroize();
+                return number;
+            }
+        }
+    }
+}
+
+/// A random number generator that threads can share, which gives
+/// each thread its own ChaCha20 generator, seeded with random bytes
+/// of the system or from a master generator, so they do not wait for
+/// each other while generating numbers.
+pub struct SharedRng {
+    // None when the threads are seeded by the system
+    master: Option<Mutex<Rng>>,
+}
+
+impl SharedRng {
+    /// Creates a shared generator whose threads are seeded with
+    /// random bytes of the system, see Rng::new.
+    ///
+    /// # Returns
+    ///
+    /// A new shared generator.
+    pub fn new() -> Self {
+        SharedRng { master: None }
+    }
+
+    /// Creates a shared generator from a master generator, for
+    /// example one created with Rng::with_seed, so the generators of
+    /// the threads are the same for the same master.
+    ///
+    /// # Parameters
+    ///
+    /// - master: The generator the threads are seeded from.
     ///
     /// # Returns
     ///
-    /// An internal wrapped intance of RandState.
-    pub(super) fn internal_rep(&mut self) -> &mut RandState<'a> {
-        &mut self.inner
+    /// A new shared generator.
+    pub fn from_rng(master: Rng) -> Self {
+        SharedRng {
+            master: Some(Mutex::new(master)),
+        }
+    }
+
+    /// Creates the generator of a thread, seeded with random bytes of
+    /// the system or with the next bytes of the master generator.
+    ///
+    /// # Returns
+    ///
+    /// A new number generator, which the thread owns.
+    ///
+    /// # Errors
+    ///
+    /// This method returns an error if the random number generator
+    /// of the system fails.
+    pub fn fork(&self) -> Result<Rng, getrandom::Error> {
+        let master = match &self.master {
+            Some(master) => master,
+            None => return Rng::new(),
+        };
+        let mut seed = [0u8; SEED_SIZE];
+        // a panic of another thread leaves the master usable
+        master
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .fill_bytes(&mut seed);
+        let rng = Rng::from_seed(seed);
+        seed.zeroize();
+        Ok(rng)
+    }
+}
+
+impl Default for SharedRng {
+    fn default() -> Self {
+        SharedRng::new()
+    }
+}
+
+/////////////////////////////////
+// Unit testing of the module. //
+/////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    fn is_send_sync<T: Send + Sync>() {}
+
+    #[test]
+    fn seeded_from_system() {
+        let bound = Integer::from(1) << 128;
+        assert_ne!(
+            Rng::new().unwrap().below(&bound),
+            Rng::new().unwrap().below(&bound)
+        );
+        assert_eq!(
+            Rng::with_seed(1).below(&bound),
+            Rng::with_seed(1).below(&bound)
+        );
+    }
+
+    #[test]
+    fn chacha20_stream() {
+        // RFC 8439, section A.1, test vector 1: the key stream of
+        // the zero key, with the nonce and the counter at zero
+        let mut stream = [0u8; 64];
+        Rng::from_seed([0; SEED_SIZE]).fill_bytes(&mut stream);
+        assert_eq!(
+            crate::encoding::to_hex(&stream),
+            "76b8e0ada0f13d90405d6ae55386bd28bdd219b8a08ded1aa836efcc8b770dc7\
+             da41597c5157488d7724e03fb8d84a376a43b8f41518a11cc387b669b2ee6586"
+        );
+        // a number is the first bits of the stream if it is below
+        // the bound
+        let bound = Integer::from(0x77);
+        assert_eq!(Rng::from_seed([0; SEED_SIZE]).below(&bound), 0x76);
+    }
+
+    #[test]
+    fn shared_between_threads() {
+        is_send_sync::<Rng>();
+        is_send_sync::<SharedRng>();
+        let bound = Integer::from(1) << 128;
+        let shared = SharedRng::from_rng(Rng::with_seed(7));
+        let numbers: Vec<Integer> = thread::scope(|scope| {
+            let handles: Vec<_> = (0..4)
+                .map(|_| scope.spawn(|| shared.fork().unwrap().below(&bound)))
+                .collect();
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        });
+        // every thread gets a different generator
+        for (i, number) in numbers.iter().enumerate() {
+            assert!(!numbers[i + 1..].contains(number));
+        }
+        // the same master forks the same generators
+        let again = SharedRng::from_rng(Rng::with_seed(7));
+        let mut expected: Vec<Integer> = (0..4)
+            .map(|_| again.fork().unwrap().below(&bound))
+            .collect();
+        let mut numbers = numbers;
+        numbers.sort();
+        expected.sort();
+        assert_eq!(numbers, expected);
+        // the system seeds different generators
+        let system = SharedRng::new();
+        assert_ne!(
+            system.fork().unwrap().below(&bound),
+            system.fork().unwrap().below(&bound)
+        );
     }
 }
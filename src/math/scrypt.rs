@@ -0,0 +1,85 @@
+use std::convert::TryFrom;
+
+/// Cost parameters of scrypt (RFC 7914).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Params {
+    /// Base 2 logarithm of the CPU and memory cost N.
+    pub log_n: u8,
+    /// Block size r, the memory used is 128 * r * N bytes.
+    pub r: usize,
+    /// Parallelization p, the number of blocks mixed one after another.
+    pub p: usize,
+}
+
+/// Derives a key from a password with scrypt (RFC 7914), a key
+/// derivation function that needs a lot of memory, so each guess
+/// of the password is expensive even with dedicated hardware, with
+/// the scrypt crate.
+///
+/// # Parameters
+///
+/// - password: The password.
+/// - salt: The salt, different for each key derived.
+/// - params: The cost parameters, which must have log_n below 64
+///   and r and p greater than 0.
+/// - output: Where the derived key is written, of any length.
+///
+/// # Panics
+///
+/// If the cost parameters are not valid.
+pub fn scrypt(password: &[u8], salt: &[u8], params: Params, output: &mut [u8]) {
+    let params = ::scrypt::Params::new(
+        params.log_n,
+        u32::try_from(params.r).expect("the block size fits in 32 bits"),
+        u32::try_from(params.p).expect("the parallelization fits in 32 bits"),
+        ::scrypt::Params::RECOMMENDED_LEN,
+    )
+    .expect("the cost parameters of scrypt are not valid");
+    ::scrypt::scrypt(password, salt, &params, output).expect("the output has a valid length");
+}
+
+/////////////////////////////////
+// Unit testing of the module. //
+/////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn from_hex(hex: &str) -> Vec<u8> {
+        let hex: String = hex.split_whitespace().collect();
+        crate::encoding::from_hex(&hex).unwrap()
+    }
+
+    // RFC 7914, section 12
+    #[test]
+    fn known_answers() {
+        let mut output = [0u8; 64];
+        let params = Params {
+            log_n: 4,
+            r: 1,
+            p: 1,
+        };
+        scrypt(b"", b"", params, &mut output);
+        assert_eq!(
+            output.to_vec(),
+            from_hex(
+                "77d6576238657b203b19ca42c18a0497f16b4844e3074ae8dfdffa3fede21442
+                 fcd0069ded0948f8326a753a0fc81f17e8d3e0fb2e0d3628cf35e20c38d18906"
+            )
+        );
+        let params = Params {
+            log_n: 10,
+            r: 8,
+            p: 16,
+        };
+        scrypt(b"password", b"NaCl", params, &mut output);
+        assert_eq!(
+            output.to_vec(),
+            from_hex(
+                "fdbabe1c9d3472007856e7190d01e9fe7c6ad7cbc8237830e77376634b373162
+                 2eaf30d92e22a3886ff109279d9830dac727afb94a83ee6d8360cbdfa2cc0640"
+            )
+        );
+    }
+}
@@ -0,0 +1,286 @@
+use std::env;
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::Path;
+
+use sha2::{Digest, Sha256};
+
+use crate::encoding::to_hex;
+
+/// Name of the file of an offline bundle with its instructions.
+pub const INSTRUCTIONS_FILE: &str = "RECOVERY.txt";
+
+/// Name of the file of an offline bundle with the fingerprints of
+/// the shares.
+pub const FINGERPRINTS_FILE: &str = "FINGERPRINTS.txt";
+
+/// Name of the file of an offline bundle with the SHA-256 of the
+/// other files, in the format of sha256sum.
+pub const CHECKSUMS_FILE: &str = "SHA256SUMS";
+
+/// The contents of a directory to recover an encrypted file on a
+/// computer without network: the file, the program, the
+/// fingerprints that tell the right shares and the instructions.
+pub struct Bundle<'a> {
+    /// Name of the encrypted file in the bundle.
+    pub file_name: &'a str,
+    /// Minimum and total number of shares, if the file records them.
+    pub scheme: Option<(usize, usize)>,
+    /// The holders recorded in the file.
+    pub holders: &'a [String],
+    /// The holder, or the index, and the fingerprint of each share.
+    pub fingerprints: &'a [(String, String)],
+    /// Fingerprint of the whole set of shares, see
+    /// fragments::set_fingerprint, if it is known.
+    pub set_fingerprint: Option<&'a str>,
+    /// Path of the program in the bundle, see binary_path, or None if
+    /// it is not included.
+    pub binary: Option<&'a str>,
+}
+
+impl Bundle<'_> {
+    /// Returns the path in a bundle of a copy of the program, in a
+    /// directory for the system and architecture of this computer,
+    /// such as bin/linux-x86_64/shared_secrets.
+    ///
+    /// # Parameters
+    ///
+    /// - binary: The path of the program.
+    pub fn binary_path(binary: &str) -> String {
+        let name = Path::new(binary)
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| env!("CARGO_PKG_NAME").into());
+        format!("bin/{}-{}/{}", env::consts::OS, env::consts::ARCH, name)
+    }
+
+    /// Writes the instructions of this bundle, a text with the
+    /// scheme and the steps to check the bundle and the shares and to
+    /// rehearse and perform the recovery.
+    ///
+    /// # Parameters
+    ///
+    /// - writer: The writer where the instructions are written.
+    ///
+    /// # Errors
+    ///
+    /// This method returns an error if an error occurs while writing.
+    pub fn write_instructions(&self, writer: &mut impl Write) -> io::Result<()> {
+        let program = self.binary.map_or(env!("CARGO_PKG_NAME"), |binary| binary);
+        writeln!(writer, "Shared Secrets - offline recovery")?;
+        writeln!(writer)?;
+        writeln!(writer, "Encrypted file: {}", self.file_name)?;
+        match self.scheme {
+            Some((threshold, total)) => writeln!(
+                writer,
+                "Scheme: {} of {} shares are needed to decrypt",
+                threshold, total
+            )?,
+            None => writeln!(writer, "Scheme: not recorded in the file")?,
+        }
+        if !self.holders.is_empty() {
+            writeln!(writer, "Holders: {}", self.holders.join(", "))?;
+        }
+        if let Some(set_fingerprint) = self.set_fingerprint {
+            writeln!(writer, "Share set fingerprint: {}", set_fingerprint)?;
+        }
+        writeln!(
+            writer,
+            "Program: {} {}",
+            env!("CARGO_PKG_NAME"),
+            env!("CARGO_PKG_VERSION")
+        )?;
+        writeln!(writer)?;
+        writeln!(
+            writer,
+            "This directory has everything needed to recover the file on a"
+        )?;
+        writeln!(
+            writer,
+            "computer that is not connected to any network. The shares are"
+        )?;
+        writeln!(writer, "not in it, the holders bring them.")?;
+        writeln!(writer)?;
+        match self.binary {
+            Some(binary) => {
+                writeln!(writer, "The program is {}, built for", binary)?;
+                writeln!(
+                    writer,
+                    "{} on {}; on another computer bring a copy built for it.",
+                    env::consts::ARCH,
+                    env::consts::OS
+                )?;
+            }
+            None => {
+                writeln!(
+                    writer,
+                    "Bring a copy of the program, version {}, built for the",
+                    env!("CARGO_PKG_VERSION")
+                )?;
+                writeln!(
+                    writer,
+                    "computer; a static binary, built with --target x86_64-unknown-linux-musl,"
+                )?;
+                writeln!(writer, "runs on any Linux.")?;
+            }
+        }
+        writeln!(writer)?;
+        writeln!(
+            writer,
+            "1. Check that no file changed since the bundle was written:"
+        )?;
+        writeln!(writer, "       sha256sum -c {}", CHECKSUMS_FILE)?;
+        writeln!(writer, "   or, on macOS:")?;
+        writeln!(writer, "       shasum -a 256 -c {}", CHECKSUMS_FILE)?;
+        writeln!(writer)?;
+        writeln!(
+            writer,
+            "2. Gather the shares into a shares file, one share per line, and"
+        )?;
+        if self.fingerprints.is_empty() {
+            writeln!(writer, "   check them against the file:")?;
+        } else {
+            writeln!(
+                writer,
+                "   check that their fingerprints are the ones of {}:",
+                FINGERPRINTS_FILE
+            )?;
+        }
+        writeln!(
+            writer,
+            "       {} inspect {} <shares file>",
+            program, self.file_name
+        )?;
+        writeln!(writer)?;
+        writeln!(
+            writer,
+            "3. Rehearse the recovery, which checks the file without writing it:"
+        )?;
+        writeln!(
+            writer,
+            "       {} d {} <shares file> --check",
+            program, self.file_name
+        )?;
+        writeln!(writer)?;
+        writeln!(writer, "4. Recover the file:")?;
+        writeln!(
+            writer,
+            "       {} d {} <shares file>",
+            program, self.file_name
+        )?;
+        writeln!(
+            writer,
+            "   or let each holder type their share, without a shares file:"
+        )?;
+        writeln!(writer, "       {} ceremony {}", program, self.file_name)?;
+        writeln!(writer)?;
+        writeln!(
+            writer,
+            "Afterwards, erase the shares file and keep the recovered file only"
+        )?;
+        writeln!(writer, "on the computer or the medium it is meant for.")
+    }
+
+    /// Writes the fingerprint of each share of this bundle, one per
+    /// line after its holder or index, and the one of the set.
+    ///
+    /// # Parameters
+    ///
+    /// - writer: The writer where the fingerprints are written.
+    ///
+    /// # Errors
+    ///
+    /// This method returns an error if an error occurs while writing.
+    pub fn write_fingerprints(&self, writer: &mut impl Write) -> io::Result<()> {
+        for (holder, fingerprint) in self.fingerprints {
+            writeln!(writer, "{}: {}", holder, fingerprint)?;
+        }
+        if let Some(set_fingerprint) = self.set_fingerprint {
+            writeln!(writer, "set: {}", set_fingerprint)?;
+        }
+        Ok(())
+    }
+}
+
+/// Writes the SHA-256 of some files of a bundle in the format of
+/// sha256sum, so they can be checked with the tools of any system.
+///
+/// # Parameters
+///
+/// - dir: The directory of the bundle.
+/// - files: The paths of the files, relative to the directory and
+///   with '/' as separator.
+/// - writer: The writer where the checksums are written.
+///
+/// # Errors
+///
+/// This function returns an error if a file cannot be read or an
+/// error occurs while writing.
+pub fn write_checksums(dir: &Path, files: &[String], writer: &mut impl Write) -> io::Result<()> {
+    for file in files {
+        let mut hasher = Sha256::new();
+        io::copy(&mut File::open(dir.join(file))?, &mut hasher)?;
+        writeln!(writer, "{}  {}", to_hex(&hasher.finalize()), file)?;
+    }
+    Ok(())
+}
+
+/////////////////////////////////
+// Unit testing of the module. //
+/////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn bundle() {
+        let holders = ["alice".to_string(), "bob".to_string()];
+        let fingerprints = [("alice".to_string(), "0a1b".to_string())];
+        let binary = Bundle::binary_path("/usr/local/bin/shared_secrets");
+        assert!(binary.starts_with("bin/") && binary.ends_with("/shared_secrets"));
+        let mut bundle = Bundle {
+            file_name: "secret.aes",
+            scheme: Some((2, 3)),
+            holders: &holders,
+            fingerprints: &fingerprints,
+            set_fingerprint: Some("2c3d"),
+            binary: Some(&binary),
+        };
+        let mut instructions = Vec::new();
+        bundle.write_instructions(&mut instructions).unwrap();
+        let instructions = String::from_utf8(instructions).unwrap();
+        assert!(instructions.contains("2 of 3 shares"));
+        assert!(instructions.contains("Holders: alice, bob\n"));
+        assert!(instructions.contains(&format!("{} d secret.aes <shares file> --check", binary)));
+        assert!(instructions.contains(FINGERPRINTS_FILE));
+        let mut written = Vec::new();
+        bundle.write_fingerprints(&mut written).unwrap();
+        assert_eq!(written, b"alice: 0a1b\nset: 2c3d\n");
+
+        bundle.fingerprints = &[];
+        bundle.binary = None;
+        let mut instructions = Vec::new();
+        bundle.write_instructions(&mut instructions).unwrap();
+        let instructions = String::from_utf8(instructions).unwrap();
+        assert!(instructions.contains("shared_secrets d secret.aes <shares file>\n"));
+        assert!(instructions.contains("static binary"));
+        assert!(!instructions.contains(FINGERPRINTS_FILE));
+    }
+
+    #[test]
+    fn checksums() {
+        let dir = env::temp_dir().join(format!("offline-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("abc.txt"), "abc").unwrap();
+        let mut checksums = Vec::new();
+        write_checksums(&dir, &["abc.txt".into()], &mut checksums).unwrap();
+        assert_eq!(
+            String::from_utf8(checksums).unwrap(),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad  abc.txt\n"
+        );
+        assert!(write_checksums(&dir, &["missing".into()], &mut Vec::new()).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,229 @@
+use std::io::{self, Write};
+
+use qrcode::{Color, QrCode};
+
+use crate::fragments::Fragment;
+
+// Size of an A4 page in points
+const PAGE_WIDTH: f64 = 595.0;
+const PAGE_HEIGHT: f64 = 842.0;
+
+// Left margin and size of the QR code in points
+const MARGIN: f64 = 56.0;
+const QR_SIZE: f64 = 220.0;
+
+/// The information printed in a paper backup of a share.
+pub struct Sheet<'a> {
+    /// The fragment to print.
+    pub fragment: &'a Fragment,
+    /// Name of the encrypted file the share unlocks.
+    pub file_name: &'a str,
+    /// Minimum number of shares needed to decrypt the file.
+    pub threshold: usize,
+    /// Total number of shares generated.
+    pub total: usize,
+}
+
+impl Sheet<'_> {
+    /// Renders this sheet as a single page PDF document.
+    ///
+    /// The page contains the scheme parameters, recovery instructions,
+    /// the armored fragment and a QR code encoding the fragment line.
+    ///
+    /// # Returns
+    ///
+    /// The bytes of the PDF document.
+    ///
+    /// # Errors
+    ///
+    /// This method returns an error if the fragment is too large
+    /// to be encoded in a QR code.
+    pub fn render(&self) -> Result<Vec<u8>, qrcode::types::QrError> {
+        let mut content = String::new();
+        let mut y = PAGE_HEIGHT - MARGIN;
+        text(&mut content, 18.0, MARGIN, y, "Shared Secrets - key share");
+        y -= 36.0;
+        for line in self.description() {
+            text(&mut content, 11.0, MARGIN, y, &line);
+            y -= 16.0;
+        }
+        y -= 12.0;
+        for line in self.fragment.armor().lines() {
+            text(&mut content, 9.0, MARGIN, y, line);
+            y -= 12.0;
+        }
+        qr(
+            &mut content,
+            &QrCode::new(self.fragment.to_line().as_bytes())?,
+            (PAGE_WIDTH - QR_SIZE) / 2.0,
+            MARGIN,
+        );
+        Ok(pdf(&content))
+    }
+
+    // lines describing the scheme and how to recover the key
+    fn description(&self) -> Vec<String> {
+        let mut lines = Vec::new();
+        if let Some(holder) = &self.fragment.holder {
+            lines.push(format!("Holder: {}", holder));
+        }
+        lines.push(format!("Encrypted file: {}", self.file_name));
+        lines.push(format!(
+            "Scheme: {} of {} shares are needed to decrypt",
+            self.threshold, self.total
+        ));
+        lines.push(String::new());
+        lines.push("To recover the file, gather at least the required number".into());
+        lines.push("of shares, copy the line between the BEGIN and END markers".into());
+        lines.push("of each one (or scan its QR code) into a shares file, one".into());
+        lines.push("share per line, and run:".into());
+        lines.push(format!(
+            "    shared_secrets d {} <shares file>",
+            self.file_name
+        ));
+        lines.push("Keep this sheet sealed and in a safe place.".into());
+        lines
+    }
+}
+
+// appends the operators to draw a line of text
+fn text(content: &mut String, size: f64, x: f64, y: f64, line: &str) {
+    let escaped = line
+        .replace('\\', "\\\\")
+        .replace('(', "\\(")
+        .replace(')', "\\)");
+    content.push_str(&format!(
+        "BT /F1 {} Tf {} {} Td ({}) Tj ET\n",
+        size, x, y, escaped
+    ));
+}
+
+// appends the operators to draw a QR code with its lower left corner at (x, y)
+fn qr(content: &mut String, code: &QrCode, x: f64, y: f64) {
+    let width = code.width();
+    let module = QR_SIZE / width as f64;
+    content.push_str("0 0 0 rg\n");
+    for row in 0..width {
+        for column in 0..width {
+            if code[(column, row)] == Color::Dark {
+                content.push_str(&format!(
+                    "{:.3} {:.3} {:.3} {:.3} re\n",
+                    x + column as f64 * module,
+                    y + (width - row - 1) as f64 * module,
+                    module,
+                    module
+                ));
+            }
+        }
+    }
+    content.push_str("f\n");
+}
+
+// builds a one page PDF document with the given content stream
+fn pdf(content: &str) -> Vec<u8> {
+    let objects = [
+        "<< /Type /Catalog /Pages 2 0 R >>".to_string(),
+        "<< /Type /Pages /Kids [3 0 R] /Count 1 >>".to_string(),
+        format!(
+            "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {} {}] \
+             /Contents 4 0 R /Resources << /Font << /F1 5 0 R >> >> >>",
+            PAGE_WIDTH, PAGE_HEIGHT
+        ),
+        format!(
+            "<< /Length {} >>\nstream\n{}endstream",
+            content.len(),
+            content
+        ),
+        "<< /Type /Font /Subtype /Type1 /BaseFont /Courier >>".to_string(),
+    ];
+    let mut document = b"%PDF-1.4\n".to_vec();
+    let mut offsets = Vec::with_capacity(objects.len());
+    for (i, object) in objects.iter().enumerate() {
+        offsets.push(document.len());
+        document.extend_from_slice(format!("{} 0 obj\n{}\nendobj\n", i + 1, object).as_bytes());
+    }
+    let xref = document.len();
+    document.extend_from_slice(format!("xref\n0 {}\n", objects.len() + 1).as_bytes());
+    document.extend_from_slice(b"0000000000 65535 f \n");
+    for offset in offsets {
+        document.extend_from_slice(format!("{:010} 00000 n \n", offset).as_bytes());
+    }
+    document.extend_from_slice(
+        format!(
+            "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n",
+            objects.len() + 1,
+            xref
+        )
+        .as_bytes(),
+    );
+    document
+}
+
+/// Writes a paper backup sheet to the given writer.
+///
+/// # Parameters
+///
+/// - sheet: The sheet to render.
+/// - writer: The writer where the PDF document is written.
+///
+/// # Errors
+///
+/// This method returns an error if the sheet cannot be rendered or
+/// if an error occurs while writing.
+pub fn write_sheet(sheet: &Sheet, writer: &mut impl Write) -> io::Result<()> {
+    let document = sheet
+        .render()
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+    writer.write_all(&document)
+}
+
+/////////////////////////////////
+// Unit testing of the module. //
+/////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::Share;
+
+    fn sheet_bytes() -> Vec<u8> {
+        let fragment = Fragment {
+            share: Share("1a2b3c".into(), "4d5e6f".into()),
+            holder: Some("alice (sales)".into()),
+            scheme: None,
+            field: None,
+            length: None,
+            mac: None,
+            pin: None,
+        };
+        let sheet = Sheet {
+            fragment: &fragment,
+            file_name: "msg.aes",
+            threshold: 2,
+            total: 3,
+        };
+        sheet.render().unwrap()
+    }
+
+    #[test]
+    fn sheet_is_pdf() {
+        let document = sheet_bytes();
+        assert!(document.starts_with(b"%PDF-1.4\n"));
+        assert!(document.ends_with(b"%%EOF\n"));
+    }
+
+    #[test]
+    fn sheet_xref_offset() {
+        let document = String::from_utf8(sheet_bytes()).unwrap();
+        let start = document.rfind("startxref\n").unwrap() + "startxref\n".len();
+        let offset: usize = document[start..].lines().next().unwrap().parse().unwrap();
+        assert!(document[offset..].starts_with("xref\n"));
+    }
+
+    #[test]
+    fn sheet_escapes_text() {
+        let document = String::from_utf8(sheet_bytes()).unwrap();
+        assert!(document.contains("(Holder: alice \\(sales\\))"));
+        assert!(document.contains("1a2b3c:4d5e6f:holder=alice \\(sales\\)"));
+    }
+}
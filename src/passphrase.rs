@@ -0,0 +1,293 @@
+use std::error::Error;
+
+use sha2::{Digest, Sha256};
+use unicode_normalization::UnicodeNormalization;
+
+use crate::crypto::shamir::{self, Share};
+use crate::encoding::to_hex;
+use crate::error::{ArgumentError, CorruptFileError};
+
+/// Maximum length in bytes of a passphrase, once normalized.
+pub const MAX_PASSPHRASE_LEN: usize = 256;
+
+// Size in bytes of the blocks of the passphrase split separately,
+// the largest that is always smaller than the prime
+const BLOCK_SIZE: usize = 32;
+
+// Number of base 36 digits of a value of the field
+const VALUE_DIGITS: usize = 50;
+
+// Number of characters in each group of a share
+const GROUP_LEN: usize = 5;
+
+// Number of bytes of the checksum of a share
+const CHECKSUM_SIZE: usize = 2;
+
+/// Normalizes a passphrase, so the same passphrase typed in other
+/// keyboards or systems gives the same bytes.
+///
+/// The passphrase is decomposed with NFKD, the whitespace at both
+/// ends is removed and the whitespace between words is replaced
+/// by a single space.
+///
+/// # Parameters
+///
+/// - passphrase: The passphrase as typed.
+pub fn normalize(passphrase: &str) -> String {
+    passphrase
+        .nfkd()
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Splits a passphrase into shares meant to be written down by hand.
+///
+/// Each share is a line of words: the index of the share, groups of
+/// five characters and a checksum that detects typos. Passphrases
+/// longer than 32 bytes are split in blocks of 32 bytes, each shared
+/// separately at the same indices.
+///
+/// # Parameters
+///
+/// - passphrase: The passphrase, which is normalized first.
+/// - n: The total number of shares to return.
+/// - k: The minimum number of shares to recover the passphrase.
+///
+/// # Returns
+///
+/// The n shares, the share with index i at position i - 1.
+///
+/// # Errors
+///
+/// An ArgumentError if the passphrase is empty, too long or has
+/// null characters, if n is greater than MAX_SHARES or if k is not
+/// in the range 0 < k <= n.
+pub fn split(passphrase: &str, n: usize, k: usize) -> Result<Vec<String>, ArgumentError> {
+    let passphrase = normalize(passphrase);
+    if passphrase.is_empty() {
+        return Err(ArgumentError("the passphrase is empty".into()));
+    }
+    if passphrase.contains('\0') {
+        return Err(ArgumentError(
+            "the passphrase cannot have null characters".into(),
+        ));
+    }
+    if passphrase.len() > MAX_PASSPHRASE_LEN {
+        return Err(ArgumentError(format!(
+            "the passphrase cannot be longer than {} bytes",
+            MAX_PASSPHRASE_LEN
+        )));
+    }
+    if k == 0 || k > n {
+        return Err(ArgumentError(
+            "K must be greater than 0 and not greater than N".into(),
+        ));
+    }
+    if n > shamir::MAX_SHARES {
+        return Err(ArgumentError(format!(
+            "N must not be greater than {}",
+            shamir::MAX_SHARES
+        )));
+    }
+    let mut bodies = vec![String::new(); n];
+    // no block starts with a zero byte, so none is shortened
+    for block in passphrase.as_bytes().chunks(BLOCK_SIZE) {
+        for (body, Share(_, y)) in bodies.iter_mut().zip(
+            shamir::split_secret_indexed(block, n, k).map_err(|e| ArgumentError(e.to_string()))?,
+        ) {
+            body.push_str(&format!("{:0>1$}", y.to_uppercase(), VALUE_DIGITS));
+        }
+    }
+    Ok(bodies
+        .iter()
+        .enumerate()
+        .map(|(i, body)| format_share(i + 1, body))
+        .collect())
+}
+
+/// Recovers a passphrase from the shares returned by split.
+///
+/// # Parameters
+///
+/// - shares: At least k of the shares, in any order.
+///
+/// # Returns
+///
+/// The normalized passphrase.
+///
+/// # Errors
+///
+/// A CorruptFileError if a share is malformed or has a typo, or an
+/// ArgumentError if the shares are not of the same passphrase or
+/// are not enough to recover it.
+pub fn combine(shares: &[&str]) -> Result<String, Box<dyn Error>> {
+    let shares = shares
+        .iter()
+        .map(|share| parse_share(share))
+        .collect::<Result<Vec<_>, _>>()?;
+    let blocks = match shares.first() {
+        Some((_, body)) => body.len() / VALUE_DIGITS,
+        None => return Err(Box::new(ArgumentError("no shares were given".into()))),
+    };
+    for (i, (index, body)) in shares.iter().enumerate() {
+        if body.len() != blocks * VALUE_DIGITS {
+            return Err(Box::new(ArgumentError(format!(
+                "share {} is not of the same passphrase",
+                index
+            ))));
+        }
+        if shares[..i].iter().any(|(other, _)| other == index) {
+            return Err(Box::new(ArgumentError(format!(
+                "share {} was given twice",
+                index
+            ))));
+        }
+    }
+    let mut passphrase = Vec::new();
+    for block in 0..blocks {
+        let values = shares.iter().map(|(index, body)| {
+            let value = &body[block * VALUE_DIGITS..(block + 1) * VALUE_DIGITS];
+            Share(to_radix_36(*index), value.to_lowercase())
+        });
+        passphrase.extend(shamir::recover_secret(values, None)?);
+    }
+    // too few shares recover random bytes instead of the passphrase
+    let not_enough = || ArgumentError("the shares given are not enough".into());
+    let passphrase = String::from_utf8(passphrase).map_err(|_| not_enough())?;
+    if passphrase != normalize(&passphrase) || passphrase.contains('\0') {
+        return Err(Box::new(not_enough()));
+    }
+    Ok(passphrase)
+}
+
+// writes a share as its index, the groups of its body and its checksum
+fn format_share(index: usize, body: &str) -> String {
+    let mut words = vec![index.to_string()];
+    for group in body.as_bytes().chunks(GROUP_LEN) {
+        words.push(String::from_utf8(group.to_vec()).unwrap());
+    }
+    words.push(checksum(index, body));
+    words.join(" ")
+}
+
+// reads the index and the body of a share, checking its checksum
+fn parse_share(share: &str) -> Result<(usize, String), CorruptFileError> {
+    let corrupt = || CorruptFileError(format!("'{}' is not a share", share.trim()));
+    let words: Vec<&str> = share.split_whitespace().collect();
+    if words.len() < 3 {
+        return Err(corrupt());
+    }
+    let index: usize = words[0].parse().map_err(|_| corrupt())?;
+    let body = words[1..words.len() - 1].concat().to_uppercase();
+    if index == 0
+        || body.len() % VALUE_DIGITS != 0
+        || !body.chars().all(|c| c.is_ascii_alphanumeric())
+    {
+        return Err(corrupt());
+    }
+    if !words[words.len() - 1].eq_ignore_ascii_case(&checksum(index, &body)) {
+        return Err(CorruptFileError(format!(
+            "share {} has a typo, check every group",
+            index
+        )));
+    }
+    Ok((index, body))
+}
+
+// the first bytes of the SHA-256 of the index and body, in hexadecimal
+fn checksum(index: usize, body: &str) -> String {
+    let digest = Sha256::digest(format!("{}:{}", index, body).as_bytes());
+    to_hex(&digest[..CHECKSUM_SIZE]).to_uppercase()
+}
+
+// writes a number in base 36, as the first element of a share
+fn to_radix_36(mut n: usize) -> String {
+    let mut digits = Vec::new();
+    loop {
+        digits.push(std::char::from_digit((n % 36) as u32, 36).unwrap());
+        n /= 36;
+        if n == 0 {
+            break;
+        }
+    }
+    digits.iter().rev().collect()
+}
+
+/////////////////////////////////
+// Unit testing of the module. //
+/////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SEED: &str = "abandon ability able about above absent absorb abstract \
+        absurd abuse access accident";
+
+    #[test]
+    fn normalization() {
+        assert_eq!(
+            normalize("  correct\thorse \n battery "),
+            "correct horse battery"
+        );
+        assert_eq!(normalize("contraseña"), "contrasen\u{303}a");
+        assert_eq!(normalize("Ångström"), "A\u{30a}ngstro\u{308}m");
+        assert_eq!(
+            normalize("ｐａｓｓ\u{3000}word\u{a0}½"),
+            "pass word 1\u{2044}2"
+        );
+        // beyond the Latin letters too
+        assert_eq!(normalize("パスワード"), "ハ\u{309a}スワート\u{3099}");
+    }
+
+    #[test]
+    fn split_and_combine() {
+        let shares = split(SEED, 5, 3).unwrap();
+        assert_eq!(shares.len(), 5);
+        assert!(shares[0].starts_with("1 "));
+        let some: Vec<&str> = vec![&shares[4], &shares[1], &shares[2]];
+        assert_eq!(combine(&some).unwrap(), normalize(SEED));
+        let lowercase = shares[3].to_lowercase();
+        let some: Vec<&str> = vec![&shares[0], &lowercase, &shares[2]];
+        assert_eq!(combine(&some).unwrap(), normalize(SEED));
+        assert!(combine(&[&shares[0], &shares[1]]).is_err());
+        assert!(combine(&[&shares[0], &shares[0], &shares[1]]).is_err());
+    }
+
+    #[test]
+    fn short_passphrase() {
+        let shares = split(" contraseña ", 3, 2).unwrap();
+        assert_eq!(shares[0].split_whitespace().count(), 1 + 10 + 1);
+        let some: Vec<&str> = vec![&shares[2], &shares[0]];
+        assert_eq!(combine(&some).unwrap(), "contrasen\u{303}a");
+    }
+
+    #[test]
+    fn typos() {
+        let shares = split("correct horse battery staple", 3, 2).unwrap();
+        let mut words: Vec<String> = shares[1].split(' ').map(String::from).collect();
+        words[3] = if words[3].starts_with('Z') { "0" } else { "Z" }.to_string() + &words[3][1..];
+        let typo = words.join(" ");
+        let error = combine(&[&shares[0], &typo]).unwrap_err();
+        assert!(error.to_string().contains("typo"));
+        assert!(combine(&[&shares[0], "1 2 3"]).is_err());
+    }
+
+    #[test]
+    fn invalid_passphrases() {
+        assert!(split("   ", 3, 2).is_err());
+        assert!(split(&"a".repeat(MAX_PASSPHRASE_LEN + 1), 3, 2).is_err());
+        assert!(split("passphrase", 3, 4).is_err());
+        assert!(split("passphrase", shamir::MAX_SHARES + 1, 2).is_err());
+    }
+
+    #[test]
+    fn radix_36() {
+        assert_eq!(to_radix_36(0), "0");
+        assert_eq!(to_radix_36(9), "9");
+        assert_eq!(to_radix_36(36), "10");
+        assert_eq!(to_radix_36(71), "1z");
+    }
+}
@@ -0,0 +1,236 @@
+use subtle::ConstantTimeEq;
+use zeroize::Zeroize;
+
+use crate::encoding::{from_hex, to_hex};
+use crate::error::{ArgumentError, CorruptFileError};
+use crate::fragments::Fragment;
+use crate::math::scrypt::{self, Params};
+
+/// Minimum number of digits of a PIN.
+pub const MIN_PIN_LEN: usize = 4;
+
+/// Maximum number of digits of a PIN.
+pub const MAX_PIN_LEN: usize = 12;
+
+// Cost of the derivation of the mask of a share, 32 MiB of memory
+const PARAMS: Params = Params {
+    log_n: 15,
+    r: 8,
+    p: 1,
+};
+
+// Number of bytes of the random salt of each share
+const SALT_SIZE: usize = 16;
+
+// Number of bytes of the check that tells a wrong PIN, short so it
+// only discards a few PINs in a search
+const CHECK_SIZE: usize = 2;
+
+// Separator of the values of the pin attribute of a fragment
+const SEPARATOR: char = '.';
+
+/// Checks that a PIN has only digits and a valid length.
+///
+/// # Parameters
+///
+/// - pin: The PIN to check.
+///
+/// # Returns
+///
+/// True if the PIN has between MIN_PIN_LEN and MAX_PIN_LEN
+/// decimal digits.
+pub fn is_valid_pin(pin: &str) -> bool {
+    (MIN_PIN_LEN..=MAX_PIN_LEN).contains(&pin.len()) && pin.bytes().all(|b| b.is_ascii_digit())
+}
+
+/// Protects the share of a fragment with a PIN, for shares kept on
+/// a personal device, where a full passphrase would be too much.
+///
+/// The hexadecimal digits of the value of the share are masked with
+/// a key derived from the PIN and a random salt with scrypt, and the
+/// cost of scrypt, the salt and a short check of the key are kept in
+/// the pin attribute of the fragment. Anyone who gets the fragment can
+/// still try every PIN, each try taking the time and memory of scrypt,
+/// so the PIN only slows down the use of a stolen share.
+///
+/// # Parameters
+///
+/// - fragment: The fragment to protect, without PIN.
+/// - pin: The PIN, see is_valid_pin.
+///
+/// # Returns
+///
+/// The fragment with the masked share.
+///
+/// # Errors
+///
+/// An ArgumentError if the PIN is not valid, the fragment already has
+/// a PIN or the salt cannot be generated.
+pub fn protect(fragment: &Fragment, pin: &str) -> Result<Fragment, ArgumentError> {
+    protect_with(fragment, pin, PARAMS)
+}
+
+/// Removes the PIN of a fragment protected by protect.
+///
+/// # Parameters
+///
+/// - fragment: The protected fragment.
+/// - pin: The PIN of the fragment.
+///
+/// # Returns
+///
+/// The fragment with its original share and without PIN.
+///
+/// # Errors
+///
+/// A CorruptFileError if the pin attribute of the fragment is invalid
+/// or the fragment has no PIN, or an ArgumentError if the PIN is wrong.
+pub fn unprotect(fragment: &Fragment, pin: &str) -> Result<Fragment, Box<dyn std::error::Error>> {
+    let attribute = fragment
+        .pin
+        .as_deref()
+        .ok_or_else(|| CorruptFileError("the share has no PIN".into()))?;
+    let (params, salt, check) =
+        parse_attribute(attribute).ok_or_else(|| CorruptFileError("invalid pin".into()))?;
+    let (mut derived_check, mut key) = derive(pin, &salt, params, &fragment.share.1);
+    let matches: bool = derived_check.ct_eq(&check).into();
+    derived_check.zeroize();
+    if !matches {
+        key.zeroize();
+        return Err(Box::new(ArgumentError(match &fragment.holder {
+            Some(holder) => format!("wrong PIN for the share of {}", holder),
+            None => format!("wrong PIN for share {}", fragment.share.0),
+        })));
+    }
+    let mut unprotected = fragment.clone();
+    unprotected.share.1 = mask(&fragment.share.1, &key);
+    unprotected.pin = None;
+    key.zeroize();
+    Ok(unprotected)
+}
+
+// protects a fragment with the cost given
+fn protect_with(fragment: &Fragment, pin: &str, params: Params) -> Result<Fragment, ArgumentError> {
+    if !is_valid_pin(pin) {
+        return Err(ArgumentError(format!(
+            "a PIN must have between {} and {} digits",
+            MIN_PIN_LEN, MAX_PIN_LEN
+        )));
+    }
+    if fragment.pin.is_some() {
+        return Err(ArgumentError("the share already has a PIN".into()));
+    }
+    let mut salt = [0u8; SALT_SIZE];
+    getrandom::getrandom(&mut salt)
+        .map_err(|_| ArgumentError("Error while generating the salt of a PIN".into()))?;
+    let (check, mut key) = derive(pin, &salt, params, &fragment.share.1);
+    let mut protected = fragment.clone();
+    protected.share.1 = mask(&fragment.share.1, &key);
+    protected.pin = Some(format!(
+        "{}{}{}{}{}",
+        params.log_n,
+        SEPARATOR,
+        to_hex(&salt),
+        SEPARATOR,
+        to_hex(&check)
+    ));
+    key.zeroize();
+    Ok(protected)
+}
+
+// derives from the PIN the check and the key that masks the digits
+// of the value of a share
+fn derive(pin: &str, salt: &[u8], params: Params, value: &str) -> (Vec<u8>, Vec<u8>) {
+    let digits = value.chars().filter(char::is_ascii_hexdigit).count();
+    let mut output = vec![0u8; CHECK_SIZE + digits.div_ceil(2)];
+    scrypt::scrypt(pin.as_bytes(), salt, params, &mut output);
+    let key = output.split_off(CHECK_SIZE);
+    (output, key)
+}
+
+// masks each hexadecimal digit of a value with a half byte of the
+// key, keeping the other characters, so it is its own inverse on
+// the lowercase values the schemes write
+fn mask(value: &str, key: &[u8]) -> String {
+    let mut nibbles = key.iter().flat_map(|byte| [byte >> 4, byte & 0xf]);
+    value
+        .chars()
+        .map(|c| match c.to_digit(16) {
+            Some(digit) => {
+                let masked = digit as u8 ^ nibbles.next().unwrap();
+                std::char::from_digit(u32::from(masked), 16).unwrap()
+            }
+            None => c,
+        })
+        .collect()
+}
+
+// the cost, the salt and the check of the pin attribute of a fragment
+fn parse_attribute(attribute: &str) -> Option<(Params, Vec<u8>, Vec<u8>)> {
+    let mut values = attribute.split(SEPARATOR);
+    let log_n: u8 = values.next()?.parse().ok()?;
+    let salt = from_hex(values.next()?)?;
+    let check = from_hex(values.next()?)?;
+    // the cost of a share written by protect is at most PARAMS
+    if log_n > PARAMS.log_n || check.len() != CHECK_SIZE || values.next().is_some() {
+        return None;
+    }
+    let params = Params { log_n, ..PARAMS };
+    Some((params, salt, check))
+}
+
+/////////////////////////////////
+// Unit testing of the module. //
+/////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a cheap cost, so the tests run fast
+    const TEST_PARAMS: Params = Params {
+        log_n: 4,
+        r: 8,
+        p: 1,
+    };
+
+    #[test]
+    fn valid_pins() {
+        assert!(is_valid_pin("1234"));
+        assert!(is_valid_pin("000000000000"));
+        assert!(!is_valid_pin("123"));
+        assert!(!is_valid_pin("1234567890123"));
+        assert!(!is_valid_pin("12a4"));
+        assert!(!is_valid_pin("１２３４"));
+    }
+
+    #[test]
+    fn masking() {
+        let key = [0x5a, 0xf0, 0x0f];
+        assert_eq!(mask("12.abc", &key), "48.5bc");
+        assert_eq!(mask(&mask("12.abc", &key), &key), "12.abc");
+    }
+
+    #[test]
+    fn protect_and_unprotect() {
+        let fragment = Fragment::parse("3:1f2e.3d4c:holder=alice:len=32:mac=00ff").unwrap();
+        let protected = protect_with(&fragment, "2468", TEST_PARAMS).unwrap();
+        assert_ne!(protected.share.1, fragment.share.1);
+        assert_eq!(protected.share.1.len(), fragment.share.1.len());
+        assert_eq!(protected.share.0, fragment.share.0);
+        let line = protected.to_line();
+        assert!(line.contains(":pin=4."));
+        let parsed = Fragment::parse(&line).unwrap();
+        assert_eq!(parsed, protected);
+        assert_eq!(unprotect(&parsed, "2468").unwrap(), fragment);
+        let wrong = unprotect(&parsed, "2469").unwrap_err();
+        assert_eq!(wrong.to_string(), "wrong PIN for the share of alice");
+
+        assert!(protect_with(&fragment, "12", TEST_PARAMS).is_err());
+        assert!(protect_with(&protected, "2468", TEST_PARAMS).is_err());
+        assert!(unprotect(&fragment, "2468").is_err());
+        let mut corrupt = protected.clone();
+        corrupt.pin = Some("99.00.0000".into());
+        assert!(unprotect(&corrupt, "2468").is_err());
+    }
+}
@@ -0,0 +1,419 @@
+use std::error::Error;
+use std::fmt;
+
+use crate::crypto::shamir;
+use crate::crypto::Share;
+use crate::error::ArgumentError;
+use crate::fragments;
+
+/// An access structure: the groups of holders that can
+/// recover a secret together.
+///
+/// A policy is written like `(2 of alice,bob,carol) and (1 of hsm,offline)`,
+/// where `N of a,b,...` needs N of the policies listed, `and` needs
+/// every policy joined and `or` any of them. Each threshold is a
+/// Shamir instance whose shares are split again by the thresholds
+/// below it, so every holder receives a single share.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Policy {
+    /// A person who receives a share.
+    Holder(String),
+    /// At least the given number of the policies.
+    Threshold(usize, Vec<Policy>),
+}
+
+impl Policy {
+    /// Parses a policy.
+    ///
+    /// `and` binds tighter than `or`, and the policies listed after
+    /// `N of` are holders or policies between parentheses.
+    ///
+    /// # Parameters
+    ///
+    /// - text: The policy to parse.
+    ///
+    /// # Returns
+    ///
+    /// The policy, always a threshold even if the text names
+    /// a single holder.
+    ///
+    /// # Errors
+    ///
+    /// An ArgumentError if the text is not a policy, if a threshold
+    /// is out of range or if a holder is named twice.
+    pub fn parse(text: &str) -> Result<Self, ArgumentError> {
+        let tokens = tokenize(text);
+        let mut parser = Parser { tokens, next: 0 };
+        let policy = parser.expression()?;
+        if let Some(token) = parser.peek() {
+            return Err(ArgumentError(format!(
+                "unexpected '{}' in the policy",
+                token
+            )));
+        }
+        let policy = match policy {
+            Policy::Holder(_) => Policy::Threshold(1, vec![policy]),
+            policy => policy,
+        };
+        let holders = policy.holders();
+        for (i, holder) in holders.iter().enumerate() {
+            if holders[..i].contains(holder) {
+                return Err(ArgumentError(format!(
+                    "holder '{}' appears twice in the policy",
+                    holder
+                )));
+            }
+        }
+        Ok(policy)
+    }
+
+    /// Returns the holders of this policy, in the order
+    /// they are written.
+    pub fn holders(&self) -> Vec<&str> {
+        match self {
+            Policy::Holder(name) => vec![name.as_str()],
+            Policy::Threshold(_, policies) => policies.iter().flat_map(Policy::holders).collect(),
+        }
+    }
+
+    /// Returns the smallest number of holders that can
+    /// recover a secret split with this policy.
+    pub fn min_holders(&self) -> usize {
+        match self {
+            Policy::Holder(_) => 1,
+            Policy::Threshold(threshold, policies) => {
+                let mut mins: Vec<usize> = policies.iter().map(Policy::min_holders).collect();
+                mins.sort_unstable();
+                mins[..*threshold].iter().sum()
+            }
+        }
+    }
+
+    /// Splits a secret according to this policy.
+    ///
+    /// The first element of each share is the path from the top
+    /// of the policy to the holder, the first elements of the
+    /// shares of every threshold separated by `/`.
+    ///
+    /// # Parameters
+    ///
+    /// - secret: The secret to share.
+    ///
+    /// # Returns
+    ///
+    /// The share of each holder, together with their name.
+    pub fn split(&self, secret: &[u8]) -> Vec<(String, Share)> {
+        let mut shares = Vec::new();
+        self.split_value(&shamir::secret_to_value(secret), "", &mut shares);
+        shares
+    }
+
+    // splits a value among the policies of a threshold, the path
+    // being the one of the share the value comes from
+    fn split_value(&self, value: &str, path: &str, shares: &mut Vec<(String, Share)>) {
+        match self {
+            Policy::Holder(name) => shares.push((name.clone(), Share(path.into(), value.into()))),
+            Policy::Threshold(threshold, policies) => {
+                // the values are generated here, so they always parse
+                let values = shamir::split_value(value, policies.len(), *threshold).unwrap();
+                for (policy, Share(x, y)) in policies.iter().zip(values) {
+                    let path = if path.is_empty() {
+                        x
+                    } else {
+                        format!("{}/{}", path, x)
+                    };
+                    policy.split_value(&y, &path, shares);
+                }
+            }
+        }
+    }
+
+    /// Recovers a secret split with this policy.
+    ///
+    /// # Parameters
+    ///
+    /// - shares: The shares given, with the name of their holder.
+    ///
+    /// # Returns
+    ///
+    /// A vector of bytes containing the original secret.
+    ///
+    /// # Errors
+    ///
+    /// An ArgumentError if the holders of the shares do not satisfy
+    /// the policy, or an error if it fails to parse the shares.
+    pub fn recover(&self, shares: &[(String, Share)]) -> Result<Vec<u8>, Box<dyn Error>> {
+        match self.recover_value(shares)? {
+            Some(Share(_, value)) => Ok(shamir::value_to_secret(&value)?),
+            None => Err(Box::new(ArgumentError(
+                "the holders of the shares given do not satisfy the policy".into(),
+            ))),
+        }
+    }
+
+    // recovers the value of this policy and the path of the share
+    // it came from, None if there are not enough shares
+    fn recover_value(&self, shares: &[(String, Share)]) -> Result<Option<Share>, Box<dyn Error>> {
+        match self {
+            Policy::Holder(name) => Ok(shares
+                .iter()
+                .find(|(holder, _)| holder == name)
+                .map(|(_, share)| share.clone())),
+            Policy::Threshold(threshold, policies) => {
+                let mut path = String::new();
+                let mut values = Vec::with_capacity(*threshold);
+                for policy in policies {
+                    if values.len() == *threshold {
+                        break;
+                    }
+                    if let Some(Share(child_path, value)) = policy.recover_value(shares)? {
+                        // the last step of the path is the x of the share
+                        let (parent, x) = match child_path.rfind('/') {
+                            Some(i) => (&child_path[..i], &child_path[i + 1..]),
+                            None => ("", &child_path[..]),
+                        };
+                        path = parent.to_string();
+                        values.push(Share(x.to_string(), value));
+                    }
+                }
+                if values.len() < *threshold {
+                    return Ok(None);
+                }
+                Ok(Some(Share(
+                    path,
+                    shamir::recover_value(values.into_iter())?,
+                )))
+            }
+        }
+    }
+}
+
+impl fmt::Display for Policy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Policy::Holder(name) => write!(f, "{}", name),
+            Policy::Threshold(threshold, policies) => {
+                write!(f, "{} of ", threshold)?;
+                for (i, policy) in policies.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    match policy {
+                        Policy::Holder(_) => write!(f, "{}", policy)?,
+                        Policy::Threshold(..) => write!(f, "({})", policy)?,
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+// splits the text in names, keywords, commas and parentheses
+fn tokenize(text: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut token = String::new();
+    for c in text.chars() {
+        if c.is_whitespace() || c == '(' || c == ')' || c == ',' {
+            if !token.is_empty() {
+                tokens.push(std::mem::take(&mut token));
+            }
+            if !c.is_whitespace() {
+                tokens.push(c.to_string());
+            }
+        } else {
+            token.push(c);
+        }
+    }
+    if !token.is_empty() {
+        tokens.push(token);
+    }
+    tokens
+}
+
+// A recursive descent parser over the tokens of a policy
+struct Parser {
+    tokens: Vec<String>,
+    next: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&str> {
+        self.tokens.get(self.next).map(String::as_str)
+    }
+
+    fn advance(&mut self) -> Option<&str> {
+        self.next += 1;
+        self.tokens.get(self.next - 1).map(String::as_str)
+    }
+
+    // expression := conjunction ("or" conjunction)*
+    fn expression(&mut self) -> Result<Policy, ArgumentError> {
+        let mut policies = vec![self.conjunction()?];
+        while self.peek() == Some("or") {
+            self.advance();
+            policies.push(self.conjunction()?);
+        }
+        Ok(join(1, policies))
+    }
+
+    // conjunction := term ("and" term)*
+    fn conjunction(&mut self) -> Result<Policy, ArgumentError> {
+        let mut policies = vec![self.term()?];
+        while self.peek() == Some("and") {
+            self.advance();
+            policies.push(self.term()?);
+        }
+        let threshold = policies.len();
+        Ok(join(threshold, policies))
+    }
+
+    // term := "(" expression ")" | NUMBER "of" factor ("," factor)* | NAME
+    fn term(&mut self) -> Result<Policy, ArgumentError> {
+        let token = match self.peek() {
+            Some(token) => token.to_string(),
+            None => return Err(ArgumentError("the policy ends unexpectedly".into())),
+        };
+        if token.parse::<usize>().is_ok()
+            && self.tokens.get(self.next + 1).map(String::as_str) == Some("of")
+        {
+            let threshold: usize = token.parse().unwrap();
+            self.next += 2;
+            let mut policies = vec![self.factor()?];
+            while self.peek() == Some(",") {
+                self.advance();
+                policies.push(self.factor()?);
+            }
+            if threshold == 0 || threshold > policies.len() {
+                return Err(ArgumentError(format!(
+                    "the threshold {} must be greater than 0 and not greater than {}",
+                    threshold,
+                    policies.len()
+                )));
+            }
+            return Ok(Policy::Threshold(threshold, policies));
+        }
+        self.factor()
+    }
+
+    // factor := "(" expression ")" | NAME
+    fn factor(&mut self) -> Result<Policy, ArgumentError> {
+        match self.advance() {
+            Some("(") => {
+                let policy = self.expression()?;
+                match self.advance() {
+                    Some(")") => Ok(policy),
+                    _ => Err(ArgumentError("missing ')' in the policy".into())),
+                }
+            }
+            Some(token) if is_name(token) => Ok(Policy::Holder(token.to_string())),
+            Some(token) => Err(ArgumentError(format!(
+                "unexpected '{}' in the policy",
+                token
+            ))),
+            None => Err(ArgumentError("the policy ends unexpectedly".into())),
+        }
+    }
+}
+
+// joins policies into a threshold, unless there is a single one
+fn join(threshold: usize, mut policies: Vec<Policy>) -> Policy {
+    if policies.len() == 1 {
+        policies.pop().unwrap()
+    } else {
+        Policy::Threshold(threshold, policies)
+    }
+}
+
+// checks that a token can name a holder
+fn is_name(token: &str) -> bool {
+    !matches!(token, "(" | ")" | "," | "and" | "or" | "of") && fragments::is_valid_holder(token)
+}
+
+/////////////////////////////////
+// Unit testing of the module. //
+/////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn holder(name: &str) -> Policy {
+        Policy::Holder(name.into())
+    }
+
+    #[test]
+    fn parse_policies() {
+        let policy = Policy::parse("(2 of alice,bob,carol) and (1 of hsm,offline)").unwrap();
+        assert_eq!(
+            policy,
+            Policy::Threshold(
+                2,
+                vec![
+                    Policy::Threshold(2, vec![holder("alice"), holder("bob"), holder("carol")]),
+                    Policy::Threshold(1, vec![holder("hsm"), holder("offline")]),
+                ]
+            )
+        );
+        assert_eq!(
+            policy.holders(),
+            ["alice", "bob", "carol", "hsm", "offline"]
+        );
+        assert_eq!(policy.min_holders(), 3);
+        assert_eq!(Policy::parse(&policy.to_string()).unwrap(), policy);
+        assert_eq!(
+            Policy::parse("alice and bob or carol").unwrap(),
+            Policy::Threshold(
+                1,
+                vec![
+                    Policy::Threshold(2, vec![holder("alice"), holder("bob")]),
+                    holder("carol"),
+                ]
+            )
+        );
+        assert_eq!(
+            Policy::parse("alice").unwrap(),
+            Policy::Threshold(1, vec![holder("alice")])
+        );
+    }
+
+    #[test]
+    fn invalid_policies() {
+        assert!(Policy::parse("").is_err());
+        assert!(Policy::parse("(alice and bob").is_err());
+        assert!(Policy::parse("alice bob").is_err());
+        assert!(Policy::parse("0 of alice,bob").is_err());
+        assert!(Policy::parse("3 of alice,bob").is_err());
+        assert!(Policy::parse("alice and alice").is_err());
+        assert!(Policy::parse("alice and or").is_err());
+        assert!(Policy::parse("alice:x").is_err());
+    }
+
+    #[test]
+    fn split_and_recover() {
+        let policy = Policy::parse("(2 of alice,bob,carol) and (1 of hsm,offline)").unwrap();
+        let secret = vec![0x42u8; 32];
+        let shares = policy.split(&secret);
+        assert_eq!(shares.len(), 5);
+        let pick = |names: &[&str]| -> Vec<(String, Share)> {
+            shares
+                .iter()
+                .filter(|(holder, _)| names.contains(&holder.as_str()))
+                .cloned()
+                .collect()
+        };
+        assert_eq!(
+            policy
+                .recover(&pick(&["alice", "carol", "offline"]))
+                .unwrap(),
+            secret
+        );
+        assert_eq!(
+            policy.recover(&pick(&["bob", "carol", "hsm"])).unwrap(),
+            secret
+        );
+        assert_eq!(policy.recover(&shares).unwrap(), secret);
+        assert!(policy.recover(&pick(&["alice", "hsm", "offline"])).is_err());
+        assert!(policy.recover(&pick(&["alice", "bob", "carol"])).is_err());
+    }
+}
@@ -0,0 +1,177 @@
+use std::error::Error;
+use std::fs::OpenOptions;
+use std::io::Write;
+
+use crate::crypto::Share;
+use crate::encoding::to_hex;
+use crate::error::{ArgumentError, CorruptFileError};
+use crate::fragments::{self, Fragment};
+use crate::report::Json;
+
+/// Suffix of the public registry of the shares of an encryption.
+pub const EXTENSION: &str = ".registry.json";
+
+// Context of the commitments to the shares
+const COMMITMENT_CONTEXT: &str = "shared_secrets share commitment v1";
+
+/// Returns the commitment to a share, its BLAKE3 hash in a context
+/// of its own, which binds the registry to the share without
+/// revealing it.
+///
+/// Each share is committed to on its own, so the registry detects a
+/// share that was substituted after it was written, but not shares
+/// that were dealt inconsistent, off the polynomial of the others.
+/// Feldman or Pedersen commitments to the coefficients would detect
+/// those, but they need the shares to be split in the field of the
+/// order of a group where discrete logarithms are hard, and the
+/// fields the keys are split in are not.
+///
+/// # Parameters
+///
+/// - fragment: The share, as it is written in the shares file.
+pub fn commitment(fragment: &Fragment) -> String {
+    let Share(x, y) = &fragment.share;
+    let share = format!("{}:{}", x, y).to_lowercase();
+    to_hex(&blake3::derive_key(COMMITMENT_CONTEXT, share.as_bytes()))
+}
+
+/// Creates the public registry of the shares of an encryption: the
+/// scheme, and the index, holder, fingerprint and commitment of each
+/// share, but nothing secret, so it can be kept in version control.
+/// The shares are listed in the order of their indices.
+///
+/// # Parameters
+///
+/// - file_name: The name of the encrypted file.
+/// - threshold: The minimum number of shares that decrypt the file.
+/// - fragments: The shares of the file.
+pub fn new(file_name: &str, threshold: usize, fragments: &[Fragment]) -> Json {
+    let mut sorted: Vec<&Fragment> = fragments.iter().collect();
+    sorted.sort_by(|a, b| fragments::canonical_order(a, b));
+    let shares = sorted
+        .iter()
+        .map(|fragment| {
+            Json::object(vec![
+                ("index", fragment.share.0.as_str().into()),
+                ("holder", fragment.holder.clone().into()),
+                ("fingerprint", fragment.fingerprint().into()),
+                ("commitment", commitment(fragment).into()),
+            ])
+        })
+        .collect();
+    Json::object(vec![
+        ("tool", env!("CARGO_PKG_NAME").into()),
+        ("version", env!("CARGO_PKG_VERSION").into()),
+        ("file", file_name.into()),
+        ("threshold", threshold.into()),
+        ("total", fragments.len().into()),
+        (
+            "set_fingerprint",
+            fragments::set_fingerprint(fragments).into(),
+        ),
+        ("shares", Json::Array(shares)),
+    ])
+}
+
+/// Writes a registry created with new to a new file.
+///
+/// # Parameters
+///
+/// - path: The path of the registry, which must not exist.
+/// - registry: The registry.
+///
+/// # Errors
+///
+/// An io::Error if the file exists or cannot be written.
+pub fn write(path: &str, registry: &Json) -> Result<(), Box<dyn Error>> {
+    let mut file = OpenOptions::new().write(true).create_new(true).open(path)?;
+    writeln!(file, "{}", registry)?;
+    file.sync_all()?;
+    Ok(())
+}
+
+/// Checks that some shares are shares of the registry, each one
+/// with the index and commitment it was registered with.
+///
+/// # Parameters
+///
+/// - registry: The registry.
+/// - fragments: The shares to check.
+///
+/// # Returns
+///
+/// The holder of each share, or its index if it has no holder.
+///
+/// # Errors
+///
+/// A CorruptFileError if the registry is not valid, or an
+/// ArgumentError if a share is not in the registry or does not match
+/// its commitment.
+pub fn check(registry: &Json, fragments: &[Fragment]) -> Result<Vec<String>, Box<dyn Error>> {
+    let shares = match registry.get("shares") {
+        Some(Json::Array(shares)) => shares,
+        _ => return Err(Box::new(corrupt())),
+    };
+    let mut checked = Vec::new();
+    for fragment in fragments {
+        let x = fragment.share.0.as_str();
+        let entry = shares
+            .iter()
+            .find(|share| share.get("index").and_then(Json::as_str) == Some(x))
+            .ok_or_else(|| ArgumentError(format!("share {} is not in the registry", x)))?;
+        let expected = entry
+            .get("commitment")
+            .and_then(Json::as_str)
+            .ok_or_else(corrupt)?;
+        if !expected.eq_ignore_ascii_case(&commitment(fragment)) {
+            return Err(Box::new(ArgumentError(format!(
+                "share {} does not match the registry",
+                x
+            ))));
+        }
+        let holder = entry.get("holder").and_then(Json::as_str).unwrap_or(x);
+        checked.push(holder.to_string());
+    }
+    Ok(checked)
+}
+
+fn corrupt() -> CorruptFileError {
+    CorruptFileError("not a valid share registry".into())
+}
+
+/////////////////////////////////
+// Unit testing of the module. //
+/////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn registry() {
+        let fragments = vec![
+            Fragment::parse("1:abc:holder=alice").unwrap(),
+            Fragment::parse("2:def").unwrap(),
+            Fragment::parse("3:0a1").unwrap(),
+        ];
+        let registry = new("secret.aes", 2, &fragments);
+        assert_eq!(registry.get("total"), Some(&Json::Number(3)));
+        let share = registry.get("shares").unwrap().to_string();
+        assert!(share.starts_with(&format!(
+            "[{{\"index\":\"1\",\"holder\":\"alice\",\"fingerprint\":\"{}\",\"commitment\":\"{}\"}}",
+            fragments[0].fingerprint(),
+            commitment(&fragments[0])
+        )));
+        let registry = Json::parse(&registry.to_string()).unwrap();
+        assert_eq!(check(&registry, &fragments[..2]).unwrap(), ["alice", "2"]);
+        assert_eq!(
+            commitment(&Fragment::parse("1:ABC").unwrap()),
+            commitment(&fragments[0])
+        );
+        let forged = Fragment::parse("2:dee").unwrap();
+        assert!(check(&registry, &[forged]).is_err());
+        let unknown = Fragment::parse("4:def").unwrap();
+        assert!(check(&registry, &[unknown]).is_err());
+        assert!(check(&Json::parse("{}").unwrap(), &fragments).is_err());
+    }
+}
@@ -0,0 +1,590 @@
+use std::env;
+use std::error::Error;
+use std::fmt;
+use std::fmt::Write;
+use std::iter::Peekable;
+use std::str::Chars;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::encoding;
+use crate::error::CorruptFileError;
+
+/// A value of the JSON output of a command.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(u64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+    /// A JSON document already written, inserted as is.
+    Raw(String),
+}
+
+impl Json {
+    /// Returns an object with the given fields, in order.
+    ///
+    /// # Parameters
+    ///
+    /// - fields: The names and values of the fields.
+    pub fn object(fields: Vec<(&str, Json)>) -> Json {
+        Json::Object(
+            fields
+                .into_iter()
+                .map(|(name, value)| (name.to_string(), value))
+                .collect(),
+        )
+    }
+
+    /// Parses a JSON document, with numbers limited to unsigned
+    /// integers as in the output of the commands.
+    ///
+    /// # Parameters
+    ///
+    /// - text: The document.
+    ///
+    /// # Errors
+    ///
+    /// A CorruptFileError if the document is not valid JSON.
+    pub fn parse(text: &str) -> Result<Json, CorruptFileError> {
+        let mut parser = Parser {
+            chars: text.chars().peekable(),
+        };
+        let value = parser.value()?;
+        parser.skip_whitespace();
+        match parser.chars.next() {
+            None => Ok(value),
+            Some(_) => Err(invalid_json()),
+        }
+    }
+
+    /// Returns the value of a field of an object, None if the value
+    /// is not an object or has no such field.
+    ///
+    /// # Parameters
+    ///
+    /// - name: The name of the field.
+    pub fn get(&self, name: &str) -> Option<&Json> {
+        match self {
+            Json::Object(fields) => fields
+                .iter()
+                .find(|(field, _)| field == name)
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    /// Returns the string of a string value.
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::String(s) => Some(s),
+            _ => None,
+        }
+    }
+}
+
+// A recursive descent parser of JSON documents
+struct Parser<'a> {
+    chars: Peekable<Chars<'a>>,
+}
+
+impl Parser<'_> {
+    fn skip_whitespace(&mut self) {
+        while self.chars.next_if(|c| c.is_ascii_whitespace()).is_some() {}
+    }
+
+    // consumes the given characters
+    fn expect(&mut self, expected: &str) -> Result<(), CorruptFileError> {
+        for c in expected.chars() {
+            if self.chars.next() != Some(c) {
+                return Err(invalid_json());
+            }
+        }
+        Ok(())
+    }
+
+    fn value(&mut self) -> Result<Json, CorruptFileError> {
+        self.skip_whitespace();
+        match self.chars.peek().ok_or_else(invalid_json)? {
+            'n' => self.expect("null").map(|_| Json::Null),
+            't' => self.expect("true").map(|_| Json::Bool(true)),
+            'f' => self.expect("false").map(|_| Json::Bool(false)),
+            '"' => self.string().map(Json::String),
+            '[' => {
+                self.chars.next();
+                let mut values = Vec::new();
+                self.skip_whitespace();
+                if self.chars.next_if_eq(&']').is_none() {
+                    loop {
+                        values.push(self.value()?);
+                        self.skip_whitespace();
+                        match self.chars.next() {
+                            Some(',') => continue,
+                            Some(']') => break,
+                            _ => return Err(invalid_json()),
+                        }
+                    }
+                }
+                Ok(Json::Array(values))
+            }
+            '{' => {
+                self.chars.next();
+                let mut fields = Vec::new();
+                self.skip_whitespace();
+                if self.chars.next_if_eq(&'}').is_none() {
+                    loop {
+                        self.skip_whitespace();
+                        let name = self.string()?;
+                        self.skip_whitespace();
+                        self.expect(":")?;
+                        fields.push((name, self.value()?));
+                        self.skip_whitespace();
+                        match self.chars.next() {
+                            Some(',') => continue,
+                            Some('}') => break,
+                            _ => return Err(invalid_json()),
+                        }
+                    }
+                }
+                Ok(Json::Object(fields))
+            }
+            c if c.is_ascii_digit() => {
+                let mut digits = String::new();
+                while let Some(c) = self.chars.next_if(|c| c.is_ascii_digit()) {
+                    digits.push(c);
+                }
+                digits.parse().map(Json::Number).map_err(|_| invalid_json())
+            }
+            _ => Err(invalid_json()),
+        }
+    }
+
+    fn string(&mut self) -> Result<String, CorruptFileError> {
+        self.expect("\"")?;
+        let mut s = String::new();
+        loop {
+            match self.chars.next().ok_or_else(invalid_json)? {
+                '"' => return Ok(s),
+                '\\' => match self.chars.next().ok_or_else(invalid_json)? {
+                    'u' => {
+                        let hex: String = (0..4).filter_map(|_| self.chars.next()).collect();
+                        let code = encoding::from_hex(&hex)
+                            .filter(|code| code.len() == 2)
+                            .ok_or_else(invalid_json)?;
+                        let code = u16::from_be_bytes([code[0], code[1]]) as u32;
+                        s.push(char::from_u32(code).ok_or_else(invalid_json)?);
+                    }
+                    'n' => s.push('\n'),
+                    't' => s.push('\t'),
+                    'r' => s.push('\r'),
+                    c @ ('"' | '\\' | '/') => s.push(c),
+                    _ => return Err(invalid_json()),
+                },
+                c => s.push(c),
+            }
+        }
+    }
+}
+
+fn invalid_json() -> CorruptFileError {
+    CorruptFileError("invalid JSON document".into())
+}
+
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Json::Null => write!(f, "null"),
+            Json::Bool(b) => write!(f, "{}", b),
+            Json::Number(n) => write!(f, "{}", n),
+            Json::String(s) => write!(f, "{}", json_string(s)),
+            Json::Array(values) => {
+                let values: Vec<String> = values.iter().map(Json::to_string).collect();
+                write!(f, "[{}]", values.join(","))
+            }
+            Json::Object(fields) => {
+                let fields: Vec<String> = fields
+                    .iter()
+                    .map(|(name, value)| format!("{}:{}", json_string(name), value))
+                    .collect();
+                write!(f, "{{{}}}", fields.join(","))
+            }
+            Json::Raw(json) => write!(f, "{}", json.trim()),
+        }
+    }
+}
+
+impl From<bool> for Json {
+    fn from(b: bool) -> Json {
+        Json::Bool(b)
+    }
+}
+
+impl From<usize> for Json {
+    fn from(n: usize) -> Json {
+        Json::Number(n as u64)
+    }
+}
+
+impl From<&str> for Json {
+    fn from(s: &str) -> Json {
+        Json::String(s.to_string())
+    }
+}
+
+impl From<String> for Json {
+    fn from(s: String) -> Json {
+        Json::String(s)
+    }
+}
+
+impl<T: Into<Json>> From<Vec<T>> for Json {
+    fn from(values: Vec<T>) -> Json {
+        Json::Array(values.into_iter().map(Into::into).collect())
+    }
+}
+
+impl<T: Into<Json>> From<Option<T>> for Json {
+    fn from(value: Option<T>) -> Json {
+        value.map_or(Json::Null, Into::into)
+    }
+}
+
+// ANSI escape sequence that restores the default color
+const RESET: &str = "\x1b[0m";
+
+// Whether the warnings written to stderr are colored, set once by
+// the program, see set_color
+static COLOR: AtomicBool = AtomicBool::new(false);
+
+// Whether the output of the program is JSON, set once by the
+// program, see set_json
+static JSON: AtomicBool = AtomicBool::new(false);
+
+/// How a line of a report is presented in a terminal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tone {
+    /// Information, without color.
+    Plain,
+    /// Something that was done, in green.
+    Success,
+    /// Something unusual that did not stop the command, in yellow.
+    Warning,
+    /// Something that failed, in red.
+    Error,
+}
+
+impl Tone {
+    // the ANSI escape sequence of the color of the tone
+    fn color(self) -> Option<&'static str> {
+        match self {
+            Tone::Plain => None,
+            Tone::Success => Some("\x1b[32m"),
+            Tone::Warning => Some("\x1b[33m"),
+            Tone::Error => Some("\x1b[31m"),
+        }
+    }
+}
+
+/// Returns whether the output written to a stream is colored.
+///
+/// Colors are only used in terminals, and never when the NO_COLOR
+/// environment variable is set and not empty, see no-color.org.
+///
+/// # Parameters
+///
+/// - no_color: Whether the user disabled the colors with --no-color.
+/// - terminal: Whether the stream is a terminal.
+pub fn use_color(no_color: bool, terminal: bool) -> bool {
+    let disabled = env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+    !no_color && terminal && !disabled
+}
+
+/// Sets whether the warnings written to stderr are colored, see
+/// use_color; they are not until this is called.
+pub fn set_color(color: bool) {
+    COLOR.store(color, Ordering::Relaxed);
+}
+
+/// Returns a text in the color of a tone, or as is without color.
+///
+/// # Parameters
+///
+/// - tone: The tone of the text.
+/// - text: The text.
+/// - color: Whether the text is colored, see use_color.
+pub fn paint(tone: Tone, text: &str, color: bool) -> String {
+    match tone.color() {
+        Some(code) if color => format!("{}{}{}", code, text, RESET),
+        _ => text.to_string(),
+    }
+}
+
+/// Sets whether the output of the program is JSON, in which case
+/// the status messages are not written; it is not until this is
+/// called.
+pub fn set_json(json: bool) {
+    JSON.store(json, Ordering::Relaxed);
+}
+
+/// Writes a message to stderr about a command that goes on until it
+/// is stopped, such as a server, so it is seen before the report.
+/// Nothing is written with --json, whose report has the same facts.
+///
+/// # Parameters
+///
+/// - message: The message.
+pub fn status(message: impl fmt::Display) {
+    if !JSON.load(Ordering::Relaxed) {
+        eprintln!("{}", message);
+    }
+}
+
+/// Writes a warning to stderr, which is not part of the report, so
+/// it is seen even with --json.
+///
+/// # Parameters
+///
+/// - message: The warning, without the "warning: " prefix.
+pub fn warn(message: impl fmt::Display) {
+    let prefix = paint(Tone::Warning, "warning", COLOR.load(Ordering::Relaxed));
+    eprintln!("{}: {}", prefix, message);
+}
+
+/// The result of a command: the lines printed for people, and the
+/// fields of the JSON object printed instead with `--json`.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Report {
+    lines: Vec<(Tone, String)>,
+    // the lines written to stderr instead of stdout
+    notes: Vec<String>,
+    fields: Vec<(String, Json)>,
+}
+
+impl Report {
+    /// Creates an empty report.
+    pub fn new() -> Report {
+        Report::default()
+    }
+
+    /// Adds a line to the text of the report.
+    ///
+    /// # Parameters
+    ///
+    /// - line: The line, without the newline.
+    pub fn line(&mut self, line: impl Into<String>) {
+        self.lines.push((Tone::Plain, line.into()));
+    }
+
+    /// Adds a line that tells what the command did, such as the files
+    /// it wrote, shown in green in a terminal.
+    ///
+    /// # Parameters
+    ///
+    /// - line: The line, without the newline.
+    pub fn success(&mut self, line: impl Into<String>) {
+        self.lines.push((Tone::Success, line.into()));
+    }
+
+    /// Adds a line about something unusual, shown in yellow in a
+    /// terminal.
+    ///
+    /// # Parameters
+    ///
+    /// - line: The line, without the newline.
+    pub fn warning(&mut self, line: impl Into<String>) {
+        self.lines.push((Tone::Warning, line.into()));
+    }
+
+    /// Adds a line about something that failed, shown in red in a
+    /// terminal.
+    ///
+    /// # Parameters
+    ///
+    /// - line: The line, without the newline.
+    pub fn error(&mut self, line: impl Into<String>) {
+        self.lines.push((Tone::Error, line.into()));
+    }
+
+    /// Adds a line written to stderr instead of with the text of the
+    /// report, such as the fingerprints of the shares printed on
+    /// stdout, so stdout keeps only the output of the command. The
+    /// notes are not written with --json, so their facts must also
+    /// be in the fields.
+    ///
+    /// # Parameters
+    ///
+    /// - line: The line, without the newline.
+    pub fn note(&mut self, line: impl Into<String>) {
+        self.notes.push(line.into());
+    }
+
+    /// Returns the notes of the report, a line after another.
+    pub fn notes(&self) -> String {
+        self.notes
+            .iter()
+            .map(|line| format!("{}\n", line))
+            .collect()
+    }
+
+    /// Adds a field to the JSON object of the report.
+    ///
+    /// # Parameters
+    ///
+    /// - name: The name of the field.
+    /// - value: The value of the field.
+    pub fn field(&mut self, name: &str, value: impl Into<Json>) {
+        self.fields.push((name.to_string(), value.into()));
+    }
+
+    /// Returns the text of the report, a line after another.
+    pub fn text(&self) -> String {
+        self.render(false)
+    }
+
+    /// Returns the text of the report with each line in the color of
+    /// its tone, or without colors, as text does.
+    ///
+    /// # Parameters
+    ///
+    /// - color: Whether the lines are colored, see use_color.
+    pub fn render(&self, color: bool) -> String {
+        self.lines
+            .iter()
+            .map(|(tone, line)| format!("{}\n", paint(*tone, line, color)))
+            .collect()
+    }
+
+    /// Returns the report as a JSON object with the field ok set
+    /// to true followed by the fields of the report.
+    pub fn to_json(&self) -> String {
+        let mut fields = vec![("ok".to_string(), Json::Bool(true))];
+        fields.extend(self.fields.iter().cloned());
+        Json::Object(fields).to_string()
+    }
+}
+
+/// Returns an error as a JSON object with the field ok set to false
+/// and the message of the error in the field error.
+///
+/// # Parameters
+///
+/// - error: The error that stopped the command.
+pub fn error_json(error: &dyn Error) -> String {
+    Json::object(vec![
+        ("ok", Json::Bool(false)),
+        ("error", error.to_string().into()),
+    ])
+    .to_string()
+}
+
+/// Quotes a string, escaping the characters JSON requires.
+///
+/// # Parameters
+///
+/// - s: The string to quote.
+pub fn json_string(s: &str) -> String {
+    let mut json = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            c if (c as u32) < 0x20 => write!(json, "\\u{:04x}", c as u32).unwrap(),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+/////////////////////////////////
+// Unit testing of the module. //
+/////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ArgumentError;
+
+    #[test]
+    fn json_values() {
+        let value = Json::object(vec![
+            ("file", "a \"b\"\n".into()),
+            ("shares", vec![1usize, 2].into()),
+            ("holder", Option::<String>::None.into()),
+            ("vectors", Json::Raw("{\"x\":1}\n".into())),
+        ]);
+        assert_eq!(
+            value.to_string(),
+            "{\"file\":\"a \\\"b\\\"\\u000a\",\"shares\":[1,2],\"holder\":null,\"vectors\":{\"x\":1}}"
+        );
+    }
+
+    #[test]
+    fn parse_json() {
+        let value = Json::object(vec![
+            ("file", "a \"b\"\n".into()),
+            ("shares", vec![1usize, 2].into()),
+            ("holder", Option::<String>::None.into()),
+            ("empty", Json::Array(vec![])),
+            ("paper", true.into()),
+        ]);
+        assert_eq!(Json::parse(&value.to_string()).unwrap(), value);
+        let value = Json::parse(" { \"a\" : [ {} , \"\\u00e9\" ] }\n").unwrap();
+        assert_eq!(
+            value.get("a"),
+            Some(&Json::Array(vec![Json::Object(vec![]), "é".into()]))
+        );
+        assert_eq!(value.get("b"), None);
+        assert!(Json::parse("{\"a\":1,}").is_err());
+        assert!(Json::parse("[1] 2").is_err());
+        assert!(Json::parse("\"open").is_err());
+        assert!(Json::parse("-1").is_err());
+    }
+
+    #[test]
+    fn reports() {
+        let mut report = Report::new();
+        report.line("cipher: aes-gcm");
+        report.field("cipher", "aes-gcm");
+        report.field("digest", false);
+        assert_eq!(report.text(), "cipher: aes-gcm\n");
+        assert_eq!(
+            report.to_json(),
+            "{\"ok\":true,\"cipher\":\"aes-gcm\",\"digest\":false}"
+        );
+        assert_eq!(Report::new().text(), "");
+        report.success("wrote 5 shares (threshold 3) to ./out/");
+        report.warning("a copy of share 2");
+        assert_eq!(
+            report.text(),
+            "cipher: aes-gcm\nwrote 5 shares (threshold 3) to ./out/\na copy of share 2\n"
+        );
+        assert_eq!(
+            report.render(true),
+            "cipher: aes-gcm\n\x1b[32mwrote 5 shares (threshold 3) to ./out/\x1b[0m\n\
+             \x1b[33ma copy of share 2\x1b[0m\n"
+        );
+        assert_eq!(report.render(false), report.text());
+        report.note("fingerprint: 1a2b");
+        assert_eq!(report.notes(), "fingerprint: 1a2b\n");
+        assert!(!report.text().contains("fingerprint"));
+        assert_eq!(
+            error_json(&ArgumentError("N must be greater than 2".into())),
+            "{\"ok\":false,\"error\":\"N must be greater than 2\"}"
+        );
+    }
+
+    #[test]
+    fn colors() {
+        assert_eq!(paint(Tone::Error, "error", true), "\x1b[31merror\x1b[0m");
+        assert_eq!(paint(Tone::Error, "error", false), "error");
+        assert_eq!(paint(Tone::Plain, "cipher", true), "cipher");
+        assert!(!use_color(true, true));
+        assert!(!use_color(false, false));
+        assert_eq!(
+            use_color(false, true),
+            env::var_os("NO_COLOR").map_or(true, |v| v.is_empty())
+        );
+    }
+}
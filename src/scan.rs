@@ -0,0 +1,94 @@
+use std::error::Error;
+use std::io;
+use std::process::Command;
+
+use crate::error::{ArgumentError, CorruptFileError};
+use crate::fragments::{self, Fragment, ARMOR_BEGIN};
+
+// Program that decodes the QR codes of an image
+const ZBARIMG: &str = "zbarimg";
+
+/// Reads the fragments in the QR codes of an image, such as a
+/// photo of a paper backup.
+///
+/// The image is decoded with the zbarimg program of the zbar
+/// tools, which reads most image formats.
+///
+/// # Parameters
+///
+/// - path: The path of the image.
+///
+/// # Returns
+///
+/// A vector with the fragment of each QR code in the image.
+///
+/// # Errors
+///
+/// This method returns an error if zbarimg is not installed, if
+/// the image has no QR code or if a QR code is not a fragment.
+pub fn read_image(path: &str) -> Result<Vec<Fragment>, Box<dyn Error>> {
+    read_image_with(ZBARIMG, path)
+}
+
+// decodes the image with the given program, which takes the
+// arguments of zbarimg
+fn read_image_with(program: &str, path: &str) -> Result<Vec<Fragment>, Box<dyn Error>> {
+    let output = match Command::new(program)
+        .args(["--raw", "--quiet", "-Sdisable", "-Sqrcode.enable", path])
+        .output()
+    {
+        Ok(output) => output,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            return Err(Box::new(ArgumentError(format!(
+                "reading shares from images requires {} (zbar-tools)",
+                program
+            ))))
+        }
+        Err(e) => return Err(Box::new(e)),
+    };
+    let text = String::from_utf8_lossy(&output.stdout);
+    if !output.status.success() || text.trim().is_empty() {
+        return Err(Box::new(ArgumentError(format!(
+            "no QR code was found in {}",
+            path
+        ))));
+    }
+    parse_payloads(&text)
+        .map_err(|e| Box::new(CorruptFileError(format!("{}: {}", path, e))) as Box<dyn Error>)
+}
+
+// parses the fragments decoded from the QR codes, one per line as
+// in a shares file unless a QR code holds an armored fragment
+fn parse_payloads(text: &str) -> Result<Vec<Fragment>, Box<dyn Error>> {
+    if text.trim_start().starts_with(ARMOR_BEGIN) {
+        return Ok(vec![Fragment::from_armor(text)?]);
+    }
+    Ok(fragments::parse_fragments(text)?)
+}
+
+/////////////////////////////////
+// Unit testing of the module. //
+/////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::Share;
+
+    #[test]
+    fn payloads() {
+        let fragments = parse_payloads("1a:2b:holder=alice\n3c:4d\n").unwrap();
+        assert_eq!(fragments.len(), 2);
+        assert_eq!(fragments[0].holder, Some("alice".into()));
+        assert_eq!(fragments[1].share, Share("3c".into(), "4d".into()));
+        let fragment = Fragment::parse("1a:2b:holder=bob").unwrap();
+        assert_eq!(parse_payloads(&fragment.armor()).unwrap(), vec![fragment]);
+        assert!(parse_payloads("hello world\n").is_err());
+    }
+
+    #[test]
+    fn missing_decoder() {
+        let error = read_image_with("shared-secrets-no-such-tool", "photo.png").unwrap_err();
+        assert!(error.to_string().contains("zbar-tools"));
+    }
+}
@@ -0,0 +1,337 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io;
+use std::ops::{Deref, Range};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+use subtle::ConstantTimeEq;
+use zeroize::Zeroize;
+
+use crate::report;
+
+// Whether the warning about memory that cannot be locked was
+// printed, so it is printed once
+static WARNED: AtomicBool = AtomicBool::new(false);
+
+// Number of buffers locked in each page, by the number of the page;
+// the system locks whole pages, so a page is only unlocked when the
+// last buffer in it is dropped
+static LOCKED_PAGES: Mutex<BTreeMap<usize, usize>> = Mutex::new(BTreeMap::new());
+
+/// Bytes of a secret, such as a key or a password, kept in memory
+/// that is locked so it is never written to swap, with mlock on Unix
+/// and VirtualLock on Windows.
+///
+/// When the system does not allow locking the memory, for example
+/// because of RLIMIT_MEMLOCK, the bytes are kept in ordinary memory
+/// and a warning is printed once. The bytes are overwritten with
+/// zeros and unlocked when the buffer is dropped, and never printed.
+///
+/// The system locks whole pages, so the buffers that share a page
+/// are counted and the page stays locked until the last of them is
+/// dropped.
+pub struct SecureBuffer {
+    bytes: Vec<u8>,
+    locked: bool,
+}
+
+impl SecureBuffer {
+    /// Takes ownership of the bytes of a secret and locks their
+    /// memory, which is never reallocated afterwards.
+    ///
+    /// # Parameters
+    ///
+    /// - bytes: The bytes of the secret.
+    pub fn new(bytes: Vec<u8>) -> Self {
+        if bytes.is_empty() {
+            return SecureBuffer {
+                bytes,
+                locked: true,
+            };
+        }
+        let locked = match lock(&bytes) {
+            Ok(()) => true,
+            Err(e) => {
+                warn_unlocked(e);
+                false
+            }
+        };
+        SecureBuffer { bytes, locked }
+    }
+
+    /// Returns the bytes of the secret.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Returns the bytes of the secret, to be changed in place.
+    pub fn as_mut_bytes(&mut self) -> &mut [u8] {
+        &mut self.bytes
+    }
+
+    /// Returns whether the memory of the secret is locked, which is
+    /// always the case for an empty secret.
+    pub fn is_locked(&self) -> bool {
+        self.locked
+    }
+
+    /// Returns the length of the secret in bytes.
+    pub fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    /// Returns whether the secret has no bytes.
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+}
+
+impl Drop for SecureBuffer {
+    fn drop(&mut self) {
+        self.bytes.as_mut_slice().zeroize();
+        if self.locked && !self.bytes.is_empty() {
+            unlock(&self.bytes);
+        }
+        self.bytes.zeroize();
+    }
+}
+
+impl Clone for SecureBuffer {
+    fn clone(&self) -> Self {
+        SecureBuffer::new(self.bytes.clone())
+    }
+}
+
+impl fmt::Debug for SecureBuffer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "SecureBuffer({} bytes)", self.len())
+    }
+}
+
+/// A password or another secret text, kept in a SecureBuffer.
+#[derive(Clone)]
+pub struct SecureString {
+    buffer: SecureBuffer,
+}
+
+impl SecureString {
+    /// Returns the text.
+    pub fn as_str(&self) -> &str {
+        // the buffer only holds the bytes of a String
+        std::str::from_utf8(self.buffer.as_bytes()).unwrap_or_default()
+    }
+
+    /// Returns whether the memory of the text is locked.
+    pub fn is_locked(&self) -> bool {
+        self.buffer.is_locked()
+    }
+}
+
+impl From<String> for SecureString {
+    fn from(text: String) -> Self {
+        SecureString {
+            buffer: SecureBuffer::new(text.into_bytes()),
+        }
+    }
+}
+
+impl From<&str> for SecureString {
+    fn from(text: &str) -> Self {
+        SecureString::from(text.to_string())
+    }
+}
+
+impl Deref for SecureString {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+// compares in constant time, so the time does not tell how much of
+// a password was guessed right
+impl PartialEq for SecureString {
+    fn eq(&self, other: &SecureString) -> bool {
+        self.buffer.as_bytes().ct_eq(other.buffer.as_bytes()).into()
+    }
+}
+
+impl Eq for SecureString {}
+
+impl fmt::Debug for SecureString {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "SecureString({} bytes)", self.buffer.len())
+    }
+}
+
+// warns that secrets are kept in memory that is not locked, the
+// first time it happens
+fn warn_unlocked(error: io::Error) {
+    if !WARNED.swap(true, Ordering::Relaxed) {
+        report::warn(format!(
+            "the memory of keys and passwords cannot be locked, they may be written to swap: {}",
+            error
+        ));
+    }
+}
+
+// locks the pages of the bytes that no other buffer has locked yet,
+// and counts the bytes in all of them
+fn lock(bytes: &[u8]) -> io::Result<()> {
+    let size = page_size();
+    let mut locked = locked_pages();
+    let pages = pages_of(bytes, size);
+    for page in pages.clone() {
+        if !locked.contains_key(&page) {
+            if let Err(e) = lock_page(page * size, size) {
+                // the pages counted so far are released
+                for page in pages.start..page {
+                    release(&mut locked, page, size);
+                }
+                return Err(e);
+            }
+        }
+        *locked.entry(page).or_insert(0) += 1;
+    }
+    Ok(())
+}
+
+// uncounts the bytes in their pages and unlocks the pages where no
+// other buffer is left
+fn unlock(bytes: &[u8]) {
+    let size = page_size();
+    let mut locked = locked_pages();
+    for page in pages_of(bytes, size) {
+        release(&mut locked, page, size);
+    }
+}
+
+fn release(locked: &mut BTreeMap<usize, usize>, page: usize, size: usize) {
+    if let Some(count) = locked.get_mut(&page) {
+        *count -= 1;
+        if *count == 0 {
+            locked.remove(&page);
+            unlock_page(page * size, size);
+        }
+    }
+}
+
+// the counts of the locked pages, which stay right even if a thread
+// panicked while holding them, since they are changed in single steps
+fn locked_pages() -> MutexGuard<'static, BTreeMap<usize, usize>> {
+    LOCKED_PAGES.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+// the numbers of the pages of some bytes, which are not empty
+fn pages_of(bytes: &[u8], size: usize) -> Range<usize> {
+    let start = bytes.as_ptr() as usize;
+    start / size..(start + bytes.len() - 1) / size + 1
+}
+
+#[cfg(unix)]
+fn page_size() -> usize {
+    match unsafe { libc::sysconf(libc::_SC_PAGESIZE) } {
+        size if size > 0 => size as usize,
+        _ => 4096,
+    }
+}
+
+#[cfg(unix)]
+fn lock_page(address: usize, size: usize) -> io::Result<()> {
+    if unsafe { libc::mlock(address as *const libc::c_void, size) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn unlock_page(address: usize, size: usize) {
+    unsafe { libc::munlock(address as *const libc::c_void, size) };
+}
+
+#[cfg(windows)]
+#[link(name = "kernel32")]
+extern "system" {
+    fn VirtualLock(address: *const u8, size: usize) -> i32;
+    fn VirtualUnlock(address: *const u8, size: usize) -> i32;
+}
+
+// the pages of Windows are of 4 KiB on all its architectures, and
+// the other systems lock no pages
+#[cfg(not(unix))]
+fn page_size() -> usize {
+    4096
+}
+
+#[cfg(windows)]
+fn lock_page(address: usize, size: usize) -> io::Result<()> {
+    if unsafe { VirtualLock(address as *const u8, size) } == 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(windows)]
+fn unlock_page(address: usize, size: usize) {
+    unsafe { VirtualUnlock(address as *const u8, size) };
+}
+
+#[cfg(not(any(unix, windows)))]
+fn lock_page(_address: usize, _size: usize) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "not supported on this system",
+    ))
+}
+
+#[cfg(not(any(unix, windows)))]
+fn unlock_page(_address: usize, _size: usize) {}
+
+/////////////////////////////////
+// Unit testing of the module. //
+/////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn secure_buffer() {
+        let mut buffer = SecureBuffer::new(vec![1, 2, 3]);
+        assert_eq!(buffer.as_bytes(), [1, 2, 3]);
+        buffer.as_mut_bytes()[0] = 7;
+        assert_eq!(buffer.clone().as_bytes(), [7, 2, 3]);
+        assert_eq!(format!("{:?}", buffer), "SecureBuffer(3 bytes)");
+        assert!(SecureBuffer::new(Vec::new()).is_locked());
+    }
+
+    #[test]
+    fn secure_string() {
+        let password = SecureString::from("secure password");
+        assert_eq!(&*password, "secure password");
+        assert_eq!(password.as_bytes(), b"secure password");
+        assert_eq!(
+            password,
+            SecureString::from(String::from("secure password"))
+        );
+        assert_ne!(password, SecureString::from("secure passwore"));
+        assert_eq!(format!("{:?}", password), "SecureString(15 bytes)");
+    }
+
+    #[test]
+    fn shared_page() {
+        let bytes = vec![0u8; 2];
+        let page = pages_of(&bytes, page_size()).start;
+        if lock(&bytes[..1]).is_err() {
+            // the memory cannot be locked on this system
+            return;
+        }
+        lock(&bytes).unwrap();
+        // the page stays locked for the buffer left in it
+        unlock(&bytes[..1]);
+        assert!(locked_pages().contains_key(&page));
+        unlock(&bytes);
+    }
+}
@@ -0,0 +1,1785 @@
+use std::error::Error;
+use std::fmt;
+use std::fs::OpenOptions;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::str::FromStr;
+use std::time::Duration;
+#[cfg(feature = "server")]
+use std::{
+    collections::HashMap,
+    env, fs,
+    net::IpAddr,
+    net::TcpListener,
+    path::Path,
+    process::{Command, Stdio},
+    sync::atomic::{AtomicUsize, Ordering},
+    sync::{Arc, Mutex},
+    thread,
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
+
+#[cfg(feature = "server")]
+use subtle::ConstantTimeEq;
+
+#[cfg(feature = "server")]
+use crate::container::Header;
+use crate::crypto::SecretKey;
+use crate::encoding::{self, from_base64, to_base64, to_hex};
+use crate::error::{ArgumentError, CorruptFileError};
+#[cfg(feature = "server")]
+use crate::fragments::{self, Format, Fragment};
+#[cfg(feature = "server")]
+use crate::report::{self, Json};
+#[cfg(feature = "server")]
+use crate::webhook::Webhooks;
+
+/// Largest body of a request or a response, in bytes.
+pub const MAX_BODY: usize = 64 * 1024 * 1024;
+
+/// Header with a shares file, in base64, sent with the encrypted
+/// files and returned with the files encrypted.
+pub const SHARES_HEADER: &str = "x-shares";
+
+/// Header with the name of a decrypted file.
+pub const FILE_NAME_HEADER: &str = "x-file-name";
+
+/// Header with the id of a recovery that is waiting, see
+/// RecoveryPolicy.
+pub const RECOVERY_HEADER: &str = "x-recovery-id";
+
+/// Path where a recovery that is waiting is vetoed, with its id in
+/// the query: POST /veto?id=ID, by an enrolled holder who sends
+/// their token in HOLDER_TOKENS_HEADER.
+pub const VETO_PATH: &str = "/veto";
+
+/// Header with the tokens of the holders of the shares sent, in
+/// base64, one holder:token line for each holder, for a server that
+/// only takes the shares of enrolled holders, or the one of the
+/// holder who vetoes a recovery.
+pub const HOLDER_TOKENS_HEADER: &str = "x-holder-tokens";
+
+// Context of the hashes of the tokens of the holders
+const HOLDER_TOKEN_CONTEXT: &str = "shared_secrets holder token v1";
+
+// Largest request or status line and header, in bytes
+const MAX_LINE: usize = 8 * 1024;
+
+/// An operation of the service, each served at POST /NAME.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operation {
+    /// Splits the body into shares, given as a shares file.
+    Split,
+    /// Recovers the secret of the shares file in the body.
+    Combine,
+    /// Encrypts the body, returning the encrypted file and its shares.
+    Encrypt,
+    /// Decrypts the encrypted file in the body with the shares sent.
+    Decrypt,
+}
+
+impl Operation {
+    /// Returns the path of the endpoint of the operation.
+    pub fn path(self) -> &'static str {
+        match self {
+            Operation::Split => "/split",
+            Operation::Combine => "/combine",
+            Operation::Encrypt => "/encrypt",
+            Operation::Decrypt => "/decrypt",
+        }
+    }
+}
+
+impl FromStr for Operation {
+    type Err = ArgumentError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim_start_matches('/') {
+            "split" => Ok(Operation::Split),
+            "combine" => Ok(Operation::Combine),
+            "encrypt" => Ok(Operation::Encrypt),
+            "decrypt" => Ok(Operation::Decrypt),
+            _ => Err(ArgumentError(format!("unknown operation '{}'", s))),
+        }
+    }
+}
+
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.path().trim_start_matches('/'))
+    }
+}
+
+/// How a server holds the recoveries, the Combine and Decrypt
+/// operations, once it is given enough shares, so the owners of the
+/// secret have time to veto a recovery made under coercion.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RecoveryPolicy {
+    /// How long a recovery waits before it is performed; no time
+    /// performs it at once.
+    pub delay: Duration,
+    /// A command of the shell run with each event of a recovery that
+    /// waits, which reads the event as JSON in its input, to notify
+    /// the owners.
+    pub notify: Option<String>,
+}
+
+/// A holder enrolled in the holder tokens file of a server.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EnrolledHolder {
+    /// The name of the holder, as it is in their share.
+    pub name: String,
+    /// The index of the share of the holder, without leading zeros
+    /// and in lowercase; the holder can only submit this share.
+    pub index: String,
+    /// The hash of the token of the holder.
+    pub token_hash: String,
+}
+
+/// Enrolls a holder in the holder tokens file of a server, which
+/// then only takes the shares of the holders enrolled, each sent
+/// with the token of its holder and only the share of the index the
+/// holder was enrolled with. The token is random and only its hash
+/// is saved, so the file does not give the tokens away.
+///
+/// # Parameters
+///
+/// - path: The path of the holder tokens file, created readable only
+///   by its owner if it does not exist.
+/// - holder: The name of the holder, as it is in their share.
+/// - index: The index of the share of the holder, as it is in the
+///   share.
+///
+/// # Returns
+///
+/// The token, which is given only to the holder.
+///
+/// # Errors
+///
+/// An ArgumentError if the name or the index is not valid, or the
+/// holder or the index is already enrolled, or an io::Error if the
+/// file cannot be written.
+pub fn enroll_holder(path: &str, holder: &str, index: &str) -> Result<String, Box<dyn Error>> {
+    if !crate::fragments::is_valid_holder(holder) {
+        return Err(Box::new(ArgumentError(format!(
+            "invalid holder name '{}'",
+            holder
+        ))));
+    }
+    let index = share_index(index);
+    if index.is_empty() || !index.bytes().all(|b| b.is_ascii_alphanumeric()) {
+        return Err(Box::new(ArgumentError(format!(
+            "invalid share index '{}'",
+            index
+        ))));
+    }
+    let enrolled = match std::fs::read_to_string(path) {
+        Ok(_) => read_holder_tokens(path)?,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+        Err(e) => return Err(Box::new(e)),
+    };
+    if enrolled.iter().any(|other| other.name == holder) {
+        return Err(Box::new(ArgumentError(format!(
+            "{} is already enrolled",
+            holder
+        ))));
+    }
+    if let Some(other) = enrolled.iter().find(|other| other.index == index) {
+        return Err(Box::new(ArgumentError(format!(
+            "share {} is already enrolled for {}",
+            index, other.name
+        ))));
+    }
+    let token = to_hex(SecretKey::generate(16)?.expose());
+    let mut options = OpenOptions::new();
+    options.append(true).create(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let mut file = options.open(path)?;
+    writeln!(file, "{}:{}:{}", holder, index, holder_token_hash(&token))?;
+    file.sync_all()?;
+    Ok(token)
+}
+
+/// Reads the holders enrolled in a holder tokens file, with the
+/// index of their share and the hash of each token.
+///
+/// # Parameters
+///
+/// - path: The path of the holder tokens file.
+///
+/// # Errors
+///
+/// An io::Error if the file cannot be read, or a CorruptFileError if
+/// a line is not a holder, the index of their share and the hash of
+/// its token.
+pub fn read_holder_tokens(path: &str) -> Result<Vec<EnrolledHolder>, Box<dyn Error>> {
+    std::fs::read_to_string(path)?
+        .lines()
+        .filter(|line| !line.trim().is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            let mut fields = line.rsplitn(3, ':');
+            match (fields.next(), fields.next(), fields.next()) {
+                (Some(hash), Some(index), Some(name)) if hash.len() == 64 => Ok(EnrolledHolder {
+                    name: name.to_string(),
+                    index: share_index(index),
+                    token_hash: hash.to_lowercase(),
+                }),
+                _ => Err(Box::new(CorruptFileError(format!(
+                    "{} is not a holder tokens file, each line must be a holder, the index \
+                     of their share and the hash of their token",
+                    path
+                ))) as _),
+            }
+        })
+        .collect()
+}
+
+// the index of a share as the tokens file keeps it, without leading
+// zeros and in lowercase, so it does not depend on how it was typed
+fn share_index(index: &str) -> String {
+    index.trim().trim_start_matches('0').to_lowercase()
+}
+
+// the hash of the token of a holder, as the tokens file keeps it
+fn holder_token_hash(token: &str) -> String {
+    to_hex(&blake3::derive_key(HOLDER_TOKEN_CONTEXT, token.as_bytes()))
+}
+
+/// How many requests a server takes from each client, known by its
+/// IP address, so the token and the shares cannot be guessed by
+/// trying, nor the recoveries flooded.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ClientLimits {
+    /// Most requests of a client in a minute, or None for no limit.
+    pub requests_per_minute: Option<u32>,
+    /// Failed attempts of a client, with a wrong token or with shares
+    /// that do not recover the secret, after which it is locked out,
+    /// or None to never lock a client out.
+    pub max_failures: Option<u32>,
+    /// How long a client is locked out.
+    pub lockout: Duration,
+}
+
+/// The state of a server: the recoveries that wait under its
+/// RecoveryPolicy, the requests of its clients under its
+/// ClientLimits, the holders enrolled and the webhooks told of its
+/// events.
+#[cfg(feature = "server")]
+pub struct Server {
+    policy: RecoveryPolicy,
+    limits: ClientLimits,
+    webhooks: Webhooks,
+    holders: Vec<EnrolledHolder>,
+    pending: Mutex<Vec<Pending>>,
+    clients: Mutex<HashMap<IpAddr, Client>>,
+}
+
+// Most clients remembered; beyond it the ones that are neither
+// counted in the current minute nor locked out are forgotten
+#[cfg(feature = "server")]
+const MAX_CLIENTS: usize = 4096;
+
+// Most recoveries that wait; beyond it no other one is started
+#[cfg(feature = "server")]
+const MAX_PENDING: usize = 1024;
+
+// How long a recovery is kept once its delay passes, to be
+// performed or, if it was vetoed, refused
+#[cfg(feature = "server")]
+const PENDING_LIFETIME: Duration = Duration::from_secs(24 * 60 * 60);
+
+// Most connections served at the same time; the others are refused
+#[cfg(feature = "server")]
+const MAX_CONNECTIONS: usize = 64;
+
+// Longest wait for a read or a write of a connection, so a client
+// that stops sending or reading does not keep its thread
+#[cfg(feature = "server")]
+const STREAM_TIMEOUT: Duration = Duration::from_secs(30);
+
+// the requests of a client in the current minute and its failures
+#[cfg(feature = "server")]
+struct Client {
+    minute: Instant,
+    requests: u32,
+    failures: u32,
+    locked_until: Option<Instant>,
+}
+
+// a recovery that waits, known by the hash of its target, see
+// recovery_target
+#[cfg(feature = "server")]
+struct Pending {
+    id: String,
+    target: blake3::Hash,
+    ready: Instant,
+    vetoed: bool,
+}
+
+#[cfg(feature = "server")]
+impl Server {
+    /// Creates the state of a server, with no recovery waiting.
+    ///
+    /// # Parameters
+    ///
+    /// - policy: The recovery policy of the server.
+    /// - limits: The limits of the requests of each client.
+    /// - webhooks: The webhooks told of the events of the server.
+    pub fn new(policy: RecoveryPolicy, limits: ClientLimits, webhooks: Webhooks) -> Self {
+        Server {
+            policy,
+            limits,
+            webhooks,
+            holders: Vec::new(),
+            pending: Mutex::new(Vec::new()),
+            clients: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Takes only the shares of the holders enrolled, each sent with
+    /// the token of its holder, see enroll_holder.
+    ///
+    /// # Parameters
+    ///
+    /// - holders: The holders, with the index of their share and the
+    ///   hash of their tokens, as read_holder_tokens returns them;
+    ///   none takes any share.
+    pub fn with_holders(mut self, holders: Vec<EnrolledHolder>) -> Self {
+        self.holders = holders;
+        self
+    }
+
+    // checks that every share submitted is the one of an enrolled
+    // holder, who sent their token, and that no holder submitted
+    // more than one share
+    fn authenticate_holders(
+        &self,
+        fragments: &[Fragment],
+        request: &Message,
+    ) -> Result<(), Box<dyn Error>> {
+        if self.holders.is_empty() {
+            return Ok(());
+        }
+        let tokens = holder_tokens(request)?;
+        let mut submitted: Vec<&str> = Vec::new();
+        for fragment in fragments {
+            let holder = fragment.holder.as_deref().ok_or_else(|| {
+                ArgumentError(format!(
+                    "share {} has no holder, only enrolled holders take part",
+                    fragment.share.0
+                ))
+            })?;
+            let enrolled = self.enrolled_holder(holder, &tokens)?;
+            // the name in a share can be changed, its index is bound
+            // to the holder when enrolling
+            if share_index(&fragment.share.0) != enrolled.index {
+                return Err(Box::new(ArgumentError(format!(
+                    "share {} is not the one enrolled for {}",
+                    fragment.share.0, holder
+                ))));
+            }
+            if submitted.contains(&holder) {
+                return Err(Box::new(ArgumentError(format!(
+                    "{} submitted more than one share",
+                    holder
+                ))));
+            }
+            submitted.push(holder);
+        }
+        Ok(())
+    }
+
+    // the enrolled holder of the name, if the token sent for them in
+    // the holder:token lines is theirs
+    fn enrolled_holder(
+        &self,
+        holder: &str,
+        tokens: &str,
+    ) -> Result<&EnrolledHolder, Box<dyn Error>> {
+        let enrolled = self
+            .holders
+            .iter()
+            .find(|enrolled| enrolled.name == holder)
+            .ok_or_else(|| ArgumentError(format!("{} is not an enrolled holder", holder)))?;
+        let token = tokens
+            .lines()
+            .filter_map(|line| line.rsplit_once(':'))
+            .find(|(other, _)| *other == holder)
+            .map(|(_, token)| token.trim())
+            .ok_or_else(|| ArgumentError(format!("the token of {} was not sent", holder)))?;
+        let valid: bool = holder_token_hash(token)
+            .as_bytes()
+            .ct_eq(enrolled.token_hash.as_bytes())
+            .into();
+        if !valid {
+            return Err(Box::new(ArgumentError(format!(
+                "wrong token for {}",
+                holder
+            ))));
+        }
+        Ok(enrolled)
+    }
+
+    // counts a request of a client, returning the answer if it is
+    // locked out or made too many requests this minute
+    fn admit(&self, client: IpAddr) -> Option<Message> {
+        let now = Instant::now();
+        let minute = Duration::from_secs(60);
+        let mut clients = self.clients.lock().unwrap();
+        if clients.len() >= MAX_CLIENTS {
+            clients.retain(|_, other| {
+                now.duration_since(other.minute) < minute
+                    || other.locked_until.is_some_and(|until| now < until)
+            });
+        }
+        let state = clients.entry(client).or_insert(Client {
+            minute: now,
+            requests: 0,
+            failures: 0,
+            locked_until: None,
+        });
+        if let Some(until) = state.locked_until {
+            if now < until {
+                return Some(status(
+                    429,
+                    &format!(
+                        "too many failed attempts, try again in {} seconds",
+                        (until - now).as_secs() + 1
+                    ),
+                ));
+            }
+            state.locked_until = None;
+            state.failures = 0;
+        }
+        if now.duration_since(state.minute) >= minute {
+            state.minute = now;
+            state.requests = 0;
+        }
+        state.requests += 1;
+        match self.limits.requests_per_minute {
+            Some(limit) if state.requests > limit => Some(status(
+                429,
+                &format!("more than {} requests in a minute", limit),
+            )),
+            _ => None,
+        }
+    }
+
+    // counts the failure of a request with a wrong token or with
+    // shares that do not recover the secret, and locks the client
+    // out after too many of them
+    fn record(&self, client: IpAddr, request: &Message, response: &Message) {
+        let code = response.start.split(' ').nth(1).unwrap_or_default();
+        let recovery = request
+            .start
+            .split(' ')
+            .nth(1)
+            .map(|target| parse_target(target).0)
+            .and_then(|path| path.parse::<Operation>().ok())
+            .is_some_and(|operation| {
+                operation == Operation::Combine || operation == Operation::Decrypt
+            });
+        if code != "401" && !(recovery && code == "400") {
+            return;
+        }
+        let max_failures = match self.limits.max_failures {
+            Some(max_failures) => max_failures,
+            None => return,
+        };
+        let mut clients = self.clients.lock().unwrap();
+        let state = match clients.get_mut(&client) {
+            Some(state) => state,
+            None => return,
+        };
+        state.failures += 1;
+        if state.failures >= max_failures {
+            state.locked_until = Some(Instant::now() + self.limits.lockout);
+            drop(clients);
+            self.webhooks.send(
+                "client_locked_out",
+                vec![
+                    ("client", client.to_string().into()),
+                    ("failures", Json::Number(u64::from(max_failures))),
+                    ("lockout", Json::Number(self.limits.lockout.as_secs())),
+                ],
+            );
+        }
+    }
+
+    // holds a recovery until its delay passes: the first request
+    // starts waiting, and a request for the same recovery afterwards,
+    // of the same operation and file with shares of the same indices,
+    // is performed; returns the answer while it waits or was vetoed
+    fn hold(
+        &self,
+        operation: Operation,
+        request: &Message,
+        fragments: &[Fragment],
+    ) -> Result<Option<Message>, Box<dyn Error>> {
+        if self.policy.delay == Duration::ZERO {
+            return Ok(None);
+        }
+        let target = recovery_target(operation, request, fragments);
+        let mut pending = self.pending.lock().unwrap();
+        let now = Instant::now();
+        // the recoveries never repeated and the vetoed ones expire
+        pending.retain(|recovery| now < recovery.ready + PENDING_LIFETIME);
+        let recovery = match pending
+            .iter()
+            .position(|recovery| recovery.target == target)
+        {
+            Some(i) => &pending[i],
+            None if pending.len() >= MAX_PENDING => {
+                return Ok(Some(status(503, "too many recoveries are waiting")));
+            }
+            None => {
+                let id = to_hex(SecretKey::generate(8)?.expose());
+                pending.push(Pending {
+                    id: id.clone(),
+                    target,
+                    ready: now + self.policy.delay,
+                    vetoed: false,
+                });
+                self.notify("recovery_requested", self.recovery_fields(&id, operation));
+                return Ok(Some(waiting(&id, self.policy.delay)));
+            }
+        };
+        if recovery.vetoed {
+            return Ok(Some(status(
+                403,
+                &format!("recovery {} was vetoed", recovery.id),
+            )));
+        }
+        if now < recovery.ready {
+            return Ok(Some(waiting(&recovery.id, recovery.ready - now)));
+        }
+        let id = recovery.id.clone();
+        pending.retain(|recovery| recovery.id != id);
+        self.notify("recovery_performed", self.recovery_fields(&id, operation));
+        Ok(None)
+    }
+
+    // vetoes a recovery that waits, which is then always refused;
+    // only an enrolled holder, with their token, can veto
+    fn veto(&self, id: &str, request: &Message) -> Message {
+        if self.holders.is_empty() {
+            return status(
+                403,
+                "only enrolled holders can veto, and this server has none",
+            );
+        }
+        let holder = holder_tokens(request).and_then(|tokens| {
+            let holder = tokens
+                .lines()
+                .find_map(|line| line.rsplit_once(':'))
+                .map(|(holder, _)| holder)
+                .ok_or_else(|| {
+                    ArgumentError("the token of an enrolled holder was not sent".into())
+                })?;
+            Ok(self.enrolled_holder(holder, &tokens)?.name.clone())
+        });
+        let holder = match holder {
+            Ok(holder) => holder,
+            Err(e) => return status(401, &e.to_string()),
+        };
+        let mut pending = self.pending.lock().unwrap();
+        match pending.iter_mut().find(|recovery| recovery.id == id) {
+            Some(recovery) if !recovery.vetoed => {
+                recovery.vetoed = true;
+                drop(pending);
+                self.notify(
+                    "recovery_vetoed",
+                    vec![("id", id.into()), ("holder", holder.into())],
+                );
+                status(200, &format!("recovery {} was vetoed", id))
+            }
+            Some(_) => status(200, &format!("recovery {} was already vetoed", id)),
+            None => status(404, &format!("no recovery {} is waiting", id)),
+        }
+    }
+
+    fn recovery_fields(&self, id: &str, operation: Operation) -> Vec<(&'static str, Json)> {
+        vec![
+            ("id", id.into()),
+            ("operation", operation.to_string().into()),
+            ("delay", Json::Number(self.policy.delay.as_secs())),
+        ]
+    }
+
+    // tells the webhooks of an event of a recovery that waits, and
+    // runs the notification command with the event in its input; a
+    // command that fails is reported but does not stop the server
+    fn notify(&self, event: &str, fields: Vec<(&str, Json)>) {
+        self.webhooks.send(event, fields.clone());
+        let command = match &self.policy.notify {
+            Some(command) => command,
+            None => return,
+        };
+        let mut body = vec![
+            ("event", event.into()),
+            ("time", Json::Number(now_seconds())),
+        ];
+        body.extend(fields);
+        let event = Json::object(body);
+        let (shell, flag) = if cfg!(windows) {
+            ("cmd", "/C")
+        } else {
+            ("sh", "-c")
+        };
+        let result = Command::new(shell)
+            .args([flag, command.as_str()])
+            .stdin(Stdio::piped())
+            .spawn()
+            .and_then(|mut child| {
+                let mut input = child.stdin.take().unwrap();
+                writeln!(input, "{}", event)?;
+                drop(input);
+                child.wait()
+            });
+        match result {
+            Ok(exit) if exit.success() => {}
+            Ok(exit) => report::warn(format!("the notification command failed: {}", exit)),
+            Err(e) => report::warn(format!("the notification command failed: {}", e)),
+        }
+    }
+}
+
+// what a recovery recovers, so the requests for it are told apart
+// from the others however its shares are written: the operation,
+// the file decrypted and the indices of the shares submitted
+#[cfg(feature = "server")]
+fn recovery_target(
+    operation: Operation,
+    request: &Message,
+    fragments: &[Fragment],
+) -> blake3::Hash {
+    let mut indices: Vec<String> = fragments
+        .iter()
+        .map(|fragment| share_index(&fragment.share.0))
+        .collect();
+    indices.sort();
+    indices.dedup();
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(operation.path().as_bytes());
+    // the shares of a decryption are in a header, the body is the file
+    if operation == Operation::Decrypt {
+        hasher.update(blake3::hash(&request.body).as_bytes());
+    }
+    for index in &indices {
+        hasher.update(b"\n");
+        hasher.update(index.as_bytes());
+    }
+    hasher.finalize()
+}
+
+// the holder:token lines sent with a request, empty if none were
+#[cfg(feature = "server")]
+fn holder_tokens(request: &Message) -> Result<String, Box<dyn Error>> {
+    match request.header(HOLDER_TOKENS_HEADER) {
+        Some(tokens) => Ok(String::from_utf8(decode_header(tokens)?)?),
+        None => Ok(String::new()),
+    }
+}
+
+// the answer to a recovery that waits
+#[cfg(feature = "server")]
+fn waiting(id: &str, remaining: Duration) -> Message {
+    status(
+        202,
+        &format!(
+            "recovery {} is waiting, repeat the request in {} seconds; until then it can be vetoed with POST {}?id={}",
+            id,
+            remaining.as_secs() + u64::from(remaining.subsec_nanos() > 0),
+            VETO_PATH,
+            id
+        ),
+    )
+    .with_header(RECOVERY_HEADER, id)
+}
+
+#[cfg(feature = "server")]
+fn now_seconds() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |time| time.as_secs())
+}
+
+/// A request or a response of HTTP/1.1, with its body read whole.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Message {
+    /// The request line or the status line.
+    pub start: String,
+    /// The headers, with their names in lowercase.
+    pub headers: Vec<(String, String)>,
+    /// The body.
+    pub body: Vec<u8>,
+}
+
+impl Message {
+    /// Creates a message without headers.
+    ///
+    /// # Parameters
+    ///
+    /// - start: The request line or the status line.
+    /// - body: The body.
+    pub fn new(start: impl Into<String>, body: Vec<u8>) -> Self {
+        Message {
+            start: start.into(),
+            headers: Vec::new(),
+            body,
+        }
+    }
+
+    /// Adds a header to the message.
+    ///
+    /// # Parameters
+    ///
+    /// - name: The name of the header.
+    /// - value: The value, which must not have line breaks.
+    pub fn with_header(mut self, name: &str, value: impl Into<String>) -> Self {
+        self.headers.push((name.to_lowercase(), value.into()));
+        self
+    }
+
+    /// Replaces the body of the message.
+    ///
+    /// # Parameters
+    ///
+    /// - body: The new body.
+    pub fn with_body(mut self, body: Vec<u8>) -> Self {
+        self.body = body;
+        self
+    }
+
+    /// Returns the value of the first header with a name.
+    ///
+    /// # Parameters
+    ///
+    /// - name: The name of the header, in any case.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(other, _)| other.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Reads a message, with a body of the length in its
+    /// Content-Length header.
+    ///
+    /// # Parameters
+    ///
+    /// - reader: The reader of the connection.
+    ///
+    /// # Errors
+    ///
+    /// A CorruptFileError if the message is malformed or its body is
+    /// larger than MAX_BODY, or an io::Error if reading fails.
+    pub fn read_from(reader: &mut impl BufRead) -> Result<Self, Box<dyn Error>> {
+        let start = read_line(reader)?;
+        let mut headers = Vec::new();
+        loop {
+            let line = read_line(reader)?;
+            if line.is_empty() {
+                break;
+            }
+            let (name, value) = line
+                .split_once(':')
+                .ok_or_else(|| CorruptFileError(format!("'{}' is not a header", line)))?;
+            headers.push((name.trim().to_lowercase(), value.trim().to_string()));
+        }
+        let mut message = Message {
+            start,
+            headers,
+            body: Vec::new(),
+        };
+        let length: usize = match message.header("content-length") {
+            Some(length) => length
+                .parse()
+                .map_err(|_| CorruptFileError("invalid Content-Length".into()))?,
+            None => 0,
+        };
+        if length > MAX_BODY {
+            return Err(Box::new(CorruptFileError(format!(
+                "the body cannot be larger than {} bytes",
+                MAX_BODY
+            ))));
+        }
+        message.body = vec![0u8; length];
+        reader.read_exact(&mut message.body)?;
+        Ok(message)
+    }
+
+    /// Writes the message, with its Content-Length and closing
+    /// the connection afterwards.
+    ///
+    /// # Parameters
+    ///
+    /// - writer: The writer of the connection.
+    ///
+    /// # Errors
+    ///
+    /// This method returns an error if an error occurs while writing.
+    pub fn write_to(&self, writer: &mut impl Write) -> std::io::Result<()> {
+        write!(writer, "{}\r\n", self.start)?;
+        for (name, value) in &self.headers {
+            write!(writer, "{}: {}\r\n", name, value)?;
+        }
+        write!(
+            writer,
+            "content-length: {}\r\nconnection: close\r\n\r\n",
+            self.body.len()
+        )?;
+        writer.write_all(&self.body)?;
+        writer.flush()
+    }
+}
+
+/// Calls an operation of a server, the thin client of the service.
+///
+/// The connection is plain HTTP, so the server should be local or
+/// behind a proxy with TLS; see check_loopback.
+///
+/// # Parameters
+///
+/// - server: The address of the server, as host:port.
+/// - token: The token the server was started with.
+/// - operation: The operation to call.
+/// - query: The parameters of the operation.
+/// - body: The body of the request.
+/// - shares: The shares file sent with Decrypt, or None.
+/// - holder_tokens: The holder:token lines of the holders of the
+///   shares, for a server with enrolled holders, or None.
+///
+/// # Returns
+///
+/// The response of the server.
+///
+/// # Errors
+///
+/// An io::Error if the server cannot be reached, or an
+/// ArgumentError with the message of the server if it refuses the
+/// request.
+pub fn call(
+    server: &str,
+    token: &str,
+    operation: Operation,
+    query: &[(&str, String)],
+    body: Vec<u8>,
+    shares: Option<&str>,
+    holder_tokens: Option<&str>,
+) -> Result<Message, Box<dyn Error>> {
+    let mut target = operation.path().to_string();
+    for (i, (name, value)) in query.iter().enumerate() {
+        let separator = if i == 0 { '?' } else { '&' };
+        target.push_str(&format!("{}{}={}", separator, name, percent_encode(value)));
+    }
+    let mut request = Message::new(format!("POST {} HTTP/1.1", target), body)
+        .with_header("host", server)
+        .with_header("authorization", format!("Bearer {}", token));
+    if let Some(shares) = shares {
+        request = request.with_header(SHARES_HEADER, encode_header(shares.as_bytes()));
+    }
+    if let Some(holder_tokens) = holder_tokens {
+        request = request.with_header(
+            HOLDER_TOKENS_HEADER,
+            encode_header(holder_tokens.as_bytes()),
+        );
+    }
+    let mut stream = TcpStream::connect(server)?;
+    request.write_to(&mut stream)?;
+    let response = Message::read_from(&mut BufReader::new(stream))?;
+    let status = response.start.split(' ').nth(1).unwrap_or_default();
+    if status != "200" {
+        return Err(Box::new(ArgumentError(format!(
+            "the server answered {}: {}",
+            status,
+            String::from_utf8_lossy(&response.body).trim()
+        ))));
+    }
+    Ok(response)
+}
+
+/// Checks that an address is only reachable from this computer, as
+/// the token, the shares and the secrets go over HTTP without TLS.
+///
+/// # Parameters
+///
+/// - address: The address served or called, as host:port.
+/// - insecure: Whether the operator accepts plain HTTP outside this
+///   computer, behind a proxy with TLS or in a trusted network.
+///
+/// # Errors
+///
+/// An ArgumentError if the address resolves to an address that is
+/// not a loopback one and insecure is false, or an io::Error if it
+/// cannot be resolved.
+pub fn check_loopback(address: &str, insecure: bool) -> Result<(), Box<dyn Error>> {
+    if insecure {
+        return Ok(());
+    }
+    let mut addresses = address.to_socket_addrs()?;
+    if addresses.all(|address| address.ip().is_loopback()) {
+        return Ok(());
+    }
+    Err(Box::new(ArgumentError(format!(
+        "{} is reachable from other computers and HTTP is not encrypted; \
+         put a proxy with TLS in front and give --insecure-http",
+        address
+    ))))
+}
+
+/// Encodes a value for a header in base64.
+///
+/// # Parameters
+///
+/// - value: The bytes of the value.
+pub fn encode_header(value: &[u8]) -> String {
+    to_base64(value)
+}
+
+/// Decodes a header encoded with encode_header.
+///
+/// # Parameters
+///
+/// - value: The value of the header.
+///
+/// # Errors
+///
+/// A CorruptFileError if the value is not base64.
+pub fn decode_header(value: &str) -> Result<Vec<u8>, CorruptFileError> {
+    from_base64(value).ok_or_else(|| CorruptFileError("a header is not base64".into()))
+}
+
+/// Splits the target of a request into its path and its query
+/// parameters, which are percent-decoded.
+///
+/// # Parameters
+///
+/// - target: The target of the request line, like /split?n=5&k=3.
+pub fn parse_target(target: &str) -> (&str, Vec<(String, String)>) {
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let parameters = query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (percent_decode(name), percent_decode(value))
+        })
+        .collect();
+    (path, parameters)
+}
+
+/// Serves the operations over HTTP, each connection in its own
+/// thread, until the process is stopped.
+///
+/// At most 64 connections are served at the same time, the others
+/// are answered 503, and a connection that sends or reads nothing
+/// for 30 seconds is closed. A recovery that waits is forgotten a
+/// day after its delay passes, whether it was vetoed or not.
+///
+/// Every request must have the header `Authorization: Bearer TOKEN`.
+/// The files and shares keep the formats of the command line, so
+/// the results can be used without the server. The recoveries wait
+/// as the policy says, and an enrolled holder, with their token, can
+/// veto them at VETO_PATH while they wait.
+///
+/// The webhooks are told when shares are split, when shares are
+/// submitted, when they reach the threshold and recover the secret
+/// and when a file is decrypted, with the operation, the number of
+/// shares and their fingerprints, but never a share or a secret.
+///
+/// # Parameters
+///
+/// - listener: The socket the server listens on.
+/// - token: The token the clients must give.
+/// - server: The state of the server, with its recovery policy, the
+///   limits of its clients and its webhooks.
+///
+/// # Errors
+///
+/// An io::Error if the socket fails.
+#[cfg(feature = "server")]
+pub fn serve(listener: TcpListener, token: &str, server: Server) -> Result<(), Box<dyn Error>> {
+    let server = Arc::new(server);
+    let connections = Arc::new(AtomicUsize::new(0));
+    for stream in listener.incoming() {
+        let stream = stream?;
+        stream.set_read_timeout(Some(STREAM_TIMEOUT))?;
+        stream.set_write_timeout(Some(STREAM_TIMEOUT))?;
+        let connection = match Connection::open(&connections) {
+            Some(connection) => connection,
+            None => {
+                let _ = status(503, "too many connections, try again later").write_to(&mut &stream);
+                continue;
+            }
+        };
+        let token = token.to_string();
+        let server = Arc::clone(&server);
+        thread::spawn(move || {
+            let _connection = connection;
+            let client = match stream.peer_addr() {
+                Ok(address) => address.ip(),
+                Err(_) => return,
+            };
+            let response = match Message::read_from(&mut BufReader::new(&stream)) {
+                Ok(request) => server.admit(client).unwrap_or_else(|| {
+                    let response = answer(&request, &token, Some(&server));
+                    server.record(client, &request, &response);
+                    response
+                }),
+                Err(e) => status(400, &e.to_string()),
+            };
+            let _ = response.write_to(&mut &stream);
+        });
+    }
+    Ok(())
+}
+
+// a connection being served, counted until it is dropped
+#[cfg(feature = "server")]
+struct Connection(Arc<AtomicUsize>);
+
+#[cfg(feature = "server")]
+impl Connection {
+    // counts a new connection, or returns None if there are already
+    // MAX_CONNECTIONS
+    fn open(connections: &Arc<AtomicUsize>) -> Option<Self> {
+        connections
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |open| {
+                (open < MAX_CONNECTIONS).then_some(open + 1)
+            })
+            .ok()
+            .map(|_| Connection(Arc::clone(connections)))
+    }
+}
+
+#[cfg(feature = "server")]
+impl Drop for Connection {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+// answers a request, or tells why it was refused; without the
+// state of a server, the recoveries are performed at once and no
+// event is sent
+#[cfg(feature = "server")]
+pub(crate) fn answer(request: &Message, token: &str, server: Option<&Server>) -> Message {
+    let mut words = request.start.split(' ');
+    let (method, target) = (
+        words.next().unwrap_or_default(),
+        words.next().unwrap_or_default(),
+    );
+    let given = request
+        .header("authorization")
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .unwrap_or_default();
+    // compares every byte, so the time does not depend on the token
+    let authorized: bool = given.as_bytes().ct_eq(token.as_bytes()).into();
+    if !authorized {
+        return status(401, "missing or invalid token");
+    }
+    if method != "POST" {
+        return status(405, "only POST is allowed");
+    }
+    let (path, query) = parse_target(target);
+    let parameter = |name: &str| {
+        query
+            .iter()
+            .find(|(other, _)| other == name)
+            .map(|(_, value)| value.as_str())
+    };
+    if path == VETO_PATH {
+        return match (server, parameter("id")) {
+            (Some(server), Some(id)) => server.veto(id, request),
+            (Some(_), None) => status(400, "the parameter id is missing"),
+            (None, _) => status(404, "no recovery is waiting"),
+        };
+    }
+    let operation: Operation = match path.parse() {
+        Ok(operation) => operation,
+        Err(_) => return status(404, "unknown operation"),
+    };
+    let server = match server {
+        Some(server) => server,
+        None => return perform(operation, parameter, request),
+    };
+    let operation_field = || ("operation", Json::from(operation.to_string()));
+    if let Some(fragments) = submitted_shares(operation, request) {
+        if let Err(e) = server.authenticate_holders(&fragments, request) {
+            return status(401, &e.to_string());
+        }
+        let fingerprints: Vec<_> = fragments.iter().map(Fragment::fingerprint).collect();
+        server.webhooks.send(
+            "shares_submitted",
+            vec![
+                operation_field(),
+                ("shares", fragments.len().into()),
+                ("fingerprints", fingerprints.into()),
+            ],
+        );
+        match server.hold(operation, request, &fragments) {
+            Ok(Some(answer)) => return answer,
+            Ok(None) => {}
+            Err(e) => return status(400, &e.to_string()),
+        }
+    }
+    let response = perform(operation, parameter, request);
+    if response.start.split(' ').nth(1) != Some("200") {
+        return response;
+    }
+    match operation {
+        Operation::Split | Operation::Encrypt => {
+            let value = |name| Json::from(parameter(name).and_then(|n| n.parse::<usize>().ok()));
+            server.webhooks.send(
+                "split",
+                vec![
+                    operation_field(),
+                    ("total", value("n")),
+                    ("threshold", value("k")),
+                ],
+            );
+        }
+        Operation::Combine => server
+            .webhooks
+            .send("threshold_reached", vec![operation_field()]),
+        Operation::Decrypt => {
+            server
+                .webhooks
+                .send("threshold_reached", vec![operation_field()]);
+            let file = response
+                .header(FILE_NAME_HEADER)
+                .and_then(|name| decode_header(name).ok())
+                .map(|name| String::from_utf8_lossy(&name).into_owned());
+            server
+                .webhooks
+                .send("decryption_performed", vec![("file", file.into())]);
+        }
+    }
+    response
+}
+
+// performs an operation, or tells why it failed
+#[cfg(feature = "server")]
+fn perform<'a>(
+    operation: Operation,
+    parameter: impl Fn(&str) -> Option<&'a str> + Copy,
+    request: &Message,
+) -> Message {
+    let result = match operation {
+        Operation::Split => split(parameter, &request.body),
+        Operation::Combine => combine(&request.body),
+        Operation::Encrypt => encrypt(parameter, &request.body),
+        Operation::Decrypt => decrypt(request),
+    };
+    result.unwrap_or_else(|e| status(400, &e.to_string()))
+}
+
+// the shares submitted to recover a secret, or None for the other
+// operations and for shares that cannot be read
+#[cfg(feature = "server")]
+fn submitted_shares(operation: Operation, request: &Message) -> Option<Vec<Fragment>> {
+    let shares = match operation {
+        Operation::Combine => String::from_utf8(request.body.clone()).ok()?,
+        Operation::Decrypt => {
+            String::from_utf8(decode_header(request.header(SHARES_HEADER)?).ok()?).ok()?
+        }
+        _ => return None,
+    };
+    fragments::parse_fragments(&shares).ok()
+}
+
+// a response with a status and a message, or the body of an answer
+#[cfg(feature = "server")]
+pub(crate) fn status(code: u16, message: &str) -> Message {
+    let reason = match code {
+        200 => "OK",
+        202 => "Accepted",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        429 => "Too Many Requests",
+        404 => "Not Found",
+        503 => "Service Unavailable",
+        _ => "Method Not Allowed",
+    };
+    Message::new(
+        format!("HTTP/1.1 {} {}", code, reason),
+        format!("{}\n", message).into_bytes(),
+    )
+}
+
+// reads the number of shares and the threshold of the query
+#[cfg(feature = "server")]
+fn scheme<'a>(
+    parameter: impl Fn(&str) -> Option<&'a str>,
+) -> Result<(usize, usize), Box<dyn Error>> {
+    let value = |name: &str| -> Result<usize, Box<dyn Error>> {
+        Ok(parameter(name)
+            .ok_or_else(|| ArgumentError(format!("the parameter {} is missing", name)))?
+            .parse()?)
+    };
+    let (total_evals, min_required_evals) = (value("n")?, value("k")?);
+    crate::check_scheme(total_evals, min_required_evals)?;
+    Ok((total_evals, min_required_evals))
+}
+
+// splits the body into a shares file
+#[cfg(feature = "server")]
+fn split<'a>(
+    parameter: impl Fn(&str) -> Option<&'a str>,
+    body: &[u8],
+) -> Result<Message, Box<dyn Error>> {
+    let (total_evals, min_required_evals) = scheme(parameter)?;
+    if body.is_empty() {
+        return Err(Box::new(ArgumentError("the secret is empty".into())));
+    }
+    let key = SecretKey::new(body.to_vec());
+    let fragments = crate::key_fragments(&key, total_evals, min_required_evals, None)?;
+    let mut shares = Vec::new();
+    fragments::write_fragments(&fragments, Format::Lines, &mut shares)?;
+    Ok(status(200, "").with_body(shares))
+}
+
+// recovers the secret of the shares file in the body
+#[cfg(feature = "server")]
+fn combine(body: &[u8]) -> Result<Message, Box<dyn Error>> {
+    let text = std::str::from_utf8(body)?;
+    let key = crate::fragments_key(fragments::parse_fragments(text)?)?;
+    Ok(status(200, "").with_body(key.expose().to_vec()))
+}
+
+// encrypts the body in a private directory, returning the encrypted
+// file and its shares file
+#[cfg(feature = "server")]
+fn encrypt<'a>(
+    parameter: impl Fn(&str) -> Option<&'a str> + Copy,
+    body: &[u8],
+) -> Result<Message, Box<dyn Error>> {
+    let (total_evals, min_required_evals) = scheme(parameter)?;
+    let name = parameter("name").unwrap_or("file");
+    if name.is_empty() || name == "." || name == ".." || name.contains(['/', '\\']) {
+        return Err(Box::new(ArgumentError(format!(
+            "'{}' is not a file name",
+            name
+        ))));
+    }
+    let random = SecretKey::generate(16)?;
+    let dir = env::temp_dir().join(format!(
+        "shared_secrets-{}",
+        encoding::to_hex(random.expose())
+    ));
+    let mut builder = fs::DirBuilder::new();
+    #[cfg(unix)]
+    std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+    builder.create(&dir)?;
+    let result = encrypt_in(&dir, name, total_evals, min_required_evals, body);
+    fs::remove_dir_all(&dir)?;
+    result
+}
+
+#[cfg(feature = "server")]
+fn encrypt_in(
+    dir: &Path,
+    name: &str,
+    total_evals: usize,
+    min_required_evals: usize,
+    body: &[u8],
+) -> Result<Message, Box<dyn Error>> {
+    fs::write(dir.join(name), body)?;
+    // the shares are the only way to unlock the file, so the password
+    // is random and forgotten
+    let password = SecretKey::generate(32)?;
+    let config = crate::EncryptConfig::new(
+        dir.join(name),
+        &dir.to_string_lossy(),
+        name,
+        encoding::to_hex(password.expose()).into(),
+        total_evals,
+        min_required_evals,
+    );
+    crate::run_encrypt(config)?;
+    let encrypted = fs::read(dir.join(format!("{}.aes", name)))?;
+    let shares = fs::read(dir.join(format!("{}.frg", name)))?;
+    Ok(status(200, "")
+        .with_body(encrypted)
+        .with_header(SHARES_HEADER, encode_header(&shares)))
+}
+
+// decrypts the encrypted file in the body with the shares sent
+#[cfg(feature = "server")]
+fn decrypt(request: &Message) -> Result<Message, Box<dyn Error>> {
+    let shares = request
+        .header(SHARES_HEADER)
+        .ok_or_else(|| ArgumentError("the shares were not sent".into()))?;
+    let shares = String::from_utf8(decode_header(shares)?)?;
+    let mut reader = &request.body[..];
+    let header = Header::read_from(&mut reader)?;
+    let key = crate::shares_cipher(fragments::parse_fragments(&shares)?, &header, false)?;
+    let cipher = crate::unwrap_data_key(&header, &key)?;
+    let mut plaintext = Vec::new();
+    crate::decrypt_into(&header, &cipher, &mut reader, &mut plaintext)?;
+    Ok(status(200, "")
+        .with_body(plaintext)
+        .with_header(FILE_NAME_HEADER, encode_header(&header.file_name)))
+}
+
+// reads a line ended by CRLF or LF, without the line break
+fn read_line(reader: &mut impl BufRead) -> Result<String, Box<dyn Error>> {
+    let mut line = Vec::new();
+    reader
+        .by_ref()
+        .take(MAX_LINE as u64 + 1)
+        .read_until(b'\n', &mut line)?;
+    if line.last() != Some(&b'\n') {
+        return Err(Box::new(CorruptFileError(
+            "the message ends early or has a line too long".into(),
+        )));
+    }
+    let line =
+        String::from_utf8(line).map_err(|_| CorruptFileError("a line is not UTF-8".into()))?;
+    Ok(line
+        .trim_end_matches('\n')
+        .trim_end_matches('\r')
+        .to_string())
+}
+
+// encodes every byte that is not unreserved in a URL
+fn percent_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+// decodes the bytes encoded with percent_encode, keeping the
+// invalid escapes as they are
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = bytes
+            .get(i + 1..i + 3)
+            .filter(|_| bytes[i] == b'%')
+            .and_then(|hex| encoding::from_hex(std::str::from_utf8(hex).ok()?))
+            .map(|byte| byte[0]);
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(if bytes[i] == b'+' { b' ' } else { bytes[i] });
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/////////////////////////////////
+// Unit testing of the module. //
+/////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn messages() {
+        let message = Message::new("POST /split?n=5&k=3 HTTP/1.1", b"secret".to_vec())
+            .with_header("Authorization", "Bearer token");
+        let mut written = Vec::new();
+        message.write_to(&mut written).unwrap();
+        let read = Message::read_from(&mut &written[..]).unwrap();
+        assert_eq!(read.start, message.start);
+        assert_eq!(read.header("authorization"), Some("Bearer token"));
+        assert_eq!(read.header("Content-Length"), Some("6"));
+        assert_eq!(read.body, b"secret");
+        assert!(Message::read_from(&mut &b"POST / HTTP/1.1\r\nno header\r\n\r\n"[..]).is_err());
+        let truncated = b"POST / HTTP/1.1\r\ncontent-length: 10\r\n\r\nshort";
+        assert!(Message::read_from(&mut &truncated[..]).is_err());
+    }
+
+    #[test]
+    fn targets() {
+        let (path, query) = parse_target("/encrypt?n=5&name=my%20file.txt&k=3");
+        assert_eq!(path, "/encrypt");
+        assert_eq!(query[1], ("name".into(), "my file.txt".into()));
+        assert_eq!(percent_decode(&percent_encode("año/1 %")), "año/1 %");
+        assert_eq!(parse_target("/combine"), ("/combine", Vec::new()));
+        assert_eq!("split".parse::<Operation>().unwrap(), Operation::Split);
+        assert!("sign".parse::<Operation>().is_err());
+    }
+
+    #[test]
+    fn loopback_only() {
+        assert!(check_loopback("127.0.0.1:7272", false).is_ok());
+        assert!(check_loopback("[::1]:7272", false).is_ok());
+        assert!(check_loopback("0.0.0.0:7272", false).is_err());
+        assert!(check_loopback("192.0.2.1:7272", false).is_err());
+        assert!(check_loopback("0.0.0.0:7272", true).is_ok());
+    }
+
+    #[cfg(feature = "server")]
+    #[test]
+    fn client_limits() {
+        let server = Server::new(
+            RecoveryPolicy::default(),
+            ClientLimits {
+                requests_per_minute: Some(5),
+                max_failures: Some(2),
+                lockout: Duration::from_millis(200),
+            },
+            Webhooks::default(),
+        );
+        let (alice, bob): (IpAddr, IpAddr) =
+            ("10.0.0.1".parse().unwrap(), "10.0.0.2".parse().unwrap());
+        let send = |client, request: &Message, token| {
+            server.admit(client).unwrap_or_else(|| {
+                let response = answer(request, token, Some(&server));
+                server.record(client, request, &response);
+                response
+            })
+        };
+        let code = |response: Message| response.start.split(' ').nth(1).unwrap().to_string();
+        let split = Message::new("POST /split?n=3&k=2 HTTP/1.1", b"secret".to_vec())
+            .with_header("authorization", "Bearer token");
+        let combine = Message::new("POST /combine HTTP/1.1", Vec::new())
+            .with_header("authorization", "Bearer token");
+
+        // a request refused for its parameters is not a failure
+        let invalid = Message::new("POST /split?n=1&k=2 HTTP/1.1", b"secret".to_vec())
+            .with_header("authorization", "Bearer token");
+        assert_eq!(code(send(alice, &invalid, "token")), "400");
+        assert_eq!(code(send(alice, &split, "token")), "200");
+        // a wrong token and shares that do not recover lock out
+        assert_eq!(code(send(alice, &split, "other")), "401");
+        assert_eq!(code(send(alice, &combine, "token")), "400");
+        let locked = send(alice, &split, "token");
+        assert_eq!(code(locked.clone()), "429");
+        assert!(String::from_utf8_lossy(&locked.body).contains("failed attempts"));
+        // only the client that failed
+        assert_eq!(code(send(bob, &split, "token")), "200");
+        thread::sleep(Duration::from_millis(250));
+        assert_eq!(code(send(alice, &split, "token")), "200");
+
+        // more requests than the rate in a minute
+        for _ in 0..4 {
+            assert_eq!(code(send(bob, &split, "token")), "200");
+        }
+        let limited = send(bob, &split, "token");
+        assert_eq!(code(limited.clone()), "429");
+        assert!(String::from_utf8_lossy(&limited.body).contains("5 requests in a minute"));
+    }
+
+    #[cfg(feature = "server")]
+    #[test]
+    fn holder_tokens() {
+        let tokens_file = env::temp_dir().join(format!("holder-tokens-{}", std::process::id()));
+        let path = tokens_file.to_str().unwrap();
+        let alice = enroll_holder(path, "alice", "1").unwrap();
+        let bob = enroll_holder(path, "bob", "02").unwrap();
+        assert_ne!(alice, bob);
+        assert!(enroll_holder(path, "alice", "3").is_err());
+        assert!(enroll_holder(path, "carol", "2").is_err());
+        assert!(enroll_holder(path, "carol", "0").is_err());
+        let holders = read_holder_tokens(path).unwrap();
+        assert_eq!(holders.len(), 2);
+        assert_eq!(holders[1].index, "2");
+        assert!(!fs::read_to_string(path).unwrap().contains(&alice));
+        fs::remove_file(&tokens_file).unwrap();
+
+        let server = Server::new(
+            RecoveryPolicy::default(),
+            ClientLimits::default(),
+            Webhooks::default(),
+        )
+        .with_holders(holders);
+        let split = Message::new("POST /split?n=3&k=2 HTTP/1.1", b"secret".to_vec())
+            .with_header("authorization", "Bearer token");
+        let shares = String::from_utf8(answer(&split, "token", Some(&server)).body).unwrap();
+        let mut fragments = fragments::parse_fragments(&shares).unwrap();
+        let anonymous = fragments[..2]
+            .iter()
+            .map(Fragment::to_line)
+            .collect::<Vec<_>>();
+        fragments[0].holder = Some("alice".into());
+        fragments[1].holder = Some("bob".into());
+        let body = format!("{}\n{}\n", fragments[0].to_line(), fragments[1].to_line());
+        let combine = |body: &str, tokens: Option<String>| {
+            let mut request = Message::new("POST /combine HTTP/1.1", body.as_bytes().to_vec())
+                .with_header("authorization", "Bearer token");
+            if let Some(tokens) = tokens {
+                request =
+                    request.with_header(HOLDER_TOKENS_HEADER, encode_header(tokens.as_bytes()));
+            }
+            answer(&request, "token", Some(&server))
+        };
+        let tokens = format!("alice:{}\nbob:{}\n", alice, bob);
+        let response = combine(&body, Some(tokens.clone()));
+        assert!(response.start.contains(" 200 "));
+        assert_eq!(response.body, b"secret");
+        // a missing or wrong token, or a share of nobody enrolled
+        assert!(combine(&body, None).start.contains(" 401 "));
+        let wrong = combine(&body, Some(format!("alice:{}\nbob:{}\n", alice, alice)));
+        assert!(wrong.start.contains(" 401 "));
+        assert!(String::from_utf8_lossy(&wrong.body).contains("wrong token for bob"));
+        // a holder only submits the share enrolled for them, once
+        fragments[1].holder = Some("alice".into());
+        let other = format!("{}\n{}\n", fragments[0].to_line(), fragments[1].to_line());
+        let response = combine(&other, Some(tokens.clone()));
+        assert!(response.start.contains(" 401 "));
+        assert!(String::from_utf8_lossy(&response.body).contains("not the one enrolled for alice"));
+        let twice = format!("{}\n{}\n", fragments[0].to_line(), fragments[0].to_line());
+        let response = combine(&twice, Some(tokens));
+        assert!(response.start.contains(" 401 "));
+        assert!(String::from_utf8_lossy(&response.body).contains("more than one share"));
+        let anonymous = Message::new("POST /combine HTTP/1.1", anonymous.join("\n").into_bytes())
+            .with_header("authorization", "Bearer token");
+        assert!(answer(&anonymous, "token", Some(&server))
+            .start
+            .contains(" 401 "));
+    }
+
+    #[cfg(feature = "server")]
+    #[test]
+    fn webhook_events() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/events", listener.local_addr().unwrap());
+        let receiver = thread::spawn(move || {
+            let mut events = Vec::new();
+            for stream in listener.incoming().take(3) {
+                let stream = stream.unwrap();
+                let request = Message::read_from(&mut BufReader::new(&stream)).unwrap();
+                Message::new("HTTP/1.1 204 No Content", Vec::new())
+                    .write_to(&mut &stream)
+                    .unwrap();
+                events.push(Json::parse(std::str::from_utf8(&request.body).unwrap()).unwrap());
+            }
+            events
+        });
+        let server = Server::new(
+            RecoveryPolicy::default(),
+            ClientLimits::default(),
+            Webhooks::parse(&[url]).unwrap(),
+        );
+        let request = |start: &str, body: Vec<u8>| {
+            let request = Message::new(start, body).with_header("authorization", "Bearer token");
+            answer(&request, "token", Some(&server))
+        };
+        let shares = request("POST /split?n=3&k=2 HTTP/1.1", b"secret".to_vec()).body;
+        let fragments = fragments::parse_fragments(std::str::from_utf8(&shares).unwrap()).unwrap();
+        let response = request("POST /combine HTTP/1.1", shares);
+        assert_eq!(response.body, b"secret");
+
+        let events = receiver.join().unwrap();
+        let field = |i: usize, name| events[i].get(name).cloned();
+        assert_eq!(field(0, "event"), Some(Json::from("split")));
+        assert_eq!(field(0, "total"), Some(Json::from(3)));
+        assert_eq!(field(1, "event"), Some(Json::from("shares_submitted")));
+        assert_eq!(field(1, "shares"), Some(Json::from(3)));
+        let fingerprints: Vec<_> = fragments.iter().map(Fragment::fingerprint).collect();
+        assert_eq!(field(1, "fingerprints"), Some(Json::from(fingerprints)));
+        assert_eq!(field(2, "event"), Some(Json::from("threshold_reached")));
+        assert_eq!(field(2, "operation"), Some(Json::from("combine")));
+        // no event tells the shares or the secret
+        let events: String = events.iter().map(Json::to_string).collect();
+        assert!(!events.contains(&fragments[0].share.1));
+    }
+
+    #[cfg(all(feature = "server", unix))]
+    #[test]
+    fn recovery_delay() {
+        let events_file = env::temp_dir().join(format!("recovery-events-{}", std::process::id()));
+        let server = Server::new(
+            RecoveryPolicy {
+                delay: Duration::from_millis(300),
+                notify: Some(format!("cat >> '{}'", events_file.display())),
+            },
+            ClientLimits::default(),
+            Webhooks::default(),
+        );
+        let tokens_file = env::temp_dir().join(format!("recovery-holders-{}", std::process::id()));
+        let path = tokens_file.to_str().unwrap();
+        let tokens: Vec<_> = ["alice", "bob", "carol"]
+            .iter()
+            .zip(1..)
+            .map(|(holder, index)| {
+                let token = enroll_holder(path, holder, &index.to_string()).unwrap();
+                format!("{}:{}\n", holder, token)
+            })
+            .collect();
+        let server = server.with_holders(read_holder_tokens(path).unwrap());
+        fs::remove_file(&tokens_file).unwrap();
+        let key = SecretKey::new(b"secret".to_vec());
+        let mut fragments = crate::key_fragments(&key, 3, 2, None).unwrap();
+        for (fragment, holder) in fragments.iter_mut().zip(&["alice", "bob", "carol"]) {
+            fragment.holder = Some(holder.to_string());
+        }
+        let combine = |fragments: &[fragments::Fragment]| {
+            let shares: String = fragments.iter().map(|f| f.to_line() + "\n").collect();
+            Message::new("POST /combine HTTP/1.1", shares.into_bytes())
+                .with_header("authorization", "Bearer token")
+                .with_header(
+                    HOLDER_TOKENS_HEADER,
+                    encode_header(tokens.concat().as_bytes()),
+                )
+        };
+        let answer = |request: &Message| answer(request, "token", Some(&server));
+
+        // the first request waits, and so does the same one repeated,
+        // however its shares are written
+        let request = combine(&fragments[..2]);
+        let response = answer(&request);
+        assert!(response.start.starts_with("HTTP/1.1 202"));
+        let id = response.header(RECOVERY_HEADER).unwrap().to_string();
+        assert!(answer(&request).start.starts_with("HTTP/1.1 202"));
+        let reordered = combine(&[fragments[1].clone(), fragments[0].clone()]);
+        assert_eq!(
+            answer(&reordered).header(RECOVERY_HEADER),
+            Some(id.as_str())
+        );
+        // another recovery is vetoed by an enrolled holder and stays
+        // refused
+        let vetoed = combine(&fragments[1..]);
+        let other = answer(&vetoed).header(RECOVERY_HEADER).unwrap().to_string();
+        assert_ne!(other, id);
+        let veto = |id: &str, token: &str| {
+            answer(
+                &Message::new(format!("POST /veto?id={} HTTP/1.1", id), Vec::new())
+                    .with_header("authorization", "Bearer token")
+                    .with_header(HOLDER_TOKENS_HEADER, encode_header(token.as_bytes())),
+            )
+        };
+        assert!(veto(&other, "").start.starts_with("HTTP/1.1 401"));
+        assert!(veto(&other, "alice:0000").start.starts_with("HTTP/1.1 401"));
+        assert!(veto(&other, "mallory:0000")
+            .start
+            .starts_with("HTTP/1.1 401"));
+        assert!(veto("0000", &tokens[0]).start.starts_with("HTTP/1.1 404"));
+        assert!(veto(&other, &tokens[0]).start.starts_with("HTTP/1.1 200"));
+        thread::sleep(Duration::from_millis(400));
+        assert!(answer(&vetoed).start.starts_with("HTTP/1.1 403"));
+        // the delay passed
+        let response = answer(&request);
+        assert!(response.start.starts_with("HTTP/1.1 200"));
+        assert_eq!(response.body, b"secret");
+        assert!(answer(&request).start.starts_with("HTTP/1.1 202"));
+
+        let events = fs::read_to_string(&events_file).unwrap();
+        fs::remove_file(&events_file).unwrap();
+        let events: Vec<_> = events
+            .lines()
+            .map(|line| Json::parse(line).unwrap())
+            .collect();
+        let names: Vec<_> = events
+            .iter()
+            .map(|event| event.get("event").and_then(Json::as_str).unwrap())
+            .collect();
+        assert_eq!(
+            names,
+            [
+                "recovery_requested",
+                "recovery_requested",
+                "recovery_vetoed",
+                "recovery_performed",
+                "recovery_requested"
+            ]
+        );
+        assert_eq!(
+            events[0].get("id").and_then(Json::as_str),
+            Some(id.as_str())
+        );
+        assert_eq!(
+            events[0].get("operation").and_then(Json::as_str),
+            Some("combine")
+        );
+        assert_eq!(
+            events[2].get("holder").and_then(Json::as_str),
+            Some("alice")
+        );
+        // without the state of a server nothing waits
+        let response = super::answer(&request, "token", None);
+        assert_eq!(response.body, b"secret");
+    }
+
+    #[cfg(feature = "server")]
+    #[test]
+    fn recoveries_expire() {
+        let server = Server::new(
+            RecoveryPolicy {
+                delay: Duration::from_secs(60),
+                notify: None,
+            },
+            ClientLimits::default(),
+            Webhooks::default(),
+        );
+        let key = SecretKey::new(b"secret".to_vec());
+        let fragments = crate::key_fragments(&key, 3, 2, None).unwrap();
+        let combine = |fragments: &[fragments::Fragment]| {
+            let mut shares = Vec::new();
+            fragments::write_fragments(fragments, Format::Lines, &mut shares).unwrap();
+            Message::new("POST /combine HTTP/1.1", shares)
+                .with_header("authorization", "Bearer token")
+        };
+        let request = combine(&fragments[..2]);
+        let answer = |request: &Message| answer(request, "token", Some(&server));
+        let id = answer(&request)
+            .header(RECOVERY_HEADER)
+            .unwrap()
+            .to_string();
+        // without enrolled holders nobody can veto
+        let veto = Message::new(format!("POST /veto?id={} HTTP/1.1", id), Vec::new())
+            .with_header("authorization", "Bearer token");
+        assert!(answer(&veto).start.starts_with("HTTP/1.1 403"));
+        // a vetoed recovery is forgotten once it expires
+        let expired = match Instant::now().checked_sub(PENDING_LIFETIME) {
+            Some(expired) => expired,
+            None => return,
+        };
+        for recovery in server.pending.lock().unwrap().iter_mut() {
+            recovery.vetoed = true;
+            recovery.ready = expired;
+        }
+        let response = answer(&request);
+        assert!(response.start.starts_with("HTTP/1.1 202"));
+        assert_ne!(response.header(RECOVERY_HEADER), Some(id.as_str()));
+        // no other recovery waits once there are too many
+        let mut pending = server.pending.lock().unwrap();
+        while pending.len() < MAX_PENDING {
+            let id = pending.len().to_string();
+            pending.push(Pending {
+                target: blake3::hash(id.as_bytes()),
+                id,
+                ready: Instant::now(),
+                vetoed: false,
+            });
+        }
+        drop(pending);
+        assert!(answer(&combine(&fragments[1..]))
+            .start
+            .starts_with("HTTP/1.1 503"));
+        assert!(answer(&request).start.starts_with("HTTP/1.1 202"));
+    }
+
+    #[cfg(feature = "server")]
+    #[test]
+    fn connections() {
+        let connections = Arc::new(AtomicUsize::new(0));
+        let open: Vec<_> = (0..MAX_CONNECTIONS)
+            .map(|_| Connection::open(&connections).unwrap())
+            .collect();
+        assert!(Connection::open(&connections).is_none());
+        drop(open);
+        assert_eq!(connections.load(Ordering::SeqCst), 0);
+        assert!(Connection::open(&connections).is_some());
+    }
+}
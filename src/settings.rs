@@ -0,0 +1,259 @@
+use std::env;
+use std::error::Error;
+use std::fs;
+use std::path::PathBuf;
+
+use crate::crypto::CipherSuite;
+use crate::error::CorruptFileError;
+use crate::fragments::Format;
+
+/// Content of the configuration file written by `config init`,
+/// with every setting commented out.
+pub const TEMPLATE: &str = "\
+# Defaults of shared_secrets, overridden by the arguments.
+
+# Total number of shares and shares needed to decrypt
+# n = 5
+# k = 3
+
+# Cipher of the encrypted files: aes-gcm, aes-gcm-siv or aes-128-gcm
+# cipher = \"aes-gcm\"
+
+# Directory where the encrypted files and the shares are written
+# output_dir = \".\"
+
+# Format of the shares files: lines, csv or tsv
+# format = \"lines\"
+
+# Most memory used to encrypt and decrypt, in bytes or with
+# the suffix K, M or G
+# max_memory = \"512M\"
+";
+
+/// Defaults of the arguments of the encrypt command, read from
+/// the configuration file.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Settings {
+    pub n: Option<usize>,
+    pub k: Option<usize>,
+    pub cipher: Option<CipherSuite>,
+    pub output_dir: Option<String>,
+    pub format: Option<Format>,
+    pub max_memory: Option<u64>,
+}
+
+// A value of the configuration file
+enum Value {
+    Integer(usize),
+    String(String),
+}
+
+impl Settings {
+    /// Returns the path of the configuration file,
+    /// `shared_secrets/config.toml` in the directory given by
+    /// XDG_CONFIG_HOME or else in `~/.config`.
+    ///
+    /// # Returns
+    ///
+    /// The path, or None if neither XDG_CONFIG_HOME nor HOME is set.
+    pub fn path() -> Option<PathBuf> {
+        let dir = match env::var_os("XDG_CONFIG_HOME").map(PathBuf::from) {
+            Some(dir) if dir.is_absolute() => dir,
+            _ => PathBuf::from(env::var_os("HOME")?).join(".config"),
+        };
+        Some(dir.join("shared_secrets").join("config.toml"))
+    }
+
+    /// Reads the configuration file.
+    ///
+    /// # Returns
+    ///
+    /// The settings in the file, or the empty settings if
+    /// there is no configuration file.
+    ///
+    /// # Errors
+    ///
+    /// This method returns an error if the file cannot be read
+    /// or if it is malformed.
+    pub fn load() -> Result<Settings, Box<dyn Error>> {
+        let path = match Settings::path() {
+            Some(path) if path.exists() => path,
+            _ => return Ok(Settings::default()),
+        };
+        Ok(Settings::parse(&fs::read_to_string(&path)?)
+            .map_err(|e| CorruptFileError(format!("{}: {}", path.display(), e.0)))?)
+    }
+
+    /// Parses the text of a configuration file.
+    ///
+    /// The file is a subset of TOML: lines `key = value` where the
+    /// value is an integer or a string in double quotes, blank lines
+    /// and comments starting with `#`. The keys are n, k, cipher,
+    /// output_dir, format and max_memory.
+    ///
+    /// # Parameters
+    ///
+    /// - text: The content of the configuration file.
+    ///
+    /// # Errors
+    ///
+    /// A CorruptFileError telling the line with an unknown key or
+    /// an invalid value.
+    pub fn parse(text: &str) -> Result<Settings, CorruptFileError> {
+        let mut settings = Settings::default();
+        for (i, line) in text.lines().enumerate() {
+            let corrupt = |reason: String| CorruptFileError(format!("line {}: {}", i + 1, reason));
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| corrupt("expected 'key = value'".into()))?;
+            let key = key.trim();
+            let invalid = || corrupt(format!("invalid value of {}", key));
+            match (key, parse_value(value.trim()).ok_or_else(invalid)?) {
+                ("n", Value::Integer(n)) => settings.n = Some(n),
+                ("k", Value::Integer(k)) => settings.k = Some(k),
+                ("cipher", Value::String(cipher)) => {
+                    settings.cipher = Some(cipher.parse().map_err(|_| invalid())?)
+                }
+                ("output_dir", Value::String(dir)) if !dir.is_empty() => {
+                    settings.output_dir = Some(dir)
+                }
+                ("format", Value::String(format)) => {
+                    settings.format = Some(format.parse().map_err(|_| invalid())?)
+                }
+                ("max_memory", Value::Integer(bytes)) => settings.max_memory = Some(bytes as u64),
+                ("max_memory", Value::String(size)) => {
+                    settings.max_memory = Some(parse_size(&size).ok_or_else(invalid)?)
+                }
+                ("n" | "k" | "cipher" | "output_dir" | "format", _) => return Err(invalid()),
+                _ => return Err(corrupt(format!("unknown key '{}'", key))),
+            }
+        }
+        Ok(settings)
+    }
+}
+
+/// Parses an amount of memory, a number of bytes optionally
+/// followed by the suffix K, M or G, in powers of 1024.
+///
+/// # Parameters
+///
+/// - size: The amount, such as 4096 or 512M.
+///
+/// # Returns
+///
+/// The number of bytes, or None if the amount is invalid.
+pub fn parse_size(size: &str) -> Option<u64> {
+    let size = size.trim();
+    let (digits, unit) = match size.char_indices().last()? {
+        (i, 'K' | 'k') => (&size[..i], 1 << 10),
+        (i, 'M' | 'm') => (&size[..i], 1 << 20),
+        (i, 'G' | 'g') => (&size[..i], 1 << 30),
+        _ => (size, 1),
+    };
+    digits.parse::<u64>().ok()?.checked_mul(unit)
+}
+
+// parses the value of a line, an integer or a string in double
+// quotes with the escapes \" and \\, followed by an optional comment
+fn parse_value(value: &str) -> Option<Value> {
+    let rest = match value.strip_prefix('"') {
+        Some(rest) => rest,
+        None => {
+            let value = value.split('#').next().unwrap().trim();
+            return value.parse().ok().map(Value::Integer);
+        }
+    };
+    let mut string = String::new();
+    let mut chars = rest.chars();
+    loop {
+        match chars.next()? {
+            '"' => break,
+            '\\' => match chars.next()? {
+                c @ ('"' | '\\') => string.push(c),
+                _ => return None,
+            },
+            c => string.push(c),
+        }
+    }
+    let comment = chars.as_str().trim();
+    if !comment.is_empty() && !comment.starts_with('#') {
+        return None;
+    }
+    Some(Value::String(string))
+}
+
+/////////////////////////////////
+// Unit testing of the module. //
+/////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_settings() {
+        let text = "# defaults\n\nn = 5\nk=3 # majority\ncipher = \"aes-gcm-siv\"\n\
+            output_dir = \"/backups/\\\"vault\\\"\"\nformat = \"csv\" # spreadsheet\n";
+        let settings = Settings::parse(text).unwrap();
+        assert_eq!(settings.n, Some(5));
+        assert_eq!(settings.k, Some(3));
+        assert_eq!(settings.cipher, Some(CipherSuite::Aes256GcmSiv));
+        assert_eq!(settings.output_dir, Some("/backups/\"vault\"".into()));
+        assert_eq!(settings.format, Some(Format::Csv));
+        assert_eq!(Settings::parse("").unwrap(), Settings::default());
+        let settings = Settings::parse("max_memory = \"2G\"").unwrap();
+        assert_eq!(settings.max_memory, Some(2 << 30));
+        let settings = Settings::parse("max_memory = 4096").unwrap();
+        assert_eq!(settings.max_memory, Some(4096));
+    }
+
+    #[test]
+    fn template() {
+        assert_eq!(Settings::parse(TEMPLATE).unwrap(), Settings::default());
+        let uncommented = TEMPLATE
+            .replace("# n", "n")
+            .replace("# format", "format")
+            .replace("# max_memory", "max_memory");
+        let settings = Settings::parse(&uncommented).unwrap();
+        assert_eq!(settings.n, Some(5));
+        assert_eq!(settings.format, Some(Format::Lines));
+        assert_eq!(settings.max_memory, Some(512 << 20));
+    }
+
+    #[test]
+    fn sizes() {
+        assert_eq!(parse_size("4096"), Some(4096));
+        assert_eq!(parse_size("64K"), Some(64 << 10));
+        assert_eq!(parse_size("512m"), Some(512 << 20));
+        assert_eq!(parse_size("3G"), Some(3 << 30));
+        assert_eq!(parse_size("G"), None);
+        assert_eq!(parse_size("-1M"), None);
+        assert_eq!(parse_size("99999999999999999999G"), None);
+    }
+
+    #[test]
+    fn invalid_settings() {
+        let error = |text: &str| Settings::parse(text).unwrap_err().0;
+        assert_eq!(
+            error("n = 5\nthreshold = 3\n"),
+            "line 2: unknown key 'threshold'"
+        );
+        assert_eq!(error("n = \"5\""), "line 1: invalid value of n");
+        assert_eq!(error("k = -1"), "line 1: invalid value of k");
+        assert_eq!(error("cipher = \"des\""), "line 1: invalid value of cipher");
+        assert_eq!(error("format = \"csv"), "line 1: invalid value of format");
+        assert_eq!(
+            error("format = \"csv\" tsv"),
+            "line 1: invalid value of format"
+        );
+        assert_eq!(error("[defaults]"), "line 1: expected 'key = value'");
+        assert_eq!(
+            error("max_memory = \"lots\""),
+            "line 1: invalid value of max_memory"
+        );
+    }
+}
@@ -0,0 +1,328 @@
+use std::convert::TryFrom;
+use std::error::Error;
+use std::io;
+use std::path::Path;
+
+use zeroize::Zeroize;
+
+use crate::crypto::SecretKey;
+use crate::encoding::{from_hex, to_hex};
+use crate::error::{ArgumentError, CorruptFileError};
+use crate::fragments::{self, Fragment};
+use crate::manifest::{self, hash_file, secret};
+use crate::math::ed25519::{self, KEY_SIZE, SIGNATURE_SIZE};
+use crate::report::Json;
+
+/// A participant of a recovery that signed its transcript.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Signer {
+    /// The holder of the share, or its index if it has no holder.
+    pub participant: String,
+    /// The Ed25519 public key of the signature, in hexadecimal.
+    pub public_key: String,
+}
+
+/// Creates the transcript of a recovery, still without signatures.
+///
+/// The transcript records the encrypted and the decrypted files,
+/// with their sizes and BLAKE3 hashes, the shares used, each with
+/// its index, holder and fingerprint, in the order of their indices,
+/// the time, see manifest::SOURCE_DATE_EPOCH, and the version of
+/// the program. Each participant then signs it with sign, and the
+/// signatures are kept together in the transcript.
+///
+/// # Parameters
+///
+/// - encrypted_file: The path of the encrypted file.
+/// - decrypted_file: The path of the file it was decrypted to.
+/// - fragments: The shares that recovered the key.
+///
+/// # Errors
+///
+/// An io::Error if a file cannot be read.
+pub fn new(
+    encrypted_file: &str,
+    decrypted_file: &str,
+    fragments: &[Fragment],
+) -> Result<Json, Box<dyn Error>> {
+    let created = manifest::created()?;
+    let mut fragments: Vec<&Fragment> = fragments.iter().collect();
+    fragments.sort_by(|a, b| fragments::canonical_order(a, b));
+    let shares = fragments
+        .iter()
+        .map(|fragment| {
+            Json::object(vec![
+                ("index", fragment.share.0.as_str().into()),
+                ("holder", fragment.holder.clone().into()),
+                ("fingerprint", fragment.fingerprint().into()),
+            ])
+        })
+        .collect();
+    let body = Json::object(vec![
+        ("tool", env!("CARGO_PKG_NAME").into()),
+        ("version", env!("CARGO_PKG_VERSION").into()),
+        ("created", Json::Number(created)),
+        ("encrypted_file", file_json(encrypted_file)?),
+        ("decrypted_file", file_json(decrypted_file)?),
+        ("shares", Json::Array(shares)),
+    ]);
+    Ok(Json::object(vec![
+        ("transcript", body),
+        ("signatures", Json::Array(Vec::new())),
+    ]))
+}
+
+/// Returns the participants of a recovery, the holder of each share
+/// used, or its index if it has no holder.
+///
+/// # Parameters
+///
+/// - transcript: The transcript of the recovery.
+///
+/// # Errors
+///
+/// A CorruptFileError if the transcript is not valid.
+pub fn participants(transcript: &Json) -> Result<Vec<String>, CorruptFileError> {
+    let shares = match transcript
+        .get("transcript")
+        .and_then(|body| body.get("shares"))
+    {
+        Some(Json::Array(shares)) => shares,
+        _ => return Err(corrupt()),
+    };
+    shares
+        .iter()
+        .map(|share| {
+            share
+                .get("holder")
+                .and_then(Json::as_str)
+                .or_else(|| share.get("index").and_then(Json::as_str))
+                .map(String::from)
+                .ok_or_else(corrupt)
+        })
+        .collect()
+}
+
+/// Signs a transcript as one of its participants, with Ed25519
+/// over the recorded facts, and adds the signature to it.
+///
+/// # Parameters
+///
+/// - transcript: The transcript of the recovery.
+/// - participant: The holder, or the index of the share, signing.
+/// - key: The key of the participant, an operator key.
+///
+/// # Returns
+///
+/// The transcript with the new signature.
+///
+/// # Errors
+///
+/// An ArgumentError if the participant did not take part in the
+/// recovery or already signed, or a CorruptFileError if the
+/// transcript is not valid.
+pub fn sign(transcript: &Json, participant: &str, key: &SecretKey) -> Result<Json, Box<dyn Error>> {
+    if !participants(transcript)?.iter().any(|p| p == participant) {
+        return Err(Box::new(ArgumentError(format!(
+            "{} did not take part in the recovery",
+            participant
+        ))));
+    }
+    let mut signatures = signatures(transcript)?.clone();
+    if signatures
+        .iter()
+        .any(|signature| signature.get("participant").and_then(Json::as_str) == Some(participant))
+    {
+        return Err(Box::new(ArgumentError(format!(
+            "{} already signed the transcript",
+            participant
+        ))));
+    }
+    let body = transcript.get("transcript").ok_or_else(corrupt)?;
+    let mut secret = secret(key);
+    let public_key = ed25519::public_key(&secret);
+    let signature = ed25519::sign(&secret, &signed_message(body, participant));
+    secret.zeroize();
+    signatures.push(Json::object(vec![
+        ("participant", participant.into()),
+        ("public_key", to_hex(&public_key).into()),
+        ("signature", to_hex(&signature).into()),
+    ]));
+    Ok(Json::object(vec![
+        ("transcript", body.clone()),
+        ("signatures", Json::Array(signatures)),
+    ]))
+}
+
+/// Verifies every signature of a transcript.
+///
+/// # Parameters
+///
+/// - transcript: The transcript of the recovery.
+/// - public_keys: The public key in hexadecimal expected from some
+///   participants, who must have signed with it.
+///
+/// # Returns
+///
+/// The participants that signed the transcript.
+///
+/// # Errors
+///
+/// A CorruptFileError if the transcript or a signature is not
+/// valid, or an ArgumentError if a participant of public_keys did
+/// not sign or signed with another key.
+pub fn verify(
+    transcript: &Json,
+    public_keys: &[(String, String)],
+) -> Result<Vec<Signer>, Box<dyn Error>> {
+    let body = transcript.get("transcript").ok_or_else(corrupt)?;
+    let participants = participants(transcript)?;
+    let mut signers: Vec<Signer> = Vec::new();
+    for signature in signatures(transcript)? {
+        let (participant, public_key, signature) = match (
+            signature.get("participant").and_then(Json::as_str),
+            signature.get("public_key").and_then(Json::as_str),
+            signature.get("signature").and_then(Json::as_str),
+        ) {
+            (Some(participant), Some(public_key), Some(signature)) => {
+                (participant, public_key, signature)
+            }
+            _ => return Err(Box::new(corrupt())),
+        };
+        let key = from_hex(public_key)
+            .and_then(|key| <[u8; KEY_SIZE]>::try_from(key).ok())
+            .ok_or_else(corrupt)?;
+        let signature = from_hex(signature)
+            .and_then(|signature| <[u8; SIGNATURE_SIZE]>::try_from(signature).ok())
+            .ok_or_else(corrupt)?;
+        if !participants.iter().any(|p| p == participant)
+            || signers
+                .iter()
+                .any(|signer| signer.participant == participant)
+            || !ed25519::verify(&key, &signed_message(body, participant), &signature)
+        {
+            return Err(Box::new(CorruptFileError(format!(
+                "the signature of {} is not valid",
+                participant
+            ))));
+        }
+        signers.push(Signer {
+            participant: participant.to_string(),
+            public_key: public_key.to_string(),
+        });
+    }
+    for (participant, public_key) in public_keys {
+        match signers
+            .iter()
+            .find(|signer| &signer.participant == participant)
+        {
+            Some(signer) if signer.public_key.eq_ignore_ascii_case(public_key) => {}
+            Some(signer) => {
+                return Err(Box::new(ArgumentError(format!(
+                    "{} signed with another key: {}",
+                    participant, signer.public_key
+                ))))
+            }
+            None => {
+                return Err(Box::new(ArgumentError(format!(
+                    "{} did not sign the transcript",
+                    participant
+                ))))
+            }
+        }
+    }
+    Ok(signers)
+}
+
+// the message a participant signs, the facts of the recovery and
+// the name they sign as, so a signature cannot be moved to another
+fn signed_message(body: &Json, participant: &str) -> Vec<u8> {
+    Json::object(vec![
+        ("transcript", body.clone()),
+        ("participant", participant.into()),
+    ])
+    .to_string()
+    .into_bytes()
+}
+
+fn signatures(transcript: &Json) -> Result<&Vec<Json>, CorruptFileError> {
+    match transcript.get("signatures") {
+        Some(Json::Array(signatures)) => Ok(signatures),
+        _ => Err(corrupt()),
+    }
+}
+
+// the name, size and BLAKE3 hash of a file
+fn file_json(path: &str) -> io::Result<Json> {
+    let (size, hash) = hash_file(path)?;
+    let name = Path::new(path)
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    Ok(Json::object(vec![
+        ("file", name.into()),
+        ("size", Json::Number(size)),
+        ("blake3", hash.into()),
+    ]))
+}
+
+fn corrupt() -> CorruptFileError {
+    CorruptFileError("not a valid recovery transcript".into())
+}
+
+/////////////////////////////////
+// Unit testing of the module. //
+/////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+
+    #[test]
+    fn sign_and_verify() {
+        let dir = env::temp_dir().join(format!("transcript-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = |name: &str| dir.join(name).to_str().unwrap().to_string();
+        fs::write(path("a.aes"), b"encrypted").unwrap();
+        fs::write(path("a"), b"decrypted").unwrap();
+        let fragments = vec![
+            Fragment::parse("1:abc:holder=alice").unwrap(),
+            Fragment::parse("3:def").unwrap(),
+        ];
+        let transcript = new(&path("a.aes"), &path("a"), &fragments).unwrap();
+        assert_eq!(participants(&transcript).unwrap(), ["alice", "3"]);
+        let body = transcript.get("transcript").unwrap();
+        assert_eq!(
+            body.get("decrypted_file").unwrap().get("size"),
+            Some(&Json::Number(9))
+        );
+        assert!(verify(&transcript, &[]).unwrap().is_empty());
+
+        let alice = SecretKey::new(vec![1u8; KEY_SIZE]);
+        let other = SecretKey::new(vec![2u8; KEY_SIZE]);
+        let alice_public = to_hex(&ed25519::public_key(&secret(&alice)));
+        let signed = sign(&transcript, "alice", &alice).unwrap();
+        let signed = sign(&signed, "3", &other).unwrap();
+        assert!(sign(&signed, "alice", &alice).is_err());
+        assert!(sign(&signed, "bob", &alice).is_err());
+        let signers = verify(&signed, &[("alice".into(), alice_public.clone())]).unwrap();
+        assert_eq!(signers.len(), 2);
+        assert_eq!(signers[0].participant, "alice");
+        assert_eq!(signers[0].public_key, alice_public);
+        assert!(verify(&signed, &[("3".into(), alice_public.clone())]).is_err());
+        assert!(verify(&transcript, &[("alice".into(), alice_public)]).is_err());
+
+        // a transcript changed after it was signed
+        let text = signed.to_string().replace("\"size\":9", "\"size\":8");
+        assert!(verify(&Json::parse(&text).unwrap(), &[]).is_err());
+        // a signature moved to another participant
+        let text =
+            signed
+                .to_string()
+                .replacen("\"participant\":\"alice\"", "\"participant\":\"3\"", 1);
+        assert!(verify(&Json::parse(&text).unwrap(), &[]).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
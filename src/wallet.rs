@@ -0,0 +1,203 @@
+use std::error::Error;
+
+use crate::codex32;
+use crate::crypto::shamir::{self, Curve, Share, SCALAR_SIZE};
+use crate::encoding::{from_hex, to_hex};
+use crate::error::{ArgumentError, CorruptFileError};
+
+/// How the key of a wallet is split and its shares written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// A private key split in the field of the scalars of its curve.
+    Scalar(Curve),
+    /// A BIP-32 master seed split in codex32 strings, see BIP-93.
+    Codex32,
+}
+
+/// Splits the private key of a wallet.
+///
+/// In the Scalar format the key is split in the field of the
+/// scalars of its curve, so every share is a valid key of the curve
+/// too. Keys and shares are written in hexadecimal in the byte order
+/// the tooling of each curve expects: little endian for Ed25519, as
+/// in RFC 8032, and big endian for secp256k1. In the Codex32 format
+/// the key is a master seed and the shares are codex32 strings.
+///
+/// # Parameters
+///
+/// - key: The key in hexadecimal, optionally prefixed by 0x.
+/// - format: How the key is split.
+/// - n: The total number of shares to return.
+/// - k: The minimum number of shares to recover the key.
+///
+/// # Returns
+///
+/// The n shares, as lines `index:scalar` or codex32 strings.
+///
+/// # Errors
+///
+/// An ArgumentError if the key is not valid for the format, or if
+/// k is not in the range 0 < k <= n.
+pub fn split(key: &str, format: Format, n: usize, k: usize) -> Result<Vec<String>, Box<dyn Error>> {
+    if k == 0 || k > n {
+        return Err(Box::new(ArgumentError(
+            "K must be greater than 0 and not greater than N".into(),
+        )));
+    }
+    let key = key.trim();
+    let key = key.strip_prefix("0x").unwrap_or(key);
+    let curve = match format {
+        Format::Scalar(curve) => curve,
+        Format::Codex32 => {
+            let seed = from_hex(key)
+                .ok_or_else(|| ArgumentError("the seed must be in hexadecimal".into()))?;
+            return Ok(codex32::split(&seed, n, k)?);
+        }
+    };
+    let scalar = from_hex(key)
+        .filter(|scalar| scalar.len() == SCALAR_SIZE)
+        .ok_or_else(|| {
+            ArgumentError(format!(
+                "the key must have {} hexadecimal digits",
+                2 * SCALAR_SIZE
+            ))
+        })?;
+    let scalar = to_big_endian(scalar, curve);
+    let shares =
+        shamir::split_scalar(&scalar, curve, n, k).map_err(|e| ArgumentError(e.to_string()))?;
+    Ok(shares
+        .into_iter()
+        .map(|Share(x, y)| {
+            let y = to_big_endian(from_hex(&y).unwrap(), curve);
+            format!("{}:{}", x, to_hex(&y))
+        })
+        .collect())
+}
+
+/// Recovers the private key of a wallet from the shares returned
+/// by split.
+///
+/// # Parameters
+///
+/// - shares: At least k of the shares, in any order.
+/// - format: How the key was split.
+///
+/// # Returns
+///
+/// The key in hexadecimal.
+///
+/// # Errors
+///
+/// A CorruptFileError if a share is malformed, or an ArgumentError
+/// if no share or the same share twice is given.
+pub fn combine(shares: &[&str], format: Format) -> Result<String, Box<dyn Error>> {
+    let curve = match format {
+        Format::Scalar(curve) => curve,
+        Format::Codex32 => return Ok(to_hex(&codex32::combine(shares)?)),
+    };
+    if shares.is_empty() {
+        return Err(Box::new(ArgumentError("no shares were given".into())));
+    }
+    let mut parsed = Vec::with_capacity(shares.len());
+    for share in shares {
+        let corrupt = || CorruptFileError(format!("'{}' is not a share", share.trim()));
+        let (x, y) = share.trim().split_once(':').ok_or_else(corrupt)?;
+        let index: usize = x.parse().map_err(|_| corrupt())?;
+        let scalar = from_hex(y)
+            .filter(|scalar| index > 0 && scalar.len() == SCALAR_SIZE)
+            .ok_or_else(corrupt)?;
+        if parsed
+            .iter()
+            .any(|Share(other, _)| *other == index.to_string())
+        {
+            return Err(Box::new(ArgumentError(format!(
+                "share {} was given twice",
+                index
+            ))));
+        }
+        parsed.push(Share(
+            index.to_string(),
+            to_hex(&to_big_endian(scalar, curve)),
+        ));
+    }
+    let scalar = shamir::recover_scalar(parsed.into_iter(), curve)?;
+    Ok(to_hex(&to_big_endian(scalar, curve)))
+}
+
+// changes the bytes of a scalar between the byte order of the
+// curve and big endian, the same conversion both ways
+fn to_big_endian(mut scalar: Vec<u8>, curve: Curve) -> Vec<u8> {
+    if curve == Curve::Ed25519 {
+        scalar.reverse();
+    }
+    scalar
+}
+
+/////////////////////////////////
+// Unit testing of the module. //
+/////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the Ed25519 scalar 1 and the secp256k1 key 1, in their byte order
+    const ED25519_ONE: &str = "0100000000000000000000000000000000000000000000000000000000000000";
+    const SECP256K1_ONE: &str = "0000000000000000000000000000000000000000000000000000000000000001";
+
+    #[test]
+    fn split_and_combine() {
+        for &(key, curve) in &[
+            (ED25519_ONE, Curve::Ed25519),
+            (SECP256K1_ONE, Curve::Secp256k1),
+        ] {
+            let format = Format::Scalar(curve);
+            let shares = split(key, format, 5, 3).unwrap();
+            assert!(shares[0].starts_with("1:"));
+            let some: Vec<&str> = vec![&shares[4], &shares[0], &shares[2]];
+            assert_eq!(combine(&some, format).unwrap(), key);
+            assert!(combine(&[&shares[0], &shares[0], &shares[1]], format).is_err());
+        }
+        let key = format!("0x{}", SECP256K1_ONE.to_uppercase());
+        let shares = split(&key, Format::Scalar(Curve::Secp256k1), 3, 2).unwrap();
+        let some: Vec<&str> = vec![&shares[1], &shares[2]];
+        assert_eq!(
+            combine(&some, Format::Scalar(Curve::Secp256k1)).unwrap(),
+            SECP256K1_ONE
+        );
+    }
+
+    #[test]
+    fn codex32_seed() {
+        let seed = "0x".to_string() + &"5a".repeat(16);
+        let shares = split(&seed, Format::Codex32, 3, 2).unwrap();
+        assert!(shares[0].starts_with("ms12"));
+        let some: Vec<&str> = vec![&shares[2], &shares[0]];
+        assert_eq!(combine(&some, Format::Codex32).unwrap(), "5a".repeat(16));
+        assert!(split("5a5a", Format::Codex32, 3, 2).is_err());
+    }
+
+    #[test]
+    fn invalid_keys() {
+        // the byte order matters, this is 2^248 for Ed25519
+        assert!(split(SECP256K1_ONE, Format::Scalar(Curve::Ed25519), 3, 2).is_ok());
+        assert!(split(
+            &"ff".repeat(SCALAR_SIZE),
+            Format::Scalar(Curve::Ed25519),
+            3,
+            2
+        )
+        .is_err());
+        assert!(split(
+            &"00".repeat(SCALAR_SIZE),
+            Format::Scalar(Curve::Secp256k1),
+            3,
+            2
+        )
+        .is_err());
+        assert!(split("0102", Format::Scalar(Curve::Secp256k1), 3, 2).is_err());
+        assert!(split(ED25519_ONE, Format::Scalar(Curve::Ed25519), 2, 3).is_err());
+        assert!(combine(&["1:zz"], Format::Scalar(Curve::Ed25519)).is_err());
+        assert!(combine(&[], Format::Scalar(Curve::Ed25519)).is_err());
+    }
+}
@@ -0,0 +1,174 @@
+use std::error::Error;
+use std::io::BufReader;
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::error::ArgumentError;
+use crate::report::{self, Json};
+use crate::service::Message;
+
+// Longest time a webhook is given to connect and to answer
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The webhooks that receive the events of a server or an agent,
+/// such as a recovery, each POSTed to every URL as a JSON object
+/// with its name in "event" and its time in "time".
+///
+/// The connection is plain HTTP, so a webhook outside the computer
+/// should be reached through a proxy with TLS.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Webhooks {
+    targets: Vec<Target>,
+}
+
+// a webhook: the URL, and the address and path it is posted to
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Target {
+    url: String,
+    host: String,
+    path: String,
+}
+
+impl Webhooks {
+    /// Parses the URLs of the webhooks.
+    ///
+    /// # Parameters
+    ///
+    /// - urls: The URLs, like http://host:port/path; the port is 80
+    ///   by default.
+    ///
+    /// # Errors
+    ///
+    /// An ArgumentError if a URL is not an http:// URL.
+    pub fn parse(urls: &[String]) -> Result<Self, ArgumentError> {
+        let targets = urls
+            .iter()
+            .map(|url| {
+                let invalid = || ArgumentError(format!("'{}' is not an http:// URL", url));
+                let rest = url.strip_prefix("http://").ok_or_else(invalid)?;
+                let (host, path) = match rest.find('/') {
+                    Some(i) => (&rest[..i], &rest[i..]),
+                    None => (rest, "/"),
+                };
+                if host.is_empty() || path.contains(char::is_whitespace) {
+                    return Err(invalid());
+                }
+                let host = if host.contains(':') {
+                    host.to_string()
+                } else {
+                    format!("{}:80", host)
+                };
+                Ok(Target {
+                    url: url.clone(),
+                    host,
+                    path: path.into(),
+                })
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Webhooks { targets })
+    }
+
+    /// Returns whether there are no webhooks.
+    pub fn is_empty(&self) -> bool {
+        self.targets.is_empty()
+    }
+
+    /// Sends an event to every webhook, one after the other. A
+    /// webhook that fails is reported in the standard error, but the
+    /// event is still sent to the others.
+    ///
+    /// # Parameters
+    ///
+    /// - event: The name of the event.
+    /// - fields: The facts of the event, which must not be secret.
+    pub fn send(&self, event: &str, fields: Vec<(&str, Json)>) {
+        if self.targets.is_empty() {
+            return;
+        }
+        let time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |time| time.as_secs());
+        let mut body = vec![("event", event.into()), ("time", Json::Number(time))];
+        body.extend(fields);
+        let body = Json::object(body).to_string().into_bytes();
+        for target in &self.targets {
+            if let Err(e) = post(target, &body) {
+                report::warn(format!("the webhook {} failed: {}", target.url, e));
+            }
+        }
+    }
+}
+
+// posts the body of an event to a webhook, which must answer 2xx
+fn post(target: &Target, body: &[u8]) -> Result<(), Box<dyn Error>> {
+    let address = target
+        .host
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| ArgumentError(format!("{} has no address", target.host)))?;
+    let mut stream = TcpStream::connect_timeout(&address, TIMEOUT)?;
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    stream.set_write_timeout(Some(TIMEOUT))?;
+    Message::new(format!("POST {} HTTP/1.1", target.path), body.to_vec())
+        .with_header("host", target.host.as_str())
+        .with_header("content-type", "application/json")
+        .write_to(&mut stream)?;
+    let response = Message::read_from(&mut BufReader::new(stream))?;
+    let status = response.start.split(' ').nth(1).unwrap_or_default();
+    if !status.starts_with('2') {
+        return Err(Box::new(ArgumentError(format!("it answered {}", status))));
+    }
+    Ok(())
+}
+
+/////////////////////////////////
+// Unit testing of the module. //
+/////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+    use std::thread;
+
+    #[test]
+    fn send_events() {
+        let urls = |urls: &[&str]| {
+            Webhooks::parse(&urls.iter().map(|url| url.to_string()).collect::<Vec<_>>())
+        };
+        let webhooks = urls(&["http://example.com", "http://127.0.0.1:8080/hooks/a"]).unwrap();
+        assert_eq!(webhooks.targets[0].host, "example.com:80");
+        assert_eq!(webhooks.targets[0].path, "/");
+        assert_eq!(webhooks.targets[1].host, "127.0.0.1:8080");
+        assert_eq!(webhooks.targets[1].path, "/hooks/a");
+        assert!(urls(&["https://example.com"]).is_err());
+        assert!(urls(&["http:///path"]).is_err());
+        assert!(Webhooks::default().is_empty());
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/events", listener.local_addr().unwrap());
+        let receiver = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let request = Message::read_from(&mut BufReader::new(&stream)).unwrap();
+            Message::new("HTTP/1.1 200 OK", Vec::new())
+                .write_to(&mut &stream)
+                .unwrap();
+            request
+        });
+        urls(&[&url])
+            .unwrap()
+            .send("threshold_reached", vec![("operation", "combine".into())]);
+        let request = receiver.join().unwrap();
+        assert_eq!(request.start, "POST /events HTTP/1.1");
+        let event = Json::parse(std::str::from_utf8(&request.body).unwrap()).unwrap();
+        assert_eq!(
+            event.get("event").and_then(Json::as_str),
+            Some("threshold_reached")
+        );
+        assert_eq!(
+            event.get("operation").and_then(Json::as_str),
+            Some("combine")
+        );
+        assert!(event.get("time").is_some());
+    }
+}